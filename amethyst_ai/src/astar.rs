@@ -0,0 +1,125 @@
+//! The A* search, finding the cheapest paths through graphs.
+
+use std::{
+    cmp::Ordering,
+    collections::{hash_map::Entry, BinaryHeap, HashMap},
+    hash::Hash,
+};
+
+// A node to visit, the one of the lowest estimate coming first out of the heap.
+struct Visit<N> {
+    node: N,
+    cost: f32,
+    estimate: f32,
+}
+
+impl<N> PartialEq for Visit<N> {
+    fn eq(&self, other: &Self) -> bool {
+        self.estimate == other.estimate
+    }
+}
+
+impl<N> Eq for Visit<N> {}
+
+impl<N> PartialOrd for Visit<N> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<N> Ord for Visit<N> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .estimate
+            .partial_cmp(&self.estimate)
+            .unwrap_or(Ordering::Equal)
+    }
+}
+
+/// Finds the cheapest path from `start` to `goal`, both included.
+///
+/// `neighbours` pushes the nodes next to a node with the costs of going to them, which must not
+/// be negative, and `heuristic` estimates the cost of going from a node to the goal. The path is
+/// the cheapest one as long as the heuristic never overestimates the cost.
+pub fn astar<N, F, H>(start: N, goal: N, mut neighbours: F, mut heuristic: H) -> Option<Vec<N>>
+where
+    N: Copy + Eq + Hash,
+    F: FnMut(N, &mut Vec<(N, f32)>),
+    H: FnMut(N) -> f32,
+{
+    // The cheapest cost found to each node, with the node it is reached from.
+    let mut costs = HashMap::new();
+    costs.insert(start, (0.0, None));
+    let mut open = BinaryHeap::new();
+    open.push(Visit {
+        node: start,
+        cost: 0.0,
+        estimate: heuristic(start),
+    });
+    let mut next = Vec::new();
+    while let Some(Visit { node, cost, .. }) = open.pop() {
+        if node == goal {
+            let mut path = vec![goal];
+            let mut current = goal;
+            while let Some(&(_, Some(previous))) = costs.get(&current) {
+                path.push(previous);
+                current = previous;
+            }
+            path.reverse();
+            return Some(path);
+        }
+        if cost > costs[&node].0 {
+            // A cheaper way to the node was visited already.
+            continue;
+        }
+        next.clear();
+        neighbours(node, &mut next);
+        for &(neighbour, step) in &next {
+            let cost = cost + step;
+            match costs.entry(neighbour) {
+                Entry::Occupied(ref entry) if entry.get().0 <= cost => continue,
+                Entry::Occupied(mut entry) => {
+                    entry.insert((cost, Some(node)));
+                }
+                Entry::Vacant(entry) => {
+                    entry.insert((cost, Some(node)));
+                }
+            }
+            open.push(Visit {
+                node: neighbour,
+                cost,
+                estimate: cost + heuristic(neighbour),
+            });
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_cheapest_path_is_found() {
+        // A line of nodes, the direct edge from 0 to 3 costing more than going through 1 and 2.
+        let edges = [
+            (0, 1, 1.0),
+            (1, 2, 1.0),
+            (2, 3, 1.0),
+            (0, 3, 5.0),
+            (3, 4, 1.0),
+        ];
+        let neighbours = |node: u32, next: &mut Vec<(u32, f32)>| {
+            for &(a, b, cost) in &edges {
+                if a == node {
+                    next.push((b, cost));
+                } else if b == node {
+                    next.push((a, cost));
+                }
+            }
+        };
+        assert_eq!(astar(0, 4, neighbours, |_| 0.0), Some(vec![0, 1, 2, 3, 4]));
+        assert_eq!(astar(0, 0, neighbours, |_| 0.0), Some(vec![0]));
+        assert_eq!(astar(0, 5, neighbours, |_| 0.0), None);
+    }
+}
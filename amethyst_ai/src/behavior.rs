@@ -0,0 +1,515 @@
+//! The behavior trees of the agents, loaded from RON.
+//!
+//! A tree is made of the composite nodes, ticking their children in turn, the decorators,
+//! changing what their child gives, and the leaves, waiting or doing the `Action`s of the game:
+//!
+//! ~~~ron
+//! (
+//!     root: Selector([
+//!         Sequence([Action(SeeEnemy), Action(Attack)]),
+//!         Repeat(times: Some(3), child: Sequence([Action(Wander), Wait(1.0)])),
+//!     ]),
+//! )
+//! ~~~
+
+use std::marker::PhantomData;
+
+use serde::{Deserialize, Serialize};
+
+use amethyst_assets::{Asset, AssetStorage, Handle, ProcessingState};
+use amethyst_core::{
+    ecs::prelude::{
+        Component, DenseVecStorage, Entities, Entity, Join, Read, System, SystemData, VecStorage,
+        WriteStorage,
+    },
+    timing::Time,
+};
+use amethyst_error::Error;
+
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+/// What a node of a `BehaviorTree` gives when it is ticked.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Status {
+    /// The node did what it does.
+    Success,
+    /// The node couldn't do what it does.
+    Failure,
+    /// The node isn't done yet, and is ticked again on the next frame.
+    Running,
+}
+
+/// A leaf of a `BehaviorTree` doing what the game needs, as moving or attacking, with the data of
+/// the world it reads and writes.
+///
+/// The actions are loaded with their trees, usually as an enum of the actions of the game, and
+/// are cloned for each entity running the tree, so that they keep their own state while they
+/// run. An action is cloned again from the tree when it starts over.
+pub trait Action<'a>: Clone + Send + Sync + 'static {
+    /// The data of the world the actions use.
+    type SystemData: SystemData<'a>;
+
+    /// Does the action for an entity, until it gives `Status::Success` or `Status::Failure`.
+    fn tick(&mut self, entity: Entity, data: &mut Self::SystemData) -> Status;
+}
+
+/// A node of a `BehaviorTree`, with the actions `A`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Node<A> {
+    /// Ticks its children in order, failing as soon as one fails and succeeding once all of them
+    /// succeeded.
+    Sequence(Vec<Node<A>>),
+    /// Ticks its children in order, succeeding as soon as one succeeds and failing once all of
+    /// them failed.
+    Selector(Vec<Node<A>>),
+    /// Ticks all of its children at each tick, succeeding once enough of them succeeded and
+    /// failing once too many of them failed for that, the others being interrupted.
+    Parallel {
+        /// How many of the children must succeed.
+        successes: usize,
+        /// The children.
+        children: Vec<Node<A>>,
+    },
+    /// Succeeds when its child fails, and fails when it succeeds.
+    Invert(Box<Node<A>>),
+    /// Succeeds when its child is done, even if it failed.
+    Succeed(Box<Node<A>>),
+    /// Runs its child again each time it succeeds, failing as soon as it fails.
+    Repeat {
+        /// How many times the child runs before the node succeeds, or `None` to run it forever.
+        /// With `Some(0)`, the node succeeds without running the child.
+        times: Option<u32>,
+        /// The child.
+        child: Box<Node<A>>,
+    },
+    /// Runs its child again until it fails, then succeeds.
+    UntilFailure(Box<Node<A>>),
+    /// Runs for a number of seconds, then succeeds.
+    Wait(f32),
+    /// An action of the game.
+    Action(A),
+}
+
+/// A behavior tree, the asset of the `Behavior`s of the entities with the actions `A`, loaded
+/// with the `RonFormat`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BehaviorTree<A> {
+    /// The node ticked by the `BehaviorTreeSystem`.
+    pub root: Node<A>,
+}
+
+impl<A> BehaviorTree<A> {
+    /// Creates a tree from its root.
+    pub fn new(root: Node<A>) -> Self {
+        BehaviorTree { root }
+    }
+}
+
+impl<A> Asset for BehaviorTree<A>
+where
+    A: Send + Sync + 'static,
+{
+    const NAME: &'static str = "ai::BehaviorTree";
+    type Data = Self;
+    type HandleStorage = VecStorage<Handle<Self>>;
+}
+
+impl<A> Into<Result<ProcessingState<BehaviorTree<A>>, Error>> for BehaviorTree<A> {
+    fn into(self) -> Result<ProcessingState<BehaviorTree<A>>, Error> {
+        Ok(ProcessingState::Loaded(self))
+    }
+}
+
+/// Runs a `BehaviorTree` for its entity, ticked by the `BehaviorTreeSystem` on every frame.
+///
+/// The tree starts over once its root is done, as the trees usually run for as long as their
+/// entities live.
+pub struct Behavior<A> {
+    tree: Handle<BehaviorTree<A>>,
+    running: Option<Running<A>>,
+    status: Option<Status>,
+}
+
+impl<A> Behavior<A> {
+    /// Creates the behavior of a tree, which starts once the tree is loaded.
+    pub fn new(tree: Handle<BehaviorTree<A>>) -> Self {
+        Behavior {
+            tree,
+            running: None,
+            status: None,
+        }
+    }
+
+    /// The tree.
+    pub fn tree(&self) -> &Handle<BehaviorTree<A>> {
+        &self.tree
+    }
+
+    /// What the root gave on the last tick, if the tree was ticked.
+    pub fn status(&self) -> Option<Status> {
+        self.status
+    }
+
+    /// Interrupts the running nodes, starting the tree over on the next tick.
+    pub fn restart(&mut self) {
+        self.running = None;
+        self.status = None;
+    }
+}
+
+impl<A> Component for Behavior<A>
+where
+    A: Send + Sync + 'static,
+{
+    type Storage = DenseVecStorage<Self>;
+}
+
+// The state of a running node, the actions keeping the ones they were cloned from to start over.
+enum Running<A> {
+    Sequence {
+        children: Vec<Running<A>>,
+        current: usize,
+    },
+    Selector {
+        children: Vec<Running<A>>,
+        current: usize,
+    },
+    Parallel {
+        successes: usize,
+        children: Vec<Running<A>>,
+        done: Vec<Option<Status>>,
+    },
+    Invert(Box<Running<A>>),
+    Succeed(Box<Running<A>>),
+    Repeat {
+        times: Option<u32>,
+        count: u32,
+        child: Box<Running<A>>,
+    },
+    UntilFailure(Box<Running<A>>),
+    Wait {
+        duration: f32,
+        elapsed: f32,
+    },
+    Action {
+        template: A,
+        action: A,
+    },
+}
+
+impl<A: Clone> Running<A> {
+    fn new(node: &Node<A>) -> Self {
+        let all = |nodes: &[Node<A>]| nodes.iter().map(Running::new).collect::<Vec<_>>();
+        match node {
+            Node::Sequence(children) => Running::Sequence {
+                children: all(children),
+                current: 0,
+            },
+            Node::Selector(children) => Running::Selector {
+                children: all(children),
+                current: 0,
+            },
+            Node::Parallel {
+                successes,
+                children,
+            } => Running::Parallel {
+                successes: *successes,
+                children: all(children),
+                done: vec![None; children.len()],
+            },
+            Node::Invert(child) => Running::Invert(Box::new(Running::new(child))),
+            Node::Succeed(child) => Running::Succeed(Box::new(Running::new(child))),
+            Node::Repeat { times, child } => Running::Repeat {
+                times: *times,
+                count: 0,
+                child: Box::new(Running::new(child)),
+            },
+            Node::UntilFailure(child) => Running::UntilFailure(Box::new(Running::new(child))),
+            Node::Wait(duration) => Running::Wait {
+                duration: *duration,
+                elapsed: 0.0,
+            },
+            Node::Action(action) => Running::Action {
+                template: action.clone(),
+                action: action.clone(),
+            },
+        }
+    }
+
+    // Interrupts the node and its children, for them to start over.
+    fn reset(&mut self) {
+        match self {
+            Running::Sequence { children, current } | Running::Selector { children, current } => {
+                children.iter_mut().for_each(Running::reset);
+                *current = 0;
+            }
+            Running::Parallel { children, done, .. } => {
+                children.iter_mut().for_each(Running::reset);
+                done.iter_mut().for_each(|done| *done = None);
+            }
+            Running::Invert(child) | Running::Succeed(child) | Running::UntilFailure(child) => {
+                child.reset()
+            }
+            Running::Repeat { count, child, .. } => {
+                *count = 0;
+                child.reset();
+            }
+            Running::Wait { elapsed, .. } => *elapsed = 0.0,
+            Running::Action { template, action } => *action = template.clone(),
+        }
+    }
+
+    // Ticks the node, which starts over once it is done.
+    fn tick<'a>(&mut self, entity: Entity, delta: f32, data: &mut A::SystemData) -> Status
+    where
+        A: Action<'a>,
+    {
+        let status = match self {
+            Running::Sequence { children, current } => loop {
+                match children.get_mut(*current) {
+                    None => break Status::Success,
+                    Some(child) => match child.tick(entity, delta, data) {
+                        Status::Success => *current += 1,
+                        status => break status,
+                    },
+                }
+            },
+            Running::Selector { children, current } => loop {
+                match children.get_mut(*current) {
+                    None => break Status::Failure,
+                    Some(child) => match child.tick(entity, delta, data) {
+                        Status::Failure => *current += 1,
+                        status => break status,
+                    },
+                }
+            },
+            Running::Parallel {
+                successes,
+                children,
+                done,
+            } => {
+                for (child, done) in children.iter_mut().zip(done.iter_mut()) {
+                    if done.is_none() {
+                        match child.tick(entity, delta, data) {
+                            Status::Running => {}
+                            status => *done = Some(status),
+                        }
+                    }
+                }
+                let succeeded = done.iter().filter(|&&d| d == Some(Status::Success)).count();
+                let failed = done.iter().filter(|&&d| d == Some(Status::Failure)).count();
+                if succeeded >= *successes {
+                    Status::Success
+                } else if failed > children.len().saturating_sub(*successes) {
+                    Status::Failure
+                } else {
+                    Status::Running
+                }
+            }
+            Running::Invert(child) => match child.tick(entity, delta, data) {
+                Status::Success => Status::Failure,
+                Status::Failure => Status::Success,
+                Status::Running => Status::Running,
+            },
+            Running::Succeed(child) => match child.tick(entity, delta, data) {
+                Status::Running => Status::Running,
+                _ => Status::Success,
+            },
+            Running::Repeat { times: Some(0), .. } => Status::Success,
+            Running::Repeat {
+                times,
+                count,
+                child,
+            } => match child.tick(entity, delta, data) {
+                Status::Success => {
+                    *count += 1;
+                    // The child runs again on the next tick, not to loop within a frame.
+                    if Some(*count) == *times {
+                        Status::Success
+                    } else {
+                        Status::Running
+                    }
+                }
+                status => status,
+            },
+            Running::UntilFailure(child) => match child.tick(entity, delta, data) {
+                Status::Failure => Status::Success,
+                _ => Status::Running,
+            },
+            Running::Wait { duration, elapsed } => {
+                *elapsed += delta;
+                if *elapsed >= *duration {
+                    Status::Success
+                } else {
+                    Status::Running
+                }
+            }
+            Running::Action { action, .. } => action.tick(entity, data),
+        };
+        if status != Status::Running {
+            self.reset();
+        }
+        status
+    }
+}
+
+/// Ticks the `Behavior`s with the actions `A` on every frame. The trees are loaded by a
+/// `Processor<BehaviorTree<A>>`, both being added by the `BehaviorTreeBundle`.
+pub struct BehaviorTreeSystem<A> {
+    marker: PhantomData<A>,
+}
+
+impl<A> BehaviorTreeSystem<A> {
+    /// Creates a new `BehaviorTreeSystem`.
+    pub fn new() -> Self {
+        BehaviorTreeSystem {
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<A> Default for BehaviorTreeSystem<A> {
+    fn default() -> Self {
+        BehaviorTreeSystem::new()
+    }
+}
+
+impl<'a, A> System<'a> for BehaviorTreeSystem<A>
+where
+    A: Action<'a>,
+{
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, Time>,
+        Read<'a, AssetStorage<BehaviorTree<A>>>,
+        WriteStorage<'a, Behavior<A>>,
+        A::SystemData,
+    );
+
+    fn run(&mut self, (entities, time, trees, mut behaviors, mut data): Self::SystemData) {
+        #[cfg(feature = "profiler")]
+        profile_scope!("behavior_tree_system");
+
+        let delta = time.delta_seconds();
+        for (entity, behavior) in (&*entities, &mut behaviors).join() {
+            if behavior.running.is_none() {
+                behavior.running = trees
+                    .get(&behavior.tree)
+                    .map(|tree| Running::new(&tree.root));
+            }
+            if let Some(ref mut running) = behavior.running {
+                behavior.status = Some(running.tick(entity, delta, &mut data));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use amethyst_assets::{Loader, RonFormat, SimpleFormat};
+    use amethyst_core::ecs::{Builder, RunNow, World, Write};
+    use rayon::ThreadPoolBuilder;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct Counter(u32);
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    enum TestAction {
+        Count,
+        Fail,
+    }
+
+    impl<'a> Action<'a> for TestAction {
+        type SystemData = Write<'a, Counter>;
+
+        fn tick(&mut self, _: Entity, counter: &mut Self::SystemData) -> Status {
+            match self {
+                TestAction::Count => {
+                    counter.0 += 1;
+                    Status::Success
+                }
+                TestAction::Fail => Status::Failure,
+            }
+        }
+    }
+
+    #[test]
+    fn trees_are_ticked_from_ron() {
+        let ron = "(root: Sequence([Action(Count), Wait(0.5), \
+                   Selector([Action(Fail), Invert(Action(Fail))])]))";
+        let tree: BehaviorTree<TestAction> =
+            SimpleFormat::<BehaviorTree<TestAction>>::import(&RonFormat, ron.into(), ()).unwrap();
+        assert_eq!(
+            tree.root,
+            Node::Sequence(vec![
+                Node::Action(TestAction::Count),
+                Node::Wait(0.5),
+                Node::Selector(vec![
+                    Node::Action(TestAction::Fail),
+                    Node::Invert(Box::new(Node::Action(TestAction::Fail))),
+                ]),
+            ])
+        );
+
+        let mut world = World::new();
+        let mut system = BehaviorTreeSystem::<TestAction>::new();
+        System::setup(&mut system, &mut world.res);
+        world.write_resource::<Time>().set_delta_seconds(0.3);
+        let pool = Arc::new(ThreadPoolBuilder::new().build().unwrap());
+        let handle = {
+            let loader = Loader::new(".", pool.clone());
+            let trees = world.read_resource::<AssetStorage<BehaviorTree<TestAction>>>();
+            loader.load_from_data(tree, (), &*trees)
+        };
+        world
+            .write_resource::<AssetStorage<BehaviorTree<TestAction>>>()
+            .process(Into::into, 0, &pool, None);
+        let agent = world.create_entity().with(Behavior::new(handle)).build();
+
+        let mut statuses = Vec::new();
+        for _ in 0..3 {
+            system.run_now(&world.res);
+            statuses.push(
+                world
+                    .read_storage::<Behavior<TestAction>>()
+                    .get(agent)
+                    .unwrap()
+                    .status(),
+            );
+        }
+        assert_eq!(
+            statuses,
+            vec![
+                Some(Status::Running),
+                Some(Status::Success),
+                Some(Status::Running)
+            ]
+        );
+        assert_eq!(world.read_resource::<Counter>().0, 2);
+    }
+
+    #[test]
+    fn repeating_zero_times_succeeds_right_away() {
+        let mut world = World::new();
+        world.add_resource(Counter::default());
+        let agent = world.create_entity().build();
+        let mut running = Running::new(&Node::Sequence(vec![
+            Node::Repeat {
+                times: Some(0),
+                child: Box::new(Node::Action(TestAction::Count)),
+            },
+            Node::Repeat {
+                times: Some(1),
+                child: Box::new(Node::Action(TestAction::Count)),
+            },
+        ]));
+        let mut counter = Write::<'_, Counter>::fetch(&world.res);
+        assert_eq!(running.tick(agent, 0.1, &mut counter), Status::Success);
+        assert_eq!(counter.0, 1);
+    }
+}
@@ -0,0 +1,125 @@
+//! The bundles of the agents.
+
+use std::marker::PhantomData;
+
+use amethyst_assets::Processor;
+use amethyst_core::bundle::{BundleBuilder, SystemBundle};
+use amethyst_error::Error;
+
+use crate::{
+    behavior::{Action, BehaviorTree, BehaviorTreeSystem},
+    pathfinding::PathfindingSystem,
+    steering::{AgentMovementSystem, SteeringSystem},
+};
+
+/// Adds the `PathfindingSystem`, finding the `Path`s of the `PathRequest`s, the `SteeringSystem`
+/// and the `AgentMovementSystem`, moving the `Agent`s. The bundle should be added before the
+/// `TransformBundle`, for the agents to be drawn where they moved to. The
+/// `NavigationDebugSystem` isn't added, as it is only drawn while debugging.
+#[derive(Debug, Default)]
+pub struct AiBundle;
+
+impl AiBundle {
+    /// Creates the bundle.
+    pub fn new() -> Self {
+        AiBundle
+    }
+}
+
+impl<'a, 'b> SystemBundle<'a, 'b> for AiBundle {
+    fn build(self, builder: &mut BundleBuilder<'a, 'b>) -> Result<(), Error> {
+        builder.add(PathfindingSystem::new(), "pathfinding", &[]);
+        builder.add(SteeringSystem::new(), "steering", &["pathfinding"]);
+        builder.add(AgentMovementSystem::new(), "agent_movement", &["steering"]);
+        Ok(())
+    }
+}
+
+/// Adds the `BehaviorTreeSystem` of the actions `A`, with the `Processor` of their
+/// `BehaviorTree`s, with the given names. The trees of each type of actions have their own
+/// bundle, and so their own names.
+#[derive(Debug)]
+pub struct BehaviorTreeBundle<'a, A> {
+    name: &'a str,
+    processor_name: &'a str,
+    marker: PhantomData<A>,
+}
+
+impl<'a, A> BehaviorTreeBundle<'a, A> {
+    /// Creates the bundle.
+    ///
+    /// ### Parameters:
+    ///
+    /// - `name`: name of the `BehaviorTreeSystem`
+    /// - `processor_name`: name of the `Processor` of the trees
+    pub fn new(name: &'a str, processor_name: &'a str) -> Self {
+        BehaviorTreeBundle {
+            name,
+            processor_name,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, 'b, 'c, A> SystemBundle<'a, 'b> for BehaviorTreeBundle<'c, A>
+where
+    A: for<'d> Action<'d>,
+{
+    fn build(self, builder: &mut BundleBuilder<'a, 'b>) -> Result<(), Error> {
+        builder.add(
+            Processor::<BehaviorTree<A>>::new(),
+            self.processor_name,
+            &[],
+        );
+        builder.add(
+            BehaviorTreeSystem::<A>::new(),
+            self.name,
+            &[self.processor_name],
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::ecs::prelude::{DispatcherBuilder, Entity};
+
+    use super::*;
+    use crate::behavior::Status;
+
+    #[derive(Clone)]
+    struct Idle;
+
+    impl<'a> Action<'a> for Idle {
+        type SystemData = ();
+
+        fn tick(&mut self, _: Entity, _: &mut Self::SystemData) -> Status {
+            Status::Success
+        }
+    }
+
+    #[derive(Clone)]
+    struct Flee;
+
+    impl<'a> Action<'a> for Flee {
+        type SystemData = ();
+
+        fn tick(&mut self, _: Entity, _: &mut Self::SystemData) -> Status {
+            Status::Running
+        }
+    }
+
+    #[test]
+    fn trees_of_different_actions_are_added_side_by_side() {
+        let mut builder = BundleBuilder::with_systems(DispatcherBuilder::new(), Vec::new());
+        BehaviorTreeBundle::<Idle>::new("idle_tree", "idle_tree_processor")
+            .build(&mut builder)
+            .unwrap();
+        BehaviorTreeBundle::<Flee>::new("flee_tree", "flee_tree_processor")
+            .build(&mut builder)
+            .unwrap();
+        assert!(builder.duplicates().is_empty());
+        assert!(builder.missing().is_empty());
+        assert_eq!(builder.systems().len(), 4);
+    }
+}
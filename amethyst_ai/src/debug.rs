@@ -0,0 +1,59 @@
+//! The debug drawing of the navigation.
+
+use amethyst_core::{
+    ecs::prelude::{Join, Read, ReadStorage, System, Write},
+    math::Point3,
+    GlobalTransform,
+};
+use amethyst_renderer::{DebugLines, Rgba};
+
+use crate::{navigation::NavigationWorld, pathfinding::Path, steering::Agent};
+
+/// Draws the `NavigationWorld`, the `Path`s and the `Agent`s through the `DebugLines` resource,
+/// for the `DrawDebugLines` pass. It should run on every frame:
+///
+/// ~~~ignore
+/// let game_data = GameDataBuilder::default()
+///     .with(NavigationDebugSystem, "navigation_debug", &["pathfinding"]);
+/// ~~~
+///
+/// The edges of the surface are gray, the rest of the paths green, from the entities to their
+/// next points, and the desired velocities of the agents blue, as where they go within a second.
+#[derive(Debug, Default)]
+pub struct NavigationDebugSystem;
+
+impl<'a> System<'a> for NavigationDebugSystem {
+    type SystemData = (
+        Read<'a, NavigationWorld>,
+        ReadStorage<'a, Path>,
+        ReadStorage<'a, Agent>,
+        ReadStorage<'a, GlobalTransform>,
+        Write<'a, DebugLines>,
+    );
+
+    fn run(&mut self, (navigation, paths, agents, globals, mut lines): Self::SystemData) {
+        if let Some(navigation) = navigation.navigation() {
+            for [start, end] in navigation.debug_edges() {
+                lines.draw_line(start, end, Rgba(0.5, 0.5, 0.5, 1.0));
+            }
+        }
+        let green = Rgba(0.0, 1.0, 0.0, 1.0);
+        for (path, global) in (&paths, globals.maybe()).join() {
+            let next = match path.next_point() {
+                Some(next) => next,
+                None => continue,
+            };
+            if let Some(global) = global {
+                let position = Point3::new(global.0[(0, 3)], global.0[(1, 3)], global.0[(2, 3)]);
+                lines.draw_line(position, next, green);
+            }
+            for pair in path.points[path.next..].windows(2) {
+                lines.draw_line(pair[0], pair[1], green);
+            }
+        }
+        for (agent, global) in (&agents, &globals).join() {
+            let position = Point3::new(global.0[(0, 3)], global.0[(1, 3)], global.0[(2, 3)]);
+            lines.draw_direction(position, agent.desired_velocity, Rgba(0.0, 0.5, 1.0, 1.0));
+        }
+    }
+}
@@ -0,0 +1,245 @@
+//! The navigation grids, the walkable cells of the tile maps.
+
+use serde::{Deserialize, Serialize};
+
+use amethyst_core::math::{Point3, Vector3};
+
+use crate::{astar::astar, navigation::Navigation};
+
+/// A grid of cells the agents walk on, going from cell to cell in the eight directions.
+///
+/// The columns go right along X and the rows go down along Y from the top left corner of the
+/// grid, as the tiles of an orthogonal `TileMap`. The agents walk between the blocked cells
+/// without cutting their corners, each cell having a cost which multiplies the distances walked
+/// into it, as for the slower grounds.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct NavGrid {
+    /// The position of the top left corner of the grid.
+    pub origin: Point3<f32>,
+    /// The width and the height of the cells.
+    pub cell_size: [f32; 2],
+    width: u32,
+    height: u32,
+    costs: Vec<Option<f32>>,
+}
+
+impl NavGrid {
+    /// Creates a grid of walkable cells, whose costs are 1.
+    pub fn new(width: u32, height: u32, cell_size: [f32; 2]) -> Self {
+        NavGrid {
+            origin: Point3::origin(),
+            cell_size,
+            width,
+            height,
+            costs: vec![Some(1.0); (width * height) as usize],
+        }
+    }
+
+    /// Creates the grid of the tiles of an orthogonal tile map, relative to the map.
+    ///
+    /// The tiles of its layers whose `COLLISION_PROPERTY` is set are blocked.
+    #[cfg(feature = "tiles")]
+    pub fn from_tile_map(map: &amethyst_tiles::TileMap) -> Self {
+        use amethyst_tiles::COLLISION_PROPERTY;
+
+        let mut grid = NavGrid::new(map.width(), map.height(), map.tile_size);
+        for layer in map.layers() {
+            let solid = layer
+                .properties
+                .get(COLLISION_PROPERTY)
+                .and_then(|value| value.as_bool())
+                .unwrap_or(false);
+            if solid {
+                for (x, y, _) in layer.tiles() {
+                    grid.set_cost(x, y, None);
+                }
+            }
+        }
+        grid
+    }
+
+    /// The number of columns.
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// The number of rows.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// The cost of walking into a cell, or `None` if it is blocked or out of the grid.
+    pub fn cost(&self, x: u32, y: u32) -> Option<f32> {
+        if x < self.width && y < self.height {
+            self.costs[(y * self.width + x) as usize]
+        } else {
+            None
+        }
+    }
+
+    /// Sets the cost of walking into a cell, `None` blocking it.
+    pub fn set_cost(&mut self, x: u32, y: u32, cost: Option<f32>) {
+        if x < self.width && y < self.height {
+            self.costs[(y * self.width + x) as usize] = cost;
+        }
+    }
+
+    /// Whether a cell is walkable.
+    pub fn is_walkable(&self, x: u32, y: u32) -> bool {
+        self.cost(x, y).is_some()
+    }
+
+    /// The position of the center of a cell.
+    pub fn cell_position(&self, x: u32, y: u32) -> Point3<f32> {
+        self.origin
+            + Vector3::new(
+                (x as f32 + 0.5) * self.cell_size[0],
+                -(y as f32 + 0.5) * self.cell_size[1],
+                0.0,
+            )
+    }
+
+    /// The column and the row of the cell at a position, if it is on the grid.
+    pub fn cell_at(&self, position: Point3<f32>) -> Option<(u32, u32)> {
+        let x = ((position.x - self.origin.x) / self.cell_size[0]).floor();
+        let y = ((self.origin.y - position.y) / self.cell_size[1]).floor();
+        if x >= 0.0 && y >= 0.0 && x < self.width as f32 && y < self.height as f32 {
+            Some((x as u32, y as u32))
+        } else {
+            None
+        }
+    }
+
+    /// Finds the cheapest path from a point to another, going through the centers of the cells
+    /// between the cells of the points.
+    ///
+    /// The path only keeps the centers where it turns. It is `None` if either point is off the
+    /// grid or blocked, or if there is no way between them.
+    pub fn find_path(&self, from: Point3<f32>, to: Point3<f32>) -> Option<Vec<Point3<f32>>> {
+        let start = self
+            .cell_at(from)
+            .filter(|&(x, y)| self.is_walkable(x, y))?;
+        let goal = self.cell_at(to).filter(|&(x, y)| self.is_walkable(x, y))?;
+        // The heuristic must not overestimate, the cheapest cell bounding the cost of a step.
+        let cheapest = self
+            .costs
+            .iter()
+            .filter_map(|&cost| cost)
+            .fold(std::f32::INFINITY, f32::min);
+        let [w, h] = self.cell_size;
+        let cells = astar(
+            start,
+            goal,
+            |(x, y), next| {
+                for dy in -1i64..=1 {
+                    for dx in -1i64..=1 {
+                        let (nx, ny) = (x as i64 + dx, y as i64 + dy);
+                        if (dx == 0 && dy == 0) || nx < 0 || ny < 0 {
+                            continue;
+                        }
+                        let (nx, ny) = (nx as u32, ny as u32);
+                        let cost = match self.cost(nx, ny) {
+                            Some(cost) => cost,
+                            None => continue,
+                        };
+                        // The diagonals don't cut the corners of the blocked cells.
+                        if dx != 0
+                            && dy != 0
+                            && !(self.is_walkable(nx, y) && self.is_walkable(x, ny))
+                        {
+                            continue;
+                        }
+                        let distance = ((dx as f32 * w).powi(2) + (dy as f32 * h).powi(2)).sqrt();
+                        next.push(((nx, ny), distance * cost));
+                    }
+                }
+            },
+            |(x, y)| {
+                let (dx, dy) = (
+                    (goal.0 as f32 - x as f32).abs() * w,
+                    (goal.1 as f32 - y as f32).abs() * h,
+                );
+                cheapest * (dx * dx + dy * dy).sqrt()
+            },
+        )?;
+
+        let mut path = vec![from];
+        for (i, &(x, y)) in cells
+            .iter()
+            .enumerate()
+            .skip(1)
+            .take(cells.len().saturating_sub(2))
+        {
+            let (px, py) = cells[i - 1];
+            let (nx, ny) = cells[i + 1];
+            let turns = (x as i64 - px as i64, y as i64 - py as i64)
+                != (nx as i64 - x as i64, ny as i64 - y as i64);
+            if turns {
+                path.push(self.cell_position(x, y));
+            }
+        }
+        path.push(to);
+        Some(path)
+    }
+}
+
+impl Navigation for NavGrid {
+    fn find_path(&self, from: Point3<f32>, to: Point3<f32>) -> Option<Vec<Point3<f32>>> {
+        NavGrid::find_path(self, from, to)
+    }
+
+    fn debug_edges(&self) -> Vec<[Point3<f32>; 2]> {
+        // The outlines of the blocked cells.
+        let [w, h] = self.cell_size;
+        let mut edges = Vec::new();
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if self.is_walkable(x, y) {
+                    continue;
+                }
+                let center = self.cell_position(x, y);
+                let corners = [
+                    center + Vector3::new(-w / 2.0, h / 2.0, 0.0),
+                    center + Vector3::new(w / 2.0, h / 2.0, 0.0),
+                    center + Vector3::new(w / 2.0, -h / 2.0, 0.0),
+                    center + Vector3::new(-w / 2.0, -h / 2.0, 0.0),
+                ];
+                for i in 0..4 {
+                    edges.push([corners[i], corners[(i + 1) % 4]]);
+                }
+            }
+        }
+        edges
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths_go_around_the_blocked_cells() {
+        // A wall down the middle column, open in the bottom row.
+        let mut grid = NavGrid::new(3, 3, [1.0, 1.0]);
+        grid.set_cost(1, 0, None);
+        grid.set_cost(1, 1, None);
+        assert_eq!(grid.cell_at(Point3::new(0.5, -2.5, 0.0)), Some((0, 2)));
+        assert_eq!(grid.cell_position(2, 1), Point3::new(2.5, -1.5, 0.0));
+
+        let (from, to) = (Point3::new(0.5, -0.5, 0.0), Point3::new(2.5, -0.5, 0.0));
+        let path = grid.find_path(from, to).unwrap();
+        // Down the left column, across the bottom row without cutting the wall, and up.
+        assert_eq!(
+            path,
+            vec![
+                from,
+                Point3::new(0.5, -2.5, 0.0),
+                Point3::new(2.5, -2.5, 0.0),
+                to,
+            ]
+        );
+
+        grid.set_cost(1, 2, None);
+        assert_eq!(grid.find_path(from, to), None);
+    }
+}
@@ -0,0 +1,37 @@
+//! AI for Amethyst, with the navigation of the agents.
+//!
+//! The agents walk on the `NavigationWorld`, a `NavMesh` baked from the geometry of a level or a
+//! `NavGrid` of cells, as the tiles of a tile map under the `tiles` feature. The agents ask for
+//! their paths with `PathRequest`s, which the `PathfindingSystem` finds with A* in the thread
+//! pool, giving the agents their `Path`s.
+//!
+//! The `Agent`s go by their `Steering`, seeking or arriving at points or following their paths,
+//! the `SteeringSystem` writing their desired velocities and turning them aside from each other
+//! with their `Avoidance`.
+//!
+//! The decisions of the agents are `BehaviorTree`s of the `Action`s of the game, loaded from RON
+//! and ticked for each `Behavior` by the `BehaviorTreeSystem`.
+
+#![warn(missing_docs, rust_2018_idioms, rust_2018_compatibility)]
+
+pub use self::{
+    astar::astar,
+    behavior::{Action, Behavior, BehaviorTree, BehaviorTreeSystem, Node, Status},
+    bundle::{AiBundle, BehaviorTreeBundle},
+    debug::NavigationDebugSystem,
+    grid::NavGrid,
+    navigation::{Navigation, NavigationWorld},
+    navmesh::{NavMesh, NavMeshSettings},
+    pathfinding::{Path, PathEvent, PathRequest, PathfindingSystem},
+    steering::{Agent, AgentMovementSystem, Avoidance, Steering, SteeringSystem},
+};
+
+mod astar;
+mod behavior;
+mod bundle;
+mod debug;
+mod grid;
+mod navigation;
+mod navmesh;
+mod pathfinding;
+mod steering;
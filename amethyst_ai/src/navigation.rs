@@ -0,0 +1,54 @@
+//! The surfaces the agents find their paths on.
+
+use std::sync::Arc;
+
+use amethyst_core::math::Point3;
+
+/// A surface the agents walk on, as a `NavMesh` or a `NavGrid`, finding the paths between its
+/// points.
+pub trait Navigation: Send + Sync {
+    /// Finds a path from a point to another, both included, or `None` if there is no way.
+    fn find_path(&self, from: Point3<f32>, to: Point3<f32>) -> Option<Vec<Point3<f32>>>;
+
+    /// The edges of the surface drawn by the `NavigationDebugSystem`.
+    fn debug_edges(&self) -> Vec<[Point3<f32>; 2]> {
+        Vec::new()
+    }
+}
+
+/// The surface of the level, on which the `PathfindingSystem` finds the paths of the agents.
+///
+/// It is shared with the paths being found, the paths found on the previous surface still being
+/// given once it is replaced.
+#[derive(Clone, Default)]
+pub struct NavigationWorld {
+    navigation: Option<Arc<dyn Navigation>>,
+}
+
+impl NavigationWorld {
+    /// Creates the navigation of a surface.
+    pub fn new<N: Navigation + 'static>(navigation: N) -> Self {
+        NavigationWorld {
+            navigation: Some(Arc::new(navigation)),
+        }
+    }
+
+    /// Replaces the surface, as when a level is loaded.
+    pub fn set<N: Navigation + 'static>(&mut self, navigation: N) {
+        self.navigation = Some(Arc::new(navigation));
+    }
+
+    /// Removes the surface, no paths being found until one is set.
+    pub fn clear(&mut self) {
+        self.navigation = None;
+    }
+
+    /// The surface, if there is one.
+    pub fn navigation(&self) -> Option<&dyn Navigation> {
+        self.navigation.as_ref().map(|navigation| &**navigation)
+    }
+
+    pub(crate) fn shared(&self) -> Option<Arc<dyn Navigation>> {
+        self.navigation.clone()
+    }
+}
@@ -0,0 +1,382 @@
+//! The navigation meshes, the walkable triangles of the levels.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use amethyst_core::math::{Point3, Vector2, Vector3};
+
+use crate::{astar::astar, navigation::Navigation};
+
+/// How a `NavMesh` is baked from the geometry of a level.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct NavMeshSettings {
+    /// The up direction of the level.
+    pub up: Vector3<f32>,
+    /// The steepest slope the agents walk on, in radians.
+    pub max_slope: f32,
+    /// The distance under which the vertices are welded together, joining the triangles of the
+    /// separate meshes of the level.
+    pub weld_distance: f32,
+}
+
+impl Default for NavMeshSettings {
+    fn default() -> Self {
+        NavMeshSettings {
+            up: Vector3::y(),
+            max_slope: std::f32::consts::FRAC_PI_4,
+            weld_distance: 0.01,
+        }
+    }
+}
+
+/// A navigation mesh, the triangles the agents walk on, joined by their shared edges.
+///
+/// The paths go from triangle to triangle through the shared edges, and are then pulled tight
+/// around the corners they go by.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct NavMesh {
+    vertices: Vec<Point3<f32>>,
+    triangles: Vec<[usize; 3]>,
+    up: Vector3<f32>,
+    // The triangle across each edge of a triangle, the edge `i` going from its vertex `i` to the
+    // next one.
+    neighbours: Vec<[Option<usize>; 3]>,
+}
+
+impl NavMesh {
+    /// Creates a mesh of walkable triangles, the triangles sharing the vertices of their shared
+    /// edges.
+    pub fn new(vertices: Vec<Point3<f32>>, triangles: Vec<[usize; 3]>, up: Vector3<f32>) -> Self {
+        let mut edges = HashMap::new();
+        let mut neighbours = vec![[None; 3]; triangles.len()];
+        for (t, triangle) in triangles.iter().enumerate() {
+            for i in 0..3 {
+                let (a, b) = (triangle[i], triangle[(i + 1) % 3]);
+                match edges.remove(&(a.min(b), a.max(b))) {
+                    Some((other, j)) => {
+                        neighbours[t][i] = Some(other);
+                        neighbours[other][j] = Some(t);
+                    }
+                    None => {
+                        edges.insert((a.min(b), a.max(b)), (t, i));
+                    }
+                }
+            }
+        }
+        NavMesh {
+            vertices,
+            triangles,
+            up: up.normalize(),
+            neighbours,
+        }
+    }
+
+    /// Bakes the mesh of the walkable triangles of the geometry of a level, as the vertices and
+    /// the triangles of its meshes in world space.
+    ///
+    /// The triangles kept are those whose front faces, going counterclockwise, face up no steeper
+    /// than the max slope. The vertices closer than the weld distance are merged, so that the
+    /// triangles of the separate meshes of a level join.
+    pub fn bake(
+        vertices: &[Point3<f32>],
+        triangles: &[[usize; 3]],
+        settings: &NavMeshSettings,
+    ) -> Self {
+        let up = settings.up.normalize();
+        let min_cos = settings.max_slope.cos();
+        let weld = settings.weld_distance.max(std::f32::EPSILON);
+        let mut welded = HashMap::new();
+        let mut baked_vertices = Vec::new();
+        let mut baked_triangles = Vec::new();
+        for triangle in triangles {
+            let (a, b, c) = (
+                vertices[triangle[0]],
+                vertices[triangle[1]],
+                vertices[triangle[2]],
+            );
+            let normal = (b - a).cross(&(c - a));
+            let area = normal.norm();
+            if area <= std::f32::EPSILON || normal.dot(&up) / area < min_cos {
+                continue;
+            }
+            let mut indices = [0; 3];
+            for (index, &vertex) in indices.iter_mut().zip(triangle) {
+                let position = vertices[vertex];
+                let cell = (
+                    (position.x / weld).round() as i64,
+                    (position.y / weld).round() as i64,
+                    (position.z / weld).round() as i64,
+                );
+                *index = *welded.entry(cell).or_insert_with(|| {
+                    baked_vertices.push(position);
+                    baked_vertices.len() - 1
+                });
+            }
+            if indices[0] != indices[1] && indices[1] != indices[2] && indices[2] != indices[0] {
+                baked_triangles.push(indices);
+            }
+        }
+        NavMesh::new(baked_vertices, baked_triangles, up)
+    }
+
+    /// The vertices of the triangles.
+    pub fn vertices(&self) -> &[Point3<f32>] {
+        &self.vertices
+    }
+
+    /// The triangles, as the indices of their vertices.
+    pub fn triangles(&self) -> &[[usize; 3]] {
+        &self.triangles
+    }
+
+    /// The triangle closest to a point, with the point of the triangle closest to it, if the
+    /// mesh has triangles.
+    pub fn closest_point(&self, point: Point3<f32>) -> Option<(usize, Point3<f32>)> {
+        self.triangles
+            .iter()
+            .enumerate()
+            .map(|(t, triangle)| {
+                let [a, b, c] = self.corners(triangle);
+                (t, closest_point(a, b, c, point))
+            })
+            .min_by(|&(_, a), &(_, b)| {
+                let (a, b) = ((a - point).norm_squared(), (b - point).norm_squared());
+                a.partial_cmp(&b).unwrap_or(std::cmp::Ordering::Equal)
+            })
+    }
+
+    /// Finds the shortest path from a point to another, going through the triangles between
+    /// the closest points of the mesh.
+    ///
+    /// The path is the corridor of the triangles found with A* from the centers of the
+    /// triangles, pulled tight so that it only turns at the corners of the corridor.
+    pub fn find_path(&self, from: Point3<f32>, to: Point3<f32>) -> Option<Vec<Point3<f32>>> {
+        let (start, from) = self.closest_point(from)?;
+        let (goal, to) = self.closest_point(to)?;
+        let centers = |t: usize| {
+            let [a, b, c] = self.corners(&self.triangles[t]);
+            Point3::from((a.coords + b.coords + c.coords) / 3.0)
+        };
+        let goal_center = centers(goal);
+        let corridor = astar(
+            start,
+            goal,
+            |t, next| {
+                for neighbour in self.neighbours[t].iter().filter_map(|&n| n) {
+                    next.push((neighbour, (centers(neighbour) - centers(t)).norm()));
+                }
+            },
+            |t| (goal_center - centers(t)).norm(),
+        )?;
+
+        // The portals are the shared edges the corridor goes through, as their left and right
+        // sides looking through them.
+        let flat = self.flattening();
+        let mut portals = vec![(from, from)];
+        for pair in corridor.windows(2) {
+            let triangle = &self.triangles[pair[0]];
+            let edge = (0..3)
+                .find(|&i| self.neighbours[pair[0]][i] == Some(pair[1]))
+                .expect("the triangles of the corridor are neighbours");
+            let (a, b) = (
+                self.vertices[triangle[edge]],
+                self.vertices[triangle[(edge + 1) % 3]],
+            );
+            let center = flat(centers(pair[0]));
+            let direction = (flat(a) + flat(b)) / 2.0 - center;
+            if cross(direction, flat(a) - center) > 0.0 {
+                portals.push((a, b));
+            } else {
+                portals.push((b, a));
+            }
+        }
+        portals.push((to, to));
+        Some(funnel(&portals, flat))
+    }
+
+    fn corners(&self, triangle: &[usize; 3]) -> [Point3<f32>; 3] {
+        [
+            self.vertices[triangle[0]],
+            self.vertices[triangle[1]],
+            self.vertices[triangle[2]],
+        ]
+    }
+
+    // The projection of the points on the ground, the turns counterclockwise seen from above
+    // being positive.
+    fn flattening(&self) -> impl Fn(Point3<f32>) -> Vector2<f32> {
+        let up = self.up;
+        let other = if up.x.abs() < 0.9 {
+            Vector3::x()
+        } else {
+            Vector3::y()
+        };
+        let u = up.cross(&other).normalize();
+        let v = up.cross(&u);
+        move |point| Vector2::new(point.coords.dot(&u), point.coords.dot(&v))
+    }
+}
+
+impl Navigation for NavMesh {
+    fn find_path(&self, from: Point3<f32>, to: Point3<f32>) -> Option<Vec<Point3<f32>>> {
+        NavMesh::find_path(self, from, to)
+    }
+
+    fn debug_edges(&self) -> Vec<[Point3<f32>; 2]> {
+        let mut edges = Vec::new();
+        for (t, triangle) in self.triangles.iter().enumerate() {
+            for i in 0..3 {
+                // The shared edges are drawn once, by the first of their triangles.
+                if self.neighbours[t][i].map_or(true, |neighbour| neighbour > t) {
+                    edges.push([
+                        self.vertices[triangle[i]],
+                        self.vertices[triangle[(i + 1) % 3]],
+                    ]);
+                }
+            }
+        }
+        edges
+    }
+}
+
+fn cross(a: Vector2<f32>, b: Vector2<f32>) -> f32 {
+    a.x * b.y - a.y * b.x
+}
+
+// Pulls the path through the portals tight, the funnel of the sides of the portals seen from the
+// last corner narrowing until one side crosses the other, whose point is the next corner.
+fn funnel<F>(portals: &[(Point3<f32>, Point3<f32>)], flat: F) -> Vec<Point3<f32>>
+where
+    F: Fn(Point3<f32>) -> Vector2<f32>,
+{
+    let mut path = vec![portals[0].0];
+    let (mut apex, mut left, mut right) = (portals[0].0, portals[0].0, portals[0].1);
+    let (mut left_index, mut right_index) = (0, 0);
+    let mut i = 1;
+    while i < portals.len() {
+        let (next_left, next_right) = portals[i];
+        let side =
+            |a: Point3<f32>, b: Point3<f32>| cross(flat(a) - flat(apex), flat(b) - flat(apex));
+
+        // The right side narrows the funnel when it turns left.
+        if side(right, next_right) >= 0.0 {
+            if apex == right || side(left, next_right) < 0.0 {
+                right = next_right;
+                right_index = i;
+            } else {
+                // The right side crosses the left one, which is the next corner.
+                apex = left;
+                path.push(apex);
+                right = apex;
+                right_index = left_index;
+                i = left_index + 1;
+                continue;
+            }
+        }
+
+        // The left side narrows the funnel when it turns right.
+        if side(left, next_left) <= 0.0 {
+            if apex == left || side(right, next_left) > 0.0 {
+                left = next_left;
+                left_index = i;
+            } else {
+                apex = right;
+                path.push(apex);
+                left = apex;
+                left_index = right_index;
+                i = right_index + 1;
+                continue;
+            }
+        }
+        i += 1;
+    }
+    let end = portals[portals.len() - 1].0;
+    if path.last() != Some(&end) {
+        path.push(end);
+    }
+    path
+}
+
+// The point of the triangle `a b c` closest to `point`.
+fn closest_point(
+    a: Point3<f32>,
+    b: Point3<f32>,
+    c: Point3<f32>,
+    point: Point3<f32>,
+) -> Point3<f32> {
+    let normal = (b - a).cross(&(c - a));
+    let projected = point - normal * ((point - a).dot(&normal) / normal.norm_squared());
+    let inside = [(a, b), (b, c), (c, a)]
+        .iter()
+        .all(|&(u, v)| (v - u).cross(&(projected - u)).dot(&normal) >= 0.0);
+    if inside {
+        return projected;
+    }
+    [(a, b), (b, c), (c, a)]
+        .iter()
+        .map(|&(u, v)| {
+            let t = ((point - u).dot(&(v - u)) / (v - u).norm_squared())
+                .max(0.0)
+                .min(1.0);
+            u + (v - u) * t
+        })
+        .min_by(|a, b| {
+            let (a, b) = ((a - point).norm_squared(), (b - point).norm_squared());
+            a.partial_cmp(&b).unwrap_or(std::cmp::Ordering::Equal)
+        })
+        .expect("a triangle has edges")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths_go_around_the_corners() {
+        // An L of three squares of the ground, and a wall which isn't walkable.
+        let vertices = vec![
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(1.0, 0.0, 0.0),
+            Point3::new(2.0, 0.0, 0.0),
+            Point3::new(0.0, 0.0, -1.0),
+            Point3::new(1.0, 0.0, -1.0),
+            Point3::new(2.0, 0.0, -1.0),
+            Point3::new(0.0, 0.0, -2.0),
+            Point3::new(1.0, 0.0, -2.0),
+            Point3::new(0.0, 1.0, -2.0),
+        ];
+        let triangles = [
+            [0, 1, 4],
+            [0, 4, 3],
+            [1, 2, 5],
+            [1, 5, 4],
+            [3, 4, 7],
+            [3, 7, 6],
+            [6, 7, 8],
+        ];
+        let mesh = NavMesh::bake(&vertices, &triangles, &NavMeshSettings::default());
+        assert_eq!(mesh.triangles().len(), 6);
+
+        let path = mesh
+            .find_path(Point3::new(1.8, 0.0, -0.5), Point3::new(0.5, 0.0, -1.8))
+            .unwrap();
+        assert_eq!(
+            path,
+            vec![
+                Point3::new(1.8, 0.0, -0.5),
+                Point3::new(1.0, 0.0, -1.0),
+                Point3::new(0.5, 0.0, -1.8),
+            ]
+        );
+
+        // The points off the mesh go from and to the closest points of the mesh.
+        let path = mesh
+            .find_path(Point3::new(0.5, 1.0, -0.5), Point3::new(3.0, 0.0, -0.5))
+            .unwrap();
+        assert_eq!(
+            path,
+            vec![Point3::new(0.5, 0.0, -0.5), Point3::new(2.0, 0.0, -0.5)]
+        );
+    }
+}
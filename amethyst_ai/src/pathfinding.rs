@@ -0,0 +1,255 @@
+//! The paths of the agents, found in the background.
+
+use std::collections::HashMap;
+
+use crossbeam_channel::{unbounded, Receiver, Sender};
+
+use amethyst_core::{
+    ecs::prelude::{
+        Component, DenseVecStorage, Entities, Entity, Join, Read, ReadExpect, ReadStorage, System,
+        Write, WriteStorage,
+    },
+    math::Point3,
+    shrev::EventChannel,
+    ArcThreadPool, GlobalTransform,
+};
+
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use crate::navigation::NavigationWorld;
+
+/// Asks the `PathfindingSystem` for a path from the position of the entity to a point.
+///
+/// The request is removed once the search starts, the system inserting the `Path` it finds, or
+/// removing the path of the entity if there is no way. A request made while another is being
+/// found replaces it, the entity keeping its current path in the meantime.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PathRequest {
+    /// The destination.
+    pub to: Point3<f32>,
+}
+
+impl PathRequest {
+    /// Creates a request of a path to a point.
+    pub fn new(to: Point3<f32>) -> Self {
+        PathRequest { to }
+    }
+}
+
+impl Component for PathRequest {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// The path an entity follows, found by the `PathfindingSystem`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Path {
+    /// The points of the path, from the position of the entity to the destination.
+    pub points: Vec<Point3<f32>>,
+    /// The index of the next point to reach, advanced as the entity gets to the points.
+    pub next: usize,
+}
+
+impl Path {
+    /// Creates a path through points, the first one being the next to reach.
+    pub fn new(points: Vec<Point3<f32>>) -> Self {
+        Path { points, next: 0 }
+    }
+
+    /// The next point to reach, if the end of the path isn't reached.
+    pub fn next_point(&self) -> Option<Point3<f32>> {
+        self.points.get(self.next).cloned()
+    }
+
+    /// Moves on to the point after the next one.
+    pub fn advance(&mut self) {
+        self.next = (self.next + 1).min(self.points.len());
+    }
+
+    /// Whether all the points of the path are reached.
+    pub fn is_finished(&self) -> bool {
+        self.next >= self.points.len()
+    }
+
+    /// The destination of the path.
+    pub fn destination(&self) -> Option<Point3<f32>> {
+        self.points.last().cloned()
+    }
+}
+
+impl Component for Path {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// The outcome of a `PathRequest`, sent by the `PathfindingSystem`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PathEvent {
+    /// The `Path` of the entity was found.
+    Found(Entity),
+    /// There is no way to the destination, or no `NavigationWorld` to find it on.
+    NotFound(Entity),
+}
+
+// A path found in the background, for the request of an entity.
+type Found = (Entity, u64, Option<Vec<Point3<f32>>>);
+
+/// Finds the paths of the `PathRequest`s on the `NavigationWorld`, in the tasks of the thread
+/// pool, inserting their `Path`s and sending `PathEvent`s once they are found.
+///
+/// The paths go from the `GlobalTransform`s of the entities, the requests of the entities without
+/// one waiting for it.
+pub struct PathfindingSystem {
+    sender: Sender<Found>,
+    receiver: Receiver<Found>,
+    // The last request of each entity, the paths of the older ones being dropped.
+    pending: HashMap<Entity, u64>,
+    next_request: u64,
+}
+
+impl PathfindingSystem {
+    /// Creates a new `PathfindingSystem`.
+    pub fn new() -> Self {
+        let (sender, receiver) = unbounded();
+        PathfindingSystem {
+            sender,
+            receiver,
+            pending: HashMap::new(),
+            next_request: 0,
+        }
+    }
+}
+
+impl Default for PathfindingSystem {
+    fn default() -> Self {
+        PathfindingSystem::new()
+    }
+}
+
+impl<'a> System<'a> for PathfindingSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, NavigationWorld>,
+        ReadExpect<'a, ArcThreadPool>,
+        ReadStorage<'a, GlobalTransform>,
+        WriteStorage<'a, PathRequest>,
+        WriteStorage<'a, Path>,
+        Write<'a, EventChannel<PathEvent>>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (entities, navigation, pool, globals, mut requests, mut paths, mut events) = data;
+        #[cfg(feature = "profiler")]
+        profile_scope!("pathfinding_system");
+
+        let started = (&*entities, &globals, &requests)
+            .join()
+            .map(|(entity, global, request)| {
+                let from = Point3::new(global.0[(0, 3)], global.0[(1, 3)], global.0[(2, 3)]);
+                (entity, from, request.to)
+            })
+            .collect::<Vec<_>>();
+        for (entity, from, to) in started {
+            requests.remove(entity);
+            let id = self.next_request;
+            self.next_request += 1;
+            self.pending.insert(entity, id);
+            let sender = self.sender.clone();
+            match navigation.shared() {
+                Some(navigation) => pool.spawn(move || {
+                    // The system is gone if the receiver is, and the path isn't needed anymore.
+                    let _ = sender.send((entity, id, navigation.find_path(from, to)));
+                }),
+                None => {
+                    let _ = sender.send((entity, id, None));
+                }
+            }
+        }
+
+        let mut path_events = Vec::new();
+        for (entity, id, points) in self.receiver.try_iter() {
+            if self.pending.get(&entity) != Some(&id) {
+                continue;
+            }
+            self.pending.remove(&entity);
+            if !entities.is_alive(entity) {
+                continue;
+            }
+            match points {
+                Some(points) => {
+                    paths
+                        .insert(entity, Path::new(points))
+                        .expect("Unreachable: The entity is alive");
+                    path_events.push(PathEvent::Found(entity));
+                }
+                None => {
+                    paths.remove(entity);
+                    path_events.push(PathEvent::NotFound(entity));
+                }
+            }
+        }
+        events.iter_write(path_events.drain(..));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, thread, time::Duration};
+
+    use amethyst_core::{
+        ecs::{Builder, RunNow, World},
+        math::{Matrix4, Vector3},
+    };
+    use rayon::ThreadPoolBuilder;
+
+    use super::*;
+    use crate::grid::NavGrid;
+
+    #[test]
+    fn paths_are_found_in_the_background() {
+        let mut world = World::new();
+        let mut system = PathfindingSystem::new();
+        System::setup(&mut system, &mut world.res);
+        let pool: ArcThreadPool = Arc::new(ThreadPoolBuilder::new().build().unwrap());
+        world.add_resource(pool);
+        world.add_resource(NavigationWorld::new(NavGrid::new(4, 4, [1.0, 1.0])));
+        let mut reader = world
+            .write_resource::<EventChannel<PathEvent>>()
+            .register_reader();
+
+        let start = Matrix4::new_translation(&Vector3::new(0.5, -0.5, 0.0));
+        let agent = world
+            .create_entity()
+            .with(GlobalTransform(start))
+            .with(PathRequest::new(Point3::new(3.5, -3.5, 0.0)))
+            .build();
+        let lost = world
+            .create_entity()
+            .with(GlobalTransform(start))
+            .with(PathRequest::new(Point3::new(9.0, 9.0, 0.0)))
+            .build();
+
+        let mut found = Vec::new();
+        for _ in 0..100 {
+            system.run_now(&world.res);
+            found.extend(
+                world
+                    .read_resource::<EventChannel<PathEvent>>()
+                    .read(&mut reader)
+                    .cloned(),
+            );
+            if found.len() == 2 {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(found.contains(&PathEvent::Found(agent)));
+        assert!(found.contains(&PathEvent::NotFound(lost)));
+        assert_eq!(world.read_storage::<PathRequest>().join().count(), 0);
+        let paths = world.read_storage::<Path>();
+        assert_eq!(
+            paths.get(agent).and_then(Path::destination),
+            Some(Point3::new(3.5, -3.5, 0.0))
+        );
+        assert!(paths.get(lost).is_none());
+    }
+}
@@ -0,0 +1,535 @@
+//! The steering of the agents, towards their targets and around each other.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use amethyst_core::{
+    ecs::prelude::{
+        Component, DenseVecStorage, Entities, Entity, Join, Read, ReadStorage, System, WriteStorage,
+    },
+    math::{Point3, Vector3},
+    timing::Time,
+    GlobalTransform, Transform,
+};
+
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use crate::pathfinding::Path;
+
+/// An agent, steered by its `Steering` and moved at its velocity.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Agent {
+    /// The radius of the agent, which the other agents keep away from.
+    pub radius: f32,
+    /// The fastest the agent moves.
+    pub max_speed: f32,
+    /// The fastest change of the velocity of the agent, per second.
+    pub max_acceleration: f32,
+    /// The velocity the agent moves at, set from the desired velocity by the
+    /// `AgentMovementSystem`, or by the game from the velocity of its rigid body.
+    pub velocity: Vector3<f32>,
+    /// The velocity the agent steers to, written by the `SteeringSystem`.
+    pub desired_velocity: Vector3<f32>,
+}
+
+impl Agent {
+    /// Creates a still agent, whose velocity changes at once.
+    pub fn new(radius: f32, max_speed: f32) -> Self {
+        Agent {
+            radius,
+            max_speed,
+            max_acceleration: std::f32::INFINITY,
+            velocity: Vector3::zeros(),
+            desired_velocity: Vector3::zeros(),
+        }
+    }
+
+    /// Limits how fast the velocity of the agent changes, per second.
+    pub fn with_max_acceleration(mut self, max_acceleration: f32) -> Self {
+        self.max_acceleration = max_acceleration;
+        self
+    }
+}
+
+impl Component for Agent {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// What an `Agent` steers towards.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Steering {
+    /// Stays still.
+    Idle,
+    /// Goes to a point at full speed, going past it.
+    Seek(Point3<f32>),
+    /// Goes to a point, slowing down within the slowing radius to stop on it.
+    Arrive {
+        /// The point to stop on.
+        target: Point3<f32>,
+        /// The distance from the target from which the agent slows down.
+        slowing_radius: f32,
+    },
+    /// Follows the `Path` of the agent, going on to the next point once within the reach radius
+    /// of a point, and arriving at the last one.
+    FollowPath {
+        /// The distance from a point under which it is reached.
+        reach_radius: f32,
+        /// The distance from the last point from which the agent slows down.
+        slowing_radius: f32,
+    },
+}
+
+impl Default for Steering {
+    fn default() -> Self {
+        Steering::Idle
+    }
+}
+
+impl Component for Steering {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// Makes an `Agent` avoid the other agents, with reciprocal velocity obstacles: the agents
+/// picking the velocities closest to their desired ones which don't collide within the time
+/// horizon, each one taking half of the avoiding of the other agents that avoid too, so that
+/// crowds go around each other without clumping or overlapping.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Avoidance {
+    /// The up direction, the agents avoiding each other in the plane across it, as Z for the 2D
+    /// games.
+    pub up: Vector3<f32>,
+    /// How long ahead the collisions are avoided, in seconds.
+    pub time_horizon: f32,
+    /// The distance within which the other agents are avoided.
+    pub neighbour_distance: f32,
+    /// How much the collisions weigh against the changes of the velocity, the higher weights
+    /// avoiding the other agents earlier.
+    pub weight: f32,
+}
+
+impl Default for Avoidance {
+    fn default() -> Self {
+        Avoidance {
+            up: Vector3::y(),
+            time_horizon: 2.0,
+            neighbour_distance: 5.0,
+            weight: 1.0,
+        }
+    }
+}
+
+impl Component for Avoidance {
+    type Storage = DenseVecStorage<Self>;
+}
+
+// The directions and the speeds of the velocities tried by the avoidance.
+const AVOIDANCE_DIRECTIONS: usize = 16;
+const AVOIDANCE_SPEEDS: [f32; 3] = [1.0 / 3.0, 2.0 / 3.0, 1.0];
+
+/// Writes the desired velocities of the `Agent`s from their `Steering`s, avoiding the other
+/// agents with their `Avoidance`, and advancing the `Path`s the agents follow.
+///
+/// The positions of the agents are their `GlobalTransform`s.
+#[derive(Debug, Default)]
+pub struct SteeringSystem;
+
+impl SteeringSystem {
+    /// Creates a new `SteeringSystem`.
+    pub fn new() -> Self {
+        SteeringSystem
+    }
+}
+
+impl<'a> System<'a> for SteeringSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, Time>,
+        ReadStorage<'a, GlobalTransform>,
+        ReadStorage<'a, Steering>,
+        ReadStorage<'a, Avoidance>,
+        WriteStorage<'a, Path>,
+        WriteStorage<'a, Agent>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        #[cfg(feature = "profiler")]
+        profile_scope!("steering_system");
+
+        let (entities, time, globals, steerings, avoidances, mut paths, mut agents) = data;
+        let delta = time.delta_seconds();
+
+        // The agents in the cells of the largest neighbour distance, for the avoidance.
+        let neighbours = (&*entities, &agents, &globals, avoidances.maybe())
+            .join()
+            .map(|(entity, agent, global, avoidance)| Neighbour {
+                entity,
+                position: position_of(global),
+                velocity: agent.velocity,
+                radius: agent.radius,
+                reciprocal: avoidance.is_some(),
+            })
+            .collect::<Vec<_>>();
+        let cell_size = (&avoidances)
+            .join()
+            .map(|avoidance| avoidance.neighbour_distance)
+            .fold(std::f32::EPSILON, f32::max);
+        let mut cells = HashMap::new();
+        for (i, neighbour) in neighbours.iter().enumerate() {
+            cells
+                .entry(cell(neighbour.position, cell_size))
+                .or_insert_with(Vec::new)
+                .push(i);
+        }
+
+        for (entity, agent, global, steering, path, avoidance) in (
+            &*entities,
+            &mut agents,
+            &globals,
+            steerings.maybe(),
+            (&mut paths).maybe(),
+            avoidances.maybe(),
+        )
+            .join()
+        {
+            let position = position_of(global);
+            let preferred = match steering {
+                Some(steering) => preferred_velocity(steering, position, agent, path, delta),
+                None => Vector3::zeros(),
+            };
+            let desired = match avoidance {
+                Some(avoidance) => {
+                    let (x, y, z) = cell(position, cell_size);
+                    let mut near = Vec::new();
+                    for cx in x - 1..=x + 1 {
+                        for cy in y - 1..=y + 1 {
+                            for cz in z - 1..=z + 1 {
+                                for &i in cells.get(&(cx, cy, cz)).into_iter().flatten() {
+                                    let neighbour: &Neighbour = &neighbours[i];
+                                    let distance = (neighbour.position - position).norm();
+                                    if neighbour.entity != entity
+                                        && distance <= avoidance.neighbour_distance
+                                    {
+                                        near.push(neighbour);
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    avoid(agent, position, preferred, avoidance, &near)
+                }
+                None => preferred,
+            };
+
+            // The velocity changes no faster than the acceleration allows.
+            let change = desired - agent.velocity;
+            let max_change = agent.max_acceleration * delta;
+            agent.desired_velocity = if change.norm() > max_change {
+                agent.velocity + change.normalize() * max_change
+            } else {
+                desired
+            };
+        }
+    }
+}
+
+/// Moves the `Transform`s of the `Agent`s at their desired velocities, which become their
+/// velocities. It should run after the `SteeringSystem` and before the `TransformSystem`.
+///
+/// The games moving their agents with the physics set the velocities of their rigid bodies from
+/// the desired velocities instead, and the velocities of the agents from the rigid bodies.
+#[derive(Debug, Default)]
+pub struct AgentMovementSystem;
+
+impl AgentMovementSystem {
+    /// Creates a new `AgentMovementSystem`.
+    pub fn new() -> Self {
+        AgentMovementSystem
+    }
+}
+
+impl<'a> System<'a> for AgentMovementSystem {
+    type SystemData = (
+        Read<'a, Time>,
+        WriteStorage<'a, Agent>,
+        WriteStorage<'a, Transform>,
+    );
+
+    fn run(&mut self, (time, mut agents, mut transforms): Self::SystemData) {
+        #[cfg(feature = "profiler")]
+        profile_scope!("agent_movement_system");
+
+        let delta = time.delta_seconds();
+        for (agent, transform) in (&mut agents, &mut transforms).join() {
+            agent.velocity = agent.desired_velocity;
+            *transform.translation_mut() += agent.velocity * delta;
+        }
+    }
+}
+
+// An agent avoided by the others.
+struct Neighbour {
+    entity: Entity,
+    position: Point3<f32>,
+    velocity: Vector3<f32>,
+    radius: f32,
+    // Whether the agent avoids too, taking half of the avoiding.
+    reciprocal: bool,
+}
+
+fn position_of(global: &GlobalTransform) -> Point3<f32> {
+    Point3::new(global.0[(0, 3)], global.0[(1, 3)], global.0[(2, 3)])
+}
+
+fn cell(position: Point3<f32>, size: f32) -> (i64, i64, i64) {
+    (
+        (position.x / size).floor() as i64,
+        (position.y / size).floor() as i64,
+        (position.z / size).floor() as i64,
+    )
+}
+
+// The velocity the steering goes at, before the avoidance, advancing the path being followed.
+fn preferred_velocity(
+    steering: &Steering,
+    position: Point3<f32>,
+    agent: &Agent,
+    path: Option<&mut Path>,
+    delta: f32,
+) -> Vector3<f32> {
+    let seek = |target: Point3<f32>| {
+        let offset = target - position;
+        if offset.norm() > std::f32::EPSILON {
+            offset.normalize() * agent.max_speed
+        } else {
+            Vector3::zeros()
+        }
+    };
+    let arrive = |target: Point3<f32>, slowing_radius: f32| {
+        let offset = target - position;
+        let distance = offset.norm();
+        if distance <= std::f32::EPSILON {
+            return Vector3::zeros();
+        }
+        let mut speed = agent.max_speed * (distance / slowing_radius.max(std::f32::EPSILON));
+        // The agent doesn't go past the target within a frame.
+        if delta > 0.0 {
+            speed = speed.min(distance / delta);
+        }
+        offset / distance * speed.min(agent.max_speed)
+    };
+    match *steering {
+        Steering::Idle => Vector3::zeros(),
+        Steering::Seek(target) => seek(target),
+        Steering::Arrive {
+            target,
+            slowing_radius,
+        } => arrive(target, slowing_radius),
+        Steering::FollowPath {
+            reach_radius,
+            slowing_radius,
+        } => {
+            let path = match path {
+                Some(path) => path,
+                None => return Vector3::zeros(),
+            };
+            while let Some(point) = path.next_point() {
+                if (point - position).norm() <= reach_radius {
+                    path.advance();
+                } else {
+                    break;
+                }
+            }
+            match path.next_point() {
+                Some(point) if path.next + 1 < path.points.len() => seek(point),
+                Some(point) => arrive(point, slowing_radius),
+                // The agent stays on the end of the path once it has reached it.
+                None => path
+                    .destination()
+                    .map_or_else(Vector3::zeros, |end| arrive(end, slowing_radius)),
+            }
+        }
+    }
+}
+
+// The velocity closest to the preferred one that avoids the neighbours, out of velocities in the
+// plane of the avoidance, as the reciprocal velocity obstacles of the neighbours.
+fn avoid(
+    agent: &Agent,
+    position: Point3<f32>,
+    preferred: Vector3<f32>,
+    avoidance: &Avoidance,
+    neighbours: &[&Neighbour],
+) -> Vector3<f32> {
+    if neighbours.is_empty() {
+        return preferred;
+    }
+    let up = avoidance.up.normalize();
+    let other = if up.x.abs() < 0.9 {
+        Vector3::x()
+    } else {
+        Vector3::y()
+    };
+    let u = up.cross(&other).normalize();
+    let v = up.cross(&u);
+    let flat = |vector: Vector3<f32>| vector - up * vector.dot(&up);
+
+    let mut candidates = vec![preferred, Vector3::zeros()];
+    for direction in 0..AVOIDANCE_DIRECTIONS {
+        let angle = direction as f32 / AVOIDANCE_DIRECTIONS as f32 * std::f32::consts::PI * 2.0;
+        for &speed in &AVOIDANCE_SPEEDS {
+            let velocity = (u * angle.cos() + v * angle.sin()) * speed * agent.max_speed;
+            candidates.push(velocity + up * preferred.dot(&up));
+        }
+    }
+
+    let penalty = |candidate: Vector3<f32>| {
+        let mut collision = std::f32::INFINITY;
+        for neighbour in neighbours {
+            let offset = flat(neighbour.position - position);
+            // The reciprocal agents each take half of the avoiding.
+            let relative = if neighbour.reciprocal {
+                flat(candidate * 2.0 - agent.velocity - neighbour.velocity)
+            } else {
+                flat(candidate - neighbour.velocity)
+            };
+            let time = time_to_collision(offset, relative, agent.radius + neighbour.radius);
+            collision = collision.min(time);
+        }
+        let collision = if collision <= avoidance.time_horizon {
+            avoidance.weight / collision.max(1.0e-3)
+        } else {
+            0.0
+        };
+        collision + (candidate - preferred).norm() / agent.max_speed.max(std::f32::EPSILON)
+    };
+    candidates
+        .into_iter()
+        .map(|candidate| (candidate, penalty(candidate)))
+        .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+        .map_or(preferred, |(candidate, _)| candidate)
+}
+
+// When an agent at the origin moving at the relative velocity touches a circle, infinite if it
+// never does. The agents already overlapping collide at once unless they move apart.
+fn time_to_collision(offset: Vector3<f32>, velocity: Vector3<f32>, radius: f32) -> f32 {
+    let approach = velocity.dot(&offset);
+    let gap = offset.norm_squared() - radius * radius;
+    if gap < 0.0 {
+        return if approach > 0.0 {
+            0.0
+        } else {
+            std::f32::INFINITY
+        };
+    }
+    let speed = velocity.norm_squared();
+    let discriminant = approach * approach - speed * gap;
+    if approach <= 0.0 || speed <= std::f32::EPSILON || discriminant < 0.0 {
+        return std::f32::INFINITY;
+    }
+    (approach - discriminant.sqrt()) / speed
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::{
+        ecs::{Builder, RunNow, World},
+        math::Matrix4,
+    };
+
+    use super::*;
+
+    fn agent_at(world: &mut World, x: f32, steering: Steering) -> Entity {
+        let translation = Vector3::new(x, 0.0, 0.0);
+        world
+            .create_entity()
+            .with(GlobalTransform(Matrix4::new_translation(&translation)))
+            .with(Agent::new(0.5, 1.0))
+            .with(steering)
+            .build()
+    }
+
+    #[test]
+    fn agents_steer_to_their_targets() {
+        let mut world = World::new();
+        let mut system = SteeringSystem::new();
+        System::setup(&mut system, &mut world.res);
+        world.write_resource::<Time>().set_delta_seconds(0.1);
+        let seeker = agent_at(&mut world, 0.0, Steering::Seek(Point3::new(10.0, 0.0, 0.0)));
+        let arriving = agent_at(
+            &mut world,
+            0.0,
+            Steering::Arrive {
+                target: Point3::new(0.0, 0.5, 0.0),
+                slowing_radius: 1.0,
+            },
+        );
+        let follower = agent_at(
+            &mut world,
+            0.0,
+            Steering::FollowPath {
+                reach_radius: 0.1,
+                slowing_radius: 1.0,
+            },
+        );
+        world
+            .write_storage::<Path>()
+            .insert(
+                follower,
+                Path::new(vec![Point3::origin(), Point3::new(0.0, 0.0, 4.0)]),
+            )
+            .unwrap();
+
+        system.run_now(&world.res);
+        let agents = world.read_storage::<Agent>();
+        let desired = |entity| agents.get(entity).unwrap().desired_velocity;
+        assert_eq!(desired(seeker), Vector3::new(1.0, 0.0, 0.0));
+        assert_eq!(desired(arriving), Vector3::new(0.0, 0.5, 0.0));
+        assert_eq!(desired(follower), Vector3::new(0.0, 0.0, 1.0));
+        assert_eq!(world.read_storage::<Path>().get(follower).unwrap().next, 1);
+    }
+
+    #[test]
+    fn agents_avoid_each_other() {
+        let mut world = World::new();
+        let mut system = SteeringSystem::new();
+        System::setup(&mut system, &mut world.res);
+        world.write_resource::<Time>().set_delta_seconds(0.1);
+        // Two agents heading at each other along X.
+        let left = agent_at(
+            &mut world,
+            -1.5,
+            Steering::Seek(Point3::new(10.0, 0.0, 0.0)),
+        );
+        let right = agent_at(
+            &mut world,
+            1.5,
+            Steering::Seek(Point3::new(-10.0, 0.0, 0.0)),
+        );
+        for &entity in &[left, right] {
+            world
+                .write_storage::<Avoidance>()
+                .insert(entity, Avoidance::default())
+                .unwrap();
+        }
+
+        system.run_now(&world.res);
+        let agents = world.read_storage::<Agent>();
+        let (left, right) = (
+            agents.get(left).unwrap().desired_velocity,
+            agents.get(right).unwrap().desired_velocity,
+        );
+        // They still head on, turning aside so as not to collide as each one takes half.
+        assert!(left.x > 0.0 && right.x < 0.0);
+        let offset = Vector3::new(3.0, 0.0, 0.0);
+        assert_eq!(
+            time_to_collision(offset, left * 2.0, 1.0),
+            std::f32::INFINITY
+        );
+        assert_eq!(
+            time_to_collision(-offset, right * 2.0, 1.0),
+            std::f32::INFINITY
+        );
+    }
+}
@@ -9,10 +9,7 @@ use crate::{
 };
 use amethyst_error::Error;
 
-use amethyst_core::{
-    ecs::prelude::{Component, DispatcherBuilder},
-    SystemBundle,
-};
+use amethyst_core::{ecs::prelude::Component, BundleBuilder, SystemBundle};
 
 /// Bundle for vertex skinning
 ///
@@ -37,7 +34,7 @@ impl<'a> VertexSkinningBundle<'a> {
 }
 
 impl<'a, 'b, 'c> SystemBundle<'a, 'b> for VertexSkinningBundle<'c> {
-    fn build(self, builder: &mut DispatcherBuilder<'a, 'b>) -> Result<(), Error> {
+    fn build(self, builder: &mut BundleBuilder<'a, 'b>) -> Result<(), Error> {
         builder.add(
             VertexSkinningSystem::new(),
             "vertex_skinning_system",
@@ -87,7 +84,7 @@ impl<'a, 'b, 'c, T> SystemBundle<'a, 'b> for SamplingBundle<'c, T>
 where
     T: AnimationSampling + Component,
 {
-    fn build(self, builder: &mut DispatcherBuilder<'a, 'b>) -> Result<(), Error> {
+    fn build(self, builder: &mut BundleBuilder<'a, 'b>) -> Result<(), Error> {
         builder.add(SamplerProcessor::<T::Primitive>::new(), "", &[]);
         builder.add(SamplerInterpolationSystem::<T>::new(), self.name, self.dep);
         Ok(())
@@ -142,7 +139,7 @@ where
     I: PartialEq + Eq + Hash + Copy + Send + Sync + 'static,
     T: AnimationSampling + Component + Clone,
 {
-    fn build(self, builder: &mut DispatcherBuilder<'a, 'b>) -> Result<(), Error> {
+    fn build(self, builder: &mut BundleBuilder<'a, 'b>) -> Result<(), Error> {
         builder.add(AnimationProcessor::<T>::new(), "", &[]);
         builder.add(
             AnimationControlSystem::<I, T>::new(),
@@ -64,6 +64,7 @@ pub use self::{
         AnimationControlSystem, AnimationProcessor, SamplerInterpolationSystem, SamplerProcessor,
     },
     transform::TransformChannel,
+    ui::{RgbaChannel, UiFillChannel, UiProgressChannel, UiTextChannel, UiTransformChannel},
     util::{get_animation_set, SamplerPrimitive},
 };
 
@@ -75,4 +76,5 @@ mod skinning;
 mod sprite;
 mod systems;
 mod transform;
+mod ui;
 mod util;
@@ -0,0 +1,217 @@
+use serde::{Deserialize, Serialize};
+
+use amethyst_renderer::Rgba;
+use amethyst_ui::{UiFill, UiProgress, UiText, UiTransform};
+
+use crate::{
+    resources::{AnimationSampling, ApplyData, BlendMethod},
+    util::SamplerPrimitive,
+};
+
+/// Channels that can be animated on `UiFill`
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq, Serialize, Deserialize)]
+pub enum UiFillChannel {
+    /// The part of the image shown, from 0 to 1.
+    Value,
+}
+
+impl<'a> ApplyData<'a> for UiFill {
+    type ApplyData = ();
+}
+
+impl AnimationSampling for UiFill {
+    type Primitive = SamplerPrimitive<f32>;
+    type Channel = UiFillChannel;
+
+    fn apply_sample(&mut self, channel: &Self::Channel, data: &SamplerPrimitive<f32>, _: &()) {
+        match (channel, *data) {
+            (UiFillChannel::Value, SamplerPrimitive::Scalar(value)) => self.value = value,
+            _ => panic!("Attempt to apply invalid sample to UiFill"),
+        }
+    }
+
+    fn current_sample(&self, channel: &Self::Channel, _: &()) -> SamplerPrimitive<f32> {
+        match channel {
+            UiFillChannel::Value => SamplerPrimitive::Scalar(self.value),
+        }
+    }
+
+    fn default_primitive(_: &Self::Channel) -> Self::Primitive {
+        SamplerPrimitive::Scalar(0.)
+    }
+
+    fn blend_method(&self, _: &Self::Channel) -> Option<BlendMethod> {
+        Some(BlendMethod::Linear)
+    }
+}
+
+/// Channels that can be animated on `UiProgress`
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq, Serialize, Deserialize)]
+pub enum UiProgressChannel {
+    /// The progress, from 0 to 1.
+    Value,
+}
+
+impl<'a> ApplyData<'a> for UiProgress {
+    type ApplyData = ();
+}
+
+impl AnimationSampling for UiProgress {
+    type Primitive = SamplerPrimitive<f32>;
+    type Channel = UiProgressChannel;
+
+    fn apply_sample(&mut self, channel: &Self::Channel, data: &SamplerPrimitive<f32>, _: &()) {
+        match (channel, *data) {
+            (UiProgressChannel::Value, SamplerPrimitive::Scalar(value)) => self.value = value,
+            _ => panic!("Attempt to apply invalid sample to UiProgress"),
+        }
+    }
+
+    fn current_sample(&self, channel: &Self::Channel, _: &()) -> SamplerPrimitive<f32> {
+        match channel {
+            UiProgressChannel::Value => SamplerPrimitive::Scalar(self.value),
+        }
+    }
+
+    fn default_primitive(_: &Self::Channel) -> Self::Primitive {
+        SamplerPrimitive::Scalar(0.)
+    }
+
+    fn blend_method(&self, _: &Self::Channel) -> Option<BlendMethod> {
+        Some(BlendMethod::Linear)
+    }
+}
+
+/// Channels that can be animated on `UiTransform`
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq, Serialize, Deserialize)]
+pub enum UiTransformChannel {
+    /// The local position of the element, as `[x, y, z]`.
+    Translation,
+    /// The size of the element, as `[width, height]`.
+    Size,
+}
+
+impl<'a> ApplyData<'a> for UiTransform {
+    type ApplyData = ();
+}
+
+impl AnimationSampling for UiTransform {
+    type Primitive = SamplerPrimitive<f32>;
+    type Channel = UiTransformChannel;
+
+    fn apply_sample(&mut self, channel: &Self::Channel, data: &SamplerPrimitive<f32>, _: &()) {
+        match (channel, *data) {
+            (UiTransformChannel::Translation, SamplerPrimitive::Vec3(d)) => {
+                self.local_x = d[0];
+                self.local_y = d[1];
+                self.local_z = d[2];
+            }
+            (UiTransformChannel::Size, SamplerPrimitive::Vec2(d)) => {
+                self.width = d[0];
+                self.height = d[1];
+            }
+            _ => panic!("Attempt to apply invalid sample to UiTransform"),
+        }
+    }
+
+    fn current_sample(&self, channel: &Self::Channel, _: &()) -> SamplerPrimitive<f32> {
+        match channel {
+            UiTransformChannel::Translation => {
+                SamplerPrimitive::Vec3([self.local_x, self.local_y, self.local_z])
+            }
+            UiTransformChannel::Size => SamplerPrimitive::Vec2([self.width, self.height]),
+        }
+    }
+
+    fn default_primitive(channel: &Self::Channel) -> Self::Primitive {
+        match channel {
+            UiTransformChannel::Translation => SamplerPrimitive::Vec3([0.; 3]),
+            UiTransformChannel::Size => SamplerPrimitive::Vec2([0.; 2]),
+        }
+    }
+
+    fn blend_method(&self, _: &Self::Channel) -> Option<BlendMethod> {
+        Some(BlendMethod::Linear)
+    }
+}
+
+/// Channels that can be animated on `UiText`
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq, Serialize, Deserialize)]
+pub enum UiTextChannel {
+    /// The color of the text, as `[r, g, b, a]`.
+    Color,
+    /// The font size of the text.
+    FontSize,
+}
+
+impl<'a> ApplyData<'a> for UiText {
+    type ApplyData = ();
+}
+
+impl AnimationSampling for UiText {
+    type Primitive = SamplerPrimitive<f32>;
+    type Channel = UiTextChannel;
+
+    fn apply_sample(&mut self, channel: &Self::Channel, data: &SamplerPrimitive<f32>, _: &()) {
+        match (channel, *data) {
+            (UiTextChannel::Color, SamplerPrimitive::Vec4(d)) => self.color = d,
+            (UiTextChannel::FontSize, SamplerPrimitive::Scalar(size)) => self.font_size = size,
+            _ => panic!("Attempt to apply invalid sample to UiText"),
+        }
+    }
+
+    fn current_sample(&self, channel: &Self::Channel, _: &()) -> SamplerPrimitive<f32> {
+        match channel {
+            UiTextChannel::Color => SamplerPrimitive::Vec4(self.color),
+            UiTextChannel::FontSize => SamplerPrimitive::Scalar(self.font_size),
+        }
+    }
+
+    fn default_primitive(channel: &Self::Channel) -> Self::Primitive {
+        match channel {
+            UiTextChannel::Color => SamplerPrimitive::Vec4([0.; 4]),
+            UiTextChannel::FontSize => SamplerPrimitive::Scalar(0.),
+        }
+    }
+
+    fn blend_method(&self, _: &Self::Channel) -> Option<BlendMethod> {
+        Some(BlendMethod::Linear)
+    }
+}
+
+/// Channels that can be animated on `Rgba`, the tint of images and texts
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq, Serialize, Deserialize)]
+pub enum RgbaChannel {
+    /// The color, as `[r, g, b, a]`.
+    Color,
+}
+
+impl<'a> ApplyData<'a> for Rgba {
+    type ApplyData = ();
+}
+
+impl AnimationSampling for Rgba {
+    type Primitive = SamplerPrimitive<f32>;
+    type Channel = RgbaChannel;
+
+    fn apply_sample(&mut self, channel: &Self::Channel, data: &SamplerPrimitive<f32>, _: &()) {
+        match (channel, *data) {
+            (RgbaChannel::Color, SamplerPrimitive::Vec4(d)) => *self = Rgba(d[0], d[1], d[2], d[3]),
+            _ => panic!("Attempt to apply invalid sample to Rgba"),
+        }
+    }
+
+    fn current_sample(&self, channel: &Self::Channel, _: &()) -> SamplerPrimitive<f32> {
+        match channel {
+            RgbaChannel::Color => SamplerPrimitive::Vec4([self.0, self.1, self.2, self.3]),
+        }
+    }
+
+    fn default_primitive(_: &Self::Channel) -> Self::Primitive {
+        SamplerPrimitive::Vec4([0.; 4])
+    }
+
+    fn blend_method(&self, _: &Self::Channel) -> Option<BlendMethod> {
+        Some(BlendMethod::Linear)
+    }
+}
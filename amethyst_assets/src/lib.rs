@@ -20,7 +20,7 @@ pub use crate::{
     prefab::{AssetPrefab, Prefab, PrefabData, PrefabLoader, PrefabLoaderSystem},
     progress::{Completion, Progress, ProgressCounter, Tracker},
     reload::{HotReloadBundle, HotReloadStrategy, HotReloadSystem, Reload, SingleFile},
-    source::{Directory, Source},
+    source::{Directory, Source, SourceReader},
     storage::{AssetStorage, Handle, ProcessingState, Processor, WeakHandle},
 };
 
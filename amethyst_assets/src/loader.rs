@@ -46,6 +46,11 @@ impl Loader {
         loader
     }
 
+    /// Sets the thread pool the assets are loaded and decoded on.
+    pub fn set_pool(&mut self, pool: Arc<ThreadPool>) {
+        self.pool = pool;
+    }
+
     /// Add a source to the `Loader`, given an id and the source.
     pub fn add_source<I, S>(&mut self, id: I, source: S)
     where
@@ -206,6 +211,11 @@ impl Loader {
         handle
     }
 
+    /// Returns the source added with the id `source`, the default source being `""`.
+    pub fn get_source(&self, source: &str) -> Option<Arc<dyn Source>> {
+        self.sources.get(source).cloned()
+    }
+
     fn source(&self, source: &str) -> Arc<dyn Source> {
         self.sources
             .get(source)
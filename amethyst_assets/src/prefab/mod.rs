@@ -457,8 +457,8 @@ mod tests {
     use rayon::ThreadPoolBuilder;
 
     use amethyst_core::{
-        ecs::{Builder, RunNow, World},
-        GlobalTransform, Time, Transform,
+        ecs::{Builder, Join, RunNow, World},
+        GlobalTransform, Parent, Time, Transform,
     };
 
     use crate::Loader;
@@ -495,4 +495,41 @@ mod tests {
             .get(root_entity)
             .is_some());
     }
+
+    #[test]
+    fn test_prefab_respawn_on_reload() {
+        let mut world = World::new();
+        let pool = Arc::new(ThreadPoolBuilder::default().build().unwrap());
+        world.add_resource(pool.clone());
+        world.add_resource(Loader::new(".", pool));
+        world.add_resource(Time::default());
+        let mut system = PrefabLoaderSystem::<MyPrefab>::default().with_respawn_on_reload();
+        RunNow::setup(&mut system, &mut world.res);
+
+        let mut prefab = Prefab::new_main(Transform::default());
+        prefab.add(Some(0), Some(Transform::default()));
+        let handle = world.read_resource::<Loader>().load_from_data(
+            prefab,
+            (),
+            &world.read_resource::<AssetStorage<Prefab<MyPrefab>>>(),
+        );
+        world.create_entity().with(handle.clone()).build();
+        system.run_now(&world.res);
+        let child = (&world.entities(), &world.read_storage::<Parent>())
+            .join()
+            .map(|(entity, _)| entity)
+            .next()
+            .unwrap();
+
+        // A hot-reload replaces the prefab and gives it a new tag.
+        {
+            let mut storage = world.write_resource::<AssetStorage<Prefab<MyPrefab>>>();
+            let prefab = storage.get_mut(&handle).unwrap();
+            prefab.tag = prefab.tag.map(|tag| tag + 100);
+        }
+        system.run_now(&world.res);
+        world.maintain();
+        assert!(!world.is_alive(child));
+        assert_eq!(world.read_storage::<Parent>().join().count(), 1);
+    }
 }
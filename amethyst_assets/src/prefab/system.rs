@@ -27,6 +27,14 @@ pub struct PrefabLoaderSystem<T> {
     to_process: BitSet,
     insert_reader: Option<ReaderId<ComponentEvent>>,
     next_tag: u64,
+    respawn_on_reload: bool,
+    spawned: HashMap<Entity, SpawnedPrefab>,
+}
+
+// The entities created for a prefab instance, and the tag of the prefab they were created from.
+struct SpawnedPrefab {
+    tag: u64,
+    entities: Vec<Entity>,
 }
 
 impl<T> Default for PrefabLoaderSystem<T> {
@@ -38,10 +46,27 @@ impl<T> Default for PrefabLoaderSystem<T> {
             to_process: BitSet::default(),
             insert_reader: None,
             next_tag: 0,
+            respawn_on_reload: false,
+            spawned: HashMap::default(),
         }
     }
 }
 
+impl<T> PrefabLoaderSystem<T> {
+    /// Respawns the entities created from a prefab when it is hot-reloaded.
+    ///
+    /// The entities created for the prefab are deleted and created again from the new prefab,
+    /// and the data of the main entity is added to it again, replacing its components. Components
+    /// of the main entity that the new prefab doesn't have are kept.
+    ///
+    /// This requires keeping track of the entities created for every prefab instance, so it is
+    /// best only enabled during development.
+    pub fn with_respawn_on_reload(mut self) -> Self {
+        self.respawn_on_reload = true;
+        self
+    }
+}
+
 impl<'a, T> System<'a> for PrefabLoaderSystem<T>
 where
     T: PrefabData<'a> + Send + Sync + 'static,
@@ -105,6 +130,26 @@ where
                     self.to_process.add(*id);
                 }
             });
+        if self.respawn_on_reload {
+            self.spawned.retain(|root, _| entities.is_alive(*root));
+            for (root_entity, handle) in (&*entities, &prefab_handles).join() {
+                let reloaded = match (self.spawned.get(&root_entity), prefab_storage.get(handle)) {
+                    (Some(spawned), Some(prefab)) => prefab.tag != Some(spawned.tag),
+                    _ => false,
+                };
+                if reloaded {
+                    let spawned = self
+                        .spawned
+                        .remove(&root_entity)
+                        .expect("Unreachable: The prefab was spawned");
+                    for entity in spawned.entities {
+                        // The entity may have been deleted since it was spawned, which is fine.
+                        let _ = entities.delete(entity);
+                    }
+                    self.to_process.add(root_entity.id());
+                }
+            }
+        }
         self.finished.clear();
         for (root_entity, handle, _) in (&*entities, &prefab_handles, &self.to_process).join() {
             if let Some(prefab) = prefab_storage.get(handle) {
@@ -142,6 +187,17 @@ where
                     )
                     .expect("Unable to insert `PrefabTag` for prefab entity");
                 }
+                if self.respawn_on_reload {
+                    self.spawned.insert(
+                        root_entity,
+                        SpawnedPrefab {
+                            tag: prefab.tag.expect(
+                                "Unreachable: Every loaded prefab should have a `PrefabTag`",
+                            ),
+                            entities: self.entities[1..].to_vec(),
+                        },
+                    );
+                }
                 // create components
                 for (index, entity_data) in prefab.entities.iter().enumerate() {
                     if let Some(ref prefab_data) = &entity_data.data {
@@ -1,3 +1,5 @@
+use std::io::{Cursor, Read, Seek};
+
 use amethyst_error::Error;
 
 pub use self::dir::Directory;
@@ -7,6 +9,11 @@ use thread_profiler::profile_scope;
 
 mod dir;
 
+/// A reader of the bytes of an asset, returned by `Source::open`.
+pub trait SourceReader: Read + Seek + Send {}
+
+impl<R: Read + Seek + Send> SourceReader for R {}
+
 /// A trait for asset sources, which provides
 /// methods for loading bytes.
 pub trait Source: Send + Sync + 'static {
@@ -32,4 +39,12 @@ pub trait Source: Send + Sync + 'static {
 
         Ok((b, m))
     }
+
+    /// Opens the asset at the path to read it in parts, like the audio streams do.
+    ///
+    /// The default implementation reads the whole asset with `load`, sources which can read
+    /// parts of it, like the `Directory`, only read what is needed.
+    fn open(&self, path: &str) -> Result<Box<dyn SourceReader>, Error> {
+        Ok(Box::new(Cursor::new(self.load(path)?)))
+    }
 }
@@ -0,0 +1,157 @@
+//! Provides groups of sounds played with random variations.
+
+use rand::Rng;
+
+use amethyst_assets::{Asset, Handle, ProcessingState};
+use amethyst_core::ecs::prelude::VecStorage;
+use amethyst_error::Error;
+
+use crate::source::SourceHandle;
+
+/// A handle to a sound bank asset.
+pub type SoundBankHandle = Handle<SoundBank>;
+
+/// A group of sources played as a single sound: every time the bank is played, one of its sources
+/// is picked at random, with a random pitch and volume, so that repeated sounds like footsteps
+/// don't all sound the same.
+///
+/// Sound banks are created with `Loader::load_from_data`:
+///
+/// ```rust,ignore
+/// let footsteps: SoundBankHandle = loader.load_from_data(
+///     SoundBank::new(vec![step_1, step_2, step_3])
+///         .with_pitch(0.95, 1.05)
+///         .with_volume(0.8, 1.0),
+///     (),
+///     &world.read_resource(),
+/// );
+/// ```
+#[derive(Clone, Debug)]
+pub struct SoundBank {
+    /// The sources picked from.
+    pub sources: Vec<SourceHandle>,
+    /// The range of the pitch of a played sound, 1.0 being unchanged.
+    pub pitch: (f32, f32),
+    /// The range of the volume of a played sound, 1.0 being unchanged.
+    pub volume: (f32, f32),
+    /// Never picks the same source twice in a row, if the bank has more than one.
+    pub avoid_repeat: bool,
+    last: Option<usize>,
+}
+
+/// A source picked from a `SoundBank`, with the pitch and volume to play it at.
+#[derive(Clone, Debug)]
+pub struct Variation {
+    /// The picked source.
+    pub source: SourceHandle,
+    /// The pitch to play the source at.
+    pub pitch: f32,
+    /// The volume to play the source at.
+    pub volume: f32,
+}
+
+impl SoundBank {
+    /// Creates a bank picking from the given sources, without pitch and volume variations and
+    /// without immediate repeats.
+    pub fn new(sources: Vec<SourceHandle>) -> Self {
+        SoundBank {
+            sources,
+            pitch: (1.0, 1.0),
+            volume: (1.0, 1.0),
+            avoid_repeat: true,
+            last: None,
+        }
+    }
+
+    /// Plays the sources with a random pitch between `min` and `max`.
+    pub fn with_pitch(mut self, min: f32, max: f32) -> Self {
+        self.pitch = (min, max);
+        self
+    }
+
+    /// Plays the sources with a random volume between `min` and `max`.
+    pub fn with_volume(mut self, min: f32, max: f32) -> Self {
+        self.volume = (min, max);
+        self
+    }
+
+    /// Allows the same source to be picked twice in a row.
+    pub fn allow_repeat(mut self) -> Self {
+        self.avoid_repeat = false;
+        self
+    }
+
+    /// Picks the next sound to play, `None` if the bank is empty.
+    pub fn pick(&mut self) -> Option<Variation> {
+        self.pick_with(&mut rand::thread_rng())
+    }
+
+    /// Picks the next sound to play using the given random number generator.
+    pub fn pick_with<R: Rng + ?Sized>(&mut self, rng: &mut R) -> Option<Variation> {
+        let index = pick_index(self.sources.len(), self.last, self.avoid_repeat, rng)?;
+        self.last = Some(index);
+        Some(Variation {
+            source: self.sources[index].clone(),
+            pitch: Self::jitter(rng, self.pitch),
+            volume: Self::jitter(rng, self.volume).max(0.0),
+        })
+    }
+
+    fn jitter<R: Rng + ?Sized>(rng: &mut R, (min, max): (f32, f32)) -> f32 {
+        if max > min {
+            rng.gen_range(min, max)
+        } else {
+            min
+        }
+    }
+}
+
+impl Asset for SoundBank {
+    const NAME: &'static str = "audio::SoundBank";
+    type Data = SoundBank;
+    type HandleStorage = VecStorage<SoundBankHandle>;
+}
+
+impl Into<Result<ProcessingState<SoundBank>, Error>> for SoundBank {
+    fn into(self) -> Result<ProcessingState<SoundBank>, Error> {
+        Ok(ProcessingState::Loaded(self))
+    }
+}
+
+/// Picks a random index below `count`, other than `last` if `avoid_repeat` is set.
+fn pick_index<R: Rng + ?Sized>(
+    count: usize,
+    last: Option<usize>,
+    avoid_repeat: bool,
+    rng: &mut R,
+) -> Option<usize> {
+    match (count, last) {
+        (0, _) => None,
+        (1, _) => Some(0),
+        (_, Some(last)) if avoid_repeat && last < count => {
+            // Skips the last source by picking among the others.
+            let index = rng.gen_range(0, count - 1);
+            Some(if index >= last { index + 1 } else { index })
+        }
+        _ => Some(rng.gen_range(0, count)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn no_immediate_repeat() {
+        let mut rng = rand::thread_rng();
+        let mut last = None;
+        for _ in 0..100 {
+            let index = pick_index(3, last, true, &mut rng).unwrap();
+            assert!(index < 3);
+            assert_ne!(Some(index), last);
+            last = Some(index);
+        }
+        assert_eq!(pick_index(1, Some(0), true, &mut rng), Some(0));
+        assert_eq!(pick_index(0, None, true, &mut rng), None);
+    }
+}
@@ -1,14 +1,21 @@
 //! ECS audio bundles
 
 use amethyst_assets::Processor;
-use amethyst_core::{bundle::SystemBundle, ecs::prelude::DispatcherBuilder};
+use amethyst_core::bundle::{BundleBuilder, SystemBundle};
 use amethyst_error::Error;
 
-use crate::{output::Output, source::*, systems::AudioSystem};
+use crate::{
+    bank::SoundBank,
+    output::Output,
+    source::*,
+    stream::StreamSource,
+    systems::{AudioSystem, AudioZoneSystem, MusicClockSystem},
+};
 
 /// Audio bundle
 ///
-/// This will only add the audio system and the asset processor for `Source`.
+/// This will only add the audio system, the audio zone system, the music clock system and the
+/// asset processors for `Source`, `StreamSource` and `SoundBank`.
 ///
 /// `DjSystem` must be added separately if you want to use our background music system.
 ///
@@ -16,9 +23,13 @@ use crate::{output::Output, source::*, systems::AudioSystem};
 pub struct AudioBundle(Output);
 
 impl<'a, 'b> SystemBundle<'a, 'b> for AudioBundle {
-    fn build(self, builder: &mut DispatcherBuilder<'a, 'b>) -> Result<(), Error> {
+    fn build(self, builder: &mut BundleBuilder<'a, 'b>) -> Result<(), Error> {
         builder.add(AudioSystem::new(self.0), "audio_system", &[]);
+        builder.add(AudioZoneSystem::default(), "audio_zone_system", &[]);
+        builder.add(MusicClockSystem::default(), "music_clock_system", &[]);
         builder.add(Processor::<Source>::new(), "source_processor", &[]);
+        builder.add(Processor::<StreamSource>::new(), "stream_processor", &[]);
+        builder.add(Processor::<SoundBank>::new(), "sound_bank_processor", &[]);
         Ok(())
     }
 }
@@ -0,0 +1,272 @@
+//! Provides the position of the playing music in bars and beats.
+
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use rodio::Source as RSource;
+use serde::{Deserialize, Serialize};
+
+use crate::stream::StreamDecoder;
+
+/// The tempo of a music track.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Tempo {
+    /// Beats per minute.
+    pub bpm: f32,
+    /// Beats in a bar, 4 for a 4/4 time signature.
+    pub beats_per_bar: u32,
+    /// Time of the first beat from the start of the track, in seconds.
+    pub offset: f32,
+}
+
+impl Tempo {
+    /// Creates a tempo with the first beat at the start of the track.
+    pub fn new(bpm: f32, beats_per_bar: u32) -> Self {
+        Tempo {
+            bpm,
+            beats_per_bar,
+            offset: 0.0,
+        }
+    }
+
+    /// Moves the first beat to `offset` seconds into the track.
+    pub fn with_offset(mut self, offset: f32) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// The duration of a beat.
+    pub fn beat_duration(&self) -> Duration {
+        duration(60.0 / f64::from(self.bpm.max(std::f32::EPSILON)))
+    }
+
+    /// The position at `secs` seconds into the track, `None` before the first beat.
+    pub fn position_at(&self, secs: f64) -> Option<BeatPosition> {
+        let beats = (secs - f64::from(self.offset)) * f64::from(self.bpm) / 60.0;
+        if beats < 0.0 || !beats.is_finite() {
+            return None;
+        }
+        let index = beats.floor() as u64;
+        let beats_per_bar = u64::from(self.beats_per_bar.max(1));
+        Some(BeatPosition {
+            bar: index / beats_per_bar,
+            beat: (index % beats_per_bar) as u32,
+            phase: (beats - beats.floor()) as f32,
+        })
+    }
+}
+
+/// A position in a music track.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BeatPosition {
+    /// The bar, counted from 0.
+    pub bar: u64,
+    /// The beat in the bar, counted from 0.
+    pub beat: u32,
+    /// How far the track is into the beat, from 0.0 to 1.0.
+    pub phase: f32,
+}
+
+impl BeatPosition {
+    /// The number of beats since the first beat of the track.
+    pub fn beats(&self, tempo: &Tempo) -> u64 {
+        self.bar * u64::from(tempo.beats_per_bar.max(1)) + u64::from(self.beat)
+    }
+}
+
+#[derive(Debug, Default)]
+struct ClockShared {
+    // The tempos of the appended tracks by generation, only locked by the game thread.
+    tempos: Mutex<Vec<(usize, Tempo)>>,
+    next_generation: AtomicUsize,
+    // Written by the audio thread, 0 if no track with a tempo plays.
+    current: AtomicUsize,
+    samples: AtomicUsize,
+    sample_rate: AtomicUsize,
+    channels: AtomicUsize,
+}
+
+/// The resource reporting the bar, beat and phase of the music played by the `AudioSink`, for
+/// streams with a tempo. The `MusicClockSystem` sends a `MusicBeat` event on every beat.
+///
+/// ```rust,ignore
+/// let stream = StreamSource::new(&loader, path).looping().with_tempo(Tempo::new(128.0, 4));
+/// // ...
+/// if let Some(position) = world.read_resource::<MusicClock>().now() {
+///     println!("bar {} beat {}", position.bar, position.beat);
+/// }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct MusicClock {
+    shared: Arc<ClockShared>,
+}
+
+impl MusicClock {
+    /// The tempo of the playing track, `None` if it has none or nothing plays.
+    pub fn tempo(&self) -> Option<Tempo> {
+        let current = self.shared.current.load(Ordering::Acquire);
+        if current == 0 {
+            return None;
+        }
+        let mut tempos = self.shared.tempos.lock().ok()?;
+        // Tracks before the playing one are done.
+        tempos.retain(|(generation, _)| *generation >= current);
+        tempos
+            .iter()
+            .find(|(generation, _)| *generation == current)
+            .map(|(_, tempo)| *tempo)
+    }
+
+    /// How long the playing track has been playing, since it last looped.
+    pub fn position(&self) -> Duration {
+        let rate = self.shared.sample_rate.load(Ordering::Relaxed).max(1)
+            * self.shared.channels.load(Ordering::Relaxed).max(1);
+        duration(self.shared.samples.load(Ordering::Relaxed) as f64 / rate as f64)
+    }
+
+    /// The bar, beat and phase the playing track is at, `None` before its first beat or if it
+    /// has no tempo.
+    pub fn now(&self) -> Option<BeatPosition> {
+        let tempo = self.tempo()?;
+        tempo.position_at(secs(self.position()))
+    }
+
+    /// The time until the next beat, to start a stinger on the beat.
+    pub fn until_next_beat(&self) -> Option<Duration> {
+        let tempo = self.tempo()?;
+        let position = tempo.position_at(secs(self.position()))?;
+        Some(duration(
+            secs(tempo.beat_duration()) * f64::from(1.0 - position.phase),
+        ))
+    }
+
+    /// The time until the next bar starts.
+    pub fn until_next_bar(&self) -> Option<Duration> {
+        let tempo = self.tempo()?;
+        let position = tempo.position_at(secs(self.position()))?;
+        let beats_left =
+            f64::from(tempo.beats_per_bar.max(1) - position.beat) - f64::from(position.phase);
+        Some(duration(secs(tempo.beat_duration()) * beats_left))
+    }
+
+    /// An identifier of the playing track, changing whenever a new track starts.
+    pub(crate) fn generation(&self) -> usize {
+        self.shared.current.load(Ordering::Acquire)
+    }
+
+    /// Wraps a stream with a tempo so that the clock follows it while it plays.
+    pub(crate) fn track(&self, input: StreamDecoder, tempo: Tempo) -> ClockSource {
+        let generation = self.shared.next_generation.fetch_add(1, Ordering::Relaxed) + 1;
+        if let Ok(mut tempos) = self.shared.tempos.lock() {
+            tempos.push((generation, tempo));
+        }
+        ClockSource {
+            input,
+            shared: self.shared.clone(),
+            generation,
+            started: false,
+        }
+    }
+}
+
+fn secs(duration: Duration) -> f64 {
+    duration.as_secs() as f64 + f64::from(duration.subsec_nanos()) / 1e9
+}
+
+fn duration(secs: f64) -> Duration {
+    let secs = secs.max(0.0);
+    Duration::new(secs.trunc() as u64, (secs.fract() * 1e9) as u32)
+}
+
+/// Counts the samples of a stream played, for the `MusicClock`.
+pub(crate) struct ClockSource {
+    input: StreamDecoder,
+    shared: Arc<ClockShared>,
+    generation: usize,
+    started: bool,
+}
+
+impl ClockSource {
+    fn stop(&self) {
+        // Only the playing track stops the clock, a later track may already have started it.
+        let _ = self.shared.current.compare_exchange(
+            self.generation,
+            0,
+            Ordering::AcqRel,
+            Ordering::Relaxed,
+        );
+    }
+}
+
+impl Iterator for ClockSource {
+    type Item = i16;
+
+    fn next(&mut self) -> Option<i16> {
+        if !self.started {
+            self.started = true;
+            self.shared
+                .sample_rate
+                .store(self.input.sample_rate() as usize, Ordering::Relaxed);
+            self.shared
+                .channels
+                .store(usize::from(self.input.channels()), Ordering::Relaxed);
+            self.shared.samples.store(0, Ordering::Relaxed);
+            self.shared
+                .current
+                .store(self.generation, Ordering::Release);
+        }
+        let sample = self.input.next();
+        if sample.is_none() {
+            self.stop();
+        } else if self.input.take_looped() {
+            self.shared.samples.store(1, Ordering::Relaxed);
+        } else {
+            self.shared.samples.fetch_add(1, Ordering::Relaxed);
+        }
+        sample
+    }
+}
+
+impl RSource for ClockSource {
+    fn current_frame_len(&self) -> Option<usize> {
+        self.input.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+}
+
+impl Drop for ClockSource {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn beat_position_from_tempo() {
+        let tempo = Tempo::new(120.0, 4).with_offset(1.0);
+        assert_eq!(tempo.position_at(0.5), None);
+        let position = tempo.position_at(4.25).unwrap();
+        assert_eq!((position.bar, position.beat), (1, 2));
+        assert!((position.phase - 0.5).abs() < 1e-6);
+        assert_eq!(position.beats(&tempo), 6);
+    }
+}
@@ -1,21 +1,60 @@
-use std::{
-    io::Cursor,
-    sync::{atomic::AtomicBool, Arc},
-};
+use std::{io::Cursor, sync::Arc};
 
-use rodio::{Decoder, SpatialSink};
+use rodio::{Decoder, Sink, Source as RSource};
 use smallvec::SmallVec;
 
-use amethyst_core::ecs::{prelude::Component, storage::BTreeStorage};
+use amethyst_core::{
+    ecs::{prelude::Component, storage::BTreeStorage},
+    math::Vector3,
+};
+
+use amethyst_assets::AssetStorage;
 
-use crate::{source::Source, DecoderError};
+use crate::{
+    bank::SoundBank,
+    effects::EffectChain,
+    live::LiveStream,
+    mixer::Bus,
+    source::{Source, SourceHandle},
+    spatial::{DistanceModel, SpatialState},
+    DecoderError,
+};
+
+/// A sound waiting for the `AudioSystem` to start it.
+pub(crate) struct QueuedSound {
+    pub decoder: Box<dyn RSource<Item = i16> + Send>,
+    pub bus: Option<Bus>,
+    pub handle: Option<SourceHandle>,
+    pub priority: i32,
+    pub volume: f32,
+    pub pitch: f32,
+}
+
+/// A sound played by an emitter.
+pub(crate) struct PlayingSound {
+    pub sink: Sink,
+    pub state: Arc<SpatialState>,
+    pub handle: Option<SourceHandle>,
+    pub bus: Bus,
+    pub priority: i32,
+    pub volume: f32,
+    pub pitch: f32,
+}
 
 /// An audio source, add this component to anything that emits sound.
 #[derive(Default)]
 pub struct AudioEmitter {
-    pub(crate) sinks: SmallVec<[(SpatialSink, Arc<AtomicBool>); 4]>,
-    pub(crate) sound_queue: SmallVec<[Decoder<Cursor<Source>>; 4]>,
+    /// Overrides the distance model of the `SpatialAudio` resource for this emitter.
+    pub distance_model: Option<DistanceModel>,
+    /// The effects applied to the sounds of this emitter, before the effects of their bus.
+    pub effects: EffectChain,
+    /// The priority of the sounds played from now on, 0 by default. Sounds with a higher
+    /// priority are stopped last when more sounds play than the `VoiceLimit` allows.
+    pub priority: i32,
+    pub(crate) sinks: SmallVec<[PlayingSound; 4]>,
+    pub(crate) sound_queue: SmallVec<[QueuedSound; 4]>,
     pub(crate) picker: Option<Box<dyn FnMut(&mut AudioEmitter) -> bool + Send + Sync>>,
+    pub(crate) previous_position: Option<Vector3<f32>>,
 }
 
 impl AudioEmitter {
@@ -26,10 +65,83 @@ impl AudioEmitter {
         Default::default()
     }
 
-    /// Plays an audio source from this emitter.
+    /// Uses the given distance model for this emitter instead of the one of the `SpatialAudio`
+    /// resource.
+    pub fn with_distance_model(mut self, distance_model: DistanceModel) -> Self {
+        self.distance_model = Some(distance_model);
+        self
+    }
+
+    /// Plays an audio source from this emitter, on the sfx bus of the `Mixer`.
     pub fn play(&mut self, source: &Source) -> Result<(), DecoderError> {
-        self.sound_queue
-            .push(Decoder::new(Cursor::new(source.clone())).map_err(|_| DecoderError)?);
+        self.queue(source, None, None, (1.0, 1.0))
+    }
+
+    /// Plays an audio source from this emitter, on the given bus of the `Mixer`.
+    pub fn play_on(&mut self, source: &Source, bus: &Bus) -> Result<(), DecoderError> {
+        self.queue(source, Some(bus.clone()), None, (1.0, 1.0))
+    }
+
+    /// Plays a loaded audio source from this emitter, on the sfx bus of the `Mixer`. The handle
+    /// is part of the `AudioFinished` event sent when the sound ends.
+    ///
+    /// Fails if the source isn't loaded yet or can't be decoded.
+    pub fn play_handle(
+        &mut self,
+        handle: &SourceHandle,
+        storage: &AssetStorage<Source>,
+    ) -> Result<(), DecoderError> {
+        let source = storage.get(handle).ok_or(DecoderError)?;
+        self.queue(source, None, Some(handle.clone()), (1.0, 1.0))
+    }
+
+    /// Plays a sound picked from a sound bank from this emitter, on the sfx bus of the `Mixer`.
+    /// The handle of the picked source is part of the `AudioFinished` event.
+    ///
+    /// Fails if the bank is empty, or if the picked source isn't loaded yet or can't be decoded.
+    pub fn play_bank(
+        &mut self,
+        bank: &mut SoundBank,
+        storage: &AssetStorage<Source>,
+    ) -> Result<(), DecoderError> {
+        let variation = bank.pick().ok_or(DecoderError)?;
+        let source = storage.get(&variation.source).ok_or(DecoderError)?;
+        self.queue(
+            source,
+            None,
+            Some(variation.source.clone()),
+            (variation.volume, variation.pitch),
+        )
+    }
+
+    /// Plays a live stream from this emitter, on the given bus of the `Mixer`, until the stream
+    /// is closed.
+    pub fn play_live(&mut self, stream: &LiveStream, bus: &Bus) {
+        self.sound_queue.push(QueuedSound {
+            decoder: Box::new(stream.source()),
+            bus: Some(bus.clone()),
+            handle: None,
+            priority: self.priority,
+            volume: 1.0,
+            pitch: 1.0,
+        });
+    }
+
+    fn queue(
+        &mut self,
+        source: &Source,
+        bus: Option<Bus>,
+        handle: Option<SourceHandle>,
+        (volume, pitch): (f32, f32),
+    ) -> Result<(), DecoderError> {
+        self.sound_queue.push(QueuedSound {
+            decoder: Box::new(Decoder::new(Cursor::new(source.clone())).map_err(|_| DecoderError)?),
+            bus,
+            handle,
+            priority: self.priority,
+            volume,
+            pitch,
+        });
         Ok(())
     }
 
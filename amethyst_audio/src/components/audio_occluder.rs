@@ -0,0 +1,39 @@
+use amethyst_core::{
+    ecs::{prelude::Component, storage::DenseVecStorage},
+    math::Vector3,
+};
+use serde::{Deserialize, Serialize};
+
+/// A box blocking sound, like a wall, used by the default occlusion heuristic of the
+/// `AudioSystem`.
+///
+/// The box is centered on the global transform of its entity. The more of it lies between the
+/// listener and an emitter, the more the emitter is occluded.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct AudioOccluder {
+    /// Half the size of the box along each axis of the transform.
+    pub half_extents: Vector3<f32>,
+    /// The occlusion per world unit of the box the sound goes through, between 0.0 and 1.0.
+    /// With 0.5, a sound going through 2 units of the box is fully occluded.
+    pub absorption: f32,
+}
+
+impl AudioOccluder {
+    /// Creates an occluder of the given half size with an absorption of 1.0 per world unit.
+    pub fn new(half_extents: Vector3<f32>) -> Self {
+        AudioOccluder {
+            half_extents,
+            absorption: 1.0,
+        }
+    }
+
+    /// Changes the occlusion per world unit of the box the sound goes through.
+    pub fn with_absorption(mut self, absorption: f32) -> Self {
+        self.absorption = absorption;
+        self
+    }
+}
+
+impl Component for AudioOccluder {
+    type Storage = DenseVecStorage<Self>;
+}
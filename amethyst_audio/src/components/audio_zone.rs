@@ -0,0 +1,152 @@
+use amethyst_core::{
+    ecs::{prelude::Component, storage::DenseVecStorage},
+    math::{Matrix4, Point3, Vector3},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{effects::Effect, occlusion::OPEN_CUTOFF};
+
+/// The volume covered by an `AudioZone`, centered on the global transform of its entity.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub enum ZoneShape {
+    /// A sphere with the given radius, in world units.
+    Sphere {
+        /// The radius of the sphere.
+        radius: f32,
+    },
+    /// A box with the given half size along each axis of the transform.
+    Box {
+        /// Half the size of the box along each axis.
+        half_extents: Vector3<f32>,
+    },
+}
+
+/// An area of the world with its own acoustics, like a cave or a hall. The `AudioZoneSystem`
+/// applies the effects of the zone around the listener to a bus of the `Mixer`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct AudioZone {
+    /// The volume of the zone.
+    pub shape: ZoneShape,
+    /// The effects of the zone.
+    pub effects: Vec<Effect>,
+    /// The distance outside of the shape over which the effects fade out, so that walking
+    /// into the zone is smooth.
+    pub blend_distance: f32,
+}
+
+impl AudioZone {
+    /// Creates a zone with the given shape and effects, blending over 2 world units.
+    pub fn new(shape: ZoneShape, effects: Vec<Effect>) -> Self {
+        AudioZone {
+            shape,
+            effects,
+            blend_distance: 2.0,
+        }
+    }
+
+    /// Changes the distance over which the effects fade out.
+    pub fn with_blend_distance(mut self, blend_distance: f32) -> Self {
+        self.blend_distance = blend_distance;
+        self
+    }
+
+    /// How much the zone applies at `position`, from 0.0 far away to 1.0 inside of it.
+    pub fn weight(&self, transform: &Matrix4<f32>, position: Vector3<f32>) -> f32 {
+        let distance = match self.shape {
+            ZoneShape::Sphere { radius } => {
+                ((position - transform.column(3).xyz()).norm() - radius).max(0.0)
+            }
+            ZoneShape::Box { half_extents } => match transform.try_inverse() {
+                Some(inverse) => {
+                    let local = inverse.transform_point(&Point3::from(position)).coords;
+                    let outside = local.abs() - half_extents.abs();
+                    let outside = outside.map(|x| x.max(0.0));
+                    // Back to world units, for transforms with a scale.
+                    transform.transform_vector(&outside).norm()
+                }
+                None => return 0.0,
+            },
+        };
+        if distance <= 0.0 {
+            1.0
+        } else if self.blend_distance > 0.0 {
+            (1.0 - distance / self.blend_distance).max(0.0)
+        } else {
+            0.0
+        }
+    }
+}
+
+impl Component for AudioZone {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// Blends the effects of zones with their weights. Overlapping zones share the weight of a
+/// single zone, so that moving from one zone into another crossfades them.
+pub(crate) fn blend<'a>(zones: impl Iterator<Item = (f32, &'a [Effect])> + Clone) -> Vec<Effect> {
+    let total = zones
+        .clone()
+        .map(|(weight, _)| weight)
+        .sum::<f32>()
+        .max(1.0);
+    zones
+        .filter(|(weight, _)| *weight > 0.0)
+        .flat_map(|(weight, effects)| {
+            let weight = weight / total;
+            effects.iter().map(move |effect| scale(effect, weight))
+        })
+        .collect()
+}
+
+/// Scales the strength of an effect, 0.0 having no effect.
+fn scale(effect: &Effect, weight: f32) -> Effect {
+    match *effect {
+        Effect::LowPass { cutoff } => Effect::LowPass {
+            cutoff: OPEN_CUTOFF * (cutoff.max(1.0) / OPEN_CUTOFF).powf(weight),
+        },
+        Effect::Echo {
+            delay,
+            feedback,
+            mix,
+        } => Effect::Echo {
+            delay,
+            feedback,
+            mix: mix * weight,
+        },
+        Effect::Reverb {
+            room_size,
+            damping,
+            mix,
+        } => Effect::Reverb {
+            room_size,
+            damping,
+            mix: mix * weight,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[allow(clippy::float_cmp)] // The weight is exactly 1.0 inside of the zone.
+    fn weight_fades_out_of_the_zone() {
+        let zone = AudioZone::new(ZoneShape::Sphere { radius: 5.0 }, Vec::new());
+        let transform = Matrix4::identity();
+        assert_eq!(zone.weight(&transform, Vector3::new(1.0, 0.0, 0.0)), 1.0);
+        assert!((zone.weight(&transform, Vector3::new(6.0, 0.0, 0.0)) - 0.5).abs() < 1e-5);
+        assert!(zone.weight(&transform, Vector3::new(9.0, 0.0, 0.0)).abs() < 1e-6);
+
+        let reverb = [Effect::Reverb {
+            room_size: 0.8,
+            damping: 0.5,
+            mix: 0.5,
+        }];
+        let blended = blend(vec![(0.5, &reverb[..])].into_iter());
+        match blended[..] {
+            [Effect::Reverb { mix, .. }] => assert!((mix - 0.25).abs() < 1e-6),
+            _ => panic!("Expected a single reverb, got {:?}", blended),
+        }
+    }
+}
@@ -1,6 +1,16 @@
 //! `amethyst` audio ecs components
 
-pub use self::{audio_emitter::AudioEmitter, audio_listener::AudioListener};
+pub use self::{
+    audio_emitter::AudioEmitter,
+    audio_listener::AudioListener,
+    audio_occluder::AudioOccluder,
+    audio_zone::{AudioZone, ZoneShape},
+};
+
+pub(crate) use self::{
+    audio_emitter::{PlayingSound, QueuedSound},
+    audio_zone::blend,
+};
 
 use amethyst_assets::PrefabData;
 use amethyst_core::{
@@ -15,6 +25,8 @@ use crate::output::Output;
 
 mod audio_emitter;
 mod audio_listener;
+mod audio_occluder;
+mod audio_zone;
 
 /// `PrefabData` for loading audio components
 ///
@@ -0,0 +1,734 @@
+//! Provides control over sounds while they play.
+
+use std::{
+    io::Cursor,
+    sync::{
+        atomic::{AtomicBool, AtomicIsize, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use rodio::{Decoder, Sample, Sink, Source as RSource};
+
+use amethyst_assets::AssetStorage;
+
+use crate::{
+    bank::SoundBank,
+    mixer::{Bus, BusSource},
+    output::Output,
+    source::Source,
+    stream::{StreamDecoder, StreamSource},
+    DecoderError,
+};
+
+/// Number of frames between two checks for new requests of a `Playback`.
+const REFRESH_FRAMES: usize = 32;
+/// Duration of the fade applied when a sound is stopped, paused or its volume is set, long enough
+/// to avoid an audible click.
+const CLICK_FADE_SECS: f32 = 0.005;
+/// Lowest playback speed, so that the reported sample rate never gets zero.
+const MIN_PITCH: f32 = 0.01;
+
+/// A sound which can be played by the `AudioController`.
+#[derive(Clone, Copy)]
+pub enum Sound<'a> {
+    /// A sound loaded into memory.
+    Source(&'a Source),
+    /// A sound streamed from disk.
+    Stream(&'a StreamSource),
+}
+
+impl<'a> From<&'a Source> for Sound<'a> {
+    fn from(source: &'a Source) -> Self {
+        Sound::Source(source)
+    }
+}
+
+impl<'a> From<&'a StreamSource> for Sound<'a> {
+    fn from(stream: &'a StreamSource) -> Self {
+        Sound::Stream(stream)
+    }
+}
+
+impl<'a> Sound<'a> {
+    fn decode(self) -> Result<Decoded, DecoderError> {
+        Ok(match self {
+            Sound::Source(source) => {
+                let bytes = SharedBytes(Arc::new(source.bytes.clone()));
+                Decoded::Memory(
+                    Decoder::new(Cursor::new(bytes.clone())).map_err(|_| DecoderError)?,
+                    bytes,
+                )
+            }
+            Sound::Stream(stream) => Decoded::Stream(stream.decoder()?),
+        })
+    }
+}
+
+/// Bytes shared between the decoder of a sound and the copy used to restart it.
+#[derive(Clone)]
+pub(crate) struct SharedBytes(Arc<Vec<u8>>);
+
+impl AsRef<[u8]> for SharedBytes {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// A source which can be played again from its start.
+pub(crate) trait Rewind {
+    /// Starts the source over, returns false if that isn't possible.
+    fn rewind(&mut self) -> bool;
+
+    /// Returns true once after the source looped back to its start by itself.
+    fn take_looped(&mut self) -> bool {
+        false
+    }
+}
+
+/// The decoder of a `Sound`.
+pub(crate) enum Decoded {
+    Memory(Decoder<Cursor<SharedBytes>>, SharedBytes),
+    Stream(StreamDecoder),
+}
+
+impl Rewind for Decoded {
+    fn rewind(&mut self) -> bool {
+        match self {
+            Decoded::Memory(decoder, bytes) => match Decoder::new(Cursor::new(bytes.clone())) {
+                Ok(restarted) => {
+                    *decoder = restarted;
+                    true
+                }
+                Err(_) => false,
+            },
+            Decoded::Stream(decoder) => decoder.restart().is_ok(),
+        }
+    }
+
+    fn take_looped(&mut self) -> bool {
+        match self {
+            Decoded::Memory(..) => false,
+            Decoded::Stream(decoder) => decoder.take_looped(),
+        }
+    }
+}
+
+impl Iterator for Decoded {
+    type Item = i16;
+
+    fn next(&mut self) -> Option<i16> {
+        match self {
+            Decoded::Memory(decoder, _) => decoder.next(),
+            Decoded::Stream(decoder) => decoder.next(),
+        }
+    }
+}
+
+impl RSource for Decoded {
+    fn current_frame_len(&self) -> Option<usize> {
+        match self {
+            Decoded::Memory(decoder, _) => decoder.current_frame_len(),
+            Decoded::Stream(decoder) => decoder.current_frame_len(),
+        }
+    }
+
+    fn channels(&self) -> u16 {
+        match self {
+            Decoded::Memory(decoder, _) => decoder.channels(),
+            Decoded::Stream(decoder) => decoder.channels(),
+        }
+    }
+
+    fn sample_rate(&self) -> u32 {
+        match self {
+            Decoded::Memory(decoder, _) => decoder.sample_rate(),
+            Decoded::Stream(decoder) => decoder.sample_rate(),
+        }
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        match self {
+            Decoded::Memory(decoder, _) => decoder.total_duration(),
+            Decoded::Stream(decoder) => decoder.total_duration(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Fade {
+    target: f32,
+    secs: f32,
+    stop: bool,
+}
+
+fn load_f32(atomic: &AtomicUsize) -> f32 {
+    f32::from_bits(atomic.load(Ordering::Relaxed) as u32)
+}
+
+fn store_f32(atomic: &AtomicUsize, value: f32) {
+    atomic.store(value.to_bits() as usize, Ordering::Relaxed);
+}
+
+#[derive(Debug, Default)]
+struct PlaybackState {
+    // Bits of `f32`s, so that the audio thread can read them without locking.
+    volume: AtomicUsize,
+    pitch: AtomicUsize,
+    priority: AtomicIsize,
+    paused: AtomicBool,
+    finished: AtomicBool,
+    // Interleaved samples played since the start of the sound, and played per second.
+    position: AtomicUsize,
+    samples_per_sec: AtomicUsize,
+    // Only ever locked with `try_lock` on the audio thread.
+    fade: Mutex<Option<Fade>>,
+    seek: Mutex<Option<Duration>>,
+}
+
+/// A handle to a sound played by the `AudioController`, controlling the sound while it plays.
+///
+/// Clones refer to the same sound. Dropping the handle doesn't stop the sound.
+#[derive(Clone, Debug)]
+pub struct Playback {
+    state: Arc<PlaybackState>,
+    bus: Bus,
+}
+
+impl Playback {
+    fn new(volume: f32, bus: Bus) -> Self {
+        let state = PlaybackState::default();
+        store_f32(&state.volume, volume);
+        store_f32(&state.pitch, 1.0);
+        Playback {
+            state: Arc::new(state),
+            bus,
+        }
+    }
+
+    /// The bus the sound is played on.
+    pub fn bus(&self) -> &Bus {
+        &self.bus
+    }
+
+    /// Returns true once the sound ended or was stopped.
+    pub fn is_finished(&self) -> bool {
+        self.state.finished.load(Ordering::Relaxed)
+    }
+
+    /// The current volume of the sound, including fades but excluding its bus.
+    pub fn volume(&self) -> f32 {
+        load_f32(&self.state.volume)
+    }
+
+    /// Sets the volume of the sound. A volume of 1.0 is unchanged, while 0.0 is silent.
+    pub fn set_volume(&self, volume: f32) {
+        self.fade(volume, CLICK_FADE_SECS, false);
+    }
+
+    /// The playback speed of the sound, 1.0 being the original speed and pitch.
+    pub fn pitch(&self) -> f32 {
+        load_f32(&self.state.pitch)
+    }
+
+    /// Changes the playback speed, and with it the pitch, of the sound. 2.0 plays twice as
+    /// fast, one octave higher.
+    pub fn set_pitch(&self, pitch: f32) {
+        store_f32(&self.state.pitch, pitch.max(MIN_PITCH));
+    }
+
+    /// The priority of the sound when the `VoiceLimit` is reached, 0 by default.
+    pub fn priority(&self) -> i32 {
+        self.state.priority.load(Ordering::Relaxed) as i32
+    }
+
+    /// Sets the priority of the sound. Sounds with a higher priority are stopped last when more
+    /// sounds play than the `VoiceLimit` allows.
+    pub fn set_priority(&self, priority: i32) {
+        self.state
+            .priority
+            .store(priority as isize, Ordering::Relaxed);
+    }
+
+    /// Pauses the sound, it keeps its position until resumed.
+    pub fn pause(&self) {
+        self.state.paused.store(true, Ordering::Relaxed);
+    }
+
+    /// Resumes the sound if it was paused.
+    pub fn resume(&self) {
+        self.state.paused.store(false, Ordering::Relaxed);
+    }
+
+    /// Returns true if the sound is paused.
+    pub fn is_paused(&self) -> bool {
+        self.state.paused.load(Ordering::Relaxed)
+    }
+
+    /// Stops the sound.
+    pub fn stop(&self) {
+        self.fade(0.0, CLICK_FADE_SECS, true);
+    }
+
+    /// Moves the sound to `position` from its start.
+    ///
+    /// Seeking decodes the sound up to the new position, from the start when seeking backwards.
+    /// This happens on a thread of its own while the sound is silent, so seeking far into long
+    /// streams takes a while but doesn't hold up the other sounds.
+    pub fn seek(&self, position: Duration) {
+        *self.state.seek.lock().expect("Playback mutex poisoned") = Some(position);
+    }
+
+    /// The position of the sound from its start, from the start of the current loop for looping
+    /// streams. Updated by the audio thread about every millisecond.
+    pub fn position(&self) -> Duration {
+        let samples = self.state.position.load(Ordering::Relaxed) as u64;
+        let samples_per_sec = self.state.samples_per_sec.load(Ordering::Relaxed).max(1) as u64;
+        Duration::from_secs(samples / samples_per_sec)
+            + Duration::from_nanos((samples % samples_per_sec) * 1_000_000_000 / samples_per_sec)
+    }
+
+    fn fade(&self, target: f32, secs: f32, stop: bool) {
+        *self.state.fade.lock().expect("Playback mutex poisoned") = Some(Fade {
+            target: target.max(0.0),
+            secs: secs.max(0.0),
+            stop,
+        });
+    }
+}
+
+/// Applies the requests made through a `Playback` to a source, on the audio thread.
+pub(crate) struct PlaybackSource<I> {
+    // `None` while seeking.
+    input: Option<I>,
+    // The input and its position once seeked, handed back by the seeking thread.
+    seeked: Arc<Mutex<Option<(I, usize)>>>,
+    state: Arc<PlaybackState>,
+    channels: usize,
+    sample_rate: u32,
+    samples_per_sec: f32,
+    position: usize,
+    gain: f32,
+    target: f32,
+    step: f32,
+    stop_at_target: bool,
+    pause_gain: f32,
+    pause_step: f32,
+    paused: bool,
+    silent: bool,
+    countdown: usize,
+    done: bool,
+}
+
+impl<I> PlaybackSource<I>
+where
+    I: RSource + Rewind + Send + 'static,
+    I::Item: Sample,
+{
+    fn new(input: I, playback: &Playback) -> Self {
+        let gain = playback.volume();
+        let channels = usize::from(input.channels().max(1));
+        let samples_per_sec = input.sample_rate() as f32 * channels as f32;
+        playback
+            .state
+            .samples_per_sec
+            .store(input.sample_rate() as usize * channels, Ordering::Relaxed);
+        PlaybackSource {
+            sample_rate: input.sample_rate(),
+            input: Some(input),
+            seeked: Arc::new(Mutex::new(None)),
+            state: playback.state.clone(),
+            channels,
+            samples_per_sec,
+            position: 0,
+            gain,
+            target: gain,
+            step: 0.0,
+            stop_at_target: false,
+            pause_gain: 1.0,
+            pause_step: 1.0 / (CLICK_FADE_SECS * samples_per_sec).max(1.0),
+            paused: false,
+            silent: false,
+            countdown: 0,
+            done: false,
+        }
+    }
+
+    #[allow(clippy::float_cmp)] // the pause gain is set to exactly 0.0 at the end of the fade
+    fn refresh(&mut self) {
+        if let Ok(mut fade) = self.state.fade.try_lock() {
+            if let Some(fade) = fade.take() {
+                let samples = (fade.secs * self.samples_per_sec).max(1.0);
+                self.target = fade.target;
+                self.step = (fade.target - self.gain).abs() / samples;
+                self.stop_at_target = fade.stop;
+            }
+        }
+        if self.input.is_none() {
+            if let Ok(mut seeked) = self.seeked.try_lock() {
+                if let Some((input, position)) = seeked.take() {
+                    self.input = Some(input);
+                    self.position = position;
+                    // Fades in from the new position.
+                    self.pause_gain = 0.0;
+                }
+            }
+        }
+        // A seek requested while seeking waits for the first one to finish.
+        let seek = match self.input {
+            Some(_) => self
+                .state
+                .seek
+                .try_lock()
+                .ok()
+                .and_then(|mut seek| seek.take()),
+            None => None,
+        };
+        if let Some(seek) = seek {
+            self.seek(seek);
+        }
+        self.paused = self.state.paused.load(Ordering::Relaxed);
+        // Switching between silence and the input only here keeps the channels aligned, as
+        // refreshes happen at frame boundaries.
+        self.silent = self.paused && self.pause_gain == 0.0;
+        store_f32(&self.state.volume, self.gain);
+        self.state.position.store(self.position, Ordering::Relaxed);
+    }
+
+    // Hands the input to a thread decoding it up to `position`, the sound being silent until
+    // the thread is done.
+    fn seek(&mut self, position: Duration) {
+        let secs = position.as_secs() as f64 + f64::from(position.subsec_nanos()) / 1e9;
+        let target = (secs * f64::from(self.samples_per_sec)) as usize;
+        let target = target - target % self.channels;
+        let mut input = match self.input.take() {
+            Some(input) => input,
+            None => return,
+        };
+        let mut position = self.position;
+        let seeked = self.seeked.clone();
+        thread::spawn(move || {
+            if target < position && input.rewind() {
+                position = 0;
+            }
+            while position < target && input.next().is_some() {
+                position += 1;
+            }
+            *seeked.lock().expect("Playback mutex poisoned") = Some((input, position));
+        });
+    }
+
+    fn finish(&mut self) -> Option<I::Item> {
+        self.done = true;
+        store_f32(&self.state.volume, self.gain);
+        self.state.position.store(self.position, Ordering::Relaxed);
+        self.state.finished.store(true, Ordering::Relaxed);
+        None
+    }
+}
+
+impl<I> Iterator for PlaybackSource<I>
+where
+    I: RSource + Rewind + Send + 'static,
+    I::Item: Sample,
+{
+    type Item = I::Item;
+
+    #[allow(clippy::float_cmp)] // the gain is set to exactly the target at the end of a fade
+    fn next(&mut self) -> Option<I::Item> {
+        if self.done {
+            return None;
+        }
+        if self.countdown == 0 {
+            self.countdown = REFRESH_FRAMES * self.channels;
+            self.refresh();
+        }
+        self.countdown -= 1;
+        if self.gain < self.target {
+            self.gain = (self.gain + self.step).min(self.target);
+        } else if self.gain > self.target {
+            self.gain = (self.gain - self.step).max(self.target);
+        }
+        if self.stop_at_target && self.gain == self.target {
+            return self.finish();
+        }
+        if self.paused {
+            self.pause_gain = (self.pause_gain - self.pause_step).max(0.0);
+        } else {
+            self.pause_gain = (self.pause_gain + self.pause_step).min(1.0);
+        }
+        let input = match self.input {
+            Some(ref mut input) if !self.silent => input,
+            _ => return Some(I::Item::zero_value()),
+        };
+        match input.next() {
+            Some(sample) => {
+                self.position += 1;
+                if input.take_looped() {
+                    self.position = 1;
+                }
+                Some(sample.amplify(self.gain * self.pause_gain))
+            }
+            None => self.finish(),
+        }
+    }
+}
+
+impl<I> RSource for PlaybackSource<I>
+where
+    I: RSource + Rewind + Send + 'static,
+    I::Item: Sample,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        if self.done {
+            return Some(0);
+        }
+        // Frames end at refreshes, so that pitch changes apply right away.
+        let until_refresh = if self.countdown == 0 {
+            REFRESH_FRAMES * self.channels
+        } else {
+            self.countdown
+        };
+        Some(
+            match self.input.as_ref().and_then(RSource::current_frame_len) {
+                Some(len) if len > 0 => len.min(until_refresh),
+                _ => until_refresh,
+            },
+        )
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels as u16
+    }
+
+    fn sample_rate(&self) -> u32 {
+        let pitch = load_f32(&self.state.pitch);
+        (self.sample_rate as f32 * pitch).round().max(1.0) as u32
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+/// Plays sounds which can be controlled while they play, through the returned `Playback`.
+///
+/// Fades are applied sample by sample on the audio thread, so they are click-free and don't
+/// depend on the frame rate. It's inserted by the `AudioSystem` and by `init_output`.
+///
+/// ```rust,ignore
+/// let playback = controller.play(source, mixer.sfx())?;
+/// // Later, when the game is paused:
+/// controller.pause_bus(mixer.sfx());
+/// ```
+pub struct AudioController {
+    output: Output,
+    playbacks: Vec<Playback>,
+}
+
+impl AudioController {
+    /// Creates a controller playing sounds on the given output.
+    pub fn new(output: &Output) -> Self {
+        AudioController {
+            output: output.clone(),
+            playbacks: Vec::new(),
+        }
+    }
+
+    /// Plays a sound on a bus of the `Mixer`.
+    pub fn play<'a, S: Into<Sound<'a>>>(
+        &mut self,
+        sound: S,
+        bus: &Bus,
+    ) -> Result<Playback, DecoderError> {
+        self.play_with_volume(sound, bus, 1.0)
+    }
+
+    /// Plays a sound on a bus of the `Mixer`. A volume of 1.0 is unchanged, while 0.0 is silent.
+    pub fn play_with_volume<'a, S: Into<Sound<'a>>>(
+        &mut self,
+        sound: S,
+        bus: &Bus,
+        volume: f32,
+    ) -> Result<Playback, DecoderError> {
+        let decoded = sound.into().decode()?;
+        let playback = Playback::new(volume.max(0.0), bus.clone());
+        let sink = Sink::new(&self.output.device);
+        sink.append(BusSource::new(
+            PlaybackSource::new(decoded, &playback),
+            bus.clone(),
+        ));
+        sink.detach();
+        self.playbacks.push(playback.clone());
+        Ok(playback)
+    }
+
+    /// Plays a sound picked from a sound bank on a bus of the `Mixer`, with the pitch and volume
+    /// of the pick.
+    ///
+    /// Fails if the bank is empty, or if the picked source isn't loaded yet or can't be decoded.
+    pub fn play_bank(
+        &mut self,
+        bank: &mut SoundBank,
+        storage: &AssetStorage<Source>,
+        bus: &Bus,
+    ) -> Result<Playback, DecoderError> {
+        let variation = bank.pick().ok_or(DecoderError)?;
+        let source = storage.get(&variation.source).ok_or(DecoderError)?;
+        let playback = self.play_with_volume(source, bus, variation.volume)?;
+        playback.set_pitch(variation.pitch);
+        Ok(playback)
+    }
+
+    /// Plays a sound starting silent and fading in to full volume over `secs` seconds.
+    pub fn fade_in<'a, S: Into<Sound<'a>>>(
+        &mut self,
+        sound: S,
+        bus: &Bus,
+        secs: f32,
+    ) -> Result<Playback, DecoderError> {
+        let playback = self.play_with_volume(sound, bus, 0.0)?;
+        self.fade_to(&playback, 1.0, secs);
+        Ok(playback)
+    }
+
+    /// Changes the volume of a sound gradually over `secs` seconds, replacing any running fade.
+    pub fn fade_to(&self, playback: &Playback, volume: f32, secs: f32) {
+        playback.fade(volume, secs, false);
+    }
+
+    /// Fades a sound out over `secs` seconds, and stops it.
+    pub fn fade_out(&self, playback: &Playback, secs: f32) {
+        playback.fade(0.0, secs, true);
+    }
+
+    /// Fades `from` out while fading `to` in, both over `secs` seconds. Meant to switch between
+    /// music tracks.
+    pub fn crossfade<'a, S: Into<Sound<'a>>>(
+        &mut self,
+        from: &Playback,
+        to: S,
+        bus: &Bus,
+        secs: f32,
+    ) -> Result<Playback, DecoderError> {
+        let playback = self.fade_in(to, bus, secs)?;
+        self.fade_out(from, secs);
+        Ok(playback)
+    }
+
+    /// Iterates over the sounds which haven't finished yet.
+    pub fn playbacks(&self) -> impl Iterator<Item = &Playback> {
+        self.playbacks
+            .iter()
+            .filter(|playback| !playback.is_finished())
+    }
+
+    /// Removes the sounds which finished since the last call.
+    pub(crate) fn take_finished(&mut self) -> Vec<Playback> {
+        let (finished, playing) = self
+            .playbacks
+            .drain(..)
+            .partition(|playback| playback.is_finished());
+        self.playbacks = playing;
+        finished
+    }
+
+    /// Pauses all sounds played on `bus`, for example the sound effects while the game is
+    /// paused.
+    pub fn pause_bus(&self, bus: &Bus) {
+        self.on_bus(bus).for_each(Playback::pause);
+    }
+
+    /// Resumes all sounds played on `bus`.
+    pub fn resume_bus(&self, bus: &Bus) {
+        self.on_bus(bus).for_each(Playback::resume);
+    }
+
+    /// Stops all sounds played on `bus`.
+    pub fn stop_bus(&self, bus: &Bus) {
+        self.on_bus(bus).for_each(Playback::stop);
+    }
+
+    /// Pauses all sounds.
+    pub fn pause_all(&self) {
+        self.playbacks().for_each(Playback::pause);
+    }
+
+    /// Resumes all sounds.
+    pub fn resume_all(&self) {
+        self.playbacks().for_each(Playback::resume);
+    }
+
+    /// Stops all sounds.
+    pub fn stop_all(&self) {
+        self.playbacks().for_each(Playback::stop);
+    }
+
+    fn on_bus<'a>(&'a self, bus: &'a Bus) -> impl Iterator<Item = &'a Playback> {
+        self.playbacks()
+            .filter(move |playback| playback.bus.name() == bus.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread::sleep;
+
+    use rodio::buffer::SamplesBuffer;
+
+    use super::*;
+    use crate::mixer::Mixer;
+
+    impl Rewind for SamplesBuffer<f32> {
+        fn rewind(&mut self) -> bool {
+            false
+        }
+    }
+
+    #[test]
+    fn fade_out_ends_the_sound() {
+        let playback = Playback::new(1.0, Mixer::new().sfx().clone());
+        let mut source =
+            PlaybackSource::new(SamplesBuffer::new(1, 64, vec![1.0f32; 100]), &playback);
+        assert_eq!(source.next(), Some(1.0));
+
+        playback.fade(0.0, 0.125, true);
+        source.countdown = 0;
+        let samples = source.collect::<Vec<_>>();
+        assert_eq!(samples, vec![0.875, 0.75, 0.625, 0.5, 0.375, 0.25, 0.125]);
+        assert!(playback.is_finished());
+        assert_eq!(playback.volume(), 0.0);
+    }
+
+    #[test]
+    fn paused_sound_keeps_its_position() {
+        let playback = Playback::new(1.0, Mixer::new().sfx().clone());
+        let samples = (0..200).map(|i| i as f32).collect::<Vec<_>>();
+        let mut source = PlaybackSource::new(SamplesBuffer::new(1, 100, samples), &playback);
+
+        playback.seek(Duration::from_millis(500));
+        // Silent while seeking.
+        let mut sample = source.next();
+        for _ in 0..100 {
+            if sample != Some(0.0) {
+                break;
+            }
+            sleep(Duration::from_millis(1));
+            source.countdown = 0;
+            sample = source.next();
+        }
+        assert_eq!(sample, Some(50.0));
+        playback.pause();
+        for _ in 0..64 {
+            source.next();
+        }
+        let position = playback.position();
+        assert!(source.by_ref().take(32).all(|sample| sample == 0.0));
+        assert_eq!(playback.position(), position);
+
+        playback.resume();
+        assert!(source.take(64).any(|sample| sample > 50.0));
+    }
+}
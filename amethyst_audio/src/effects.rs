@@ -0,0 +1,534 @@
+//! Provides audio effects, applied to the sounds of a mixer bus or an emitter.
+
+use std::{
+    f32::consts::PI,
+    fmt,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, Weak,
+    },
+    time::Duration,
+};
+
+use rodio::{Sample, Source as RSource};
+use serde::{Deserialize, Serialize};
+
+/// Number of frames between two checks for changes of the `EffectChain`.
+const REFRESH_FRAMES: usize = 64;
+/// Longest tail played after the end of a sound, for echoes with a high feedback.
+const MAX_TAIL_SECS: f32 = 10.0;
+/// Comb filter lengths of the reverb at 44100 Hz, from Freeverb.
+const COMB_TUNING: [usize; 4] = [1116, 1188, 1277, 1356];
+/// All-pass filter lengths of the reverb at 44100 Hz, from Freeverb.
+const ALL_PASS_TUNING: [usize; 2] = [556, 441];
+
+/// An audio effect.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub enum Effect {
+    /// Removes the frequencies above `cutoff`, for example for sounds heard under water or
+    /// through a wall.
+    LowPass {
+        /// The cutoff frequency in Hz.
+        cutoff: f32,
+    },
+    /// Repeats the sound after a delay, for example in a canyon.
+    Echo {
+        /// The delay between two repetitions, in seconds.
+        delay: f32,
+        /// The volume of each repetition relative to the previous one, below 1.0.
+        feedback: f32,
+        /// The volume of the repetitions relative to the original sound.
+        mix: f32,
+    },
+    /// Simulates the reflections of a room, from a small room to a cave.
+    Reverb {
+        /// The size of the room, between 0.0 and 1.0.
+        room_size: f32,
+        /// How much the walls absorb high frequencies, between 0.0 and 1.0.
+        damping: f32,
+        /// The volume of the reverberation relative to the original sound, between 0.0 and 1.0.
+        mix: f32,
+    },
+}
+
+#[derive(Debug, Default)]
+struct SharedChain {
+    effects: Mutex<Vec<Effect>>,
+    // Incremented on every change, so that the audio thread only locks when needed.
+    version: AtomicUsize,
+    // The sounds the chain is applied to, which get their processors prepared on changes.
+    voices: Mutex<Vec<Weak<Voice>>>,
+}
+
+/// The processors prepared for a sound the chain is applied to, so that the audio thread
+/// doesn't allocate them.
+struct Voice {
+    channels: usize,
+    sample_rate: u32,
+    // Only ever locked with `try_lock` on the audio thread.
+    processors: Mutex<Prepared>,
+}
+
+#[derive(Default)]
+struct Prepared {
+    // The processors for a version of the chain, one per effect.
+    next: Option<(usize, Vec<Processor>)>,
+    // The processors the audio thread replaced, dropped on the next change.
+    retired: Option<Vec<Processor>>,
+}
+
+impl fmt::Debug for Voice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Voice")
+            .field("channels", &self.channels)
+            .field("sample_rate", &self.sample_rate)
+            .finish()
+    }
+}
+
+/// A chain of effects applied in order, which can be changed while sounds play.
+///
+/// Every mixer `Bus` and `AudioEmitter` has one. Clones refer to the same chain.
+#[derive(Clone, Debug, Default)]
+pub struct EffectChain {
+    shared: Arc<SharedChain>,
+}
+
+impl EffectChain {
+    /// Creates an empty chain.
+    pub fn new() -> Self {
+        EffectChain::default()
+    }
+
+    /// Replaces the effects of the chain.
+    pub fn set(&self, effects: Vec<Effect>) {
+        self.update(|current| *current = effects);
+    }
+
+    /// Adds an effect at the end of the chain.
+    pub fn push(&self, effect: Effect) {
+        self.update(|effects| effects.push(effect));
+    }
+
+    /// Removes all effects.
+    pub fn clear(&self) {
+        self.update(Vec::clear);
+    }
+
+    /// Changes the effects of the chain, for example the parameters of one effect. Effects of
+    /// the same kind at the same place in the chain keep their state, so that changing their
+    /// parameters is seamless.
+    ///
+    /// The effects of the sounds playing with the chain are prepared here, for the audio thread
+    /// to swap them in.
+    pub fn update<F: FnOnce(&mut Vec<Effect>)>(&self, f: F) {
+        let mut effects = self.shared.effects.lock().expect("Effect mutex poisoned");
+        f(&mut effects);
+        let version = self.shared.version.fetch_add(1, Ordering::Release) + 1;
+        let mut voices = self.shared.voices.lock().expect("Effect mutex poisoned");
+        voices.retain(|voice| voice.upgrade().is_some());
+        for voice in voices.iter().filter_map(Weak::upgrade) {
+            let processors = effects
+                .iter()
+                .map(|effect| Processor::new(effect, voice.channels, voice.sample_rate))
+                .collect();
+            let mut prepared = voice.processors.lock().expect("Effect mutex poisoned");
+            prepared.next = Some((version, processors));
+            prepared.retired = None;
+        }
+    }
+
+    /// A copy of the effects of the chain.
+    pub fn effects(&self) -> Vec<Effect> {
+        self.shared
+            .effects
+            .lock()
+            .expect("Effect mutex poisoned")
+            .clone()
+    }
+
+    fn version(&self) -> usize {
+        self.shared.version.load(Ordering::Acquire)
+    }
+}
+
+/// The state of an effect on the audio thread.
+///
+/// Buffers are interleaved like the samples, so that one position serves all channels.
+enum Processor {
+    LowPass {
+        coefficient: f32,
+        previous: Vec<f32>,
+    },
+    Echo {
+        buffer: Vec<f32>,
+        position: usize,
+        feedback: f32,
+        mix: f32,
+    },
+    Reverb {
+        combs: Vec<(Vec<f32>, usize)>,
+        filters: Vec<f32>,
+        all_passes: Vec<(Vec<f32>, usize)>,
+        feedback: f32,
+        damping: f32,
+        mix: f32,
+    },
+}
+
+impl Processor {
+    fn new(effect: &Effect, channels: usize, sample_rate: u32) -> Self {
+        let scaled = |samples: usize| {
+            (samples as f32 * sample_rate as f32 / 44100.0).max(1.0) as usize * channels
+        };
+        let mut processor = match *effect {
+            Effect::LowPass { .. } => Processor::LowPass {
+                coefficient: 1.0,
+                previous: vec![0.0; channels],
+            },
+            Effect::Echo { delay, .. } => Processor::Echo {
+                buffer: vec![
+                    0.0;
+                    (delay.max(0.0) * sample_rate as f32).max(1.0) as usize * channels
+                ],
+                position: 0,
+                feedback: 0.0,
+                mix: 0.0,
+            },
+            Effect::Reverb { .. } => Processor::Reverb {
+                combs: COMB_TUNING
+                    .iter()
+                    .map(|&len| (vec![0.0; scaled(len)], 0))
+                    .collect(),
+                filters: vec![0.0; COMB_TUNING.len() * channels],
+                all_passes: ALL_PASS_TUNING
+                    .iter()
+                    .map(|&len| (vec![0.0; scaled(len)], 0))
+                    .collect(),
+                feedback: 0.0,
+                damping: 0.0,
+                mix: 0.0,
+            },
+        };
+        processor.update(effect, channels, sample_rate);
+        processor
+    }
+
+    /// Takes the parameters of `effect`, returns false if the processor can't be reused.
+    fn update(&mut self, effect: &Effect, channels: usize, sample_rate: u32) -> bool {
+        match (self, *effect) {
+            (Processor::LowPass { coefficient, .. }, Effect::LowPass { cutoff }) => {
+                *coefficient = 1.0 - (-2.0 * PI * cutoff.max(0.0) / sample_rate as f32).exp();
+                true
+            }
+            (
+                Processor::Echo {
+                    buffer,
+                    feedback: current_feedback,
+                    mix: current_mix,
+                    ..
+                },
+                Effect::Echo {
+                    delay,
+                    feedback,
+                    mix,
+                },
+            ) => {
+                *current_feedback = feedback.max(0.0).min(0.99);
+                *current_mix = mix.max(0.0);
+                buffer.len() == (delay.max(0.0) * sample_rate as f32).max(1.0) as usize * channels
+            }
+            (
+                Processor::Reverb {
+                    feedback,
+                    damping: current_damping,
+                    mix: current_mix,
+                    ..
+                },
+                Effect::Reverb {
+                    room_size,
+                    damping,
+                    mix,
+                },
+            ) => {
+                *feedback = 0.7 + 0.28 * room_size.max(0.0).min(1.0);
+                *current_damping = 0.4 * damping.max(0.0).min(1.0);
+                *current_mix = mix.max(0.0).min(1.0);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Number of frames the effect keeps sounding after its input went silent.
+    fn tail_frames(&self, channels: usize, sample_rate: u32) -> usize {
+        let secs = match self {
+            Processor::LowPass { .. } => 0.0,
+            Processor::Echo {
+                buffer, feedback, ..
+            } => {
+                let delay = buffer.len() as f32 / (channels * sample_rate as usize) as f32;
+                if *feedback > 0.0 {
+                    delay * (1.0 + 0.001f32.ln() / feedback.ln())
+                } else {
+                    delay
+                }
+            }
+            Processor::Reverb { feedback, .. } => 0.2 + 0.001f32.ln() / feedback.ln() * 0.03,
+        };
+        (secs.min(MAX_TAIL_SECS) * sample_rate as f32) as usize
+    }
+
+    fn process(&mut self, input: f32, channel: usize, channels: usize) -> f32 {
+        match self {
+            Processor::LowPass {
+                coefficient,
+                previous,
+            } => {
+                let output = previous[channel] + *coefficient * (input - previous[channel]);
+                previous[channel] = output;
+                output
+            }
+            Processor::Echo {
+                buffer,
+                position,
+                feedback,
+                mix,
+            } => {
+                let delayed = buffer[*position];
+                buffer[*position] = input + delayed * *feedback;
+                *position = (*position + 1) % buffer.len();
+                input + delayed * *mix
+            }
+            Processor::Reverb {
+                combs,
+                filters,
+                all_passes,
+                feedback,
+                damping,
+                mix,
+            } => {
+                let mut wet = 0.0;
+                for (index, (buffer, position)) in combs.iter_mut().enumerate() {
+                    let delayed = buffer[*position];
+                    let filter = &mut filters[index * channels + channel];
+                    *filter = delayed * (1.0 - *damping) + *filter * *damping;
+                    buffer[*position] = input * 0.25 + *filter * *feedback;
+                    *position = (*position + 1) % buffer.len();
+                    wet += delayed;
+                }
+                for (buffer, position) in all_passes.iter_mut() {
+                    let delayed = buffer[*position];
+                    buffer[*position] = wet + delayed * 0.5;
+                    *position = (*position + 1) % buffer.len();
+                    wet = delayed - wet;
+                }
+                input * (1.0 - *mix) + wet * *mix
+            }
+        }
+    }
+}
+
+/// Applies an `EffectChain` to a source on the audio thread, and plays the tail of the effects
+/// once the source ended.
+pub(crate) struct EffectSource<I> {
+    input: I,
+    chain: EffectChain,
+    voice: Arc<Voice>,
+    version: usize,
+    processors: Vec<Processor>,
+    channel: usize,
+    countdown: usize,
+    tail: Option<usize>,
+}
+
+impl<I> EffectSource<I>
+where
+    I: RSource,
+    I::Item: Sample,
+{
+    /// Prepares the effects of the chain for `input`, which needs to be done outside of the
+    /// audio thread.
+    pub fn new(input: I, chain: EffectChain) -> Self {
+        let voice = Arc::new(Voice {
+            channels: usize::from(input.channels().max(1)),
+            sample_rate: input.sample_rate(),
+            processors: Mutex::new(Prepared::default()),
+        });
+        let (version, processors) = {
+            let effects = chain.shared.effects.lock().expect("Effect mutex poisoned");
+            let mut voices = chain.shared.voices.lock().expect("Effect mutex poisoned");
+            voices.retain(|voice| voice.upgrade().is_some());
+            voices.push(Arc::downgrade(&voice));
+            let processors = effects
+                .iter()
+                .map(|effect| Processor::new(effect, voice.channels, voice.sample_rate))
+                .collect();
+            (chain.version(), processors)
+        };
+        EffectSource {
+            input,
+            chain,
+            voice,
+            version,
+            processors,
+            channel: 0,
+            countdown: 0,
+            tail: None,
+        }
+    }
+
+    fn channels(&self) -> usize {
+        usize::from(self.input.channels().max(1))
+    }
+
+    // Swaps in the processors prepared for the new version of the chain, keeping the current
+    // ones that can take the new parameters. Doesn't allocate nor free.
+    fn refresh(&mut self) {
+        if self.version == self.chain.version() {
+            return;
+        }
+        let effects = match self.chain.shared.effects.try_lock() {
+            Ok(effects) => effects,
+            Err(_) => return,
+        };
+        let mut prepared = match self.voice.processors.try_lock() {
+            Ok(prepared) => prepared,
+            Err(_) => return,
+        };
+        // Both are locked, so the version can't change.
+        let version = self.chain.version();
+        let mut processors = match prepared.next.take() {
+            Some((prepared, processors)) if prepared == version => processors,
+            _ => return,
+        };
+        let (channels, sample_rate) = (self.voice.channels, self.voice.sample_rate);
+        for ((processor, effect), current) in processors
+            .iter_mut()
+            .zip(effects.iter())
+            .zip(self.processors.iter_mut())
+        {
+            if current.update(effect, channels, sample_rate) {
+                std::mem::swap(processor, current);
+            }
+        }
+        std::mem::swap(&mut self.processors, &mut processors);
+        prepared.retired = Some(processors);
+        self.version = version;
+    }
+}
+
+impl<I> Iterator for EffectSource<I>
+where
+    I: RSource,
+    I::Item: Sample,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let channels = self.channels();
+        if self.countdown == 0 {
+            self.countdown = REFRESH_FRAMES * channels;
+            self.refresh();
+        }
+        self.countdown -= 1;
+        let input = match self.tail {
+            None => match self.input.next() {
+                Some(sample) => sample.to_f32(),
+                None => {
+                    let sample_rate = self.input.sample_rate();
+                    let tail = self
+                        .processors
+                        .iter()
+                        .map(|processor| processor.tail_frames(channels, sample_rate))
+                        .max()
+                        .unwrap_or(0);
+                    if tail == 0 {
+                        return None;
+                    }
+                    // This sample is the first of the tail, which ends at the end of a frame.
+                    self.tail = Some(tail * channels - self.channel - 1);
+                    0.0
+                }
+            },
+            Some(0) => return None,
+            Some(ref mut remaining) => {
+                *remaining -= 1;
+                0.0
+            }
+        };
+        let channel = self.channel;
+        self.channel = (channel + 1) % channels;
+        Some(self.processors.iter_mut().fold(input, |sample, processor| {
+            processor.process(sample, channel, channels)
+        }))
+    }
+}
+
+impl<I> RSource for EffectSource<I>
+where
+    I: RSource,
+    I::Item: Sample,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        match self.tail {
+            Some(remaining) => Some(remaining),
+            None => self.input.current_frame_len(),
+        }
+    }
+
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rodio::buffer::SamplesBuffer;
+
+    use super::*;
+
+    #[test]
+    fn echo_repeats_the_sound_after_the_delay() {
+        let chain = EffectChain::new();
+        chain.push(Effect::Echo {
+            delay: 0.5,
+            feedback: 0.0,
+            mix: 0.5,
+        });
+        let samples = EffectSource::new(SamplesBuffer::new(1, 4, vec![1.0f32, 0.0]), chain)
+            .collect::<Vec<_>>();
+        assert_eq!(samples, vec![1.0, 0.0, 0.5, 0.0]);
+    }
+
+    #[test]
+    fn changes_swap_in_the_prepared_effects() {
+        let chain = EffectChain::new();
+        chain.push(Effect::Echo {
+            delay: 0.5,
+            feedback: 0.0,
+            mix: 0.5,
+        });
+        let input = SamplesBuffer::new(1, 4, vec![1.0f32, 0.0, 0.0, 0.0]);
+        let mut source = EffectSource::new(input, chain.clone());
+        assert_eq!(source.next(), Some(1.0));
+
+        chain.push(Effect::LowPass { cutoff: 1.0e6 });
+        assert!(source.voice.processors.lock().unwrap().next.is_some());
+        source.countdown = 0;
+        // The echo kept its state, repeating the first sample after the delay.
+        assert_eq!(source.by_ref().take(2).collect::<Vec<_>>(), vec![0.0, 0.5]);
+        assert_eq!(source.processors.len(), 2);
+        let prepared = source.voice.processors.lock().unwrap();
+        assert!(prepared.next.is_none());
+        assert!(prepared.retired.is_some());
+    }
+}
@@ -0,0 +1,34 @@
+//! Provides the events sent when sounds finish and on music beats.
+
+use amethyst_core::ecs::prelude::Entity;
+
+use crate::{controller::Playback, source::SourceHandle, systems::DjTrack};
+
+/// Sent through an `EventChannel<AudioFinished>` when a sound finished playing or was stopped.
+///
+/// Emitter sounds and playbacks are reported by the `AudioSystem`, music tracks by the
+/// `DjSystem`, at most one frame after they finished.
+#[derive(Clone, Debug)]
+pub enum AudioFinished {
+    /// A sound played by an `AudioEmitter`.
+    Emitter {
+        /// The entity with the emitter.
+        entity: Entity,
+        /// The source of the sound, if it was played with `AudioEmitter::play_handle`.
+        source: Option<SourceHandle>,
+    },
+    /// A sound played by the `AudioController`.
+    Playback(Playback),
+    /// A music track played by the `DjSystem`.
+    Music(DjTrack),
+}
+
+/// Sent through an `EventChannel<MusicBeat>` by the `MusicClockSystem` on every beat of the music
+/// played by the `AudioSink`, for streams with a tempo.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MusicBeat {
+    /// The bar, counted from 0.
+    pub bar: u64,
+    /// The beat in the bar, counted from 0. The first beat of a bar is the downbeat.
+    pub beat: u32,
+}
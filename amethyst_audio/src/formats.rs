@@ -1,3 +1,5 @@
+use std::path::Path;
+
 use amethyst_assets::*;
 use amethyst_error::Error;
 
@@ -63,8 +65,17 @@ impl SimpleFormat<Audio> for Mp3Format {
         Ok(AudioData(bytes))
     }
 }
+
 /// Aggregate sound format
-#[derive(Debug, Clone, Deserialize, Serialize)]
+///
+/// Use `AudioFormat::from_path` to pick the format from the extension of a file:
+///
+/// ```rust,ignore
+/// let path = "sounds/explosion.mp3";
+/// let format = AudioFormat::from_path(path).expect("unsupported audio file");
+/// let handle = loader.load(path, format, (), (), &storage);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
 pub enum AudioFormat {
     /// Ogg
     Ogg,
@@ -76,6 +87,27 @@ pub enum AudioFormat {
     Mp3,
 }
 
+impl AudioFormat {
+    /// The format of files with the given extension, ignoring case.
+    pub fn from_extension(extension: &str) -> Option<AudioFormat> {
+        match extension.to_lowercase().as_str() {
+            "ogg" | "oga" => Some(AudioFormat::Ogg),
+            "wav" | "wave" => Some(AudioFormat::Wav),
+            "flac" => Some(AudioFormat::Flac),
+            "mp3" => Some(AudioFormat::Mp3),
+            _ => None,
+        }
+    }
+
+    /// The format of the file at `path`, from its extension.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<AudioFormat> {
+        path.as_ref()
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .and_then(AudioFormat::from_extension)
+    }
+}
+
 impl SimpleFormat<Audio> for AudioFormat {
     const NAME: &'static str = "AudioFormat";
 
@@ -90,3 +122,30 @@ impl SimpleFormat<Audio> for AudioFormat {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_from_extension() {
+        assert_eq!(
+            AudioFormat::from_path("music/theme.MP3"),
+            Some(AudioFormat::Mp3)
+        );
+        assert_eq!(
+            AudioFormat::from_path("sfx/hit.flac"),
+            Some(AudioFormat::Flac)
+        );
+        assert_eq!(
+            AudioFormat::from_path("sfx/hit.ogg"),
+            Some(AudioFormat::Ogg)
+        );
+        assert_eq!(
+            AudioFormat::from_path("sfx/hit.wav"),
+            Some(AudioFormat::Wav)
+        );
+        assert_eq!(AudioFormat::from_path("sfx/hit.txt"), None);
+        assert_eq!(AudioFormat::from_path("sfx/hit"), None);
+    }
+}
@@ -0,0 +1,341 @@
+//! Provides structures and functions used to capture audio from microphones.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    error::Error,
+    fmt::{Debug, Display, Formatter, Result as FmtResult},
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use cpal::{
+    default_input_device, input_devices, Device, EventLoop, InputDevices, Sample as CSample,
+    StreamData, StreamId, UnknownTypeInputBuffer,
+};
+use lazy_static::lazy_static;
+use log::error;
+
+/// A microphone through which audio can be captured.
+#[derive(Clone, Eq, PartialEq)]
+pub struct Input {
+    device: Device,
+}
+
+impl Input {
+    /// Gets the name of the input
+    pub fn name(&self) -> String {
+        self.device.name()
+    }
+}
+
+impl Debug for Input {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("Input")
+            .field("device", &self.name())
+            .finish()
+    }
+}
+
+/// An iterator over inputs
+pub struct InputIterator {
+    input: InputDevices,
+}
+
+impl Iterator for InputIterator {
+    type Item = Input;
+
+    fn next(&mut self) -> Option<Input> {
+        self.input.next().map(|device| Input { device })
+    }
+}
+
+/// Get the default input, returns none if no inputs are available.
+pub fn default_input() -> Option<Input> {
+    default_input_device().map(|device| Input { device })
+}
+
+/// Get a list of inputs available to the system.
+pub fn inputs() -> InputIterator {
+    InputIterator {
+        input: input_devices(),
+    }
+}
+
+/// An error occurred while opening a microphone.
+#[derive(Debug)]
+pub struct CaptureError(String);
+
+impl Display for CaptureError {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> FmtResult {
+        write!(formatter, "CaptureError: {}", self.0)
+    }
+}
+
+impl Error for CaptureError {
+    fn description(&self) -> &str {
+        "An error occurred while opening an audio input."
+    }
+
+    fn cause(&self) -> Option<&dyn Error> {
+        None
+    }
+}
+
+/// Mixes captured audio down to mono and converts it to another sample rate, by linear
+/// interpolation.
+struct Resampler {
+    // Input samples per output sample.
+    step: f64,
+    // Position of the next output sample, in input samples after `previous`.
+    position: f64,
+    previous: f32,
+}
+
+impl Resampler {
+    fn new(input_rate: u32, output_rate: u32) -> Self {
+        Resampler {
+            step: f64::from(input_rate) / f64::from(output_rate.max(1)),
+            position: 0.0,
+            previous: 0.0,
+        }
+    }
+
+    /// Takes the next input sample, pushing the output samples up to it.
+    fn push(&mut self, sample: f32, output: &mut Vec<f32>) {
+        while self.position < 1.0 {
+            let t = self.position as f32;
+            output.push(self.previous + (sample - self.previous) * t);
+            self.position += self.step;
+        }
+        self.position -= 1.0;
+        self.previous = sample;
+    }
+}
+
+#[derive(Default)]
+struct Captured {
+    samples: VecDeque<f32>,
+    level: f32,
+}
+
+/// A stream captured on the `CaptureLoop`, converted for its `Microphone`.
+struct Capture {
+    captured: Arc<Mutex<Captured>>,
+    channels: usize,
+    capacity: usize,
+    resampler: Resampler,
+    converted: Vec<f32>,
+}
+
+impl Capture {
+    fn push(&mut self, data: StreamData<'_>) {
+        self.converted.clear();
+        let (channels, resampler, converted) =
+            (self.channels, &mut self.resampler, &mut self.converted);
+        match data {
+            StreamData::Input {
+                buffer: UnknownTypeInputBuffer::U16(buffer),
+            } => mix_down(&*buffer, channels, resampler, converted),
+            StreamData::Input {
+                buffer: UnknownTypeInputBuffer::I16(buffer),
+            } => mix_down(&*buffer, channels, resampler, converted),
+            StreamData::Input {
+                buffer: UnknownTypeInputBuffer::F32(buffer),
+            } => mix_down(&*buffer, channels, resampler, converted),
+            _ => return,
+        }
+        if converted.is_empty() {
+            return;
+        }
+        let mut captured = match self.captured.lock() {
+            Ok(captured) => captured,
+            Err(_) => return,
+        };
+        let squares = converted.iter().map(|sample| sample * sample).sum::<f32>();
+        captured.level = (squares / converted.len() as f32).sqrt();
+        captured.samples.extend(converted.iter().cloned());
+        let excess = captured.samples.len().saturating_sub(self.capacity);
+        captured.samples.drain(..excess);
+    }
+}
+
+/// The event loop all the microphones capture on.
+///
+/// The event loops of `cpal` run on their thread forever once started, so a single one is
+/// shared rather than one being started per microphone.
+struct CaptureLoop {
+    event_loop: EventLoop,
+    captures: Mutex<HashMap<StreamId, Capture>>,
+}
+
+lazy_static! {
+    static ref CAPTURE_LOOP: Arc<CaptureLoop> = {
+        let capture_loop = Arc::new(CaptureLoop {
+            event_loop: EventLoop::new(),
+            captures: Mutex::new(HashMap::new()),
+        });
+        let running = capture_loop.clone();
+        let started = thread::Builder::new()
+            .name("amethyst_audio microphone".into())
+            .spawn(move || {
+                running.event_loop.run(|stream, data| {
+                    if let Ok(mut captures) = running.captures.lock() {
+                        if let Some(capture) = captures.get_mut(&stream) {
+                            capture.push(data);
+                        }
+                    }
+                })
+            });
+        if let Err(err) = started {
+            error!("Failed to start the microphone capture thread: {}", err);
+        }
+        capture_loop
+    };
+}
+
+/// The resource with the samples captured from a microphone, in mono at the requested sample
+/// rate.
+///
+/// The samples are buffered until they are read with `read`, up to a capacity of one second, so
+/// a consumer like a voice chat system should read them every frame. The oldest samples are
+/// dropped when the buffer is full.
+///
+/// ```rust,ignore
+/// let microphone = Microphone::open(&default_input().unwrap(), 16000)?;
+/// world.add_resource(microphone);
+/// ```
+pub struct Microphone {
+    captured: Arc<Mutex<Captured>>,
+    stream: StreamId,
+    sample_rate: u32,
+    name: String,
+}
+
+impl Microphone {
+    /// Starts capturing from the given input, converting its audio to `sample_rate`.
+    pub fn open(input: &Input, sample_rate: u32) -> Result<Microphone, CaptureError> {
+        let format = input
+            .device
+            .default_input_format()
+            .map_err(|err| CaptureError(format!("{:?}", err)))?;
+        let capture_loop = &*CAPTURE_LOOP;
+        let stream = capture_loop
+            .event_loop
+            .build_input_stream(&input.device, &format)
+            .map_err(|err| CaptureError(format!("{:?}", err)))?;
+
+        let captured = Arc::new(Mutex::new(Captured::default()));
+        let capture = Capture {
+            captured: captured.clone(),
+            channels: usize::from(format.channels.max(1)),
+            capacity: sample_rate as usize,
+            resampler: Resampler::new(format.sample_rate.0, sample_rate),
+            converted: Vec::new(),
+        };
+        capture_loop
+            .captures
+            .lock()
+            .map_err(|_| CaptureError("The microphone capture thread panicked".into()))?
+            .insert(stream.clone(), capture);
+        capture_loop.event_loop.play_stream(stream.clone());
+
+        Ok(Microphone {
+            captured,
+            stream,
+            sample_rate,
+            name: input.name(),
+        })
+    }
+
+    /// Starts capturing from the default input, see `open`.
+    pub fn open_default(sample_rate: u32) -> Result<Microphone, CaptureError> {
+        let input = default_input().ok_or_else(|| CaptureError("No input device".into()))?;
+        Microphone::open(&input, sample_rate)
+    }
+
+    /// The name of the input captured from.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The sample rate of the captured samples.
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Moves the samples captured since the last call to the end of `samples`.
+    pub fn read(&self, samples: &mut Vec<f32>) {
+        match self.captured.lock() {
+            Ok(mut captured) => samples.extend(captured.samples.drain(..)),
+            Err(_) => error!("The microphone capture thread panicked"),
+        }
+    }
+
+    /// The loudness of the last captured block, as the root mean square of its samples between
+    /// 0.0 and 1.0. Meant for audio-reactive gameplay.
+    pub fn level(&self) -> f32 {
+        self.captured
+            .lock()
+            .map(|captured| captured.level)
+            .unwrap_or(0.0)
+    }
+
+    /// Stops capturing until `resume` is called.
+    pub fn pause(&self) {
+        CAPTURE_LOOP.event_loop.pause_stream(self.stream.clone());
+    }
+
+    /// Resumes capturing after `pause`.
+    pub fn resume(&self) {
+        CAPTURE_LOOP.event_loop.play_stream(self.stream.clone());
+    }
+}
+
+impl Drop for Microphone {
+    fn drop(&mut self) {
+        CAPTURE_LOOP.event_loop.destroy_stream(self.stream.clone());
+        if let Ok(mut captures) = CAPTURE_LOOP.captures.lock() {
+            captures.remove(&self.stream);
+        }
+    }
+}
+
+impl Debug for Microphone {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("Microphone")
+            .field("name", &self.name)
+            .field("sample_rate", &self.sample_rate)
+            .finish()
+    }
+}
+
+fn mix_down<S: CSample>(
+    buffer: &[S],
+    channels: usize,
+    resampler: &mut Resampler,
+    output: &mut Vec<f32>,
+) {
+    for frame in buffer.chunks(channels) {
+        let sum = frame.iter().map(|sample| sample.to_f32()).sum::<f32>();
+        resampler.push(sum / frame.len() as f32, output);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resample_halves_the_rate() {
+        let mut resampler = Resampler::new(48000, 24000);
+        let mut output = Vec::new();
+        for sample in &[0.0, 0.5, 1.0, 0.5, 0.0, -0.5] {
+            resampler.push(*sample, &mut output);
+        }
+        assert_eq!(output.len(), 3);
+        assert!(output[1..]
+            .iter()
+            .zip(&[0.5, 0.5])
+            .all(|(a, b)| (a - b).abs() < 1e-6));
+    }
+}
@@ -2,12 +2,23 @@
 #![warn(missing_docs, rust_2018_idioms, rust_2018_compatibility)]
 
 pub use self::{
+    bank::{SoundBank, SoundBankHandle, Variation},
     bundle::AudioBundle,
+    clock::{BeatPosition, MusicClock, Tempo},
     components::*,
+    controller::{AudioController, Playback, Sound},
+    effects::{Effect, EffectChain},
+    event::{AudioFinished, MusicBeat},
     formats::{AudioFormat, FlacFormat, Mp3Format, OggFormat, WavFormat},
+    live::LiveStream,
+    mixer::{Bus, Mixer, MASTER_BUS, MUSIC_BUS, SFX_BUS, VOICE_BUS},
+    occlusion::{Occlusion, OcclusionCallback},
     sink::AudioSink,
     source::{Source, SourceHandle},
+    spatial::{Attenuation, DistanceModel, SpatialAudio},
+    stream::{StreamHandle, StreamSource},
     systems::*,
+    voices::VoiceLimit,
 };
 
 use std::{
@@ -15,15 +26,27 @@ use std::{
     fmt::{Display, Formatter, Result as FmtResult},
 };
 
+pub mod input;
 pub mod output;
 
+mod bank;
 mod bundle;
+mod clock;
 mod components;
+mod controller;
+mod effects;
 mod end_signal;
+mod event;
 mod formats;
+mod live;
+mod mixer;
+mod occlusion;
 mod sink;
 mod source;
+mod spatial;
+mod stream;
 mod systems;
+mod voices;
 
 /// An error occurred while decoding the source.
 #[derive(Debug)]
@@ -0,0 +1,173 @@
+//! Provides the streams of samples produced while they play.
+
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use rodio::Source as RSource;
+
+use amethyst_core::timing::duration_to_nanos;
+
+// How many samples the audio thread takes from the shared buffer at once.
+const CHUNK: usize = 256;
+
+struct LiveBuffer {
+    samples: VecDeque<f32>,
+    // Whether the stream waits for `latency` samples before playing again.
+    buffering: bool,
+    closed: bool,
+}
+
+/// A mono stream of samples pushed while it plays, like the voice of a player received over the
+/// network, played from an emitter with `AudioEmitter::play_live`.
+///
+/// The stream plays silence until `latency` worth of samples is buffered, and again each time it
+/// runs out of samples, to absorb the jitter of their arrival. It plays until it is closed and its
+/// samples are played.
+#[derive(Clone)]
+pub struct LiveStream {
+    shared: Arc<Mutex<LiveBuffer>>,
+    sample_rate: u32,
+    latency: usize,
+}
+
+impl LiveStream {
+    /// Creates a stream of samples at `sample_rate`, buffering `latency` of them before playing.
+    pub fn new(sample_rate: u32, latency: Duration) -> Self {
+        let latency = (u128::from(duration_to_nanos(latency)) * u128::from(sample_rate)
+            / 1_000_000_000) as usize;
+        LiveStream {
+            shared: Arc::new(Mutex::new(LiveBuffer {
+                samples: VecDeque::new(),
+                buffering: true,
+                closed: false,
+            })),
+            sample_rate,
+            latency,
+        }
+    }
+
+    /// The sample rate of the stream.
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Appends samples to the stream. Up to one second is buffered, the oldest samples being
+    /// dropped beyond that.
+    pub fn push(&self, samples: &[f32]) {
+        if let Ok(mut buffer) = self.shared.lock() {
+            buffer.samples.extend(samples.iter().cloned());
+            let excess = buffer
+                .samples
+                .len()
+                .saturating_sub(self.sample_rate.max(1) as usize);
+            buffer.samples.drain(..excess);
+        }
+    }
+
+    /// How many samples are waiting to be played.
+    pub fn buffered(&self) -> usize {
+        self.shared
+            .lock()
+            .map(|buffer| buffer.samples.len())
+            .unwrap_or(0)
+    }
+
+    /// Ends the stream once its buffered samples are played.
+    pub fn close(&self) {
+        if let Ok(mut buffer) = self.shared.lock() {
+            buffer.closed = true;
+        }
+    }
+
+    pub(crate) fn source(&self) -> LiveSource {
+        LiveSource {
+            stream: self.clone(),
+            chunk: VecDeque::with_capacity(CHUNK),
+        }
+    }
+}
+
+/// Plays a `LiveStream` on the audio thread.
+pub(crate) struct LiveSource {
+    stream: LiveStream,
+    chunk: VecDeque<f32>,
+}
+
+impl LiveSource {
+    // Takes the next samples from the shared buffer, returning false once the stream ended.
+    fn refill(&mut self) -> bool {
+        let mut buffer = match self.stream.shared.lock() {
+            Ok(buffer) => buffer,
+            Err(_) => return false,
+        };
+        if buffer.samples.is_empty() {
+            if buffer.closed {
+                return false;
+            }
+            buffer.buffering = true;
+        }
+        if buffer.buffering && buffer.samples.len() < self.stream.latency.max(1) && !buffer.closed {
+            // Underrun: play a chunk of silence while the buffer fills up.
+            self.chunk.extend((0..CHUNK).map(|_| 0.0));
+            return true;
+        }
+        buffer.buffering = false;
+        let taken = buffer.samples.len().min(CHUNK);
+        self.chunk.extend(buffer.samples.drain(..taken));
+        true
+    }
+}
+
+impl Iterator for LiveSource {
+    type Item = i16;
+
+    fn next(&mut self) -> Option<i16> {
+        if self.chunk.is_empty() && !self.refill() {
+            return None;
+        }
+        self.chunk
+            .pop_front()
+            .map(|sample| (sample.max(-1.0).min(1.0) * f32::from(i16::max_value())) as i16)
+    }
+}
+
+impl RSource for LiveSource {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.stream.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn silence_until_the_latency_is_buffered() {
+        let stream = LiveStream::new(1000, Duration::from_millis(10));
+        let mut source = stream.source();
+        stream.push(&[0.5; 5]);
+        assert!(source.by_ref().take(CHUNK).all(|sample| sample == 0));
+
+        stream.push(&[0.5; 5]);
+        assert_eq!(source.next(), Some(i16::max_value() / 2));
+        assert_eq!(source.by_ref().take(9).count(), 9);
+
+        stream.close();
+        assert_eq!(source.next(), None);
+    }
+}
@@ -0,0 +1,303 @@
+//! Provides mixer buses grouping sounds to control their volume together.
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use rodio::{Sample, Source as RSource};
+
+use crate::effects::{EffectChain, EffectSource};
+
+/// Name of the bus all other buses are mixed into.
+pub const MASTER_BUS: &str = "master";
+/// Name of the bus used for music, including the `AudioSink`.
+pub const MUSIC_BUS: &str = "music";
+/// Name of the bus used for sound effects, including the sounds of `AudioEmitter`s.
+pub const SFX_BUS: &str = "sfx";
+/// Name of the bus used for voices and dialogue.
+pub const VOICE_BUS: &str = "voice";
+
+/// Number of samples between two reads of the gain of the bus.
+const REFRESH_SAMPLES: usize = 64;
+/// Duration of the ramp to a new gain, long enough to avoid audible clicks.
+const RAMP_SECS: f32 = 0.01;
+
+#[derive(Debug)]
+struct Gain {
+    // Bits of an `f32`, so that the audio thread can read the volume without locking.
+    volume: AtomicUsize,
+    muted: AtomicBool,
+}
+
+impl Gain {
+    fn new() -> Self {
+        Gain {
+            volume: AtomicUsize::new(1.0f32.to_bits() as usize),
+            muted: AtomicBool::new(false),
+        }
+    }
+
+    fn volume(&self) -> f32 {
+        f32::from_bits(self.volume.load(Ordering::Relaxed) as u32)
+    }
+
+    fn audible_volume(&self) -> f32 {
+        if self.muted.load(Ordering::Relaxed) {
+            0.0
+        } else {
+            self.volume()
+        }
+    }
+}
+
+/// A mixer bus, scaling the volume of all sounds played on it and applying its effects to them.
+///
+/// Buses are obtained from the `Mixer` and cheap to clone, all clones control the same bus.
+/// Changes apply to sounds that are already playing.
+#[derive(Clone, Debug)]
+pub struct Bus {
+    name: Arc<str>,
+    gain: Arc<Gain>,
+    master: Option<Arc<Gain>>,
+    effects: EffectChain,
+    master_effects: Option<EffectChain>,
+}
+
+impl Bus {
+    /// The name of the bus.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The volume of the bus, excluding the volume of the master bus. 1.0 is unchanged.
+    pub fn volume(&self) -> f32 {
+        self.gain.volume()
+    }
+
+    /// Sets the volume of the bus. 1.0 is unchanged, 0.0 is silent.
+    pub fn set_volume(&self, volume: f32) {
+        self.gain
+            .volume
+            .store(volume.max(0.0).to_bits() as usize, Ordering::Relaxed);
+    }
+
+    /// Returns true if the bus is muted.
+    pub fn is_muted(&self) -> bool {
+        self.gain.muted.load(Ordering::Relaxed)
+    }
+
+    /// Mutes or unmutes the bus, keeping its volume.
+    pub fn set_muted(&self, muted: bool) {
+        self.gain.muted.store(muted, Ordering::Relaxed);
+    }
+
+    /// The effects applied to the sounds of the bus, before the effects of the master bus.
+    pub fn effects(&self) -> &EffectChain {
+        &self.effects
+    }
+
+    /// The volume applied to the sounds of the bus, including mute and the master bus.
+    pub fn effective_volume(&self) -> f32 {
+        self.gain.audible_volume()
+            * self
+                .master
+                .as_ref()
+                .map_or(1.0, |master| master.audible_volume())
+    }
+}
+
+/// The resource holding the mixer buses, for example for the audio options of a game.
+///
+/// Has a master bus and the `music`, `sfx` and `voice` buses by default.
+/// It's inserted by the `AudioSystem` and by `init_output`.
+#[derive(Debug)]
+pub struct Mixer {
+    master: Bus,
+    buses: HashMap<String, Bus>,
+}
+
+impl Default for Mixer {
+    fn default() -> Self {
+        let mut mixer = Mixer {
+            master: Bus {
+                name: MASTER_BUS.into(),
+                gain: Arc::new(Gain::new()),
+                master: None,
+                effects: EffectChain::new(),
+                master_effects: None,
+            },
+            buses: HashMap::new(),
+        };
+        mixer.add_bus(MUSIC_BUS);
+        mixer.add_bus(SFX_BUS);
+        mixer.add_bus(VOICE_BUS);
+        mixer
+    }
+}
+
+impl Mixer {
+    /// Creates a mixer with the default buses.
+    pub fn new() -> Self {
+        Mixer::default()
+    }
+
+    /// The master bus, scaling all other buses.
+    pub fn master(&self) -> &Bus {
+        &self.master
+    }
+
+    /// The music bus.
+    pub fn music(&self) -> &Bus {
+        self.bus(MUSIC_BUS)
+            .expect("The music bus is always present")
+    }
+
+    /// The sound effects bus.
+    pub fn sfx(&self) -> &Bus {
+        self.bus(SFX_BUS).expect("The sfx bus is always present")
+    }
+
+    /// The voice bus.
+    pub fn voice(&self) -> &Bus {
+        self.bus(VOICE_BUS)
+            .expect("The voice bus is always present")
+    }
+
+    /// Gets a bus by name, including the master bus.
+    pub fn bus(&self, name: &str) -> Option<&Bus> {
+        if name == MASTER_BUS {
+            Some(&self.master)
+        } else {
+            self.buses.get(name)
+        }
+    }
+
+    /// Adds a bus feeding into the master bus, or returns the existing bus with that name.
+    pub fn add_bus(&mut self, name: &str) -> &Bus {
+        if name == MASTER_BUS {
+            return &self.master;
+        }
+        let master = self.master.gain.clone();
+        let master_effects = self.master.effects.clone();
+        self.buses.entry(name.to_string()).or_insert_with(|| Bus {
+            name: name.into(),
+            gain: Arc::new(Gain::new()),
+            master: Some(master),
+            effects: EffectChain::new(),
+            master_effects: Some(master_effects),
+        })
+    }
+
+    /// Iterates over the buses, excluding the master bus.
+    pub fn buses(&self) -> impl Iterator<Item = &Bus> {
+        self.buses.values()
+    }
+}
+
+/// Applies the effects and the volume of a bus to a source, ramping smoothly to volume changes.
+pub(crate) struct BusSource<I> {
+    input: EffectSource<EffectSource<I>>,
+    bus: Bus,
+    gain: f32,
+    target: f32,
+    step: f32,
+    countdown: usize,
+}
+
+impl<I> BusSource<I>
+where
+    I: RSource,
+    I::Item: Sample,
+{
+    pub fn new(input: I, bus: Bus) -> Self {
+        let gain = bus.effective_volume();
+        let samples_per_sec = input.sample_rate() as f32 * f32::from(input.channels().max(1));
+        let master_effects = bus.master_effects.clone().unwrap_or_default();
+        BusSource {
+            input: EffectSource::new(
+                EffectSource::new(input, bus.effects.clone()),
+                master_effects,
+            ),
+            bus,
+            gain,
+            target: gain,
+            step: 1.0 / (samples_per_sec * RAMP_SECS).max(1.0),
+            countdown: REFRESH_SAMPLES,
+        }
+    }
+}
+
+impl<I> Iterator for BusSource<I>
+where
+    I: RSource,
+    I::Item: Sample,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.countdown == 0 {
+            self.countdown = REFRESH_SAMPLES;
+            self.target = self.bus.effective_volume();
+        }
+        self.countdown -= 1;
+        if self.gain < self.target {
+            self.gain = (self.gain + self.step).min(self.target);
+        } else if self.gain > self.target {
+            self.gain = (self.gain - self.step).max(self.target);
+        }
+        self.input.next().map(|sample| sample * self.gain)
+    }
+}
+
+impl<I> RSource for BusSource<I>
+where
+    I: RSource,
+    I::Item: Sample,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        self.input.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rodio::buffer::SamplesBuffer;
+
+    use super::*;
+
+    #[test]
+    fn muting_master_ramps_all_buses_to_silence() {
+        let mixer = Mixer::new();
+        mixer.sfx().set_volume(0.5);
+        let mut source = BusSource::new(
+            SamplesBuffer::new(1, 1000, vec![1.0f32; 100]),
+            mixer.sfx().clone(),
+        );
+        assert_eq!(source.next(), Some(0.5));
+
+        mixer.master().set_muted(true);
+        assert_eq!(mixer.sfx().effective_volume(), 0.0);
+        let samples = source.collect::<Vec<_>>();
+        assert_eq!(samples.last(), Some(&0.0));
+        assert!(samples
+            .windows(2)
+            .all(|pair| pair[0] - pair[1] <= 0.1 + 1e-6));
+    }
+}
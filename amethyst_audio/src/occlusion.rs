@@ -0,0 +1,130 @@
+//! Provides the occlusion of sounds by obstacles between emitters and the listener.
+
+use amethyst_core::{
+    ecs::prelude::Entity,
+    math::{Matrix4, Point3, Vector3},
+};
+
+use crate::components::AudioOccluder;
+
+/// Cutoff frequency of unoccluded sounds, above what can be heard.
+pub(crate) const OPEN_CUTOFF: f32 = 20_000.0;
+
+/// Computes how much the sound of an emitter is occluded on its way to the listener, from 0.0
+/// (not at all) to 1.0 (fully). Gets the emitter entity, the position of the listener and the
+/// position of the emitter.
+pub type OcclusionCallback = dyn Fn(Entity, Vector3<f32>, Vector3<f32>) -> f32 + Send + Sync;
+
+/// The resource configuring how occluded sounds are played by the `AudioSystem`.
+///
+/// By default the occlusion is computed from the `AudioOccluder` boxes between the listener and
+/// an emitter. A callback replaces this heuristic, for example with a raycast against the
+/// physics world.
+pub struct Occlusion {
+    /// The volume of fully occluded sounds, 1.0 being unchanged.
+    pub gain: f32,
+    /// The cutoff frequency of the low-pass filter applied to fully occluded sounds, in hertz.
+    pub cutoff: f32,
+    callback: Option<Box<OcclusionCallback>>,
+}
+
+impl Default for Occlusion {
+    fn default() -> Self {
+        Occlusion {
+            gain: 0.3,
+            cutoff: 800.0,
+            callback: None,
+        }
+    }
+}
+
+impl Occlusion {
+    /// Computes the occlusion with the given callback instead of the `AudioOccluder`s.
+    pub fn set_callback<F>(&mut self, callback: F)
+    where
+        F: Fn(Entity, Vector3<f32>, Vector3<f32>) -> f32 + Send + Sync + 'static,
+    {
+        self.callback = Some(Box::new(callback));
+    }
+
+    /// Goes back to computing the occlusion from the `AudioOccluder`s.
+    pub fn clear_callback(&mut self) {
+        self.callback = None;
+    }
+
+    pub(crate) fn callback(&self) -> Option<&OcclusionCallback> {
+        self.callback.as_ref().map(|callback| &**callback)
+    }
+
+    /// The volume factor of a sound with the given occlusion.
+    pub(crate) fn gain_for(&self, occlusion: f32) -> f32 {
+        1.0 - occlusion * (1.0 - self.gain.max(0.0).min(1.0))
+    }
+
+    /// The low-pass cutoff frequency of a sound with the given occlusion, interpolated on a
+    /// logarithmic scale so that the filter closes evenly to the ear.
+    pub(crate) fn cutoff_for(&self, occlusion: f32) -> f32 {
+        let cutoff = self.cutoff.max(1.0).min(OPEN_CUTOFF);
+        OPEN_CUTOFF * (cutoff / OPEN_CUTOFF).powf(occlusion)
+    }
+}
+
+/// Computes the occlusion of the segment from `from` to `to` by an occluder with the given global
+/// transform.
+pub(crate) fn box_occlusion(
+    from: Vector3<f32>,
+    to: Vector3<f32>,
+    transform: &Matrix4<f32>,
+    occluder: &AudioOccluder,
+) -> f32 {
+    let inverse = match transform.try_inverse() {
+        Some(inverse) => inverse,
+        None => return 0.0,
+    };
+    let local_from = inverse.transform_point(&Point3::from(from)).coords;
+    let local_to = inverse.transform_point(&Point3::from(to)).coords;
+    let direction = local_to - local_from;
+    // Clips the segment against the slabs of the box, in parameters along the segment.
+    let (mut enter, mut exit) = (0.0f32, 1.0f32);
+    for axis in 0..3 {
+        let half = occluder.half_extents[axis].abs();
+        if direction[axis].abs() < std::f32::EPSILON {
+            if local_from[axis].abs() > half {
+                return 0.0;
+            }
+        } else {
+            let a = (-half - local_from[axis]) / direction[axis];
+            let b = (half - local_from[axis]) / direction[axis];
+            enter = enter.max(a.min(b));
+            exit = exit.min(a.max(b));
+        }
+    }
+    if exit <= enter {
+        return 0.0;
+    }
+    // Parameters are preserved by the transform, so they scale the length of the world segment.
+    let thickness = (exit - enter) * (to - from).norm();
+    (thickness * occluder.absorption).max(0.0).min(1.0)
+}
+
+/// Combines the occlusions of several obstacles, each letting through a part of what remains.
+pub(crate) fn combine(occlusions: impl Iterator<Item = f32>) -> f32 {
+    1.0 - occlusions.fold(1.0, |open, occlusion| open * (1.0 - occlusion))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wall_between_listener_and_emitter() {
+        let wall = AudioOccluder::new(Vector3::new(0.25, 5.0, 5.0)).with_absorption(1.0);
+        let transform = Matrix4::new_translation(&Vector3::new(2.0, 0.0, 0.0));
+        let listener = Vector3::zeros();
+        let behind = box_occlusion(listener, Vector3::new(4.0, 0.0, 0.0), &transform, &wall);
+        assert!((behind - 0.5).abs() < 1e-5);
+        let beside = box_occlusion(listener, Vector3::new(0.0, 0.0, 4.0), &transform, &wall);
+        assert!(beside.abs() < 1e-6);
+        assert!((combine(vec![0.5, 0.5].into_iter()) - 0.75).abs() < 1e-6);
+    }
+}
@@ -12,7 +12,14 @@ use rodio::{default_output_device, output_devices, Decoder, Device, Sink, Source
 
 use amethyst_core::shred::Resources;
 
-use crate::{sink::AudioSink, source::Source, DecoderError};
+use crate::{
+    clock::MusicClock,
+    controller::AudioController,
+    mixer::{Bus, BusSource, Mixer},
+    sink::AudioSink,
+    source::Source,
+    DecoderError,
+};
 
 /// A speaker(s) through which audio can be played.
 ///
@@ -84,6 +91,37 @@ impl Output {
         sink.detach();
         Ok(())
     }
+
+    /// Play a sound once on a bus of the `Mixer`. A volume of 1.0 is unchanged, while 0.0 is
+    /// silent.
+    ///
+    /// This will return an Error if the loaded audio file in source could not be decoded.
+    pub fn try_play_once_on(
+        &self,
+        source: &Source,
+        volume: f32,
+        bus: &Bus,
+    ) -> Result<(), DecoderError> {
+        let sink = Sink::new(&self.device);
+        sink.append(BusSource::new(
+            Decoder::new(Cursor::new(source.clone()))
+                .map_err(|_| DecoderError)?
+                .amplify(volume),
+            bus.clone(),
+        ));
+        sink.detach();
+        Ok(())
+    }
+
+    /// Play a sound once on a bus of the `Mixer`. A volume of 1.0 is unchanged, while 0.0 is
+    /// silent.
+    ///
+    /// This may silently fail, in order to get error information use `try_play_once_on`.
+    pub fn play_once_on(&self, source: &Source, volume: f32, bus: &Bus) {
+        if let Err(err) = self.try_play_once_on(source, volume, bus) {
+            error!("An error occurred while trying to play a sound: {:?}", err);
+        }
+    }
 }
 
 impl Debug for Output {
@@ -120,10 +158,24 @@ pub fn outputs() -> OutputIterator {
 }
 
 /// Initialize default output
+///
+/// The `AudioSink` plays on the music bus of the `Mixer`, and its clock is added as the
+/// `MusicClock` resource.
 pub fn init_output(res: &mut Resources) {
     if let Some(o) = default_output() {
-        res.entry::<AudioSink>()
-            .or_insert_with(|| AudioSink::new(&o));
+        res.entry::<AudioController>()
+            .or_insert_with(|| AudioController::new(&o));
+        let music = res
+            .entry::<Mixer>()
+            .or_insert_with(Mixer::default)
+            .music()
+            .clone();
+        let clock = res
+            .entry::<AudioSink>()
+            .or_insert_with(|| AudioSink::new(&o).with_bus(music))
+            .clock()
+            .clone();
+        res.entry::<MusicClock>().or_insert_with(|| clock);
         res.entry::<Output>().or_insert_with(|| o);
     } else {
         error!("Failed finding a default audio output to hook AudioSink to, audio will not work!")
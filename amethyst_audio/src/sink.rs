@@ -2,11 +2,20 @@ use std::io::Cursor;
 
 use rodio::{Decoder, Sink};
 
-use crate::{output::Output, source::Source, DecoderError};
+use crate::{
+    clock::MusicClock,
+    mixer::{Bus, BusSource},
+    output::Output,
+    source::Source,
+    stream::StreamSource,
+    DecoderError,
+};
 
 /// This structure provides a way to programmatically pick and play music.
 pub struct AudioSink {
     sink: Sink,
+    bus: Option<Bus>,
+    clock: MusicClock,
 }
 
 impl AudioSink {
@@ -14,13 +23,56 @@ impl AudioSink {
     pub fn new(output: &Output) -> AudioSink {
         AudioSink {
             sink: Sink::new(&output.device),
+            bus: None,
+            clock: MusicClock::default(),
         }
     }
 
+    /// Plays the music of the sink on a bus of the `Mixer`.
+    pub fn with_bus(mut self, bus: Bus) -> Self {
+        self.bus = Some(bus);
+        self
+    }
+
+    /// Changes the bus music appended from now on is played on.
+    pub fn set_bus(&mut self, bus: Option<Bus>) {
+        self.bus = bus;
+    }
+
+    /// The bus music of this sink is played on.
+    pub fn bus(&self) -> Option<&Bus> {
+        self.bus.as_ref()
+    }
+
     /// Adds a source to the sink's queue of music to play.
     pub fn append(&self, source: &Source) -> Result<(), DecoderError> {
-        self.sink
-            .append(Decoder::new(Cursor::new(source.clone())).map_err(|_| DecoderError)?);
+        let decoder = Decoder::new(Cursor::new(source.clone())).map_err(|_| DecoderError)?;
+        match self.bus {
+            Some(ref bus) => self.sink.append(BusSource::new(decoder, bus.clone())),
+            None => self.sink.append(decoder),
+        }
+        Ok(())
+    }
+
+    /// The clock following the streams with a tempo played by this sink.
+    pub fn clock(&self) -> &MusicClock {
+        &self.clock
+    }
+
+    /// Adds a stream to the sink's queue of music to play. The file is decoded while it plays.
+    ///
+    /// If the stream has a tempo, the `MusicClock` of the sink follows it while it plays.
+    pub fn append_stream(&self, stream: &StreamSource) -> Result<(), DecoderError> {
+        let decoder = stream.decoder()?;
+        match (stream.tempo, &self.bus) {
+            (Some(tempo), Some(bus)) => self.sink.append(BusSource::new(
+                self.clock.track(decoder, tempo),
+                bus.clone(),
+            )),
+            (Some(tempo), None) => self.sink.append(self.clock.track(decoder, tempo)),
+            (None, Some(bus)) => self.sink.append(BusSource::new(decoder, bus.clone())),
+            (None, None) => self.sink.append(decoder),
+        }
         Ok(())
     }
 
@@ -0,0 +1,399 @@
+//! Provides the positional audio model: distance attenuation, panning and the Doppler effect.
+
+use std::{
+    f32::consts::FRAC_PI_4,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use rodio::{Sample, Source as RSource};
+use serde::{Deserialize, Serialize};
+
+use amethyst_core::math::Vector3;
+
+use crate::occlusion::OPEN_CUTOFF;
+
+/// Length of the frames reported to rodio, after which a pitch change is applied.
+const REFRESH_FRAMES: usize = 32;
+/// Duration of the ramp to new gains, so that moving sounds don't click.
+const RAMP_SECS: f32 = 0.02;
+
+/// How the volume of a sound decreases with the distance to the listener.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub enum Attenuation {
+    /// The volume doesn't depend on the distance.
+    None,
+    /// The volume decreases linearly from full volume at the minimum distance to silence at the
+    /// maximum distance.
+    Linear,
+    /// The volume is `min_distance / (min_distance + rolloff * (distance - min_distance))`,
+    /// the physically plausible model.
+    Inverse {
+        /// How fast the volume decreases, 1.0 being realistic.
+        rolloff: f32,
+    },
+    /// The volume is `(distance / min_distance) ^ -rolloff`.
+    Exponential {
+        /// How fast the volume decreases.
+        rolloff: f32,
+    },
+}
+
+/// Describes the attenuation of sounds over distance.
+///
+/// Distances are clamped between `min_distance` and `max_distance`, so sounds closer than
+/// `min_distance` play at full volume and sounds further away than `max_distance` don't get any
+/// quieter, except for `Attenuation::Linear`, where they are silent.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub struct DistanceModel {
+    /// The attenuation curve.
+    pub attenuation: Attenuation,
+    /// Distance up to which sounds play at full volume.
+    pub min_distance: f32,
+    /// Distance from which sounds don't get quieter.
+    pub max_distance: f32,
+}
+
+impl Default for DistanceModel {
+    fn default() -> Self {
+        DistanceModel {
+            attenuation: Attenuation::Inverse { rolloff: 1.0 },
+            min_distance: 1.0,
+            max_distance: 100.0,
+        }
+    }
+}
+
+impl DistanceModel {
+    /// The volume of a sound at `distance` from the listener, between 0.0 and 1.0.
+    pub fn gain(&self, distance: f32) -> f32 {
+        let min = self.min_distance.max(std::f32::EPSILON);
+        let max = self.max_distance.max(min);
+        let distance = distance.max(min).min(max);
+        let gain = match self.attenuation {
+            Attenuation::None => 1.0,
+            Attenuation::Linear if max > min => 1.0 - (distance - min) / (max - min),
+            Attenuation::Linear => 1.0,
+            Attenuation::Inverse { rolloff } => min / (min + rolloff * (distance - min)),
+            Attenuation::Exponential { rolloff } => (distance / min).powf(-rolloff),
+        };
+        gain.max(0.0).min(1.0)
+    }
+}
+
+/// The resource with the settings of positional audio, used by the `AudioSystem`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SpatialAudio {
+    /// The attenuation of the emitters without a distance model of their own.
+    pub distance_model: DistanceModel,
+    /// The speed of sound, in world units per second. Defaults to 343, meters in air.
+    pub speed_of_sound: f32,
+    /// Scales the Doppler effect, 0.0 disables it and 1.0 is realistic.
+    pub doppler_factor: f32,
+}
+
+impl Default for SpatialAudio {
+    fn default() -> Self {
+        SpatialAudio {
+            distance_model: DistanceModel::default(),
+            speed_of_sound: 343.0,
+            doppler_factor: 1.0,
+        }
+    }
+}
+
+impl SpatialAudio {
+    /// The pitch factor of a sound emitted at `emitter` moving with `emitter_velocity`, heard at
+    /// `listener` moving with `listener_velocity`.
+    pub fn doppler_pitch(
+        &self,
+        emitter: Vector3<f32>,
+        emitter_velocity: Vector3<f32>,
+        listener: Vector3<f32>,
+        listener_velocity: Vector3<f32>,
+    ) -> f32 {
+        let to_listener = listener - emitter;
+        let distance = to_listener.norm();
+        if self.doppler_factor <= 0.0 || self.speed_of_sound <= 0.0 || distance < std::f32::EPSILON
+        {
+            return 1.0;
+        }
+        let axis = to_listener / distance;
+        // Speeds towards the other side on the axis between them, clamped below the speed of
+        // sound so that the pitch stays finite and positive.
+        let limit = self.speed_of_sound / self.doppler_factor * 0.99;
+        let listener_speed = (-axis.dot(&listener_velocity)).max(-limit).min(limit);
+        let emitter_speed = axis.dot(&emitter_velocity).max(-limit).min(limit);
+        (self.speed_of_sound + self.doppler_factor * listener_speed)
+            / (self.speed_of_sound - self.doppler_factor * emitter_speed)
+    }
+}
+
+/// Computes the left and right gains of a sound at `emitter`, heard by the ears of a listener.
+pub(crate) fn ear_gains(
+    emitter: Vector3<f32>,
+    left_ear: Vector3<f32>,
+    right_ear: Vector3<f32>,
+    model: &DistanceModel,
+) -> (f32, f32) {
+    let center = (left_ear + right_ear) / 2.0;
+    let to_emitter = emitter - center;
+    let distance = to_emitter.norm();
+    let ears = right_ear - left_ear;
+    let pan = if distance < std::f32::EPSILON || ears.norm() < std::f32::EPSILON {
+        0.0
+    } else {
+        to_emitter.dot(&ears) / (distance * ears.norm())
+    };
+    // Equal power panning, normalized to full volume in the center.
+    let angle = (pan + 1.0) * FRAC_PI_4;
+    let gain = model.gain(distance) * std::f32::consts::SQRT_2;
+    (angle.cos() * gain, angle.sin() * gain)
+}
+
+/// The spatial parameters of a playing sound, written by the `AudioSystem` and read on the audio
+/// thread.
+#[derive(Debug)]
+pub(crate) struct SpatialState {
+    // Bits of `f32`s, so that the audio thread can read them without locking.
+    left: AtomicUsize,
+    right: AtomicUsize,
+    pitch: AtomicUsize,
+    cutoff: AtomicUsize,
+    pub stopped: AtomicBool,
+    pub finished: AtomicBool,
+}
+
+impl SpatialState {
+    pub fn new((left, right): (f32, f32), pitch: f32) -> Arc<Self> {
+        let state = SpatialState {
+            left: AtomicUsize::new(0),
+            right: AtomicUsize::new(0),
+            pitch: AtomicUsize::new(0),
+            cutoff: AtomicUsize::new(OPEN_CUTOFF.to_bits() as usize),
+            stopped: AtomicBool::new(false),
+            finished: AtomicBool::new(false),
+        };
+        state.set((left, right), pitch);
+        Arc::new(state)
+    }
+
+    pub fn set(&self, (left, right): (f32, f32), pitch: f32) {
+        self.left.store(left.to_bits() as usize, Ordering::Relaxed);
+        self.right
+            .store(right.to_bits() as usize, Ordering::Relaxed);
+        self.pitch
+            .store(pitch.max(0.01).to_bits() as usize, Ordering::Relaxed);
+    }
+
+    /// Sets the cutoff frequency of the low-pass filter of occluded sounds.
+    pub fn set_cutoff(&self, cutoff: f32) {
+        self.cutoff
+            .store(cutoff.to_bits() as usize, Ordering::Relaxed);
+    }
+
+    /// The volume of the louder ear.
+    pub fn loudness(&self) -> f32 {
+        Self::load(&self.left).max(Self::load(&self.right))
+    }
+
+    fn load(atomic: &AtomicUsize) -> f32 {
+        f32::from_bits(atomic.load(Ordering::Relaxed) as u32)
+    }
+}
+
+/// Plays a source in stereo with the gains and pitch of its `SpatialState`.
+///
+/// Multichannel input is mixed down to mono first, like a point emitter, and low-pass filtered
+/// when occluded. Once stopped, the source fades out and ends.
+pub(crate) struct SpatialSource<I>
+where
+    I: RSource,
+    I::Item: Sample,
+{
+    input: I,
+    state: Arc<SpatialState>,
+    left: f32,
+    right: f32,
+    step: f32,
+    // One-pole low-pass filter.
+    filtered: f32,
+    coefficient: f32,
+    current: Option<f32>,
+    right_channel: bool,
+    countdown: usize,
+}
+
+impl<I> SpatialSource<I>
+where
+    I: RSource,
+    I::Item: Sample,
+{
+    pub fn new(input: I, state: Arc<SpatialState>) -> Self {
+        let step = 1.0 / (input.sample_rate() as f32 * RAMP_SECS).max(1.0);
+        SpatialSource {
+            left: SpatialState::load(&state.left),
+            right: SpatialState::load(&state.right),
+            input,
+            state,
+            step,
+            filtered: 0.0,
+            coefficient: 1.0,
+            current: None,
+            right_channel: false,
+            countdown: 0,
+        }
+    }
+
+    fn update_filter(&mut self) {
+        let cutoff = SpatialState::load(&self.state.cutoff);
+        let rate = self.input.sample_rate() as f32;
+        self.coefficient = if cutoff >= OPEN_CUTOFF || cutoff * 2.0 >= rate {
+            1.0
+        } else {
+            1.0 - (-2.0 * std::f32::consts::PI * cutoff / rate).exp()
+        };
+    }
+
+    fn ramp(value: f32, target: f32, step: f32) -> f32 {
+        if value < target {
+            (value + step).min(target)
+        } else {
+            (value - step).max(target)
+        }
+    }
+}
+
+impl<I> Iterator for SpatialSource<I>
+where
+    I: RSource,
+    I::Item: Sample,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.right_channel {
+            self.right_channel = false;
+            return self.current.map(|sample| sample * self.right);
+        }
+        if self.countdown == 0 {
+            self.countdown = REFRESH_FRAMES;
+            self.update_filter();
+        }
+        self.countdown -= 1;
+        let channels = self.input.channels().max(1);
+        let mut sum = 0.0;
+        for _ in 0..channels {
+            sum += self.input.next()?.to_f32();
+        }
+        self.filtered += self.coefficient * (sum / f32::from(channels) - self.filtered);
+        let sample = self.filtered;
+        let (left, right) = if self.state.stopped.load(Ordering::Relaxed) {
+            if self.left <= 0.0 && self.right <= 0.0 {
+                return None;
+            }
+            (0.0, 0.0)
+        } else {
+            (
+                SpatialState::load(&self.state.left),
+                SpatialState::load(&self.state.right),
+            )
+        };
+        self.left = Self::ramp(self.left, left, self.step);
+        self.right = Self::ramp(self.right, right, self.step);
+        self.current = Some(sample);
+        self.right_channel = true;
+        Some(sample * self.left)
+    }
+}
+
+impl<I> RSource for SpatialSource<I>
+where
+    I: RSource,
+    I::Item: Sample,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        // Frames are kept short, so that pitch changes apply right away.
+        Some(if self.right_channel {
+            self.countdown * 2 + 1
+        } else if self.countdown == 0 {
+            REFRESH_FRAMES * 2
+        } else {
+            self.countdown * 2
+        })
+    }
+
+    fn channels(&self) -> u16 {
+        2
+    }
+
+    fn sample_rate(&self) -> u32 {
+        let pitch = SpatialState::load(&self.state.pitch);
+        (self.input.sample_rate() as f32 * pitch).round().max(1.0) as u32
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn attenuation_curves() {
+        let mut model = DistanceModel {
+            attenuation: Attenuation::Linear,
+            min_distance: 2.0,
+            max_distance: 10.0,
+        };
+        assert!((model.gain(1.0) - 1.0).abs() < 1e-6);
+        assert!((model.gain(6.0) - 0.5).abs() < 1e-6);
+        assert!(model.gain(20.0).abs() < 1e-6);
+
+        model.attenuation = Attenuation::Inverse { rolloff: 1.0 };
+        assert!((model.gain(4.0) - 0.5).abs() < 1e-6);
+        assert!((model.gain(20.0) - 0.2).abs() < 1e-6);
+
+        model.attenuation = Attenuation::Exponential { rolloff: 2.0 };
+        assert!((model.gain(4.0) - 0.25).abs() < 1e-6);
+    }
+
+    #[test]
+    fn approaching_emitters_sound_higher() {
+        let spatial = SpatialAudio::default();
+        let still = Vector3::zeros();
+        let listener = Vector3::new(10.0, 0.0, 0.0);
+        let pitch = spatial.doppler_pitch(still, Vector3::new(10.0, 0.0, 0.0), listener, still);
+        assert!((pitch - 343.0 / 333.0).abs() < 1e-5);
+
+        // The listener moving towards the emitter.
+        let pitch = spatial.doppler_pitch(still, still, listener, Vector3::new(-10.0, 0.0, 0.0));
+        assert!((pitch - 353.0 / 343.0).abs() < 1e-5);
+
+        // Faster than sound, clamped.
+        let pitch = spatial.doppler_pitch(still, Vector3::new(1000.0, 0.0, 0.0), listener, still);
+        assert!(pitch.is_finite() && pitch > 1.0);
+    }
+
+    #[test]
+    fn receding_emitters_sound_lower() {
+        let spatial = SpatialAudio::default();
+        let still = Vector3::zeros();
+        let listener = Vector3::new(10.0, 0.0, 0.0);
+        let pitch = spatial.doppler_pitch(still, Vector3::new(-10.0, 0.0, 0.0), listener, still);
+        assert!((pitch - 343.0 / 353.0).abs() < 1e-5);
+
+        // Moving across the axis doesn't change the pitch.
+        let pitch = spatial.doppler_pitch(still, Vector3::new(0.0, 10.0, 0.0), listener, still);
+        assert!((pitch - 1.0).abs() < 1e-6);
+
+        let pitch = spatial.doppler_pitch(still, still, listener, Vector3::new(-1000.0, 0.0, 0.0));
+        assert!(pitch.is_finite() && pitch > 0.0);
+    }
+}
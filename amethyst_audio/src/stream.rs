@@ -0,0 +1,216 @@
+//! Provides structures used to stream long audio files from their source.
+
+use std::{fmt, sync::Arc, time::Duration};
+
+use log::error;
+use rodio::{Decoder, Source as RSource};
+
+use amethyst_assets::{
+    Asset, Handle, Loader, ProcessingState, Source as AssetSource, SourceReader,
+};
+use amethyst_core::ecs::prelude::VecStorage;
+use amethyst_error::Error;
+
+use crate::{clock::Tempo, DecoderError};
+
+/// A handle to a stream asset.
+pub type StreamHandle = Handle<StreamSource>;
+
+/// An audio file which is decoded in small chunks while it plays, instead of being held in
+/// memory as a whole like a `Source`. Meant for background music and other long tracks.
+///
+/// The file is read from an asset source, like the other assets, but only opened once the stream
+/// is played, so a stream asset is created with `Loader::load_from_data` rather than with a
+/// format:
+///
+/// ```rust,ignore
+/// let loader = world.read_resource::<Loader>();
+/// let handle: StreamHandle = loader.load_from_data(
+///     StreamSource::new(&loader, "music/theme.ogg").looping(),
+///     (),
+///     &world.read_resource(),
+/// );
+/// ```
+#[derive(Clone)]
+pub struct StreamSource {
+    /// Path of the audio file in its source.
+    pub path: String,
+    /// Starts the stream over once the end of the file is reached, without a gap.
+    pub looping: bool,
+    /// The tempo of the track, followed by the `MusicClock` while the `AudioSink` plays it.
+    pub tempo: Option<Tempo>,
+    source: Arc<dyn AssetSource>,
+}
+
+impl fmt::Debug for StreamSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StreamSource")
+            .field("path", &self.path)
+            .field("looping", &self.looping)
+            .field("tempo", &self.tempo)
+            .finish()
+    }
+}
+
+impl StreamSource {
+    /// Creates a stream playing the file at `path` of the default source of the `Loader` once.
+    pub fn new<P: Into<String>>(loader: &Loader, path: P) -> Self {
+        let source = loader
+            .get_source("")
+            .expect("Unreachable: The loader always has a default source");
+        StreamSource::from_source(source, path)
+    }
+
+    /// Creates a stream playing the file at `path` of `source` once, like a source added with
+    /// `Loader::add_source` and returned by `Loader::get_source`.
+    pub fn from_source<P: Into<String>>(source: Arc<dyn AssetSource>, path: P) -> Self {
+        StreamSource {
+            path: path.into(),
+            looping: false,
+            tempo: None,
+            source,
+        }
+    }
+
+    /// Makes the stream loop seamlessly.
+    pub fn looping(mut self) -> Self {
+        self.looping = true;
+        self
+    }
+
+    /// Sets the tempo of the track, for the `MusicClock`.
+    pub fn with_tempo(mut self, tempo: Tempo) -> Self {
+        self.tempo = Some(tempo);
+        self
+    }
+
+    /// Opens the file and creates the decoder for the stream.
+    pub(crate) fn decoder(&self) -> Result<StreamDecoder, DecoderError> {
+        Ok(StreamDecoder {
+            decoder: open(&*self.source, &self.path)?,
+            source: self.source.clone(),
+            path: self.path.clone(),
+            looping: self.looping,
+            looped: false,
+        })
+    }
+}
+
+impl Asset for StreamSource {
+    const NAME: &'static str = "audio::StreamSource";
+    type Data = StreamSource;
+    type HandleStorage = VecStorage<StreamHandle>;
+}
+
+impl Into<Result<ProcessingState<StreamSource>, Error>> for StreamSource {
+    fn into(self) -> Result<ProcessingState<StreamSource>, Error> {
+        Ok(ProcessingState::Loaded(self))
+    }
+}
+
+fn open(
+    source: &dyn AssetSource,
+    path: &str,
+) -> Result<Decoder<Box<dyn SourceReader>>, DecoderError> {
+    let reader = source.open(path).map_err(|_| DecoderError)?;
+    Decoder::new(reader).map_err(|_| DecoderError)
+}
+
+/// Decodes a stream while the audio thread pulls samples from it.
+pub(crate) struct StreamDecoder {
+    decoder: Decoder<Box<dyn SourceReader>>,
+    source: Arc<dyn AssetSource>,
+    path: String,
+    looping: bool,
+    looped: bool,
+}
+
+impl StreamDecoder {
+    /// Starts decoding the file from the beginning again.
+    pub fn restart(&mut self) -> Result<(), DecoderError> {
+        self.decoder = open(&*self.source, &self.path)?;
+        Ok(())
+    }
+
+    /// Returns true once after the stream looped back to the start of the file.
+    pub fn take_looped(&mut self) -> bool {
+        std::mem::replace(&mut self.looped, false)
+    }
+}
+
+impl Iterator for StreamDecoder {
+    type Item = i16;
+
+    fn next(&mut self) -> Option<i16> {
+        match self.decoder.next() {
+            Some(sample) => Some(sample),
+            None if self.looping => {
+                // The file is reopened right when the last sample was consumed, so the first
+                // sample of the next loop directly follows it.
+                match open(&*self.source, &self.path) {
+                    Ok(decoder) => {
+                        self.decoder = decoder;
+                        self.looped = true;
+                        self.decoder.next()
+                    }
+                    Err(err) => {
+                        error!("Failed to reopen {:?} for looping: {}", self.path, err);
+                        None
+                    }
+                }
+            }
+            None => None,
+        }
+    }
+}
+
+impl RSource for StreamDecoder {
+    fn current_frame_len(&self) -> Option<usize> {
+        self.decoder.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.decoder.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.decoder.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        if self.looping {
+            None
+        } else {
+            self.decoder.total_duration()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_assets::Directory;
+    use amethyst_utils::app_root_dir::application_root_dir;
+
+    use super::*;
+
+    #[test]
+    fn streams_are_read_from_their_source_and_loop() {
+        let tests = application_root_dir().unwrap().join("tests");
+        let source = Arc::new(Directory::new(tests)) as Arc<dyn AssetSource>;
+        let stream = StreamSource::from_source(source.clone(), "sound_test.wav");
+        let samples = stream.decoder().unwrap().collect::<Vec<_>>();
+        assert!(!samples.is_empty());
+
+        let mut decoder = stream.looping().decoder().unwrap();
+        assert_eq!(decoder.total_duration(), None);
+        let played = decoder.by_ref().take(samples.len()).collect::<Vec<_>>();
+        assert_eq!(played, samples);
+        assert!(!decoder.take_looped());
+        // The first sample of the next loop directly follows the last one.
+        assert_eq!(decoder.next(), Some(samples[0]));
+        assert!(decoder.take_looped());
+
+        let missing = StreamSource::from_source(source, "missing.wav");
+        assert!(missing.decoder().is_err());
+    }
+}
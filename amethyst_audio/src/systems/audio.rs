@@ -1,38 +1,65 @@
 use std::{
     iter::Iterator,
     mem::replace,
-    sync::{
-        atomic::{AtomicBool, Ordering},
-        Arc,
-    },
+    sync::{atomic::Ordering, Arc},
 };
 
-use rodio::SpatialSink;
+use rodio::Sink;
 
 #[cfg(feature = "profiler")]
 use thread_profiler::profile_scope;
 
 use amethyst_core::{
     ecs::prelude::{
-        Entities, Entity, Join, Read, ReadStorage, Resources, System, SystemData, WriteStorage,
+        Entities, Entity, Join, Read, ReadStorage, Resources, System, SystemData, Write,
+        WriteStorage,
     },
+    math::Vector3,
+    shrev::EventChannel,
+    timing::Time,
     transform::GlobalTransform,
 };
 
 use crate::{
-    components::{AudioEmitter, AudioListener},
+    components::{AudioEmitter, AudioListener, AudioOccluder, PlayingSound, QueuedSound},
+    controller::{AudioController, Playback},
+    effects::EffectSource,
     end_signal::EndSignalSource,
+    event::AudioFinished,
+    mixer::{Bus, BusSource, Mixer},
+    occlusion::{box_occlusion, combine, Occlusion},
     output::Output,
+    spatial::{ear_gains, SpatialAudio, SpatialSource, SpatialState},
+    voices::{voices_to_steal, VoiceLimit},
 };
 
 /// Syncs 3D transform data with the audio engine to provide 3D audio.
+///
+/// The velocities of emitters and the listener are derived from the movement of their transforms
+/// between frames, for the Doppler effect. The `SpatialAudio` resource configures the distance
+/// attenuation and the Doppler effect.
+///
+/// Emitters are heard through the listener of the `SelectedListener` resource, the listeners of
+/// the `ListenerBlend` resource, or else the first `AudioListener` found.
+///
+/// Sounds blocked by `AudioOccluder`s, or by the callback of the `Occlusion` resource, are
+/// attenuated and low-pass filtered.
+///
+/// Sends an `AudioFinished` event for every finished sound of an emitter or of the
+/// `AudioController`, and stops sounds above the `VoiceLimit`.
 #[derive(Default)]
-pub struct AudioSystem(Output);
+pub struct AudioSystem {
+    output: Output,
+    listeners: Vec<(Entity, Vector3<f32>)>,
+}
 
 impl AudioSystem {
     /// Produces a new AudioSystem that uses the given output.
     pub fn new(output: Output) -> AudioSystem {
-        AudioSystem(output)
+        AudioSystem {
+            output,
+            listeners: Vec::new(),
+        }
     }
 }
 
@@ -41,84 +68,430 @@ impl AudioSystem {
 /// the first AudioListener it finds.
 pub struct SelectedListener(pub Entity);
 
+/// Add this resource to hear through several `AudioListener`s at once, each with a weight, for
+/// example for split-screen or to crossfade between two cameras. Emitters are heard as the
+/// weighted blend of what each listener hears. Takes precedence over `SelectedListener` when
+/// not empty.
+#[derive(Clone, Debug, Default)]
+pub struct ListenerBlend {
+    /// The listener entities and their weights.
+    pub listeners: Vec<(Entity, f32)>,
+}
+
+/// The listeners heard through, with their transforms and their weights, which add up to 1.0.
+///
+/// Listeners without a transform can't be heard through, so they are left out before the
+/// weights are normalized.
+pub(crate) fn active_listeners<'a>(
+    selected: Option<&SelectedListener>,
+    blend: Option<&ListenerBlend>,
+    entities: &Entities<'_>,
+    listeners: &'a ReadStorage<'_, AudioListener>,
+    transforms: &'a ReadStorage<'_, GlobalTransform>,
+) -> Vec<(Entity, &'a AudioListener, &'a GlobalTransform, f32)> {
+    let get = move |entity: Entity| Some((entity, listeners.get(entity)?, transforms.get(entity)?));
+    let mut active = blend
+        .map(|blend| {
+            blend
+                .listeners
+                .iter()
+                .filter(|(_, weight)| *weight > 0.0)
+                .filter_map(|&(entity, weight)| {
+                    get(entity)
+                        .map(|(entity, listener, transform)| (entity, listener, transform, weight))
+                })
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    if active.is_empty() {
+        active.extend(
+            selected
+                .and_then(|sl| get(sl.0))
+                .or_else(|| (&**entities, listeners, transforms).join().next())
+                .map(|(entity, listener, transform)| (entity, listener, transform, 1.0)),
+        );
+    }
+    let total = active.iter().map(|(_, _, _, weight)| weight).sum::<f32>();
+    for (_, _, _, weight) in &mut active {
+        *weight /= total;
+    }
+    active
+}
+
+/// The ears of a listener heard through.
+struct Ears {
+    left: Vector3<f32>,
+    right: Vector3<f32>,
+    position: Vector3<f32>,
+    velocity: Vector3<f32>,
+    weight: f32,
+}
+
+fn velocity(
+    previous: Option<Vector3<f32>>,
+    current: Vector3<f32>,
+    delta_seconds: f32,
+) -> Vector3<f32> {
+    match previous {
+        Some(previous) if delta_seconds > 0.0 => (current - previous) / delta_seconds,
+        _ => Vector3::zeros(),
+    }
+}
+
+/// A sound of an emitter waiting for the voice limit to be applied before it starts.
+struct PendingSound {
+    entity: Entity,
+    sound: QueuedSound,
+    bus: Bus,
+    gains: (f32, f32),
+    pitch: f32,
+    cutoff: f32,
+}
+
+/// A sound counting towards the voice limit.
+enum Voice {
+    Emitter(Arc<SpatialState>),
+    Playback(Playback),
+    Pending(usize),
+}
+
 impl<'a> System<'a> for AudioSystem {
     type SystemData = (
         Option<Read<'a, Output>>,
         Option<Read<'a, SelectedListener>>,
+        Option<Read<'a, ListenerBlend>>,
+        Read<'a, Mixer>,
+        Read<'a, SpatialAudio>,
+        Read<'a, Occlusion>,
+        Read<'a, VoiceLimit>,
+        Read<'a, Time>,
         Entities<'a>,
         ReadStorage<'a, GlobalTransform>,
         ReadStorage<'a, AudioListener>,
+        ReadStorage<'a, AudioOccluder>,
         WriteStorage<'a, AudioEmitter>,
+        Option<Write<'a, AudioController>>,
+        Write<'a, EventChannel<AudioFinished>>,
     );
 
     fn run(
         &mut self,
-        (output, select_listener, entities, transform, listener, mut audio_emitter): Self::SystemData,
+        (
+            output,
+            select_listener,
+            listener_blend,
+            mixer,
+            spatial,
+            occlusion,
+            voice_limit,
+            time,
+            entities,
+            transform,
+            listener,
+            occluders,
+            mut audio_emitter,
+            mut controller,
+            mut finished,
+        ): Self::SystemData,
     ) {
         #[cfg(feature = "profiler")]
         profile_scope!("audio_system");
-        // Process emitters and listener.
-        if let Some((listener, entity)) = select_listener
-            .as_ref()
-            .and_then(|sl| listener.get(sl.0).map(|l| (l, sl.0)))
-            .or_else(|| (&listener, &*entities).join().next())
-        {
-            if let Some(listener_transform) = select_listener
-                .as_ref()
-                .and_then(|sl| transform.get(sl.0))
-                .or_else(|| transform.get(entity))
+        let mut voices = Vec::new();
+        let mut pending = Vec::new();
+        if let Some(ref mut controller) = controller {
+            finished.iter_write(
+                controller
+                    .take_finished()
+                    .into_iter()
+                    .map(AudioFinished::Playback),
+            );
+            for playback in controller.playbacks().filter(|p| !p.is_paused()) {
+                let loudness = playback.volume() * playback.bus().effective_volume();
+                voices.push((
+                    (playback.priority(), loudness),
+                    Voice::Playback(playback.clone()),
+                ));
+            }
+        }
+        let delta_seconds = time.delta_seconds();
+        // Process listeners.
+        let mut ears = Vec::new();
+        let mut positions = Vec::new();
+        for (entity, listener, listener_transform, weight) in active_listeners(
+            select_listener.as_ref().map(|sl| &**sl),
+            listener_blend.as_ref().map(|lb| &**lb),
+            &entities,
+            &listener,
+            &transform,
+        ) {
+            let listener_transform = listener_transform.0;
+            let left = listener_transform
+                .transform_point(&listener.left_ear)
+                .to_homogeneous()
+                .xyz();
+            let right = listener_transform
+                .transform_point(&listener.right_ear)
+                .to_homogeneous()
+                .xyz();
+            let position = (left + right) / 2.0;
+            let previous = self
+                .listeners
+                .iter()
+                .find(|(previous, _)| *previous == entity)
+                .map(|(_, position)| *position);
+            positions.push((entity, position));
+            ears.push(Ears {
+                left,
+                right,
+                position,
+                velocity: velocity(previous, position, delta_seconds),
+                weight,
+            });
+        }
+        self.listeners = positions;
+
+        // Process emitters.
+        if !ears.is_empty() {
+            let occluders = (&*entities, &transform, &occluders)
+                .join()
+                .map(|(entity, transform, occluder)| (entity, transform.0, occluder))
+                .collect::<Vec<_>>();
+
+            for (emitter_entity, transform, mut audio_emitter) in
+                (&*entities, &transform, &mut audio_emitter).join()
             {
-                let listener_transform = listener_transform.0;
-                let left_ear_position = listener_transform
-                    .transform_point(&listener.left_ear)
-                    .to_homogeneous()
-                    .xyz();
-                let right_ear_position = listener_transform
-                    .transform_point(&listener.right_ear)
-                    .to_homogeneous()
-                    .xyz();
-                for (transform, mut audio_emitter) in (&transform, &mut audio_emitter).join() {
-                    let x = transform.0[(0, 3)];
-                    let y = transform.0[(1, 3)];
-                    let z = transform.0[(2, 3)];
-                    let emitter_position = [x, y, z];
-                    // Remove all sinks whose sounds have ended.
-                    audio_emitter.sinks.retain(|s| !s.1.load(Ordering::Relaxed));
-                    for &mut (ref mut sink, _) in &mut audio_emitter.sinks {
-                        sink.set_emitter_position(emitter_position);
-                        sink.set_left_ear_position(left_ear_position.into());
-                        sink.set_right_ear_position(right_ear_position.into());
+                let emitter_position = transform.0.column(3).xyz();
+                let emitter_velocity = velocity(
+                    audio_emitter.previous_position,
+                    emitter_position,
+                    delta_seconds,
+                );
+                audio_emitter.previous_position = Some(emitter_position);
+                let model = audio_emitter
+                    .distance_model
+                    .unwrap_or(spatial.distance_model);
+                // The sound heard by several listeners is the weighted blend of what each hears.
+                let mut gains = (0.0, 0.0);
+                let mut pitch = 0.0;
+                let mut occluded = 0.0;
+                for ears in &ears {
+                    let amount = match occlusion.callback() {
+                        Some(callback) => callback(emitter_entity, ears.position, emitter_position),
+                        // An emitter isn't occluded by its own entity.
+                        None => combine(
+                            occluders
+                                .iter()
+                                .filter(|(entity, _, _)| *entity != emitter_entity)
+                                .map(|(_, transform, occluder)| {
+                                    box_occlusion(
+                                        ears.position,
+                                        emitter_position,
+                                        transform,
+                                        occluder,
+                                    )
+                                }),
+                        ),
                     }
-                    if audio_emitter.sinks.is_empty() {
-                        if let Some(mut picker) = replace(&mut audio_emitter.picker, None) {
-                            if picker(&mut audio_emitter) {
-                                audio_emitter.picker = Some(picker);
-                            }
-                        }
+                    .max(0.0)
+                    .min(1.0);
+                    let occlusion_gain = occlusion.gain_for(amount) * ears.weight;
+                    let (left, right) = ear_gains(emitter_position, ears.left, ears.right, &model);
+                    gains.0 += left * occlusion_gain;
+                    gains.1 += right * occlusion_gain;
+                    pitch += spatial.doppler_pitch(
+                        emitter_position,
+                        emitter_velocity,
+                        ears.position,
+                        ears.velocity,
+                    ) * ears.weight;
+                    occluded += amount * ears.weight;
+                }
+                let cutoff = occlusion.cutoff_for(occluded);
+                // Remove all sinks whose sounds have ended.
+                audio_emitter.sinks.retain(|sound| {
+                    let playing = !sound.state.finished.load(Ordering::Relaxed);
+                    if !playing {
+                        finished.single_write(AudioFinished::Emitter {
+                            entity: emitter_entity,
+                            source: sound.handle.clone(),
+                        });
+                    }
+                    playing
+                });
+                for sound in &audio_emitter.sinks {
+                    sound.state.set(
+                        (gains.0 * sound.volume, gains.1 * sound.volume),
+                        pitch * sound.pitch,
+                    );
+                    sound.state.set_cutoff(cutoff);
+                    if !sound.state.stopped.load(Ordering::Relaxed) {
+                        let loudness = sound.state.loudness() * sound.bus.effective_volume();
+                        voices.push((
+                            (sound.priority, loudness),
+                            Voice::Emitter(sound.state.clone()),
+                        ));
                     }
-                    while let Some(source) = audio_emitter.sound_queue.pop() {
-                        if let Some(output) = &output {
-                            let sink = SpatialSink::new(
-                                &output.device,
-                                emitter_position,
-                                left_ear_position.into(),
-                                right_ear_position.into(),
-                            );
-                            let atomic_bool = Arc::new(AtomicBool::new(false));
-                            let clone = atomic_bool.clone();
-                            sink.append(EndSignalSource::new(source, move || {
-                                clone.store(true, Ordering::Relaxed);
-                            }));
-                            audio_emitter.sinks.push((sink, atomic_bool));
+                }
+                if audio_emitter.sinks.is_empty() {
+                    if let Some(mut picker) = replace(&mut audio_emitter.picker, None) {
+                        if picker(&mut audio_emitter) {
+                            audio_emitter.picker = Some(picker);
                         }
                     }
                 }
+                while let Some(sound) = audio_emitter.sound_queue.pop() {
+                    if output.is_none() {
+                        continue;
+                    }
+                    let bus = sound.bus.clone().unwrap_or_else(|| mixer.sfx().clone());
+                    let loudness = gains.0.max(gains.1) * sound.volume * bus.effective_volume();
+                    voices.push(((sound.priority, loudness), Voice::Pending(pending.len())));
+                    pending.push(Some(PendingSound {
+                        entity: emitter_entity,
+                        sound,
+                        bus,
+                        gains,
+                        pitch,
+                        cutoff,
+                    }));
+                }
+            }
+        }
+
+        // Apply the voice limit, playing voices come first to win ties against new sounds.
+        let ranked = voices.iter().map(|(rank, _)| *rank).collect::<Vec<_>>();
+        for index in voices_to_steal(&ranked, voice_limit.max_voices) {
+            match voices[index].1 {
+                Voice::Emitter(ref state) => state.stopped.store(true, Ordering::Relaxed),
+                Voice::Playback(ref playback) => playback.stop(),
+                Voice::Pending(pending_index) => pending[pending_index] = None,
+            }
+        }
+
+        if let Some(output) = &output {
+            for PendingSound {
+                entity,
+                sound,
+                bus,
+                gains,
+                pitch,
+                cutoff,
+            } in pending.into_iter().filter_map(|sound| sound)
+            {
+                let audio_emitter = match audio_emitter.get_mut(entity) {
+                    Some(audio_emitter) => audio_emitter,
+                    None => continue,
+                };
+                let sink = Sink::new(&output.device);
+                let state = SpatialState::new(
+                    (gains.0 * sound.volume, gains.1 * sound.volume),
+                    pitch * sound.pitch,
+                );
+                state.set_cutoff(cutoff);
+                let clone = state.clone();
+                let source = EffectSource::new(sound.decoder, audio_emitter.effects.clone());
+                sink.append(EndSignalSource::new(
+                    BusSource::new(SpatialSource::new(source, state.clone()), bus.clone()),
+                    move || {
+                        clone.finished.store(true, Ordering::Relaxed);
+                    },
+                ));
+                audio_emitter.sinks.push(PlayingSound {
+                    sink,
+                    state,
+                    handle: sound.handle,
+                    bus,
+                    priority: sound.priority,
+                    volume: sound.volume,
+                    pitch: sound.pitch,
+                });
             }
         }
     }
 
     fn setup(&mut self, res: &mut Resources) {
         Self::SystemData::setup(res);
-        res.insert(self.0.clone());
+        res.entry::<AudioController>()
+            .or_insert_with(|| AudioController::new(&self.output));
+        res.insert(self.output.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use amethyst_core::ecs::prelude::{Builder, World};
+
+    fn setup() -> World {
+        let mut world = World::new();
+        world.register::<AudioListener>();
+        world.register::<GlobalTransform>();
+        world
+    }
+
+    fn listener(world: &mut World, transformed: bool) -> Entity {
+        let builder = world.create_entity().with(AudioListener::default());
+        if transformed {
+            builder.with(GlobalTransform::default()).build()
+        } else {
+            builder.build()
+        }
+    }
+
+    fn active(
+        world: &World,
+        selected: Option<&SelectedListener>,
+        blend: Option<&ListenerBlend>,
+    ) -> Vec<(Entity, f32)> {
+        active_listeners(
+            selected,
+            blend,
+            &world.entities(),
+            &world.read_storage(),
+            &world.read_storage(),
+        )
+        .into_iter()
+        .map(|(entity, _, _, weight)| (entity, weight))
+        .collect()
+    }
+
+    #[test]
+    fn untransformed_listeners_are_left_out_before_normalizing() {
+        let mut world = setup();
+        let first = listener(&mut world, true);
+        let untransformed = listener(&mut world, false);
+        let second = listener(&mut world, true);
+        let blend = ListenerBlend {
+            listeners: vec![(first, 1.0), (untransformed, 2.0), (second, 3.0)],
+        };
+        assert_eq!(
+            active(&world, None, Some(&blend)),
+            vec![(first, 0.25), (second, 0.75)]
+        );
+
+        let blend = ListenerBlend {
+            listeners: vec![(untransformed, 1.0)],
+        };
+        let selected = SelectedListener(second);
+        assert_eq!(
+            active(&world, Some(&selected), Some(&blend)),
+            vec![(second, 1.0)]
+        );
+    }
+
+    #[test]
+    fn untransformed_selected_listeners_fall_back_to_a_transformed_one() {
+        let mut world = setup();
+        let untransformed = listener(&mut world, false);
+        let transformed = listener(&mut world, true);
+        let selected = SelectedListener(untransformed);
+        assert_eq!(
+            active(&world, Some(&selected), None),
+            vec![(transformed, 1.0)]
+        );
+
+        let mut world = setup();
+        listener(&mut world, false);
+        assert!(active(&world, None, None).is_empty());
     }
 }
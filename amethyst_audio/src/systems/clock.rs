@@ -0,0 +1,61 @@
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use amethyst_core::{
+    ecs::prelude::{Read, System, Write},
+    shrev::EventChannel,
+};
+
+use crate::{clock::MusicClock, event::MusicBeat};
+
+/// Sends a `MusicBeat` event for every beat of the music followed by the `MusicClock`.
+///
+/// Beats are reported on the first frame after they started, so the events are late by up to a
+/// frame. Use `MusicClock::until_next_beat` to schedule sounds right on a beat.
+#[derive(Default)]
+pub struct MusicClockSystem {
+    // The playing track and the number of beats reported for it.
+    last: Option<(usize, u64)>,
+}
+
+impl<'a> System<'a> for MusicClockSystem {
+    type SystemData = (
+        Option<Read<'a, MusicClock>>,
+        Write<'a, EventChannel<MusicBeat>>,
+    );
+
+    fn run(&mut self, (clock, mut beats): Self::SystemData) {
+        #[cfg(feature = "profiler")]
+        profile_scope!("music_clock_system");
+        let clock = match clock {
+            Some(clock) => clock,
+            None => return,
+        };
+        let (tempo, position) = match (clock.tempo(), clock.now()) {
+            (Some(tempo), Some(position)) => (tempo, position),
+            _ => {
+                if clock.tempo().is_none() {
+                    self.last = None;
+                }
+                return;
+            }
+        };
+        let generation = clock.generation();
+        let now = position.beats(&tempo) + 1;
+        let reported = match self.last {
+            // Looping restarts the count.
+            Some((last_generation, reported))
+                if last_generation == generation && reported <= now =>
+            {
+                reported
+            }
+            _ => now - 1,
+        };
+        let beats_per_bar = u64::from(tempo.beats_per_bar.max(1));
+        beats.iter_write((reported..now).map(|beat| MusicBeat {
+            bar: beat / beats_per_bar,
+            beat: (beat % beats_per_bar) as u32,
+        }));
+        self.last = Some((generation, now));
+    }
+}
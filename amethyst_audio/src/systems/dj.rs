@@ -7,20 +7,47 @@ use amethyst_assets::AssetStorage;
 use amethyst_core::{
     ecs::{
         common::Errors,
-        prelude::{Read, System, WriteExpect},
+        prelude::{Read, System, Write, WriteExpect},
     },
     shred::{Resource, Resources},
+    shrev::EventChannel,
 };
 
 use crate::{
+    event::AudioFinished,
     output::init_output,
     sink::AudioSink,
     source::{Source, SourceHandle},
+    stream::{StreamHandle, StreamSource},
 };
 
+/// A track picked by the `DjSystem`.
+#[derive(Clone, Debug)]
+pub enum DjTrack {
+    /// A track loaded into memory.
+    Source(SourceHandle),
+    /// A track streamed from disk, preferable for long music tracks.
+    Stream(StreamHandle),
+}
+
+impl From<SourceHandle> for DjTrack {
+    fn from(handle: SourceHandle) -> Self {
+        DjTrack::Source(handle)
+    }
+}
+
+impl From<StreamHandle> for DjTrack {
+    fn from(handle: StreamHandle) -> Self {
+        DjTrack::Stream(handle)
+    }
+}
+
 /// Calls a closure if the `AudioSink` is empty.
+///
+/// Sends an `AudioFinished::Music` event when a picked track finished.
 pub struct DjSystem<F, R> {
     f: F,
+    current: Option<DjTrack>,
     marker: PhantomData<R>,
 }
 
@@ -29,33 +56,54 @@ impl<F, R> DjSystem<F, R> {
     /// The closure takes a parameter, which needs to be a reference to
     /// a resource type, e.g. `&MusicLibrary`. This resource will be fetched
     /// by the system and passed to the picker.
+    ///
+    /// The picker returns either a `SourceHandle` or a `StreamHandle`, or a `DjTrack` to mix both.
     pub fn new(f: F) -> Self {
         DjSystem {
             f,
+            current: None,
             marker: PhantomData,
         }
     }
 }
 
-impl<'a, F, R> System<'a> for DjSystem<F, R>
+impl<'a, F, R, T> System<'a> for DjSystem<F, R>
 where
-    F: FnMut(&mut R) -> Option<SourceHandle>,
+    F: FnMut(&mut R) -> Option<T>,
     R: Resource,
+    T: Into<DjTrack>,
 {
     type SystemData = (
         Read<'a, AssetStorage<Source>>,
+        Read<'a, AssetStorage<StreamSource>>,
         Read<'a, Errors>,
         Option<Read<'a, AudioSink>>,
         WriteExpect<'a, R>,
+        Write<'a, EventChannel<AudioFinished>>,
     );
 
-    fn run(&mut self, (storage, errors, sink, mut res): Self::SystemData) {
+    fn run(&mut self, (storage, streams, errors, sink, mut res, mut finished): Self::SystemData) {
         #[cfg(feature = "profiler")]
         profile_scope!("dj_system");
         if let Some(ref sink) = sink {
             if sink.empty() {
-                if let Some(source) = (&mut self.f)(&mut res).and_then(|h| storage.get(&h)) {
-                    errors.execute(|| sink.append(source));
+                if let Some(track) = self.current.take() {
+                    finished.single_write(AudioFinished::Music(track));
+                }
+                if let Some(track) = (&mut self.f)(&mut res).map(Into::into) {
+                    let appended = match track {
+                        DjTrack::Source(ref handle) => {
+                            storage.get(handle).map(|source| sink.append(source))
+                        }
+                        DjTrack::Stream(ref handle) => {
+                            streams.get(handle).map(|stream| sink.append_stream(stream))
+                        }
+                    };
+                    match appended {
+                        Some(Ok(())) => self.current = Some(track),
+                        Some(Err(err)) => errors.execute(|| Err(err)),
+                        None => {}
+                    }
                 }
             }
         }
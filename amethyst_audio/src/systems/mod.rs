@@ -1,6 +1,15 @@
 //! `amethyst` audio ecs systems
 
-pub use self::{audio::AudioSystem, dj::DjSystem};
+pub use self::{
+    audio::{AudioSystem, ListenerBlend, SelectedListener},
+    clock::MusicClockSystem,
+    dj::{DjSystem, DjTrack},
+    zone::AudioZoneSystem,
+};
+
+pub(crate) use self::audio::active_listeners;
 
 mod audio;
+mod clock;
 mod dj;
+mod zone;
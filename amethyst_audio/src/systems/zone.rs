@@ -0,0 +1,100 @@
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use amethyst_core::{
+    ecs::prelude::{Entities, Join, Read, ReadStorage, System},
+    transform::GlobalTransform,
+};
+
+use crate::{
+    components::{blend, AudioListener, AudioZone},
+    effects::Effect,
+    mixer::{Mixer, SFX_BUS},
+    systems::{active_listeners, ListenerBlend, SelectedListener},
+};
+
+/// Applies the effects of the `AudioZone`s around the listener to a bus of the `Mixer`, the sfx
+/// bus by default.
+///
+/// The effects of a zone fade in as the listener approaches it, and overlapping zones are
+/// crossfaded. With a `ListenerBlend`, zones are weighted like the listeners. The system replaces
+/// the effects of its bus, so other effects should be added to another bus, or to the emitters.
+pub struct AudioZoneSystem {
+    bus: String,
+    applied: Vec<Effect>,
+}
+
+impl Default for AudioZoneSystem {
+    fn default() -> Self {
+        AudioZoneSystem::new(SFX_BUS)
+    }
+}
+
+impl AudioZoneSystem {
+    /// Creates a system applying the zone effects to the bus with the given name.
+    pub fn new<S: Into<String>>(bus: S) -> Self {
+        AudioZoneSystem {
+            bus: bus.into(),
+            applied: Vec::new(),
+        }
+    }
+}
+
+impl<'a> System<'a> for AudioZoneSystem {
+    type SystemData = (
+        Option<Read<'a, SelectedListener>>,
+        Option<Read<'a, ListenerBlend>>,
+        Read<'a, Mixer>,
+        Entities<'a>,
+        ReadStorage<'a, GlobalTransform>,
+        ReadStorage<'a, AudioListener>,
+        ReadStorage<'a, AudioZone>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            select_listener,
+            listener_blend,
+            mixer,
+            entities,
+            transform,
+            listener,
+            zones,
+        ): Self::SystemData,
+    ) {
+        #[cfg(feature = "profiler")]
+        profile_scope!("audio_zone_system");
+        let bus = match mixer.bus(&self.bus) {
+            Some(bus) => bus,
+            None => return,
+        };
+        let positions = active_listeners(
+            select_listener.as_ref().map(|sl| &**sl),
+            listener_blend.as_ref().map(|lb| &**lb),
+            &entities,
+            &listener,
+            &transform,
+        )
+        .into_iter()
+        .map(|(_, _, transform, weight)| (transform.0.column(3).xyz(), weight))
+        .collect::<Vec<_>>();
+        // Zones apply as much as they apply to each listener heard through.
+        let weights = (&transform, &zones)
+            .join()
+            .map(|(transform, zone)| {
+                let weight = positions
+                    .iter()
+                    .map(|(position, weight)| zone.weight(&transform.0, *position) * weight)
+                    .sum::<f32>();
+                (weight, &zone.effects[..])
+            })
+            .collect::<Vec<_>>();
+        let effects = blend(weights.into_iter());
+        // Only changes the chain when needed, so that the audio thread doesn't lock it.
+        if effects != self.applied {
+            bus.effects().set(effects.clone());
+            self.applied = effects;
+        }
+    }
+}
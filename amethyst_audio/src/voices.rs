@@ -0,0 +1,58 @@
+//! Provides the limit of sounds playing at the same time.
+
+use std::cmp::Ordering;
+
+use serde::{Deserialize, Serialize};
+
+/// The resource limiting the number of sounds playing at the same time, used by the
+/// `AudioSystem`.
+///
+/// Sounds of `AudioEmitter`s and of the `AudioController` count as voices, paused sounds don't.
+/// When more sounds play, the sounds with the lowest priority are stopped, the quietest ones
+/// first among sounds of the same priority. New sounds losing against all playing sounds aren't
+/// started at all.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct VoiceLimit {
+    /// The maximum number of voices.
+    pub max_voices: usize,
+}
+
+impl Default for VoiceLimit {
+    fn default() -> Self {
+        VoiceLimit { max_voices: 64 }
+    }
+}
+
+/// Returns the indices of the voices to stop so that at most `max_voices` remain, given their
+/// priority and loudness. Voices earlier in the slice win ties.
+pub(crate) fn voices_to_steal(voices: &[(i32, f32)], max_voices: usize) -> Vec<usize> {
+    if voices.len() <= max_voices {
+        return Vec::new();
+    }
+    let mut ranking = (0..voices.len()).collect::<Vec<_>>();
+    // Stable, so that ties keep their order.
+    ranking.sort_by(|&a, &b| {
+        voices[b].0.cmp(&voices[a].0).then(
+            voices[b]
+                .1
+                .partial_cmp(&voices[a].1)
+                .unwrap_or(Ordering::Equal),
+        )
+    });
+    ranking.split_off(max_voices)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn steals_low_priority_and_quiet_voices() {
+        let voices = [(0, 0.5), (1, 0.1), (0, 0.2), (0, 0.5), (0, 0.9)];
+        let mut stolen = voices_to_steal(&voices, 3);
+        stolen.sort();
+        assert_eq!(stolen, vec![2, 3]);
+        assert!(voices_to_steal(&voices, 5).is_empty());
+    }
+}
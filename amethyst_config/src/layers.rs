@@ -0,0 +1,393 @@
+//! Configurations merged from their defaults, a file, the environment and the command line.
+
+use std::{
+    env,
+    marker::PhantomData,
+    path::{Path, PathBuf},
+};
+
+use log::warn;
+use ron::{de::Error as DeError, ser::Error as SerError};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::{Config, ConfigError};
+
+/// Loads a configuration from layers, each overriding the ones before it: the defaults, a RON
+/// file, the environment variables, then the command line flags.
+///
+/// The environment variables are named after a prefix and the key, in upper case, with `__`
+/// between the keys of the nested structures: with the prefix `GAME_DISPLAY`, the variable
+/// `GAME_DISPLAY_FULLSCREEN` overrides `fullscreen`, and `GAME_NET__PORT` overrides `net.port`
+/// with the prefix `GAME`. The flags are the prefix and the key, with dots:
+/// `--display.fullscreen=true` or `--display.dimensions [1920, 1080]`.
+///
+/// The values are JSON, falling back to a string when the JSON doesn't fit the key, so that
+/// `title=My game` or `name=123` for an `Option<String>` need no quotes. Only keys of the
+/// configuration can be overridden: the environment variables of unknown keys are ignored with
+/// a warning, as the environment is shared with other programs, while the flags of unknown keys
+/// are errors. A value that doesn't fit its key is an error naming the key:
+///
+/// ~~~no_run
+/// # use serde_derive::{Deserialize, Serialize};
+/// use amethyst_config::ConfigLayers;
+///
+/// #[derive(Default, Deserialize, Serialize)]
+/// #[serde(default)]
+/// struct ServerConfig {
+///     port: u16,
+///     max_players: u32,
+/// }
+///
+/// let config = ConfigLayers::<ServerConfig>::new()
+///     .with_file("resources/server.ron")
+///     .with_env("SERVER")
+///     .with_args("server", std::env::args())
+///     .load()
+///     .expect("Invalid server configuration");
+/// ~~~
+pub struct ConfigLayers<T> {
+    file: Option<PathBuf>,
+    env_prefix: Option<String>,
+    args: Vec<(String, String, String)>,
+    vars: Option<Vec<(String, String)>>,
+    marker: PhantomData<T>,
+}
+
+impl<T> Default for ConfigLayers<T> {
+    fn default() -> Self {
+        ConfigLayers {
+            file: None,
+            env_prefix: None,
+            args: Vec::new(),
+            vars: None,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T> ConfigLayers<T>
+where
+    T: for<'a> Deserialize<'a> + Serialize + Default,
+{
+    /// Creates the layers, with only the defaults.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Loads the file over the defaults. It must exist, like with `Config::load_no_fallback`.
+    pub fn with_file<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.file = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Overrides the keys with the environment variables starting with `prefix` and `_`, ignoring
+    /// those of unknown keys.
+    pub fn with_env(mut self, prefix: &str) -> Self {
+        self.env_prefix = Some(prefix.to_uppercase());
+        self
+    }
+
+    /// Overrides the keys with the environment variables of `vars` instead of those of the
+    /// process.
+    pub fn with_vars<I>(mut self, prefix: &str, vars: I) -> Self
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        self.env_prefix = Some(prefix.to_uppercase());
+        self.vars = Some(vars.into_iter().collect());
+        self
+    }
+
+    /// Overrides the keys with the flags of `args` starting with `--`, `prefix` and `.`, in
+    /// their order. The other arguments are left to the game, with the name of the program.
+    pub fn with_args<I, S>(mut self, prefix: &str, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let start = format!("--{}.", prefix);
+        let mut args = args.into_iter().map(Into::into).peekable();
+        while let Some(arg) = args.next() {
+            if !arg.starts_with(&start) {
+                continue;
+            }
+            let flag = &arg[start.len()..];
+            let origin = format!("flag {}", arg.split('=').next().unwrap_or(&arg));
+            let (key, value) = match flag.find('=') {
+                Some(index) => (flag[..index].to_string(), flag[index + 1..].to_string()),
+                None => {
+                    // The value is the next argument, unless the flag is alone to set a boolean.
+                    let next_is_value = args.peek().map_or(false, |next| !next.starts_with("--"));
+                    let value = if next_is_value {
+                        args.next().unwrap_or_default()
+                    } else {
+                        "true".to_string()
+                    };
+                    (flag.to_string(), value)
+                }
+            };
+            self.args.push((key, value, origin));
+        }
+        self
+    }
+
+    /// Loads the configuration from the layers.
+    pub fn load(self) -> Result<T, ConfigError> {
+        let base = match self.file {
+            Some(ref path) => T::load_no_fallback(path)?,
+            None => T::default(),
+        };
+        // The overrides, and whether their keys must be known.
+        let mut overrides = Vec::new();
+        if let Some(ref prefix) = self.env_prefix {
+            let mut vars = match self.vars {
+                Some(ref vars) => vars.clone(),
+                None => env::vars().collect(),
+            };
+            // Applied in a fixed order, when several variables set the same key.
+            vars.sort();
+            let start = format!("{}_", prefix);
+            for (name, value) in vars {
+                if !name.starts_with(&start) || name.len() == start.len() {
+                    continue;
+                }
+                let key = name[start.len()..].to_lowercase().replace("__", ".");
+                let origin = format!("environment variable {}", name);
+                overrides.push((key, value, origin, false));
+            }
+        }
+        overrides.extend(
+            self.args
+                .into_iter()
+                .map(|(key, value, origin)| (key, value, origin, true)),
+        );
+        if overrides.is_empty() {
+            return Ok(base);
+        }
+
+        let mut tree = serde_json::to_value(&base)
+            .map_err(|err| ConfigError::Serializer(SerError::Message(err.to_string())))?;
+        for (key, value, origin, strict) in overrides {
+            let reason = match apply::<T>(&mut tree, &key, &value) {
+                Ok(()) => continue,
+                Err(Refusal::UnknownKey) if !strict => {
+                    warn!(
+                        "Ignoring the {}, the configuration has no key `{}`",
+                        origin, key
+                    );
+                    continue;
+                }
+                Err(Refusal::UnknownKey) => "the configuration has no such key".to_string(),
+                Err(Refusal::Invalid(reason)) => reason,
+            };
+            return Err(ConfigError::Override {
+                key,
+                origin,
+                reason,
+            });
+        }
+        serde_json::from_value(tree)
+            .map_err(|err| ConfigError::Parser(DeError::Message(err.to_string())))
+    }
+}
+
+// Why an override was refused.
+#[derive(Debug)]
+enum Refusal {
+    UnknownKey,
+    Invalid(String),
+}
+
+// Sets `key` to `raw` in the tree, checking that it's still a `T`.
+//
+// The keys skipped when serializing, like the `None` of the fields with `skip_serializing_if`,
+// aren't in the tree, so they are added, and kept if the configuration still has them once
+// overridden.
+fn apply<T>(tree: &mut Value, key: &str, raw: &str) -> Result<(), Refusal>
+where
+    T: for<'a> Deserialize<'a> + Serialize,
+{
+    let pointer = format!("/{}", key.replace('.', "/"));
+    let mut values = Vec::new();
+    match tree.pointer(&pointer) {
+        Some(Value::String(_)) => {}
+        _ => values.extend(serde_json::from_str::<Value>(raw).ok()),
+    }
+    if values.first() != Some(&Value::String(raw.to_string())) {
+        values.push(Value::String(raw.to_string()));
+    }
+
+    let mut reason = None;
+    for value in values {
+        let mut candidate = tree.clone();
+        let known = set(&mut candidate, key, value).ok_or(Refusal::UnknownKey)?;
+        let config = match serde_json::from_value::<T>(candidate.clone()) {
+            Ok(config) => config,
+            Err(err) => {
+                reason = reason.or_else(|| Some(err.to_string()));
+                continue;
+            }
+        };
+        if !known {
+            let kept =
+                serde_json::to_value(&config).map_err(|err| Refusal::Invalid(err.to_string()))?;
+            if kept.pointer(&pointer).is_none() {
+                return Err(Refusal::UnknownKey);
+            }
+        }
+        *tree = candidate;
+        return Ok(());
+    }
+    Err(Refusal::Invalid(reason.unwrap_or_default()))
+}
+
+// Sets `key` to `value` in the tree, adding the objects missing on the way. Returns whether the
+// key was in the tree, or `None` if it can't be set.
+fn set(tree: &mut Value, key: &str, value: Value) -> Option<bool> {
+    let parts = key.split('.').collect::<Vec<_>>();
+    let (last, path) = parts.split_last()?;
+    let mut known = true;
+    let mut node = tree;
+    for part in path {
+        if node.is_null() {
+            *node = Value::Object(Map::new());
+            known = false;
+        }
+        node = match *node {
+            Value::Object(ref mut map) => {
+                known &= map.contains_key(*part);
+                map.entry(part.to_string()).or_insert(Value::Null)
+            }
+            Value::Array(ref mut seq) => part
+                .parse::<usize>()
+                .ok()
+                .and_then(move |i| seq.get_mut(i))?,
+            _ => return None,
+        };
+    }
+    if node.is_null() {
+        *node = Value::Object(Map::new());
+        known = false;
+    }
+    match *node {
+        Value::Object(ref mut map) => {
+            known &= map.contains_key(*last);
+            map.insert(last.to_string(), value);
+        }
+        Value::Array(ref mut seq) => {
+            *last.parse::<usize>().ok().and_then(|i| seq.get_mut(i))? = value;
+        }
+        _ => return None,
+    }
+    Some(known)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_derive::{Deserialize, Serialize};
+
+    use super::*;
+
+    #[derive(Debug, Default, PartialEq, Deserialize, Serialize)]
+    #[serde(default)]
+    struct Net {
+        port: u16,
+        host: String,
+    }
+
+    #[derive(Debug, Default, PartialEq, Deserialize, Serialize)]
+    #[serde(default)]
+    struct Server {
+        fullscreen: bool,
+        dimensions: Option<(u32, u32)>,
+        name: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        motd: Option<String>,
+        net: Net,
+    }
+
+    fn vars(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter()
+            .map(|&(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn flags_override_the_environment() {
+        let vars = vars(&[
+            ("GAME_NET__PORT", "4000"),
+            ("GAME_NET__HOST", "example.com"),
+            ("OTHER_FULLSCREEN", "true"),
+        ]);
+        let args = vec![
+            "game",
+            "--game.net.port=5000",
+            "--game.dimensions",
+            "[800, 600]",
+        ];
+        let server = ConfigLayers::<Server>::new()
+            .with_vars("game", vars)
+            .with_args("game", args)
+            .load()
+            .unwrap();
+        assert_eq!(
+            server,
+            Server {
+                dimensions: Some((800, 600)),
+                net: Net {
+                    port: 5000,
+                    host: "example.com".to_string(),
+                },
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn values_of_the_wrong_type_are_errors() {
+        let err = ConfigLayers::<Server>::new()
+            .with_args("game", vec!["--game.net.port=many"])
+            .load()
+            .unwrap_err();
+        match err {
+            ConfigError::Override { key, origin, .. } => {
+                assert_eq!(key, "net.port");
+                assert_eq!(origin, "flag --game.net.port");
+            }
+            err => panic!("Unexpected error: {}", err),
+        }
+        assert!(ConfigLayers::<Server>::new()
+            .with_vars("game", vars(&[("GAME_FULLSCREEN", "sometimes")]))
+            .load()
+            .is_err());
+    }
+
+    #[test]
+    fn unknown_keys_are_errors_only_for_flags() {
+        let server = ConfigLayers::<Server>::new()
+            .with_vars("game", vars(&[("GAME_WINDOWED", "true")]))
+            .load()
+            .unwrap();
+        assert_eq!(server, Server::default());
+
+        let err = ConfigLayers::<Server>::new()
+            .with_args("game", vec!["--game.windowed"])
+            .load()
+            .unwrap_err();
+        match err {
+            ConfigError::Override { key, .. } => assert_eq!(key, "windowed"),
+            err => panic!("Unexpected error: {}", err),
+        }
+    }
+
+    #[test]
+    fn optional_and_skipped_keys_are_overridden() {
+        let server = ConfigLayers::<Server>::new()
+            .with_args("game", vec!["--game.name=123", "--game.motd=Welcome"])
+            .load()
+            .unwrap();
+        assert_eq!(server.name, Some("123".to_string()));
+        assert_eq!(server.motd, Some("Welcome".to_string()));
+    }
+}
@@ -13,6 +13,10 @@ use log::error;
 use ron::{self, de::Error as DeError, ser::Error as SerError};
 use serde::{Deserialize, Serialize};
 
+pub use crate::layers::ConfigLayers;
+
+mod layers;
+
 /// Error related to anything that manages/creates configurations as well as
 /// "workspace"-related things.
 #[derive(Debug)]
@@ -25,6 +29,16 @@ pub enum ConfigError {
     Serializer(SerError),
     /// Related to the path of the file.
     Extension(PathBuf),
+    /// A value of an environment variable or of a command line flag doesn't fit the
+    /// configuration.
+    Override {
+        /// The key of the value, with dots between the keys of the nested structures.
+        key: String,
+        /// Where the value comes from, as the name of the environment variable.
+        origin: String,
+        /// Why the value doesn't fit, as the key being unknown or the value having the wrong type.
+        reason: String,
+    },
 }
 
 impl fmt::Display for ConfigError {
@@ -46,6 +60,15 @@ impl fmt::Display for ConfigError {
                     found,
                 )
             }
+            ConfigError::Override {
+                ref key,
+                ref origin,
+                ref reason,
+            } => write!(
+                f,
+                "Invalid value of `{}` in the {}: {}",
+                key, origin, reason
+            ),
         }
     }
 }
@@ -75,6 +98,7 @@ impl Error for ConfigError {
             ConfigError::Parser(_) => "Project parser error",
             ConfigError::Serializer(_) => "Project serializer error",
             ConfigError::Extension(_) => "Invalid extension or directory for a file",
+            ConfigError::Override { .. } => "Invalid override of a configuration value",
         }
     }
 
@@ -103,6 +127,10 @@ where
 
     /// Writes a configuration structure to a file.
     fn write<P: AsRef<Path>>(&self, path: P) -> Result<(), ConfigError>;
+
+    /// Layers loading the configuration from a file, the environment variables and the command
+    /// line flags, over the defaults.
+    fn layers() -> ConfigLayers<Self>;
 }
 
 impl<T> Config for T
@@ -158,4 +186,8 @@ where
 
         Ok(())
     }
+
+    fn layers() -> ConfigLayers<Self> {
+        ConfigLayers::new()
+    }
 }
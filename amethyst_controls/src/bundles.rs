@@ -1,6 +1,6 @@
 use std::{hash::Hash, marker::PhantomData};
 
-use amethyst_core::{bundle::SystemBundle, ecs::prelude::DispatcherBuilder};
+use amethyst_core::bundle::{BundleBuilder, SystemBundle};
 use amethyst_error::Error;
 
 use super::*;
@@ -74,7 +74,7 @@ where
     A: Send + Sync + Hash + Eq + Clone + 'static,
     B: Send + Sync + Hash + Eq + Clone + 'static,
 {
-    fn build(self, builder: &mut DispatcherBuilder<'a, 'b>) -> Result<(), Error> {
+    fn build(self, builder: &mut BundleBuilder<'a, 'b>) -> Result<(), Error> {
         builder.add(
             FlyMovementSystem::<A, B>::new(
                 self.speed,
@@ -136,7 +136,7 @@ where
     A: Send + Sync + Hash + Eq + Clone + 'static,
     B: Send + Sync + Hash + Eq + Clone + 'static,
 {
-    fn build(self, builder: &mut DispatcherBuilder<'a, 'b>) -> Result<(), Error> {
+    fn build(self, builder: &mut BundleBuilder<'a, 'b>) -> Result<(), Error> {
         builder.add(ArcBallRotationSystem::default(), "arc_ball_rotation", &[]);
         builder.add(
             FreeRotationSystem::<A, B>::new(self.sensitivity_x, self.sensitivity_y),
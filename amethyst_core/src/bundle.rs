@@ -1,10 +1,171 @@
 //! Provides a trait for adding bundles of systems to a dispatcher.
 
-use crate::ecs::prelude::DispatcherBuilder;
+use std::collections::HashSet;
+
+use crate::{
+    dispatcher_profile::DispatcherProfile,
+    ecs::prelude::{DispatcherBuilder, System},
+};
 use amethyst_error::Error;
 
 /// A bundle of ECS components, resources and systems.
 pub trait SystemBundle<'a, 'b> {
     /// Build and add ECS resources, register components, add systems etc to the Application.
-    fn build(self, dispatcher: &mut DispatcherBuilder<'a, 'b>) -> Result<(), Error>;
+    fn build(self, builder: &mut BundleBuilder<'a, 'b>) -> Result<(), Error>;
+
+    /// The name of the bundle, used in error messages. Defaults to `"unnamed bundle"`.
+    fn name(&self) -> &'static str {
+        "unnamed bundle"
+    }
+
+    /// The systems that must be added before the bundle, because its systems depend on them.
+    fn requirements(&self) -> Vec<BundleRequirement> {
+        Vec::new()
+    }
+}
+
+/// A system a bundle needs, and the bundle adding it, to tell the user what is missing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BundleRequirement {
+    /// The name of the system.
+    pub system: &'static str,
+    /// The name of the bundle adding it, like `TransformBundle`.
+    pub bundle: &'static str,
+}
+
+impl BundleRequirement {
+    /// Requires the system `system` of the bundle `bundle`.
+    pub fn new(system: &'static str, bundle: &'static str) -> Self {
+        BundleRequirement { system, bundle }
+    }
+}
+
+/// The `DispatcherBuilder` bundles add their systems to, recording the names of the systems.
+///
+/// When it knows the systems added before the bundle, a system with the name of another one is
+/// added without its name, and the dependencies that don't exist are left out, so that they are
+/// reported as errors instead of panicking in the dispatcher. With a `DispatcherProfile`, the
+/// systems are timed in it.
+pub struct BundleBuilder<'a, 'b> {
+    dispatcher: DispatcherBuilder<'a, 'b>,
+    known: Option<HashSet<String>>,
+    profile: Option<DispatcherProfile>,
+    systems: Vec<String>,
+    duplicates: Vec<String>,
+    missing: Vec<(String, String)>,
+}
+
+impl<'a, 'b> BundleBuilder<'a, 'b> {
+    /// Wraps `dispatcher`, whose systems are unknown, so that the systems are added as they are.
+    pub fn new(dispatcher: DispatcherBuilder<'a, 'b>) -> Self {
+        BundleBuilder {
+            dispatcher,
+            known: None,
+            profile: None,
+            systems: Vec::new(),
+            duplicates: Vec::new(),
+            missing: Vec::new(),
+        }
+    }
+
+    /// Wraps `dispatcher`, which has the systems named `systems`.
+    pub fn with_systems<I>(dispatcher: DispatcherBuilder<'a, 'b>, systems: I) -> Self
+    where
+        I: IntoIterator<Item = String>,
+    {
+        BundleBuilder {
+            known: Some(systems.into_iter().collect()),
+            ..BundleBuilder::new(dispatcher)
+        }
+    }
+
+    /// Times the systems added from now on in `profile`.
+    pub fn with_profile(mut self, profile: DispatcherProfile) -> Self {
+        self.profile = Some(profile);
+        self
+    }
+
+    /// Builds `bundle` into `dispatcher`, for dispatchers built without a `GameDataBuilder`.
+    pub fn build<B>(dispatcher: &mut DispatcherBuilder<'a, 'b>, bundle: B) -> Result<(), Error>
+    where
+        B: SystemBundle<'a, 'b>,
+    {
+        let mut builder =
+            BundleBuilder::new(std::mem::replace(dispatcher, DispatcherBuilder::new()));
+        let result = bundle.build(&mut builder);
+        *dispatcher = builder.into_dispatcher();
+        result
+    }
+
+    /// Adds a system, see `DispatcherBuilder::add`.
+    pub fn add<T>(&mut self, system: T, name: &str, dependencies: &[&str])
+    where
+        for<'c> T: System<'c> + Send + 'a,
+    {
+        let mut existing = Vec::with_capacity(dependencies.len());
+        for &dependency in dependencies {
+            match self.known {
+                Some(ref known) if !known.contains(dependency) => {
+                    self.missing.push((name.to_owned(), dependency.to_owned()))
+                }
+                _ => existing.push(dependency),
+            }
+        }
+        let profile_name = name;
+        let mut name = name;
+        if !name.is_empty() {
+            if let Some(ref mut known) = self.known {
+                if !known.insert(name.to_owned()) {
+                    self.duplicates.push(name.to_owned());
+                    name = "";
+                }
+            }
+            if !name.is_empty() {
+                self.systems.push(name.to_owned());
+            }
+        }
+        match self.profile {
+            Some(ref profile) => {
+                let system = profile.wrap(system, profile_name);
+                self.dispatcher.add(system, name, &existing)
+            }
+            None => self.dispatcher.add(system, name, &existing),
+        }
+    }
+
+    /// Adds a thread-local system, see `DispatcherBuilder::add_thread_local`.
+    pub fn add_thread_local<T>(&mut self, system: T)
+    where
+        for<'c> T: System<'c> + 'b,
+    {
+        match self.profile {
+            Some(ref profile) => self.dispatcher.add_thread_local(profile.wrap(system, "")),
+            None => self.dispatcher.add_thread_local(system),
+        }
+    }
+
+    /// Adds a barrier, see `DispatcherBuilder::add_barrier`.
+    pub fn add_barrier(&mut self) {
+        self.dispatcher.add_barrier();
+    }
+
+    /// The names of the systems added.
+    pub fn systems(&self) -> &[String] {
+        &self.systems
+    }
+
+    /// The names of the systems added with the name of a system added before them.
+    pub fn duplicates(&self) -> &[String] {
+        &self.duplicates
+    }
+
+    /// The systems added with a dependency that wasn't added before them, and the dependency.
+    pub fn missing(&self) -> &[(String, String)] {
+        &self.missing
+    }
+
+    /// Unwraps the `DispatcherBuilder`.
+    pub fn into_dispatcher(self) -> DispatcherBuilder<'a, 'b> {
+        self.dispatcher
+    }
 }
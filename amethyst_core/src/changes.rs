@@ -0,0 +1,165 @@
+//! Tracking of the changes of flagged components.
+
+use std::{marker::PhantomData, ops::Deref};
+
+use hibitset::{BitSet, BitSetLike, BitSetOr};
+
+use crate::ecs::{
+    prelude::{Component, ComponentEvent, ReaderId, Resources, SystemData, WriteStorage},
+    storage::{MaskedStorage, Storage, Tracked},
+};
+
+/// The entities whose component `T` was inserted, modified or removed since the last `update`.
+///
+/// `T` must use a `FlaggedStorage`, like `Transform`, `GlobalTransform`, `Parent` and `Named`.
+/// `GlobalTransform`s are modified by the `TransformSystem` whenever the entity or one of its
+/// parents moves, so tracking them finds the entities that moved in the world.
+///
+/// Components are flagged as modified when they are borrowed mutably, which includes joining
+/// over a `WriteStorage` mutably, even if they don't actually change.
+///
+/// ```rust
+/// use amethyst::{
+///     core::{changes::ComponentChanges, GlobalTransform},
+///     ecs::prelude::*,
+/// };
+///
+/// #[derive(Default)]
+/// struct SpatialIndexSystem {
+///     changes: Option<ComponentChanges<GlobalTransform>>,
+/// }
+///
+/// impl<'a> System<'a> for SpatialIndexSystem {
+///     type SystemData = (Entities<'a>, ReadStorage<'a, GlobalTransform>);
+///
+///     fn run(&mut self, (entities, globals): Self::SystemData) {
+///         let changes = self.changes.as_mut().expect("setup was not called");
+///         changes.update(&globals);
+///         for (entity, global, _) in (&entities, &globals, changes.changed()).join() {
+///             // Only the entities that moved since the last run.
+///             println!("{:?} moved to {}", entity, global.0.column(3));
+///         }
+///     }
+///
+///     fn setup(&mut self, res: &mut Resources) {
+///         Self::SystemData::setup(res);
+///         self.changes = Some(ComponentChanges::new(res));
+///     }
+/// }
+/// ```
+pub struct ComponentChanges<T> {
+    reader: ReaderId<ComponentEvent>,
+    inserted: BitSet,
+    modified: BitSet,
+    removed: BitSet,
+    marker: PhantomData<T>,
+}
+
+impl<T> ComponentChanges<T>
+where
+    T: Component,
+    T::Storage: Tracked,
+{
+    /// Starts tracking the changes of the component, from now on.
+    pub fn new(res: &mut Resources) -> Self {
+        let mut storage = WriteStorage::<T>::fetch(res);
+        ComponentChanges {
+            reader: storage.register_reader(),
+            inserted: BitSet::new(),
+            modified: BitSet::new(),
+            removed: BitSet::new(),
+            marker: PhantomData,
+        }
+    }
+
+    /// Forgets the previous changes and reads the ones since the last call.
+    ///
+    /// An entity is only in one of the sets: a component inserted and then modified only counts as
+    /// inserted, and one inserted and then removed only as removed.
+    pub fn update<D>(&mut self, storage: &Storage<'_, T, D>)
+    where
+        D: Deref<Target = MaskedStorage<T>>,
+    {
+        self.inserted.clear();
+        self.modified.clear();
+        self.removed.clear();
+        for event in storage.channel().read(&mut self.reader) {
+            match *event {
+                ComponentEvent::Inserted(id) => {
+                    self.removed.remove(id);
+                    self.modified.remove(id);
+                    self.inserted.add(id);
+                }
+                ComponentEvent::Modified(id) => {
+                    if !self.inserted.contains(id) {
+                        self.modified.add(id);
+                    }
+                }
+                ComponentEvent::Removed(id) => {
+                    self.inserted.remove(id);
+                    self.modified.remove(id);
+                    self.removed.add(id);
+                }
+            }
+        }
+    }
+
+    /// The entities whose component was inserted.
+    pub fn inserted(&self) -> &BitSet {
+        &self.inserted
+    }
+
+    /// The entities whose component was modified.
+    pub fn modified(&self) -> &BitSet {
+        &self.modified
+    }
+
+    /// The entities whose component was removed, including the deleted entities. These ids may
+    /// already belong to new entities.
+    pub fn removed(&self) -> &BitSet {
+        &self.removed
+    }
+
+    /// The entities whose component was inserted or modified.
+    pub fn changed(&self) -> BitSetOr<&BitSet, &BitSet> {
+        BitSetOr(&self.inserted, &self.modified)
+    }
+
+    /// Checks whether nothing changed.
+    pub fn is_empty(&self) -> bool {
+        self.inserted.is_empty() && self.modified.is_empty() && self.removed.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ecs::prelude::{Builder, World},
+        Transform,
+    };
+
+    #[test]
+    fn changes_since_last_update() {
+        let mut world = World::new();
+        world.register::<Transform>();
+        let moved = world.create_entity().with(Transform::default()).build();
+        let mut changes = ComponentChanges::<Transform>::new(&mut world.res);
+
+        let created = world.create_entity().with(Transform::default()).build();
+        world
+            .write_storage::<Transform>()
+            .get_mut(moved)
+            .unwrap()
+            .move_up(1.0);
+        changes.update(&world.read_storage::<Transform>());
+        assert!(changes.inserted().contains(created.id()));
+        assert!(changes.modified().contains(moved.id()));
+        assert!(!changes.modified().contains(created.id()));
+
+        world.delete_entity(moved).unwrap();
+        changes.update(&world.read_storage::<Transform>());
+        assert!(changes.removed().contains(moved.id()));
+        assert!(changes.changed().iter().next().is_none());
+    }
+}
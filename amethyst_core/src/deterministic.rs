@@ -0,0 +1,264 @@
+//! Utilities for deterministic simulations, as needed by lockstep networking and replays.
+//!
+//! A simulation is deterministic when the same inputs always give the same frames. In the
+//! deterministic mode of the application, set with `ApplicationBuilder::with_deterministic`:
+//!
+//! * the systems run one at a time on a single thread, the ones writing resources that others
+//!   read or write in the order they were added. The dispatcher may run a system before one added
+//!   earlier when they share nothing they write, which doesn't change what either sees,
+//! * every frame advances the `Time` by exactly one fixed step, whatever the wall clock says,
+//! * the `GameRng` resource is seeded with the given seed.
+//!
+//! Gameplay systems must then take their time from the `Time` resource, and their randomness
+//! from the `GameRng`, never from `Instant`, `thread_rng` or the iteration order of a `HashMap`.
+//! The `FrameHasher` hashes the state of the world, to compare the frames of two simulations and
+//! find where they diverge.
+
+use std::hash::{Hash, Hasher};
+
+use fnv::FnvHasher;
+use rand::{Error as RandError, FromEntropy, RngCore, SeedableRng};
+use rand_pcg::Pcg32;
+
+use crate::{
+    ecs::prelude::{Component, Entities, Join, ReadStorage, Resources, SystemData},
+    transform::{GlobalTransform, Parent, Transform},
+    Named,
+};
+
+/// The random number generator of the game, seeded in the deterministic mode.
+///
+/// Outside of the deterministic mode it is seeded from the operating system. It implements
+/// `RngCore`, so all the methods of `rand::Rng` can be used with it.
+///
+/// ```rust,ignore
+/// fn run(&mut self, mut rng: Write<'_, GameRng>) {
+///     let damage = rng.gen_range(10, 20);
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct GameRng {
+    seed: Option<u64>,
+    rng: Pcg32,
+}
+
+impl GameRng {
+    /// Creates a generator giving the same numbers for the same seed, on every platform.
+    pub fn new(seed: u64) -> Self {
+        GameRng {
+            seed: Some(seed),
+            rng: Pcg32::seed_from_u64(seed),
+        }
+    }
+
+    /// The seed, `None` if the generator was seeded from the operating system.
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    /// Starts over with a new seed.
+    pub fn reseed(&mut self, seed: u64) {
+        *self = GameRng::new(seed);
+    }
+}
+
+impl Default for GameRng {
+    fn default() -> Self {
+        GameRng {
+            seed: None,
+            rng: Pcg32::from_entropy(),
+        }
+    }
+}
+
+impl RngCore for GameRng {
+    fn next_u32(&mut self) -> u32 {
+        self.rng.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.rng.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.rng.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), RandError> {
+        self.rng.try_fill_bytes(dest)
+    }
+}
+
+/// Feeds the state of a component or resource to a hasher, for the `FrameHasher`.
+///
+/// Unlike `Hash`, it is implemented for floats, by their bits, since two simulations are only the
+/// same if their floats are exactly the same.
+pub trait StateHash {
+    /// Feeds the state to the hasher.
+    fn hash_state<H: Hasher>(&self, state: &mut H);
+}
+
+macro_rules! impl_state_hash {
+    ($($ty:ty),*) => {
+        $(
+            impl StateHash for $ty {
+                fn hash_state<H: Hasher>(&self, state: &mut H) {
+                    self.hash(state);
+                }
+            }
+        )*
+    };
+}
+
+impl_state_hash!(bool, u8, u16, u32, u64, usize, i8, i16, i32, i64, isize, String);
+
+impl StateHash for f32 {
+    fn hash_state<H: Hasher>(&self, state: &mut H) {
+        self.to_bits().hash(state);
+    }
+}
+
+impl StateHash for f64 {
+    fn hash_state<H: Hasher>(&self, state: &mut H) {
+        self.to_bits().hash(state);
+    }
+}
+
+impl StateHash for Transform {
+    fn hash_state<H: Hasher>(&self, state: &mut H) {
+        let rotation = self.rotation().quaternion().coords;
+        for value in self
+            .translation()
+            .iter()
+            .chain(rotation.iter())
+            .chain(self.scale().iter())
+        {
+            value.hash_state(state);
+        }
+    }
+}
+
+impl StateHash for GlobalTransform {
+    fn hash_state<H: Hasher>(&self, state: &mut H) {
+        for value in self.0.iter() {
+            value.hash_state(state);
+        }
+    }
+}
+
+impl StateHash for Parent {
+    fn hash_state<H: Hasher>(&self, state: &mut H) {
+        self.entity.id().hash(state);
+    }
+}
+
+impl StateHash for Named {
+    fn hash_state<H: Hasher>(&self, state: &mut H) {
+        self.name.hash(state);
+    }
+}
+
+type HashFn = Box<dyn Fn(&Resources, &mut FnvHasher) + Send + Sync>;
+
+/// Hashes the components and resources that make up the state of the simulation.
+///
+/// The hash is the same on every platform for the same state, so the hashes of the frames of two
+/// simulations, like the peers of a lockstep game or a replay and its recording, can be compared
+/// to detect desyncs.
+///
+/// ```rust,ignore
+/// let hasher = FrameHasher::new()
+///     .with_component::<Transform>()
+///     .with_component::<Health>()
+///     .with_resource::<Score>();
+/// let hash = hasher.hash(&world.res);
+/// ```
+#[derive(Default)]
+pub struct FrameHasher {
+    parts: Vec<HashFn>,
+}
+
+impl FrameHasher {
+    /// Creates a hasher hashing nothing yet.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Hashes the component `C` of all the entities, with their ids.
+    pub fn with_component<C>(mut self) -> Self
+    where
+        C: Component + StateHash,
+    {
+        self.parts
+            .push(Box::new(|res: &Resources, hasher: &mut FnvHasher| {
+                let (entities, storage) = <(Entities<'_>, ReadStorage<'_, C>)>::fetch(res);
+                for (entity, component) in (&entities, &storage).join() {
+                    entity.id().hash(hasher);
+                    component.hash_state(hasher);
+                }
+            }));
+        self
+    }
+
+    /// Hashes the resource `R`, if it exists.
+    pub fn with_resource<R>(mut self) -> Self
+    where
+        R: StateHash + Send + Sync + 'static,
+    {
+        self.parts.push(Box::new(
+            |res: &Resources, hasher: &mut FnvHasher| match res.try_fetch::<R>() {
+                Some(resource) => {
+                    true.hash(hasher);
+                    resource.hash_state(hasher);
+                }
+                None => false.hash(hasher),
+            },
+        ));
+        self
+    }
+
+    /// Hashes the current state of the world.
+    pub fn hash(&self, res: &Resources) -> u64 {
+        let mut hasher = FnvHasher::default();
+        for part in &self.parts {
+            part(res, &mut hasher);
+        }
+        hasher.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecs::prelude::{Builder, World};
+    use rand::Rng;
+
+    #[test]
+    fn seeded_rng_repeats() {
+        let mut first = GameRng::new(7);
+        let mut second = GameRng::new(7);
+        let numbers = (0..4).map(|_| first.gen::<u32>()).collect::<Vec<_>>();
+        assert_eq!(
+            numbers,
+            (0..4).map(|_| second.gen::<u32>()).collect::<Vec<_>>()
+        );
+        assert_eq!(first.seed(), Some(7));
+    }
+
+    #[test]
+    fn frame_hash_follows_the_state() {
+        let mut world = World::new();
+        world.register::<Transform>();
+        let entity = world.create_entity().with(Transform::default()).build();
+        let hasher = FrameHasher::new().with_component::<Transform>();
+
+        let before = hasher.hash(&world.res);
+        assert_eq!(before, hasher.hash(&world.res));
+        world
+            .write_storage::<Transform>()
+            .get_mut(entity)
+            .unwrap()
+            .move_up(1.0);
+        assert_ne!(before, hasher.hash(&world.res));
+    }
+}
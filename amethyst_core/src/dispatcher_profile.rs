@@ -0,0 +1,203 @@
+//! Per-system timings of a dispatcher.
+
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::ecs::prelude::System;
+use shred::{Resources, RunningTime};
+
+/// The time a system took to run.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SystemTiming {
+    /// The name of the system.
+    pub name: String,
+    /// The time the last run took.
+    pub last: Duration,
+    /// The average time of the recent runs.
+    pub average: Duration,
+}
+
+/// The resource with the timings of the systems of the `GameData`, updated every time they run.
+///
+/// Profiling is enabled with `GameDataBuilder::with_profiling`, after which the systems added
+/// with `with`, `with_thread_local`, `with_fixed` and by the bundles are timed. Without it, the
+/// resource isn't added and the systems run as they are.
+///
+/// ```rust,ignore
+/// for timing in world.read_resource::<DispatcherProfile>().slowest(5) {
+///     println!("{}: {:?}", timing.name, timing.average);
+/// }
+/// ```
+///
+/// The `summary` can be shown on screen with a `UiResourceBinding` and its system:
+///
+/// ```rust,ignore
+/// world
+///     .create_entity()
+///     .with(text_transform)
+///     .with(UiText::new(font, String::new(), [1.0; 4], 14.0))
+///     .with(UiResourceBinding::<DispatcherProfile>::text(|profile| profile.summary(10)))
+///     .build();
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct DispatcherProfile {
+    // Only locked to add and read the timers, each system writing to its own.
+    timers: Arc<Mutex<Vec<(String, Arc<Timer>)>>>,
+}
+
+/// The nanoseconds of the last and average runs of a system.
+#[derive(Debug, Default)]
+struct Timer {
+    last: AtomicUsize,
+    average: AtomicUsize,
+}
+
+impl DispatcherProfile {
+    /// Wraps `system` so that it records its timings under `name`, or `system <n>` if `name` is
+    /// empty, `n` being the number of systems timed before it.
+    pub fn wrap<S>(&self, system: S, name: &str) -> Profiled<S> {
+        let timer = Arc::new(Timer::default());
+        let mut timers = self.timers.lock().expect("Profile lock poisoned");
+        let name = if name.is_empty() {
+            format!("system {}", timers.len())
+        } else {
+            name.to_owned()
+        };
+        timers.push((name, timer.clone()));
+        Profiled { system, timer }
+    }
+
+    /// The timings of all the timed systems, in the order they were added.
+    pub fn timings(&self) -> Vec<SystemTiming> {
+        let nanos =
+            |timer: &AtomicUsize| Duration::from_nanos(timer.load(Ordering::Relaxed) as u64);
+        self.timers
+            .lock()
+            .map(|timers| {
+                timers
+                    .iter()
+                    .map(|(name, timer)| SystemTiming {
+                        name: name.clone(),
+                        last: nanos(&timer.last),
+                        average: nanos(&timer.average),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// The timings of the `count` systems with the longest average run time, slowest first.
+    pub fn slowest(&self, count: usize) -> Vec<SystemTiming> {
+        let mut timings = self.timings();
+        timings.sort_by(|a, b| b.average.cmp(&a.average));
+        timings.truncate(count);
+        timings
+    }
+
+    /// The timing of the system with the given name.
+    pub fn get(&self, name: &str) -> Option<SystemTiming> {
+        self.timings()
+            .into_iter()
+            .find(|timing| timing.name == name)
+    }
+
+    /// The `count` slowest systems with their average run time in milliseconds, one per line.
+    pub fn summary(&self, count: usize) -> String {
+        self.slowest(count)
+            .iter()
+            .map(|timing| {
+                let millis = timing.average.as_secs() as f64 * 1000.0
+                    + f64::from(timing.average.subsec_nanos()) / 1.0e6;
+                format!("{}: {:.2} ms", timing.name, millis)
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// The total time of the last run of all the timed systems.
+    pub fn total(&self) -> Duration {
+        self.timings()
+            .iter()
+            .map(|timing| timing.last)
+            .sum::<Duration>()
+    }
+}
+
+/// A system recording the time it takes to run in a `DispatcherProfile`.
+///
+/// This is created with [`DispatcherProfile::wrap`].
+///
+/// [`DispatcherProfile::wrap`]: struct.DispatcherProfile.html#method.wrap
+pub struct Profiled<S> {
+    system: S,
+    timer: Arc<Timer>,
+}
+
+impl<'s, S> System<'s> for Profiled<S>
+where
+    S: System<'s>,
+{
+    type SystemData = S::SystemData;
+
+    fn run(&mut self, data: Self::SystemData) {
+        let start = Instant::now();
+        self.system.run(data);
+        let elapsed = start.elapsed();
+        let elapsed = (elapsed.as_secs() as usize)
+            .saturating_mul(1_000_000_000)
+            .saturating_add(elapsed.subsec_nanos() as usize);
+        self.timer.last.store(elapsed, Ordering::Relaxed);
+        // An exponential moving average, smoothing the timings over about ten runs. Only this
+        // system writes to its timer.
+        let average = self.timer.average.load(Ordering::Relaxed);
+        self.timer
+            .average
+            .store(average / 10 * 9 + elapsed / 10, Ordering::Relaxed);
+    }
+
+    fn running_time(&self) -> RunningTime {
+        self.system.running_time()
+    }
+
+    fn setup(&mut self, res: &mut Resources) {
+        self.system.setup(res);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecs::prelude::{DispatcherBuilder, World, Write};
+
+    struct AddOne;
+
+    impl<'a> System<'a> for AddOne {
+        type SystemData = Write<'a, u32>;
+
+        fn run(&mut self, mut number: Self::SystemData) {
+            *number += 1;
+        }
+    }
+
+    #[test]
+    fn timings_are_recorded() {
+        let profile = DispatcherProfile::default();
+        let mut world = World::new();
+        let mut dispatcher = DispatcherBuilder::new()
+            .with(profile.wrap(AddOne, "add_one"), "add_one", &[])
+            .build();
+        dispatcher.setup(&mut world.res);
+        dispatcher.dispatch(&world.res);
+
+        assert_eq!(*world.read_resource::<u32>(), 1);
+        let timing = profile.get("add_one").unwrap();
+        assert_eq!(profile.timings(), vec![timing.clone()]);
+        assert_eq!(profile.slowest(1)[0].name, "add_one");
+        assert_eq!(profile.total(), timing.last);
+    }
+}
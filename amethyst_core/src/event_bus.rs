@@ -0,0 +1,477 @@
+//! A bus of events sorted into topics by their type.
+//!
+//! Unlike with an `EventChannel` per event type, the topics are created on the fly, and the
+//! subscriptions unregister themselves when they are dropped, so systems don't need to keep
+//! `ReaderId`s around and fetch the channel mutably to register them.
+
+use std::{
+    any::{type_name, Any, TypeId},
+    collections::{vec_deque, VecDeque},
+    fmt,
+    sync::{Arc, Mutex, MutexGuard, PoisonError, RwLock},
+};
+
+use fnv::FnvHashMap;
+
+/// The default number of events a topic holds before dropping the oldest unread ones.
+pub const DEFAULT_CAPACITY: usize = 4096;
+
+/// The events a new subscription receives that were published before it subscribed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backlog {
+    /// Only the events published from now on.
+    New,
+    /// At most the given number of the latest events still held by the topic.
+    Last(usize),
+    /// All the events still held by the topic.
+    Retained,
+}
+
+impl Default for Backlog {
+    fn default() -> Self {
+        Backlog::New
+    }
+}
+
+/// The traffic of a topic, see `EventBus::stats`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TopicStats {
+    /// The name of the event type.
+    pub name: &'static str,
+    /// The number of events published since the topic was created.
+    pub published: u64,
+    /// The number of events held by the topic, for the subscriptions that didn't read them yet
+    /// and for late subscribers.
+    pub held: usize,
+    /// The number of subscriptions.
+    pub subscribers: usize,
+    /// The number of events dropped before all the subscriptions read them, because the topic
+    /// was full.
+    pub dropped: u64,
+}
+
+struct Topic<T> {
+    events: VecDeque<T>,
+    // The sequence number of the first event held.
+    first: u64,
+    // The sequence number of the next event to read, for each subscription.
+    cursors: Vec<Option<u64>>,
+    retain: usize,
+    capacity: usize,
+    dropped: u64,
+}
+
+impl<T> Topic<T> {
+    fn end(&self) -> u64 {
+        self.first + self.events.len() as u64
+    }
+
+    // Drops the events all the subscriptions read and that aren't retained, and the oldest ones
+    // if the topic is over capacity.
+    fn trim(&mut self) {
+        let end = self.end();
+        let slowest = self
+            .cursors
+            .iter()
+            .filter_map(|cursor| *cursor)
+            .min()
+            .unwrap_or(end)
+            .max(self.first);
+        let keep = ((end - slowest) as usize)
+            .max(self.retain)
+            .min(self.capacity);
+        let before = self.first;
+        while self.events.len() > keep {
+            self.events.pop_front();
+            self.first += 1;
+        }
+        self.dropped += self.first.saturating_sub(slowest.max(before));
+    }
+
+    fn stats(&self) -> TopicStats {
+        TopicStats {
+            name: type_name::<T>(),
+            published: self.end(),
+            held: self.events.len(),
+            subscribers: self
+                .cursors
+                .iter()
+                .filter(|cursor| cursor.is_some())
+                .count(),
+            dropped: self.dropped,
+        }
+    }
+}
+
+impl<T> Default for Topic<T> {
+    fn default() -> Self {
+        Topic {
+            events: VecDeque::new(),
+            first: 0,
+            cursors: Vec::new(),
+            retain: 0,
+            capacity: DEFAULT_CAPACITY,
+            dropped: 0,
+        }
+    }
+}
+
+type SharedTopic<T> = Arc<Mutex<Topic<T>>>;
+
+trait AnyTopic: Send + Sync {
+    fn stats(&self) -> TopicStats;
+
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl<T> AnyTopic for SharedTopic<T>
+where
+    T: Send + Sync + 'static,
+{
+    fn stats(&self) -> TopicStats {
+        lock(self).stats()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+// A system panicking while holding a topic leaves it consistent, so the poison is ignored.
+fn lock<T>(topic: &Mutex<Topic<T>>) -> MutexGuard<'_, Topic<T>> {
+    topic.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// A resource holding a topic of events for every event type published or subscribed to.
+///
+/// Publishing and subscribing only need to read the bus, so the systems using it can run in
+/// parallel. A topic holds its events until every subscription has read them, up to its capacity,
+/// after which the oldest ones are dropped. It can retain the latest events for late subscribers.
+///
+/// ```rust
+/// use amethyst_core::{
+///     ecs::prelude::*,
+///     event_bus::{EventBus, Subscription},
+/// };
+///
+/// #[derive(Debug)]
+/// struct Explosion {
+///     radius: f32,
+/// }
+///
+/// #[derive(Default)]
+/// struct ShakeSystem {
+///     explosions: Option<Subscription<Explosion>>,
+/// }
+///
+/// impl<'a> System<'a> for ShakeSystem {
+///     type SystemData = ();
+///
+///     fn run(&mut self, _: Self::SystemData) {
+///         let explosions = self.explosions.as_mut().expect("setup was not called");
+///         for explosion in &explosions.read() {
+///             println!("Shaking for {:?}", explosion);
+///         }
+///     }
+///
+///     fn setup(&mut self, res: &mut Resources) {
+///         Self::SystemData::setup(res);
+///         self.explosions = Some(res.entry().or_insert_with(EventBus::new).subscribe());
+///     }
+/// }
+///
+/// let mut world = World::new();
+/// let mut system = ShakeSystem::default();
+/// System::setup(&mut system, &mut world.res);
+/// world.read_resource::<EventBus>().publish(Explosion { radius: 2.0 });
+/// system.run_now(&world.res);
+/// ```
+#[derive(Default)]
+pub struct EventBus {
+    topics: RwLock<FnvHashMap<TypeId, Box<dyn AnyTopic>>>,
+}
+
+impl EventBus {
+    /// Creates a bus without topics.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    fn topic<T>(&self) -> SharedTopic<T>
+    where
+        T: Send + Sync + 'static,
+    {
+        let id = TypeId::of::<T>();
+        let existing = self
+            .topics
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&id)
+            .map(|topic| downcast::<T>(&**topic));
+        existing.unwrap_or_else(|| {
+            let mut topics = self.topics.write().unwrap_or_else(PoisonError::into_inner);
+            let topic = topics
+                .entry(id)
+                .or_insert_with(|| Box::new(SharedTopic::<T>::default()) as Box<dyn AnyTopic>);
+            downcast::<T>(&**topic)
+        })
+    }
+
+    /// Publishes an event to the subscriptions of its type.
+    pub fn publish<T>(&self, event: T)
+    where
+        T: Send + Sync + 'static,
+    {
+        self.publish_all(Some(event));
+    }
+
+    /// Publishes several events of the same type at once, locking their topic only once.
+    pub fn publish_all<T, I>(&self, events: I)
+    where
+        T: Send + Sync + 'static,
+        I: IntoIterator<Item = T>,
+    {
+        let topic = self.topic::<T>();
+        let mut topic = lock(&topic);
+        for event in events {
+            topic.events.push_back(event);
+            if topic.events.len() > topic.capacity {
+                topic.trim();
+            }
+        }
+        topic.trim();
+    }
+
+    /// Subscribes to the events of type `T` published from now on.
+    pub fn subscribe<T>(&self) -> Subscription<T>
+    where
+        T: Send + Sync + 'static,
+    {
+        self.subscribe_with(Backlog::New)
+    }
+
+    /// Subscribes to the events of type `T`, starting with the given backlog of the events
+    /// published before.
+    pub fn subscribe_with<T>(&self, backlog: Backlog) -> Subscription<T>
+    where
+        T: Send + Sync + 'static,
+    {
+        let shared = self.topic::<T>();
+        let id = {
+            let mut topic = lock(&shared);
+            let end = topic.end();
+            let cursor = match backlog {
+                Backlog::New => end,
+                Backlog::Last(count) => end - count.min(topic.events.len()) as u64,
+                Backlog::Retained => topic.first,
+            };
+            match topic.cursors.iter().position(Option::is_none) {
+                Some(id) => {
+                    topic.cursors[id] = Some(cursor);
+                    id
+                }
+                None => {
+                    topic.cursors.push(Some(cursor));
+                    topic.cursors.len() - 1
+                }
+            }
+        };
+        Subscription { topic: shared, id }
+    }
+
+    /// Retains the given number of the latest events of type `T`, even once all the
+    /// subscriptions read them, for the subscriptions with a backlog.
+    pub fn set_retained<T>(&self, count: usize)
+    where
+        T: Send + Sync + 'static,
+    {
+        let topic = self.topic::<T>();
+        let mut topic = lock(&topic);
+        topic.retain = count;
+        topic.capacity = topic.capacity.max(count);
+        topic.trim();
+    }
+
+    /// Sets the number of events of type `T` held before the oldest ones are dropped, even if
+    /// some subscriptions didn't read them, `DEFAULT_CAPACITY` by default.
+    pub fn set_capacity<T>(&self, capacity: usize)
+    where
+        T: Send + Sync + 'static,
+    {
+        let topic = self.topic::<T>();
+        let mut topic = lock(&topic);
+        topic.capacity = capacity.max(1);
+        topic.retain = topic.retain.min(topic.capacity);
+        topic.trim();
+    }
+
+    /// The traffic of every topic, sorted by name, to debug how events flow through the game.
+    pub fn stats(&self) -> Vec<TopicStats> {
+        let mut stats = self
+            .topics
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .values()
+            .map(|topic| topic.stats())
+            .collect::<Vec<_>>();
+        stats.sort_by_key(|stats| stats.name);
+        stats
+    }
+}
+
+impl fmt::Debug for EventBus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EventBus")
+            .field("topics", &self.stats())
+            .finish()
+    }
+}
+
+fn downcast<T>(topic: &dyn AnyTopic) -> SharedTopic<T>
+where
+    T: Send + Sync + 'static,
+{
+    topic
+        .as_any()
+        .downcast_ref::<SharedTopic<T>>()
+        .expect("topics are stored by the id of their event type")
+        .clone()
+}
+
+/// A subscription to the events of type `T` of an `EventBus`, unsubscribing when dropped.
+pub struct Subscription<T> {
+    topic: SharedTopic<T>,
+    id: usize,
+}
+
+impl<T> Subscription<T> {
+    /// Reads the events published since the last read.
+    ///
+    /// The topic is locked while the events are borrowed, so events of the same type can't be
+    /// published until they are dropped.
+    pub fn read(&mut self) -> Events<'_, T> {
+        let mut topic = lock(&self.topic);
+        let end = topic.end();
+        let cursor = topic.cursors[self.id].unwrap_or(end);
+        let missed = topic.first.saturating_sub(cursor);
+        let start = (cursor.max(topic.first) - topic.first) as usize;
+        topic.cursors[self.id] = Some(end);
+        Events {
+            topic,
+            start,
+            missed,
+        }
+    }
+
+    /// The number of events waiting to be read.
+    pub fn pending(&self) -> usize {
+        let topic = lock(&self.topic);
+        let cursor = topic.cursors[self.id]
+            .unwrap_or(topic.first)
+            .max(topic.first);
+        (topic.end() - cursor) as usize
+    }
+}
+
+impl<T> Drop for Subscription<T> {
+    fn drop(&mut self) {
+        let mut topic = lock(&self.topic);
+        topic.cursors[self.id] = None;
+        topic.trim();
+    }
+}
+
+impl<T> fmt::Debug for Subscription<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Subscription")
+            .field("topic", &type_name::<T>())
+            .field("pending", &self.pending())
+            .finish()
+    }
+}
+
+/// The events read by a `Subscription`.
+pub struct Events<'a, T> {
+    topic: MutexGuard<'a, Topic<T>>,
+    start: usize,
+    missed: u64,
+}
+
+impl<'a, T> Events<'a, T> {
+    /// Iterates over the events, oldest first.
+    pub fn iter(&self) -> vec_deque::Iter<'_, T> {
+        self.topic.events.range(self.start..)
+    }
+
+    /// The number of events.
+    pub fn len(&self) -> usize {
+        self.topic.events.len() - self.start
+    }
+
+    /// Checks whether there are no events.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The number of events dropped before they could be read, because the topic was full.
+    pub fn missed(&self) -> u64 {
+        self.missed
+    }
+}
+
+impl<'a, 'b, T> IntoIterator for &'b Events<'a, T> {
+    type Item = &'b T;
+    type IntoIter = vec_deque::Iter<'b, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, T> Drop for Events<'a, T> {
+    fn drop(&mut self) {
+        self.topic.trim();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(subscription: &mut Subscription<u32>) -> Vec<u32> {
+        subscription.read().iter().cloned().collect()
+    }
+
+    #[test]
+    fn subscriptions_read_each_event_once() {
+        let bus = EventBus::new();
+        let mut first = bus.subscribe::<u32>();
+        bus.publish(1u32);
+        let mut second = bus.subscribe::<u32>();
+        bus.publish_all(vec![2u32, 3]);
+
+        assert_eq!(read(&mut first), vec![1, 2, 3]);
+        assert_eq!(read(&mut second), vec![2, 3]);
+        assert!(read(&mut first).is_empty());
+        assert_eq!(bus.stats()[0].held, 0);
+
+        drop(second);
+        assert_eq!(bus.stats()[0].subscribers, 1);
+    }
+
+    #[test]
+    fn backlog_and_capacity() {
+        let bus = EventBus::new();
+        bus.set_retained::<u32>(2);
+        bus.publish_all(vec![1u32, 2, 3]);
+        assert_eq!(read(&mut bus.subscribe_with(Backlog::Retained)), vec![2, 3]);
+        assert_eq!(read(&mut bus.subscribe_with(Backlog::Last(1))), vec![3]);
+
+        bus.set_capacity::<u32>(2);
+        let mut slow = bus.subscribe::<u32>();
+        bus.publish_all(vec![4u32, 5, 6]);
+        let events = slow.read();
+        assert_eq!(events.iter().cloned().collect::<Vec<_>>(), vec![5, 6]);
+        assert_eq!(events.missed(), 1);
+    }
+}
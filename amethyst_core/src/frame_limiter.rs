@@ -38,7 +38,7 @@
 //!
 //! # Frame Rate Limiting Strategies
 //!
-//! The four possible strategies described by [`FrameRateLimitStrategy`] are as follows:
+//! The five possible strategies described by [`FrameRateLimitStrategy`] are as follows:
 //!
 //! * `Unlimited` will not try to limit the frame rate to the specified maximum. Amethyst
 //!   will call [`thread::yield_now`] once and then continue to the next frame.
@@ -53,6 +53,10 @@
 //!   and then will yield until the next frame starts. This approach attempts to get the
 //!   consistent frame timings of yielding, while reducing CPU usage compared to the yield-only
 //!   approach.
+//! * `Hybrid` will sleep in short steps while there's enough time left that the operating system
+//!   will wake the game in time, and then will spin until the next frame starts. How late the
+//!   operating system wakes the game is measured while running, so unlike `SleepAndYield` there
+//!   is no grace period to tune.
 //!
 //! By default amethyst will use the `Yield` strategy, which is fine for desktop and console
 //! games that aren't as affected by extra CPU usage. For mobile devices, the `Sleep` strategy
@@ -61,13 +65,21 @@
 //! `SleepAndYield` can potentially be as accurate as `Yield` while using less CPU time, but you
 //! will have to test different grace period timings to determine how much time needs to be left
 //! to ensure that the main thread doesn't sleep too long and miss the start of the next frame.
+//! `Hybrid` finds this grace period by itself.
+//!
+//! The strategy and the maximum frame rate can be changed at runtime through the `FrameLimiter`
+//! resource, for example from a settings menu. The resource also measures how long the recent
+//! frames took with [`FrameLimiter::stats`].
 //!
 //! [`Application`]: ../../amethyst/struct.Application.html
 //! [`FrameRateLimitStrategy`]: ./enum.FrameRateLimitStrategy.html
+//! [`FrameLimiter::stats`]: ./struct.FrameLimiter.html#method.stats
 //! [`thread::yield_now`]: https://doc.rust-lang.org/std/thread/fn.yield_now.html
 //! [`thread::sleep`]: https://doc.rust-lang.org/stable/std/thread/fn.sleep.html
 
 use std::{
+    collections::VecDeque,
+    hint::spin_loop,
     thread::{sleep, yield_now},
     time::{Duration, Instant},
 };
@@ -76,6 +88,10 @@ use derive_new::new;
 use serde::{Deserialize, Serialize};
 
 const ZERO: Duration = Duration::from_millis(0);
+// The duration of the sleeps of the `Hybrid` strategy.
+const SLEEP_STEP: Duration = Duration::from_millis(1);
+// The number of frames the statistics are measured over.
+const STATS_FRAMES: usize = 120;
 
 /// Frame rate limiting strategy.
 ///
@@ -99,6 +115,12 @@ pub enum FrameRateLimitStrategy {
     /// Will sleep repeatedly until the given duration remains, and then will yield repeatedly
     /// for the remaining frame time.
     SleepAndYield(Duration),
+
+    /// Sleep while the operating system reliably wakes up in time, then spin.
+    ///
+    /// The time left when the sleeping stops adapts to how late the sleeps were measured to
+    /// end, for accurate frame timings with a low CPU usage.
+    Hybrid,
 }
 
 impl Default for FrameRateLimitStrategy {
@@ -148,8 +170,28 @@ impl Default for FrameRateLimitConfig {
 #[derive(Debug)]
 pub struct FrameLimiter {
     frame_duration: Duration,
+    fps: u32,
     strategy: FrameRateLimitStrategy,
     last_call: Instant,
+    // How late a sleep was seen to end, decaying over time.
+    oversleep: Duration,
+    // The work and total durations of the recent frames.
+    frames: VecDeque<(Duration, Duration)>,
+}
+
+/// Statistics on the duration of the recent frames, measured by the `FrameLimiter`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FrameStats {
+    /// The duration of the last frame.
+    pub last: Duration,
+    /// The average duration of the recent frames.
+    pub average: Duration,
+    /// The shortest recent frame.
+    pub min: Duration,
+    /// The longest recent frame.
+    pub max: Duration,
+    /// The average time the recent frames spent working, before waiting for the next frame.
+    pub average_work: Duration,
 }
 
 impl Default for FrameLimiter {
@@ -163,8 +205,11 @@ impl FrameLimiter {
     pub fn new(strategy: FrameRateLimitStrategy, fps: u32) -> Self {
         let mut s = Self {
             frame_duration: Duration::from_secs(0),
+            fps: 0,
             strategy: Default::default(),
             last_call: Instant::now(),
+            oversleep: SLEEP_STEP,
+            frames: VecDeque::with_capacity(STATS_FRAMES),
         };
         s.set_rate(strategy, fps);
         s
@@ -177,9 +222,62 @@ impl FrameLimiter {
             fps = 144;
         }
         self.strategy = strategy;
+        self.fps = fps;
         self.frame_duration = Duration::from_secs(1) / fps;
     }
 
+    /// Sets the maximum fps, keeping the strategy. 0 disables the limit.
+    pub fn set_fps(&mut self, fps: u32) {
+        let strategy = self.strategy.clone();
+        self.set_rate(strategy, fps);
+    }
+
+    /// Sets the frame rate limiting strategy, keeping the maximum fps.
+    pub fn set_strategy(&mut self, strategy: FrameRateLimitStrategy) {
+        self.strategy = strategy;
+    }
+
+    /// The maximum fps.
+    pub fn fps(&self) -> u32 {
+        self.fps
+    }
+
+    /// The frame rate limiting strategy.
+    pub fn strategy(&self) -> &FrameRateLimitStrategy {
+        &self.strategy
+    }
+
+    /// Statistics on the duration of the recent frames.
+    pub fn stats(&self) -> FrameStats {
+        let count = self.frames.len() as u32;
+        if count == 0 {
+            return FrameStats::default();
+        }
+        let total = self
+            .frames
+            .iter()
+            .map(|&(_, frame)| frame)
+            .sum::<Duration>();
+        let work = self.frames.iter().map(|&(work, _)| work).sum::<Duration>();
+        FrameStats {
+            last: self.frames.back().map(|&(_, frame)| frame).unwrap_or(ZERO),
+            average: total / count,
+            min: self
+                .frames
+                .iter()
+                .map(|&(_, frame)| frame)
+                .min()
+                .unwrap_or(ZERO),
+            max: self
+                .frames
+                .iter()
+                .map(|&(_, frame)| frame)
+                .max()
+                .unwrap_or(ZERO),
+            average_work: work / count,
+        }
+    }
+
     /// Creates a new frame limiter with the given config.
     pub fn from_config(config: FrameRateLimitConfig) -> Self {
         Self::new(config.strategy, config.fps)
@@ -202,6 +300,7 @@ impl FrameLimiter {
     /// [`Application`]: ../../amethyst/struct.Application.html
     pub fn wait(&mut self) {
         use self::FrameRateLimitStrategy::*;
+        let work = Instant::now() - self.last_call;
         match self.strategy {
             Unlimited => yield_now(),
 
@@ -213,8 +312,19 @@ impl FrameLimiter {
                 self.do_sleep(dur);
                 self.do_yield();
             }
+
+            Hybrid => self.do_hybrid(),
         }
-        self.last_call = Instant::now();
+        let now = Instant::now();
+        self.record(work, now - self.last_call);
+        self.last_call = now;
+    }
+
+    fn record(&mut self, work: Duration, frame: Duration) {
+        if self.frames.len() == STATS_FRAMES {
+            self.frames.pop_front();
+        }
+        self.frames.push_back((work, frame));
     }
 
     fn do_yield(&self) {
@@ -223,6 +333,25 @@ impl FrameLimiter {
         }
     }
 
+    fn do_hybrid(&mut self) {
+        let end = self.last_call + self.frame_duration;
+        loop {
+            let now = Instant::now();
+            if now >= end || end - now <= SLEEP_STEP + self.oversleep {
+                break;
+            }
+            sleep(SLEEP_STEP);
+            let late = (Instant::now() - now)
+                .checked_sub(SLEEP_STEP)
+                .unwrap_or(ZERO);
+            // Follows late sleeps right away, and slowly trusts the sleeps again.
+            self.oversleep = late.max(self.oversleep * 63 / 64);
+        }
+        while Instant::now() < end {
+            spin_loop();
+        }
+    }
+
     fn do_sleep(&self, stop_on_remaining: Duration) {
         let frame_duration = self.frame_duration - stop_on_remaining;
         loop {
@@ -235,3 +364,32 @@ impl FrameLimiter {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stats_of_recent_frames() {
+        let mut limiter = FrameLimiter::new(FrameRateLimitStrategy::Hybrid, 60);
+        assert_eq!(limiter.stats(), FrameStats::default());
+        for millis in 0..(STATS_FRAMES as u64 + 10) {
+            limiter.record(
+                Duration::from_millis(millis / 2),
+                Duration::from_millis(millis),
+            );
+        }
+        let stats = limiter.stats();
+        assert_eq!(stats.last, Duration::from_millis(129));
+        assert_eq!(stats.min, Duration::from_millis(10));
+        assert_eq!(stats.max, Duration::from_millis(129));
+        assert_eq!(stats.average, Duration::from_micros(69_500));
+
+        limiter.set_fps(0);
+        assert_eq!(limiter.fps(), 144);
+        match limiter.strategy() {
+            FrameRateLimitStrategy::Unlimited => (),
+            strategy => panic!("Expected no limit, got {:?}", strategy),
+        }
+    }
+}
@@ -16,20 +16,31 @@ use rayon;
 use std::sync::Arc;
 
 pub use crate::{
-    bundle::SystemBundle,
+    bundle::{BundleBuilder, BundleRequirement, SystemBundle},
+    changes::ComponentChanges,
+    deterministic::{FrameHasher, GameRng, StateHash},
+    dispatcher_profile::{DispatcherProfile, Profiled, SystemTiming},
     event::EventReader,
-    system_ext::{Pausable, SystemExt},
+    event_bus::{EventBus, Subscription},
+    log_buffer::{LogBuffer, LogEntry},
+    system_ext::{Pausable, RunCondition, RunIf, RunWhen, SystemExt, Throttled},
     timing::*,
     transform::*,
 };
 
 pub use self::{
     axis::{Axis2, Axis3},
-    named::{Named, WithNamed},
+    named::{NameIndex, NameIndexSystem, Named, NamedEntities, WithNamed},
 };
 
 pub mod bundle;
+pub mod changes;
+pub mod deterministic;
+pub mod dispatcher_profile;
+pub mod event_bus;
 pub mod frame_limiter;
+pub mod log_buffer;
+pub mod reflect;
 pub mod timing;
 pub mod transform;
 
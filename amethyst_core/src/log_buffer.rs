@@ -0,0 +1,171 @@
+//! The recent log records, kept for the in-game log views.
+
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+
+use log::{Level, Log, Metadata, Record};
+
+/// A log record kept by the `LogBuffer`, with its fields apart.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LogEntry {
+    /// The level of the record.
+    pub level: Level,
+    /// The target of the record, its module unless it was given one.
+    pub target: String,
+    /// The message of the record, formatted.
+    pub message: String,
+    /// The source file the record was logged in, if it's known.
+    pub file: Option<String>,
+    /// The line the record was logged on, if it's known.
+    pub line: Option<u32>,
+}
+
+impl<'a, 'b> From<&'a Record<'b>> for LogEntry {
+    fn from(record: &'a Record<'b>) -> Self {
+        LogEntry {
+            level: record.level(),
+            target: record.target().to_string(),
+            message: record.args().to_string(),
+            file: record.file().map(str::to_string),
+            line: record.line(),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Entries {
+    entries: VecDeque<LogEntry>,
+    total: u64,
+}
+
+/// A ring buffer of the recent log records, a `Log` sink chained to the logger and a resource
+/// read by the log views, like the `UiLogPanel`. The `CrashLog` of the crash reports keeps its
+/// records in one too.
+///
+/// The clones of the buffer share its records.
+#[derive(Clone, Debug)]
+pub struct LogBuffer {
+    capacity: usize,
+    entries: Arc<Mutex<Entries>>,
+}
+
+impl Default for LogBuffer {
+    fn default() -> Self {
+        LogBuffer::new(500)
+    }
+}
+
+impl LogBuffer {
+    /// Creates a buffer keeping the `capacity` most recent records.
+    pub fn new(capacity: usize) -> Self {
+        LogBuffer {
+            capacity,
+            entries: Default::default(),
+        }
+    }
+
+    /// The number of records the buffer keeps.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Adds a record, dropping the oldest one if the buffer is full.
+    pub fn push(&self, entry: LogEntry) {
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.total += 1;
+            if self.capacity == 0 {
+                return;
+            }
+            if entries.entries.len() == self.capacity {
+                entries.entries.pop_front();
+            }
+            entries.entries.push_back(entry);
+        }
+    }
+
+    /// The kept records, oldest first.
+    pub fn entries(&self) -> Vec<LogEntry> {
+        self.entries
+            .lock()
+            .map(|entries| entries.entries.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// The `count` most recent records at `level` or more severe, oldest first.
+    pub fn recent(&self, count: usize, level: Level) -> Vec<LogEntry> {
+        let entries = match self.entries.lock() {
+            Ok(entries) => entries,
+            Err(_) => return Vec::new(),
+        };
+        let mut recent = entries
+            .entries
+            .iter()
+            .rev()
+            .filter(|entry| entry.level <= level)
+            .take(count)
+            .cloned()
+            .collect::<Vec<_>>();
+        recent.reverse();
+        recent
+    }
+
+    /// The number of records added since the buffer was created, to know when it changed.
+    pub fn total(&self) -> u64 {
+        self.entries.lock().map_or(0, |entries| entries.total)
+    }
+
+    /// Removes the kept records.
+    pub fn clear(&self) {
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.entries.clear();
+            entries.total += 1;
+        }
+    }
+}
+
+impl Log for LogBuffer {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        // A panic formatting the arguments mustn't poison the buffer, so `push` locks it after.
+        self.push(LogEntry::from(record));
+    }
+
+    fn flush(&self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(level: Level, message: &str) -> LogEntry {
+        LogEntry {
+            level,
+            target: "game".to_string(),
+            message: message.to_string(),
+            file: None,
+            line: None,
+        }
+    }
+
+    #[test]
+    fn oldest_records_are_dropped() {
+        let buffer = LogBuffer::new(3);
+        buffer.push(entry(Level::Warn, "a"));
+        buffer.push(entry(Level::Info, "b"));
+        buffer.push(entry(Level::Error, "c"));
+        buffer.push(entry(Level::Debug, "d"));
+
+        assert_eq!(buffer.total(), 4);
+        assert_eq!(buffer.entries().len(), 3);
+        let recent = buffer.recent(5, Level::Info);
+        assert_eq!(
+            recent.iter().map(|e| &e.message[..]).collect::<Vec<_>>(),
+            vec!["b", "c"]
+        );
+    }
+}
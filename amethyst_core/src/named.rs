@@ -1,7 +1,17 @@
-use std::borrow::Cow;
+use std::{borrow::Cow, collections::HashMap};
 
-use crate::ecs::{world::LazyBuilder, Component, DenseVecStorage, EntityBuilder, WriteStorage};
+use crate::{
+    ecs::{
+        prelude::{
+            ComponentEvent, Entities, Entity, Read, ReadStorage, ReaderId, Resources, System, Write,
+        },
+        world::LazyBuilder,
+        Component, DenseVecStorage, EntityBuilder, FlaggedStorage, WriteStorage,
+    },
+    transform::Parent,
+};
 use serde::{Deserialize, Serialize};
+use shred_derive::SystemData;
 
 /// A component that gives a name to an [`Entity`].
 ///
@@ -16,11 +26,15 @@ use serde::{Deserialize, Serialize};
 /// can generally treat the `name` field as a [`&str`][str] without needing to know whether the
 /// name is actually an owned or borrowed string.
 ///
+/// With the `TransformBundle`, named entities are indexed in the `NameIndex` resource and can be
+/// found by name or by path with the [`NamedEntities`] system data.
+///
 /// [`Entity`]: https://docs.rs/specs/*/specs/struct.Entity.html
 /// [`Cow<'static, str>`]: https://doc.rust-lang.org/std/borrow/enum.Cow.html
 /// [`String`]: https://doc.rust-lang.org/std/string/struct.String.html
 /// [str]: https://doc.rust-lang.org/std/primitive.str.html
 /// [`Named::new`]: #method.new
+/// [`NamedEntities`]: struct.NamedEntities.html
 ///
 /// # Examples
 ///
@@ -113,7 +127,7 @@ impl Named {
 }
 
 impl Component for Named {
-    type Storage = DenseVecStorage<Self>;
+    type Storage = FlaggedStorage<Self, DenseVecStorage<Self>>;
 }
 
 /// An easy way to name an `Entity` and give it a `Named` `Component`.
@@ -150,3 +164,195 @@ impl<'a> WithNamed for LazyBuilder<'a> {
         self
     }
 }
+
+/// The resource indexing the entities by their `Named` component, kept up to date by the
+/// `NameIndexSystem` of the `TransformBundle`.
+///
+/// Several entities may have the same name. To find an entity by its path in the hierarchy, use
+/// `NamedEntities`.
+#[derive(Debug, Default)]
+pub struct NameIndex {
+    by_name: HashMap<String, Vec<Entity>>,
+    by_id: HashMap<u32, (Entity, String)>,
+}
+
+impl NameIndex {
+    /// The first entity with the given name, in the order they were named.
+    pub fn get(&self, name: &str) -> Option<Entity> {
+        self.all(name).first().cloned()
+    }
+
+    /// All the entities with the given name.
+    pub fn all(&self, name: &str) -> &[Entity] {
+        self.by_name.get(name).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    /// The name of an entity, if it has one.
+    pub fn name_of(&self, entity: Entity) -> Option<&str> {
+        self.by_id
+            .get(&entity.id())
+            .filter(|(named, _)| *named == entity)
+            .map(|(_, name)| name.as_str())
+    }
+
+    fn insert(&mut self, entity: Entity, name: &str) {
+        self.remove(entity.id());
+        self.by_name
+            .entry(name.to_owned())
+            .or_insert_with(Vec::new)
+            .push(entity);
+        self.by_id.insert(entity.id(), (entity, name.to_owned()));
+    }
+
+    fn remove(&mut self, id: u32) {
+        if let Some((entity, name)) = self.by_id.remove(&id) {
+            let now_empty = match self.by_name.get_mut(&name) {
+                Some(entities) => {
+                    entities.retain(|named| *named != entity);
+                    entities.is_empty()
+                }
+                None => false,
+            };
+            if now_empty {
+                self.by_name.remove(&name);
+            }
+        }
+    }
+}
+
+/// Keeps the `NameIndex` up to date when `Named` components are added, changed or removed, and
+/// when named entities are deleted.
+#[derive(Default)]
+pub struct NameIndexSystem {
+    reader: Option<ReaderId<ComponentEvent>>,
+}
+
+impl NameIndexSystem {
+    /// Creates a new `NameIndexSystem`.
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl<'a> System<'a> for NameIndexSystem {
+    type SystemData = (Entities<'a>, ReadStorage<'a, Named>, Write<'a, NameIndex>);
+
+    fn run(&mut self, (entities, names, mut index): Self::SystemData) {
+        let events = names.channel().read(
+            self.reader
+                .as_mut()
+                .expect("`NameIndexSystem::setup` was not called before `NameIndexSystem::run`"),
+        );
+        for event in events {
+            match *event {
+                ComponentEvent::Inserted(id) | ComponentEvent::Modified(id) => {
+                    let entity = entities.entity(id);
+                    match names.get(entity) {
+                        Some(named) => index.insert(entity, &named.name),
+                        None => index.remove(id),
+                    }
+                }
+                ComponentEvent::Removed(id) => index.remove(id),
+            }
+        }
+    }
+
+    fn setup(&mut self, res: &mut Resources) {
+        use crate::ecs::prelude::SystemData;
+        Self::SystemData::setup(res);
+        self.reader = Some(WriteStorage::<Named>::fetch(res).register_reader());
+    }
+}
+
+/// Utility `SystemData` to find entities by their name, or by their path of names in the
+/// hierarchy.
+///
+/// A path like `"Player/Weapon"` finds an entity named `Weapon` whose parent is named `Player`.
+/// The path doesn't have to start at the root of the hierarchy.
+#[derive(SystemData)]
+pub struct NamedEntities<'a> {
+    index: Read<'a, NameIndex>,
+    parents: ReadStorage<'a, Parent>,
+}
+
+impl<'a> NamedEntities<'a> {
+    /// The first entity matching the path.
+    pub fn get(&self, path: &str) -> Option<Entity> {
+        self.find(path).next()
+    }
+
+    /// All the entities matching the path.
+    pub fn find<'p>(&'p self, path: &'p str) -> impl Iterator<Item = Entity> + 'p {
+        let last = path.rsplit('/').next().unwrap_or("");
+        self.index.all(last).iter().cloned().filter(move |&entity| {
+            let mut current = entity;
+            for segment in path.rsplit('/').skip(1) {
+                match self.parents.get(current) {
+                    Some(parent) if self.index.name_of(parent.entity) == Some(segment) => {
+                        current = parent.entity;
+                    }
+                    _ => return false,
+                }
+            }
+            true
+        })
+    }
+
+    /// The name of an entity, if it has one.
+    pub fn name_of(&self, entity: Entity) -> Option<&str> {
+        self.index.name_of(entity)
+    }
+
+    /// The path of names from the topmost named ancestor to the entity, stopping at the first
+    /// ancestor without a name.
+    pub fn path_of(&self, entity: Entity) -> Option<String> {
+        let mut names = vec![self.index.name_of(entity)?];
+        let mut current = entity;
+        while let Some(parent) = self.parents.get(current) {
+            match self.index.name_of(parent.entity) {
+                Some(name) => names.push(name),
+                None => break,
+            }
+            current = parent.entity;
+        }
+        names.reverse();
+        Some(names.join("/"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecs::prelude::{Builder, RunNow, World};
+
+    #[test]
+    fn lookup_by_path() {
+        let mut world = World::new();
+        world.register::<Parent>();
+        let mut system = NameIndexSystem::new();
+        System::setup(&mut system, &mut world.res);
+
+        let player = world.create_entity().named("Player").build();
+        let weapon = world
+            .create_entity()
+            .named("Weapon")
+            .with(Parent { entity: player })
+            .build();
+        let other = world.create_entity().named("Weapon").build();
+        system.run_now(&world.res);
+
+        {
+            let names = world.system_data::<NamedEntities<'_>>();
+            assert_eq!(names.get("Player/Weapon"), Some(weapon));
+            assert_eq!(names.find("Weapon").count(), 2);
+            assert_eq!(names.path_of(weapon), Some("Player/Weapon".to_owned()));
+        }
+
+        world.delete_entity(weapon).unwrap();
+        world.maintain();
+        system.run_now(&world.res);
+        let names = world.system_data::<NamedEntities<'_>>();
+        assert_eq!(names.get("Player/Weapon"), None);
+        assert_eq!(names.get("Weapon"), Some(other));
+    }
+}
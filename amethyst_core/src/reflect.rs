@@ -0,0 +1,293 @@
+//! The registry of the components known by their names, for the tools and the scripts.
+
+use std::{collections::BTreeMap, marker::PhantomData, sync::Arc};
+
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{Map, Value};
+
+use amethyst_error::Error;
+
+use crate::ecs::prelude::{Component, Entity, ReadStorage, Resources, SystemData, WriteStorage};
+
+/// The description of a field of a component.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Field {
+    /// The name of the field, or its index in a tuple struct.
+    pub name: &'static str,
+    /// The type of the field, as written in the component.
+    pub type_name: &'static str,
+}
+
+/// A component describing its fields, which `#[derive(Reflect)]` implements for the structs.
+///
+/// The fields are only a description: the components are read and written whole, through their
+/// serialization.
+pub trait Reflect: Component + Serialize + DeserializeOwned {
+    /// The fields of the component, in their order.
+    fn fields() -> Vec<Field>;
+}
+
+/// A component registered in the `ComponentRegistry`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ComponentInfo {
+    /// The name the component is registered under.
+    pub name: String,
+    /// The fields of the component, empty when they aren't known.
+    pub fields: Vec<Field>,
+}
+
+// The access to the components of a type, through their serialization.
+trait ComponentAccess: Send + Sync {
+    fn setup(&self, res: &mut Resources);
+    fn get(&self, res: &Resources, entity: Entity) -> Result<Option<Value>, Error>;
+    fn set(&self, res: &Resources, entity: Entity, value: Value) -> Result<(), Error>;
+    fn remove(&self, res: &Resources, entity: Entity);
+}
+
+struct Access<T>(PhantomData<T>);
+
+impl<T> ComponentAccess for Access<T>
+where
+    T: Component + Serialize + DeserializeOwned,
+    T::Storage: Default,
+{
+    fn setup(&self, res: &mut Resources) {
+        WriteStorage::<T>::setup(res);
+    }
+
+    fn get(&self, res: &Resources, entity: Entity) -> Result<Option<Value>, Error> {
+        match ReadStorage::<T>::fetch(res).get(entity) {
+            Some(component) => Ok(Some(serde_json::to_value(component)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn set(&self, res: &Resources, entity: Entity, value: Value) -> Result<(), Error> {
+        let component = serde_json::from_value::<T>(value)?;
+        WriteStorage::<T>::fetch(res).insert(entity, component)?;
+        Ok(())
+    }
+
+    fn remove(&self, res: &Resources, entity: Entity) {
+        WriteStorage::<T>::fetch(res).remove(entity);
+    }
+}
+
+#[derive(Clone)]
+struct Registration {
+    info: ComponentInfo,
+    access: Arc<dyn ComponentAccess>,
+}
+
+/// The components known by their names, read and written as JSON values through their
+/// serialization, as the prefabs are. It lets the inspectors, the scripts and the scenes work on
+/// the components without knowing their types:
+///
+/// ```rust
+/// use amethyst::{
+///     core::reflect::{ComponentRegistry, Field, Reflect},
+///     derive::Reflect,
+///     ecs::prelude::*,
+/// };
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Reflect, Serialize, Deserialize)]
+/// struct Health {
+///     value: u32,
+/// }
+///
+/// impl Component for Health {
+///     type Storage = DenseVecStorage<Self>;
+/// }
+///
+/// let mut world = World::new();
+/// let mut registry = ComponentRegistry::new();
+/// registry.register::<Health, _>("Health");
+/// registry.setup(&mut world.res);
+///
+/// let entity = world.create_entity().build();
+/// registry
+///     .set(&world.res, "Health", entity, serde_json::json!({ "value": 10 }))
+///     .unwrap();
+/// assert_eq!(world.read_storage::<Health>().get(entity).unwrap().value, 10);
+/// assert_eq!(registry.info("Health").unwrap().fields[0].name, "value");
+/// ```
+///
+/// The clones of the registry share its components.
+#[derive(Clone, Default)]
+pub struct ComponentRegistry {
+    components: BTreeMap<String, Registration>,
+}
+
+impl ComponentRegistry {
+    /// Creates an empty registry.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Registers the components of a type under a name, with their fields.
+    pub fn register<T, S>(&mut self, name: S)
+    where
+        T: Reflect,
+        T::Storage: Default,
+        S: Into<String>,
+    {
+        self.register_with_fields::<T, S>(name, T::fields());
+    }
+
+    /// Registers the components of a type under a name, describing their fields without `Reflect`.
+    pub fn register_with_fields<T, S>(&mut self, name: S, fields: Vec<Field>)
+    where
+        T: Component + Serialize + DeserializeOwned,
+        T::Storage: Default,
+        S: Into<String>,
+    {
+        let name = name.into();
+        let registration = Registration {
+            info: ComponentInfo {
+                name: name.clone(),
+                fields,
+            },
+            access: Arc::new(Access::<T>(PhantomData)),
+        };
+        self.components.insert(name, registration);
+    }
+
+    /// Whether components are registered under a name.
+    pub fn contains(&self, name: &str) -> bool {
+        self.components.contains_key(name)
+    }
+
+    /// The component registered under a name.
+    pub fn info(&self, name: &str) -> Option<&ComponentInfo> {
+        self.components
+            .get(name)
+            .map(|registration| &registration.info)
+    }
+
+    /// The registered components, by their names.
+    pub fn components(&self) -> impl Iterator<Item = &ComponentInfo> {
+        self.components
+            .values()
+            .map(|registration| &registration.info)
+    }
+
+    /// Sets up the storages of the components.
+    pub fn setup(&self, res: &mut Resources) {
+        for registration in self.components.values() {
+            registration.access.setup(res);
+        }
+    }
+
+    /// The component of an entity, if it has one.
+    pub fn get(&self, res: &Resources, name: &str, entity: Entity) -> Result<Option<Value>, Error> {
+        self.access(name)?.get(res, entity)
+    }
+
+    /// Inserts or replaces the component of an entity.
+    pub fn set(
+        &self,
+        res: &Resources,
+        name: &str,
+        entity: Entity,
+        value: Value,
+    ) -> Result<(), Error> {
+        self.access(name)?.set(res, entity, value)
+    }
+
+    /// Removes the component of an entity.
+    pub fn remove(&self, res: &Resources, name: &str, entity: Entity) -> Result<(), Error> {
+        self.access(name)?.remove(res, entity);
+        Ok(())
+    }
+
+    /// The registered components an entity has, by their names, as the scenes save them.
+    pub fn components_of(
+        &self,
+        res: &Resources,
+        entity: Entity,
+    ) -> Result<Map<String, Value>, Error> {
+        let mut components = Map::new();
+        for (name, registration) in &self.components {
+            if let Some(value) = registration.access.get(res, entity)? {
+                components.insert(name.clone(), value);
+            }
+        }
+        Ok(components)
+    }
+
+    /// Inserts or replaces the components of an entity, by their names.
+    pub fn insert_components(
+        &self,
+        res: &Resources,
+        entity: Entity,
+        components: Map<String, Value>,
+    ) -> Result<(), Error> {
+        for (name, value) in components {
+            self.set(res, &name, entity, value)?;
+        }
+        Ok(())
+    }
+
+    fn access(&self, name: &str) -> Result<&dyn ComponentAccess, Error> {
+        self.components
+            .get(name)
+            .map(|registration| &*registration.access)
+            .ok_or_else(|| Error::from_string(format!("No component is registered as {}", name)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use crate::ecs::prelude::{Builder, DenseVecStorage, World};
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Position(f32, f32);
+
+    impl Component for Position {
+        type Storage = DenseVecStorage<Self>;
+    }
+
+    impl Reflect for Position {
+        fn fields() -> Vec<Field> {
+            vec![
+                Field {
+                    name: "0",
+                    type_name: "f32",
+                },
+                Field {
+                    name: "1",
+                    type_name: "f32",
+                },
+            ]
+        }
+    }
+
+    #[test]
+    fn components_are_copied_between_entities() {
+        let mut world = World::new();
+        let mut registry = ComponentRegistry::new();
+        registry.register::<Position, _>("Position");
+        registry.setup(&mut world.res);
+        assert_eq!(registry.info("Position").unwrap().fields.len(), 2);
+
+        let original = world.create_entity().with(Position(1.0, 2.0)).build();
+        let copy = world.create_entity().build();
+        let components = registry.components_of(&world.res, original).unwrap();
+        registry
+            .insert_components(&world.res, copy, components)
+            .unwrap();
+        assert_eq!(
+            world.read_storage::<Position>().get(copy),
+            Some(&Position(1.0, 2.0))
+        );
+
+        registry.remove(&world.res, "Position", copy).unwrap();
+        assert!(registry.components_of(&world.res, copy).unwrap().is_empty());
+        assert!(registry.get(&world.res, "Velocity", copy).is_err());
+    }
+}
@@ -4,7 +4,8 @@
 //! functions.
 
 use crate::ecs::prelude::{Read, System};
-use shred::{RunningTime, SystemData};
+use shred::{Resources, RunningTime, SystemData};
+use std::marker::PhantomData;
 
 /// Extension functionality associated systems.
 pub trait SystemExt {
@@ -75,6 +76,147 @@ pub trait SystemExt {
     where
         Self: Sized,
         V: Send + Sync + Default + PartialEq;
+
+    /// Make a system run only when a condition on the resource `R` holds.
+    ///
+    /// The condition is checked before every run, and the system doesn't run if the resource
+    /// doesn't exist. The same care as for `pausable` systems must be taken with event channels.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use amethyst::{
+    ///     ecs::{System, Write},
+    ///     shred::DispatcherBuilder,
+    ///     prelude::*,
+    /// };
+    ///
+    /// struct Score(u32);
+    ///
+    /// struct AddNumber(u32);
+    ///
+    /// impl<'s> System<'s> for AddNumber {
+    ///     type SystemData = Write<'s, u32>;
+    ///
+    ///     fn run(&mut self, mut number: Self::SystemData) {
+    ///         *number += self.0;
+    ///     }
+    /// }
+    ///
+    /// let mut world = World::new();
+    /// world.add_resource(Score(5));
+    ///
+    /// let mut dispatcher = DispatcherBuilder::default()
+    ///     .with(AddNumber(1).run_if(|score: &Score| score.0 > 10), "bonus", &[])
+    ///     .build();
+    /// dispatcher.setup(&mut world.res);
+    ///
+    /// dispatcher.dispatch(&mut world.res);
+    /// assert_eq!(0, *world.read_resource::<u32>());
+    ///
+    /// world.write_resource::<Score>().0 = 20;
+    /// dispatcher.dispatch(&mut world.res);
+    /// assert_eq!(1, *world.read_resource::<u32>());
+    /// ```
+    fn run_if<R, F>(self, condition: F) -> RunIf<Self, R, F>
+    where
+        Self: Sized,
+        R: Send + Sync + 'static,
+        F: FnMut(&R) -> bool;
+
+    /// Make a system run only when a `RunCondition` on its system data holds, for the conditions
+    /// on several resources or on the components of the world.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use amethyst::{
+    ///     core::RunCondition,
+    ///     ecs::{Component, DenseVecStorage, Join, ReadStorage, System, Write},
+    ///     shred::DispatcherBuilder,
+    ///     prelude::*,
+    /// };
+    ///
+    /// struct Enemy;
+    ///
+    /// impl Component for Enemy {
+    ///     type Storage = DenseVecStorage<Self>;
+    /// }
+    ///
+    /// // Runs while there are enemies left.
+    /// struct EnemiesLeft;
+    ///
+    /// impl<'s> RunCondition<'s> for EnemiesLeft {
+    ///     type SystemData = ReadStorage<'s, Enemy>;
+    ///
+    ///     fn check(&mut self, enemies: Self::SystemData) -> bool {
+    ///         (&enemies).join().next().is_some()
+    ///     }
+    /// }
+    ///
+    /// struct AddNumber(u32);
+    ///
+    /// impl<'s> System<'s> for AddNumber {
+    ///     type SystemData = Write<'s, u32>;
+    ///
+    ///     fn run(&mut self, mut number: Self::SystemData) {
+    ///         *number += self.0;
+    ///     }
+    /// }
+    ///
+    /// let mut world = World::new();
+    /// let mut dispatcher = DispatcherBuilder::default()
+    ///     .with(AddNumber(1).run_when(EnemiesLeft), "fight", &[])
+    ///     .build();
+    /// dispatcher.setup(&mut world.res);
+    ///
+    /// dispatcher.dispatch(&mut world.res);
+    /// assert_eq!(0, *world.read_resource::<u32>());
+    ///
+    /// world.create_entity().with(Enemy).build();
+    /// dispatcher.dispatch(&mut world.res);
+    /// assert_eq!(1, *world.read_resource::<u32>());
+    /// ```
+    fn run_when<C>(self, condition: C) -> RunWhen<Self, C>
+    where
+        Self: Sized;
+
+    /// Make a system run only on every `frames`th dispatch, starting with the first one, to
+    /// throttle expensive systems like pathfinding.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use amethyst::{
+    ///     ecs::{System, Write},
+    ///     shred::DispatcherBuilder,
+    ///     prelude::*,
+    /// };
+    ///
+    /// struct AddNumber(u32);
+    ///
+    /// impl<'s> System<'s> for AddNumber {
+    ///     type SystemData = Write<'s, u32>;
+    ///
+    ///     fn run(&mut self, mut number: Self::SystemData) {
+    ///         *number += self.0;
+    ///     }
+    /// }
+    ///
+    /// let mut world = World::new();
+    /// let mut dispatcher = DispatcherBuilder::default()
+    ///     .with(AddNumber(1).every(3), "add_number", &[])
+    ///     .build();
+    /// dispatcher.setup(&mut world.res);
+    ///
+    /// for _ in 0..7 {
+    ///     dispatcher.dispatch(&mut world.res);
+    /// }
+    /// assert_eq!(3, *world.read_resource::<u32>());
+    /// ```
+    fn every(self, frames: u32) -> Throttled<Self>
+    where
+        Self: Sized;
 }
 
 impl<'s, S> SystemExt for S
@@ -91,6 +233,40 @@ where
             value,
         }
     }
+
+    fn run_if<R, F>(self, condition: F) -> RunIf<Self, R, F>
+    where
+        Self: Sized,
+        R: Send + Sync + 'static,
+        F: FnMut(&R) -> bool,
+    {
+        RunIf {
+            system: self,
+            condition,
+            marker: PhantomData,
+        }
+    }
+
+    fn run_when<C>(self, condition: C) -> RunWhen<Self, C>
+    where
+        Self: Sized,
+    {
+        RunWhen {
+            system: self,
+            condition,
+        }
+    }
+
+    fn every(self, frames: u32) -> Throttled<Self>
+    where
+        Self: Sized,
+    {
+        Throttled {
+            system: self,
+            frames: frames.max(1),
+            countdown: 0,
+        }
+    }
 }
 
 /// A system that is enabled when `V` has a specific value.
@@ -123,3 +299,190 @@ where
         self.system.running_time()
     }
 }
+
+/// A system that runs when a condition on the resource `R` holds.
+///
+/// This is created using the [`SystemExt::run_if`] method.
+///
+/// [`SystemExt::run_if`]: trait.SystemExt.html#tymethod.run_if
+pub struct RunIf<S, R, F> {
+    system: S,
+    condition: F,
+    marker: PhantomData<fn(&R)>,
+}
+
+impl<'s, S, R, F> System<'s> for RunIf<S, R, F>
+where
+    S::SystemData: SystemData<'s>,
+    S: System<'s>,
+    R: Send + Sync + 'static,
+    F: FnMut(&R) -> bool,
+{
+    type SystemData = (Option<Read<'s, R>>, S::SystemData);
+
+    fn run(&mut self, (resource, data): Self::SystemData) {
+        match resource {
+            Some(ref resource) if (self.condition)(resource) => self.system.run(data),
+            _ => (),
+        }
+    }
+
+    fn running_time(&self) -> RunningTime {
+        self.system.running_time()
+    }
+
+    fn setup(&mut self, res: &mut Resources) {
+        self.system.setup(res);
+    }
+}
+
+/// A condition deciding whether a system runs, from system data like a `System`.
+///
+/// It's used with the [`SystemExt::run_when`] method.
+///
+/// [`SystemExt::run_when`]: trait.SystemExt.html#tymethod.run_when
+pub trait RunCondition<'a> {
+    /// The data the condition reads.
+    type SystemData: SystemData<'a>;
+
+    /// Whether the system runs this dispatch.
+    fn check(&mut self, data: Self::SystemData) -> bool;
+}
+
+/// A system that runs when a `RunCondition` holds.
+///
+/// This is created using the [`SystemExt::run_when`] method.
+///
+/// [`SystemExt::run_when`]: trait.SystemExt.html#tymethod.run_when
+pub struct RunWhen<S, C> {
+    system: S,
+    condition: C,
+}
+
+impl<'s, S, C> System<'s> for RunWhen<S, C>
+where
+    S: System<'s>,
+    C: RunCondition<'s>,
+{
+    type SystemData = (C::SystemData, S::SystemData);
+
+    fn run(&mut self, (condition, data): Self::SystemData) {
+        if self.condition.check(condition) {
+            self.system.run(data);
+        }
+    }
+
+    fn running_time(&self) -> RunningTime {
+        self.system.running_time()
+    }
+
+    fn setup(&mut self, res: &mut Resources) {
+        C::SystemData::setup(res);
+        self.system.setup(res);
+    }
+}
+
+/// A system that runs on every `frames`th dispatch.
+///
+/// This is created using the [`SystemExt::every`] method.
+///
+/// [`SystemExt::every`]: trait.SystemExt.html#tymethod.every
+pub struct Throttled<S> {
+    system: S,
+    frames: u32,
+    countdown: u32,
+}
+
+impl<'s, S> System<'s> for Throttled<S>
+where
+    S: System<'s>,
+{
+    type SystemData = S::SystemData;
+
+    fn run(&mut self, data: Self::SystemData) {
+        if self.countdown == 0 {
+            self.countdown = self.frames;
+            self.system.run(data);
+        }
+        self.countdown -= 1;
+    }
+
+    fn running_time(&self) -> RunningTime {
+        self.system.running_time()
+    }
+
+    fn setup(&mut self, res: &mut Resources) {
+        self.system.setup(res);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ecs::prelude::{RunNow, World, Write};
+
+    use super::*;
+
+    struct AddNumber(u32);
+
+    impl<'s> System<'s> for AddNumber {
+        type SystemData = Write<'s, u32>;
+
+        fn run(&mut self, mut number: Self::SystemData) {
+            *number += self.0;
+        }
+    }
+
+    // Runs `system` `times` times, returning the number it added.
+    fn run<S>(mut system: S, world: &mut World, times: usize) -> u32
+    where
+        S: for<'s> System<'s>,
+    {
+        System::setup(&mut system, &mut world.res);
+        *world.write_resource::<u32>() = 0;
+        for _ in 0..times {
+            system.run_now(&world.res);
+        }
+        *world.read_resource::<u32>()
+    }
+
+    #[test]
+    fn run_if_checks_the_resource() {
+        struct Score(u32);
+
+        let mut world = World::new();
+        let bonus = || AddNumber(1).run_if(|score: &Score| score.0 > 10);
+        // Without the resource, the system doesn't run.
+        assert_eq!(run(bonus(), &mut world, 2), 0);
+        world.add_resource(Score(5));
+        assert_eq!(run(bonus(), &mut world, 2), 0);
+        world.write_resource::<Score>().0 = 20;
+        assert_eq!(run(bonus(), &mut world, 2), 2);
+    }
+
+    #[test]
+    fn run_when_checks_the_system_data() {
+        struct Even;
+
+        impl<'s> RunCondition<'s> for Even {
+            type SystemData = Read<'s, u32>;
+
+            fn check(&mut self, number: Self::SystemData) -> bool {
+                *number % 2 == 0
+            }
+        }
+
+        let mut world = World::new();
+        // Runs on 0, then skipped on 3 forever.
+        assert_eq!(run(AddNumber(3).run_when(Even), &mut world, 4), 3);
+    }
+
+    #[test]
+    fn every_runs_on_the_first_dispatch_then_every_n() {
+        let mut world = World::new();
+        assert_eq!(run(AddNumber(1).every(3), &mut world, 7), 3);
+        assert_eq!(run(AddNumber(1).every(3), &mut world, 3), 1);
+        // Every dispatch, with a zero.
+        assert_eq!(run(AddNumber(1).every(0), &mut world, 4), 4);
+        assert_eq!(run(AddNumber(1).every(1), &mut world, 4), 4);
+    }
+}
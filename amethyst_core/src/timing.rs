@@ -25,14 +25,21 @@ pub struct Time {
     absolute_time: Duration,
     ///Time multiplier. Affects returned delta_seconds, delta_time and absolute_time.
     time_scale: f32,
+    /// Stops delta_seconds, delta_time and absolute_time, like a time multiplier of 0.
+    paused: bool,
     /// Fixed timestep accumulator.
     fixed_time_accumulator: f32,
     /// Fixed update interpolation alpha
     interpolation_alpha: f32,
+    /// The most fixed updates run in a single frame.
+    max_fixed_steps: u32,
+    /// The fixed updates run in the current frame.
+    fixed_steps: u32,
 }
 
 impl Time {
-    /// Gets the time difference between frames in seconds.
+    /// Gets the time difference between frames in seconds, taking into account the speed
+    /// multiplier and the pause. Real deltas are meant for UI and audio that keep going.
     pub fn delta_seconds(&self) -> f32 {
         self.delta_seconds
     }
@@ -62,6 +69,11 @@ impl Time {
         self.fixed_time
     }
 
+    /// Gets the most fixed updates run in a single frame.
+    pub fn max_fixed_steps(&self) -> u32 {
+        self.max_fixed_steps
+    }
+
     /// Gets the current frame number.  This increments by 1 every frame.  There is no frame 0.
     pub fn frame_number(&self) -> u64 {
         self.frame_number
@@ -92,6 +104,11 @@ impl Time {
         self.time_scale
     }
 
+    /// Checks whether the game time is paused.
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
     /// Gets the current interpolation alpha factor.
     pub fn interpolation_alpha(&self) -> f32 {
         self.interpolation_alpha
@@ -103,8 +120,8 @@ impl Time {
     /// This should only be called by the engine.  Bad things might happen if you call this in
     /// your game.
     pub fn set_delta_seconds(&mut self, secs: f32) {
-        self.delta_seconds = secs * self.time_scale;
-        self.delta_time = secs_to_duration(secs * self.time_scale);
+        self.delta_seconds = secs * self.effective_scale();
+        self.delta_time = secs_to_duration(secs * self.effective_scale());
         self.delta_real_seconds = secs;
         self.delta_real_time = secs_to_duration(secs);
 
@@ -117,8 +134,8 @@ impl Time {
     /// This should only be called by the engine.  Bad things might happen if you call this in
     /// your game.
     pub fn set_delta_time(&mut self, time: Duration) {
-        self.delta_seconds = duration_to_secs(time) * self.time_scale;
-        self.delta_time = secs_to_duration(duration_to_secs(time) * self.time_scale);
+        self.delta_seconds = duration_to_secs(time) * self.effective_scale();
+        self.delta_time = secs_to_duration(duration_to_secs(time) * self.effective_scale());
         self.delta_real_seconds = duration_to_secs(time);
         self.delta_real_time = time;
 
@@ -138,6 +155,14 @@ impl Time {
         self.fixed_time = time;
     }
 
+    /// Sets the most fixed updates run in a single frame to catch up with the frame time.
+    ///
+    /// When a frame takes longer than this many fixed steps, the fixed updates fall behind real
+    /// time instead of taking ever longer frames to catch up.
+    pub fn set_max_fixed_steps(&mut self, steps: u32) {
+        self.max_fixed_steps = steps.max(1);
+    }
+
     /// Increments the current frame number by 1.
     ///
     /// This should only be called by the engine.  Bad things might happen if you call this in
@@ -158,12 +183,27 @@ impl Time {
         self.time_scale = multiplier;
     }
 
+    /// Pauses or resumes the game time. While paused, `delta_seconds` is 0 and no fixed updates
+    /// run, while the real time keeps going.
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    fn effective_scale(&self) -> f32 {
+        if self.paused {
+            0.0
+        } else {
+            self.time_scale
+        }
+    }
+
     /// Restarts the internal fixed update accumulator to the desired fixed update delta time.
     ///
     /// This should only be called by the engine.  Bad things might happen if you call this in
     /// your game.
     pub fn start_fixed_update(&mut self) {
         self.fixed_time_accumulator += self.delta_seconds;
+        self.fixed_steps = 0;
     }
 
     /// Checks to see if we should perform another fixed update iteration, and if so, returns true
@@ -172,13 +212,17 @@ impl Time {
     /// This should only be called by the engine.  Bad things might happen if you call this in
     /// your game.
     pub fn step_fixed_update(&mut self) -> bool {
-        match self.fixed_time_accumulator >= self.fixed_seconds {
-            true => {
-                self.fixed_time_accumulator -= self.fixed_seconds;
-                true
-            }
-            false => false,
+        if self.fixed_time_accumulator < self.fixed_seconds {
+            return false;
+        }
+        if self.fixed_steps >= self.max_fixed_steps {
+            // Drops the time that couldn't be caught up with.
+            self.fixed_time_accumulator %= self.fixed_seconds;
+            return false;
         }
+        self.fixed_time_accumulator -= self.fixed_seconds;
+        self.fixed_steps += 1;
+        true
     }
 
     /// Updates the interpolation alpha factor given the current fixed update rate and accumulator.
@@ -205,6 +249,9 @@ impl Default for Time {
             absolute_real_time: Duration::default(),
             absolute_time: Duration::default(),
             time_scale: 1.0,
+            paused: false,
+            max_fixed_steps: 5,
+            fixed_steps: 0,
         }
     }
 }
@@ -426,6 +473,45 @@ mod tests {
         }
         assert_eq!(fixed_count, 2);
     }
+
+    // Test that pausing stops the game time but not the real time
+    #[test]
+    fn paused_time() {
+        use super::Time;
+
+        let mut time = Time::default();
+        time.set_time_scale(0.5);
+        time.set_delta_seconds(0.2);
+        assert!((time.delta_seconds() - 0.1).abs() < 1e-6);
+
+        time.set_paused(true);
+        time.set_delta_seconds(0.2);
+        assert!(time.delta_seconds().abs() < 1e-6);
+        assert!((time.delta_real_seconds() - 0.2).abs() < 1e-6);
+        assert!((time.absolute_time_seconds() - 0.1).abs() < 1e-6);
+        time.start_fixed_update();
+        assert!(!time.step_fixed_update());
+    }
+
+    // Test that a long frame runs at most `max_fixed_steps` fixed updates, and drops the rest
+    #[test]
+    fn fixed_update_catch_up_is_limited() {
+        use super::Time;
+
+        let mut time = Time::default();
+        time.set_fixed_seconds(0.1);
+        time.set_max_fixed_steps(3);
+
+        time.set_delta_seconds(1.05);
+        time.start_fixed_update();
+        let mut fixed_count = 0;
+        while time.step_fixed_update() {
+            fixed_count += 1;
+        }
+        time.finish_fixed_update();
+        assert_eq!(fixed_count, 3);
+        assert!(time.interpolation_alpha() < 1.0);
+    }
 }
 
 /// Converts a Duration to the time in seconds.
@@ -1,15 +1,21 @@
 //! ECS transform bundle
 
-use crate::ecs::prelude::DispatcherBuilder;
 use amethyst_error::Error;
 use specs_hierarchy::HierarchySystem;
 
-use crate::{bundle::SystemBundle, transform::*};
+use crate::{
+    bundle::{BundleBuilder, SystemBundle},
+    named::NameIndexSystem,
+    transform::*,
+};
 
 /// Transform bundle
 ///
-/// Will register transform components, and the `TransformSystem`.
-/// `TransformSystem` will be registered with name "transform_system".
+/// Will register transform components, the `TransformSystem`, the `TransformInterpolationSystem`,
+/// the `NameIndexSystem` and the `DespawnSystem`. `TransformSystem` will be registered with name
+/// "transform_system", `TransformInterpolationSystem` with name "transform_interpolation_system",
+/// `NameIndexSystem` with name "name_index_system" and `DespawnSystem` with name
+/// "despawn_system".
 ///
 /// ## Errors
 ///
@@ -38,7 +44,7 @@ impl<'a> TransformBundle<'a> {
 }
 
 impl<'a, 'b, 'c> SystemBundle<'a, 'b> for TransformBundle<'c> {
-    fn build(self, builder: &mut DispatcherBuilder<'a, 'b>) -> Result<(), Error> {
+    fn build(self, builder: &mut BundleBuilder<'a, 'b>) -> Result<(), Error> {
         builder.add(
             HierarchySystem::<Parent>::new(),
             "parent_hierarchy_system",
@@ -49,6 +55,17 @@ impl<'a, 'b, 'c> SystemBundle<'a, 'b> for TransformBundle<'c> {
             "transform_system",
             &["parent_hierarchy_system"],
         );
+        builder.add(
+            TransformInterpolationSystem,
+            "transform_interpolation_system",
+            &["transform_system"],
+        );
+        builder.add(NameIndexSystem::new(), "name_index_system", self.dep);
+        builder.add(DespawnSystem, "despawn_system", self.dep);
         Ok(())
     }
+
+    fn name(&self) -> &'static str {
+        "TransformBundle"
+    }
 }
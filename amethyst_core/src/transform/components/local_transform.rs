@@ -4,7 +4,8 @@ use std::fmt;
 use crate::{
     ecs::prelude::{Component, DenseVecStorage, FlaggedStorage},
     math::{
-        self as na, Isometry3, Matrix4, Quaternion, Translation3, Unit, UnitQuaternion, Vector3,
+        self as na, Isometry3, Matrix3, Matrix4, Quaternion, Rotation3, Translation3, Unit,
+        UnitQuaternion, Vector3,
     },
 };
 use serde::{
@@ -528,6 +529,35 @@ impl Transform {
             .to_homogeneous()
             .append_nonuniform_scaling(&inv_scale)
     }
+
+    /// Decomposes a matrix into a translation, rotation and scale.
+    ///
+    /// A matrix combining rotations and non-uniform scales, like the world matrix of an entity
+    /// with scaled parents, may contain shear, which a `Transform` can't represent and is lost.
+    /// A mirroring is kept as a negative x scale.
+    pub fn from_matrix(matrix: &Matrix4<f32>) -> Self {
+        let column =
+            |index| Vector3::new(matrix[(0, index)], matrix[(1, index)], matrix[(2, index)]);
+        let axes = [column(0), column(1), column(2)];
+        let mut scale = Vector3::new(axes[0].norm(), axes[1].norm(), axes[2].norm());
+        if axes[0].cross(&axes[1]).dot(&axes[2]) < 0.0 {
+            scale.x = -scale.x;
+        }
+        let rotation = if scale.iter().all(|s| s.abs() > std::f32::EPSILON) {
+            let rotation =
+                Matrix3::from_columns(&[axes[0] / scale.x, axes[1] / scale.y, axes[2] / scale.z]);
+            UnitQuaternion::from_rotation_matrix(&Rotation3::from_matrix_unchecked(rotation))
+        } else {
+            UnitQuaternion::identity()
+        };
+        Transform {
+            iso: Isometry3::from_parts(
+                Translation3::new(matrix[(0, 3)], matrix[(1, 3)], matrix[(2, 3)]),
+                rotation,
+            ),
+            scale,
+        }
+    }
 }
 
 impl Default for Transform {
@@ -743,4 +773,15 @@ mod tests {
             transform.view_matrix(),
         );
     }
+
+    #[test]
+    fn test_from_matrix() {
+        let mut transform = Transform::default();
+        transform.set_translation_xyz(5.0, -2.0, 3.5);
+        transform.set_scale(-1.0, 2.0, 0.5);
+        transform.set_rotation_euler(0.3, -0.8, 1.2);
+
+        let decomposed = Transform::from_matrix(&transform.matrix());
+        assert_relative_eq!(decomposed.matrix(), transform.matrix(), epsilon = 1e-5);
+    }
 }
@@ -0,0 +1,303 @@
+//! Utilities to change the transform hierarchy.
+
+use std::{collections::HashMap, ops::Deref};
+
+use amethyst_error::{format_err, Error};
+use shred_derive::SystemData;
+
+use crate::{
+    ecs::{
+        prelude::{
+            Component, Entities, Entity, Join, NullStorage, ReadExpect, ReadStorage, System,
+            WriteStorage,
+        },
+        storage::{MaskedStorage, Storage},
+        world::EntitiesRes,
+    },
+    math::{Matrix4, Vector3},
+    transform::{Parent, ParentHierarchy, Transform},
+};
+
+/// Utility `SystemData` to attach entities to new parents and to compute their world transforms.
+///
+/// The world transforms are computed from the `Transform`s of the entity and its ancestors, so
+/// they are up to date even before the `TransformSystem` updates the `GlobalTransform`s. The
+/// children and descendants, however, come from the `ParentHierarchy`, which is only updated when
+/// the `parent_hierarchy_system` runs.
+///
+/// ```rust,ignore
+/// fn pick_up(mut hierarchy: TransformHierarchy<'_>, hand: Entity, item: Entity) {
+///     // The item stays where it is, and follows the hand from now on.
+///     hierarchy.attach_to(item, hand, true).expect("The item is already holding the hand");
+/// }
+/// ```
+#[derive(SystemData)]
+pub struct TransformHierarchy<'a> {
+    entities: Entities<'a>,
+    hierarchy: ReadExpect<'a, ParentHierarchy>,
+    parents: WriteStorage<'a, Parent>,
+    locals: WriteStorage<'a, Transform>,
+}
+
+impl<'a> TransformHierarchy<'a> {
+    /// The parent of an entity.
+    pub fn parent(&self, entity: Entity) -> Option<Entity> {
+        self.parents.get(entity).map(|parent| parent.entity)
+    }
+
+    /// Checks whether `ancestor` is the parent of `entity`, or an ancestor of its parent.
+    pub fn is_ancestor(&self, ancestor: Entity, entity: Entity) -> bool {
+        let mut current = entity;
+        while let Some(parent) = self.parent(current) {
+            if parent == ancestor {
+                return true;
+            }
+            current = parent;
+        }
+        false
+    }
+
+    /// The direct children of an entity.
+    pub fn children(&self, entity: Entity) -> &[Entity] {
+        self.hierarchy.children(entity)
+    }
+
+    /// All the descendants of an entity, parents before their children.
+    pub fn descendants(&self, entity: Entity) -> impl Iterator<Item = Entity> + '_ {
+        self.hierarchy.all_children_iter(entity)
+    }
+
+    /// The matrix transforming from the local space of the entity to the world. Entities without
+    /// a `Transform` don't transform their children.
+    pub fn world_matrix(&self, entity: Entity) -> Matrix4<f32> {
+        let mut matrix = self.local_matrix(entity);
+        let mut current = entity;
+        while let Some(parent) = self.parent(current) {
+            matrix = self.local_matrix(parent) * matrix;
+            current = parent;
+        }
+        matrix
+    }
+
+    /// The transform of the entity relative to the world, see `Transform::from_matrix`.
+    pub fn world_transform(&self, entity: Entity) -> Transform {
+        Transform::from_matrix(&self.world_matrix(entity))
+    }
+
+    /// The position of the entity in the world.
+    pub fn world_position(&self, entity: Entity) -> Vector3<f32> {
+        let matrix = self.world_matrix(entity);
+        Vector3::new(matrix[(0, 3)], matrix[(1, 3)], matrix[(2, 3)])
+    }
+
+    /// Makes `parent` the parent of `child`.
+    ///
+    /// If `keep_world_transform` is true, the `Transform` of the child is changed so that it
+    /// stays where it is in the world, otherwise it is kept and now relative to the new parent.
+    ///
+    /// Fails if either entity is dead, or if the child is the parent or one of its ancestors.
+    pub fn attach_to(
+        &mut self,
+        child: Entity,
+        parent: Entity,
+        keep_world_transform: bool,
+    ) -> Result<(), Error> {
+        if !self.entities.is_alive(child) || !self.entities.is_alive(parent) {
+            return Err(format_err!("Can't attach dead entities"));
+        }
+        if child == parent || self.is_ancestor(child, parent) {
+            return Err(format_err!(
+                "Attaching {:?} to {:?} would create a cycle in the hierarchy",
+                child,
+                parent
+            ));
+        }
+        if keep_world_transform {
+            let inverse = self.world_matrix(parent).try_inverse().ok_or_else(|| {
+                format_err!("The world transform of {:?} isn't invertible", parent)
+            })?;
+            let local = Transform::from_matrix(&(inverse * self.world_matrix(child)));
+            self.set_local(child, local)?;
+        }
+        self.parents
+            .insert(child, Parent::new(parent))
+            .map_err(|err| format_err!("Failed to attach {:?}: {}", child, err))?;
+        Ok(())
+    }
+
+    /// Removes the parent of `child`, making it a root of the hierarchy.
+    ///
+    /// If `keep_world_transform` is true, the `Transform` of the child is changed so that it
+    /// stays where it is in the world.
+    pub fn detach(&mut self, child: Entity, keep_world_transform: bool) -> Result<(), Error> {
+        if !self.entities.is_alive(child) {
+            return Err(format_err!("Can't detach a dead entity"));
+        }
+        if keep_world_transform && self.parent(child).is_some() {
+            let world = self.world_transform(child);
+            self.set_local(child, world)?;
+        }
+        self.parents.remove(child);
+        Ok(())
+    }
+
+    /// Deletes an entity with all its descendants, like the UI elements or the audio emitters
+    /// attached to it.
+    ///
+    /// The descendants are found from the `Parent`s, so the ones attached since the last
+    /// `parent_hierarchy_system` run are deleted as well. Like with `Entities::delete`, the
+    /// entities are only removed from the storages once the world is maintained.
+    pub fn despawn_recursive(&self, entity: Entity) -> Result<(), Error> {
+        if !self.entities.is_alive(entity) {
+            return Err(format_err!("Can't despawn the dead entity {:?}", entity));
+        }
+        delete_subtrees(&self.entities, &self.parents, vec![entity]);
+        Ok(())
+    }
+
+    fn local_matrix(&self, entity: Entity) -> Matrix4<f32> {
+        self.locals
+            .get(entity)
+            .map(Transform::matrix)
+            .unwrap_or_else(Matrix4::identity)
+    }
+
+    fn set_local(&mut self, entity: Entity, transform: Transform) -> Result<(), Error> {
+        self.locals
+            .insert(entity, transform)
+            .map(|_| ())
+            .map_err(|err| format_err!("Failed to move {:?}: {}", entity, err))
+    }
+}
+
+/// Marks an entity to be deleted with all its descendants by the `DespawnSystem`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Despawn;
+
+impl Component for Despawn {
+    type Storage = NullStorage<Self>;
+}
+
+/// Deletes the entities marked with `Despawn`, with all their descendants.
+///
+/// Added by the `TransformBundle` with the name "despawn_system".
+#[derive(Debug, Default)]
+pub struct DespawnSystem;
+
+impl<'a> System<'a> for DespawnSystem {
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, Despawn>,
+        ReadStorage<'a, Parent>,
+    );
+
+    fn run(&mut self, (entities, despawns, parents): Self::SystemData) {
+        let roots = (&*entities, &despawns)
+            .join()
+            .map(|(entity, _)| entity)
+            .collect::<Vec<_>>();
+        if !roots.is_empty() {
+            delete_subtrees(&entities, &parents, roots);
+        }
+    }
+}
+
+fn delete_subtrees<D>(entities: &EntitiesRes, parents: &Storage<'_, Parent, D>, roots: Vec<Entity>)
+where
+    D: Deref<Target = MaskedStorage<Parent>>,
+{
+    let mut children = HashMap::<Entity, Vec<Entity>>::new();
+    for (entity, parent) in (entities, parents).join() {
+        children.entry(parent.entity).or_default().push(entity);
+    }
+    let mut subtrees = roots;
+    let mut index = 0;
+    while index < subtrees.len() {
+        if let Some(children) = children.remove(&subtrees[index]) {
+            subtrees.extend(children);
+        }
+        index += 1;
+    }
+    for entity in subtrees {
+        // A root may be the descendant of another root, and deleted already.
+        let _ = entities.delete(entity);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        approx::assert_relative_eq,
+        ecs::prelude::{Builder, RunNow, System, World},
+    };
+    use specs_hierarchy::HierarchySystem;
+
+    #[test]
+    fn reparent_keeping_world_transform() {
+        let mut world = World::new();
+        world.register::<Transform>();
+        let mut hierarchy_system = HierarchySystem::<Parent>::new();
+        System::setup(&mut hierarchy_system, &mut world.res);
+
+        let mut transform = Transform::default();
+        transform.set_translation_xyz(1.0, 0.0, 0.0);
+        transform.set_scale(2.0, 2.0, 2.0);
+        let parent = world.create_entity().with(transform).build();
+        let child = world
+            .create_entity()
+            .with(Transform::from(Vector3::new(3.0, 0.0, 0.0)))
+            .build();
+
+        {
+            let mut hierarchy = world.system_data::<TransformHierarchy<'_>>();
+            hierarchy.attach_to(child, parent, true).unwrap();
+            assert!(hierarchy.attach_to(parent, child, false).is_err());
+            assert_relative_eq!(hierarchy.world_position(child), Vector3::new(3.0, 0.0, 0.0));
+        }
+        hierarchy_system.run_now(&world.res);
+        {
+            let hierarchy = world.system_data::<TransformHierarchy<'_>>();
+            assert_eq!(
+                hierarchy.descendants(parent).collect::<Vec<_>>(),
+                vec![child]
+            );
+        }
+
+        let mut hierarchy = world.system_data::<TransformHierarchy<'_>>();
+        hierarchy.detach(child, true).unwrap();
+        assert_eq!(hierarchy.parent(child), None);
+        assert_relative_eq!(
+            *hierarchy.locals.get(child).unwrap().translation(),
+            Vector3::new(3.0, 0.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn despawn_with_descendants() {
+        let mut world = World::new();
+        world.register::<Transform>();
+        world.register::<Despawn>();
+        System::setup(&mut HierarchySystem::<Parent>::new(), &mut world.res);
+        let root = world.create_entity().build();
+        let child = world.create_entity().with(Parent::new(root)).build();
+        let grandchild = world.create_entity().with(Parent::new(child)).build();
+        let other = world.create_entity().with(Despawn).build();
+        let kept = world.create_entity().build();
+
+        DespawnSystem.run_now(&world.res);
+        world.maintain();
+        assert!(!world.is_alive(other));
+        assert!(world.is_alive(root));
+
+        world
+            .system_data::<TransformHierarchy<'_>>()
+            .despawn_recursive(root)
+            .unwrap();
+        world.maintain();
+        assert!(!world.is_alive(root));
+        assert!(!world.is_alive(child));
+        assert!(!world.is_alive(grandchild));
+        assert!(world.is_alive(kept));
+    }
+}
@@ -0,0 +1,152 @@
+//! Interpolation of the transforms moved in fixed updates.
+
+use crate::{
+    ecs::prelude::{
+        Component, DenseVecStorage, Entities, Join, Read, ReadStorage, System, WriteStorage,
+    },
+    math::{Translation3, Vector3},
+    timing::Time,
+    transform::{GlobalTransform, Parent, Transform},
+};
+
+/// Marks an entity moved in fixed updates, whose `GlobalTransform` should be interpolated
+/// between its last two fixed steps to render it smoothly.
+///
+/// The `TransformSnapshotSystem` must run in the fixed update, before the systems moving the
+/// entity, and the `TransformInterpolationSystem` added by the `TransformBundle` then writes the
+/// interpolated `GlobalTransform`. Rendering lags behind the simulation by up to a fixed step.
+///
+/// ```rust,ignore
+/// let game_data = GameDataBuilder::default()
+///     .with_bundle(TransformBundle::new())?
+///     .with_fixed(TransformSnapshotSystem, "transform_snapshot", &[])
+///     .with_fixed(MovementSystem, "movement", &["transform_snapshot"]);
+///
+/// world.create_entity().with(Transform::default()).with(Interpolated::default()).build();
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Interpolated {
+    previous: Option<Transform>,
+}
+
+impl Interpolated {
+    /// The `Transform` before the last fixed step, `None` until a fixed step ran.
+    pub fn previous(&self) -> Option<&Transform> {
+        self.previous.as_ref()
+    }
+}
+
+impl Component for Interpolated {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// Stores the `Transform` of the `Interpolated` entities before a fixed step moves them.
+#[derive(Debug, Default)]
+pub struct TransformSnapshotSystem;
+
+impl<'a> System<'a> for TransformSnapshotSystem {
+    type SystemData = (ReadStorage<'a, Transform>, WriteStorage<'a, Interpolated>);
+
+    fn run(&mut self, (locals, mut interpolated): Self::SystemData) {
+        for (local, interpolated) in (&locals, &mut interpolated).join() {
+            interpolated.previous = Some(local.clone());
+        }
+    }
+}
+
+/// Writes the `GlobalTransform` of the `Interpolated` entities, between the `Transform` before
+/// the last fixed step and the current one, by the interpolation alpha of the `Time`.
+///
+/// The children of an interpolated entity aren't moved with it, unless they are `Interpolated`
+/// as well.
+#[derive(Debug, Default)]
+pub struct TransformInterpolationSystem;
+
+impl<'a> System<'a> for TransformInterpolationSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, Time>,
+        ReadStorage<'a, Transform>,
+        ReadStorage<'a, Interpolated>,
+        ReadStorage<'a, Parent>,
+        WriteStorage<'a, GlobalTransform>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, time, locals, interpolated, parents, mut globals): Self::SystemData,
+    ) {
+        let alpha = time.interpolation_alpha().max(0.0).min(1.0);
+        for (entity, local, interpolated) in (&*entities, &locals, &interpolated).join() {
+            let previous = match interpolated.previous {
+                Some(ref previous) => previous,
+                None => continue,
+            };
+            let mut matrix = interpolate(previous, local, alpha).matrix();
+            if let Some(parent_global) = parents
+                .get(entity)
+                .and_then(|parent| globals.get(parent.entity))
+            {
+                matrix = parent_global.0 * matrix;
+            }
+            if let Some(global) = globals.get_mut(entity) {
+                global.0 = matrix;
+            }
+        }
+    }
+}
+
+/// The transform `alpha` of the way from `from` to `to`.
+pub fn interpolate(from: &Transform, to: &Transform, alpha: f32) -> Transform {
+    let translation: Vector3<f32> = from.translation().lerp(to.translation(), alpha);
+    let rotation = from
+        .rotation()
+        .try_slerp(to.rotation(), alpha, 1.0e-6)
+        .unwrap_or_else(|| *to.rotation());
+    Transform::new(
+        Translation3::new(translation.x, translation.y, translation.z),
+        rotation,
+        from.scale().lerp(to.scale(), alpha),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        approx::assert_relative_eq,
+        ecs::prelude::{Builder, RunNow, World},
+    };
+
+    #[test]
+    fn global_transform_between_fixed_steps() {
+        let mut world = World::new();
+        world.register::<Transform>();
+        world.register::<Interpolated>();
+        world.register::<Parent>();
+        world.register::<GlobalTransform>();
+        let mut time = Time::default();
+        time.set_fixed_seconds(0.1);
+        time.set_delta_seconds(0.05);
+        time.start_fixed_update();
+        time.finish_fixed_update();
+        world.add_resource(time);
+
+        let entity = world
+            .create_entity()
+            .with(Transform::default())
+            .with(Interpolated::default())
+            .with(GlobalTransform::default())
+            .build();
+        TransformSnapshotSystem.run_now(&world.res);
+        world
+            .write_storage::<Transform>()
+            .get_mut(entity)
+            .unwrap()
+            .set_translation_xyz(2.0, 0.0, 0.0);
+        TransformInterpolationSystem.run_now(&world.res);
+
+        let globals = world.read_storage::<GlobalTransform>();
+        assert_relative_eq!(globals.get(entity).unwrap().0[(0, 3)], 1.0);
+    }
+}
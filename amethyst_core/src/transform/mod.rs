@@ -1,7 +1,15 @@
 //! `amethyst` transform ecs module
 
-pub use self::{bundle::TransformBundle, components::*, systems::*};
+pub use self::{
+    bundle::TransformBundle,
+    components::*,
+    hierarchy::{Despawn, DespawnSystem, TransformHierarchy},
+    interpolation::{Interpolated, TransformInterpolationSystem, TransformSnapshotSystem},
+    systems::*,
+};
 
 pub mod bundle;
 pub mod components;
+pub mod hierarchy;
+pub mod interpolation;
 pub mod systems;
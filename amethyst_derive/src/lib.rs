@@ -8,6 +8,7 @@ use syn::{parse_macro_input, DeriveInput};
 
 mod event_reader;
 mod prefab_data;
+mod reflect;
 mod widget_id;
 
 #[proc_macro_derive(EventReader, attributes(reader))]
@@ -27,6 +28,16 @@ pub fn prefab_data_derive(input: TokenStream) -> TokenStream {
     gen.into()
 }
 
+/// Describes the fields of a struct for the `ComponentRegistry`, by their names and the types
+/// they are written with. Deriving `Reflect` requires that
+/// `amethyst::core::reflect::{Field, Reflect}` are imported and visible in the current scope.
+#[proc_macro_derive(Reflect)]
+pub fn reflect_derive(input: TokenStream) -> TokenStream {
+    let ast = parse_macro_input!(input as DeriveInput);
+    let gen = reflect::impl_reflect(&ast);
+    gen.into()
+}
+
 /// This allows the use of an enum as an ID for the `Widgets` resource. One
 /// variant has to be marked as the default variant with `#[widget_id_default]
 /// and will be used when a `Widget` is added to the resource without an
@@ -0,0 +1,52 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Data, DeriveInput, Fields};
+
+pub fn impl_reflect(ast: &DeriveInput) -> TokenStream {
+    let fields = match &ast.data {
+        Data::Struct(ref data_struct) => match data_struct.fields {
+            Fields::Named(ref fields) => fields
+                .named
+                .iter()
+                .map(|field| {
+                    let name = field.ident.as_ref().unwrap().to_string();
+                    let ty = &field.ty;
+                    quote! {
+                        Field {
+                            name: #name,
+                            type_name: stringify!(#ty),
+                        }
+                    }
+                })
+                .collect::<Vec<_>>(),
+            Fields::Unnamed(ref fields) => fields
+                .unnamed
+                .iter()
+                .enumerate()
+                .map(|(i, field)| {
+                    let name = i.to_string();
+                    let ty = &field.ty;
+                    quote! {
+                        Field {
+                            name: #name,
+                            type_name: stringify!(#ty),
+                        }
+                    }
+                })
+                .collect(),
+            Fields::Unit => Vec::new(),
+        },
+        _ => panic!("Reflect derive only supports structs"),
+    };
+
+    let name = &ast.ident;
+    let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
+
+    quote! {
+        impl #impl_generics Reflect for #name #ty_generics #where_clause {
+            fn fields() -> Vec<Field> {
+                vec![#(#fields),*]
+            }
+        }
+    }
+}
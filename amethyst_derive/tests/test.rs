@@ -1,8 +1,10 @@
-use amethyst_derive::{EventReader, PrefabData};
+use amethyst_derive::{EventReader, PrefabData, Reflect};
+use serde::{Deserialize, Serialize};
 
 use amethyst_assets::{PrefabData, ProgressCounter};
 use amethyst_core::{
     ecs::{Component, DenseVecStorage, Entity, Read, Resources, SystemData, WriteStorage},
+    reflect::{Field, Reflect},
     shrev::{EventChannel, ReaderId},
     EventReader,
 };
@@ -75,3 +77,27 @@ pub struct Outer {
     #[prefab(Component)]
     external: External,
 }
+
+#[derive(Reflect, Serialize, Deserialize)]
+pub struct Reflected {
+    value: u32,
+    tags: Vec<String>,
+}
+
+impl Component for Reflected {
+    type Storage = DenseVecStorage<Self>;
+}
+
+#[test]
+fn reflected_fields() {
+    let fields = Reflected::fields();
+    assert_eq!(
+        fields[0],
+        Field {
+            name: "value",
+            type_name: "u32",
+        }
+    );
+    assert_eq!(fields[1].name, "tags");
+    assert_eq!(fields.len(), 2);
+}
@@ -0,0 +1,258 @@
+use amethyst_core::math::{Isometry3, Matrix4, Point3, Vector3};
+use amethyst_error::{format_err, Error};
+use amethyst_physics::{Collider, ColliderShape};
+use log::trace;
+use serde::Deserialize;
+
+use super::Buffers;
+use crate::error;
+
+/// Node name suffixes that tag a mesh for collision, checked in order.
+const SUFFIXES: &[(&str, ColliderKind, bool)] = &[
+    ("-convcolonly", ColliderKind::Convex, true),
+    ("-colonly", ColliderKind::Trimesh, true),
+    ("-convcol", ColliderKind::Convex, false),
+    ("-col", ColliderKind::Trimesh, false),
+];
+
+/// The kind of collider generated for a tagged node.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColliderKind {
+    Trimesh,
+    Convex,
+    Box,
+    Sphere,
+}
+
+/// How a node is tagged for collision.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ColliderTag {
+    pub kind: ColliderKind,
+    /// The node only provides the collider, its mesh is not rendered
+    pub only: bool,
+}
+
+#[derive(Deserialize)]
+struct ColliderExtras {
+    collider: Option<ColliderKind>,
+    #[serde(default)]
+    collider_only: bool,
+}
+
+/// Reads the collision tag of a node, from its extras first and its name second.
+pub fn collider_tag(node: &gltf::Node<'_>) -> Option<ColliderTag> {
+    let extras = node
+        .extras()
+        .as_ref()
+        .and_then(|extras| serde_json::from_str::<ColliderExtras>(extras.get()).ok());
+    if let Some(ColliderExtras {
+        collider: Some(kind),
+        collider_only,
+    }) = extras
+    {
+        return Some(ColliderTag {
+            kind,
+            only: collider_only,
+        });
+    }
+    let name = node.name()?;
+    SUFFIXES
+        .iter()
+        .find(|(suffix, _, _)| name.ends_with(suffix))
+        .map(|&(_, kind, only)| ColliderTag { kind, only })
+}
+
+/// Generates a collider from all triangle primitives of the mesh, with `transform` baked into
+/// the vertices. Fails if the mesh has no triangles, or if they refer to missing vertices.
+pub fn load_collider(
+    mesh: &gltf::Mesh<'_>,
+    buffers: &Buffers,
+    kind: ColliderKind,
+    transform: &Matrix4<f32>,
+) -> Result<Collider, Error> {
+    trace!("Loading collider");
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+
+    for primitive in mesh.primitives() {
+        if primitive.mode() != gltf::mesh::Mode::Triangles {
+            continue;
+        }
+        let reader = primitive.reader(|buffer| buffers.buffer(&buffer));
+        let start = vertices.len();
+        let positions = reader
+            .read_positions()
+            .ok_or(error::Error::MissingPositions)?;
+        vertices
+            .extend(positions.map(|[x, y, z]| transform.transform_point(&Point3::new(x, y, z))));
+        let count = vertices.len() - start;
+        let primitive_indices = match reader.read_indices() {
+            Some(read) => read.into_u32().map(|i| i as usize).collect(),
+            None => (0..count).collect::<Vec<_>>(),
+        };
+        if let Some(index) = primitive_indices.iter().find(|&&index| index >= count) {
+            return Err(format_err!(
+                "The collider of the mesh {} refers to the vertex {} of {}",
+                mesh.index(),
+                index,
+                count
+            ));
+        }
+        indices.extend(
+            primitive_indices
+                .chunks(3)
+                .filter(|face| face.len() == 3)
+                .map(|face| [start + face[0], start + face[1], start + face[2]]),
+        );
+    }
+    if indices.is_empty() {
+        return Err(format_err!(
+            "The collider of the mesh {} has no triangles",
+            mesh.index()
+        ));
+    }
+
+    let collider = match kind {
+        ColliderKind::Trimesh => Collider::new(ColliderShape::TriMesh { vertices, indices }),
+        ColliderKind::Convex => Collider::new(ColliderShape::ConvexHull { points: vertices }),
+        ColliderKind::Box | ColliderKind::Sphere => {
+            let (center, half_extents) = bounds(&vertices);
+            let shape = if kind == ColliderKind::Box {
+                ColliderShape::Cuboid { half_extents }
+            } else {
+                ColliderShape::Ball {
+                    radius: half_extents.x.max(half_extents.y).max(half_extents.z),
+                }
+            };
+            Collider::new(shape).with_offset(Isometry3::translation(center.x, center.y, center.z))
+        }
+    };
+    Ok(collider)
+}
+
+/// The center and half extents of the bounding box of the points.
+fn bounds(points: &[Point3<f32>]) -> (Point3<f32>, Vector3<f32>) {
+    if points.is_empty() {
+        return (Point3::origin(), Vector3::zeros());
+    }
+    let mut min = points[0];
+    let mut max = points[0];
+    for point in points {
+        for i in 0..3 {
+            min[i] = min[i].min(point[i]);
+            max[i] = max[i].max(point[i]);
+        }
+    }
+    (
+        Point3::from((min.coords + max.coords) / 2.),
+        (max - min) / 2.,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use gltf::Gltf;
+
+    use super::*;
+
+    // A triangle, indexed by the first mesh, the second referring to a missing vertex and the
+    // third only drawing points.
+    const GLTF: &str = r#"{
+        "asset": { "version": "2.0" },
+        "buffers": [{ "byteLength": 48 }],
+        "bufferViews": [
+            { "buffer": 0, "byteLength": 36 },
+            { "buffer": 0, "byteOffset": 36, "byteLength": 12 }
+        ],
+        "accessors": [
+            { "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+              "min": [0, 0, 0], "max": [1, 1, 0] },
+            { "bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR" },
+            { "bufferView": 1, "byteOffset": 6, "componentType": 5123, "count": 3,
+              "type": "SCALAR" }
+        ],
+        "meshes": [
+            { "primitives": [{ "attributes": { "POSITION": 0 }, "indices": 1 }] },
+            { "primitives": [{ "attributes": { "POSITION": 0 }, "indices": 2 }] },
+            { "primitives": [{ "attributes": { "POSITION": 0 }, "mode": 0 }] }
+        ],
+        "nodes": [
+            { "name": "floor-colonly", "mesh": 0 },
+            { "name": "crate", "mesh": 0,
+              "extras": { "collider": "box", "collider_only": false } },
+            { "name": "plain", "mesh": 0 }
+        ]
+    }"#;
+
+    fn buffers() -> Buffers {
+        let mut data = Vec::new();
+        for value in &[0., 0., 0., 1., 0., 0., 0., 1., 0.] {
+            data.extend_from_slice(&f32::to_bits(*value).to_le_bytes());
+        }
+        for index in &[0u16, 1, 2, 0, 1, 5] {
+            data.extend_from_slice(&index.to_le_bytes());
+        }
+        Buffers(vec![data])
+    }
+
+    #[test]
+    fn nodes_are_tagged_by_their_extras_or_names() {
+        let gltf = Gltf::from_slice(GLTF.as_bytes()).unwrap();
+        let tags = gltf
+            .nodes()
+            .map(|node| collider_tag(&node))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            tags,
+            vec![
+                Some(ColliderTag {
+                    kind: ColliderKind::Trimesh,
+                    only: true,
+                }),
+                Some(ColliderTag {
+                    kind: ColliderKind::Box,
+                    only: false,
+                }),
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn colliders_are_transformed_and_checked() {
+        let gltf = Gltf::from_slice(GLTF.as_bytes()).unwrap();
+        let buffers = buffers();
+        let meshes = gltf.meshes().collect::<Vec<_>>();
+        let transform =
+            Matrix4::new_translation(&Vector3::new(0., 0., 1.)) * Matrix4::new_scaling(2.);
+        let collider =
+            load_collider(&meshes[0], &buffers, ColliderKind::Trimesh, &transform).unwrap();
+        assert_eq!(
+            collider.shape,
+            ColliderShape::TriMesh {
+                vertices: vec![
+                    Point3::new(0., 0., 1.),
+                    Point3::new(2., 0., 1.),
+                    Point3::new(0., 2., 1.),
+                ],
+                indices: vec![[0, 1, 2]],
+            }
+        );
+
+        let identity = Matrix4::identity();
+        assert!(load_collider(&meshes[1], &buffers, ColliderKind::Trimesh, &identity).is_err());
+        assert!(load_collider(&meshes[2], &buffers, ColliderKind::Convex, &identity).is_err());
+    }
+
+    #[test]
+    fn bounds_of_points() {
+        let (center, half_extents) = bounds(&[
+            Point3::new(-1., 0., 2.),
+            Point3::new(3., 4., 2.),
+            Point3::new(1., -2., 0.),
+        ]);
+        assert_eq!(center, Point3::new(1., 1., 1.));
+        assert_eq!(half_extents, Vector3::new(2., 3., 1.));
+    }
+}
@@ -24,7 +24,7 @@ impl ImageFormat {
 
 /// Buffer data returned from `import`.
 #[derive(Clone, Debug)]
-pub struct Buffers(Vec<Vec<u8>>);
+pub struct Buffers(pub(super) Vec<Vec<u8>>);
 
 #[allow(unused)]
 impl Buffers {
@@ -9,7 +9,7 @@ use serde::{Deserialize, Serialize};
 use amethyst_animation::AnimationHierarchyPrefab;
 use amethyst_assets::{Format, FormatValue, Prefab, Source};
 use amethyst_core::{
-    math::{Quaternion, Unit},
+    math::{Matrix4, Quaternion, Unit},
     transform::Transform,
 };
 use amethyst_error::{format_err, Error, ResultExt};
@@ -25,6 +25,8 @@ use self::{
 };
 
 mod animation;
+#[cfg(feature = "physics")]
+mod collider;
 mod importer;
 mod material;
 mod mesh;
@@ -37,6 +39,14 @@ mod skin;
 /// as the root node of the scene hierarchy.
 ///
 /// See `GltfSceneOptions` for more information about the load options.
+///
+/// With the `physics` feature, nodes with a mesh can be tagged to generate a `Collider`, either
+/// with an extras field such as `{ "collider": "convex", "collider_only": true }`, where the
+/// collider is one of `trimesh`, `convex`, `box` or `sphere`, or with a node name ending in
+/// `-col` (trimesh), `-convcol` (convex hull), `-colonly` or `-convcolonly`. The `only` variants
+/// load the collider without the graphics of the node. As the physics places the colliders by
+/// their local transforms, the collider of a node nested in another node is added to an entity
+/// of its own under the root, with the transform of the node relative to the root baked in.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct GltfSceneFormat;
 
@@ -140,6 +150,7 @@ fn load_scene(
             &mut skin_map,
             &mut bounding_box,
             &mut material_set,
+            None,
         )?;
     } else {
         for node in scene.nodes() {
@@ -157,6 +168,7 @@ fn load_scene(
                 &mut skin_map,
                 &mut bounding_box,
                 &mut material_set,
+                None,
             )?;
         }
         if bounding_box.valid() {
@@ -223,6 +235,7 @@ fn load_node(
     skin_map: &mut HashMap<usize, SkinInfo>,
     parent_bounding_box: &mut GltfNodeExtent,
     material_set: &mut GltfMaterialSet,
+    to_root: Option<Matrix4<f32>>,
 ) -> Result<(), Error> {
     node_map.insert(node.index(), entity_index);
 
@@ -244,6 +257,8 @@ fn load_node(
     ));
     *local_transform.scale_mut() = scale.into();
     prefab.data_or_default(entity_index).transform = Some(local_transform);
+    // The transform of the node in the space of the root, if its parent isn't the root.
+    let node_to_root = to_root.map(|to_root| to_root * Matrix4::from(node.transform().matrix()));
 
     // check for skinning
     let mut skin = node.skin().map(|skin| SkinInfo {
@@ -253,8 +268,34 @@ fn load_node(
 
     let mut bounding_box = GltfNodeExtent::default();
 
+    // load collider
+    #[cfg(feature = "physics")]
+    let load_graphics = match (collider::collider_tag(node), node.mesh()) {
+        (Some(tag), Some(mesh)) => {
+            match node_to_root {
+                None => {
+                    let scaling =
+                        Matrix4::new_nonuniform_scaling(&amethyst_core::math::Vector3::from(scale));
+                    prefab.data_or_default(entity_index).collider =
+                        Some(collider::load_collider(&mesh, buffers, tag.kind, &scaling)?);
+                }
+                Some(ref node_to_root) => {
+                    let collider = collider::load_collider(&mesh, buffers, tag.kind, node_to_root)?;
+                    let collider_entity = prefab.add(Some(0), None);
+                    let prefab_data = prefab.data_or_default(collider_entity);
+                    prefab_data.transform = Some(Transform::default());
+                    prefab_data.collider = Some(collider);
+                }
+            }
+            !tag.only
+        }
+        _ => true,
+    };
+    #[cfg(not(feature = "physics"))]
+    let load_graphics = true;
+
     // load graphics
-    if let Some(mesh) = node.mesh() {
+    if let Some(mesh) = node.mesh().filter(|_| load_graphics) {
         let mut graphics = load_mesh(&mesh, buffers, options)?;
         if graphics.len() == 1 {
             // single primitive can be loaded directly onto the node
@@ -310,6 +351,11 @@ fn load_node(
     }
 
     // load children
+    let child_to_root = if entity_index == 0 {
+        None
+    } else {
+        Some(node_to_root.unwrap_or_else(|| Matrix4::from(node.transform().matrix())))
+    };
     for child in node.children() {
         let index = prefab.add(Some(entity_index), None);
         load_node(
@@ -325,6 +371,7 @@ fn load_node(
             skin_map,
             &mut bounding_box,
             material_set,
+            child_to_root,
         )?;
     }
     if bounding_box.valid() {
@@ -49,6 +49,9 @@ pub struct GltfPrefab {
     pub extent: Option<GltfNodeExtent>,
     /// Node name
     pub name: Option<Named>,
+    /// `Collider` generated for nodes tagged for collision, requires the `physics` feature
+    #[cfg(feature = "physics")]
+    pub collider: Option<amethyst_physics::Collider>,
     pub(crate) materials: Option<GltfMaterialSet>,
     pub(crate) material_id: Option<usize>,
 }
@@ -160,6 +163,11 @@ pub struct GltfMaterialSet {
     pub(crate) materials: HashMap<usize, MaterialPrefab<TextureFormat>>,
 }
 
+#[cfg(feature = "physics")]
+type ColliderData<'a> = <amethyst_physics::Collider as PrefabData<'a>>::SystemData;
+#[cfg(not(feature = "physics"))]
+type ColliderData<'a> = ();
+
 /// Options used when loading a GLTF file
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 #[serde(default)]
@@ -187,6 +195,7 @@ impl<'a> PrefabData<'a> for GltfPrefab {
         // TODO make optional after prefab refactor. We need a way to pass options to decide to enable this or not, but without touching the prefab.
         WriteStorage<'a, MeshData>,
         Write<'a, GltfMaterialSet>,
+        ColliderData<'a>,
     );
     type Result = ();
 
@@ -207,6 +216,7 @@ impl<'a> PrefabData<'a> for GltfPrefab {
             ref mut extents,
             ref mut mesh_data,
             _,
+            ref mut colliders,
         ) = system_data;
         if let Some(ref transform) = self.transform {
             transform.add_to_entity(entity, transforms, entities, children)?;
@@ -232,6 +242,14 @@ impl<'a> PrefabData<'a> for GltfPrefab {
         if let Some(ref extent) = self.extent {
             extents.insert(entity, extent.clone())?;
         }
+        #[cfg(feature = "physics")]
+        {
+            if let Some(ref collider) = self.collider {
+                collider.add_to_entity(entity, colliders, entities, children)?;
+            }
+        }
+        #[cfg(not(feature = "physics"))]
+        let _ = colliders;
         Ok(())
     }
 
@@ -250,6 +268,7 @@ impl<'a> PrefabData<'a> for GltfPrefab {
             _,
             _,
             ref mut mat_set,
+            _,
         ) = system_data;
         let mut ret = false;
         if let Some(ref mut mats) = self.materials {
@@ -0,0 +1,42 @@
+//! The bundle of imgui.
+
+use amethyst_core::bundle::{BundleBuilder, SystemBundle};
+use amethyst_error::Error;
+
+use crate::input::ImguiInputSystem;
+
+/// Adds the `ImguiInputSystem`, giving the window events to imgui. The `DrawImgui` pass must be
+/// added to the pipeline too, after the other passes, for the windows to be drawn over the game:
+///
+/// ~~~ignore
+/// let pipe = Pipeline::build().with_stage(
+///     Stage::with_backbuffer()
+///         .clear_target([0.0, 0.0, 0.0, 1.0], 1.0)
+///         .with_pass(DrawFlat2D::new())
+///         .with_pass(DrawUi::new())
+///         .with_pass(DrawImgui::new()),
+/// );
+/// let game_data = GameDataBuilder::default()
+///     .with_bundle(ImguiBundle::new())?
+///     .with_bundle(RenderBundle::new(pipe, Some(config)))?;
+/// ~~~
+#[derive(Debug, Default)]
+pub struct ImguiBundle;
+
+impl ImguiBundle {
+    /// Creates the bundle.
+    pub fn new() -> Self {
+        ImguiBundle
+    }
+}
+
+impl<'a, 'b> SystemBundle<'a, 'b> for ImguiBundle {
+    fn build(self, builder: &mut BundleBuilder<'a, 'b>) -> Result<(), Error> {
+        builder.add(ImguiInputSystem::new(), "imgui_input", &[]);
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        "ImguiBundle"
+    }
+}
@@ -0,0 +1,93 @@
+//! The windows drawn on the frame.
+
+use std::sync::Mutex;
+
+use imgui::Ui;
+
+type Window = Box<dyn FnMut(&Ui<'_>) + Send>;
+
+/// The imgui windows of the frame, drawn by the systems into the `Ui` of the `DrawImgui` pass:
+///
+/// ~~~ignore
+/// impl<'a> System<'a> for DebugSystem {
+///     type SystemData = (Read<'a, ImguiFrame>, Read<'a, Time>);
+///
+///     fn run(&mut self, (frame, time): Self::SystemData) {
+///         let fps = 1.0 / time.delta_real_seconds();
+///         frame.draw(move |ui| {
+///             ui.window(im_str!("Debug")).build(|| {
+///                 ui.text(format!("{:.0} fps", fps));
+///             });
+///         });
+///     }
+/// }
+/// ~~~
+///
+/// The windows are drawn once, on the frame they were added on, so the systems add them on every
+/// frame. A window sends what it reads back to the game through the data it captured, like a
+/// channel or an `Arc<Mutex<_>>`. The windows no `DrawImgui` pass drew by the next frame are
+/// dropped by the `ImguiInputSystem`.
+#[derive(Default)]
+pub struct ImguiFrame {
+    windows: Mutex<Windows>,
+}
+
+#[derive(Default)]
+struct Windows {
+    windows: Vec<Window>,
+    // The number of windows already there on the last frame, the first ones.
+    stale: usize,
+}
+
+impl ImguiFrame {
+    /// Adds a window to the frame, drawn into its `Ui`. The systems may add windows in parallel.
+    pub fn draw<F>(&self, window: F)
+    where
+        F: FnMut(&Ui<'_>) + Send + 'static,
+    {
+        self.windows
+            .lock()
+            .expect("A window panicked while it was drawn")
+            .windows
+            .push(Box::new(window));
+    }
+
+    pub(crate) fn take(&self) -> Vec<Window> {
+        self.windows
+            .lock()
+            .map(|mut windows| {
+                windows.stale = 0;
+                windows.windows.split_off(0)
+            })
+            .unwrap_or_default()
+    }
+
+    // Drops the windows that were already there on the last frame, as no pass drew them.
+    pub(crate) fn drop_stale(&self) {
+        if let Ok(mut windows) = self.windows.lock() {
+            let stale = windows.stale;
+            windows.windows.drain(..stale);
+            windows.stale = windows.windows.len();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn windows_are_drawn_once() {
+        let frame = ImguiFrame::default();
+        frame.draw(|_| {});
+        frame.draw(|_| {});
+        assert_eq!(frame.take().len(), 2);
+        assert!(frame.take().is_empty());
+
+        frame.draw(|_| {});
+        frame.drop_stale();
+        frame.draw(|_| {});
+        frame.drop_stale();
+        assert_eq!(frame.take().len(), 1);
+    }
+}
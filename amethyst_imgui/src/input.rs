@@ -0,0 +1,184 @@
+//! The input of the imgui windows.
+
+use winit::{
+    ElementState, Event, KeyboardInput, MouseButton, MouseScrollDelta, VirtualKeyCode, WindowEvent,
+};
+
+use amethyst_core::{
+    ecs::prelude::{Read, Resources, System, SystemData, Write},
+    shrev::{EventChannel, ReaderId},
+};
+
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use crate::frame::ImguiFrame;
+
+/// The input given to imgui on the next frame, and whether imgui wants it.
+///
+/// The games should ignore the mouse while `wants_mouse` is true, and the keyboard while
+/// `wants_keyboard` is, as the input goes to a window then.
+#[derive(Clone, Debug, Default)]
+pub struct ImguiInput {
+    pub(crate) mouse_position: [f32; 2],
+    pub(crate) mouse_down: [bool; 5],
+    pub(crate) mouse_wheel: f32,
+    pub(crate) keys: Vec<(u8, bool)>,
+    pub(crate) characters: Vec<char>,
+    pub(crate) ctrl: bool,
+    pub(crate) shift: bool,
+    pub(crate) alt: bool,
+    pub(crate) logo: bool,
+    pub(crate) wants_mouse: bool,
+    pub(crate) wants_keyboard: bool,
+}
+
+impl ImguiInput {
+    /// Whether a window is under the mouse, or dragged.
+    pub fn wants_mouse(&self) -> bool {
+        self.wants_mouse
+    }
+
+    /// Whether a window has the keyboard focus.
+    pub fn wants_keyboard(&self) -> bool {
+        self.wants_keyboard
+    }
+
+    /// Records a window event, for the next frame.
+    pub fn handle(&mut self, event: &WindowEvent) {
+        match *event {
+            WindowEvent::CursorMoved { position, .. } => {
+                self.mouse_position = [position.x as f32, position.y as f32];
+            }
+            WindowEvent::MouseInput { state, button, .. } => {
+                let index = match button {
+                    MouseButton::Left => 0,
+                    MouseButton::Right => 1,
+                    MouseButton::Middle => 2,
+                    MouseButton::Other(index) if index < 2 => index as usize + 3,
+                    MouseButton::Other(_) => return,
+                };
+                self.mouse_down[index] = state == ElementState::Pressed;
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                self.mouse_wheel += match delta {
+                    MouseScrollDelta::LineDelta(_, y) => y,
+                    MouseScrollDelta::PixelDelta(position) => position.y as f32 / 20.0,
+                };
+            }
+            WindowEvent::ReceivedCharacter(c) => {
+                // The control characters are given as keys.
+                if !c.is_control() {
+                    self.characters.push(c);
+                }
+            }
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state,
+                        virtual_keycode: Some(key),
+                        modifiers,
+                        ..
+                    },
+                ..
+            } => {
+                self.keys.push((key as u8, state == ElementState::Pressed));
+                self.ctrl = modifiers.ctrl;
+                self.shift = modifiers.shift;
+                self.alt = modifiers.alt;
+                self.logo = modifiers.logo;
+            }
+            WindowEvent::Focused(false) => {
+                self.mouse_down = [false; 5];
+                self.ctrl = false;
+                self.shift = false;
+                self.alt = false;
+                self.logo = false;
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The keys of the shortcuts of imgui, as their virtual key codes.
+pub(crate) const KEYS: [VirtualKeyCode; 19] = [
+    VirtualKeyCode::Tab,
+    VirtualKeyCode::Left,
+    VirtualKeyCode::Right,
+    VirtualKeyCode::Up,
+    VirtualKeyCode::Down,
+    VirtualKeyCode::PageUp,
+    VirtualKeyCode::PageDown,
+    VirtualKeyCode::Home,
+    VirtualKeyCode::End,
+    VirtualKeyCode::Delete,
+    VirtualKeyCode::Back,
+    VirtualKeyCode::Return,
+    VirtualKeyCode::Escape,
+    VirtualKeyCode::A,
+    VirtualKeyCode::C,
+    VirtualKeyCode::V,
+    VirtualKeyCode::X,
+    VirtualKeyCode::Y,
+    VirtualKeyCode::Z,
+];
+
+/// Gives the window events to the `ImguiInput`, for the `DrawImgui` pass, and drops the windows of
+/// the `ImguiFrame` left from the frame before.
+#[derive(Default)]
+pub struct ImguiInputSystem {
+    reader: Option<ReaderId<Event>>,
+}
+
+impl ImguiInputSystem {
+    /// Creates a new `ImguiInputSystem`.
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl<'a> System<'a> for ImguiInputSystem {
+    type SystemData = (
+        Read<'a, EventChannel<Event>>,
+        Write<'a, ImguiInput>,
+        Read<'a, ImguiFrame>,
+    );
+
+    fn run(&mut self, (events, mut input, frame): Self::SystemData) {
+        #[cfg(feature = "profiler")]
+        profile_scope!("imgui_input_system");
+
+        frame.drop_stale();
+        for event in events.read(
+            self.reader
+                .as_mut()
+                .expect("`ImguiInputSystem::setup` was not called before `ImguiInputSystem::run`"),
+        ) {
+            if let Event::WindowEvent { ref event, .. } = *event {
+                input.handle(event);
+            }
+        }
+    }
+
+    fn setup(&mut self, res: &mut Resources) {
+        Self::SystemData::setup(res);
+        self.reader = Some(res.fetch_mut::<EventChannel<Event>>().register_reader());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn window_events_are_recorded() {
+        let mut input = ImguiInput::default();
+        input.mouse_down[0] = true;
+        input.handle(&WindowEvent::ReceivedCharacter('a'));
+        input.handle(&WindowEvent::ReceivedCharacter('\u{8}'));
+        input.handle(&WindowEvent::Focused(false));
+        assert_eq!(input.characters, vec!['a']);
+        assert_eq!(input.mouse_down, [false; 5]);
+        assert!(!input.wants_keyboard());
+    }
+}
@@ -0,0 +1,23 @@
+//! [Dear imgui](https://github.com/ocornut/imgui) for Amethyst, for the debug tools.
+//!
+//! The `ImguiBundle` gives the window events to imgui, and the `DrawImgui` pass draws the windows
+//! the systems added to the `ImguiFrame`, over the game, with the images of the `ImguiTextures`.
+//! The `ImguiInput` tells the game whether the mouse and the keyboard go to the windows.
+
+#![warn(missing_docs, rust_2018_idioms, rust_2018_compatibility)]
+
+pub use imgui;
+
+pub use self::{
+    bundle::ImguiBundle,
+    frame::ImguiFrame,
+    input::{ImguiInput, ImguiInputSystem},
+    pass::DrawImgui,
+    textures::ImguiTextures,
+};
+
+mod bundle;
+mod frame;
+mod input;
+mod pass;
+mod textures;
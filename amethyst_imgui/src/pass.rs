@@ -0,0 +1,339 @@
+//! The pass drawing the imgui windows.
+
+use std::{mem, ops::Range};
+
+use gfx::{
+    buffer,
+    handle::Buffer,
+    memory::{Bind, Usage},
+    preset::blend,
+    pso::buffer::{ElemStride, Element},
+    state::ColorMask,
+    traits::{Factory as GfxFactory, Pod},
+    Slice,
+};
+use glsl_layout::{vec2, vec4, Uniform};
+use imgui::{FrameSize, ImGui, ImGuiKey, ImTexture};
+use log::error;
+
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use amethyst_assets::AssetStorage;
+use amethyst_core::{
+    ecs::prelude::{Read, ReadExpect, Write},
+    Time,
+};
+use amethyst_error::Error;
+use amethyst_renderer::{
+    pipe::{
+        pass::{Pass, PassData},
+        Effect, NewEffect,
+    },
+    Attribute, Attributes, Color, Encoder, Factory, Position, Resources, ScreenDimensions,
+    TexCoord, Texture, TextureBuilder, VertexFormat,
+};
+
+use crate::{
+    frame::ImguiFrame,
+    input::{ImguiInput, KEYS},
+    textures::{font_texture, ImguiTextures},
+};
+
+const VERT_SRC: &[u8] = include_bytes!("shaders/vertex.glsl");
+const FRAG_SRC: &[u8] = include_bytes!("shaders/frag.glsl");
+
+/// The keys of imgui, in the order of `KEYS`.
+const IMGUI_KEYS: [ImGuiKey; 19] = [
+    ImGuiKey::Tab,
+    ImGuiKey::LeftArrow,
+    ImGuiKey::RightArrow,
+    ImGuiKey::UpArrow,
+    ImGuiKey::DownArrow,
+    ImGuiKey::PageUp,
+    ImGuiKey::PageDown,
+    ImGuiKey::Home,
+    ImGuiKey::End,
+    ImGuiKey::Delete,
+    ImGuiKey::Backspace,
+    ImGuiKey::Enter,
+    ImGuiKey::Escape,
+    ImGuiKey::A,
+    ImGuiKey::C,
+    ImGuiKey::V,
+    ImGuiKey::X,
+    ImGuiKey::Y,
+    ImGuiKey::Z,
+];
+
+#[derive(Copy, Clone, Debug, Uniform)]
+#[allow(dead_code)] // This is used by the shaders
+#[repr(C)]
+struct ImguiArgs {
+    screen_size: vec2,
+    clip_rect: vec4,
+}
+
+/// A vertex of the windows, with the color of imgui.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+struct ImguiVertex {
+    position: [f32; 3],
+    color: [f32; 4],
+    tex_coord: [f32; 2],
+}
+
+unsafe impl Pod for ImguiVertex {}
+
+impl VertexFormat for ImguiVertex {
+    const ATTRIBUTES: Attributes<'static> = &[
+        (
+            Position::NAME,
+            Element {
+                offset: 0,
+                format: Position::FORMAT,
+            },
+        ),
+        (
+            Color::NAME,
+            Element {
+                offset: Position::SIZE,
+                format: Color::FORMAT,
+            },
+        ),
+        (
+            TexCoord::NAME,
+            Element {
+                offset: Position::SIZE + Color::SIZE,
+                format: TexCoord::FORMAT,
+            },
+        ),
+    ];
+}
+
+// The vertices of a draw of the windows, its clip rectangle and its texture.
+type Draw = (Range<u32>, [f32; 4], ImTexture);
+
+/// Draws the windows of the `ImguiFrame`, over the passes before it. The context of imgui lives
+/// in the pass, which is created with the pipeline.
+///
+/// The vertices of the windows are written to a buffer kept between the frames, which grows with
+/// the windows.
+#[derive(Default)]
+pub struct DrawImgui {
+    imgui: Option<ImGui>,
+    font: Option<Texture>,
+    vertices: Vec<ImguiVertex>,
+    draws: Vec<Draw>,
+    buffer: Option<(Buffer<Resources, ImguiVertex>, usize)>,
+}
+
+impl DrawImgui {
+    /// Creates the pass.
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl<'a> PassData<'a> for DrawImgui {
+    type Data = (
+        ReadExpect<'a, ScreenDimensions>,
+        Read<'a, Time>,
+        Write<'a, ImguiInput>,
+        Read<'a, ImguiFrame>,
+        Read<'a, ImguiTextures>,
+        Read<'a, AssetStorage<Texture>>,
+    );
+}
+
+impl Pass for DrawImgui {
+    fn compile(&mut self, mut effect: NewEffect<'_>) -> Result<Effect, Error> {
+        #[cfg(feature = "profiler")]
+        profile_scope!("imgui_pass_compile");
+
+        let mut imgui = ImGui::init();
+        imgui.set_ini_filename(None);
+        for (&key, &code) in IMGUI_KEYS.iter().zip(KEYS.iter()) {
+            imgui.set_imgui_key(key, code as u8);
+        }
+        let font = imgui.prepare_texture(|atlas| {
+            TextureBuilder::new(atlas.pixels)
+                .with_size(atlas.width as u16, atlas.height as u16)
+                .build(&mut *effect.factory)
+        })?;
+        imgui.set_font_texture_id(font_texture());
+        self.imgui = Some(imgui);
+        self.font = Some(font);
+
+        effect
+            .simple(VERT_SRC, FRAG_SRC)
+            .with_raw_constant_buffer(
+                "ImguiArgs",
+                mem::size_of::<<ImguiArgs as Uniform>::Std140>(),
+                1,
+            )
+            .with_raw_vertex_buffer(
+                ImguiVertex::ATTRIBUTES,
+                mem::size_of::<ImguiVertex>() as ElemStride,
+                0,
+            )
+            .with_texture("font")
+            .with_blended_output("color", ColorMask::all(), blend::ALPHA, None)
+            .build()
+    }
+
+    fn apply<'a, 'b: 'a>(
+        &'a mut self,
+        encoder: &mut Encoder,
+        effect: &mut Effect,
+        mut factory: Factory,
+        (screen_dimensions, time, mut input, frame, images, textures): <Self as PassData<'a>>::Data,
+    ) {
+        #[cfg(feature = "profiler")]
+        profile_scope!("imgui_pass_apply");
+
+        let (imgui, font) = match (self.imgui.as_mut(), self.font.as_ref()) {
+            (Some(imgui), Some(font)) => (imgui, font),
+            _ => return,
+        };
+
+        input.wants_mouse = imgui.want_capture_mouse();
+        input.wants_keyboard = imgui.want_capture_keyboard();
+
+        // The positions of winit are logical, and the frame is drawn in physical pixels.
+        let hidpi = screen_dimensions.hidpi_factor() as f32;
+        imgui.set_mouse_pos(
+            input.mouse_position[0] * hidpi,
+            input.mouse_position[1] * hidpi,
+        );
+        imgui.set_mouse_down(input.mouse_down);
+        imgui.set_mouse_wheel(input.mouse_wheel);
+        input.mouse_wheel = 0.0;
+        imgui.set_key_ctrl(input.ctrl);
+        imgui.set_key_shift(input.shift);
+        imgui.set_key_alt(input.alt);
+        imgui.set_key_super(input.logo);
+        for (key, pressed) in input.keys.drain(..) {
+            imgui.set_key(key, pressed);
+        }
+        for c in input.characters.drain(..) {
+            imgui.add_input_character(c);
+        }
+
+        let (width, height) = (screen_dimensions.width(), screen_dimensions.height());
+        let ui = imgui.frame(
+            FrameSize::new(f64::from(width), f64::from(height), 1.0),
+            time.delta_real_seconds().max(1e-4),
+        );
+        for mut window in frame.take() {
+            window(&ui);
+        }
+
+        // The draws share the buffer, each with its range of the vertices, its clip rectangle and
+        // its texture.
+        let (vertices, draws) = (&mut self.vertices, &mut self.draws);
+        vertices.clear();
+        draws.clear();
+        let rendered = ui.render::<_, Error>(|_, draw_data| {
+            for draw_list in draw_data.into_iter() {
+                let mut indices = draw_list.idx_buffer.iter();
+                for command in draw_list.cmd_buffer {
+                    let start = vertices.len() as u32;
+                    for &index in indices.by_ref().take(command.elem_count as usize) {
+                        let vertex = &draw_list.vtx_buffer[index as usize];
+                        let color = vertex.col;
+                        vertices.push(ImguiVertex {
+                            position: [vertex.pos.x, vertex.pos.y, 0.0],
+                            color: [
+                                (color & 0xFF) as f32 / 255.0,
+                                (color >> 8 & 0xFF) as f32 / 255.0,
+                                (color >> 16 & 0xFF) as f32 / 255.0,
+                                (color >> 24 & 0xFF) as f32 / 255.0,
+                            ],
+                            // The rows of the textures are flipped when they are built.
+                            tex_coord: [vertex.uv.x, 1.0 - vertex.uv.y],
+                        });
+                    }
+                    let clip = command.clip_rect;
+                    draws.push((
+                        start..vertices.len() as u32,
+                        [clip.x, clip.y, clip.z, clip.w],
+                        ImTexture::from(command.texture_id),
+                    ));
+                }
+            }
+            Ok(())
+        });
+        if let Err(err) = rendered {
+            error!("Failed to render the imgui windows: {}", err);
+            return;
+        }
+        if self.vertices.is_empty() {
+            return;
+        }
+
+        let capacity = self.buffer.as_ref().map_or(0, |&(_, capacity)| capacity);
+        if capacity < self.vertices.len() {
+            let capacity = self.vertices.len().next_power_of_two();
+            match factory.create_buffer(
+                capacity,
+                buffer::Role::Vertex,
+                Usage::Dynamic,
+                Bind::empty(),
+            ) {
+                Ok(buffer) => self.buffer = Some((buffer, capacity)),
+                Err(err) => {
+                    error!(
+                        "Failed to create the buffer of the imgui windows: {:?}",
+                        err
+                    );
+                    return;
+                }
+            }
+        }
+        let vbuf = match self.buffer {
+            Some((ref buffer, _)) => buffer,
+            None => return,
+        };
+        if let Err(err) = encoder.update_buffer(vbuf, &self.vertices, 0) {
+            error!(
+                "Failed to update the buffer of the imgui windows: {:?}",
+                err
+            );
+            return;
+        }
+
+        for (range, clip_rect, texture) in self.draws.drain(..) {
+            if range.start == range.end {
+                continue;
+            }
+            let texture = if texture == font_texture() {
+                font
+            } else {
+                match images.get(texture).and_then(|handle| textures.get(handle)) {
+                    Some(texture) => texture,
+                    None => continue,
+                }
+            };
+            effect.data.vertex_bufs.push(vbuf.raw().clone());
+            effect.data.textures.push(texture.view().clone());
+            effect.data.samplers.push(texture.sampler().clone());
+            let args = ImguiArgs {
+                screen_size: [width as f32, height as f32].into(),
+                clip_rect: clip_rect.into(),
+            };
+            effect.update_constant_buffer("ImguiArgs", &args.std140(), encoder);
+            effect.draw(
+                &Slice {
+                    start: range.start,
+                    end: range.end,
+                    base_vertex: 0,
+                    instances: None,
+                    buffer: Default::default(),
+                },
+                encoder,
+            );
+            effect.clear();
+        }
+    }
+}
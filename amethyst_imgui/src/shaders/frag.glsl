@@ -0,0 +1,25 @@
+// The fragments of the imgui windows, clipped to the rectangles of their draws.
+
+#version 150 core
+
+layout (std140) uniform ImguiArgs {
+    uniform vec2 screen_size;
+    uniform vec4 clip_rect;
+};
+
+uniform sampler2D font;
+
+in VertexData {
+    vec2 tex_coord;
+    vec4 color;
+} vertex;
+
+out vec4 color;
+
+void main() {
+    vec2 pixel = vec2(gl_FragCoord.x, screen_size.y - gl_FragCoord.y);
+    if (any(lessThan(pixel, clip_rect.xy)) || any(greaterThan(pixel, clip_rect.zw))) {
+        discard;
+    }
+    color = texture(font, vertex.tex_coord) * vertex.color;
+}
@@ -0,0 +1,30 @@
+// The vertices of the imgui windows.
+
+#version 150 core
+
+layout (std140) uniform ImguiArgs {
+    uniform vec2 screen_size;
+    // The rectangle the draw is clipped to, as (x_min, y_min, x_max, y_max) from the top left.
+    uniform vec4 clip_rect;
+};
+
+in vec3 position;
+in vec4 color;
+in vec2 tex_coord;
+
+out VertexData {
+    vec2 tex_coord;
+    vec4 color;
+} vertex;
+
+void main() {
+    vertex.tex_coord = tex_coord;
+    vertex.color = color;
+    // From the pixels of imgui, from the top left corner, to the [-1, 1] square.
+    gl_Position = vec4(
+        position.x / screen_size.x * 2.0 - 1.0,
+        1.0 - position.y / screen_size.y * 2.0,
+        0.0,
+        1.0
+    );
+}
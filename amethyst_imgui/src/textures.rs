@@ -0,0 +1,51 @@
+//! The textures shown in the imgui windows.
+
+use std::{collections::HashMap, os::raw::c_void, ptr};
+
+use imgui::ImTexture;
+
+use amethyst_renderer::TextureHandle;
+
+/// The textures of the images of the imgui windows, by the ids given to `Ui::image`:
+///
+/// ~~~ignore
+/// let minimap = world.write_resource::<ImguiTextures>().insert(texture);
+/// frame.draw(move |ui| {
+///     ui.window(im_str!("Map")).build(|| {
+///         ui.image(minimap, (256.0, 256.0)).build();
+///     });
+/// });
+/// ~~~
+///
+/// The images are drawn once their texture is loaded.
+#[derive(Debug, Default)]
+pub struct ImguiTextures {
+    textures: HashMap<ImTexture, TextureHandle>,
+    // The ids start after the one of the font.
+    last: usize,
+}
+
+impl ImguiTextures {
+    /// Adds a texture, returning its id for imgui.
+    pub fn insert(&mut self, texture: TextureHandle) -> ImTexture {
+        self.last += 1;
+        let id = ImTexture::from(self.last as *mut c_void);
+        self.textures.insert(id, texture);
+        id
+    }
+
+    /// Removes a texture, returning it if it was added.
+    pub fn remove(&mut self, id: ImTexture) -> Option<TextureHandle> {
+        self.textures.remove(&id)
+    }
+
+    /// The texture with the given id.
+    pub fn get(&self, id: ImTexture) -> Option<&TextureHandle> {
+        self.textures.get(&id)
+    }
+}
+
+/// The id of the texture of the font, which imgui draws the text with.
+pub(crate) fn font_texture() -> ImTexture {
+    ImTexture::from(ptr::null_mut::<c_void>())
+}
@@ -5,7 +5,7 @@ use serde::{de::DeserializeOwned, Serialize};
 use std::{error, fmt, hash::Hash, path::Path};
 
 use amethyst_config::{Config, ConfigError};
-use amethyst_core::{bundle::SystemBundle, ecs::prelude::DispatcherBuilder};
+use amethyst_core::bundle::{BundleBuilder, SystemBundle};
 use amethyst_error::Error;
 
 use crate::{BindingError, Bindings, InputSystem};
@@ -98,7 +98,7 @@ where
     AX: Hash + Eq + Clone + Send + Sync + 'static,
     AC: Hash + Eq + Clone + Send + Sync + 'static,
 {
-    fn build(self, builder: &mut DispatcherBuilder<'a, 'b>) -> Result<(), Error> {
+    fn build(self, builder: &mut BundleBuilder<'a, 'b>) -> Result<(), Error> {
         #[cfg(feature = "sdl_controller")]
         {
             use super::SdlEventsSystem;
@@ -114,6 +114,10 @@ where
         );
         Ok(())
     }
+
+    fn name(&self) -> &'static str {
+        "InputBundle"
+    }
 }
 
 /// An error occurred while loading the bindings file.
@@ -3,6 +3,7 @@
 use std::{borrow::Borrow, hash::Hash};
 
 use derivative::Derivative;
+use serde::{Deserialize, Serialize};
 use smallvec::SmallVec;
 use winit::{
     dpi::LogicalPosition, DeviceEvent, ElementState, Event, KeyboardInput, MouseButton,
@@ -42,6 +43,19 @@ where
     /// while second is the ID used by incoming events.
     connected_controllers: SmallVec<[(u32, u32); 8]>,
     mouse_position: Option<(f64, f64)>,
+    /// Whether the events are ignored, while the state is replayed.
+    replaying: bool,
+}
+
+/// The state of the input devices held by the `InputHandler`, recorded to be replayed.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct InputState {
+    keys: Vec<(VirtualKeyCode, u32)>,
+    mouse_buttons: Vec<MouseButton>,
+    controller_buttons: Vec<(u32, ControllerButton)>,
+    controller_axes: Vec<(u32, ControllerAxis, f64)>,
+    controllers: Vec<(u32, u32)>,
+    mouse_position: Option<(f64, f64)>,
 }
 
 impl<AX, AC> InputHandler<AX, AC>
@@ -53,6 +67,39 @@ where
     pub fn new() -> Self {
         Default::default()
     }
+
+    /// The state of the input devices, to replay it with `set_state`.
+    pub fn state(&self) -> InputState {
+        InputState {
+            keys: self.pressed_keys.to_vec(),
+            mouse_buttons: self.pressed_mouse_buttons.to_vec(),
+            controller_buttons: self.pressed_controller_buttons.to_vec(),
+            controller_axes: self.controller_axes.to_vec(),
+            controllers: self.connected_controllers.to_vec(),
+            mouse_position: self.mouse_position,
+        }
+    }
+
+    /// Replaces the state of the input devices with a recorded one.
+    pub fn set_state(&mut self, state: InputState) {
+        self.pressed_keys = state.keys.into_iter().collect();
+        self.pressed_mouse_buttons = state.mouse_buttons.into_iter().collect();
+        self.pressed_controller_buttons = state.controller_buttons.into_iter().collect();
+        self.controller_axes = state.controller_axes.into_iter().collect();
+        self.connected_controllers = state.controllers.into_iter().collect();
+        self.mouse_position = state.mouse_position;
+    }
+
+    /// Whether the events of the devices are ignored, while a recording is replayed.
+    pub fn is_replaying(&self) -> bool {
+        self.replaying
+    }
+
+    /// Ignores the events of the devices while `replaying`, so that only the replayed state is
+    /// seen by the game.
+    pub fn set_replaying(&mut self, replaying: bool) {
+        self.replaying = replaying;
+    }
 }
 
 impl<AX, AC> InputHandler<AX, AC>
@@ -70,6 +117,9 @@ where
         event_handler: &mut EventChannel<InputEvent<AC>>,
         hidpi: f64,
     ) {
+        if self.replaying {
+            return;
+        }
         match *event {
             Event::WindowEvent { ref event, .. } => match *event {
                 WindowEvent::ReceivedCharacter(c) => {
@@ -277,6 +327,9 @@ where
     ) {
         use self::ControllerEvent::*;
 
+        if self.replaying {
+            return;
+        }
         match *event {
             ControllerAxisMoved { which, axis, value } => {
                 if let Some(controller_id) = self.controller_idx_to_id(which) {
@@ -1013,6 +1066,21 @@ right: `{:?}`",
         }
     }
 
+    #[test]
+    fn replayed_state_is_kept() {
+        let mut handler = InputHandler::<String, String>::new();
+        let mut events = EventChannel::<InputEvent<String>>::new();
+        handler.send_event(&key_press(104, VirtualKeyCode::Up), &mut events, HIDPI);
+        let state = handler.state();
+
+        let mut replayed = InputHandler::<String, String>::new();
+        replayed.set_replaying(true);
+        replayed.set_state(state.clone());
+        replayed.send_event(&key_release(104, VirtualKeyCode::Up), &mut events, HIDPI);
+        assert!(replayed.key_is_down(VirtualKeyCode::Up));
+        assert_eq!(replayed.state(), state);
+    }
+
     fn key_press(scancode: ScanCode, virtual_keycode: VirtualKeyCode) -> Event {
         key_event(scancode, virtual_keycode, ElementState::Pressed)
     }
@@ -11,7 +11,7 @@ pub use self::{
     button::Button,
     controller::{ControllerAxis, ControllerButton, ControllerEvent},
     event::InputEvent,
-    input_handler::InputHandler,
+    input_handler::{InputHandler, InputState},
     scroll_direction::ScrollDirection,
     system::InputSystem,
     util::{get_input_axis_simple, get_key, is_close_requested, is_key_down},
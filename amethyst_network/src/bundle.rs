@@ -2,10 +2,19 @@ use std::net::SocketAddr;
 
 use serde::{de::DeserializeOwned, Serialize};
 
-use amethyst_core::{bundle::SystemBundle, shred::DispatcherBuilder};
+use amethyst_core::bundle::{BundleBuilder, SystemBundle};
 use amethyst_error::{Error, ResultExt};
 
-use crate::{filter::NetFilter, server::ServerConfig, NetSocketSystem};
+use crate::{
+    channel::ChannelMode,
+    filter::NetFilter,
+    server::ServerConfig,
+    tick::NetworkTickSystem,
+    transport::{
+        EncryptionConfig, FragmentationConfig, NetworkConditions, PackingConfig, TransportKind,
+    },
+    NetSocketSystem,
+};
 
 /// A convenience bundle to create the infrastructure needed to send and receive network messages.
 pub struct NetworkBundle<T> {
@@ -24,11 +33,69 @@ impl<T> NetworkBundle<T> {
     pub fn new(udp_socket_addr: SocketAddr, filters: Vec<Box<dyn NetFilter<T>>>) -> Self {
         let config = ServerConfig {
             udp_socket_addr,
-            max_throughput: 5000,
+            ..Default::default()
         };
 
         NetworkBundle { config, filters }
     }
+
+    /// Sets the transport used to send and receive packets, UDP by default.
+    pub fn with_transport(mut self, transport: TransportKind) -> Self {
+        self.config.transport = transport;
+        self
+    }
+
+    /// Sets whether a `NetConnection` is created for the endpoints asking to connect, which
+    /// servers need.
+    pub fn with_accept_connections(mut self, accept: bool) -> Self {
+        self.config.accept_connections = accept;
+        self
+    }
+
+    /// Limits how many connections are accepted, see `with_accept_connections`.
+    pub fn with_max_connections(mut self, max: usize) -> Self {
+        self.config.max_connections = Some(max);
+        self
+    }
+
+    /// Simulates the given network conditions on the transport, in debug builds only.
+    pub fn with_simulation(mut self, conditions: NetworkConditions) -> Self {
+        self.config.simulation = Some(conditions);
+        self
+    }
+
+    /// Aggregates the small events into MTU-sized packets and compresses the large ones. The
+    /// remote endpoints must pack too.
+    pub fn with_packing(mut self, packing: PackingConfig) -> Self {
+        self.config.packing = Some(packing);
+        self
+    }
+
+    /// Encrypts the payloads and authenticates the endpoints with the given keys and token. The
+    /// remote endpoints must encrypt with the same pre-shared key.
+    pub fn with_encryption(mut self, encryption: EncryptionConfig) -> Self {
+        self.config.encryption = Some(encryption);
+        self
+    }
+
+    /// Changes how the payloads too large for a datagram are split, with the UDP transport.
+    pub fn with_fragmentation(mut self, fragmentation: FragmentationConfig) -> Self {
+        self.config.fragmentation = fragmentation;
+        self
+    }
+
+    /// Sends the events and takes the snapshots `rate` times per second, instead of every
+    /// frame.
+    pub fn with_tick_rate(mut self, rate: u32) -> Self {
+        self.config.tick_rate = Some(rate);
+        self
+    }
+
+    /// Adds a channel, or changes the mode of one of the default channels.
+    pub fn with_channel(mut self, channel: u8, mode: ChannelMode) -> Self {
+        self.config.channels.set(channel, mode);
+        self
+    }
 }
 
 impl<'a, 'b, T> SystemBundle<'a, 'b> for NetworkBundle<T>
@@ -36,12 +103,18 @@ where
     T: Send + Sync + PartialEq + Serialize + Clone + DeserializeOwned + 'static,
 {
     /// Build the networking bundle by adding the networking system to the application.
-    fn build(self, builder: &mut DispatcherBuilder<'_, '_>) -> Result<(), Error> {
+    fn build(self, builder: &mut BundleBuilder<'_, '_>) -> Result<(), Error> {
+        let tick_system = NetworkTickSystem::new(self.config.tick_rate);
         let socket_system = NetSocketSystem::<T>::new(self.config, self.filters)
             .with_context(|_| Error::from_string("Failed to open network system."))?;
 
-        builder.add(socket_system, "net_socket", &[]);
+        builder.add(tick_system, "net_tick", &[]);
+        builder.add(socket_system, "net_socket", &["net_tick"]);
 
         Ok(())
     }
+
+    fn name(&self) -> &'static str {
+        "NetworkBundle"
+    }
 }
@@ -0,0 +1,142 @@
+//! Channels grouping the network events by how they have to be delivered.
+//!
+//! Each channel is its own ordering stream, so a reliable event waiting for a resend doesn't
+//! hold back the unreliable ones sent after it on another channel.
+
+use std::collections::HashMap;
+
+use log::warn;
+
+use crate::{net_event::NetPacket, transport::Delivery};
+
+/// The channel the chat and the state changes that can't be lost are sent on by default.
+pub const RELIABLE_CHANNEL: u8 = 0;
+
+/// The channel the frequent updates, like the movement snapshots, are sent on by default.
+pub const UNRELIABLE_CHANNEL: u8 = 1;
+
+/// The channel the voice chat is sent on by default.
+pub const VOICE_CHANNEL: u8 = 2;
+
+/// How the events of a channel are delivered.
+///
+/// The reliable modes are acknowledged by the receiver and resent until they are, which the UDP
+/// transport does through `laminar`. The stream transports deliver everything reliably and in
+/// order whatever the mode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChannelMode {
+    /// May be dropped, duplicated or arrive out of order.
+    Unreliable,
+    /// May be dropped, the events arriving after a newer one are dropped.
+    UnreliableSequenced,
+    /// Always arrives, in any order.
+    ReliableUnordered,
+    /// Always arrives, in the order it was sent.
+    ReliableOrdered,
+    /// Always arrives, the events arriving after a newer one are dropped.
+    ReliableSequenced,
+}
+
+impl ChannelMode {
+    /// The delivery of the events of the channel `channel` with this mode.
+    pub fn delivery(self, channel: u8) -> Delivery {
+        match self {
+            ChannelMode::Unreliable => Delivery::Unreliable,
+            ChannelMode::UnreliableSequenced => Delivery::UnreliableSequenced(Some(channel)),
+            ChannelMode::ReliableUnordered => Delivery::ReliableUnordered,
+            ChannelMode::ReliableOrdered => Delivery::ReliableOrdered(Some(channel)),
+            ChannelMode::ReliableSequenced => Delivery::ReliableSequenced(Some(channel)),
+        }
+    }
+}
+
+/// The channels the `NetSocketSystem` sends the packets created with `NetPacket::on_channel` on.
+///
+/// By default, `RELIABLE_CHANNEL` is reliable ordered, and `UNRELIABLE_CHANNEL` and
+/// `VOICE_CHANNEL` are unreliable sequenced.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Channels {
+    modes: HashMap<u8, ChannelMode>,
+}
+
+impl Default for Channels {
+    fn default() -> Self {
+        Channels::empty()
+            .with(RELIABLE_CHANNEL, ChannelMode::ReliableOrdered)
+            .with(UNRELIABLE_CHANNEL, ChannelMode::UnreliableSequenced)
+            .with(VOICE_CHANNEL, ChannelMode::UnreliableSequenced)
+    }
+}
+
+impl Channels {
+    /// Creates a set without any channel.
+    pub fn empty() -> Self {
+        Channels {
+            modes: HashMap::new(),
+        }
+    }
+
+    /// Adds a channel, or changes the mode of an existing one.
+    pub fn with(mut self, channel: u8, mode: ChannelMode) -> Self {
+        self.set(channel, mode);
+        self
+    }
+
+    /// Adds a channel, or changes the mode of an existing one.
+    pub fn set(&mut self, channel: u8, mode: ChannelMode) {
+        self.modes.insert(channel, mode);
+    }
+
+    /// Returns the mode of a channel.
+    pub fn mode(&self, channel: u8) -> Option<ChannelMode> {
+        self.modes.get(&channel).cloned()
+    }
+
+    /// Gives the packet the delivery of its channel, if it was sent on one.
+    ///
+    /// The packets sent on an unknown channel keep being reliable ordered.
+    pub fn apply<T>(&self, packet: &mut NetPacket<T>) {
+        if let Some(channel) = packet.channel() {
+            match self.mode(channel) {
+                Some(mode) => packet.set_delivery(mode.delivery(channel)),
+                None => warn!(
+                    "Sending a packet on the unknown channel {}, it is reliable ordered.",
+                    channel
+                ),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packets_get_the_delivery_of_their_channel() {
+        let channels = Channels::default().with(7, ChannelMode::ReliableSequenced);
+
+        let mut chat = NetPacket::on_channel("hello", RELIABLE_CHANNEL);
+        channels.apply(&mut chat);
+        assert_eq!(
+            chat.delivery(),
+            Delivery::ReliableOrdered(Some(RELIABLE_CHANNEL))
+        );
+
+        let mut snapshot = NetPacket::on_channel("position", UNRELIABLE_CHANNEL);
+        channels.apply(&mut snapshot);
+        assert!(snapshot.is_unreliable());
+        assert_eq!(
+            snapshot.delivery(),
+            Delivery::UnreliableSequenced(Some(UNRELIABLE_CHANNEL))
+        );
+
+        let mut custom = NetPacket::on_channel("custom", 7);
+        channels.apply(&mut custom);
+        assert_eq!(custom.delivery(), Delivery::ReliableSequenced(Some(7)));
+
+        let mut unreliable = NetPacket::unreliable("plain");
+        channels.apply(&mut unreliable);
+        assert_eq!(unreliable.delivery(), Delivery::Unreliable);
+    }
+}
@@ -28,6 +28,9 @@ pub struct NetConnection<E: 'static> {
     /// Private. Used by `NetSocketSystem` to be able to immediately send events upon receiving a new NetConnection.
     #[serde(skip)]
     send_reader: ReaderId<NetEvent<E>>,
+    /// Whether the `NetSocketSystem` closed the dropped connection on its transport.
+    #[serde(skip)]
+    pub(crate) closed: bool,
 }
 
 impl<E: Send + Sync + 'static> NetConnection<E> {
@@ -42,6 +45,7 @@ impl<E: Send + Sync + 'static> NetConnection<E> {
             send_buffer,
             receive_buffer: EventChannel::<NetEvent<E>>::new(),
             send_reader,
+            closed: false,
         }
     }
 
@@ -0,0 +1,216 @@
+use std::{
+    io::{Read, Write},
+    net::{SocketAddr, TcpStream},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use crossbeam_channel::Receiver;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use amethyst_core::{
+    ecs::{Read as ReadResource, ReadExpect, System},
+    timing::Time,
+};
+
+use crate::error::{Error, Result};
+
+use super::ServerInfo;
+
+/// How long a request to the master server may take.
+const HTTP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A game listed by a master server.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServerEntry {
+    /// The address to connect to.
+    pub addr: SocketAddr,
+    /// What the server told about itself.
+    pub info: ServerInfo,
+}
+
+/// A master server, listing the games over the internet.
+///
+/// The servers `POST` their `ServerInfo` as JSON to the URL of the master server, which is
+/// expected to take the address of the game from the request. The clients `GET` the same URL
+/// and receive the JSON list of the `ServerEntry`s. Only plain `http://` URLs are supported.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MasterServer {
+    url: Url,
+    interval: Duration,
+}
+
+impl MasterServer {
+    /// A master server at `url`, which the servers register with every 30 seconds.
+    pub fn new(url: Url) -> Self {
+        MasterServer {
+            url,
+            interval: Duration::from_secs(30),
+        }
+    }
+
+    /// Sets how often the servers register, which must be shorter than the time after which
+    /// the master server forgets them.
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Registers a server, blocking until the master server answers.
+    pub fn register(&self, info: &ServerInfo) -> Result<()> {
+        let body = serde_json::to_vec(info)?;
+        self.request("POST", &body).map(|_| ())
+    }
+
+    /// Lists the registered servers, blocking until the master server answers.
+    ///
+    /// The systems use `list_in_background` instead, not to stall the frame.
+    pub fn list(&self) -> Result<Vec<ServerEntry>> {
+        let body = self.request("GET", &[])?;
+        Ok(serde_json::from_slice(&body)?)
+    }
+
+    /// Lists the registered servers on another thread, the list being received from the
+    /// returned channel once the master server answers.
+    ///
+    /// ```rust,ignore
+    /// match listing.try_recv() {
+    ///     Ok(Ok(servers)) => show(servers),
+    ///     Ok(Err(e)) => warn!("Failed to list the servers: {}", e),
+    ///     Err(_) => {} // Still waiting.
+    /// }
+    /// ```
+    pub fn list_in_background(&self) -> Receiver<Result<Vec<ServerEntry>>> {
+        let (sender, receiver) = crossbeam_channel::bounded(1);
+        let master_server = self.clone();
+        thread::spawn(move || {
+            let _ = sender.send(master_server.list());
+        });
+        receiver
+    }
+
+    // Sends an HTTP/1.0 request, so the answer isn't chunked, and returns the body of the answer.
+    fn request(&self, method: &str, body: &[u8]) -> Result<Vec<u8>> {
+        if self.url.scheme() != "http" {
+            return Err(Error::MasterServerError(format!(
+                "Unsupported scheme {}",
+                self.url.scheme()
+            )));
+        }
+        let host = self
+            .url
+            .host_str()
+            .ok_or_else(|| Error::MasterServerError("The URL has no host".to_string()))?;
+        let port = self.url.port_or_known_default().unwrap_or(80);
+        let mut path = self.url.path().to_string();
+        if let Some(query) = self.url.query() {
+            path.push('?');
+            path.push_str(query);
+        }
+
+        let mut stream = TcpStream::connect((host, port))?;
+        stream.set_read_timeout(Some(HTTP_TIMEOUT))?;
+        stream.set_write_timeout(Some(HTTP_TIMEOUT))?;
+        write!(
+            stream,
+            "{} {} HTTP/1.0\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n",
+            method,
+            path,
+            host,
+            body.len()
+        )?;
+        stream.write_all(body)?;
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response)?;
+        parse_response(&response)
+    }
+}
+
+// Checks the status of an HTTP answer and returns its body.
+fn parse_response(response: &[u8]) -> Result<Vec<u8>> {
+    let invalid = || Error::MasterServerError("Invalid HTTP answer".to_string());
+    let end = response
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .ok_or_else(invalid)?;
+    let head = String::from_utf8_lossy(&response[..end]);
+    let status = head
+        .lines()
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|status| status.parse::<u16>().ok())
+        .ok_or_else(invalid)?;
+    if status < 200 || status >= 300 {
+        return Err(Error::MasterServerError(format!(
+            "The master server answered {}",
+            status
+        )));
+    }
+    Ok(response[end + 4..].to_vec())
+}
+
+/// Registers the `ServerInfo` resource with a master server periodically, on another thread.
+pub struct MasterServerSystem {
+    master_server: Arc<MasterServer>,
+    last_sent: Option<Duration>,
+    busy: Arc<AtomicBool>,
+}
+
+impl MasterServerSystem {
+    /// Registers with the given master server.
+    pub fn new(master_server: MasterServer) -> Self {
+        MasterServerSystem {
+            master_server: Arc::new(master_server),
+            last_sent: None,
+            busy: Arc::new(AtomicBool::new(false)),
+        }
+    }
+}
+
+impl<'a> System<'a> for MasterServerSystem {
+    type SystemData = (ReadResource<'a, Time>, ReadExpect<'a, ServerInfo>);
+
+    fn run(&mut self, (time, info): Self::SystemData) {
+        let now = time.absolute_real_time();
+        let interval = self.master_server.interval;
+        if self.busy.load(Ordering::Acquire)
+            || self
+                .last_sent
+                .map_or(false, |last_sent| now - last_sent < interval)
+        {
+            return;
+        }
+        self.last_sent = Some(now);
+        self.busy.store(true, Ordering::Release);
+        let master_server = Arc::clone(&self.master_server);
+        let busy = Arc::clone(&self.busy);
+        let info = info.clone();
+        thread::spawn(move || {
+            if let Err(e) = master_server.register(&info) {
+                warn!("Failed to register with the master server: {}", e);
+            }
+            busy.store(false, Ordering::Release);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn answers_are_parsed() {
+        assert_eq!(
+            parse_response(b"HTTP/1.0 200 OK\r\nContent-Length: 2\r\n\r\n[]").unwrap(),
+            b"[]".to_vec()
+        );
+        assert!(parse_response(b"HTTP/1.1 404 Not Found\r\n\r\n").is_err());
+        assert!(parse_response(b"garbage").is_err());
+    }
+}
@@ -0,0 +1,433 @@
+//! Finding the games to join without typing addresses.
+//!
+//! The servers broadcast a beacon describing them on the local network, which the clients'
+//! scanners turn into `DiscoveryEvent`s and a list of `DiscoveredServers`. The servers can also
+//! register with a master server, which lists the games over the internet.
+
+use std::{
+    collections::HashMap,
+    io,
+    net::{Ipv4Addr, SocketAddr, UdpSocket},
+    time::Duration,
+};
+
+use log::{error, warn};
+use net2::UdpBuilder;
+use serde::{Deserialize, Serialize};
+use shrev::EventChannel;
+
+use amethyst_core::{
+    bundle::{BundleBuilder, SystemBundle},
+    ecs::{Read, ReadExpect, Resources, System, SystemData, Write},
+    timing::Time,
+};
+use amethyst_error::{Error, ResultExt};
+
+use crate::error::Result;
+
+pub use self::master::{MasterServer, MasterServerSystem, ServerEntry};
+
+mod master;
+
+/// The port the beacons are broadcast to by default.
+pub const DISCOVERY_PORT: u16 = 47_777;
+
+// Identifies the beacons among the other broadcasts.
+const BEACON_MAGIC: u32 = 0x414d_4554;
+
+/// What a server tells about itself, kept up to date by the game in a resource.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServerInfo {
+    /// The name shown to the players.
+    pub name: String,
+    /// The game, servers of other games are ignored by the scanners.
+    pub game: String,
+    /// The port the `NetworkBundle` of the server is bound to.
+    pub port: u16,
+    /// The number of players in the game.
+    pub players: u32,
+    /// The most players the game accepts.
+    pub max_players: u32,
+    /// The version of the protocol of the server.
+    pub protocol_version: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Beacon {
+    magic: u32,
+    info: ServerInfo,
+}
+
+/// The port and timings of the discovery.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DiscoveryConfig {
+    /// The port the beacons are broadcast to, and the scanners listen on.
+    pub port: u16,
+    /// How often the servers broadcast their beacon.
+    pub interval: Duration,
+    /// How long after its last beacon a server is considered gone.
+    pub timeout: Duration,
+}
+
+impl Default for DiscoveryConfig {
+    fn default() -> Self {
+        DiscoveryConfig {
+            port: DISCOVERY_PORT,
+            interval: Duration::from_secs(1),
+            timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+/// A server found on the local network.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DiscoveredServer {
+    /// The address to connect to.
+    pub addr: SocketAddr,
+    /// What the server told about itself.
+    pub info: ServerInfo,
+    /// When its last beacon was received, in the absolute real time.
+    pub last_seen: Duration,
+}
+
+/// The changes of the `DiscoveredServers`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DiscoveryEvent {
+    /// A new server was found.
+    Found(DiscoveredServer),
+    /// A known server changed its information.
+    Updated(DiscoveredServer),
+    /// A server stopped broadcasting, identified by its address.
+    Lost(SocketAddr),
+}
+
+/// The servers currently broadcasting on the local network.
+#[derive(Debug, Default)]
+pub struct DiscoveredServers {
+    servers: HashMap<SocketAddr, DiscoveredServer>,
+}
+
+impl DiscoveredServers {
+    /// The server at an address, if it was found.
+    pub fn get(&self, addr: SocketAddr) -> Option<&DiscoveredServer> {
+        self.servers.get(&addr)
+    }
+
+    /// All the servers found, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = &DiscoveredServer> {
+        self.servers.values()
+    }
+
+    /// The number of servers found.
+    pub fn len(&self) -> usize {
+        self.servers.len()
+    }
+
+    /// Whether no server was found.
+    pub fn is_empty(&self) -> bool {
+        self.servers.is_empty()
+    }
+}
+
+/// Broadcasts the `ServerInfo` resource on the local network.
+pub struct DiscoveryBeaconSystem {
+    socket: UdpSocket,
+    target: SocketAddr,
+    info: Option<ServerInfo>,
+    interval: Duration,
+    last_sent: Option<Duration>,
+}
+
+impl DiscoveryBeaconSystem {
+    /// Binds the broadcasting socket. The `ServerInfo` resource is inserted with `info` when
+    /// missing.
+    pub fn new(info: ServerInfo, config: &DiscoveryConfig) -> Result<Self> {
+        let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+        socket.set_broadcast(true)?;
+        socket.set_nonblocking(true)?;
+        Ok(DiscoveryBeaconSystem {
+            socket,
+            target: (Ipv4Addr::BROADCAST, config.port).into(),
+            info: Some(info),
+            interval: config.interval,
+            last_sent: None,
+        })
+    }
+}
+
+impl<'a> System<'a> for DiscoveryBeaconSystem {
+    type SystemData = (Read<'a, Time>, ReadExpect<'a, ServerInfo>);
+
+    fn run(&mut self, (time, info): Self::SystemData) {
+        let now = time.absolute_real_time();
+        if self
+            .last_sent
+            .map_or(false, |last_sent| now - last_sent < self.interval)
+        {
+            return;
+        }
+        self.last_sent = Some(now);
+        let beacon = Beacon {
+            magic: BEACON_MAGIC,
+            info: info.clone(),
+        };
+        match bincode::serialize(&beacon) {
+            Ok(payload) => {
+                if let Err(e) = self.socket.send_to(&payload, self.target) {
+                    warn!("Failed to broadcast the server beacon: {}", e);
+                }
+            }
+            Err(e) => error!("Failed to serialize the server beacon: {}", e),
+        }
+    }
+
+    fn setup(&mut self, res: &mut Resources) {
+        Self::SystemData::setup(res);
+        if let Some(info) = self.info.take() {
+            res.entry::<ServerInfo>().or_insert(info);
+        }
+    }
+}
+
+/// Listens to the beacons of the servers of a game, keeping the `DiscoveredServers` resource up
+/// to date and writing the changes to the `EventChannel<DiscoveryEvent>`.
+pub struct DiscoveryScannerSystem {
+    socket: UdpSocket,
+    game: String,
+    timeout: Duration,
+}
+
+impl DiscoveryScannerSystem {
+    /// Listens for the beacons of the servers of `game`.
+    ///
+    /// The port is bound for reuse, so that several games on the same machine can scan at once.
+    pub fn new(game: impl Into<String>, config: &DiscoveryConfig) -> Result<Self> {
+        let builder = UdpBuilder::new_v4()?;
+        builder.reuse_address(true)?;
+        #[cfg(unix)]
+        {
+            use net2::unix::UnixUdpBuilderExt;
+            builder.reuse_port(true)?;
+        }
+        let socket = builder.bind((Ipv4Addr::UNSPECIFIED, config.port))?;
+        socket.set_nonblocking(true)?;
+        Ok(DiscoveryScannerSystem {
+            socket,
+            game: game.into(),
+            timeout: config.timeout,
+        })
+    }
+
+    fn receive(
+        &self,
+        payload: &[u8],
+        from: SocketAddr,
+        now: Duration,
+        servers: &mut DiscoveredServers,
+        events: &mut EventChannel<DiscoveryEvent>,
+    ) {
+        let beacon = match bincode::deserialize::<Beacon>(payload) {
+            Ok(ref beacon) if beacon.magic != BEACON_MAGIC => return,
+            Ok(beacon) => beacon,
+            Err(_) => return,
+        };
+        if beacon.info.game != self.game {
+            return;
+        }
+        let addr = SocketAddr::new(from.ip(), beacon.info.port);
+        let server = DiscoveredServer {
+            addr,
+            info: beacon.info,
+            last_seen: now,
+        };
+        match servers.servers.insert(addr, server.clone()) {
+            None => events.single_write(DiscoveryEvent::Found(server)),
+            Some(ref previous) if previous.info != server.info => {
+                events.single_write(DiscoveryEvent::Updated(server))
+            }
+            Some(_) => {}
+        }
+    }
+
+    fn expire(
+        &self,
+        now: Duration,
+        servers: &mut DiscoveredServers,
+        events: &mut EventChannel<DiscoveryEvent>,
+    ) {
+        let timeout = self.timeout;
+        let lost = servers
+            .servers
+            .values()
+            .filter(|server| {
+                now.checked_sub(server.last_seen)
+                    .map_or(false, |age| age > timeout)
+            })
+            .map(|server| server.addr)
+            .collect::<Vec<_>>();
+        for addr in lost {
+            servers.servers.remove(&addr);
+            events.single_write(DiscoveryEvent::Lost(addr));
+        }
+    }
+}
+
+impl<'a> System<'a> for DiscoveryScannerSystem {
+    type SystemData = (
+        Read<'a, Time>,
+        Write<'a, DiscoveredServers>,
+        Write<'a, EventChannel<DiscoveryEvent>>,
+    );
+
+    fn run(&mut self, (time, mut servers, mut events): Self::SystemData) {
+        let now = time.absolute_real_time();
+        let mut buffer = [0; 2048];
+        loop {
+            match self.socket.recv_from(&mut buffer) {
+                Ok((size, from)) => {
+                    self.receive(&buffer[..size], from, now, &mut servers, &mut events)
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    warn!("Failed to receive a server beacon: {}", e);
+                    break;
+                }
+            }
+        }
+        self.expire(now, &mut servers, &mut events);
+    }
+}
+
+/// Adds the discovery systems of a server or a client.
+pub struct DiscoveryBundle {
+    role: DiscoveryRole,
+    config: DiscoveryConfig,
+    master_server: Option<MasterServer>,
+}
+
+enum DiscoveryRole {
+    Server(ServerInfo),
+    Client(String),
+}
+
+impl DiscoveryBundle {
+    /// Broadcasts the server, inserting the `ServerInfo` resource with `info` when missing.
+    pub fn server(info: ServerInfo) -> Self {
+        DiscoveryBundle::new(DiscoveryRole::Server(info))
+    }
+
+    /// Scans for the servers of a game.
+    pub fn client(game: impl Into<String>) -> Self {
+        DiscoveryBundle::new(DiscoveryRole::Client(game.into()))
+    }
+
+    fn new(role: DiscoveryRole) -> Self {
+        DiscoveryBundle {
+            role,
+            config: DiscoveryConfig::default(),
+            master_server: None,
+        }
+    }
+
+    /// Sets the port and the timings.
+    pub fn with_config(mut self, config: DiscoveryConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Also registers the server with a master server, ignored by the clients.
+    pub fn with_master_server(mut self, master_server: MasterServer) -> Self {
+        self.master_server = Some(master_server);
+        self
+    }
+}
+
+impl<'a, 'b> SystemBundle<'a, 'b> for DiscoveryBundle {
+    fn build(self, builder: &mut BundleBuilder<'a, 'b>) -> std::result::Result<(), Error> {
+        match self.role {
+            DiscoveryRole::Server(info) => {
+                let beacon = DiscoveryBeaconSystem::new(info, &self.config)
+                    .with_context(|_| Error::from_string("Failed to open the beacon socket."))?;
+                builder.add(beacon, "discovery_beacon", &[]);
+                if let Some(master_server) = self.master_server {
+                    builder.add(
+                        MasterServerSystem::new(master_server),
+                        "master_server_registration",
+                        &[],
+                    );
+                }
+            }
+            DiscoveryRole::Client(game) => {
+                let scanner = DiscoveryScannerSystem::new(game, &self.config)
+                    .with_context(|_| Error::from_string("Failed to open the scanner socket."))?;
+                builder.add(scanner, "discovery_scanner", &[]);
+            }
+        }
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        "DiscoveryBundle"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(players: u32) -> ServerInfo {
+        ServerInfo {
+            name: "Test".to_string(),
+            game: "pong".to_string(),
+            port: 3456,
+            players,
+            max_players: 2,
+            protocol_version: 1,
+        }
+    }
+
+    #[test]
+    fn beacons_update_the_discovered_servers() {
+        let scanner = DiscoveryScannerSystem::new(
+            "pong",
+            &DiscoveryConfig {
+                port: 0,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let mut servers = DiscoveredServers::default();
+        let mut events = EventChannel::new();
+        let mut reader = events.register_reader();
+        let from = "192.168.1.7:50000".parse().unwrap();
+        let addr = "192.168.1.7:3456".parse().unwrap();
+        let beacon = |players| {
+            bincode::serialize(&Beacon {
+                magic: BEACON_MAGIC,
+                info: info(players),
+            })
+            .unwrap()
+        };
+
+        let second = Duration::from_secs(1);
+        scanner.receive(&beacon(0), from, second, &mut servers, &mut events);
+        scanner.receive(&beacon(0), from, 2 * second, &mut servers, &mut events);
+        scanner.receive(&beacon(1), from, 3 * second, &mut servers, &mut events);
+        scanner.receive(b"garbage", from, 3 * second, &mut servers, &mut events);
+        assert_eq!(servers.len(), 1);
+        assert_eq!(servers.get(addr).unwrap().info.players, 1);
+
+        scanner.expire(10 * second, &mut servers, &mut events);
+        assert!(servers.is_empty());
+        let events = events.read(&mut reader).cloned().collect::<Vec<_>>();
+        assert_eq!(events.len(), 3);
+        match (&events[0], &events[1], &events[2]) {
+            (DiscoveryEvent::Found(found), DiscoveryEvent::Updated(updated), lost) => {
+                assert_eq!(found.addr, addr);
+                assert_eq!(updated.info, info(1));
+                assert_eq!(lost, &DiscoveryEvent::Lost(addr));
+            }
+            events => panic!("Unexpected events {:?}", events),
+        }
+    }
+}
@@ -20,6 +20,18 @@ pub enum Error {
     /// Error that could occur when serializing whit `bincode`
     #[error(display = "Serialization error occurred")]
     SerializeError(#[cause] bincode::Error),
+    /// Error that could occur on a WebSocket.
+    #[error(display = "WebSocket error occurred")]
+    WebSocketError(#[cause] tungstenite::Error),
+    /// Error that could occur when encoding or decoding the JSON of a master server.
+    #[error(display = "JSON error occurred")]
+    JsonError(#[cause] serde_json::Error),
+    /// Error that could occur when talking to a master server.
+    #[error(display = "Master server error: {}", _0)]
+    MasterServerError(String),
+    /// Error that could occur when encrypting, or during the handshake of an encrypted session.
+    #[error(display = "Encryption error: {}", _0)]
+    EncryptionError(String),
     /// Error that could occur when sending an `ServerSocketEvent` to some channel.
     #[error(display = "Channel send error occurred")]
     ChannelSendError(#[cause] crossbeam_channel::SendError<laminar::Packet>),
@@ -46,6 +58,18 @@ impl From<laminar::ErrorKind> for Error {
     }
 }
 
+impl From<tungstenite::Error> for Error {
+    fn from(e: tungstenite::Error) -> Error {
+        Error::WebSocketError(e)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Error {
+        Error::JsonError(e)
+    }
+}
+
 impl From<bincode::Error> for Error {
     fn from(e: bincode::Error) -> Error {
         Error::SerializeError(e)
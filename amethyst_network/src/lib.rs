@@ -4,59 +4,136 @@
 
 pub use crate::{
     bundle::NetworkBundle,
+    channel::{ChannelMode, Channels, RELIABLE_CHANNEL, UNRELIABLE_CHANNEL, VOICE_CHANNEL},
     connection::{ConnectionState, NetConnection, NetIdentity},
+    discovery::{
+        DiscoveredServer, DiscoveredServers, DiscoveryBeaconSystem, DiscoveryBundle,
+        DiscoveryConfig, DiscoveryEvent, DiscoveryScannerSystem, MasterServer, MasterServerSystem,
+        ServerEntry, ServerInfo, DISCOVERY_PORT,
+    },
     error::Result,
     filter::{FilterConnected, NetFilter},
+    lifecycle::{
+        ConnectionCommands, ConnectionEvent, ConnectionInfo, ConnectionLifecycleSystem,
+        ConnectionRole, DisconnectReason, LifecycleBundle, LifecycleConfig,
+    },
+    lockstep::{
+        Desync, LockstepBundle, LockstepConfig, LockstepEvent, LockstepMessage, LockstepPeer,
+        LockstepSession, LockstepSystem, LockstepTick, HASH_HISTORY, INPUT_WINDOW,
+    },
+    nat::{
+        NatBundle, NatConfig, NatEvent, NatMessage, NatPeer, NatStatus, NatTraversalEvent,
+        NatTraversalSystem, Rendezvous, RendezvousPeer, RendezvousServerSystem,
+    },
     net_event::{NetEvent, NetPacket},
     network_socket::NetSocketSystem,
+    prediction::{
+        AuthoritySystem, ControlledEntity, LocallyControlled, Prediction, PredictionClientBundle,
+        PredictionEvent, PredictionHistory, PredictionInput, PredictionMessage,
+        PredictionMessageOf, PredictionPeer, PredictionServerBundle, PredictionSystem,
+        PREDICTION_HISTORY,
+    },
+    replication::{
+        EntityDelta, GridInterest, InterestPolicy, Interpolate, InterpolationConfig, NetworkId,
+        NetworkedComponent, Observer, RadiusInterest, ReplayFrame, ReplayPlayback,
+        ReplayPlaybackSystem, ReplayReader, ReplayRecordSystem, ReplayWriter, Replicated,
+        ReplicationClient, ReplicationClientBundle, ReplicationEvent, ReplicationMessage,
+        ReplicationPeer, ReplicationServer, ReplicationServerBundle, ReplicationState, Rewound,
+        Snapshot, SnapshotApplySystem, SnapshotCaptureSystem, SnapshotInterpolationSystem,
+        SnapshotReceiveSystem, SnapshotSendSystem, SnapshotStartSystem, TransformHistory,
+        TransformHistorySystem, DEFAULT_HISTORY_WINDOW, SNAPSHOT_HISTORY,
+    },
+    rpc::{
+        Message, MessageInbox, MessageSender, Received, RpcBundle, RpcDispatchSystem, RpcEvent,
+        RpcMessage, RpcPeer, RpcReceiveSystem, RpcSendSystem,
+    },
     server::{Host, ServerConfig},
+    stats::{ConnectionStats, NetworkStats, Traffic},
+    tick::{NetworkTick, NetworkTickSystem},
+    transport::{
+        AuthToken, Delivery, EncryptedTransport, EncryptionConfig, FragmentationConfig,
+        FragmentingTransport, NetworkConditions, PackingConfig, PackingTransport,
+        SimulatedTransport, TcpTransport, Transport, TransportEvent, TransportKind, UdpTransport,
+        WebSocketTransport,
+    },
+};
+
+#[cfg(feature = "voice")]
+pub use crate::voice::{
+    VoiceBundle, VoiceCaptureSystem, VoiceChat, VoiceConfig, VoiceEvent, VoiceMessage, VoicePeer,
+    VoicePlaybackSystem, VoiceRelaySystem, VOICE_SAMPLE_RATES,
 };
 
 use std::net::SocketAddr;
 
 use bincode::{deserialize, serialize};
-use crossbeam_channel::Sender;
-use laminar::Packet;
 use log::error;
 use serde::{de::DeserializeOwned, Serialize};
 
 mod bundle;
+mod channel;
 mod connection;
+mod discovery;
 mod error;
 mod filter;
+mod lifecycle;
+mod lockstep;
+mod nat;
 mod net_event;
 mod network_socket;
+mod prediction;
+mod replication;
+mod rpc;
 mod server;
+mod stats;
 mod test;
+mod tick;
+mod transport;
+#[cfg(feature = "voice")]
+mod voice;
+
+/// Sends an event to the target NetConnection using the provided transport.
+pub fn send_event<T>(event: NetPacket<T>, addr: SocketAddr, transport: &mut dyn Transport)
+where
+    T: Serialize,
+{
+    send_net_event(&NetEvent::Packet(event), addr, transport);
+}
 
-/// Sends an event to the target NetConnection using the provided network Socket.
-/// The socket has to be bound.
-pub fn send_event<T>(event: NetPacket<T>, addr: SocketAddr, sender: &Sender<Packet>)
+/// Sends any network event, including the control ones, using the provided transport.
+///
+/// Returns the size of the payload sent, or `None` if it couldn't be sent.
+pub fn send_net_event<T>(
+    event: &NetEvent<T>,
+    addr: SocketAddr,
+    transport: &mut dyn Transport,
+) -> Option<usize>
 where
     T: Serialize,
 {
-    let ser = serialize(&event);
+    let ser = serialize(event);
     match ser {
         Ok(s) => {
-            let p = if event.is_reliable() {
-                Packet::reliable_unordered(addr, s)
-            } else {
-                Packet::unreliable(addr, s)
-            };
-
-            match sender.send(p) {
-                Ok(_qty) => {}
-                Err(e) => error!("Failed to send data to network socket: {}", e),
+            let size = s.len();
+            match transport.send(addr, s, event.delivery()) {
+                Ok(()) => Some(size),
+                Err(e) => {
+                    error!("Failed to send data to network socket: {}", e);
+                    None
+                }
             }
         }
-        Err(e) => error!("Failed to serialize the event: {}", e),
+        Err(e) => {
+            error!("Failed to serialize the event: {}", e);
+            None
+        }
     }
 }
 
 // Attempts to deserialize an event from the raw byte data.
-fn deserialize_event<T>(data: &[u8]) -> Result<NetPacket<T>>
+fn deserialize_event<T>(data: &[u8]) -> Result<NetEvent<T>>
 where
     T: DeserializeOwned,
 {
-    Ok(deserialize::<NetPacket<T>>(data)?)
+    Ok(deserialize::<NetEvent<T>>(data)?)
 }
@@ -0,0 +1,619 @@
+//! The lifecycle of the connections: handshake, keep-alive pings and timeouts.
+//!
+//! The client sends a `NetEvent::Connect` with its protocol version until the server answers
+//! with `NetEvent::Connected`, or refuses the connection when the versions differ. Once
+//! connected, both sides ping each other to measure the round trip time and notice the dead
+//! connections. The changes are written to the `EventChannel<ConnectionEvent>`.
+
+use std::{marker::PhantomData, net::SocketAddr, time::Duration};
+
+use log::warn;
+use shrev::{EventChannel, ReaderId};
+use uuid::Uuid;
+
+use amethyst_core::{
+    bundle::{BundleBuilder, BundleRequirement, SystemBundle},
+    ecs::{
+        Component, DenseVecStorage, Entities, Entity, Join, Read, Resources, System, SystemData,
+        Write, WriteStorage,
+    },
+    timing::Time,
+};
+use amethyst_error::Error;
+
+use crate::{stats::NetworkStats, ConnectionState, NetConnection, NetEvent, NetIdentity};
+
+/// The timings of the connections.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LifecycleConfig {
+    /// The version of the protocol, the server refuses the clients with another one.
+    pub protocol_version: u32,
+    /// How often the connected endpoints are pinged, and the client resends its `Connect`.
+    pub ping_interval: Duration,
+    /// How long without receiving anything before a connection is dropped.
+    pub timeout: Duration,
+    /// How long the server has to accept the connection.
+    pub handshake_timeout: Duration,
+}
+
+impl Default for LifecycleConfig {
+    fn default() -> Self {
+        LifecycleConfig {
+            protocol_version: 0,
+            ping_interval: Duration::from_secs(1),
+            timeout: Duration::from_secs(10),
+            handshake_timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+/// Which side of the connections a `ConnectionLifecycleSystem` is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConnectionRole {
+    /// Connects to the endpoints of its `NetConnection`s.
+    Client,
+    /// Accepts the connections, see `NetworkBundle::with_accept_connections`.
+    Server,
+}
+
+/// Why a connection was dropped.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DisconnectReason {
+    /// The server refused the connection.
+    Refused(String),
+    /// The protocol versions of the client and the server differ.
+    VersionMismatch {
+        /// The version of this endpoint.
+        local: u32,
+        /// The version of the remote endpoint.
+        remote: u32,
+    },
+    /// Nothing was received for too long.
+    Timeout,
+    /// The remote endpoint disconnected.
+    Remote(String),
+    /// The connection was closed with `ConnectionCommands::disconnect`.
+    Local(String),
+}
+
+/// A change of the lifecycle of a connection.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConnectionEvent {
+    /// The handshake succeeded.
+    Connected {
+        /// The entity of the `NetConnection`.
+        connection: Entity,
+        /// The address of the remote endpoint.
+        addr: SocketAddr,
+        /// The uuid of the remote endpoint.
+        uuid: Uuid,
+    },
+    /// The connection was dropped, its entity is deleted two frames later.
+    Disconnected {
+        /// The entity of the `NetConnection`.
+        connection: Entity,
+        /// The address of the remote endpoint.
+        addr: SocketAddr,
+        /// Why the connection was dropped.
+        reason: DisconnectReason,
+    },
+}
+
+/// The connections to close, closed by the `ConnectionLifecycleSystem` this frame.
+#[derive(Debug, Default)]
+pub struct ConnectionCommands {
+    disconnects: Vec<(Entity, String)>,
+}
+
+impl ConnectionCommands {
+    /// Tells the remote endpoint of the connection the reason it is disconnected, and closes it.
+    pub fn disconnect<S: Into<String>>(&mut self, connection: Entity, reason: S) {
+        self.disconnects.push((connection, reason.into()));
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Phase {
+    Handshaking,
+    Established,
+    // The last event is sent, then the connection is marked disconnected, then deleted.
+    Closing,
+    Closed,
+}
+
+/// The lifecycle of a connection, added by the `ConnectionLifecycleSystem`.
+pub struct ConnectionInfo<E: 'static> {
+    reader: ReaderId<NetEvent<E>>,
+    phase: Phase,
+    remote_uuid: Option<Uuid>,
+    rtt: Option<Duration>,
+    packet_loss: Option<f32>,
+    started: Duration,
+    last_received: Duration,
+    last_sent: Duration,
+    next_ping: u32,
+    pending_ping: Option<(u32, Duration)>,
+}
+
+impl<E: 'static> ConnectionInfo<E> {
+    /// Whether the handshake succeeded and the connection is open.
+    pub fn is_established(&self) -> bool {
+        self.phase == Phase::Established
+    }
+
+    /// The uuid of the remote endpoint, once the handshake succeeded.
+    pub fn remote_uuid(&self) -> Option<Uuid> {
+        self.remote_uuid
+    }
+
+    /// The smoothed round trip time, once a ping was answered.
+    pub fn rtt(&self) -> Option<Duration> {
+        self.rtt
+    }
+
+    /// The estimated ratio of packets lost, from the pings left unanswered.
+    pub fn packet_loss(&self) -> Option<f32> {
+        self.packet_loss
+    }
+
+    fn sample_loss(&mut self, lost: bool) {
+        let sample = if lost { 1.0 } else { 0.0 };
+        self.packet_loss = Some(match self.packet_loss {
+            Some(loss) => loss * 0.9 + sample * 0.1,
+            None => sample,
+        });
+    }
+
+    fn sample_rtt(&mut self, sample: Duration) {
+        // The usual TCP smoothing, an eighth of each new sample.
+        self.rtt = Some(match self.rtt {
+            Some(rtt) => rtt * 7 / 8 + sample / 8,
+            None => sample,
+        });
+    }
+}
+
+impl<E: Send + Sync + 'static> Component for ConnectionInfo<E> {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// Runs the handshake, the pings and the timeouts of the connections.
+pub struct ConnectionLifecycleSystem<E> {
+    role: ConnectionRole,
+    config: LifecycleConfig,
+    _marker: PhantomData<E>,
+}
+
+impl<E> ConnectionLifecycleSystem<E> {
+    /// Creates the system of one side of the connections.
+    pub fn new(role: ConnectionRole, config: LifecycleConfig) -> Self {
+        ConnectionLifecycleSystem {
+            role,
+            config,
+            _marker: PhantomData,
+        }
+    }
+
+    fn connect(&self, identity: &NetIdentity) -> NetEvent<E> {
+        NetEvent::Connect {
+            client_uuid: identity.uuid,
+            protocol_version: self.config.protocol_version,
+        }
+    }
+}
+
+impl<'a, E> System<'a> for ConnectionLifecycleSystem<E>
+where
+    E: Send + Sync + 'static,
+{
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, NetConnection<E>>,
+        WriteStorage<'a, ConnectionInfo<E>>,
+        Read<'a, Time>,
+        Read<'a, NetIdentity>,
+        Write<'a, ConnectionCommands>,
+        Write<'a, EventChannel<ConnectionEvent>>,
+        Write<'a, NetworkStats>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            mut connections,
+            mut infos,
+            time,
+            identity,
+            mut commands,
+            mut events,
+            mut stats,
+        ): Self::SystemData,
+    ) {
+        let now = time.absolute_real_time();
+
+        let new = (&*entities, &mut connections, !&infos)
+            .join()
+            .map(|(entity, connection, _)| {
+                if self.role == ConnectionRole::Client {
+                    connection.send_buffer.single_write(self.connect(&identity));
+                }
+                (entity, connection.receive_buffer.register_reader())
+            })
+            .collect::<Vec<_>>();
+        for (entity, reader) in new {
+            let info = ConnectionInfo {
+                reader,
+                phase: Phase::Handshaking,
+                remote_uuid: None,
+                rtt: None,
+                packet_loss: None,
+                started: now,
+                last_received: now,
+                last_sent: now,
+                next_ping: 0,
+                pending_ping: None,
+            };
+            infos
+                .insert(entity, info)
+                .expect("Unreachable: The entity is alive");
+        }
+
+        let disconnects = commands.disconnects.drain(..).collect::<Vec<_>>();
+        for (entity, connection, info) in (&*entities, &mut connections, &mut infos).join() {
+            match info.phase {
+                Phase::Closing => {
+                    info.phase = Phase::Closed;
+                    connection.state = ConnectionState::Disconnected;
+                    continue;
+                }
+                Phase::Closed => {
+                    if let Err(e) = entities.delete(entity) {
+                        warn!("Failed to delete a closed connection: {}", e);
+                    }
+                    continue;
+                }
+                Phase::Handshaking | Phase::Established => {}
+            }
+
+            let addr = connection.target_addr;
+            let mut outgoing = Vec::new();
+            let mut closed = None;
+            for event in connection.receive_buffer.read(&mut info.reader) {
+                info.last_received = now;
+                match event {
+                    NetEvent::Connect {
+                        client_uuid,
+                        protocol_version,
+                    } if self.role == ConnectionRole::Server => {
+                        if *protocol_version != self.config.protocol_version {
+                            outgoing.push(NetEvent::ConnectionRefused {
+                                reason: format!(
+                                    "The server uses the version {} of the protocol, not {}",
+                                    self.config.protocol_version, protocol_version
+                                ),
+                            });
+                            closed = Some(DisconnectReason::VersionMismatch {
+                                local: self.config.protocol_version,
+                                remote: *protocol_version,
+                            });
+                            break;
+                        }
+                        // The client resends its `Connect` until it receives the answer.
+                        outgoing.push(NetEvent::Connected {
+                            server_uuid: identity.uuid,
+                        });
+                        if info.phase == Phase::Handshaking {
+                            info.phase = Phase::Established;
+                            info.remote_uuid = Some(*client_uuid);
+                            events.single_write(ConnectionEvent::Connected {
+                                connection: entity,
+                                addr,
+                                uuid: *client_uuid,
+                            });
+                        }
+                    }
+                    NetEvent::Connected { server_uuid }
+                        if self.role == ConnectionRole::Client
+                            && info.phase == Phase::Handshaking =>
+                    {
+                        info.phase = Phase::Established;
+                        info.remote_uuid = Some(*server_uuid);
+                        events.single_write(ConnectionEvent::Connected {
+                            connection: entity,
+                            addr,
+                            uuid: *server_uuid,
+                        });
+                    }
+                    NetEvent::ConnectionRefused { reason } => {
+                        closed = Some(DisconnectReason::Refused(reason.clone()));
+                        break;
+                    }
+                    NetEvent::Disconnect { reason } | NetEvent::Disconnected { reason } => {
+                        closed = Some(DisconnectReason::Remote(reason.clone()));
+                        break;
+                    }
+                    NetEvent::Ping { id } => outgoing.push(NetEvent::Pong { id: *id }),
+                    NetEvent::Pong { id } => {
+                        if let Some((pending, sent)) = info.pending_ping {
+                            if pending == *id {
+                                info.sample_rtt(now - sent);
+                                info.pending_ping = None;
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+
+            if closed.is_none() {
+                if let Some((_, reason)) = disconnects.iter().find(|(e, _)| *e == entity) {
+                    outgoing.push(NetEvent::Disconnect {
+                        reason: reason.clone(),
+                    });
+                    closed = Some(DisconnectReason::Local(reason.clone()));
+                }
+            }
+            if closed.is_none() {
+                let timeout = match info.phase {
+                    Phase::Handshaking => now - info.started > self.config.handshake_timeout,
+                    _ => now - info.last_received > self.config.timeout,
+                };
+                if timeout {
+                    closed = Some(DisconnectReason::Timeout);
+                }
+            }
+
+            if closed.is_none() && now - info.last_sent >= self.config.ping_interval {
+                info.last_sent = now;
+                match info.phase {
+                    Phase::Handshaking if self.role == ConnectionRole::Client => {
+                        outgoing.push(self.connect(&identity));
+                        stats.record_resend(addr);
+                    }
+                    Phase::Established => {
+                        if info.next_ping > 0 {
+                            let lost = info.pending_ping.is_some();
+                            info.sample_loss(lost);
+                        }
+                        let id = info.next_ping;
+                        info.next_ping = info.next_ping.wrapping_add(1);
+                        info.pending_ping = Some((id, now));
+                        outgoing.push(NetEvent::Ping { id });
+                    }
+                    _ => {}
+                }
+            }
+
+            if let Some(reason) = closed {
+                info.phase = Phase::Closing;
+                events.single_write(ConnectionEvent::Disconnected {
+                    connection: entity,
+                    addr,
+                    reason,
+                });
+            } else if info.phase == Phase::Established {
+                connection.state = ConnectionState::Connected;
+                stats.set_quality(addr, info.rtt, info.packet_loss);
+            }
+            connection.send_buffer.iter_write(outgoing);
+        }
+    }
+
+    fn setup(&mut self, res: &mut Resources) {
+        Self::SystemData::setup(res);
+    }
+}
+
+/// Adds the `ConnectionLifecycleSystem` of one side of the connections.
+pub struct LifecycleBundle<E> {
+    role: ConnectionRole,
+    config: LifecycleConfig,
+    _marker: PhantomData<E>,
+}
+
+impl<E> LifecycleBundle<E> {
+    /// Connects to the endpoints of the `NetConnection`s.
+    pub fn client() -> Self {
+        LifecycleBundle::new(ConnectionRole::Client)
+    }
+
+    /// Accepts the clients, the `NetworkBundle` must accept the connections.
+    pub fn server() -> Self {
+        LifecycleBundle::new(ConnectionRole::Server)
+    }
+
+    fn new(role: ConnectionRole) -> Self {
+        LifecycleBundle {
+            role,
+            config: LifecycleConfig::default(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Sets the protocol version and the timings.
+    pub fn with_config(mut self, config: LifecycleConfig) -> Self {
+        self.config = config;
+        self
+    }
+}
+
+impl<'a, 'b, E> SystemBundle<'a, 'b> for LifecycleBundle<E>
+where
+    E: Send + Sync + 'static,
+{
+    fn build(self, builder: &mut BundleBuilder<'a, 'b>) -> Result<(), Error> {
+        builder.add(
+            ConnectionLifecycleSystem::<E>::new(self.role, self.config),
+            "connection_lifecycle",
+            &["net_socket"],
+        );
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        "LifecycleBundle"
+    }
+
+    fn requirements(&self) -> Vec<BundleRequirement> {
+        vec![BundleRequirement::new("net_socket", "NetworkBundle")]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::ecs::{Builder, RunNow, World};
+
+    use super::*;
+
+    struct Side {
+        world: World,
+        system: ConnectionLifecycleSystem<()>,
+        connection: Entity,
+        reader: ReaderId<ConnectionEvent>,
+    }
+
+    impl Side {
+        fn new(role: ConnectionRole, protocol_version: u32) -> Self {
+            let mut world = World::new();
+            let mut system = ConnectionLifecycleSystem::new(
+                role,
+                LifecycleConfig {
+                    protocol_version,
+                    ..Default::default()
+                },
+            );
+            System::setup(&mut system, &mut world.res);
+            world.add_resource(Time::default());
+            let reader = world
+                .write_resource::<EventChannel<ConnectionEvent>>()
+                .register_reader();
+            let connection = world
+                .create_entity()
+                .with(NetConnection::<()>::new("127.0.0.1:3456".parse().unwrap()))
+                .build();
+            Side {
+                world,
+                system,
+                connection,
+                reader,
+            }
+        }
+
+        fn run(&mut self, elapsed: Duration) {
+            self.world.write_resource::<Time>().set_delta_time(elapsed);
+            self.system.run_now(&self.world.res);
+            self.world.maintain();
+        }
+
+        fn sent(&mut self) -> Vec<NetEvent<()>> {
+            let mut connections = self.world.write_storage::<NetConnection<()>>();
+            connections
+                .get_mut(self.connection)
+                .map(|connection| connection.send_buffer_early_read().cloned().collect())
+                .unwrap_or_default()
+        }
+
+        fn receive(&mut self, events: Vec<NetEvent<()>>) {
+            let mut connections = self.world.write_storage::<NetConnection<()>>();
+            if let Some(connection) = connections.get_mut(self.connection) {
+                connection.receive_buffer.iter_write(events);
+            }
+        }
+
+        fn events(&mut self) -> Vec<ConnectionEvent> {
+            let channel = self.world.read_resource::<EventChannel<ConnectionEvent>>();
+            channel.read(&mut self.reader).cloned().collect()
+        }
+
+        fn state(&self) -> Option<ConnectionState> {
+            self.world
+                .read_storage::<NetConnection<()>>()
+                .get(self.connection)
+                .map(|connection| connection.state.clone())
+        }
+    }
+
+    // Runs both sides, carrying the events sent by each side to the other.
+    fn exchange(client: &mut Side, server: &mut Side, elapsed: Duration) {
+        client.run(elapsed);
+        server.run(elapsed);
+        let to_server = client.sent();
+        let to_client = server.sent();
+        server.receive(to_server);
+        client.receive(to_client);
+    }
+
+    #[test]
+    fn handshake_then_ping() {
+        let mut client = Side::new(ConnectionRole::Client, 3);
+        let mut server = Side::new(ConnectionRole::Server, 3);
+        let frame = Duration::from_millis(100);
+
+        for _ in 0..3 {
+            exchange(&mut client, &mut server, frame);
+        }
+        match &server.events()[..] {
+            [ConnectionEvent::Connected { .. }] => {}
+            events => panic!("Unexpected events: {:?}", events),
+        }
+        match &client.events()[..] {
+            [ConnectionEvent::Connected { .. }] => {}
+            events => panic!("Unexpected events: {:?}", events),
+        }
+        assert_eq!(client.state(), Some(ConnectionState::Connected));
+
+        for _ in 0..15 {
+            exchange(&mut client, &mut server, frame);
+        }
+        let infos = client.world.read_storage::<ConnectionInfo<()>>();
+        assert!(infos.get(client.connection).unwrap().rtt().is_some());
+    }
+
+    #[test]
+    fn version_mismatch_is_refused() {
+        let mut client = Side::new(ConnectionRole::Client, 1);
+        let mut server = Side::new(ConnectionRole::Server, 2);
+        let frame = Duration::from_millis(100);
+
+        for _ in 0..3 {
+            exchange(&mut client, &mut server, frame);
+        }
+        match &server.events()[..] {
+            [ConnectionEvent::Disconnected {
+                reason:
+                    DisconnectReason::VersionMismatch {
+                        local: 2,
+                        remote: 1,
+                    },
+                ..
+            }] => {}
+            events => panic!("Unexpected events: {:?}", events),
+        }
+        match &client.events()[..] {
+            [ConnectionEvent::Disconnected {
+                reason: DisconnectReason::Refused(_),
+                ..
+            }] => {}
+            events => panic!("Unexpected events: {:?}", events),
+        }
+    }
+
+    #[test]
+    fn silent_connection_times_out() {
+        let mut client = Side::new(ConnectionRole::Client, 0);
+        client.run(Duration::from_millis(100));
+        client.run(Duration::from_secs(6));
+        match &client.events()[..] {
+            [ConnectionEvent::Disconnected {
+                reason: DisconnectReason::Timeout,
+                ..
+            }] => {}
+            events => panic!("Unexpected events: {:?}", events),
+        }
+        client.run(Duration::from_millis(100));
+        assert_eq!(client.state(), Some(ConnectionState::Disconnected));
+        client.run(Duration::from_millis(100));
+        assert_eq!(client.state(), None);
+    }
+}
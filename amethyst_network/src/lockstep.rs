@@ -0,0 +1,569 @@
+//! Deterministic lockstep: the peers only exchange their inputs, and every peer runs the same
+//! simulation on the same inputs.
+//!
+//! Each frame, the `LockstepSystem` sends the input of the local player for a tick a few ticks
+//! ahead, to hide the latency, and makes the next tick available in the `LockstepSession` once
+//! the inputs of every player for it arrived. The simulation systems of the game only advance
+//! when there is such a tick, and must be deterministic, see `amethyst_core::deterministic`. To
+//! notice the simulations diverging, the game reports the hash of its state given by a
+//! `FrameHasher` after some ticks, which the peers compare, writing a `Desync` on a mismatch.
+//!
+//! The session should start once every player is connected, the inputs sent before a peer
+//! connects are not sent to it again.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    marker::PhantomData,
+};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use shrev::{EventChannel, ReaderId};
+
+use amethyst_core::{
+    bundle::{BundleBuilder, BundleRequirement, SystemBundle},
+    ecs::{
+        Component, DenseVecStorage, Entities, Join, Resources, System, SystemData, Write,
+        WriteExpect, WriteStorage,
+    },
+};
+use amethyst_error::Error;
+
+use crate::{channel::RELIABLE_CHANNEL, NetConnection, NetEvent, NetPacket};
+
+/// How many ticks the local hashes are kept, waiting for the ones of the peers.
+pub const HASH_HISTORY: u32 = 256;
+
+/// How many ticks past the input delay after the next tick to simulate the inputs of the peers
+/// are accepted, the later ones being dropped.
+pub const INPUT_WINDOW: u32 = 256;
+
+/// The messages exchanged by the peers of a lockstep session.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum LockstepMessage<I> {
+    /// The input of a player for a tick.
+    Input {
+        /// The player.
+        player: u16,
+        /// The tick the input is for.
+        tick: u32,
+        /// The input.
+        input: I,
+    },
+    /// The hash of the state of a player after a tick.
+    Hash {
+        /// The player.
+        player: u16,
+        /// The tick.
+        tick: u32,
+        /// The hash of the state.
+        hash: u64,
+    },
+}
+
+/// A network event type the lockstep messages can be sent as.
+pub trait LockstepEvent<I>: Send + Sync + 'static {
+    /// Wraps a lockstep message.
+    fn from_message(message: LockstepMessage<I>) -> Self;
+
+    /// Returns the lockstep message of the event, if it is one.
+    fn as_message(&self) -> Option<&LockstepMessage<I>>;
+}
+
+impl<I: Send + Sync + 'static> LockstepEvent<I> for LockstepMessage<I> {
+    fn from_message(message: LockstepMessage<I>) -> Self {
+        message
+    }
+
+    fn as_message(&self) -> Option<&LockstepMessage<I>> {
+        Some(self)
+    }
+}
+
+/// The lockstep state of a connection, added by the `LockstepSystem`.
+pub struct LockstepPeer<E: 'static> {
+    reader: ReaderId<NetEvent<E>>,
+}
+
+impl<E: Send + Sync + 'static> Component for LockstepPeer<E> {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// The players of a lockstep session.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LockstepConfig {
+    /// The player of this peer, between 0 and `players`.
+    pub local_player: u16,
+    /// The number of players.
+    pub players: u16,
+    /// How many ticks after it is given an input is applied. The inputs of the first ticks
+    /// are the default ones.
+    pub input_delay: u32,
+}
+
+/// The inputs of every player for a tick, to simulate.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LockstepTick<I> {
+    /// The tick.
+    pub tick: u32,
+    /// The input of each player, indexed by player.
+    pub inputs: Vec<I>,
+}
+
+/// The simulations of two peers diverged.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Desync {
+    /// The tick after which the states differ.
+    pub tick: u32,
+    /// The player whose state differs from the local one.
+    pub player: u16,
+    /// The local hash.
+    pub local: u64,
+    /// The hash of the player.
+    pub remote: u64,
+}
+
+/// The state of the lockstep session, inserted by the `LockstepSystem`.
+pub struct LockstepSession<I> {
+    config: LockstepConfig,
+    tick: u32,
+    next_local: u32,
+    input: Option<I>,
+    inputs: BTreeMap<u32, HashMap<u16, I>>,
+    current: Option<LockstepTick<I>>,
+    local_hashes: BTreeMap<u32, u64>,
+    remote_hashes: Vec<(u16, u32, u64)>,
+    unsent_hashes: Vec<(u32, u64)>,
+}
+
+impl<I: Clone + Default> LockstepSession<I> {
+    /// Starts a session at the first tick.
+    pub fn new(config: LockstepConfig) -> Self {
+        LockstepSession {
+            tick: 0,
+            next_local: config.input_delay,
+            config,
+            input: None,
+            inputs: BTreeMap::new(),
+            current: None,
+            local_hashes: BTreeMap::new(),
+            remote_hashes: Vec::new(),
+            unsent_hashes: Vec::new(),
+        }
+    }
+
+    /// The player of this peer.
+    pub fn local_player(&self) -> u16 {
+        self.config.local_player
+    }
+
+    /// Sets the input of the local player, sent for the next tick not given one yet. The
+    /// default input is sent when none is set.
+    pub fn set_input(&mut self, input: I) {
+        self.input = Some(input);
+    }
+
+    /// The tick to simulate this frame, if the inputs of every player arrived.
+    pub fn current(&self) -> Option<&LockstepTick<I>> {
+        self.current.as_ref()
+    }
+
+    /// The next tick to simulate.
+    pub fn tick(&self) -> u32 {
+        self.tick
+    }
+
+    /// The players whose input for the next tick didn't arrive.
+    pub fn waiting_for(&self) -> Vec<u16> {
+        if self.tick < self.config.input_delay {
+            return Vec::new();
+        }
+        let inputs = self.inputs.get(&self.tick);
+        (0..self.config.players)
+            .filter(|player| inputs.map_or(true, |inputs| !inputs.contains_key(player)))
+            .collect()
+    }
+
+    /// Reports the hash of the local state after a tick, compared with the ones of the peers.
+    pub fn report_hash(&mut self, tick: u32, hash: u64) {
+        self.local_hashes.insert(tick, hash);
+        self.unsent_hashes.push((tick, hash));
+    }
+
+    fn receive(&mut self, message: &LockstepMessage<I>) {
+        match message {
+            LockstepMessage::Input {
+                player,
+                tick,
+                input,
+            } => {
+                // The local inputs are only given by this peer, and the first input of a player
+                // for a tick is final.
+                let last = self
+                    .tick
+                    .saturating_add(self.config.input_delay + INPUT_WINDOW);
+                if *tick >= self.tick
+                    && *tick <= last
+                    && *player < self.config.players
+                    && *player != self.config.local_player
+                {
+                    self.inputs
+                        .entry(*tick)
+                        .or_insert_with(HashMap::new)
+                        .entry(*player)
+                        .or_insert_with(|| input.clone());
+                }
+            }
+            LockstepMessage::Hash { player, tick, hash } => {
+                self.remote_hashes.push((*player, *tick, *hash))
+            }
+        }
+    }
+
+    // Schedules the local input for the next tick not given one, if not too far ahead.
+    fn schedule(&mut self) -> Option<LockstepMessage<I>> {
+        if self.next_local > self.tick + self.config.input_delay {
+            return None;
+        }
+        let tick = self.next_local;
+        self.next_local += 1;
+        let input = self.input.take().unwrap_or_default();
+        let player = self.config.local_player;
+        self.inputs
+            .entry(tick)
+            .or_insert_with(HashMap::new)
+            .insert(player, input.clone());
+        Some(LockstepMessage::Input {
+            player,
+            tick,
+            input,
+        })
+    }
+
+    fn advance(&mut self) {
+        self.current = None;
+        let inputs = if self.tick < self.config.input_delay {
+            vec![I::default(); usize::from(self.config.players)]
+        } else if self.waiting_for().is_empty() {
+            let mut inputs = self
+                .inputs
+                .remove(&self.tick)
+                .expect("Unreachable: No input is missing");
+            (0..self.config.players)
+                .map(|player| {
+                    inputs
+                        .remove(&player)
+                        .expect("Unreachable: No input is missing")
+                })
+                .collect()
+        } else {
+            return;
+        };
+        self.current = Some(LockstepTick {
+            tick: self.tick,
+            inputs,
+        });
+        self.tick += 1;
+    }
+
+    fn compare_hashes(&mut self, desyncs: &mut EventChannel<Desync>) {
+        let oldest = self.tick.saturating_sub(HASH_HISTORY);
+        let local_hashes = &self.local_hashes;
+        self.remote_hashes.retain(|&(player, tick, remote)| {
+            if tick < oldest {
+                return false;
+            }
+            match local_hashes.get(&tick) {
+                Some(&local) => {
+                    if local != remote {
+                        desyncs.single_write(Desync {
+                            tick,
+                            player,
+                            local,
+                            remote,
+                        });
+                    }
+                    false
+                }
+                None => true,
+            }
+        });
+        self.local_hashes = self.local_hashes.split_off(&oldest);
+    }
+}
+
+/// Exchanges the inputs and the hashes with the peers, and advances the `LockstepSession`.
+pub struct LockstepSystem<I, E> {
+    config: LockstepConfig,
+    _marker: PhantomData<(I, E)>,
+}
+
+impl<I, E> LockstepSystem<I, E> {
+    /// Creates the system of a session with the given players.
+    pub fn new(config: LockstepConfig) -> Self {
+        LockstepSystem {
+            config,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, I, E> System<'a> for LockstepSystem<I, E>
+where
+    I: Clone + Default + Send + Sync + 'static,
+    E: LockstepEvent<I>,
+{
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, NetConnection<E>>,
+        WriteStorage<'a, LockstepPeer<E>>,
+        WriteExpect<'a, LockstepSession<I>>,
+        Write<'a, EventChannel<Desync>>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, mut connections, mut peers, mut session, mut desyncs): Self::SystemData,
+    ) {
+        let new = (&*entities, &mut connections, !&peers)
+            .join()
+            .map(|(entity, connection, _)| (entity, connection.receive_buffer.register_reader()))
+            .collect::<Vec<_>>();
+        for (entity, reader) in new {
+            peers
+                .insert(entity, LockstepPeer { reader })
+                .expect("Unreachable: The entity is alive");
+        }
+
+        for (connection, peer) in (&mut connections, &mut peers).join() {
+            for event in connection.receive_buffer.read(&mut peer.reader) {
+                if let NetEvent::Packet(packet) = event {
+                    if let Some(message) = packet.content().as_message() {
+                        session.receive(message);
+                    }
+                }
+            }
+        }
+
+        let player = session.config.local_player;
+        let mut messages = session.schedule().into_iter().collect::<Vec<_>>();
+        messages.extend(
+            session
+                .unsent_hashes
+                .drain(..)
+                .map(|(tick, hash)| LockstepMessage::Hash { player, tick, hash }),
+        );
+        for connection in (&mut connections).join() {
+            for message in &messages {
+                connection
+                    .send_buffer
+                    .single_write(NetEvent::Packet(NetPacket::on_channel(
+                        E::from_message(message.clone()),
+                        RELIABLE_CHANNEL,
+                    )));
+            }
+        }
+
+        session.advance();
+        session.compare_hashes(&mut desyncs);
+    }
+
+    fn setup(&mut self, res: &mut Resources) {
+        res.insert(LockstepSession::<I>::new(self.config.clone()));
+        Self::SystemData::setup(res);
+    }
+}
+
+/// Adds the `LockstepSystem`, the simulation systems of the game should depend on
+/// "lockstep".
+pub struct LockstepBundle<I, E> {
+    config: LockstepConfig,
+    _marker: PhantomData<(I, E)>,
+}
+
+impl<I, E> LockstepBundle<I, E> {
+    /// A session with the given players.
+    pub fn new(config: LockstepConfig) -> Self {
+        LockstepBundle {
+            config,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, 'b, I, E> SystemBundle<'a, 'b> for LockstepBundle<I, E>
+where
+    I: Clone + Default + Send + Sync + 'static,
+    E: LockstepEvent<I>,
+{
+    fn build(self, builder: &mut BundleBuilder<'a, 'b>) -> Result<(), Error> {
+        builder.add(
+            LockstepSystem::<I, E>::new(self.config),
+            "lockstep",
+            &["net_socket"],
+        );
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        "LockstepBundle"
+    }
+
+    fn requirements(&self) -> Vec<BundleRequirement> {
+        vec![BundleRequirement::new("net_socket", "NetworkBundle")]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::ecs::{Builder, Entity, RunNow, World};
+
+    use super::*;
+
+    type Message = LockstepMessage<u8>;
+
+    struct Peer {
+        world: World,
+        system: LockstepSystem<u8, Message>,
+        connection: Entity,
+    }
+
+    impl Peer {
+        fn new(local_player: u16) -> Self {
+            let mut world = World::new();
+            let mut system = LockstepSystem::new(LockstepConfig {
+                local_player,
+                players: 2,
+                input_delay: 1,
+            });
+            System::setup(&mut system, &mut world.res);
+            let connection = world
+                .create_entity()
+                .with(NetConnection::<Message>::new(
+                    "127.0.0.1:3456".parse().unwrap(),
+                ))
+                .build();
+            Peer {
+                world,
+                system,
+                connection,
+            }
+        }
+
+        fn run(&mut self) -> Option<LockstepTick<u8>> {
+            self.system.run_now(&self.world.res);
+            self.world
+                .read_resource::<LockstepSession<u8>>()
+                .current()
+                .cloned()
+        }
+
+        // Delivers the events sent by this peer to the other.
+        fn deliver(&mut self, other: &mut Peer) {
+            let events = self
+                .world
+                .write_storage::<NetConnection<Message>>()
+                .get_mut(self.connection)
+                .unwrap()
+                .send_buffer_early_read()
+                .cloned()
+                .collect::<Vec<_>>();
+            other
+                .world
+                .write_storage::<NetConnection<Message>>()
+                .get_mut(other.connection)
+                .unwrap()
+                .receive_buffer
+                .iter_write(events);
+        }
+    }
+
+    #[test]
+    fn ticks_wait_for_every_input() {
+        let mut a = Peer::new(0);
+        let mut b = Peer::new(1);
+        // The readers are registered on the first run, whose tick has no input to wait for.
+        assert_eq!(a.run().map(|tick| tick.tick), Some(0));
+        assert_eq!(b.run().map(|tick| tick.tick), Some(0));
+
+        a.world.write_resource::<LockstepSession<u8>>().set_input(7);
+        a.deliver(&mut b);
+        assert_eq!(a.run(), None);
+        assert_eq!(
+            a.world.read_resource::<LockstepSession<u8>>().waiting_for(),
+            vec![1]
+        );
+
+        b.world.write_resource::<LockstepSession<u8>>().set_input(9);
+        b.deliver(&mut a);
+        a.deliver(&mut b);
+        let tick = LockstepTick {
+            tick: 1,
+            inputs: vec![0, 0],
+        };
+        assert_eq!(b.run(), Some(tick.clone()));
+        b.deliver(&mut a);
+        assert_eq!(a.run(), Some(tick));
+        a.deliver(&mut b);
+        b.deliver(&mut a);
+        assert_eq!(a.run().map(|tick| tick.inputs), Some(vec![7, 9]));
+    }
+
+    #[test]
+    fn different_hashes_are_desyncs() {
+        let mut a = Peer::new(0);
+        let mut b = Peer::new(1);
+        let mut reader = a
+            .world
+            .write_resource::<EventChannel<Desync>>()
+            .register_reader();
+        a.run();
+        b.run();
+        a.world
+            .write_resource::<LockstepSession<u8>>()
+            .report_hash(0, 1);
+        b.world
+            .write_resource::<LockstepSession<u8>>()
+            .report_hash(0, 2);
+        b.run();
+        b.deliver(&mut a);
+        a.run();
+        let desyncs = a
+            .world
+            .read_resource::<EventChannel<Desync>>()
+            .read(&mut reader)
+            .cloned()
+            .collect::<Vec<_>>();
+        assert_eq!(
+            desyncs,
+            vec![Desync {
+                tick: 0,
+                player: 1,
+                local: 1,
+                remote: 2,
+            }]
+        );
+    }
+
+    #[test]
+    fn forged_duplicate_and_distant_inputs_are_ignored() {
+        let mut session = LockstepSession::<u8>::new(LockstepConfig {
+            local_player: 0,
+            players: 2,
+            input_delay: 1,
+        });
+        let input = |player, tick, input| Message::Input {
+            player,
+            tick,
+            input,
+        };
+        session.receive(&input(0, 1, 5));
+        session.receive(&input(1, 1, 3));
+        session.receive(&input(1, 1, 4));
+        session.receive(&input(1, 2 + INPUT_WINDOW, 6));
+        session.receive(&input(2, 1, 7));
+        assert_eq!(session.inputs.len(), 1);
+        assert_eq!(session.inputs[&1].get(&0), None);
+        assert_eq!(session.inputs[&1].get(&1), Some(&3));
+        assert_eq!(session.inputs[&1].len(), 1);
+    }
+}
@@ -0,0 +1,896 @@
+//! NAT traversal for the peer-to-peer sessions, by hole punching with a relay fallback.
+//!
+//! Both peers connect to a rendezvous server reachable by everyone, the `NetConnection` of
+//! which has a `Rendezvous` component naming the session. Once both peers registered, the
+//! server introduces them to each other, and each peer creates a `NetConnection` to the public
+//! address of the other and punches through its NAT by sending to it. When no punch arrives in
+//! time, the events of the peer connection are relayed by the rendezvous server instead, which
+//! the rest of the game doesn't notice.
+
+use std::{collections::HashMap, marker::PhantomData, net::SocketAddr, time::Duration};
+
+use bincode::{deserialize, serialize};
+use log::{error, warn};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use shrev::{EventChannel, ReaderId};
+
+use amethyst_core::{
+    bundle::{BundleBuilder, BundleRequirement, SystemBundle},
+    ecs::{
+        Component, DenseVecStorage, Entities, Entity, Join, Read, Resources, System, SystemData,
+        Write, WriteStorage,
+    },
+    timing::Time,
+};
+use amethyst_error::Error;
+
+use crate::{
+    channel::{RELIABLE_CHANNEL, UNRELIABLE_CHANNEL},
+    ConnectionState, NetConnection, NetEvent, NetPacket,
+};
+
+/// The messages of the NAT traversal.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NatMessage {
+    /// Asks the rendezvous server for the other peer of a session.
+    Register {
+        /// The session to join.
+        session: String,
+    },
+    /// Sent by the rendezvous server with the public address of the other peer.
+    Introduce {
+        /// The address to punch.
+        addr: SocketAddr,
+    },
+    /// Sent between the peers to open their NATs, answered with an acknowledged punch.
+    Punch {
+        /// Whether this punch answers one received.
+        acknowledged: bool,
+    },
+    /// A serialized event sent through the rendezvous server.
+    Relay {
+        /// The peer the event is for, or came from once relayed.
+        peer: SocketAddr,
+        /// The serialized `NetEvent`.
+        payload: Vec<u8>,
+    },
+}
+
+/// A network event type the NAT traversal messages can be sent as.
+pub trait NatEvent: Send + Sync + 'static {
+    /// Wraps a message.
+    fn from_message(message: NatMessage) -> Self;
+
+    /// Returns the message of the event, if it is one.
+    fn as_message(&self) -> Option<&NatMessage>;
+}
+
+impl NatEvent for NatMessage {
+    fn from_message(message: NatMessage) -> Self {
+        message
+    }
+
+    fn as_message(&self) -> Option<&NatMessage> {
+        Some(self)
+    }
+}
+
+fn send<E: NatEvent>(connection: &mut NetConnection<E>, message: NatMessage, channel: u8) {
+    connection
+        .send_buffer
+        .single_write(NetEvent::Packet(NetPacket::on_channel(
+            E::from_message(message),
+            channel,
+        )));
+}
+
+/// The timings of the NAT traversal, and the limits of the rendezvous server.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NatConfig {
+    /// How often the registration is sent until the rendezvous server answers.
+    pub register_interval: Duration,
+    /// How often a peer is punched until it answers.
+    pub punch_interval: Duration,
+    /// How long to punch before relaying the events.
+    pub punch_timeout: Duration,
+    /// How long the rendezvous server keeps a registration the peer doesn't send again while
+    /// waiting for the other peer of its session.
+    pub registration_timeout: Duration,
+    /// The most registrations the rendezvous server keeps waiting, the new sessions being
+    /// refused until the others are met or expire.
+    pub max_waiting: usize,
+}
+
+impl Default for NatConfig {
+    fn default() -> Self {
+        NatConfig {
+            register_interval: Duration::from_secs(1),
+            punch_interval: Duration::from_millis(100),
+            punch_timeout: Duration::from_secs(3),
+            registration_timeout: Duration::from_secs(30),
+            max_waiting: 1024,
+        }
+    }
+}
+
+/// Marks the `NetConnection` to a rendezvous server, to meet the other peer of a session.
+#[derive(Clone, Debug)]
+pub struct Rendezvous {
+    /// The session to join, shared by both peers.
+    pub session: String,
+    introduced: bool,
+    last_sent: Option<Duration>,
+}
+
+impl Rendezvous {
+    /// Joins the given session.
+    pub fn new(session: impl Into<String>) -> Self {
+        Rendezvous {
+            session: session.into(),
+            introduced: false,
+            last_sent: None,
+        }
+    }
+
+    /// Whether the rendezvous server introduced the other peer.
+    pub fn is_introduced(&self) -> bool {
+        self.introduced
+    }
+}
+
+impl Component for Rendezvous {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// The NAT traversal state of a connection to a rendezvous server or one of its peers, added
+/// by the NAT systems.
+pub struct RendezvousPeer<E: 'static> {
+    reader: ReaderId<NetEvent<E>>,
+}
+
+impl<E: Send + Sync + 'static> Component for RendezvousPeer<E> {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// How the events reach a peer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NatStatus {
+    /// Punching through the NATs.
+    Punching,
+    /// Directly, the NATs were punched.
+    Direct,
+    /// Through the rendezvous server, the NATs couldn't be punched.
+    Relayed,
+}
+
+/// Something that happened to a peer.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum NatTraversalEvent {
+    /// The NATs were punched, the events are sent directly.
+    Punched {
+        /// The entity of the `NetConnection` to the peer.
+        peer: Entity,
+        /// The public address of the peer.
+        addr: SocketAddr,
+    },
+    /// The NATs couldn't be punched, the events are relayed.
+    Relayed {
+        /// The entity of the `NetConnection` to the peer.
+        peer: Entity,
+        /// The public address of the peer.
+        addr: SocketAddr,
+    },
+}
+
+/// The `NetConnection` to a peer met through a rendezvous server, added by the
+/// `NatTraversalSystem`.
+pub struct NatPeer<E: 'static> {
+    rendezvous: Entity,
+    addr: SocketAddr,
+    status: NatStatus,
+    started: Duration,
+    last_punch: Option<Duration>,
+    relay_reader: ReaderId<NetEvent<E>>,
+}
+
+impl<E: 'static> NatPeer<E> {
+    /// The public address of the peer.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// How the events reach the peer.
+    pub fn status(&self) -> NatStatus {
+        self.status
+    }
+}
+
+impl<E: Send + Sync + 'static> Component for NatPeer<E> {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// Meets the peers through the `Rendezvous` connections and punches through their NATs,
+/// creating a `NetConnection` for each of them and writing the outcome to the
+/// `EventChannel<NatTraversalEvent>`.
+pub struct NatTraversalSystem<E> {
+    config: NatConfig,
+    _marker: PhantomData<E>,
+}
+
+impl<E> NatTraversalSystem<E> {
+    /// Creates the system with the given timings.
+    pub fn new(config: NatConfig) -> Self {
+        NatTraversalSystem {
+            config,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, E> System<'a> for NatTraversalSystem<E>
+where
+    E: NatEvent + Serialize + DeserializeOwned,
+{
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, Time>,
+        WriteStorage<'a, NetConnection<E>>,
+        WriteStorage<'a, Rendezvous>,
+        WriteStorage<'a, RendezvousPeer<E>>,
+        WriteStorage<'a, NatPeer<E>>,
+        Write<'a, EventChannel<NatTraversalEvent>>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            time,
+            mut connections,
+            mut rendezvous_connections,
+            mut peers,
+            mut nat_peers,
+            mut events,
+        ): Self::SystemData,
+    ) {
+        let now = time.absolute_real_time();
+        let new = (
+            &*entities,
+            &mut connections,
+            &rendezvous_connections,
+            !&peers,
+        )
+            .join()
+            .map(|(entity, connection, _, _)| (entity, connection.receive_buffer.register_reader()))
+            .collect::<Vec<_>>();
+        for (entity, reader) in new {
+            peers
+                .insert(entity, RendezvousPeer { reader })
+                .expect("Unreachable: The entity is alive");
+        }
+
+        let mut introductions = Vec::new();
+        let mut relayed = Vec::new();
+        for (entity, connection, rendezvous, peer) in (
+            &*entities,
+            &mut connections,
+            &mut rendezvous_connections,
+            &mut peers,
+        )
+            .join()
+        {
+            for event in connection.receive_buffer.read(&mut peer.reader) {
+                if let NetEvent::Packet(packet) = event {
+                    match packet.content().as_message() {
+                        Some(NatMessage::Introduce { addr }) if !rendezvous.introduced => {
+                            rendezvous.introduced = true;
+                            introductions.push((entity, *addr));
+                        }
+                        Some(NatMessage::Relay { peer, payload }) => {
+                            relayed.push((entity, *peer, payload.clone()))
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            let due = rendezvous.last_sent.map_or(true, |last_sent| {
+                now - last_sent >= self.config.register_interval
+            });
+            if !rendezvous.introduced && due {
+                rendezvous.last_sent = Some(now);
+                let session = rendezvous.session.clone();
+                send(
+                    connection,
+                    NatMessage::Register { session },
+                    RELIABLE_CHANNEL,
+                );
+            }
+        }
+
+        for (rendezvous, addr) in introductions {
+            let mut connection = NetConnection::<E>::new(addr);
+            let reader = connection.receive_buffer.register_reader();
+            let relay_reader = connection.send_buffer.register_reader();
+            let peer = entities.create();
+            connections
+                .insert(peer, connection)
+                .expect("Unreachable: The entity was just created");
+            peers
+                .insert(peer, RendezvousPeer { reader })
+                .expect("Unreachable: The entity was just created");
+            nat_peers
+                .insert(
+                    peer,
+                    NatPeer {
+                        rendezvous,
+                        addr,
+                        status: NatStatus::Punching,
+                        started: now,
+                        last_punch: None,
+                        relay_reader,
+                    },
+                )
+                .expect("Unreachable: The entity was just created");
+        }
+
+        let mut outgoing = Vec::new();
+        for (entity, connection, peer, nat_peer) in
+            (&*entities, &mut connections, &mut peers, &mut nat_peers).join()
+        {
+            let mut punched = false;
+            let mut answer = false;
+            for event in connection.receive_buffer.read(&mut peer.reader) {
+                if let NetEvent::Packet(packet) = event {
+                    if let Some(NatMessage::Punch { acknowledged }) = packet.content().as_message()
+                    {
+                        punched = true;
+                        answer |= !acknowledged;
+                    }
+                }
+            }
+            if answer {
+                let punch = NatMessage::Punch { acknowledged: true };
+                send(connection, punch, UNRELIABLE_CHANNEL);
+            }
+
+            let addr = nat_peer.addr;
+            if nat_peer.status != NatStatus::Direct && punched {
+                nat_peer.status = NatStatus::Direct;
+                connection.state = ConnectionState::Connected;
+                events.single_write(NatTraversalEvent::Punched { peer: entity, addr });
+            } else if nat_peer.status == NatStatus::Punching {
+                if now - nat_peer.started >= self.config.punch_timeout {
+                    nat_peer.status = NatStatus::Relayed;
+                    connection.state = ConnectionState::Connected;
+                    events.single_write(NatTraversalEvent::Relayed { peer: entity, addr });
+                } else if nat_peer.last_punch.map_or(true, |last_punch| {
+                    now - last_punch >= self.config.punch_interval
+                }) {
+                    nat_peer.last_punch = Some(now);
+                    let punch = NatMessage::Punch {
+                        acknowledged: false,
+                    };
+                    send(connection, punch, UNRELIABLE_CHANNEL);
+                }
+            }
+
+            // The punches are never relayed, so receiving one means the NATs were punched.
+            for event in connection.send_buffer.read(&mut nat_peer.relay_reader) {
+                let punch = match event {
+                    NetEvent::Packet(packet) => packet.content().as_message().is_some(),
+                    _ => false,
+                };
+                if nat_peer.status == NatStatus::Relayed && !punch {
+                    match serialize(event) {
+                        Ok(payload) => outgoing.push((nat_peer.rendezvous, addr, payload)),
+                        Err(e) => error!("Failed to serialize a relayed event: {}", e),
+                    }
+                }
+            }
+        }
+
+        for (rendezvous, peer, payload) in outgoing {
+            match connections.get_mut(rendezvous) {
+                Some(connection) => send(
+                    connection,
+                    NatMessage::Relay { peer, payload },
+                    RELIABLE_CHANNEL,
+                ),
+                None => warn!("Dropping an event relayed to {}", peer),
+            }
+        }
+        for (rendezvous, addr, payload) in relayed {
+            let event = match deserialize::<NetEvent<E>>(&payload) {
+                Ok(event) => event,
+                Err(e) => {
+                    warn!("Dropping an invalid event relayed from {}: {}", addr, e);
+                    continue;
+                }
+            };
+            let peer = (&mut connections, &nat_peers)
+                .join()
+                .find(|(_, nat_peer)| nat_peer.rendezvous == rendezvous && nat_peer.addr == addr);
+            match peer {
+                Some((connection, _)) => connection.receive_buffer.single_write(event),
+                None => warn!("Dropping an event relayed from the unknown peer {}", addr),
+            }
+        }
+    }
+
+    fn setup(&mut self, res: &mut Resources) {
+        Self::SystemData::setup(res);
+    }
+}
+
+// A peer registered with a session, waiting for the other one.
+struct Waiting {
+    entity: Entity,
+    addr: SocketAddr,
+    since: Duration,
+}
+
+/// Introduces the peers registering with the same session to each other, and relays the events
+/// of the peers that couldn't punch through their NATs.
+///
+/// The events are only relayed between the two peers of a session, so that the server can't be
+/// used to send to anyone else. A peer waits for the other one of its session for the
+/// `registration_timeout` of the `NatConfig`, registering again to keep waiting.
+pub struct RendezvousServerSystem<E> {
+    config: NatConfig,
+    waiting: HashMap<String, Waiting>,
+    // The other peer of the session of each introduced peer.
+    pairs: HashMap<Entity, Entity>,
+    _marker: PhantomData<E>,
+}
+
+impl<E> RendezvousServerSystem<E> {
+    /// Creates the system with the given limits.
+    pub fn new(config: NatConfig) -> Self {
+        RendezvousServerSystem {
+            config,
+            waiting: HashMap::new(),
+            pairs: HashMap::new(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<E> Default for RendezvousServerSystem<E> {
+    fn default() -> Self {
+        RendezvousServerSystem::new(NatConfig::default())
+    }
+}
+
+impl<'a, E: NatEvent> System<'a> for RendezvousServerSystem<E> {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, Time>,
+        WriteStorage<'a, NetConnection<E>>,
+        WriteStorage<'a, RendezvousPeer<E>>,
+    );
+
+    fn run(&mut self, (entities, time, mut connections, mut peers): Self::SystemData) {
+        let now = time.absolute_real_time();
+        let timeout = self.config.registration_timeout;
+        self.waiting.retain(|_, waiting| {
+            now - waiting.since < timeout && connections.get(waiting.entity).is_some()
+        });
+        self.pairs.retain(|entity, other| {
+            connections.get(*entity).is_some() && connections.get(*other).is_some()
+        });
+
+        let new = (&*entities, &mut connections, !&peers)
+            .join()
+            .map(|(entity, connection, _)| (entity, connection.receive_buffer.register_reader()))
+            .collect::<Vec<_>>();
+        for (entity, reader) in new {
+            peers
+                .insert(entity, RendezvousPeer { reader })
+                .expect("Unreachable: The entity is alive");
+        }
+
+        let mut registrations = Vec::new();
+        let mut relays = Vec::new();
+        for (entity, connection, peer) in (&*entities, &mut connections, &mut peers).join() {
+            let addr = connection.target_addr;
+            for event in connection.receive_buffer.read(&mut peer.reader) {
+                if let NetEvent::Packet(packet) = event {
+                    match packet.content().as_message() {
+                        Some(NatMessage::Register { session }) => {
+                            registrations.push((entity, addr, session.clone()))
+                        }
+                        Some(NatMessage::Relay { peer, payload }) => {
+                            relays.push((entity, addr, *peer, payload.clone()))
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        for (entity, addr, session) in registrations {
+            let other = self
+                .waiting
+                .get(&session)
+                .map(|waiting| (waiting.entity, waiting.addr));
+            match other {
+                Some((other, _)) if other == entity => {
+                    if let Some(waiting) = self.waiting.get_mut(&session) {
+                        waiting.since = now;
+                    }
+                }
+                Some((other, other_addr)) => {
+                    self.waiting.remove(&session);
+                    self.waiting.retain(|_, waiting| waiting.entity != entity);
+                    self.pairs.insert(entity, other);
+                    self.pairs.insert(other, entity);
+                    let introduce = |addr| NatMessage::Introduce { addr };
+                    if let Some(connection) = connections.get_mut(entity) {
+                        send(connection, introduce(other_addr), RELIABLE_CHANNEL);
+                    }
+                    if let Some(connection) = connections.get_mut(other) {
+                        send(connection, introduce(addr), RELIABLE_CHANNEL);
+                    }
+                }
+                None => {
+                    // A peer waits in a single session.
+                    self.waiting.retain(|_, waiting| waiting.entity != entity);
+                    if self.waiting.len() >= self.config.max_waiting {
+                        warn!(
+                            "Refusing the registration of {}, too many are waiting",
+                            addr
+                        );
+                        continue;
+                    }
+                    let since = now;
+                    self.waiting.insert(
+                        session,
+                        Waiting {
+                            entity,
+                            addr,
+                            since,
+                        },
+                    );
+                }
+            }
+        }
+
+        for (entity, from, to, payload) in relays {
+            let other = self
+                .pairs
+                .get(&entity)
+                .and_then(|&other| connections.get_mut(other))
+                .filter(|connection| connection.target_addr == to);
+            match other {
+                Some(connection) => send(
+                    connection,
+                    NatMessage::Relay {
+                        peer: from,
+                        payload,
+                    },
+                    RELIABLE_CHANNEL,
+                ),
+                None => warn!("Dropping an event relayed from {} to {}", from, to),
+            }
+        }
+    }
+
+    fn setup(&mut self, res: &mut Resources) {
+        Self::SystemData::setup(res);
+    }
+}
+
+/// Adds the NAT traversal system of a peer, or the rendezvous server.
+pub struct NatBundle<E> {
+    server: bool,
+    config: NatConfig,
+    _marker: PhantomData<E>,
+}
+
+impl<E> NatBundle<E> {
+    /// Meets the other peers through the `Rendezvous` connections.
+    pub fn peer() -> Self {
+        NatBundle::new(false)
+    }
+
+    /// Introduces the peers to each other, the `NetworkBundle` must accept the connections.
+    pub fn rendezvous_server() -> Self {
+        NatBundle::new(true)
+    }
+
+    fn new(server: bool) -> Self {
+        NatBundle {
+            server,
+            config: NatConfig::default(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Sets the timings of the peer, or the limits of the rendezvous server.
+    pub fn with_config(mut self, config: NatConfig) -> Self {
+        self.config = config;
+        self
+    }
+}
+
+impl<'a, 'b, E> SystemBundle<'a, 'b> for NatBundle<E>
+where
+    E: NatEvent + Serialize + DeserializeOwned,
+{
+    fn build(self, builder: &mut BundleBuilder<'a, 'b>) -> Result<(), Error> {
+        if self.server {
+            builder.add(
+                RendezvousServerSystem::<E>::new(self.config),
+                "rendezvous_server",
+                &["net_socket"],
+            );
+        } else {
+            builder.add(
+                NatTraversalSystem::<E>::new(self.config),
+                "nat_traversal",
+                &["net_socket"],
+            );
+        }
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        "NatBundle"
+    }
+
+    fn requirements(&self) -> Vec<BundleRequirement> {
+        vec![BundleRequirement::new("net_socket", "NetworkBundle")]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::ecs::{Builder, RunNow, World};
+
+    use super::*;
+
+    // The events of a game sending its own events besides the NAT messages.
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    enum GameEvent {
+        Nat(NatMessage),
+        Score(u32),
+    }
+
+    impl NatEvent for GameEvent {
+        fn from_message(message: NatMessage) -> Self {
+            GameEvent::Nat(message)
+        }
+
+        fn as_message(&self) -> Option<&NatMessage> {
+            match self {
+                GameEvent::Nat(message) => Some(message),
+                GameEvent::Score(_) => None,
+            }
+        }
+    }
+
+    fn receive<E: NatEvent>(world: &mut World, entity: Entity, event: E) {
+        world
+            .write_storage::<NetConnection<E>>()
+            .get_mut(entity)
+            .unwrap()
+            .receive_buffer
+            .single_write(NetEvent::Packet(NetPacket::reliable_ordered(event, None)));
+    }
+
+    fn sent<E: NatEvent + Clone>(world: &mut World, entity: Entity) -> Vec<E> {
+        world
+            .write_storage::<NetConnection<E>>()
+            .get_mut(entity)
+            .unwrap()
+            .send_buffer_early_read()
+            .filter_map(|event| match event {
+                NetEvent::Packet(packet) => Some(packet.content().clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn connection(world: &mut World, addr: &str) -> (Entity, SocketAddr) {
+        let addr = addr.parse().unwrap();
+        let entity = world
+            .create_entity()
+            .with(NetConnection::<NatMessage>::new(addr))
+            .build();
+        (entity, addr)
+    }
+
+    fn register(session: &str) -> NatMessage {
+        NatMessage::Register {
+            session: session.to_string(),
+        }
+    }
+
+    #[test]
+    fn peers_are_introduced_and_relayed() {
+        let mut world = World::new();
+        let mut system = RendezvousServerSystem::<NatMessage>::default();
+        System::setup(&mut system, &mut world.res);
+        let (a, a_addr) = connection(&mut world, "1.2.3.4:5000");
+        let (b, b_addr) = connection(&mut world, "5.6.7.8:6000");
+        system.run_now(&world.res);
+
+        let register = || register("game");
+        receive(&mut world, a, register());
+        system.run_now(&world.res);
+        assert!(sent::<NatMessage>(&mut world, a).is_empty());
+        receive(&mut world, b, register());
+        system.run_now(&world.res);
+        assert_eq!(
+            sent(&mut world, a),
+            vec![NatMessage::Introduce { addr: b_addr }]
+        );
+        assert_eq!(
+            sent(&mut world, b),
+            vec![NatMessage::Introduce { addr: a_addr }]
+        );
+
+        receive(
+            &mut world,
+            a,
+            NatMessage::Relay {
+                peer: b_addr,
+                payload: vec![1, 2, 3],
+            },
+        );
+        system.run_now(&world.res);
+        assert_eq!(
+            sent(&mut world, b),
+            vec![NatMessage::Relay {
+                peer: a_addr,
+                payload: vec![1, 2, 3],
+            }]
+        );
+    }
+
+    #[test]
+    fn events_are_only_relayed_within_a_session() {
+        let mut world = World::new();
+        let mut system = RendezvousServerSystem::<NatMessage>::default();
+        System::setup(&mut system, &mut world.res);
+        let (a, _) = connection(&mut world, "1.2.3.4:5000");
+        let (b, b_addr) = connection(&mut world, "5.6.7.8:6000");
+        let (victim, victim_addr) = connection(&mut world, "9.9.9.9:7000");
+        system.run_now(&world.res);
+        receive(&mut world, a, register("game"));
+        receive(&mut world, b, register("game"));
+        system.run_now(&world.res);
+        sent::<NatMessage>(&mut world, a);
+        sent::<NatMessage>(&mut world, b);
+
+        let relay = |peer| NatMessage::Relay {
+            peer,
+            payload: vec![1],
+        };
+        receive(&mut world, a, relay(victim_addr));
+        receive(&mut world, victim, relay(b_addr));
+        system.run_now(&world.res);
+        assert!(sent::<NatMessage>(&mut world, victim).is_empty());
+        assert!(sent::<NatMessage>(&mut world, b).is_empty());
+    }
+
+    #[test]
+    fn waiting_registrations_expire_and_are_bounded() {
+        let mut world = World::new();
+        let mut system = RendezvousServerSystem::<NatMessage>::new(NatConfig {
+            registration_timeout: Duration::from_secs(1),
+            max_waiting: 1,
+            ..NatConfig::default()
+        });
+        System::setup(&mut system, &mut world.res);
+        let (a, _) = connection(&mut world, "1.2.3.4:5000");
+        let (b, _) = connection(&mut world, "5.6.7.8:6000");
+        let (c, _) = connection(&mut world, "9.9.9.9:7000");
+        system.run_now(&world.res);
+
+        receive(&mut world, a, register("first"));
+        receive(&mut world, b, register("second"));
+        system.run_now(&world.res);
+        assert_eq!(system.waiting.len(), 1);
+        assert!(system.waiting.contains_key("first"));
+
+        world.write_resource::<Time>().set_delta_seconds(2.0);
+        receive(&mut world, b, register("second"));
+        receive(&mut world, c, register("first"));
+        system.run_now(&world.res);
+        assert!(sent::<NatMessage>(&mut world, a).is_empty());
+        assert!(sent::<NatMessage>(&mut world, c).is_empty());
+        assert_eq!(system.waiting.len(), 1);
+        assert!(system.waiting.contains_key("second"));
+    }
+
+    #[test]
+    fn peers_punch_through_or_relay() {
+        let mut world = World::new();
+        let mut system = NatTraversalSystem::<GameEvent>::new(NatConfig::default());
+        System::setup(&mut system, &mut world.res);
+        let mut traversal = world
+            .write_resource::<EventChannel<NatTraversalEvent>>()
+            .register_reader();
+        let server = world
+            .create_entity()
+            .with(NetConnection::<GameEvent>::new(
+                "1.2.3.4:5000".parse().unwrap(),
+            ))
+            .with(Rendezvous::new("game"))
+            .build();
+        system.run_now(&world.res);
+        assert_eq!(
+            sent::<GameEvent>(&mut world, server),
+            vec![GameEvent::Nat(register("game"))]
+        );
+
+        let addr: SocketAddr = "5.6.7.8:6000".parse().unwrap();
+        receive(
+            &mut world,
+            server,
+            GameEvent::Nat(NatMessage::Introduce { addr }),
+        );
+        system.run_now(&world.res);
+        let peer = {
+            let nat_peers = world.read_storage::<NatPeer<GameEvent>>();
+            let (entity, nat_peer) = (&world.entities(), &nat_peers).join().next().unwrap();
+            assert_eq!(nat_peer.addr(), addr);
+            assert_eq!(nat_peer.status(), NatStatus::Punching);
+            entity
+        };
+        let punch = |acknowledged| GameEvent::Nat(NatMessage::Punch { acknowledged });
+        assert_eq!(sent::<GameEvent>(&mut world, peer), vec![punch(false)]);
+
+        // Not punched in time, the events go through the server.
+        world.write_resource::<Time>().set_delta_seconds(4.0);
+        system.run_now(&world.res);
+        assert_eq!(
+            world
+                .read_resource::<EventChannel<NatTraversalEvent>>()
+                .read(&mut traversal)
+                .collect::<Vec<_>>(),
+            vec![&NatTraversalEvent::Relayed { peer, addr }]
+        );
+        let score = NetEvent::Packet(NetPacket::reliable_ordered(GameEvent::Score(3), None));
+        world
+            .write_storage::<NetConnection<GameEvent>>()
+            .get_mut(peer)
+            .unwrap()
+            .send_buffer
+            .single_write(score.clone());
+        system.run_now(&world.res);
+        let relayed = sent::<GameEvent>(&mut world, server);
+        match relayed.as_slice() {
+            [GameEvent::Nat(NatMessage::Relay { peer, payload })] => {
+                assert_eq!(*peer, addr);
+                assert_eq!(deserialize::<NetEvent<GameEvent>>(payload).unwrap(), score);
+            }
+            _ => panic!("Unexpected events {:?}", relayed),
+        }
+        assert_eq!(
+            sent::<GameEvent>(&mut world, peer),
+            vec![GameEvent::Score(3)]
+        );
+
+        // Punched late, the events go directly.
+        receive(&mut world, peer, punch(false));
+        system.run_now(&world.res);
+        assert_eq!(
+            world
+                .read_storage::<NatPeer<GameEvent>>()
+                .get(peer)
+                .unwrap()
+                .status(),
+            NatStatus::Direct
+        );
+        assert_eq!(sent::<GameEvent>(&mut world, peer), vec![punch(true)]);
+        assert_eq!(
+            world
+                .read_resource::<EventChannel<NatTraversalEvent>>()
+                .read(&mut traversal)
+                .collect::<Vec<_>>(),
+            vec![&NatTraversalEvent::Punched { peer, addr }]
+        );
+    }
+}
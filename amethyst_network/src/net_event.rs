@@ -5,6 +5,8 @@
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::transport::Delivery;
+
 /// Network events which you can send or and receive from an endpoint.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum NetEvent<T> {
@@ -12,6 +14,8 @@ pub enum NetEvent<T> {
     Connect {
         /// The client uuid.
         client_uuid: Uuid,
+        /// The version of the protocol of the client, refused if it isn't the one of the server.
+        protocol_version: u32,
     },
     /// Reply to the client that the connection has been accepted.
     Connected {
@@ -33,10 +37,34 @@ pub enum NetEvent<T> {
         /// The reason of the disconnection.
         reason: String,
     },
+    /// Ask the endpoint to answer with a `Pong`, to measure the round trip time.
+    Ping {
+        /// The number of the ping, sent back in the `Pong`.
+        id: u32,
+    },
+    /// Answer to a `Ping`.
+    Pong {
+        /// The number of the ping answered.
+        id: u32,
+    },
     /// Send a packet to all connected clients
     Packet(NetPacket<T>),
 }
 
+impl<T> NetEvent<T> {
+    /// Returns how the transport should deliver this event.
+    ///
+    /// The pings are unreliable, as a late answer is worthless, and the other control events are
+    /// reliable ordered on the default stream.
+    pub fn delivery(&self) -> Delivery {
+        match self {
+            NetEvent::Packet(packet) => packet.delivery(),
+            NetEvent::Ping { .. } | NetEvent::Pong { .. } => Delivery::Unreliable,
+            _ => Delivery::ReliableOrdered(None),
+        }
+    }
+}
+
 /// Enum to specify how a packet should be arranged.
 #[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialOrd, PartialEq, Eq)]
 enum OrderingGuarantee {
@@ -95,6 +123,7 @@ impl From<laminar::DeliveryGuarantee> for DeliveryGuarantee {
 pub struct NetPacket<T> {
     ordering_guarantee: OrderingGuarantee,
     delivery_guarantee: DeliveryGuarantee,
+    channel: Option<u8>,
     content: T,
 }
 
@@ -114,6 +143,7 @@ impl<T> NetPacket<T> {
         NetPacket {
             ordering_guarantee: OrderingGuarantee::None,
             delivery_guarantee: DeliveryGuarantee::Unreliable,
+            channel: None,
             content,
         }
     }
@@ -133,6 +163,7 @@ impl<T> NetPacket<T> {
         NetPacket {
             ordering_guarantee: OrderingGuarantee::Sequenced(stream_id),
             delivery_guarantee: DeliveryGuarantee::Unreliable,
+            channel: None,
             content,
         }
     }
@@ -151,6 +182,7 @@ impl<T> NetPacket<T> {
         NetPacket {
             ordering_guarantee: OrderingGuarantee::None,
             delivery_guarantee: DeliveryGuarantee::Reliable,
+            channel: None,
             content,
         }
     }
@@ -173,6 +205,7 @@ impl<T> NetPacket<T> {
         NetPacket {
             ordering_guarantee: OrderingGuarantee::Ordered(stream_id),
             delivery_guarantee: DeliveryGuarantee::Reliable,
+            channel: None,
             content,
         }
     }
@@ -196,10 +229,29 @@ impl<T> NetPacket<T> {
         NetPacket {
             ordering_guarantee: OrderingGuarantee::Sequenced(stream_id),
             delivery_guarantee: DeliveryGuarantee::Reliable,
+            channel: None,
+            content,
+        }
+    }
+
+    /// Create a new packet with the given content, sent on a channel of the `NetSocketSystem`.
+    ///
+    /// The packet is delivered as configured by the `Channels` of the `ServerConfig`, which lets
+    /// the chat be reliable ordered while the movement snapshots are unreliable sequenced.
+    pub fn on_channel(content: T, channel: u8) -> NetPacket<T> {
+        NetPacket {
+            ordering_guarantee: OrderingGuarantee::Ordered(Some(channel)),
+            delivery_guarantee: DeliveryGuarantee::Reliable,
+            channel: Some(channel),
             content,
         }
     }
 
+    /// Returns the channel this event is sent on, if any.
+    pub fn channel(&self) -> Option<u8> {
+        self.channel
+    }
+
     /// Returns if this event is reliable.
     ///
     /// Each net event type is either reliable or unreliable.
@@ -237,6 +289,46 @@ impl<T> NetPacket<T> {
         self.ordering_guarantee == OrderingGuarantee::None
     }
 
+    /// Returns how the transport should deliver this event.
+    pub fn delivery(&self) -> Delivery {
+        match (self.delivery_guarantee, self.ordering_guarantee) {
+            (DeliveryGuarantee::Unreliable, OrderingGuarantee::Sequenced(stream)) => {
+                Delivery::UnreliableSequenced(stream)
+            }
+            // Laminar doesn't order unreliable packets.
+            (DeliveryGuarantee::Unreliable, _) => Delivery::Unreliable,
+            (DeliveryGuarantee::Reliable, OrderingGuarantee::None) => Delivery::ReliableUnordered,
+            (DeliveryGuarantee::Reliable, OrderingGuarantee::Ordered(stream)) => {
+                Delivery::ReliableOrdered(stream)
+            }
+            (DeliveryGuarantee::Reliable, OrderingGuarantee::Sequenced(stream)) => {
+                Delivery::ReliableSequenced(stream)
+            }
+        }
+    }
+
+    // Changes the guarantees to match the delivery.
+    pub(crate) fn set_delivery(&mut self, delivery: Delivery) {
+        let (delivery_guarantee, ordering_guarantee) = match delivery {
+            Delivery::Unreliable => (DeliveryGuarantee::Unreliable, OrderingGuarantee::None),
+            Delivery::UnreliableSequenced(stream) => (
+                DeliveryGuarantee::Unreliable,
+                OrderingGuarantee::Sequenced(stream),
+            ),
+            Delivery::ReliableUnordered => (DeliveryGuarantee::Reliable, OrderingGuarantee::None),
+            Delivery::ReliableOrdered(stream) => (
+                DeliveryGuarantee::Reliable,
+                OrderingGuarantee::Ordered(stream),
+            ),
+            Delivery::ReliableSequenced(stream) => (
+                DeliveryGuarantee::Reliable,
+                OrderingGuarantee::Sequenced(stream),
+            ),
+        };
+        self.delivery_guarantee = delivery_guarantee;
+        self.ordering_guarantee = ordering_guarantee;
+    }
+
     /// Returns a immutable reference to the content.
     pub fn content(&self) -> &T {
         &self.content
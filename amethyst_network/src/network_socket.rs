@@ -1,30 +1,29 @@
 //! The network send and receive System
 
-use std::{clone::Clone, net::SocketAddr, thread};
+use std::clone::Clone;
 
-use amethyst_core::ecs::{Join, Resources, System, SystemData, WriteStorage};
+use amethyst_core::{
+    ecs::{Entities, Join, Read, Resources, System, SystemData, Write, WriteStorage},
+    timing::Time,
+};
 
-use crossbeam_channel::{Receiver, Sender};
-use laminar::{Packet, SocketEvent};
 use log::{error, warn};
 use serde::{de::DeserializeOwned, Serialize};
 
 use super::{
+    channel::ChannelMode,
     deserialize_event,
     error::Result,
-    send_event,
-    server::{Host, ServerConfig},
+    send_net_event,
+    server::ServerConfig,
+    stats::NetworkStats,
+    tick::NetworkTick,
+    transport::{
+        EncryptedTransport, PackingTransport, SimulatedTransport, Transport, TransportEvent,
+    },
     ConnectionState, NetConnection, NetEvent, NetFilter,
 };
 
-enum InternalSocketEvent<E> {
-    SendEvents {
-        target: SocketAddr,
-        events: Vec<NetEvent<E>>,
-    },
-    Stop,
-}
-
 // If a client sends both a connect event and other events,
 // only the connect event will be considered valid and all others will be lost.
 /// The System managing the network state and connections.
@@ -35,6 +34,9 @@ enum InternalSocketEvent<E> {
 ///
 /// If both a connection (Connect or Connected) event is received at the same time as another event from the same connection,
 /// only the connection event will be considered and rest will be filtered out.
+///
+/// The connections the transport reports as disconnected, like the timed out ones, are marked
+/// `ConnectionState::Disconnected` and closed.
 // TODO: add Unchecked Event type list. Those events will be let pass the client connected filter (Example: NetEvent::Connect).
 // Current behaviour: hardcoded passthrough of Connect and Connected events.
 pub struct NetSocketSystem<E: 'static>
@@ -43,10 +45,8 @@ where
 {
     /// The list of filters applied on the events received.
     pub filters: Vec<Box<dyn NetFilter<E>>>,
-    // sender on which you can queue packets to send to some endpoint.
-    event_sender: Sender<InternalSocketEvent<E>>,
-    // receiver from which you can read received packets.
-    event_receiver: Receiver<laminar::SocketEvent>,
+    // the transport the events are sent and received with.
+    transport: Box<dyn Transport>,
     config: ServerConfig,
 }
 
@@ -54,53 +54,48 @@ impl<E> NetSocketSystem<E>
 where
     E: Serialize + PartialEq + Send + 'static,
 {
-    /// Creates a `NetSocketSystem` and binds the Socket on the ip and port added in parameters.
+    /// Creates a `NetSocketSystem` and binds the transport of the configuration on the ip and
+    /// port added in parameters.
     pub fn new(config: ServerConfig, filters: Vec<Box<dyn NetFilter<E>>>) -> Result<Self> {
         if config.udp_socket_addr.port() < 1024 {
             // Just warning the user here, just in case they want to use the root port.
             warn!("Using a port below 1024, this will require root permission and should not be done.");
         }
 
-        let server = Host::run(&config)?;
-
-        let udp_send_handle = server.udp_send_handle();
-        let udp_receive_handle = server.udp_receive_handle();
-
-        let event_sender = NetSocketSystem::<E>::start_sending(udp_send_handle);
+        let mut transport = config.transport.bind(&config)?;
+        if let Some(ref conditions) = config.simulation {
+            if cfg!(debug_assertions) {
+                transport = Box::new(SimulatedTransport::new(transport, conditions.clone()));
+            } else {
+                warn!("Not simulating the network conditions in a release build.");
+            }
+        }
+        if let Some(ref encryption) = config.encryption {
+            transport = Box::new(EncryptedTransport::new(transport, encryption.clone()));
+        }
+        if let Some(ref packing) = config.packing {
+            transport = Box::new(PackingTransport::new(transport, packing.clone()));
+        }
+        Ok(NetSocketSystem::with_transport(transport, config, filters))
+    }
 
-        Ok(NetSocketSystem {
+    /// Creates a `NetSocketSystem` using an already bound transport.
+    pub fn with_transport(
+        transport: Box<dyn Transport>,
+        config: ServerConfig,
+        filters: Vec<Box<dyn NetFilter<E>>>,
+    ) -> Self {
+        NetSocketSystem {
             filters,
-            event_sender,
-            event_receiver: udp_receive_handle,
+            transport,
             config,
-        })
+        }
     }
 
-    /// Start a thread to send all queued packets.
-    fn start_sending(sender: Sender<Packet>) -> Sender<InternalSocketEvent<E>> {
-        let (event_sender, event_receiver) = crossbeam_channel::unbounded();
-
-        thread::spawn(move || loop {
-            for control_event in event_receiver.try_iter() {
-                match control_event {
-                    InternalSocketEvent::SendEvents { target, events } => {
-                        for ev in events {
-                            match ev {
-                                NetEvent::Packet(packet) => {
-                                    send_event(packet, target, &sender);
-                                }
-                                _ => { /* TODO, handle connect, disconnect etc. */ }
-                            }
-                        }
-                    }
-                    InternalSocketEvent::Stop => {
-                        break;
-                    }
-                }
-            }
-        });
-
-        event_sender
+    /// Adds a channel, or changes the mode of an existing one.
+    pub fn with_channel(mut self, channel: u8, mode: ChannelMode) -> Self {
+        self.config.channels.set(channel, mode);
+        self
     }
 }
 
@@ -108,51 +103,111 @@ impl<'a, E> System<'a> for NetSocketSystem<E>
 where
     E: Send + Sync + Serialize + Clone + DeserializeOwned + PartialEq + 'static,
 {
-    type SystemData = (WriteStorage<'a, NetConnection<E>>);
-
-    fn run(&mut self, mut net_connections: Self::SystemData) {
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, NetConnection<E>>,
+        Read<'a, Time>,
+        Write<'a, NetworkStats>,
+        Read<'a, NetworkTick>,
+    );
+
+    fn run(&mut self, (entities, mut net_connections, time, mut stats, tick): Self::SystemData) {
+        let mut closing = Vec::new();
         for connection in (&mut net_connections).join() {
-            match connection.state {
-                ConnectionState::Connected | ConnectionState::Connecting => {
-                    self.event_sender
-                        .send(InternalSocketEvent::SendEvents {
-                            target: connection.target_addr,
-                            events: connection.send_buffer_early_read().cloned().collect(),
-                        })
-                        .expect("Unreachable: Channel will be alive until a stop event is sent");
-                }
-                ConnectionState::Disconnected => {
-                    self.event_sender
-                        .send(InternalSocketEvent::Stop)
-                        .expect("Already sent a stop event to the channel");
+            let dropped = connection.state == ConnectionState::Disconnected;
+            if !dropped {
+                connection.closed = false;
+            } else if connection.closed {
+                continue;
+            }
+            // Between the ticks, the events wait in the send buffers, except for the last events
+            // of a dropped connection, like its `Disconnect`, sent before it's closed.
+            if !tick.is_tick() && !dropped {
+                continue;
+            }
+            let target = connection.target_addr;
+            for event in connection.send_buffer_early_read() {
+                let sent = match event {
+                    NetEvent::Packet(packet) => {
+                        let mut packet = packet.clone();
+                        self.config.channels.apply(&mut packet);
+                        let event = NetEvent::Packet(packet);
+                        send_net_event(&event, target, &mut *self.transport)
+                    }
+                    event => send_net_event(event, target, &mut *self.transport),
+                };
+                if let Some(size) = sent {
+                    stats.record_sent(target, size);
                 }
             }
+            if dropped {
+                connection.closed = true;
+                closing.push(target);
+            }
+        }
+        if let Err(e) = self.transport.flush() {
+            error!("Failed to send the packed events: {}", e);
+        }
+        for addr in closing {
+            self.transport.disconnect(addr);
         }
 
-        for (counter, socket_event) in self.event_receiver.try_iter().enumerate() {
-            match socket_event {
-                SocketEvent::Packet(packet) => {
+        let mut connected = (&net_connections)
+            .join()
+            .filter(|connection| connection.state != ConnectionState::Disconnected)
+            .count();
+        let mut counter = 0;
+        while let Some(transport_event) = self.transport.recv() {
+            match transport_event {
+                TransportEvent::Packet { addr, payload } => {
+                    stats.record_received(addr, payload.len());
                     // Get the event
-                    match deserialize_event::<E>(packet.payload()) {
+                    match deserialize_event::<E>(&payload) {
                         Ok(event) => {
                             // Get the NetConnection from the source
+                            let mut known = false;
                             for connection in (&mut net_connections).join() {
-                                if connection.target_addr == packet.addr() {
-                                    connection
-                                        .receive_buffer
-                                        .single_write(NetEvent::Packet(event.clone()));
+                                if connection.target_addr == addr {
+                                    connection.receive_buffer.single_write(event.clone());
+                                    known = true;
                                 };
                             }
+                            let connecting = match event {
+                                NetEvent::Connect { .. } => true,
+                                _ => false,
+                            };
+                            let full = self
+                                .config
+                                .max_connections
+                                .map_or(false, |max| connected >= max);
+                            if !known && connecting && self.config.accept_connections && !full {
+                                connected += 1;
+                                let mut connection = NetConnection::new(addr);
+                                connection.receive_buffer.single_write(event);
+                                net_connections
+                                    .insert(entities.create(), connection)
+                                    .expect("Unreachable: The entity was just created");
+                            }
                         }
                         Err(e) => error!(
                             "Failed to deserialize an incoming network event: {} From source: {:?}",
-                            e,
-                            packet.addr()
+                            e, addr
                         ),
                     };
                 }
-                SocketEvent::Connect(_) => { /* TODO: Update connection status */ }
-                SocketEvent::Timeout(_) => { /* TODO: Update connection status */ }
+                // The connections are created on their `NetEvent::Connect`, and established by
+                // their handshake rather than by the transport.
+                TransportEvent::Connected(_) => {}
+                TransportEvent::Disconnected(addr) => {
+                    for connection in (&mut net_connections).join() {
+                        if connection.target_addr == addr
+                            && connection.state != ConnectionState::Disconnected
+                        {
+                            connection.state = ConnectionState::Disconnected;
+                            connected = connected.saturating_sub(1);
+                        }
+                    }
+                }
             };
 
             // this will prevent our system to be stuck in the iterator.
@@ -161,7 +216,14 @@ where
             if counter >= self.config.max_throughput as usize {
                 break;
             }
+            counter += 1;
         }
+
+        let alive = (&net_connections)
+            .join()
+            .map(|connection| connection.target_addr)
+            .collect::<Vec<_>>();
+        stats.update(time.absolute_real_time(), &alive);
     }
 
     fn setup(&mut self, res: &mut Resources) {
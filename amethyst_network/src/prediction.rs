@@ -0,0 +1,634 @@
+//! Client-side prediction of the locally controlled entities, reconciled with the server.
+//!
+//! The client applies its inputs immediately through the `Prediction` of the game, keeps them
+//! until the server acknowledges them, and sends them to the server. The server applies the same
+//! `Prediction` to the inputs it receives and answers with the authoritative state and the
+//! sequence of the last input it applied. When this state differs from the one the client
+//! predicted, the client rewinds to it and replays the inputs the server didn't apply yet.
+
+use std::{collections::VecDeque, marker::PhantomData};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use shrev::ReaderId;
+
+use amethyst_core::{
+    bundle::{BundleBuilder, BundleRequirement, SystemBundle},
+    ecs::{
+        Component, DenseVecStorage, Entities, Entity, Join, NullStorage, ReadStorage, Resources,
+        System, SystemData, Write, WriteStorage,
+    },
+};
+use amethyst_error::Error;
+
+use crate::{
+    channel::{RELIABLE_CHANNEL, UNRELIABLE_CHANNEL},
+    replication::is_newer,
+    ConnectionState, NetConnection, NetEvent, NetPacket,
+};
+
+/// How many inputs waiting for the server are kept, the oldest ones are dropped past it.
+pub const PREDICTION_HISTORY: usize = 128;
+
+/// The movement code of the game, run on both the client and the server.
+pub trait Prediction: Clone + Send + Sync + 'static {
+    /// The input of a player for one step.
+    type Input: Clone + Serialize + DeserializeOwned + Send + Sync + 'static;
+    /// The predicted component.
+    type State: Component + Clone + PartialEq + Serialize + DeserializeOwned + Send + Sync;
+
+    /// Advances the state by one input.
+    fn apply(&self, state: &mut Self::State, input: &Self::Input);
+}
+
+/// The messages exchanged to predict an entity.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum PredictionMessage<I, S> {
+    /// The client sends an input.
+    Input {
+        /// The number of the input, increasing by one for each input.
+        sequence: u32,
+        /// The input.
+        input: I,
+    },
+    /// The server sends the state after the last input it applied.
+    State {
+        /// The number of the last input applied.
+        sequence: u32,
+        /// The state.
+        state: S,
+    },
+}
+
+/// The prediction messages of a `Prediction`.
+pub type PredictionMessageOf<P> =
+    PredictionMessage<<P as Prediction>::Input, <P as Prediction>::State>;
+
+/// A network event type the prediction messages can be sent as.
+pub trait PredictionEvent<P: Prediction>: Send + Sync + 'static {
+    /// Wraps a prediction message.
+    fn from_message(message: PredictionMessageOf<P>) -> Self;
+
+    /// Returns the prediction message of the event, if it is one.
+    fn as_message(&self) -> Option<&PredictionMessageOf<P>>;
+}
+
+impl<P: Prediction> PredictionEvent<P> for PredictionMessageOf<P> {
+    fn from_message(message: PredictionMessageOf<P>) -> Self {
+        message
+    }
+
+    fn as_message(&self) -> Option<&PredictionMessageOf<P>> {
+        Some(self)
+    }
+}
+
+/// Marks the entity of the client predicted from its inputs.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LocallyControlled;
+
+impl Component for LocallyControlled {
+    type Storage = NullStorage<Self>;
+}
+
+/// Added by the server to a connection, the entity its inputs control.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ControlledEntity(pub Entity);
+
+impl Component for ControlledEntity {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// The prediction state of a connection for a `Prediction`, added by the prediction systems.
+pub struct PredictionPeer<P: Prediction, E: 'static> {
+    reader: ReaderId<NetEvent<E>>,
+    // The last input applied by the server, or acknowledged to the client.
+    sequence: Option<u32>,
+    // The inputs received by the server, applied over the next frames.
+    queued: VecDeque<(u32, P::Input)>,
+}
+
+impl<P: Prediction, E: Send + Sync + 'static> Component for PredictionPeer<P, E> {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// The inputs of the local player to predict, push them every frame.
+pub struct PredictionInput<P: Prediction> {
+    inputs: Vec<P::Input>,
+}
+
+impl<P: Prediction> Default for PredictionInput<P> {
+    fn default() -> Self {
+        PredictionInput { inputs: Vec::new() }
+    }
+}
+
+impl<P: Prediction> PredictionInput<P> {
+    /// Queues an input, applied by the `PredictionSystem` this frame.
+    pub fn push(&mut self, input: P::Input) {
+        self.inputs.push(input);
+    }
+}
+
+/// The inputs the server didn't acknowledge yet, with the state predicted after each.
+pub struct PredictionHistory<P: Prediction> {
+    next_sequence: u32,
+    pending: VecDeque<(u32, P::Input, P::State)>,
+    mispredictions: u64,
+}
+
+impl<P: Prediction> Default for PredictionHistory<P> {
+    fn default() -> Self {
+        PredictionHistory {
+            next_sequence: 0,
+            pending: VecDeque::new(),
+            mispredictions: 0,
+        }
+    }
+}
+
+impl<P: Prediction> PredictionHistory<P> {
+    /// The number of inputs waiting for the server.
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// How many times the server disagreed with the prediction.
+    pub fn mispredictions(&self) -> u64 {
+        self.mispredictions
+    }
+
+    /// Applies an input to the state and records it, returning its sequence.
+    pub fn predict(&mut self, prediction: &P, state: &mut P::State, input: P::Input) -> u32 {
+        let sequence = self.next_sequence;
+        self.next_sequence = self.next_sequence.wrapping_add(1);
+        prediction.apply(state, &input);
+        self.pending.push_back((sequence, input, state.clone()));
+        while self.pending.len() > PREDICTION_HISTORY {
+            self.pending.pop_front();
+        }
+        sequence
+    }
+
+    /// Forgets the inputs applied by the server, and returns the corrected state if the
+    /// prediction was wrong: the authoritative state with the remaining inputs replayed.
+    pub fn reconcile(
+        &mut self,
+        prediction: &P,
+        sequence: u32,
+        authoritative: &P::State,
+    ) -> Option<P::State> {
+        let mut predicted = None;
+        while let Some((pending, _, _)) = self.pending.front() {
+            if is_newer(*pending, Some(sequence)) {
+                break;
+            }
+            let (pending, _, state) = self.pending.pop_front().expect("Unreachable: Not empty");
+            if pending == sequence {
+                predicted = Some(state);
+            }
+        }
+        if predicted.as_ref() == Some(authoritative) {
+            return None;
+        }
+
+        self.mispredictions += 1;
+        let mut state = authoritative.clone();
+        for (_, input, predicted) in &mut self.pending {
+            prediction.apply(&mut state, input);
+            *predicted = state.clone();
+        }
+        Some(state)
+    }
+}
+
+/// Predicts the `LocallyControlled` entity from the `PredictionInput`, sends the inputs to the
+/// server and reconciles with the states it answers.
+pub struct PredictionSystem<P, E> {
+    prediction: P,
+    _marker: PhantomData<E>,
+}
+
+impl<P, E> PredictionSystem<P, E> {
+    /// Predicts with the given movement code.
+    pub fn new(prediction: P) -> Self {
+        PredictionSystem {
+            prediction,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, P, E> System<'a> for PredictionSystem<P, E>
+where
+    P: Prediction,
+    E: PredictionEvent<P>,
+{
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, NetConnection<E>>,
+        WriteStorage<'a, PredictionPeer<P, E>>,
+        ReadStorage<'a, LocallyControlled>,
+        WriteStorage<'a, P::State>,
+        Write<'a, PredictionInput<P>>,
+        Write<'a, PredictionHistory<P>>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, mut connections, mut peers, controlled, mut states, mut input, mut history): Self::SystemData,
+    ) {
+        add_peers(&entities, &mut connections, &mut peers);
+
+        let mut authoritative = None;
+        for (connection, peer) in (&mut connections, &mut peers).join() {
+            for event in connection.receive_buffer.read(&mut peer.reader) {
+                if let NetEvent::Packet(packet) = event {
+                    if let Some(PredictionMessage::State { sequence, state }) =
+                        packet.content().as_message()
+                    {
+                        if is_newer(*sequence, peer.sequence) {
+                            peer.sequence = Some(*sequence);
+                            authoritative = Some((*sequence, state.clone()));
+                        }
+                    }
+                }
+            }
+        }
+
+        let inputs = input.inputs.drain(..).collect::<Vec<_>>();
+        for (_, state) in (&controlled, &mut states).join() {
+            if let Some((sequence, ref authoritative)) = authoritative {
+                if let Some(corrected) =
+                    history.reconcile(&self.prediction, sequence, authoritative)
+                {
+                    *state = corrected;
+                }
+            }
+
+            for input in &inputs {
+                let sequence = history.predict(&self.prediction, state, input.clone());
+                for connection in (&mut connections).join() {
+                    if connection.state == ConnectionState::Disconnected {
+                        continue;
+                    }
+                    connection
+                        .send_buffer
+                        .single_write(NetEvent::Packet(NetPacket::on_channel(
+                            E::from_message(PredictionMessage::Input {
+                                sequence,
+                                input: input.clone(),
+                            }),
+                            RELIABLE_CHANNEL,
+                        )));
+                }
+            }
+            // Only one entity is predicted.
+            break;
+        }
+    }
+
+    fn setup(&mut self, res: &mut Resources) {
+        Self::SystemData::setup(res);
+    }
+}
+
+/// Applies the inputs of the connections to their `ControlledEntity` on the server, and
+/// answers with the resulting state.
+///
+/// At most `max_inputs` inputs of a connection are applied each frame, 4 by default, so that a
+/// client sending more inputs than it should doesn't move faster. The others wait for the next
+/// frames, up to `PREDICTION_HISTORY` of them.
+pub struct AuthoritySystem<P, E> {
+    prediction: P,
+    max_inputs: usize,
+    _marker: PhantomData<E>,
+}
+
+impl<P, E> AuthoritySystem<P, E> {
+    /// Applies the inputs with the given movement code.
+    pub fn new(prediction: P) -> Self {
+        AuthoritySystem {
+            prediction,
+            max_inputs: 4,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Sets how many inputs of a connection are applied each frame, which should be the most
+    /// inputs a client pushes in a frame of the server.
+    pub fn with_max_inputs(mut self, max_inputs: usize) -> Self {
+        self.max_inputs = max_inputs;
+        self
+    }
+}
+
+impl<'a, P, E> System<'a> for AuthoritySystem<P, E>
+where
+    P: Prediction,
+    E: PredictionEvent<P>,
+{
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, NetConnection<E>>,
+        WriteStorage<'a, PredictionPeer<P, E>>,
+        ReadStorage<'a, ControlledEntity>,
+        WriteStorage<'a, P::State>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, mut connections, mut peers, controlled, mut states): Self::SystemData,
+    ) {
+        add_peers(&entities, &mut connections, &mut peers);
+
+        for (connection, peer, controlled) in
+            (&mut connections, &mut peers, (&controlled).maybe()).join()
+        {
+            for event in connection.receive_buffer.read(&mut peer.reader) {
+                if let NetEvent::Packet(packet) = event {
+                    if let Some(PredictionMessage::Input { sequence, input }) =
+                        packet.content().as_message()
+                    {
+                        let last = peer.queued.back().map(|(last, _)| *last).or(peer.sequence);
+                        if is_newer(*sequence, last) && peer.queued.len() < PREDICTION_HISTORY {
+                            peer.queued.push_back((*sequence, input.clone()));
+                        }
+                    }
+                }
+            }
+
+            let state = match controlled.and_then(|controlled| states.get_mut(controlled.0)) {
+                Some(state) => state,
+                None => {
+                    peer.queued.clear();
+                    continue;
+                }
+            };
+            let applied = peer.queued.len().min(self.max_inputs);
+            for (sequence, input) in peer.queued.drain(..applied) {
+                self.prediction.apply(state, &input);
+                peer.sequence = Some(sequence);
+            }
+
+            if let Some(sequence) = peer.sequence.filter(|_| applied > 0) {
+                connection
+                    .send_buffer
+                    .single_write(NetEvent::Packet(NetPacket::on_channel(
+                        E::from_message(PredictionMessage::State {
+                            sequence,
+                            state: state.clone(),
+                        }),
+                        UNRELIABLE_CHANNEL,
+                    )));
+            }
+        }
+    }
+
+    fn setup(&mut self, res: &mut Resources) {
+        Self::SystemData::setup(res);
+    }
+}
+
+fn add_peers<P: Prediction, E: Send + Sync + 'static>(
+    entities: &Entities<'_>,
+    connections: &mut WriteStorage<'_, NetConnection<E>>,
+    peers: &mut WriteStorage<'_, PredictionPeer<P, E>>,
+) {
+    let new = (&**entities, connections, !&*peers)
+        .join()
+        .map(|(entity, connection, _)| (entity, connection.receive_buffer.register_reader()))
+        .collect::<Vec<_>>();
+    for (entity, reader) in new {
+        peers
+            .insert(
+                entity,
+                PredictionPeer {
+                    reader,
+                    sequence: None,
+                    queued: VecDeque::new(),
+                },
+            )
+            .expect("Unreachable: The entity is alive");
+    }
+}
+
+/// Adds the `PredictionSystem` to the client.
+///
+/// The predicted component of the `LocallyControlled` entity shouldn't also be replicated,
+/// the corrections come from the server through the prediction messages.
+pub struct PredictionClientBundle<P, E> {
+    prediction: P,
+    _marker: PhantomData<E>,
+}
+
+impl<P, E> PredictionClientBundle<P, E> {
+    /// Predicts with the given movement code.
+    pub fn new(prediction: P) -> Self {
+        PredictionClientBundle {
+            prediction,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, 'b, P, E> SystemBundle<'a, 'b> for PredictionClientBundle<P, E>
+where
+    P: Prediction,
+    E: PredictionEvent<P>,
+{
+    fn build(self, builder: &mut BundleBuilder<'a, 'b>) -> Result<(), Error> {
+        builder.add(
+            PredictionSystem::<P, E>::new(self.prediction),
+            "prediction_system",
+            &["net_socket"],
+        );
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        "PredictionClientBundle"
+    }
+
+    fn requirements(&self) -> Vec<BundleRequirement> {
+        vec![BundleRequirement::new("net_socket", "NetworkBundle")]
+    }
+}
+
+/// Adds the `AuthoritySystem` to the server.
+pub struct PredictionServerBundle<P, E> {
+    prediction: P,
+    max_inputs: Option<usize>,
+    _marker: PhantomData<E>,
+}
+
+impl<P, E> PredictionServerBundle<P, E> {
+    /// Applies the inputs with the given movement code.
+    pub fn new(prediction: P) -> Self {
+        PredictionServerBundle {
+            prediction,
+            max_inputs: None,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Sets how many inputs of a connection are applied each frame, see
+    /// `AuthoritySystem::with_max_inputs`.
+    pub fn with_max_inputs(mut self, max_inputs: usize) -> Self {
+        self.max_inputs = Some(max_inputs);
+        self
+    }
+}
+
+impl<'a, 'b, P, E> SystemBundle<'a, 'b> for PredictionServerBundle<P, E>
+where
+    P: Prediction,
+    E: PredictionEvent<P>,
+{
+    fn build(self, builder: &mut BundleBuilder<'a, 'b>) -> Result<(), Error> {
+        let mut system = AuthoritySystem::<P, E>::new(self.prediction);
+        if let Some(max_inputs) = self.max_inputs {
+            system = system.with_max_inputs(max_inputs);
+        }
+        builder.add(system, "authority_system", &["net_socket"]);
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        "PredictionServerBundle"
+    }
+
+    fn requirements(&self) -> Vec<BundleRequirement> {
+        vec![BundleRequirement::new("net_socket", "NetworkBundle")]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::ecs::{Builder, RunNow, VecStorage, World};
+
+    use super::*;
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct Position(i32);
+
+    impl Component for Position {
+        type Storage = VecStorage<Self>;
+    }
+
+    #[derive(Clone)]
+    struct Walk;
+
+    impl Prediction for Walk {
+        type Input = i32;
+        type State = Position;
+
+        fn apply(&self, state: &mut Position, input: &i32) {
+            state.0 += input;
+        }
+    }
+
+    #[test]
+    fn reconcile_replays_the_pending_inputs() {
+        let mut history = PredictionHistory::<Walk>::default();
+        let mut state = Position(0);
+        for input in 1..=4 {
+            history.predict(&Walk, &mut state, input);
+        }
+        assert_eq!(state, Position(10));
+
+        // The server agrees with the first two inputs.
+        assert_eq!(history.reconcile(&Walk, 1, &Position(3)), None);
+        assert_eq!(history.pending(), 2);
+        assert_eq!(history.mispredictions(), 0);
+
+        // The server got pushed back by something the client didn't see.
+        assert_eq!(history.reconcile(&Walk, 2, &Position(1)), Some(Position(5)));
+        assert_eq!(history.pending(), 1);
+        assert_eq!(history.mispredictions(), 1);
+    }
+
+    type Message = PredictionMessageOf<Walk>;
+
+    fn send_inputs(world: &mut World, connection: Entity, inputs: std::ops::Range<u32>) {
+        let mut connections = world.write_storage::<NetConnection<Message>>();
+        let connection = connections.get_mut(connection).unwrap();
+        for sequence in inputs {
+            connection
+                .receive_buffer
+                .single_write(NetEvent::Packet(NetPacket::reliable_ordered(
+                    PredictionMessage::Input { sequence, input: 1 },
+                    None,
+                )));
+        }
+    }
+
+    fn answers(world: &mut World, connection: Entity) -> Vec<Message> {
+        world
+            .write_storage::<NetConnection<Message>>()
+            .get_mut(connection)
+            .unwrap()
+            .send_buffer_early_read()
+            .filter_map(|event| match event {
+                NetEvent::Packet(packet) => Some(packet.content().clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn the_inputs_applied_each_frame_are_capped() {
+        let mut world = World::new();
+        let mut system = AuthoritySystem::<Walk, Message>::new(Walk).with_max_inputs(2);
+        System::setup(&mut system, &mut world.res);
+        let player = world.create_entity().with(Position(0)).build();
+        let connection = world
+            .create_entity()
+            .with(NetConnection::<Message>::new(
+                "127.0.0.1:3456".parse().unwrap(),
+            ))
+            .build();
+        system.run_now(&world.res);
+
+        // Sent before the connection controls an entity, never applied.
+        send_inputs(&mut world, connection, 0..3);
+        system.run_now(&world.res);
+        world
+            .write_storage::<ControlledEntity>()
+            .insert(connection, ControlledEntity(player))
+            .unwrap();
+
+        send_inputs(&mut world, connection, 3..8);
+        system.run_now(&world.res);
+        assert_eq!(
+            world.read_storage::<Position>().get(player),
+            Some(&Position(2))
+        );
+        assert_eq!(
+            answers(&mut world, connection),
+            vec![PredictionMessage::State {
+                sequence: 4,
+                state: Position(2),
+            }]
+        );
+        system.run_now(&world.res);
+        system.run_now(&world.res);
+        system.run_now(&world.res);
+        assert_eq!(
+            world.read_storage::<Position>().get(player),
+            Some(&Position(5))
+        );
+        assert_eq!(
+            answers(&mut world, connection),
+            vec![
+                PredictionMessage::State {
+                    sequence: 6,
+                    state: Position(4),
+                },
+                PredictionMessage::State {
+                    sequence: 7,
+                    state: Position(5),
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,389 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    marker::PhantomData,
+    path::PathBuf,
+    sync::Arc,
+};
+
+use bincode::deserialize;
+use log::{debug, error};
+
+use amethyst_core::{
+    bundle::{BundleBuilder, BundleRequirement, SystemBundle},
+    ecs::{Entities, Entity, Join, Read, Resources, System, SystemData, Write, WriteStorage},
+};
+use amethyst_error::{Error, ResultExt};
+
+use crate::{channel::UNRELIABLE_CHANNEL, NetConnection, NetEvent, NetPacket};
+
+use super::{
+    interpolation::{Interpolate, InterpolationConfig, SnapshotInterpolationSystem},
+    is_newer,
+    replay::{ReplayPlaybackSystem, ReplayReader, ReplayRecordSystem, ReplayWriter},
+    NetworkId, NetworkedComponent, ReplicationEvent, ReplicationMessage, ReplicationPeer,
+    ReplicationState, Snapshot, SNAPSHOT_HISTORY,
+};
+
+/// The replication state of a client: the last states received, and the local entities of the
+/// server ones.
+#[derive(Default)]
+pub struct ReplicationClient {
+    entities: HashMap<NetworkId, Entity>,
+    history: VecDeque<(u32, Arc<ReplicationState>)>,
+    // The state applied this frame and the one before it, if a snapshot was received.
+    update: Option<(Arc<ReplicationState>, Arc<ReplicationState>)>,
+}
+
+impl ReplicationClient {
+    /// Returns the local entity replicating the server entity `id`.
+    pub fn entity(&self, id: NetworkId) -> Option<Entity> {
+        self.entities.get(&id).cloned()
+    }
+
+    /// Returns the tick of the last snapshot applied.
+    pub fn tick(&self) -> Option<u32> {
+        self.history.back().map(|(tick, _)| *tick)
+    }
+
+    // The states received, the oldest first.
+    pub(crate) fn states(&self) -> impl Iterator<Item = (u32, &ReplicationState)> {
+        self.history.iter().map(|(tick, state)| (*tick, &**state))
+    }
+
+    // The local entities and the server ones they replicate.
+    pub(crate) fn entities(&self) -> impl Iterator<Item = (NetworkId, Entity)> + '_ {
+        self.entities.iter().map(|(id, entity)| (*id, *entity))
+    }
+
+    // The state applied this frame, if a snapshot was received.
+    pub(crate) fn update(&self) -> Option<&Arc<ReplicationState>> {
+        self.update.as_ref().map(|(state, _)| state)
+    }
+
+    // Makes `state` the state to apply this frame, and matches the local entities to it.
+    pub(crate) fn receive(
+        &mut self,
+        tick: u32,
+        state: Arc<ReplicationState>,
+        entities: &Entities<'_>,
+        ids: &mut WriteStorage<'_, NetworkId>,
+    ) {
+        let previous = self
+            .history
+            .back()
+            .map(|(_, state)| state.clone())
+            .unwrap_or_default();
+
+        let removed = self
+            .entities
+            .keys()
+            .filter(|id| !state.contains_key(id))
+            .cloned()
+            .collect::<Vec<_>>();
+        for id in removed {
+            if let Some(entity) = self.entities.remove(&id) {
+                if let Err(e) = entities.delete(entity) {
+                    error!("Failed to delete a replicated entity: {}", e);
+                }
+            }
+        }
+        for id in state.keys() {
+            if !self.entities.contains_key(id) {
+                let entity = entities.create();
+                ids.insert(entity, *id)
+                    .expect("Unreachable: The entity was just created");
+                self.entities.insert(*id, entity);
+            }
+        }
+
+        self.history.push_back((tick, state.clone()));
+        while self.history.len() > SNAPSHOT_HISTORY {
+            self.history.pop_front();
+        }
+        self.update = Some((state, previous));
+    }
+
+    // Forgets the state of the last frame, before receiving the next one.
+    pub(crate) fn clear_update(&mut self) {
+        self.update = None;
+    }
+
+    fn baseline(&self, tick: Option<u32>) -> Option<Arc<ReplicationState>> {
+        match tick {
+            None => Some(Arc::new(ReplicationState::new())),
+            Some(tick) => self
+                .history
+                .iter()
+                .find(|(t, _)| *t == tick)
+                .map(|(_, state)| state.clone()),
+        }
+    }
+}
+
+/// Rebuilds the state of the server from the snapshots it sends, acknowledges them, and creates
+/// and deletes the local entities to match the server ones.
+pub struct SnapshotReceiveSystem<E> {
+    _marker: PhantomData<E>,
+}
+
+impl<E> Default for SnapshotReceiveSystem<E> {
+    fn default() -> Self {
+        SnapshotReceiveSystem {
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, E: ReplicationEvent> System<'a> for SnapshotReceiveSystem<E> {
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, NetConnection<E>>,
+        WriteStorage<'a, ReplicationPeer<E>>,
+        WriteStorage<'a, NetworkId>,
+        Write<'a, ReplicationClient>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, mut connections, mut peers, mut ids, mut client): Self::SystemData,
+    ) {
+        client.clear_update();
+
+        let new = (&*entities, &mut connections, !&peers)
+            .join()
+            .map(|(entity, connection, _)| (entity, connection.receive_buffer.register_reader()))
+            .collect::<Vec<_>>();
+        for (entity, reader) in new {
+            peers
+                .insert(entity, ReplicationPeer::new(reader))
+                .expect("Unreachable: The entity is alive");
+        }
+
+        let mut latest: Option<(u32, Arc<ReplicationState>)> = None;
+        for (connection, peer) in (&mut connections, &mut peers).join() {
+            let mut received = None;
+            for event in connection.receive_buffer.read(&mut peer.reader) {
+                if let NetEvent::Packet(packet) = event {
+                    if let Some(ReplicationMessage::Snapshot(snapshot)) =
+                        packet.content().as_message()
+                    {
+                        if is_newer(snapshot.tick, peer.tick) {
+                            received = Some(snapshot.clone());
+                        }
+                    }
+                }
+            }
+            let snapshot: Snapshot = match received {
+                Some(snapshot) => snapshot,
+                None => continue,
+            };
+            let baseline = match client.baseline(snapshot.baseline) {
+                Some(baseline) => baseline,
+                None => {
+                    // The server sends a full snapshot once the baseline is too old.
+                    debug!("Dropping a snapshot relative to an unknown baseline");
+                    continue;
+                }
+            };
+
+            let state = Arc::new(snapshot.apply(&baseline));
+            peer.tick = Some(snapshot.tick);
+            connection
+                .send_buffer
+                .single_write(NetEvent::Packet(NetPacket::on_channel(
+                    E::from_message(ReplicationMessage::Ack(snapshot.tick)),
+                    UNRELIABLE_CHANNEL,
+                )));
+            latest = Some((snapshot.tick, state));
+        }
+
+        if let Some((tick, state)) = latest {
+            client.receive(tick, state, &entities, &mut ids);
+        }
+    }
+
+    fn setup(&mut self, res: &mut Resources) {
+        Self::SystemData::setup(res);
+    }
+}
+
+/// Applies the changes of a networked component received this frame to the local entities.
+pub struct SnapshotApplySystem<C> {
+    index: u16,
+    _marker: PhantomData<C>,
+}
+
+impl<C> SnapshotApplySystem<C> {
+    /// Applies the component registered at `index`.
+    pub fn new(index: u16) -> Self {
+        SnapshotApplySystem {
+            index,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, C: NetworkedComponent> System<'a> for SnapshotApplySystem<C> {
+    type SystemData = (Read<'a, ReplicationClient>, WriteStorage<'a, C>);
+
+    fn run(&mut self, (client, mut components): Self::SystemData) {
+        let (state, previous) = match client.update {
+            Some((ref state, ref previous)) => (state, previous),
+            None => return,
+        };
+        for (id, data) in state.iter() {
+            let entity = match client.entity(*id) {
+                Some(entity) => entity,
+                None => continue,
+            };
+            let data = data.get(&self.index);
+            let old = previous.get(id).and_then(|data| data.get(&self.index));
+            // Only the changes are written, to not flag the unchanged components.
+            if data == old && (data.is_none() || components.contains(entity)) {
+                continue;
+            }
+            match data {
+                Some(data) => match deserialize::<C>(data) {
+                    Ok(component) => {
+                        if let Err(e) = components.insert(entity, component) {
+                            error!("Failed to insert a networked component: {}", e);
+                        }
+                    }
+                    Err(e) => error!("Failed to deserialize a networked component: {}", e),
+                },
+                None => {
+                    components.remove(entity);
+                }
+            }
+        }
+    }
+}
+
+// Adds the system of a component at an index, after the system providing the states.
+type Registration =
+    Box<dyn for<'a, 'b, 'c, 'd> FnOnce(&'c mut BundleBuilder<'a, 'b>, u16, &'d str)>;
+
+/// Adds the systems applying the snapshots of the server to the local entities.
+///
+/// The components must be registered in the same order as on the `ReplicationServerBundle`.
+/// The snapshots can be recorded to a file, and a recording played back instead of receiving
+/// them, to watch a replay of a match without a server.
+pub struct ReplicationClientBundle<E> {
+    components: Vec<Registration>,
+    recording: Option<PathBuf>,
+    replay: Option<PathBuf>,
+    _marker: PhantomData<E>,
+}
+
+impl<E> Default for ReplicationClientBundle<E> {
+    fn default() -> Self {
+        ReplicationClientBundle {
+            components: Vec::new(),
+            recording: None,
+            replay: None,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<E> ReplicationClientBundle<E> {
+    /// Creates a bundle without networked components.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Marks a component as networked.
+    pub fn with_component<C: NetworkedComponent>(mut self) -> Self {
+        self.components.push(Box::new(add_apply::<C>));
+        self
+    }
+
+    /// Marks a component as networked, shown interpolated between the snapshots instead of
+    /// applied as they arrive.
+    pub fn with_interpolated_component<C: Interpolate>(
+        mut self,
+        config: InterpolationConfig,
+    ) -> Self {
+        self.components.push(add_interpolation::<C>(config));
+        self
+    }
+
+    /// Records the snapshots applied to the file at `path`, replacing it.
+    pub fn with_recording<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.recording = Some(path.into());
+        self
+    }
+
+    /// Plays back the recording at `path` instead of receiving the snapshots of a server, which
+    /// makes the `NetworkBundle` unnecessary.
+    pub fn with_replay<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.replay = Some(path.into());
+        self
+    }
+
+    fn source(&self) -> &'static str {
+        if self.replay.is_some() {
+            "replay_playback"
+        } else {
+            "snapshot_receive"
+        }
+    }
+}
+
+fn add_apply<C: NetworkedComponent>(builder: &mut BundleBuilder<'_, '_>, index: u16, after: &str) {
+    builder.add(
+        SnapshotApplySystem::<C>::new(index),
+        &format!("snapshot_apply_{}", index),
+        &[after],
+    );
+}
+
+fn add_interpolation<C: Interpolate>(config: InterpolationConfig) -> Registration {
+    Box::new(move |builder, index, after| {
+        builder.add(
+            SnapshotInterpolationSystem::<C>::new(index, config),
+            &format!("snapshot_interpolate_{}", index),
+            &[after],
+        );
+    })
+}
+
+impl<'a, 'b, E: ReplicationEvent> SystemBundle<'a, 'b> for ReplicationClientBundle<E> {
+    fn build(self, builder: &mut BundleBuilder<'a, 'b>) -> Result<(), Error> {
+        let source = self.source();
+        match self.replay {
+            Some(ref path) => {
+                let reader = ReplayReader::open(path).with_context(|_| {
+                    Error::from_string(format!("Failed to open the replay {}", path.display()))
+                })?;
+                builder.add(ReplayPlaybackSystem::new(reader), source, &[]);
+            }
+            None => builder.add(
+                SnapshotReceiveSystem::<E>::default(),
+                source,
+                &["net_socket"],
+            ),
+        }
+        if let Some(ref path) = self.recording {
+            let writer = ReplayWriter::create(path).with_context(|_| {
+                Error::from_string(format!("Failed to create the recording {}", path.display()))
+            })?;
+            builder.add(ReplayRecordSystem::new(writer), "replay_record", &[source]);
+        }
+        for (index, register) in self.components.into_iter().enumerate() {
+            register(builder, index as u16, source);
+        }
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        "ReplicationClientBundle"
+    }
+
+    fn requirements(&self) -> Vec<BundleRequirement> {
+        if self.replay.is_some() {
+            return Vec::new();
+        }
+        vec![BundleRequirement::new("net_socket", "NetworkBundle")]
+    }
+}
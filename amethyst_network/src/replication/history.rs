@@ -0,0 +1,249 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    time::Duration,
+};
+
+use amethyst_core::{
+    ecs::{
+        Entities, Entity, Join, Read, ReadStorage, Resources, System, SystemData, Write,
+        WriteStorage,
+    },
+    math::Vector3,
+    GlobalTransform, Time,
+};
+
+use crate::tick::NetworkTick;
+
+use super::{interest::position, Replicated, ReplicationServer};
+
+/// How long the transforms are kept by default, more than the latency of the players to
+/// compensate.
+pub const DEFAULT_HISTORY_WINDOW: Duration = Duration::from_secs(1);
+
+struct Frame {
+    tick: u32,
+    time: Duration,
+    transforms: HashMap<Entity, GlobalTransform>,
+}
+
+/// The transforms of the replicated entities at the last ticks of the replication, to validate
+/// the hits of the clients against what they saw.
+///
+/// A client sends the tick of the last snapshot it applied, `ReplicationClient::tick`, with its
+/// shot, and the server checks it against the world as it was at that tick.
+pub struct TransformHistory {
+    frames: VecDeque<Frame>,
+    window: Duration,
+}
+
+impl Default for TransformHistory {
+    fn default() -> Self {
+        TransformHistory::new(DEFAULT_HISTORY_WINDOW)
+    }
+}
+
+impl TransformHistory {
+    /// Creates a history keeping the transforms for `window`.
+    pub fn new(window: Duration) -> Self {
+        TransformHistory {
+            frames: VecDeque::new(),
+            window,
+        }
+    }
+
+    /// Records the transforms of a tick, forgetting the ones older than the window.
+    ///
+    /// If the time went back, the ticks recorded after `time` are forgotten, the history staying
+    /// in the order of time.
+    pub fn record(
+        &mut self,
+        tick: u32,
+        time: Duration,
+        transforms: HashMap<Entity, GlobalTransform>,
+    ) {
+        while self.frames.back().map_or(false, |frame| frame.time > time) {
+            self.frames.pop_back();
+        }
+        self.frames.push_back(Frame {
+            tick,
+            time,
+            transforms,
+        });
+        while self.frames.front().map_or(false, |frame| {
+            time.checked_sub(frame.time)
+                .map_or(false, |age| age > self.window)
+        }) {
+            self.frames.pop_front();
+        }
+    }
+
+    /// The oldest tick still known.
+    pub fn oldest_tick(&self) -> Option<u32> {
+        self.frames.front().map(|frame| frame.tick)
+    }
+
+    /// The transform of an entity at a tick.
+    pub fn transform_at(&self, entity: Entity, tick: u32) -> Option<&GlobalTransform> {
+        self.frame(tick)
+            .and_then(|frame| frame.transforms.get(&entity))
+    }
+
+    /// The position of an entity at a time, interpolated between the ticks around it.
+    pub fn position_at_time(&self, entity: Entity, time: Duration) -> Option<Vector3<f32>> {
+        let after = self.frames.iter().position(|frame| frame.time >= time)?;
+        let after_frame = &self.frames[after];
+        let end = position(after_frame.transforms.get(&entity)?);
+        if after == 0 || after_frame.time == time {
+            return Some(end);
+        }
+        let before_frame = &self.frames[after - 1];
+        let start = match before_frame.transforms.get(&entity) {
+            Some(transform) => position(transform),
+            None => return Some(end),
+        };
+        let span = (after_frame.time - before_frame.time).as_micros() as f32;
+        let alpha = (time - before_frame.time).as_micros() as f32 / span;
+        Some(start.lerp(&end, alpha))
+    }
+
+    /// Moves the entities back to where they were at a tick, returning what undoes it, or `None`
+    /// if the tick isn't in the history.
+    ///
+    /// The entities created after the tick are left where they are.
+    pub fn rewind(
+        &self,
+        tick: u32,
+        transforms: &mut WriteStorage<'_, GlobalTransform>,
+    ) -> Option<Rewound> {
+        let frame = self.frame(tick)?;
+        let mut current = Vec::new();
+        for (entity, past) in &frame.transforms {
+            if let Some(transform) = transforms.get_mut(*entity) {
+                current.push((*entity, transform.clone()));
+                *transform = past.clone();
+            }
+        }
+        Some(Rewound { current })
+    }
+
+    fn frame(&self, tick: u32) -> Option<&Frame> {
+        self.frames.iter().rev().find(|frame| frame.tick == tick)
+    }
+}
+
+/// The transforms of the entities before `TransformHistory::rewind`.
+#[must_use = "The entities stay rewound until restored"]
+pub struct Rewound {
+    current: Vec<(Entity, GlobalTransform)>,
+}
+
+impl Rewound {
+    /// Moves the entities back to the present.
+    pub fn restore(self, transforms: &mut WriteStorage<'_, GlobalTransform>) {
+        for (entity, transform) in self.current {
+            if let Some(current) = transforms.get_mut(entity) {
+                *current = transform;
+            }
+        }
+    }
+}
+
+/// Records the transforms of the replicated entities in the `TransformHistory` every tick.
+pub struct TransformHistorySystem {
+    window: Duration,
+}
+
+impl Default for TransformHistorySystem {
+    fn default() -> Self {
+        TransformHistorySystem::new(DEFAULT_HISTORY_WINDOW)
+    }
+}
+
+impl TransformHistorySystem {
+    /// Keeps the transforms for `window`.
+    pub fn new(window: Duration) -> Self {
+        TransformHistorySystem { window }
+    }
+}
+
+impl<'a> System<'a> for TransformHistorySystem {
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, Replicated>,
+        ReadStorage<'a, GlobalTransform>,
+        Read<'a, ReplicationServer>,
+        Read<'a, Time>,
+        Write<'a, TransformHistory>,
+        Read<'a, NetworkTick>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, replicated, transforms, server, time, mut history, tick): Self::SystemData,
+    ) {
+        if !tick.is_tick() {
+            return;
+        }
+        let frame = (&*entities, &replicated, &transforms)
+            .join()
+            .map(|(entity, _, transform)| (entity, transform.clone()))
+            .collect();
+        history.record(server.tick(), time.absolute_time(), frame);
+    }
+
+    fn setup(&mut self, res: &mut Resources) {
+        res.insert(TransformHistory::new(self.window));
+        Self::SystemData::setup(res);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::{
+        approx::assert_relative_eq,
+        ecs::{Builder, World},
+        math::Matrix4,
+    };
+
+    use super::*;
+
+    fn at(x: f32) -> GlobalTransform {
+        GlobalTransform(Matrix4::new_translation(&Vector3::new(x, 0.0, 0.0)))
+    }
+
+    #[test]
+    fn rewinds_to_a_past_tick() {
+        let mut world = World::new();
+        world.register::<GlobalTransform>();
+        let entity = world.create_entity().with(at(3.0)).build();
+
+        let mut history = TransformHistory::new(Duration::from_millis(250));
+        for tick in 0..4 {
+            let transforms = vec![(entity, at(tick as f32))].into_iter().collect();
+            history.record(
+                tick,
+                Duration::from_millis(100 * u64::from(tick)),
+                transforms,
+            );
+        }
+        // The first tick fell out of the window.
+        assert_eq!(history.oldest_tick(), Some(1));
+        let position = history
+            .position_at_time(entity, Duration::from_millis(150))
+            .unwrap();
+        assert_relative_eq!(position.x, 1.5);
+
+        let mut transforms = world.write_storage::<GlobalTransform>();
+        let rewound = history.rewind(1, &mut transforms).unwrap();
+        assert_relative_eq!(transforms.get(entity).unwrap().0[(0, 3)], 1.0);
+        rewound.restore(&mut transforms);
+        assert_relative_eq!(transforms.get(entity).unwrap().0[(0, 3)], 3.0);
+        assert!(history.rewind(0, &mut transforms).is_none());
+
+        // The time going back forgets the ticks after it.
+        history.record(4, Duration::from_millis(150), HashMap::new());
+        assert_eq!(history.oldest_tick(), Some(1));
+        assert!(history.rewind(2, &mut transforms).is_none());
+        assert!(history.transform_at(entity, 1).is_some());
+    }
+}
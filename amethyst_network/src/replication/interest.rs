@@ -0,0 +1,143 @@
+use std::collections::HashMap;
+
+use amethyst_core::{
+    ecs::{Component, DenseVecStorage, Entity},
+    math::Vector3,
+    GlobalTransform,
+};
+
+use super::{NetworkId, ReplicationState};
+
+/// Added by the server to a connection, the entity the client sees the world from.
+///
+/// The clients of the connections without one, or with an observer without `GlobalTransform`,
+/// receive every entity.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Observer(pub Entity);
+
+impl Component for Observer {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// Decides which entities are replicated to which client.
+///
+/// The entities without `GlobalTransform` are always replicated, so the policies only see
+/// positioned entities. Closures taking the position of the observer and the entity implement
+/// it too.
+pub trait InterestPolicy: Send + Sync + 'static {
+    /// Whether the client observing from `observer` receives the entity `id` at `position`.
+    fn is_relevant(&self, observer: &Vector3<f32>, id: NetworkId, position: &Vector3<f32>) -> bool;
+}
+
+impl<F> InterestPolicy for F
+where
+    F: Fn(&Vector3<f32>, NetworkId, &Vector3<f32>) -> bool + Send + Sync + 'static,
+{
+    fn is_relevant(&self, observer: &Vector3<f32>, id: NetworkId, position: &Vector3<f32>) -> bool {
+        self(observer, id, position)
+    }
+}
+
+/// Replicates the entities within a distance of the observer.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RadiusInterest {
+    /// The distance.
+    pub radius: f32,
+}
+
+impl InterestPolicy for RadiusInterest {
+    fn is_relevant(&self, observer: &Vector3<f32>, _: NetworkId, position: &Vector3<f32>) -> bool {
+        (position - observer).norm_squared() <= self.radius * self.radius
+    }
+}
+
+/// Replicates the entities in the cells of a grid around the cell of the observer.
+///
+/// Unlike with a radius, the entities only enter and leave the area when they or the observer
+/// change cell, which replicates them less often at the edge.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GridInterest {
+    /// The size of the cells.
+    pub cell_size: f32,
+    /// How many cells around the one of the observer are replicated, in every direction.
+    pub range: i32,
+}
+
+impl GridInterest {
+    fn cell(&self, position: &Vector3<f32>) -> Vector3<i32> {
+        position.map(|coordinate| (coordinate / self.cell_size).floor() as i32)
+    }
+}
+
+impl InterestPolicy for GridInterest {
+    fn is_relevant(&self, observer: &Vector3<f32>, _: NetworkId, position: &Vector3<f32>) -> bool {
+        let offset = self.cell(position) - self.cell(observer);
+        offset.iter().all(|offset| offset.abs() <= self.range)
+    }
+}
+
+pub(crate) fn position(transform: &GlobalTransform) -> Vector3<f32> {
+    Vector3::new(
+        transform.0[(0, 3)],
+        transform.0[(1, 3)],
+        transform.0[(2, 3)],
+    )
+}
+
+// The part of the state relevant to an observer.
+pub(crate) fn filter(
+    state: &ReplicationState,
+    policy: &dyn InterestPolicy,
+    observer: &Vector3<f32>,
+    positions: &HashMap<NetworkId, Vector3<f32>>,
+) -> ReplicationState {
+    state
+        .iter()
+        .filter(|(id, _)| {
+            positions.get(id).map_or(true, |position| {
+                policy.is_relevant(observer, **id, position)
+            })
+        })
+        .map(|(id, components)| (*id, components.clone()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn policies_keep_the_nearby_entities() {
+        let mut state = ReplicationState::new();
+        let mut positions = HashMap::new();
+        for (id, x) in &[(0, 1.0), (1, 15.0), (2, 50.0)] {
+            state.insert(NetworkId(*id), Default::default());
+            positions.insert(NetworkId(*id), Vector3::new(*x, 0.0, 0.0));
+        }
+        // Not positioned, so always replicated.
+        state.insert(NetworkId(3), Default::default());
+        let observer = Vector3::new(0.0, 0.0, 0.0);
+
+        let ids = |state: ReplicationState| state.keys().map(|id| id.0).collect::<Vec<_>>();
+        let radius = filter(
+            &state,
+            &RadiusInterest { radius: 10.0 },
+            &observer,
+            &positions,
+        );
+        assert_eq!(ids(radius), vec![0, 3]);
+        let grid = GridInterest {
+            cell_size: 10.0,
+            range: 1,
+        };
+        assert_eq!(
+            ids(filter(&state, &grid, &observer, &positions)),
+            vec![0, 1, 3]
+        );
+        let custom = |_: &Vector3<f32>, id: NetworkId, _: &Vector3<f32>| id.0 == 2;
+        assert_eq!(
+            ids(filter(&state, &custom, &observer, &positions)),
+            vec![2, 3]
+        );
+    }
+}
@@ -0,0 +1,177 @@
+use std::{marker::PhantomData, time::Duration};
+
+use bincode::deserialize;
+use log::error;
+
+use amethyst_core::{
+    ecs::{Read, System, WriteStorage},
+    timing::duration_to_secs_f64,
+    transform::interpolation,
+    Time, Transform,
+};
+
+use super::{client::ReplicationClient, NetworkId, NetworkedComponent, ReplicationState};
+
+/// A networked component the clients can smooth between the snapshots.
+pub trait Interpolate: NetworkedComponent {
+    /// The component between `self`, at 0, and `other`, at 1.
+    fn interpolate(&self, other: &Self, t: f32) -> Self;
+}
+
+impl Interpolate for Transform {
+    fn interpolate(&self, other: &Self, t: f32) -> Self {
+        interpolation::interpolate(self, other, t)
+    }
+}
+
+/// How the clients interpolate the snapshots.
+#[derive(Clone, Debug, PartialEq)]
+pub struct InterpolationConfig {
+    /// How many snapshots the server sends per second, its network tick rate.
+    pub tick_rate: u32,
+    /// How far in the past the entities are shown, so there is a snapshot on each side of the
+    /// time shown. It should be worth a few ticks, to hide the lost snapshots.
+    pub delay: Duration,
+}
+
+impl Default for InterpolationConfig {
+    fn default() -> Self {
+        InterpolationConfig {
+            tick_rate: 20,
+            delay: Duration::from_millis(100),
+        }
+    }
+}
+
+// The component of an entity at a fractional tick, between the states around it.
+fn sample<C: Interpolate>(
+    states: &[(u32, &ReplicationState)],
+    tick: f64,
+    id: NetworkId,
+    index: u16,
+) -> Option<C> {
+    let component = |state: &ReplicationState| -> Option<C> {
+        let data = state.get(&id)?.get(&index)?;
+        match deserialize::<C>(data) {
+            Ok(component) => Some(component),
+            Err(e) => {
+                error!("Failed to deserialize a networked component: {}", e);
+                None
+            }
+        }
+    };
+    let after = states.iter().position(|(t, _)| f64::from(*t) > tick);
+    match after {
+        // Past the last snapshot, the entities stay where it put them.
+        None => states.last().and_then(|(_, state)| component(state)),
+        Some(0) => component(states[0].1),
+        Some(after) => {
+            let (from_tick, from) = states[after - 1];
+            let (to_tick, to) = states[after];
+            match (component(from), component(to)) {
+                (Some(from), Some(to)) => {
+                    let t = (tick - f64::from(from_tick)) / f64::from(to_tick - from_tick);
+                    Some(from.interpolate(&to, t as f32))
+                }
+                (from, to) => to.or(from),
+            }
+        }
+    }
+}
+
+/// Shows a networked component of the replicated entities a little in the past, interpolated
+/// between the snapshots around that time, instead of jumping at each snapshot.
+pub struct SnapshotInterpolationSystem<C> {
+    index: u16,
+    config: InterpolationConfig,
+    // The local time minus the server time of the snapshots, smoothed.
+    offset: Option<f64>,
+    last_tick: Option<u32>,
+    _marker: PhantomData<C>,
+}
+
+impl<C> SnapshotInterpolationSystem<C> {
+    /// Interpolates the component registered at `index`.
+    pub fn new(index: u16, config: InterpolationConfig) -> Self {
+        SnapshotInterpolationSystem {
+            index,
+            config,
+            offset: None,
+            last_tick: None,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, C: Interpolate> System<'a> for SnapshotInterpolationSystem<C> {
+    type SystemData = (
+        Read<'a, Time>,
+        Read<'a, ReplicationClient>,
+        WriteStorage<'a, C>,
+    );
+
+    fn run(&mut self, (time, client, mut components): Self::SystemData) {
+        let now = duration_to_secs_f64(time.absolute_real_time());
+        let interval = 1.0 / f64::from(self.config.tick_rate.max(1));
+        if let Some(tick) = client.tick() {
+            if self.last_tick != Some(tick) {
+                self.last_tick = Some(tick);
+                // The snapshots arrive late by a varying latency, the offset follows it slowly.
+                let offset = now - f64::from(tick) * interval;
+                self.offset = Some(match self.offset {
+                    Some(previous) => previous + (offset - previous) * 0.1,
+                    None => offset,
+                });
+            }
+        }
+        let offset = match self.offset {
+            Some(offset) => offset,
+            None => return,
+        };
+
+        let shown = (now - offset - duration_to_secs_f64(self.config.delay)) / interval;
+        let states = client.states().collect::<Vec<_>>();
+        for (id, entity) in client.entities() {
+            if let Some(component) = sample::<C>(&states, shown, id, self.index) {
+                if let Err(e) = components.insert(entity, component) {
+                    error!("Failed to insert an interpolated component: {}", e);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use amethyst_core::{approx::assert_relative_eq, math::Vector3};
+
+    use super::*;
+
+    fn state(x: f32) -> ReplicationState {
+        let mut transform = Transform::default();
+        transform.set_translation_x(x);
+        let mut components = BTreeMap::new();
+        components.insert(0, bincode::serialize(&transform).unwrap());
+        let mut state = ReplicationState::new();
+        state.insert(NetworkId(1), components);
+        state
+    }
+
+    #[test]
+    fn components_are_interpolated_between_snapshots() {
+        let (first, second) = (state(0.0), state(10.0));
+        let states = vec![(4, &first), (6, &second)];
+        let at = |tick| {
+            sample::<Transform>(&states, tick, NetworkId(1), 0)
+                .unwrap()
+                .translation()
+                .clone()
+        };
+        assert_relative_eq!(at(5.0), Vector3::new(5.0, 0.0, 0.0));
+        assert_relative_eq!(at(3.0), Vector3::new(0.0, 0.0, 0.0));
+        assert_relative_eq!(at(7.5), Vector3::new(10.0, 0.0, 0.0));
+        assert!(sample::<Transform>(&states, 5.0, NetworkId(2), 0).is_none());
+    }
+}
@@ -0,0 +1,401 @@
+//! Replication of the entities of a server to its clients.
+//!
+//! The server marks the entities to replicate with `Replicated`, and both sides register the
+//! networked components on their bundle, in the same order. Every network tick, the server
+//! captures the state of the replicated entities and sends each client the changes since the last
+//! snapshot the client acknowledged. The client rebuilds the state from its copy of that
+//! baseline, acknowledges it, and applies it to its own entities, mapped to the server ones
+//! through their `NetworkId`, or interpolates the components between the last snapshots. The
+//! snapshots applied can be recorded, and played back later in place of a server.
+
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::Arc,
+};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use shrev::ReaderId;
+
+use amethyst_core::ecs::{Component, DenseVecStorage, NullStorage};
+
+pub use self::{
+    client::{
+        ReplicationClient, ReplicationClientBundle, SnapshotApplySystem, SnapshotReceiveSystem,
+    },
+    history::{Rewound, TransformHistory, TransformHistorySystem, DEFAULT_HISTORY_WINDOW},
+    interest::{GridInterest, InterestPolicy, Observer, RadiusInterest},
+    interpolation::{Interpolate, InterpolationConfig, SnapshotInterpolationSystem},
+    replay::{
+        ReplayFrame, ReplayPlayback, ReplayPlaybackSystem, ReplayReader, ReplayRecordSystem,
+        ReplayWriter,
+    },
+    server::{
+        ReplicationServer, ReplicationServerBundle, SnapshotCaptureSystem, SnapshotSendSystem,
+        SnapshotStartSystem,
+    },
+};
+
+use crate::NetEvent;
+
+mod client;
+mod history;
+mod interest;
+mod interpolation;
+mod replay;
+mod server;
+
+/// How many snapshots are kept to serve as baselines.
+pub const SNAPSHOT_HISTORY: usize = 64;
+
+/// The identifier the server gives a replicated entity, the same on every client.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct NetworkId(pub u64);
+
+impl Component for NetworkId {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// Marks an entity of the server to be replicated to the clients.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Replicated;
+
+impl Component for Replicated {
+    type Storage = NullStorage<Self>;
+}
+
+/// A component that can be sent over the network.
+pub trait NetworkedComponent: Component + Clone + Serialize + DeserializeOwned {}
+
+impl<T> NetworkedComponent for T where T: Component + Clone + Serialize + DeserializeOwned {}
+
+/// The serialized components of the replicated entities, by the index of their registration.
+pub type ReplicationState = BTreeMap<NetworkId, BTreeMap<u16, Vec<u8>>>;
+
+/// The changes of the components of an entity since the baseline.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EntityDelta {
+    /// The entity.
+    pub id: NetworkId,
+    /// The components added or changed, by index.
+    pub changed: Vec<(u16, Vec<u8>)>,
+    /// The indices of the components removed.
+    pub removed: Vec<u16>,
+}
+
+/// The changes of the replicated entities between a baseline and a tick.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// The tick of the server the snapshot was taken at.
+    pub tick: u32,
+    /// The tick of the snapshot the changes are relative to, `None` if it is a full snapshot.
+    pub baseline: Option<u32>,
+    /// The entities added or changed.
+    pub changed: Vec<EntityDelta>,
+    /// The entities removed.
+    pub removed: Vec<NetworkId>,
+}
+
+impl Snapshot {
+    /// Computes the changes from `baseline` to `state`.
+    pub fn delta(
+        tick: u32,
+        baseline: Option<(u32, &ReplicationState)>,
+        state: &ReplicationState,
+    ) -> Snapshot {
+        let empty = ReplicationState::new();
+        let (baseline_tick, old) = match baseline {
+            Some((tick, old)) => (Some(tick), old),
+            None => (None, &empty),
+        };
+        let no_components = BTreeMap::new();
+
+        let mut changed = Vec::new();
+        for (id, components) in state {
+            let old_components = old.get(id).unwrap_or(&no_components);
+            let delta = EntityDelta {
+                id: *id,
+                changed: components
+                    .iter()
+                    .filter(|(index, data)| old_components.get(index) != Some(data))
+                    .map(|(index, data)| (*index, data.clone()))
+                    .collect(),
+                removed: old_components
+                    .keys()
+                    .filter(|index| !components.contains_key(index))
+                    .cloned()
+                    .collect(),
+            };
+            // New entities are sent even without components, so that they are created.
+            if !delta.changed.is_empty() || !delta.removed.is_empty() || !old.contains_key(id) {
+                changed.push(delta);
+            }
+        }
+
+        Snapshot {
+            tick,
+            baseline: baseline_tick,
+            changed,
+            removed: old
+                .keys()
+                .filter(|id| !state.contains_key(id))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// Rebuilds the state of the server from the baseline the snapshot is relative to.
+    pub fn apply(&self, baseline: &ReplicationState) -> ReplicationState {
+        let mut state = baseline.clone();
+        for id in &self.removed {
+            state.remove(id);
+        }
+        for delta in &self.changed {
+            let components = state.entry(delta.id).or_insert_with(BTreeMap::new);
+            for index in &delta.removed {
+                components.remove(index);
+            }
+            for (index, data) in &delta.changed {
+                components.insert(*index, data.clone());
+            }
+        }
+        state
+    }
+}
+
+/// The messages exchanged to replicate the entities.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ReplicationMessage {
+    /// The server sends a snapshot to a client.
+    Snapshot(Snapshot),
+    /// The client acknowledges the snapshot of a tick, which becomes its baseline.
+    Ack(u32),
+}
+
+/// A network event type the replication messages can be sent as.
+///
+/// Implement it for the event type of the `NetConnection`s to replicate over them, or use
+/// `ReplicationMessage` itself.
+pub trait ReplicationEvent: Send + Sync + 'static {
+    /// Wraps a replication message.
+    fn from_message(message: ReplicationMessage) -> Self;
+
+    /// Returns the replication message of the event, if it is one.
+    fn as_message(&self) -> Option<&ReplicationMessage>;
+}
+
+impl ReplicationEvent for ReplicationMessage {
+    fn from_message(message: ReplicationMessage) -> Self {
+        message
+    }
+
+    fn as_message(&self) -> Option<&ReplicationMessage> {
+        Some(self)
+    }
+}
+
+/// The replication state of a connection, added by the replication systems.
+pub struct ReplicationPeer<E: 'static> {
+    reader: ReaderId<NetEvent<E>>,
+    // The last snapshot acknowledged by the client, or received from the server.
+    tick: Option<u32>,
+    // The states sent to the client, which only has the entities it is interested in.
+    sent: VecDeque<(u32, Arc<ReplicationState>)>,
+}
+
+impl<E: 'static> ReplicationPeer<E> {
+    fn new(reader: ReaderId<NetEvent<E>>) -> Self {
+        ReplicationPeer {
+            reader,
+            tick: None,
+            sent: VecDeque::new(),
+        }
+    }
+
+    fn baseline(&self) -> Option<(u32, &ReplicationState)> {
+        let tick = self.tick?;
+        self.sent
+            .iter()
+            .find(|(t, _)| *t == tick)
+            .map(|(_, state)| (tick, &**state))
+    }
+}
+
+impl<E: Send + Sync + 'static> Component for ReplicationPeer<E> {
+    type Storage = DenseVecStorage<Self>;
+}
+
+// Whether the tick `a` comes after `b`, allowing the ticks to wrap around.
+pub(crate) fn is_newer(a: u32, b: Option<u32>) -> bool {
+    b.map_or(true, |b| (a.wrapping_sub(b) as i32) > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::ecs::{Builder, Dispatcher, DispatcherBuilder, Entity, World};
+
+    use crate::{channel::UNRELIABLE_CHANNEL, NetConnection, NetPacket};
+
+    use super::*;
+
+    fn state(entities: &[(u64, &[(u16, u8)])]) -> ReplicationState {
+        entities
+            .iter()
+            .map(|(id, components)| {
+                let components = components
+                    .iter()
+                    .map(|(index, data)| (*index, vec![*data]))
+                    .collect();
+                (NetworkId(*id), components)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn delta_rebuilds_the_state() {
+        let baseline = state(&[(1, &[(0, 1), (1, 1)]), (2, &[(0, 2)]), (3, &[])]);
+        let current = state(&[(1, &[(0, 5)]), (3, &[]), (4, &[(1, 4)])]);
+
+        let snapshot = Snapshot::delta(8, Some((5, &baseline)), &current);
+        assert_eq!(snapshot.baseline, Some(5));
+        assert_eq!(snapshot.removed, vec![NetworkId(2)]);
+        // The unchanged entity isn't sent.
+        assert_eq!(snapshot.changed.len(), 2);
+        assert_eq!(snapshot.apply(&baseline), current);
+
+        let full = Snapshot::delta(8, None, &current);
+        assert_eq!(full.changed.len(), 3);
+        assert_eq!(full.apply(&ReplicationState::new()), current);
+    }
+
+    #[test]
+    fn ticks_wrap_around() {
+        assert!(is_newer(1, None));
+        assert!(is_newer(2, Some(1)));
+        assert!(!is_newer(1, Some(1)));
+        assert!(is_newer(0, Some(u32::max_value())));
+    }
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct Health(u32);
+
+    impl Component for Health {
+        type Storage = DenseVecStorage<Self>;
+    }
+
+    fn peer(world: &mut World, dispatcher: &mut Dispatcher<'_, '_>) -> Entity {
+        dispatcher.setup(&mut world.res);
+        world
+            .create_entity()
+            .with(NetConnection::<ReplicationMessage>::new(
+                "127.0.0.1:3456".parse().unwrap(),
+            ))
+            .build()
+    }
+
+    fn sent(world: &mut World, connection: Entity) -> Vec<ReplicationMessage> {
+        world
+            .write_storage::<NetConnection<ReplicationMessage>>()
+            .get_mut(connection)
+            .unwrap()
+            .send_buffer_early_read()
+            .filter_map(|event| match event {
+                NetEvent::Packet(packet) => Some(packet.content().clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn receive(world: &mut World, connection: Entity, messages: Vec<ReplicationMessage>) {
+        let mut connections = world.write_storage::<NetConnection<ReplicationMessage>>();
+        let connection = connections.get_mut(connection).unwrap();
+        for message in messages {
+            connection
+                .receive_buffer
+                .single_write(NetEvent::Packet(NetPacket::on_channel(
+                    message,
+                    UNRELIABLE_CHANNEL,
+                )));
+        }
+    }
+
+    fn run(world: &mut World, dispatcher: &mut Dispatcher<'_, '_>) {
+        dispatcher.dispatch(&world.res);
+        world.maintain();
+    }
+
+    #[test]
+    fn clients_follow_the_entities_of_the_server() {
+        let mut server = World::new();
+        let mut server_systems = DispatcherBuilder::new()
+            .with_thread_local(SnapshotStartSystem)
+            .with_thread_local(SnapshotCaptureSystem::<Health>::new(0))
+            .with_thread_local(SnapshotSendSystem::<ReplicationMessage>::default())
+            .build();
+        let to_client = peer(&mut server, &mut server_systems);
+        let entity = server
+            .create_entity()
+            .with(Replicated)
+            .with(Health(10))
+            .build();
+
+        let mut client = World::new();
+        let mut client_systems = DispatcherBuilder::new()
+            .with_thread_local(SnapshotReceiveSystem::<ReplicationMessage>::default())
+            .with_thread_local(SnapshotApplySystem::<Health>::new(0))
+            .build();
+        let to_server = peer(&mut client, &mut client_systems);
+        run(&mut client, &mut client_systems);
+
+        // The first snapshot has everything.
+        run(&mut server, &mut server_systems);
+        let snapshots = sent(&mut server, to_client);
+        match snapshots.as_slice() {
+            [ReplicationMessage::Snapshot(snapshot)] => assert_eq!(snapshot.baseline, None),
+            _ => panic!("Expected a snapshot, got {:?}", snapshots),
+        }
+        receive(&mut client, to_server, snapshots);
+        run(&mut client, &mut client_systems);
+        let replica = client
+            .read_resource::<ReplicationClient>()
+            .entity(NetworkId(0))
+            .unwrap();
+        assert_eq!(
+            client.read_storage::<Health>().get(replica),
+            Some(&Health(10))
+        );
+
+        // Once acknowledged, it is the baseline of the next one.
+        let acks = sent(&mut client, to_server);
+        assert_eq!(acks, vec![ReplicationMessage::Ack(1)]);
+        receive(&mut server, to_client, acks);
+        server
+            .write_storage::<Health>()
+            .insert(entity, Health(12))
+            .unwrap();
+        run(&mut server, &mut server_systems);
+        let snapshots = sent(&mut server, to_client);
+        match snapshots.as_slice() {
+            [ReplicationMessage::Snapshot(snapshot)] => assert_eq!(snapshot.baseline, Some(1)),
+            _ => panic!("Expected a snapshot, got {:?}", snapshots),
+        }
+        receive(&mut client, to_server, snapshots);
+        run(&mut client, &mut client_systems);
+        assert_eq!(
+            client.read_storage::<Health>().get(replica),
+            Some(&Health(12))
+        );
+
+        server.delete_entity(entity).unwrap();
+        run(&mut server, &mut server_systems);
+        let snapshots = sent(&mut server, to_client);
+        receive(&mut client, to_server, snapshots);
+        run(&mut client, &mut client_systems);
+        assert_eq!(
+            client
+                .read_resource::<ReplicationClient>()
+                .entity(NetworkId(0)),
+            None
+        );
+        assert!(!client.is_alive(replica));
+    }
+}
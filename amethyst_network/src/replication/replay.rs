@@ -0,0 +1,370 @@
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    marker::PhantomData,
+    path::Path,
+    sync::Arc,
+    time::Duration,
+};
+
+use bincode::{deserialize, serialize};
+use log::error;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use amethyst_core::{
+    ecs::{
+        Entities, Read as ReadResource, Resources, System, SystemData, Write as WriteResource,
+        WriteStorage,
+    },
+    timing::{duration_to_nanos, nanos_to_duration},
+    Time,
+};
+
+use crate::error::Result;
+
+use super::{client::ReplicationClient, NetworkId, ReplicationState, Snapshot};
+
+// The start of every recording, followed by the version of the format.
+const MAGIC: &[u8; 8] = b"AMREPLAY";
+const VERSION: u16 = 1;
+// The largest frame read back, so a corrupt length doesn't allocate all the memory.
+const MAX_FRAME_LENGTH: u32 = 16 * 1024 * 1024;
+// How often the record system flushes the recording, in recorded time.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A snapshot of a recording, with the time it was applied at since the recording started.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReplayFrame {
+    /// The time since the recording started.
+    pub time: Duration,
+    /// The changes since the previous frame, the first frame being a full snapshot.
+    pub snapshot: Snapshot,
+}
+
+/// Writes the frames of a recording to a file, each prefixed by its length.
+///
+/// The frames are buffered, and written to the file on `flush` or when the writer is dropped.
+///
+/// The frames can be anything serializable, so the ticks of a `LockstepSession` can be recorded
+/// the same way as the snapshots of the replication.
+pub struct ReplayWriter<T> {
+    file: BufWriter<File>,
+    _marker: PhantomData<T>,
+}
+
+impl<T: Serialize> ReplayWriter<T> {
+    /// Creates the recording at `path`, replacing it.
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+        file.write_all(MAGIC)?;
+        file.write_all(&VERSION.to_be_bytes())?;
+        Ok(ReplayWriter {
+            file,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Appends a frame.
+    pub fn write(&mut self, frame: &T) -> Result<()> {
+        let data = serialize(frame)?;
+        self.file.write_all(&(data.len() as u32).to_be_bytes())?;
+        self.file.write_all(&data)?;
+        Ok(())
+    }
+
+    /// Writes the buffered frames to the file.
+    pub fn flush(&mut self) -> Result<()> {
+        self.file.flush()?;
+        Ok(())
+    }
+}
+
+/// Reads the frames of a recording written by a `ReplayWriter`.
+pub struct ReplayReader<T> {
+    file: BufReader<File>,
+    _marker: PhantomData<T>,
+}
+
+impl<T: DeserializeOwned> ReplayReader<T> {
+    /// Opens the recording at `path`.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let mut file = BufReader::new(File::open(path)?);
+        let mut header = [0; 10];
+        file.read_exact(&mut header)?;
+        if &header[..8] != MAGIC || header[8..] != VERSION.to_be_bytes() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Not a recording").into());
+        }
+        Ok(ReplayReader {
+            file,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Reads the next frame, `None` at the end of the recording.
+    pub fn next_frame(&mut self) -> Result<Option<T>> {
+        let mut length = [0; 4];
+        match self.file.read_exact(&mut length) {
+            Ok(()) => {}
+            Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e.into()),
+        }
+        let length = u32::from_be_bytes(length);
+        if length > MAX_FRAME_LENGTH {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Frame too large").into());
+        }
+        let mut data = vec![0; length as usize];
+        self.file.read_exact(&mut data)?;
+        Ok(Some(deserialize(&data)?))
+    }
+}
+
+/// Records the snapshots the `ReplicationClient` applies, flushing the recording every second.
+pub struct ReplayRecordSystem {
+    writer: ReplayWriter<ReplayFrame>,
+    start: Option<Duration>,
+    flushed: Duration,
+    last: Option<(u32, Arc<ReplicationState>)>,
+}
+
+impl ReplayRecordSystem {
+    /// Records to the given writer.
+    pub fn new(writer: ReplayWriter<ReplayFrame>) -> Self {
+        ReplayRecordSystem {
+            writer,
+            start: None,
+            flushed: Duration::from_secs(0),
+            last: None,
+        }
+    }
+}
+
+impl<'a> System<'a> for ReplayRecordSystem {
+    type SystemData = (ReadResource<'a, Time>, ReadResource<'a, ReplicationClient>);
+
+    fn run(&mut self, (time, client): Self::SystemData) {
+        let (tick, state) = match (client.tick(), client.update()) {
+            (Some(tick), Some(state)) => (tick, state.clone()),
+            _ => return,
+        };
+        let now = time.absolute_real_time();
+        let start = *self.start.get_or_insert(now);
+        let frame = ReplayFrame {
+            time: now - start,
+            snapshot: Snapshot::delta(
+                tick,
+                self.last.as_ref().map(|(tick, state)| (*tick, &**state)),
+                &state,
+            ),
+        };
+        if let Err(e) = self.writer.write(&frame) {
+            error!("Failed to record a snapshot: {}", e);
+        }
+        if frame.time >= self.flushed + FLUSH_INTERVAL {
+            if let Err(e) = self.writer.flush() {
+                error!("Failed to flush the recording: {}", e);
+            }
+            self.flushed = frame.time;
+        }
+        self.last = Some((tick, state));
+    }
+}
+
+/// Controls the playback of a replay.
+#[derive(Clone, Debug)]
+pub struct ReplayPlayback {
+    /// How fast the replay is played, 1 being the recorded speed.
+    pub speed: f64,
+    /// Whether the replay is paused.
+    pub paused: bool,
+    time: Duration,
+    finished: bool,
+}
+
+impl Default for ReplayPlayback {
+    fn default() -> Self {
+        ReplayPlayback {
+            speed: 1.0,
+            paused: false,
+            time: Duration::from_secs(0),
+            finished: false,
+        }
+    }
+}
+
+impl ReplayPlayback {
+    /// The time of the replay played so far.
+    pub fn time(&self) -> Duration {
+        self.time
+    }
+
+    /// Whether every frame of the replay was played.
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+}
+
+/// Feeds the frames of a recording to the `ReplicationClient`, in place of the
+/// `SnapshotReceiveSystem`, as they were received when recording.
+pub struct ReplayPlaybackSystem {
+    reader: ReplayReader<ReplayFrame>,
+    next: Option<ReplayFrame>,
+    state: Arc<ReplicationState>,
+}
+
+impl ReplayPlaybackSystem {
+    /// Plays back the frames of the given reader.
+    pub fn new(reader: ReplayReader<ReplayFrame>) -> Self {
+        ReplayPlaybackSystem {
+            reader,
+            next: None,
+            state: Arc::new(ReplicationState::new()),
+        }
+    }
+
+    // Takes the next frame if it was recorded before `time`.
+    fn frame_before(&mut self, time: Duration) -> Option<ReplayFrame> {
+        if self.next.is_none() {
+            self.next = match self.reader.next_frame() {
+                Ok(frame) => frame,
+                Err(e) => {
+                    error!("Failed to read the replay: {}", e);
+                    None
+                }
+            };
+        }
+        match self.next {
+            Some(ref frame) if frame.time <= time => self.next.take(),
+            _ => None,
+        }
+    }
+}
+
+impl<'a> System<'a> for ReplayPlaybackSystem {
+    type SystemData = (
+        Entities<'a>,
+        ReadResource<'a, Time>,
+        WriteResource<'a, ReplayPlayback>,
+        WriteResource<'a, ReplicationClient>,
+        WriteStorage<'a, NetworkId>,
+    );
+
+    fn run(&mut self, (entities, time, mut playback, mut client, mut ids): Self::SystemData) {
+        client.clear_update();
+        if playback.paused || playback.finished {
+            return;
+        }
+        let delta = time.delta_real_time();
+        playback.time +=
+            nanos_to_duration((duration_to_nanos(delta) as f64 * playback.speed.max(0.0)) as u64);
+
+        let mut latest = None;
+        while let Some(frame) = self.frame_before(playback.time) {
+            self.state = Arc::new(frame.snapshot.apply(&self.state));
+            latest = Some(frame.snapshot.tick);
+        }
+        if let Some(tick) = latest {
+            client.receive(tick, self.state.clone(), &entities, &mut ids);
+        }
+        playback.finished = self.next.is_none();
+    }
+
+    fn setup(&mut self, res: &mut Resources) {
+        Self::SystemData::setup(res);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeMap, env, fs, path::PathBuf, process};
+
+    use amethyst_core::ecs::{Join, ReadStorage, RunNow, World};
+
+    use super::*;
+
+    // Two frames, the entity of the first one removed by the second 50 ms later.
+    fn frames() -> Vec<ReplayFrame> {
+        let mut state = ReplicationState::new();
+        state.insert(NetworkId(1), BTreeMap::new());
+        vec![
+            ReplayFrame {
+                time: Duration::from_secs(0),
+                snapshot: Snapshot::delta(3, None, &state),
+            },
+            ReplayFrame {
+                time: Duration::from_millis(50),
+                snapshot: Snapshot::delta(4, Some((3, &state)), &ReplicationState::new()),
+            },
+        ]
+    }
+
+    fn record(name: &str, frames: &[ReplayFrame]) -> PathBuf {
+        let path = env::temp_dir().join(format!("amethyst-{}-{}.replay", process::id(), name));
+        let mut writer = ReplayWriter::create(&path).unwrap();
+        for frame in frames {
+            writer.write(frame).unwrap();
+        }
+        path
+    }
+
+    #[test]
+    fn frames_are_read_back() {
+        let frames = frames();
+        let path = record("read_back", &frames);
+
+        let mut reader = ReplayReader::<ReplayFrame>::open(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(reader.next_frame().unwrap(), Some(frames[0].clone()));
+        assert_eq!(reader.next_frame().unwrap(), Some(frames[1].clone()));
+        assert_eq!(reader.next_frame().unwrap(), None);
+    }
+
+    #[test]
+    fn oversized_frames_are_refused() {
+        let path = record("oversized", &[]);
+        let mut file = fs::OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&u32::max_value().to_be_bytes()).unwrap();
+        drop(file);
+
+        let mut reader = ReplayReader::<ReplayFrame>::open(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(reader.next_frame().is_err());
+    }
+
+    #[test]
+    fn frames_are_played_back_as_recorded() {
+        let path = record("playback", &frames());
+        let mut system = ReplayPlaybackSystem::new(ReplayReader::open(&path).unwrap());
+        fs::remove_file(&path).unwrap();
+        let mut world = World::new();
+        System::setup(&mut system, &mut world.res);
+        let mut step = |world: &mut World, millis| {
+            world
+                .write_resource::<Time>()
+                .set_delta_time(Duration::from_millis(millis));
+            system.run_now(&world.res);
+            world.maintain();
+        };
+        let replicated = |world: &World| {
+            world
+                .system_data::<ReadStorage<'_, NetworkId>>()
+                .join()
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+
+        step(&mut world, 10);
+        assert_eq!(world.read_resource::<ReplicationClient>().tick(), Some(3));
+        assert_eq!(replicated(&world), vec![NetworkId(1)]);
+        assert!(!world.read_resource::<ReplayPlayback>().is_finished());
+
+        world.write_resource::<ReplayPlayback>().paused = true;
+        step(&mut world, 100);
+        assert_eq!(world.read_resource::<ReplicationClient>().tick(), Some(3));
+
+        world.write_resource::<ReplayPlayback>().paused = false;
+        step(&mut world, 40);
+        assert_eq!(world.read_resource::<ReplicationClient>().tick(), Some(4));
+        assert_eq!(replicated(&world), vec![]);
+        assert!(world.read_resource::<ReplayPlayback>().is_finished());
+    }
+}
@@ -0,0 +1,337 @@
+use std::{collections::HashMap, marker::PhantomData, mem, sync::Arc, time::Duration};
+
+use bincode::serialize;
+use log::error;
+
+use amethyst_core::{
+    bundle::{BundleBuilder, BundleRequirement, SystemBundle},
+    ecs::{Entities, Join, Read, ReadStorage, Resources, System, SystemData, Write, WriteStorage},
+    GlobalTransform,
+};
+
+use crate::tick::NetworkTick;
+use amethyst_error::Error;
+
+use crate::{channel::UNRELIABLE_CHANNEL, ConnectionState, NetConnection, NetEvent, NetPacket};
+
+use super::{
+    history::TransformHistorySystem,
+    interest::{filter, position, InterestPolicy, Observer},
+    is_newer, NetworkId, NetworkedComponent, Replicated, ReplicationEvent, ReplicationMessage,
+    ReplicationPeer, ReplicationState, Snapshot, SNAPSHOT_HISTORY,
+};
+
+/// The replication state of the server.
+#[derive(Default)]
+pub struct ReplicationServer {
+    tick: u32,
+    next_id: u64,
+    current: ReplicationState,
+}
+
+impl ReplicationServer {
+    /// The tick of the snapshot being taken.
+    pub fn tick(&self) -> u32 {
+        self.tick
+    }
+
+    /// The state captured this frame.
+    pub fn current(&self) -> &ReplicationState {
+        &self.current
+    }
+}
+
+/// Gives a `NetworkId` to the new replicated entities and starts the snapshot of the frame.
+#[derive(Default)]
+pub struct SnapshotStartSystem;
+
+impl<'a> System<'a> for SnapshotStartSystem {
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, Replicated>,
+        WriteStorage<'a, NetworkId>,
+        Write<'a, ReplicationServer>,
+        Read<'a, NetworkTick>,
+    );
+
+    fn run(&mut self, (entities, replicated, mut ids, mut server, tick): Self::SystemData) {
+        // The snapshots are only taken on the network ticks.
+        if !tick.is_tick() {
+            return;
+        }
+        server.tick = server.tick.wrapping_add(1);
+        server.current.clear();
+
+        let new = (&*entities, &replicated, !&ids)
+            .join()
+            .map(|(entity, _, _)| entity)
+            .collect::<Vec<_>>();
+        for entity in new {
+            let id = NetworkId(server.next_id);
+            server.next_id += 1;
+            ids.insert(entity, id)
+                .expect("Unreachable: The entity is alive");
+        }
+
+        for (_, id) in (&replicated, &ids).join() {
+            server.current.insert(*id, Default::default());
+        }
+    }
+}
+
+/// Serializes a networked component of the replicated entities into the snapshot.
+pub struct SnapshotCaptureSystem<C> {
+    index: u16,
+    _marker: PhantomData<C>,
+}
+
+impl<C> SnapshotCaptureSystem<C> {
+    /// Captures the component registered at `index`.
+    pub fn new(index: u16) -> Self {
+        SnapshotCaptureSystem {
+            index,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, C: NetworkedComponent> System<'a> for SnapshotCaptureSystem<C> {
+    type SystemData = (
+        ReadStorage<'a, Replicated>,
+        ReadStorage<'a, NetworkId>,
+        ReadStorage<'a, C>,
+        Write<'a, ReplicationServer>,
+        Read<'a, NetworkTick>,
+    );
+
+    fn run(&mut self, (replicated, ids, components, mut server, tick): Self::SystemData) {
+        if !tick.is_tick() {
+            return;
+        }
+        for (_, id, component) in (&replicated, &ids, &components).join() {
+            match serialize(component) {
+                Ok(data) => {
+                    server
+                        .current
+                        .entry(*id)
+                        .or_insert_with(Default::default)
+                        .insert(self.index, data);
+                }
+                Err(e) => error!("Failed to serialize a networked component: {}", e),
+            }
+        }
+    }
+}
+
+/// Sends each connection the changes since the last snapshot it acknowledged.
+///
+/// With an `InterestPolicy`, the clients only receive the entities relevant to their `Observer`.
+pub struct SnapshotSendSystem<E> {
+    interest: Option<Box<dyn InterestPolicy>>,
+    _marker: PhantomData<E>,
+}
+
+impl<E> Default for SnapshotSendSystem<E> {
+    fn default() -> Self {
+        SnapshotSendSystem {
+            interest: None,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<E> SnapshotSendSystem<E> {
+    /// Only sends the clients the entities relevant to their `Observer`.
+    pub fn with_interest<P: InterestPolicy>(policy: P) -> Self {
+        SnapshotSendSystem {
+            interest: Some(Box::new(policy)),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, E: ReplicationEvent> System<'a> for SnapshotSendSystem<E> {
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, NetConnection<E>>,
+        WriteStorage<'a, ReplicationPeer<E>>,
+        ReadStorage<'a, NetworkId>,
+        ReadStorage<'a, Observer>,
+        ReadStorage<'a, GlobalTransform>,
+        Write<'a, ReplicationServer>,
+        Read<'a, NetworkTick>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            mut connections,
+            mut peers,
+            ids,
+            observers,
+            transforms,
+            mut server,
+            network_tick,
+        ): Self::SystemData,
+    ) {
+        let new = (&*entities, &mut connections, !&peers)
+            .join()
+            .map(|(entity, connection, _)| (entity, connection.receive_buffer.register_reader()))
+            .collect::<Vec<_>>();
+        for (entity, reader) in new {
+            peers
+                .insert(entity, ReplicationPeer::new(reader))
+                .expect("Unreachable: The entity is alive");
+        }
+
+        let positions = match self.interest {
+            Some(_) => (&ids, &transforms)
+                .join()
+                .map(|(id, transform)| (*id, position(transform)))
+                .collect(),
+            None => HashMap::new(),
+        };
+        let tick = server.tick;
+        let state = Arc::new(mem::replace(&mut server.current, ReplicationState::new()));
+
+        for (connection, peer, observer) in (&mut connections, &mut peers, observers.maybe()).join()
+        {
+            for event in connection.receive_buffer.read(&mut peer.reader) {
+                if let NetEvent::Packet(packet) = event {
+                    if let Some(ReplicationMessage::Ack(tick)) = packet.content().as_message() {
+                        if is_newer(*tick, peer.tick) {
+                            peer.tick = Some(*tick);
+                        }
+                    }
+                }
+            }
+            if connection.state == ConnectionState::Disconnected || !network_tick.is_tick() {
+                continue;
+            }
+
+            let observer = observer
+                .and_then(|observer| transforms.get(observer.0))
+                .map(position);
+            let relevant = match (&self.interest, observer) {
+                (Some(policy), Some(observer)) => {
+                    Arc::new(filter(&state, &**policy, &observer, &positions))
+                }
+                _ => state.clone(),
+            };
+
+            // Once the baseline falls out of the history, the client gets a full snapshot.
+            let snapshot = Snapshot::delta(tick, peer.baseline(), &relevant);
+            peer.sent.push_back((tick, relevant));
+            while peer.sent.len() > SNAPSHOT_HISTORY {
+                peer.sent.pop_front();
+            }
+            connection
+                .send_buffer
+                .single_write(NetEvent::Packet(NetPacket::on_channel(
+                    E::from_message(ReplicationMessage::Snapshot(snapshot)),
+                    UNRELIABLE_CHANNEL,
+                )));
+        }
+    }
+
+    fn setup(&mut self, res: &mut Resources) {
+        Self::SystemData::setup(res);
+    }
+}
+
+type Registration = Box<dyn for<'a, 'b, 'c> FnOnce(&'c mut BundleBuilder<'a, 'b>, u16)>;
+
+/// Adds the systems replicating the entities marked with `Replicated` to the clients.
+///
+/// The clients must register the same components in the same order on their
+/// `ReplicationClientBundle`.
+pub struct ReplicationServerBundle<E> {
+    components: Vec<Registration>,
+    interest: Option<Box<dyn InterestPolicy>>,
+    history: Option<Duration>,
+    _marker: PhantomData<E>,
+}
+
+impl<E> Default for ReplicationServerBundle<E> {
+    fn default() -> Self {
+        ReplicationServerBundle {
+            components: Vec::new(),
+            interest: None,
+            history: None,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<E> ReplicationServerBundle<E> {
+    /// Creates a bundle without networked components.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Marks a component as networked.
+    pub fn with_component<C: NetworkedComponent>(mut self) -> Self {
+        self.components.push(Box::new(add_capture::<C>));
+        self
+    }
+
+    /// Only replicates to each client the entities relevant to its `Observer`.
+    pub fn with_interest<P: InterestPolicy>(mut self, policy: P) -> Self {
+        self.interest = Some(Box::new(policy));
+        self
+    }
+
+    /// Keeps the transforms of the replicated entities of the last ticks in the
+    /// `TransformHistory`, to validate the hits of the clients against what they saw.
+    pub fn with_lag_compensation(mut self, window: Duration) -> Self {
+        self.history = Some(window);
+        self
+    }
+}
+
+fn add_capture<C: NetworkedComponent>(builder: &mut BundleBuilder<'_, '_>, index: u16) {
+    builder.add(
+        SnapshotCaptureSystem::<C>::new(index),
+        &format!("snapshot_capture_{}", index),
+        &["snapshot_start"],
+    );
+}
+
+impl<'a, 'b, E: ReplicationEvent> SystemBundle<'a, 'b> for ReplicationServerBundle<E> {
+    fn build(self, builder: &mut BundleBuilder<'a, 'b>) -> Result<(), Error> {
+        builder.add(SnapshotStartSystem, "snapshot_start", &[]);
+        if let Some(window) = self.history {
+            builder.add(
+                TransformHistorySystem::new(window),
+                "transform_history",
+                &["snapshot_start"],
+            );
+        }
+        let captures = (0..self.components.len())
+            .map(|index| format!("snapshot_capture_{}", index))
+            .collect::<Vec<_>>();
+        for (index, register) in self.components.into_iter().enumerate() {
+            register(builder, index as u16);
+        }
+        let mut dependencies = captures
+            .iter()
+            .map(|name| name.as_str())
+            .collect::<Vec<_>>();
+        dependencies.push("snapshot_start");
+        let send = SnapshotSendSystem::<E> {
+            interest: self.interest,
+            _marker: PhantomData,
+        };
+        builder.add(send, "snapshot_send", &dependencies);
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        "ReplicationServerBundle"
+    }
+
+    fn requirements(&self) -> Vec<BundleRequirement> {
+        vec![BundleRequirement::new("net_socket", "NetworkBundle")]
+    }
+}
@@ -0,0 +1,436 @@
+//! Typed messages sent over the network events.
+//!
+//! The message types are registered on the `RpcBundle` of both sides, sent with the
+//! `MessageSender` resource, and received through an `EventChannel<Received<T>>` per type. A type
+//! is identified by the `ID` of its `Message` implementation, which both sides must agree on.
+
+use std::{collections::HashMap, marker::PhantomData, net::SocketAddr};
+
+use bincode::{deserialize, serialize};
+use log::{error, warn};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use shrev::{EventChannel, ReaderId};
+
+use amethyst_core::{
+    bundle::{BundleBuilder, BundleRequirement, SystemBundle},
+    ecs::{
+        Component, DenseVecStorage, Entities, Entity, Join, Read, Resources, System, SystemData,
+        Write, WriteStorage,
+    },
+};
+use amethyst_error::Error;
+
+use crate::{
+    channel::RELIABLE_CHANNEL, error::Result, ConnectionState, NetConnection, NetEvent, NetPacket,
+};
+
+/// A message type that can be sent over the network.
+///
+/// ```rust,ignore
+/// #[derive(Serialize, Deserialize)]
+/// struct Chat(String);
+///
+/// impl Message for Chat {
+///     const ID: u64 = 1;
+/// }
+/// ```
+pub trait Message: Serialize + DeserializeOwned + Send + Sync + 'static {
+    /// The identifier of the type on the network. It must be the same on every endpoint, and
+    /// differ from the ones of the other types registered on the `RpcBundle`.
+    const ID: u64;
+}
+
+/// A serialized message, as sent over the network.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RpcMessage {
+    /// The `Message::ID` of the type of the message.
+    pub id: u64,
+    /// The serialized message.
+    pub payload: Vec<u8>,
+}
+
+/// A network event type the messages can be sent as.
+pub trait RpcEvent: Send + Sync + 'static {
+    /// Wraps a message.
+    fn from_message(message: RpcMessage) -> Self;
+
+    /// Returns the message of the event, if it is one.
+    fn as_message(&self) -> Option<&RpcMessage>;
+}
+
+impl RpcEvent for RpcMessage {
+    fn from_message(message: RpcMessage) -> Self {
+        message
+    }
+
+    fn as_message(&self) -> Option<&RpcMessage> {
+        Some(self)
+    }
+}
+
+/// A message received from a connection.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Received<T> {
+    /// The entity of the `NetConnection` the message came from.
+    pub connection: Entity,
+    /// The address the message came from.
+    pub addr: SocketAddr,
+    /// The message.
+    pub message: T,
+}
+
+struct Outgoing {
+    // `None` to send to every connection.
+    target: Option<Entity>,
+    channel: u8,
+    message: RpcMessage,
+}
+
+/// Queues the messages to send, sent by the `RpcSendSystem` once per frame.
+#[derive(Default)]
+pub struct MessageSender {
+    queue: Vec<Outgoing>,
+}
+
+impl MessageSender {
+    /// Sends a message to a connection, reliably and in order.
+    pub fn send<T: Message>(&mut self, connection: Entity, message: &T) -> Result<()> {
+        self.send_on(connection, message, RELIABLE_CHANNEL)
+    }
+
+    /// Sends a message to a connection on a channel.
+    pub fn send_on<T: Message>(
+        &mut self,
+        connection: Entity,
+        message: &T,
+        channel: u8,
+    ) -> Result<()> {
+        self.queue(Some(connection), message, channel)
+    }
+
+    /// Sends a message to every connection, reliably and in order.
+    pub fn broadcast<T: Message>(&mut self, message: &T) -> Result<()> {
+        self.broadcast_on(message, RELIABLE_CHANNEL)
+    }
+
+    /// Sends a message to every connection on a channel.
+    pub fn broadcast_on<T: Message>(&mut self, message: &T, channel: u8) -> Result<()> {
+        self.queue(None, message, channel)
+    }
+
+    fn queue<T: Message>(
+        &mut self,
+        target: Option<Entity>,
+        message: &T,
+        channel: u8,
+    ) -> Result<()> {
+        self.queue.push(Outgoing {
+            target,
+            channel,
+            message: RpcMessage {
+                id: T::ID,
+                payload: serialize(message)?,
+            },
+        });
+        Ok(())
+    }
+}
+
+/// The messages received this frame, by type, for the `RpcDispatchSystem`s.
+#[derive(Default)]
+pub struct MessageInbox {
+    messages: HashMap<u64, Vec<(Entity, SocketAddr, Vec<u8>)>>,
+}
+
+/// The message state of a connection, added by the `RpcReceiveSystem`.
+pub struct RpcPeer<E: 'static> {
+    reader: ReaderId<NetEvent<E>>,
+}
+
+impl<E: Send + Sync + 'static> Component for RpcPeer<E> {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// Writes the messages of the `MessageSender` to the connections.
+///
+/// The `RpcBundle` runs it after the `NetSocketSystem`, which sends the messages written at the
+/// start of the next frame.
+pub struct RpcSendSystem<E> {
+    _marker: PhantomData<E>,
+}
+
+impl<E> Default for RpcSendSystem<E> {
+    fn default() -> Self {
+        RpcSendSystem {
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, E: RpcEvent> System<'a> for RpcSendSystem<E> {
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, NetConnection<E>>,
+        Write<'a, MessageSender>,
+    );
+
+    fn run(&mut self, (entities, mut connections, mut sender): Self::SystemData) {
+        for outgoing in sender.queue.drain(..) {
+            for (entity, connection) in (&*entities, &mut connections).join() {
+                if outgoing.target.map_or(false, |target| target != entity)
+                    || connection.state == ConnectionState::Disconnected
+                {
+                    continue;
+                }
+                connection
+                    .send_buffer
+                    .single_write(NetEvent::Packet(NetPacket::on_channel(
+                        E::from_message(outgoing.message.clone()),
+                        outgoing.channel,
+                    )));
+            }
+        }
+    }
+}
+
+/// Sorts the messages received by the connections by type.
+pub struct RpcReceiveSystem<E> {
+    known: Vec<u64>,
+    _marker: PhantomData<E>,
+}
+
+impl<E> RpcReceiveSystem<E> {
+    /// Receives the messages with the given identifiers, warning about the others.
+    pub fn new(known: Vec<u64>) -> Self {
+        RpcReceiveSystem {
+            known,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, E: RpcEvent> System<'a> for RpcReceiveSystem<E> {
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, NetConnection<E>>,
+        WriteStorage<'a, RpcPeer<E>>,
+        Write<'a, MessageInbox>,
+    );
+
+    fn run(&mut self, (entities, mut connections, mut peers, mut inbox): Self::SystemData) {
+        for messages in inbox.messages.values_mut() {
+            messages.clear();
+        }
+
+        let new = (&*entities, &mut connections, !&peers)
+            .join()
+            .map(|(entity, connection, _)| (entity, connection.receive_buffer.register_reader()))
+            .collect::<Vec<_>>();
+        for (entity, reader) in new {
+            peers
+                .insert(entity, RpcPeer { reader })
+                .expect("Unreachable: The entity is alive");
+        }
+
+        for (entity, connection, peer) in (&*entities, &mut connections, &mut peers).join() {
+            let addr = connection.target_addr;
+            for event in connection.receive_buffer.read(&mut peer.reader) {
+                if let NetEvent::Packet(packet) = event {
+                    if let Some(message) = packet.content().as_message() {
+                        if !self.known.contains(&message.id) {
+                            warn!("Received a message of an unregistered type from {}", addr);
+                            continue;
+                        }
+                        inbox
+                            .messages
+                            .entry(message.id)
+                            .or_insert_with(Vec::new)
+                            .push((entity, addr, message.payload.clone()));
+                    }
+                }
+            }
+        }
+    }
+
+    fn setup(&mut self, res: &mut Resources) {
+        Self::SystemData::setup(res);
+    }
+}
+
+/// Deserializes the received messages of a type into its `EventChannel<Received<T>>`.
+pub struct RpcDispatchSystem<T> {
+    _marker: PhantomData<T>,
+}
+
+impl<T> Default for RpcDispatchSystem<T> {
+    fn default() -> Self {
+        RpcDispatchSystem {
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, T: Message> System<'a> for RpcDispatchSystem<T> {
+    type SystemData = (Read<'a, MessageInbox>, Write<'a, EventChannel<Received<T>>>);
+
+    fn run(&mut self, (inbox, mut channel): Self::SystemData) {
+        let messages = match inbox.messages.get(&T::ID) {
+            Some(messages) => messages,
+            None => return,
+        };
+        for (connection, addr, payload) in messages {
+            match deserialize::<T>(payload) {
+                Ok(message) => channel.single_write(Received {
+                    connection: *connection,
+                    addr: *addr,
+                    message,
+                }),
+                Err(e) => error!(
+                    "Failed to deserialize a message of id {} from {}: {}",
+                    T::ID,
+                    addr,
+                    e
+                ),
+            }
+        }
+    }
+}
+
+type Registration = Box<dyn for<'a, 'b, 'c> FnOnce(&'c mut BundleBuilder<'a, 'b>)>;
+
+/// Adds the systems sending and receiving the registered message types.
+pub struct RpcBundle<E> {
+    ids: Vec<u64>,
+    messages: Vec<Registration>,
+    _marker: PhantomData<E>,
+}
+
+impl<E> Default for RpcBundle<E> {
+    fn default() -> Self {
+        RpcBundle {
+            ids: Vec::new(),
+            messages: Vec::new(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<E> RpcBundle<E> {
+    /// Creates a bundle without message types.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Registers a message type, received through `EventChannel<Received<T>>`.
+    pub fn with_message<T: Message>(mut self) -> Self {
+        self.ids.push(T::ID);
+        self.messages.push(Box::new(add_dispatch::<T>));
+        self
+    }
+}
+
+fn add_dispatch<T: Message>(builder: &mut BundleBuilder<'_, '_>) {
+    builder.add(
+        RpcDispatchSystem::<T>::default(),
+        &format!("rpc_dispatch_{}", T::ID),
+        &["rpc_receive"],
+    );
+}
+
+impl<'a, 'b, E: RpcEvent> SystemBundle<'a, 'b> for RpcBundle<E> {
+    fn build(self, builder: &mut BundleBuilder<'a, 'b>) -> std::result::Result<(), Error> {
+        let mut ids = self.ids.clone();
+        ids.sort();
+        if let Some(pair) = ids.windows(2).find(|pair| pair[0] == pair[1]) {
+            return Err(Error::from_string(format!(
+                "Two message types are registered with the id {}",
+                pair[0]
+            )));
+        }
+        builder.add(RpcSendSystem::<E>::default(), "rpc_send", &["net_socket"]);
+        builder.add(
+            RpcReceiveSystem::<E>::new(self.ids),
+            "rpc_receive",
+            &["net_socket"],
+        );
+        for register in self.messages {
+            register(builder);
+        }
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        "RpcBundle"
+    }
+
+    fn requirements(&self) -> Vec<BundleRequirement> {
+        vec![BundleRequirement::new("net_socket", "NetworkBundle")]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::ecs::{Builder, RunNow, World};
+
+    use super::*;
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct Chat(String);
+
+    impl Message for Chat {
+        const ID: u64 = 1;
+    }
+
+    #[test]
+    fn messages_are_routed_by_type() {
+        let mut world = World::new();
+        let mut send = RpcSendSystem::<RpcMessage>::default();
+        let mut receive = RpcReceiveSystem::<RpcMessage>::new(vec![Chat::ID]);
+        let mut dispatch = RpcDispatchSystem::<Chat>::default();
+        System::setup(&mut send, &mut world.res);
+        System::setup(&mut receive, &mut world.res);
+        System::setup(&mut dispatch, &mut world.res);
+        let mut reader = world
+            .write_resource::<EventChannel<Received<Chat>>>()
+            .register_reader();
+
+        let addr = "127.0.0.1:3456".parse().unwrap();
+        let connection = world
+            .create_entity()
+            .with(NetConnection::<RpcMessage>::new(addr))
+            .build();
+        // The receive system registers its reader on the first run.
+        receive.run_now(&world.res);
+
+        world
+            .write_resource::<MessageSender>()
+            .broadcast(&Chat("hello".to_string()))
+            .unwrap();
+        send.run_now(&world.res);
+
+        // Loop the sent event back as if the other side sent it.
+        {
+            let mut connections = world.write_storage::<NetConnection<RpcMessage>>();
+            let connection = connections.get_mut(connection).unwrap();
+            let sent = connection
+                .send_buffer_early_read()
+                .cloned()
+                .collect::<Vec<_>>();
+            assert_eq!(sent.len(), 1);
+            connection.receive_buffer.iter_write(sent);
+        }
+        receive.run_now(&world.res);
+        dispatch.run_now(&world.res);
+
+        let channel = world.read_resource::<EventChannel<Received<Chat>>>();
+        let received = channel.read(&mut reader).cloned().collect::<Vec<_>>();
+        assert_eq!(
+            received,
+            vec![Received {
+                connection,
+                addr,
+                message: Chat("hello".to_string()),
+            }]
+        );
+    }
+}
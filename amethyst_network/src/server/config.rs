@@ -1,10 +1,39 @@
 use std::net::SocketAddr;
 
+use crate::{
+    channel::Channels,
+    transport::{
+        EncryptionConfig, FragmentationConfig, NetworkConditions, PackingConfig, TransportKind,
+    },
+};
+
 #[derive(Clone, Debug)]
 /// The configuration used for the networking system.
 pub struct ServerConfig {
-    /// Address at which the UDP server will listen for incoming packets.
+    /// Address at which the server will listen for incoming packets, whatever the transport.
     pub udp_socket_addr: SocketAddr,
+    /// The transport used to send and receive packets, UDP by default.
+    pub transport: TransportKind,
+    /// The channels the packets created with `NetPacket::on_channel` are sent on.
+    pub channels: Channels,
+    /// Whether a `NetConnection` is created for the endpoints asking to connect with a
+    /// `NetEvent::Connect`, false by default.
+    pub accept_connections: bool,
+    /// How many connections are accepted at most, `None` for no limit. Endpoints asking to
+    /// connect beyond it are ignored.
+    pub max_connections: Option<usize>,
+    /// The network conditions to simulate on the transport, only in debug builds.
+    pub simulation: Option<NetworkConditions>,
+    /// How the small events are aggregated and the large ones compressed, `None` to send each
+    /// event in its own packet. Both endpoints must use the same setting.
+    pub packing: Option<PackingConfig>,
+    /// The keys the payloads are encrypted with and the endpoints authenticated, `None` to send
+    /// them in the clear. Both endpoints must use the same pre-shared key.
+    pub encryption: Option<EncryptionConfig>,
+    /// How the payloads too large for a datagram are split, with the UDP transport.
+    pub fragmentation: FragmentationConfig,
+    /// How many times per second the events are sent, `None` to send them every frame.
+    pub tick_rate: Option<u32>,
     /// Specifies what the maximal packets that could be handled by the server.
     /// This value is meant for preventing some loops to read infinitely long when many packets are send and received.
     /// This value is by default 5000.
@@ -16,6 +45,15 @@ impl Default for ServerConfig {
         ServerConfig {
             // by passing in :0 port the OS will give an available port.
             udp_socket_addr: "0.0.0.0:0".parse().unwrap(),
+            transport: TransportKind::Udp,
+            channels: Channels::default(),
+            accept_connections: false,
+            max_connections: None,
+            simulation: None,
+            packing: None,
+            encryption: None,
+            fragmentation: FragmentationConfig::default(),
+            tick_rate: None,
             max_throughput: 5000,
         }
     }
@@ -0,0 +1,164 @@
+//! Statistics of the traffic of the connections.
+
+use std::{collections::HashMap, net::SocketAddr, time::Duration};
+
+/// The traffic of a connection over some time.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Traffic {
+    /// The bytes of the payloads received.
+    pub bytes_in: u64,
+    /// The bytes of the payloads sent.
+    pub bytes_out: u64,
+    /// The payloads received.
+    pub packets_in: u64,
+    /// The payloads sent.
+    pub packets_out: u64,
+    /// The events sent again by the engine because they weren't answered, like the handshake.
+    /// The resends of the transports themselves aren't counted, `laminar` doesn't report them.
+    pub resends: u64,
+}
+
+impl Traffic {
+    fn add(&mut self, other: &Traffic) {
+        self.bytes_in += other.bytes_in;
+        self.bytes_out += other.bytes_out;
+        self.packets_in += other.packets_in;
+        self.packets_out += other.packets_out;
+        self.resends += other.resends;
+    }
+}
+
+/// The statistics of a connection.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ConnectionStats {
+    /// The traffic since the connection was first seen.
+    pub total: Traffic,
+    /// The traffic during the last complete second, for the rates.
+    pub last_second: Traffic,
+    /// The smoothed round trip time, measured by the pings of the `LifecycleBundle`.
+    pub rtt: Option<Duration>,
+    /// The estimated ratio of packets lost, between 0 and 1, from the unanswered pings.
+    pub packet_loss: Option<f32>,
+    current: Traffic,
+}
+
+/// The statistics of the traffic of every connection, updated by the `NetSocketSystem`.
+///
+/// The per second rollups change once a second, which suits debug overlays and the adaptation of
+/// the send rates.
+#[derive(Debug, Default)]
+pub struct NetworkStats {
+    connections: HashMap<SocketAddr, ConnectionStats>,
+    total: ConnectionStats,
+    second_start: Duration,
+}
+
+impl NetworkStats {
+    /// The statistics of the connection to `addr`.
+    pub fn connection(&self, addr: SocketAddr) -> Option<&ConnectionStats> {
+        self.connections.get(&addr)
+    }
+
+    /// The statistics of every connection.
+    pub fn connections(&self) -> impl Iterator<Item = (&SocketAddr, &ConnectionStats)> {
+        self.connections.iter()
+    }
+
+    /// The statistics of all the connections together, without round trip time or loss.
+    pub fn total(&self) -> &ConnectionStats {
+        &self.total
+    }
+
+    /// Records a payload sent to `addr`.
+    pub fn record_sent(&mut self, addr: SocketAddr, bytes: usize) {
+        self.record(addr, |traffic| {
+            traffic.bytes_out += bytes as u64;
+            traffic.packets_out += 1;
+        });
+    }
+
+    /// Records a payload received from `addr`.
+    pub fn record_received(&mut self, addr: SocketAddr, bytes: usize) {
+        self.record(addr, |traffic| {
+            traffic.bytes_in += bytes as u64;
+            traffic.packets_in += 1;
+        });
+    }
+
+    /// Records an event sent again to `addr`.
+    pub fn record_resend(&mut self, addr: SocketAddr) {
+        self.record(addr, |traffic| traffic.resends += 1);
+    }
+
+    /// Sets the estimated round trip time and packet loss of the connection to `addr`.
+    pub fn set_quality(
+        &mut self,
+        addr: SocketAddr,
+        rtt: Option<Duration>,
+        packet_loss: Option<f32>,
+    ) {
+        let stats = self
+            .connections
+            .entry(addr)
+            .or_insert_with(Default::default);
+        stats.rtt = rtt;
+        stats.packet_loss = packet_loss;
+    }
+
+    /// Closes the second if it is over, and forgets the connections not in `alive`.
+    pub fn update<'a>(&mut self, now: Duration, alive: impl IntoIterator<Item = &'a SocketAddr>) {
+        if now < self.second_start + Duration::from_secs(1) {
+            return;
+        }
+        self.second_start = now;
+        let alive = alive.into_iter().collect::<Vec<_>>();
+        self.connections.retain(|addr, _| alive.contains(&addr));
+        for stats in self.connections.values_mut().chain(Some(&mut self.total)) {
+            stats.last_second = stats.current;
+            stats.current = Traffic::default();
+        }
+    }
+
+    fn record<F: Fn(&mut Traffic)>(&mut self, addr: SocketAddr, record: F) {
+        let stats = self
+            .connections
+            .entry(addr)
+            .or_insert_with(Default::default);
+        let mut traffic = Traffic::default();
+        record(&mut traffic);
+        for stats in &mut [stats, &mut self.total] {
+            stats.total.add(&traffic);
+            stats.current.add(&traffic);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rolls_up_every_second() {
+        let mut stats = NetworkStats::default();
+        let a = "127.0.0.1:1000".parse().unwrap();
+        let b = "127.0.0.1:2000".parse().unwrap();
+
+        stats.record_sent(a, 100);
+        stats.record_sent(a, 50);
+        stats.record_received(b, 20);
+        stats.update(Duration::from_millis(500), &[a, b]);
+        assert_eq!(stats.connection(a).unwrap().last_second, Traffic::default());
+
+        stats.update(Duration::from_millis(1100), &[a, b]);
+        let traffic = stats.connection(a).unwrap().last_second;
+        assert_eq!(traffic.bytes_out, 150);
+        assert_eq!(traffic.packets_out, 2);
+        assert_eq!(stats.total().last_second.bytes_in, 20);
+
+        stats.record_resend(a);
+        stats.update(Duration::from_millis(2200), &[a]);
+        assert_eq!(stats.connection(a).unwrap().last_second.resends, 1);
+        assert_eq!(stats.connection(a).unwrap().total.bytes_out, 150);
+        assert!(stats.connection(b).is_none());
+    }
+}
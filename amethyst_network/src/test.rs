@@ -1,6 +1,12 @@
 #[cfg(test)]
 mod test {
-    use std::{net::SocketAddr, thread::sleep, time::Duration};
+    use std::{
+        collections::VecDeque,
+        net::SocketAddr,
+        sync::{Arc, Mutex},
+        thread::sleep,
+        time::Duration,
+    };
 
     use amethyst_core::{
         ecs::{Builder, Join, World, WriteStorage},
@@ -86,6 +92,149 @@ mod test {
         assert_eq!(comp.receive_buffer.read(&mut rcv).count(), 100);
     }
 
+    // Records what is sent and which connections are closed.
+    #[derive(Clone, Default)]
+    struct Recorder(Arc<Mutex<Vec<(SocketAddr, Option<Vec<u8>>)>>>);
+
+    impl Transport for Recorder {
+        fn send(&mut self, addr: SocketAddr, payload: Vec<u8>, _: Delivery) -> Result<()> {
+            self.0.lock().unwrap().push((addr, Some(payload)));
+            Ok(())
+        }
+
+        fn recv(&mut self) -> Option<TransportEvent> {
+            None
+        }
+
+        fn disconnect(&mut self, addr: SocketAddr) {
+            self.0.lock().unwrap().push((addr, None));
+        }
+    }
+
+    #[test]
+    fn dropped_connections_send_their_last_events_and_close_once() {
+        let addr: SocketAddr = "127.0.0.1:21208".parse().unwrap();
+        let recorder = Recorder::default();
+        let mut world = World::new();
+        let mut dispatcher = DispatcherBuilder::new()
+            .with(
+                NetSocketSystem::<String>::with_transport(
+                    Box::new(recorder.clone()),
+                    ServerConfig::default(),
+                    Vec::new(),
+                ),
+                "s",
+                &[],
+            )
+            .build();
+        dispatcher.setup(&mut world.res);
+        // Between two ticks.
+        let mut tick = NetworkTick::new(Some(1));
+        tick.advance(Duration::from_millis(1));
+        world.add_resource(tick);
+
+        let mut connection = NetConnection::<String>::new(addr);
+        connection.state = ConnectionState::Disconnected;
+        connection.send_buffer.single_write(NetEvent::Disconnect {
+            reason: "Leaving".to_string(),
+        });
+        world.create_entity().with(connection).build();
+
+        dispatcher.dispatch(&mut world.res);
+        dispatcher.dispatch(&mut world.res);
+        let recorded = recorder.0.lock().unwrap();
+        assert_eq!(recorded.len(), 2);
+        assert_eq!(recorded[0].0, addr);
+        assert!(recorded[0].1.is_some());
+        assert_eq!(recorded[1], (addr, None));
+    }
+
+    // Receives the scripted events.
+    #[derive(Clone, Default)]
+    struct Incoming(Arc<Mutex<VecDeque<TransportEvent>>>);
+
+    impl Transport for Incoming {
+        fn send(&mut self, _: SocketAddr, _: Vec<u8>, _: Delivery) -> Result<()> {
+            Ok(())
+        }
+
+        fn recv(&mut self) -> Option<TransportEvent> {
+            self.0.lock().unwrap().pop_front()
+        }
+
+        fn disconnect(&mut self, _: SocketAddr) {}
+    }
+
+    #[test]
+    fn connections_are_accepted_up_to_the_limit_and_dropped_by_the_transport() {
+        let first: SocketAddr = "127.0.0.1:21210".parse().unwrap();
+        let second: SocketAddr = "127.0.0.1:21212".parse().unwrap();
+        let incoming = Incoming::default();
+        let mut world = World::new();
+        let config = ServerConfig {
+            accept_connections: true,
+            max_connections: Some(1),
+            ..Default::default()
+        };
+        let mut dispatcher = DispatcherBuilder::new()
+            .with(
+                NetSocketSystem::<String>::with_transport(
+                    Box::new(incoming.clone()),
+                    config,
+                    Vec::new(),
+                ),
+                "s",
+                &[],
+            )
+            .build();
+        dispatcher.setup(&mut world.res);
+
+        let connect = bincode::serialize(&NetEvent::<String>::Connect {
+            client_uuid: uuid::Uuid::nil(),
+            protocol_version: 0,
+        })
+        .unwrap();
+        let connections = |world: &World| {
+            world
+                .read_storage::<NetConnection<String>>()
+                .join()
+                .map(|connection| (connection.target_addr, connection.state.clone()))
+                .collect::<Vec<_>>()
+        };
+        for &addr in &[first, second] {
+            incoming.0.lock().unwrap().push_back(TransportEvent::Packet {
+                addr,
+                payload: connect.clone(),
+            });
+        }
+        dispatcher.dispatch(&mut world.res);
+        world.maintain();
+        assert_eq!(
+            connections(&world),
+            vec![(first, ConnectionState::Connecting)]
+        );
+
+        incoming
+            .0
+            .lock()
+            .unwrap()
+            .push_back(TransportEvent::Disconnected(first));
+        dispatcher.dispatch(&mut world.res);
+        assert_eq!(
+            connections(&world),
+            vec![(first, ConnectionState::Disconnected)]
+        );
+
+        // The dropped connection doesn't count towards the limit.
+        incoming.0.lock().unwrap().push_back(TransportEvent::Packet {
+            addr: second,
+            payload: connect,
+        });
+        dispatcher.dispatch(&mut world.res);
+        world.maintain();
+        assert_eq!(connections(&world).len(), 2);
+    }
+
     fn build<'a, 'b>(
         client_addr: SocketAddr,
         server_addr: SocketAddr,
@@ -97,12 +246,14 @@ mod test {
         let client_config = ServerConfig {
             udp_socket_addr: client_addr,
             max_throughput: 10000,
+            ..Default::default()
         };
 
         // server config
         let server_config = ServerConfig {
             udp_socket_addr: server_addr,
             max_throughput: 10000,
+            ..Default::default()
         };
 
         let mut cl_dispatch = DispatcherBuilder::new()
@@ -0,0 +1,121 @@
+//! The network tick, decoupling how often the events are sent from the frame rate.
+
+use std::time::Duration;
+
+use amethyst_core::{
+    ecs::{Read, Resources, System, SystemData, Write},
+    timing::Time,
+};
+
+/// Whether the current frame is a network tick, in which the events are sent and the
+/// snapshots taken.
+///
+/// Without a tick rate, every frame is a tick.
+#[derive(Clone, Debug)]
+pub struct NetworkTick {
+    interval: Option<Duration>,
+    accumulator: Duration,
+    ticked: bool,
+    count: u64,
+}
+
+impl Default for NetworkTick {
+    fn default() -> Self {
+        NetworkTick::new(None)
+    }
+}
+
+impl NetworkTick {
+    /// Ticks `rate` times per second, or every frame for `None`.
+    pub fn new(rate: Option<u32>) -> Self {
+        NetworkTick {
+            interval: rate.map(|rate| Duration::from_secs(1) / rate.max(1)),
+            accumulator: Duration::from_secs(0),
+            ticked: true,
+            count: 0,
+        }
+    }
+
+    /// The time between two ticks, `None` when every frame is a tick.
+    pub fn interval(&self) -> Option<Duration> {
+        self.interval
+    }
+
+    /// Whether the current frame is a tick.
+    pub fn is_tick(&self) -> bool {
+        self.ticked
+    }
+
+    /// The number of ticks so far.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Advances by the duration of a frame.
+    pub fn advance(&mut self, delta: Duration) {
+        self.ticked = match self.interval {
+            None => true,
+            Some(interval) => {
+                self.accumulator += delta;
+                if self.accumulator >= interval {
+                    // The frames slower than the ticks tick every frame, without catching up.
+                    self.accumulator = (self.accumulator - interval).min(interval);
+                    true
+                } else {
+                    false
+                }
+            }
+        };
+        if self.ticked {
+            self.count += 1;
+        }
+    }
+}
+
+/// Advances the `NetworkTick` with the real time, before the other network systems.
+pub struct NetworkTickSystem {
+    rate: Option<u32>,
+}
+
+impl NetworkTickSystem {
+    /// Ticks `rate` times per second, or every frame for `None`.
+    pub fn new(rate: Option<u32>) -> Self {
+        NetworkTickSystem { rate }
+    }
+}
+
+impl<'a> System<'a> for NetworkTickSystem {
+    type SystemData = (Read<'a, Time>, Write<'a, NetworkTick>);
+
+    fn run(&mut self, (time, mut tick): Self::SystemData) {
+        tick.advance(time.delta_real_time());
+    }
+
+    fn setup(&mut self, res: &mut Resources) {
+        res.insert(NetworkTick::new(self.rate));
+        Self::SystemData::setup(res);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ticks_at_the_rate() {
+        let mut tick = NetworkTick::new(Some(20));
+        let frame = Duration::from_micros(16_667);
+        let ticks = (0..60)
+            .filter(|_| {
+                tick.advance(frame);
+                tick.is_tick()
+            })
+            .count();
+        assert_eq!(ticks, 20);
+        assert_eq!(tick.count(), 20);
+
+        let mut every_frame = NetworkTick::default();
+        every_frame.advance(frame);
+        assert!(every_frame.is_tick());
+    }
+}
@@ -0,0 +1,694 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    net::SocketAddr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use log::warn;
+use ring::{aead, agreement, digest, hkdf, hmac, rand::SystemRandom};
+
+use crate::error::{Error, Result};
+
+use super::{Delivery, Transport, TransportEvent};
+
+// The kinds of packets, first byte of each.
+const HELLO: u8 = 0;
+const WELCOME: u8 = 1;
+const DATA: u8 = 2;
+
+const PUBLIC_KEY_LEN: usize = 32;
+const MAC_LEN: usize = 32;
+const COUNTER_LEN: usize = 8;
+const TIMESTAMP_LEN: usize = 8;
+
+// How old a handshake can be, in seconds, before it's refused as a replay. The clocks of the
+// endpoints may be apart by as much.
+const HANDSHAKE_WINDOW: u64 = 60;
+
+/// The keys and the token of the `EncryptedTransport`.
+///
+/// The pre-shared key must be the same on every endpoint of the game. It authenticates the
+/// handshakes, so the sessions can't be intercepted by an endpoint without it. The tokens
+/// identify the players: a server with a token secret only accepts the clients sending a token
+/// issued with it by `AuthToken::issue`, typically by the login service of the game.
+#[derive(Clone)]
+pub struct EncryptionConfig {
+    /// The pre-shared key.
+    pub key: [u8; 32],
+    /// The token sent when connecting, if any.
+    pub token: Option<Vec<u8>>,
+    /// The secret the tokens of the connecting clients are checked with, if they are required.
+    pub token_secret: Option<[u8; 32]>,
+}
+
+impl EncryptionConfig {
+    /// Encrypts with the given pre-shared key, without tokens.
+    pub fn new(key: [u8; 32]) -> Self {
+        EncryptionConfig {
+            key,
+            token: None,
+            token_secret: None,
+        }
+    }
+
+    /// Sends a token when connecting.
+    pub fn with_token(mut self, token: Vec<u8>) -> Self {
+        self.token = Some(token);
+        self
+    }
+
+    /// Only accepts the clients sending a valid token issued with `secret`.
+    pub fn with_token_secret(mut self, secret: [u8; 32]) -> Self {
+        self.token_secret = Some(secret);
+        self
+    }
+}
+
+impl fmt::Debug for EncryptionConfig {
+    // The keys are kept out of the logs.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EncryptionConfig")
+            .field("token", &self.token.as_ref().map(|_| ".."))
+            .field("token_secret", &self.token_secret.map(|_| ".."))
+            .finish()
+    }
+}
+
+/// The authentication tokens: a user id and an expiry date, signed with a secret.
+pub struct AuthToken;
+
+impl AuthToken {
+    /// Issues a token for a user, valid until `expires` seconds after the Unix epoch.
+    pub fn issue(secret: &[u8; 32], user: u64, expires: u64) -> Vec<u8> {
+        let mut token = Vec::with_capacity(16 + MAC_LEN);
+        token.extend_from_slice(&user.to_be_bytes());
+        token.extend_from_slice(&expires.to_be_bytes());
+        let key = hmac::SigningKey::new(&digest::SHA256, secret);
+        let signature = hmac::sign(&key, &token);
+        token.extend_from_slice(signature.as_ref());
+        token
+    }
+
+    /// Returns the user of a token, if it was issued with `secret` and didn't expire at `now`,
+    /// in seconds after the Unix epoch.
+    pub fn verify(secret: &[u8; 32], token: &[u8], now: u64) -> Option<u64> {
+        if token.len() != 16 + MAC_LEN {
+            return None;
+        }
+        let key = hmac::SigningKey::new(&digest::SHA256, secret);
+        hmac::verify_with_own_key(&key, &token[..16], &token[16..]).ok()?;
+        let mut user = [0; 8];
+        user.copy_from_slice(&token[..8]);
+        let mut expires = [0; 8];
+        expires.copy_from_slice(&token[8..16]);
+        if u64::from_be_bytes(expires) < now {
+            return None;
+        }
+        Some(u64::from_be_bytes(user))
+    }
+}
+
+// The last counters received, to drop the replayed packets.
+#[derive(Default)]
+struct ReplayWindow {
+    highest: Option<u64>,
+    // Bit `i` is set when `highest - i` was received.
+    seen: u64,
+}
+
+impl ReplayWindow {
+    fn accept(&mut self, counter: u64) -> bool {
+        let highest = match self.highest {
+            None => {
+                self.highest = Some(counter);
+                self.seen = 1;
+                return true;
+            }
+            Some(highest) => highest,
+        };
+        if counter > highest {
+            let shift = counter - highest;
+            self.seen = if shift >= 64 { 0 } else { self.seen << shift } | 1;
+            self.highest = Some(counter);
+            true
+        } else {
+            let age = highest - counter;
+            if age >= 64 || self.seen & (1 << age) != 0 {
+                return false;
+            }
+            self.seen |= 1 << age;
+            true
+        }
+    }
+}
+
+struct Session {
+    sealing: aead::SealingKey,
+    opening: aead::OpeningKey,
+    counter: u64,
+    window: ReplayWindow,
+    // Whether the peer proved it has the keys, by answering the handshake of this endpoint or
+    // by sending a packet in the session it started.
+    confirmed: bool,
+    // The user of the token of the peer, known once the session is confirmed.
+    user: Option<u64>,
+}
+
+impl Session {
+    // Derives the keys of both directions from the shared secret of the handshake.
+    fn new(psk: &[u8; 32], shared: &[u8], transcript: &[u8], initiator: bool) -> Result<Self> {
+        let salt = hmac::SigningKey::new(&digest::SHA256, psk);
+        let mut keys = [0; 64];
+        hkdf::extract_and_expand(&salt, shared, transcript, &mut keys);
+        let (initiator_key, responder_key) = keys.split_at(32);
+        let (sealing, opening) = if initiator {
+            (initiator_key, responder_key)
+        } else {
+            (responder_key, initiator_key)
+        };
+        Ok(Session {
+            sealing: aead::SealingKey::new(&aead::CHACHA20_POLY1305, sealing)
+                .map_err(|_| crypto_error("Invalid sealing key"))?,
+            opening: aead::OpeningKey::new(&aead::CHACHA20_POLY1305, opening)
+                .map_err(|_| crypto_error("Invalid opening key"))?,
+            counter: 0,
+            window: ReplayWindow::default(),
+            confirmed: initiator,
+            user: None,
+        })
+    }
+
+    fn seal(&mut self, payload: &[u8]) -> Result<Vec<u8>> {
+        let counter = self.counter;
+        self.counter += 1;
+        let tag_len = aead::CHACHA20_POLY1305.tag_len();
+        let mut packet = Vec::with_capacity(1 + COUNTER_LEN + payload.len() + tag_len);
+        packet.push(DATA);
+        packet.extend_from_slice(&counter.to_be_bytes());
+        packet.extend_from_slice(payload);
+        packet.resize(packet.len() + tag_len, 0);
+        let (header, body) = packet.split_at_mut(1 + COUNTER_LEN);
+        aead::seal_in_place(
+            &self.sealing,
+            nonce(counter),
+            aead::Aad::from(&header[..]),
+            body,
+            tag_len,
+        )
+        .map_err(|_| crypto_error("Failed to encrypt a payload"))?;
+        Ok(packet)
+    }
+
+    fn open(&mut self, packet: &mut [u8]) -> Option<Vec<u8>> {
+        if packet.len() < 1 + COUNTER_LEN + aead::CHACHA20_POLY1305.tag_len() {
+            return None;
+        }
+        let mut counter = [0; COUNTER_LEN];
+        counter.copy_from_slice(&packet[1..1 + COUNTER_LEN]);
+        let counter = u64::from_be_bytes(counter);
+        let (header, body) = packet.split_at_mut(1 + COUNTER_LEN);
+        let payload = aead::open_in_place(
+            &self.opening,
+            nonce(counter),
+            aead::Aad::from(&header[..]),
+            0,
+            body,
+        )
+        .ok()?
+        .to_vec();
+        // Only the authentic packets move the window, the forged ones can't block it.
+        if self.window.accept(counter) {
+            Some(payload)
+        } else {
+            None
+        }
+    }
+}
+
+fn nonce(counter: u64) -> aead::Nonce {
+    let mut nonce = [0; 12];
+    nonce[4..].copy_from_slice(&counter.to_be_bytes());
+    aead::Nonce::assume_unique_for_key(nonce)
+}
+
+fn crypto_error(message: &str) -> Error {
+    Error::EncryptionError(message.to_string())
+}
+
+// The seconds since the Unix epoch.
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_secs())
+        .unwrap_or(0)
+}
+
+fn mac(psk: &[u8; 32], parts: &[&[u8]]) -> hmac::Signature {
+    let key = hmac::SigningKey::new(&digest::SHA256, psk);
+    let mut context = hmac::SigningContext::with_key(&key);
+    for part in parts {
+        context.update(part);
+    }
+    context.sign()
+}
+
+// A handshake this endpoint started, with the payloads to send once it completes.
+struct Pending {
+    private_key: agreement::EphemeralPrivateKey,
+    public_key: Vec<u8>,
+    queued: Vec<(Vec<u8>, Delivery)>,
+}
+
+/// A transport encrypting and authenticating the payloads of another one.
+///
+/// The first payload sent to an endpoint starts a handshake exchanging ephemeral X25519 keys,
+/// authenticated with the pre-shared key, and carrying the token of the client. The session keys
+/// derived from it encrypt the payloads with ChaCha20-Poly1305, and the replayed or forged
+/// packets are dropped. Both endpoints must encrypt with the same pre-shared key.
+///
+/// The handshakes are dated, and refused when they are older than a minute or were already
+/// answered, so a recorded handshake can't be replayed. An established session isn't replaced by
+/// a new handshake until it's disconnected, and the user of a token is only known once the
+/// client proved it has the session keys, by sending its first payload.
+pub struct EncryptedTransport {
+    inner: Box<dyn Transport>,
+    config: EncryptionConfig,
+    rng: SystemRandom,
+    sessions: HashMap<SocketAddr, Session>,
+    pending: HashMap<SocketAddr, Pending>,
+    users: HashMap<SocketAddr, u64>,
+    // The keys of the handshakes answered in the window, with their dates.
+    answered: HashMap<Vec<u8>, u64>,
+}
+
+impl EncryptedTransport {
+    /// Wraps a transport.
+    pub fn new(inner: Box<dyn Transport>, config: EncryptionConfig) -> Self {
+        EncryptedTransport {
+            inner,
+            config,
+            rng: SystemRandom::new(),
+            sessions: HashMap::new(),
+            pending: HashMap::new(),
+            users: HashMap::new(),
+            answered: HashMap::new(),
+        }
+    }
+
+    /// The user of the token an endpoint connected with, when the tokens are checked.
+    pub fn user(&self, addr: SocketAddr) -> Option<u64> {
+        self.users.get(&addr).cloned()
+    }
+
+    fn key_pair(&self) -> Result<(agreement::EphemeralPrivateKey, Vec<u8>)> {
+        let private_key = agreement::EphemeralPrivateKey::generate(&agreement::X25519, &self.rng)
+            .map_err(|_| crypto_error("Failed to generate a key"))?;
+        let public_key = private_key
+            .compute_public_key()
+            .map_err(|_| crypto_error("Failed to compute a public key"))?
+            .as_ref()
+            .to_vec();
+        Ok((private_key, public_key))
+    }
+
+    fn agree(
+        &self,
+        private_key: agreement::EphemeralPrivateKey,
+        peer_key: &[u8],
+        transcript: &[u8],
+        initiator: bool,
+    ) -> Result<Session> {
+        let psk = self.config.key;
+        agreement::agree_ephemeral(
+            private_key,
+            &agreement::X25519,
+            untrusted::Input::from(peer_key),
+            crypto_error("Failed to agree on a key"),
+            |shared| Session::new(&psk, shared, transcript, initiator),
+        )
+    }
+
+    fn hello(&mut self, addr: SocketAddr) -> Result<Vec<u8>> {
+        let (private_key, public_key) = self.key_pair()?;
+        let token = self.config.token.clone().unwrap_or_default();
+        let packet = hello_packet(&self.config.key, &public_key, &token, unix_now());
+        self.pending.insert(
+            addr,
+            Pending {
+                private_key,
+                public_key,
+                queued: Vec::new(),
+            },
+        );
+        Ok(packet)
+    }
+
+    // Answers a handshake.
+    fn accept(&mut self, addr: SocketAddr, packet: &[u8]) -> Result<()> {
+        let invalid = || crypto_error("Invalid handshake");
+        let header = 1 + PUBLIC_KEY_LEN + TIMESTAMP_LEN + 2;
+        if packet.len() < header + MAC_LEN {
+            return Err(invalid());
+        }
+        let (signed, signature) = packet.split_at(packet.len() - MAC_LEN);
+        let key = hmac::SigningKey::new(&digest::SHA256, &self.config.key);
+        hmac::verify_with_own_key(&key, signed, signature).map_err(|_| invalid())?;
+        let peer_key = &signed[1..1 + PUBLIC_KEY_LEN];
+        let mut timestamp = [0; TIMESTAMP_LEN];
+        timestamp.copy_from_slice(&signed[1 + PUBLIC_KEY_LEN..1 + PUBLIC_KEY_LEN + TIMESTAMP_LEN]);
+        let timestamp = u64::from_be_bytes(timestamp);
+        let mut length = [0; 2];
+        length.copy_from_slice(&signed[header - 2..header]);
+        let token = &signed[header..];
+        if token.len() != u16::from_be_bytes(length) as usize {
+            return Err(invalid());
+        }
+
+        let now = unix_now();
+        let age = if now > timestamp {
+            now - timestamp
+        } else {
+            timestamp - now
+        };
+        if age > HANDSHAKE_WINDOW {
+            return Err(crypto_error("Outdated handshake"));
+        }
+        self.answered
+            .retain(|_, answered| now.saturating_sub(*answered) <= 2 * HANDSHAKE_WINDOW);
+        if self.answered.contains_key(peer_key) {
+            return Err(crypto_error("Replayed handshake"));
+        }
+        if self
+            .sessions
+            .get(&addr)
+            .map_or(false, |session| session.confirmed)
+        {
+            return Err(crypto_error("A session is already established"));
+        }
+
+        // When both endpoints started a handshake, the one with the lower key answers the other.
+        if let Some(pending) = self.pending.get(&addr) {
+            if pending.public_key[..] > *peer_key {
+                return Ok(());
+            }
+        }
+
+        let user = match self.config.token_secret {
+            Some(ref secret) => Some(
+                AuthToken::verify(secret, token, now)
+                    .ok_or_else(|| crypto_error("Invalid token"))?,
+            ),
+            None => None,
+        };
+
+        let (private_key, public_key) = self.key_pair()?;
+        let transcript = [peer_key, &public_key[..]].concat();
+        let mut session = self.agree(private_key, peer_key, &transcript, false)?;
+        session.user = user;
+        self.answered.insert(peer_key.to_vec(), now);
+        let queued = self
+            .pending
+            .remove(&addr)
+            .map(|pending| pending.queued)
+            .unwrap_or_default();
+
+        let mut welcome = vec![WELCOME];
+        welcome.extend_from_slice(&public_key);
+        let signature = mac(&self.config.key, &[&welcome, peer_key]);
+        welcome.extend_from_slice(signature.as_ref());
+        self.inner
+            .send(addr, welcome, Delivery::ReliableUnordered)?;
+        for (payload, delivery) in queued {
+            let packet = session.seal(&payload)?;
+            self.inner.send(addr, packet, delivery)?;
+        }
+        self.sessions.insert(addr, session);
+        Ok(())
+    }
+
+    // Completes a handshake this endpoint started, sending the queued payloads.
+    fn complete(&mut self, addr: SocketAddr, packet: &[u8]) -> Result<()> {
+        let invalid = || crypto_error("Invalid handshake answer");
+        if packet.len() != 1 + PUBLIC_KEY_LEN + MAC_LEN {
+            return Err(invalid());
+        }
+        let pending = self.pending.remove(&addr).ok_or_else(invalid)?;
+        let (signed, signature) = packet.split_at(1 + PUBLIC_KEY_LEN);
+        let key = hmac::SigningKey::new(&digest::SHA256, &self.config.key);
+        let expected = [signed, &pending.public_key[..]].concat();
+        if hmac::verify_with_own_key(&key, &expected, signature).is_err() {
+            self.pending.insert(addr, pending);
+            return Err(invalid());
+        }
+        let peer_key = &signed[1..];
+        let transcript = [&pending.public_key[..], peer_key].concat();
+        let mut session = self.agree(pending.private_key, peer_key, &transcript, true)?;
+        for (payload, delivery) in pending.queued {
+            let packet = session.seal(&payload)?;
+            self.inner.send(addr, packet, delivery)?;
+        }
+        self.sessions.insert(addr, session);
+        Ok(())
+    }
+}
+
+// The handshake of an endpoint with the key `public_key`, dated `timestamp` in seconds since the
+// Unix epoch.
+fn hello_packet(psk: &[u8; 32], public_key: &[u8], token: &[u8], timestamp: u64) -> Vec<u8> {
+    let mut packet = vec![HELLO];
+    packet.extend_from_slice(public_key);
+    packet.extend_from_slice(&timestamp.to_be_bytes());
+    packet.extend_from_slice(&(token.len() as u16).to_be_bytes());
+    packet.extend_from_slice(token);
+    let signature = mac(psk, &[&packet]);
+    packet.extend_from_slice(signature.as_ref());
+    packet
+}
+
+impl Transport for EncryptedTransport {
+    fn send(&mut self, addr: SocketAddr, payload: Vec<u8>, delivery: Delivery) -> Result<()> {
+        if let Some(session) = self.sessions.get_mut(&addr) {
+            let packet = session.seal(&payload)?;
+            return self.inner.send(addr, packet, delivery);
+        }
+        if !self.pending.contains_key(&addr) {
+            let hello = self.hello(addr)?;
+            self.inner.send(addr, hello, Delivery::ReliableUnordered)?;
+        }
+        self.pending
+            .get_mut(&addr)
+            .expect("Unreachable: The handshake was just started")
+            .queued
+            .push((payload, delivery));
+        Ok(())
+    }
+
+    fn recv(&mut self) -> Option<TransportEvent> {
+        loop {
+            match self.inner.recv()? {
+                TransportEvent::Packet { addr, mut payload } => match payload.first().cloned() {
+                    Some(HELLO) => {
+                        if let Err(e) = self.accept(addr, &payload) {
+                            warn!("Refusing the handshake of {}: {}", addr, e);
+                        }
+                    }
+                    Some(WELCOME) => {
+                        if let Err(e) = self.complete(addr, &payload) {
+                            warn!("Failed to complete the handshake with {}: {}", addr, e);
+                        }
+                    }
+                    Some(DATA) => {
+                        let opened = self.sessions.get_mut(&addr).and_then(|session| {
+                            let payload = session.open(&mut payload)?;
+                            session.confirmed = true;
+                            Some((payload, session.user.take()))
+                        });
+                        match opened {
+                            Some((payload, user)) => {
+                                if let Some(user) = user {
+                                    self.users.insert(addr, user);
+                                }
+                                return Some(TransportEvent::Packet { addr, payload });
+                            }
+                            None => warn!("Dropping an unauthentic packet from {}", addr),
+                        }
+                    }
+                    _ => warn!("Dropping an unencrypted packet from {}", addr),
+                },
+                TransportEvent::Disconnected(addr) => {
+                    self.sessions.remove(&addr);
+                    self.pending.remove(&addr);
+                    self.users.remove(&addr);
+                    return Some(TransportEvent::Disconnected(addr));
+                }
+                event => return Some(event),
+            }
+        }
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.inner.flush()
+    }
+
+    fn disconnect(&mut self, addr: SocketAddr) {
+        self.sessions.remove(&addr);
+        self.pending.remove(&addr);
+        self.users.remove(&addr);
+        self.inner.disconnect(addr);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::VecDeque,
+        sync::{Arc, Mutex},
+    };
+
+    use super::*;
+
+    type Queue = Arc<Mutex<VecDeque<TransportEvent>>>;
+
+    // One end of a pipe, the packets sent arriving from `local` on the other end.
+    struct End {
+        local: SocketAddr,
+        out: Queue,
+        inc: Queue,
+    }
+
+    impl Transport for End {
+        fn send(&mut self, _: SocketAddr, payload: Vec<u8>, _: Delivery) -> Result<()> {
+            let packet = TransportEvent::Packet {
+                addr: self.local,
+                payload,
+            };
+            self.out.lock().unwrap().push_back(packet);
+            Ok(())
+        }
+
+        fn recv(&mut self) -> Option<TransportEvent> {
+            self.inc.lock().unwrap().pop_front()
+        }
+    }
+
+    fn pair(
+        client: EncryptionConfig,
+        server: EncryptionConfig,
+    ) -> (EncryptedTransport, EncryptedTransport, Queue) {
+        let (to_server, to_client) = (Queue::default(), Queue::default());
+        let client = EncryptedTransport::new(
+            Box::new(End {
+                local: "10.0.0.1:1000".parse().unwrap(),
+                out: to_server.clone(),
+                inc: to_client.clone(),
+            }),
+            client,
+        );
+        let server = EncryptedTransport::new(
+            Box::new(End {
+                local: "10.0.0.2:2000".parse().unwrap(),
+                out: to_client,
+                inc: to_server.clone(),
+            }),
+            server,
+        );
+        (client, server, to_server)
+    }
+
+    #[test]
+    fn payloads_are_encrypted_and_authenticated() {
+        let secret = [7; 32];
+        let token = AuthToken::issue(&secret, 42, u64::max_value());
+        let (mut client, mut server, to_server) = pair(
+            EncryptionConfig::new([1; 32]).with_token(token),
+            EncryptionConfig::new([1; 32]).with_token_secret(secret),
+        );
+        let server_addr = "10.0.0.2:2000".parse().unwrap();
+        let client_addr = "10.0.0.1:1000".parse().unwrap();
+
+        client
+            .send(server_addr, b"secret".to_vec(), Delivery::Unreliable)
+            .unwrap();
+        assert_eq!(server.recv(), None);
+        assert_eq!(client.recv(), None);
+        // Not before the client sent a payload with the session keys.
+        assert_eq!(server.user(client_addr), None);
+
+        // The payload is not readable on the wire, and a replayed copy is dropped.
+        let packet = to_server.lock().unwrap().front().cloned().unwrap();
+        match packet {
+            TransportEvent::Packet { ref payload, .. } => {
+                assert!(!payload.windows(6).any(|window| window == b"secret"))
+            }
+            _ => unreachable!(),
+        }
+        to_server.lock().unwrap().push_back(packet);
+        assert_eq!(
+            server.recv(),
+            Some(TransportEvent::Packet {
+                addr: client_addr,
+                payload: b"secret".to_vec(),
+            })
+        );
+        assert_eq!(server.user(client_addr), Some(42));
+        assert_eq!(server.recv(), None);
+    }
+
+    #[test]
+    fn replayed_and_outdated_handshakes_are_refused() {
+        let (mut client, mut server, to_server) = pair(
+            EncryptionConfig::new([1; 32]),
+            EncryptionConfig::new([1; 32]),
+        );
+        let server_addr = "10.0.0.2:2000".parse().unwrap();
+        let client_addr = "10.0.0.1:1000".parse().unwrap();
+
+        client
+            .send(server_addr, vec![1], Delivery::Unreliable)
+            .unwrap();
+        let hello = to_server.lock().unwrap().front().cloned().unwrap();
+        assert_eq!(server.recv(), None);
+        assert_eq!(client.recv(), None);
+        assert!(server.recv().is_some());
+
+        // The recorded handshake doesn't replace the session.
+        to_server.lock().unwrap().push_back(hello);
+        assert_eq!(server.recv(), None);
+        client
+            .send(server_addr, vec![2], Delivery::Unreliable)
+            .unwrap();
+        assert_eq!(
+            server.recv(),
+            Some(TransportEvent::Packet {
+                addr: client_addr,
+                payload: vec![2],
+            })
+        );
+
+        let (_, public_key) = client.key_pair().unwrap();
+        let outdated = hello_packet(&[1; 32], &public_key, &[], unix_now() - 3600);
+        server.sessions.clear();
+        assert!(server.accept(client_addr, &outdated).is_err());
+        assert!(server.sessions.is_empty());
+    }
+
+    #[test]
+    fn invalid_tokens_are_refused() {
+        let token = AuthToken::issue(&[8; 32], 42, u64::max_value());
+        let (mut client, mut server, _) = pair(
+            EncryptionConfig::new([1; 32]).with_token(token),
+            EncryptionConfig::new([1; 32]).with_token_secret([7; 32]),
+        );
+        client
+            .send(
+                "10.0.0.2:2000".parse().unwrap(),
+                vec![1],
+                Delivery::Unreliable,
+            )
+            .unwrap();
+        assert_eq!(server.recv(), None);
+        assert_eq!(client.recv(), None);
+        assert!(client.sessions.is_empty());
+    }
+}
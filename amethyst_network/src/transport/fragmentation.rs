@@ -0,0 +1,733 @@
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    io,
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+use log::{error, warn};
+
+use crate::error::Result;
+
+use super::{Delivery, Transport, TransportEvent};
+
+// The kinds of packets, first byte of each.
+const WHOLE: u8 = 0;
+const FRAGMENT: u8 = 1;
+const ACK: u8 = 2;
+// A payload of an ordered or sequenced stream, sent whole.
+const WHOLE_IN_ORDER: u8 = 3;
+
+// How a payload is delivered, with its stream and its number in the stream.
+const UNRELIABLE: u8 = 0;
+const RELIABLE: u8 = 1;
+const ORDERED: u8 = 2;
+const SEQUENCED: u8 = 3;
+const ORDER_HEADER: usize = 1 + 1 + 4;
+
+// The kind, message id, fragment index, fragment count and order.
+const FRAGMENT_HEADER: usize = 1 + 4 + 2 + 2 + ORDER_HEADER;
+
+// The stream of `laminar` for the payloads sent without one.
+const DEFAULT_STREAM: u8 = 255;
+// How far ahead of the next payload of an ordered stream the later ones are kept.
+const ORDER_WINDOW: u32 = 1024;
+
+/// How the `FragmentingTransport` splits the large payloads.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FragmentationConfig {
+    /// The largest payload sent in a single packet, the larger ones are split in fragments
+    /// of this size. Must not be zero.
+    pub fragment_size: usize,
+    /// The most fragments a payload may be split in, to refuse garbage counts.
+    pub max_fragments: u16,
+    /// The most payloads of a peer being reassembled at once, the fragments of the others being
+    /// dropped until they are completed.
+    pub max_reassemblies: usize,
+    /// The most bytes kept for a peer, in the payloads being reassembled and the ones waiting
+    /// for the earlier payloads of their stream.
+    pub max_buffered: usize,
+    /// How long to wait for the ack of a fragment of a reliable payload before sending it again.
+    pub resend_after: Duration,
+    /// How long a payload may take to be completed, after which its fragments are dropped.
+    pub timeout: Duration,
+}
+
+impl Default for FragmentationConfig {
+    fn default() -> Self {
+        FragmentationConfig {
+            fragment_size: 1200,
+            max_fragments: 4096,
+            max_reassemblies: 16,
+            max_buffered: 8 * 1024 * 1024,
+            resend_after: Duration::from_millis(200),
+            timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+// How a payload is delivered, and its place in its stream for the ordered and sequenced ones.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Order {
+    delivery: u8,
+    stream: u8,
+    sequence: u32,
+}
+
+impl Order {
+    fn write(self, packet: &mut Vec<u8>) {
+        packet.push(self.delivery);
+        packet.push(self.stream);
+        packet.extend_from_slice(&self.sequence.to_be_bytes());
+    }
+
+    fn read(bytes: &[u8]) -> std::result::Result<Order, &'static str> {
+        if bytes[0] > SEQUENCED {
+            return Err("Unknown delivery");
+        }
+        Ok(Order {
+            delivery: bytes[0],
+            stream: bytes[1],
+            sequence: read_u32(&bytes[2..6]),
+        })
+    }
+}
+
+// A reliable payload sent in fragments, which are `None` once acked.
+struct Outgoing {
+    fragments: Vec<Option<Vec<u8>>>,
+    started: Instant,
+    sent_at: Instant,
+}
+
+// A payload being received, with the fragments received by index.
+struct Reassembly {
+    fragments: BTreeMap<u16, Vec<u8>>,
+    count: u16,
+    order: Order,
+    started: Instant,
+}
+
+// The payloads of an ordered stream waiting for the earlier ones, or the next payload of a
+// sequenced stream.
+#[derive(Default)]
+struct Stream {
+    next: u32,
+    waiting: BTreeMap<u32, Vec<u8>>,
+}
+
+// What is being received from a peer.
+#[derive(Default)]
+struct Peer {
+    reassemblies: HashMap<u32, Reassembly>,
+    // The payloads completed recently, whose duplicate fragments must be acked but not used.
+    completed: HashMap<u32, Instant>,
+    streams: HashMap<(u8, u8), Stream>,
+    // The bytes of the reassemblies and of the waiting payloads.
+    buffered: usize,
+}
+
+impl Peer {
+    fn is_empty(&self) -> bool {
+        self.reassemblies.is_empty() && self.completed.is_empty() && self.streams.is_empty()
+    }
+}
+
+/// A transport splitting the payloads too large for a datagram in fragments, and assembling them
+/// back on the other side, so level data or large states can be sent over UDP.
+///
+/// The fragments of the reliable payloads are acked one by one, and the lost ones sent again.
+/// The payloads of the ordered and sequenced streams are numbered, so that the fragmented ones
+/// keep their place among the others. The reassemblies of each peer are bounded by the
+/// `FragmentationConfig`. Both endpoints must fragment, which the UDP transport of the
+/// `NetSocketSystem` always does.
+pub struct FragmentingTransport {
+    inner: Box<dyn Transport>,
+    config: FragmentationConfig,
+    next_id: u32,
+    sequences: HashMap<(SocketAddr, u8, u8), u32>,
+    outgoing: HashMap<(SocketAddr, u32), Outgoing>,
+    peers: HashMap<SocketAddr, Peer>,
+    received: VecDeque<TransportEvent>,
+}
+
+impl FragmentingTransport {
+    /// Wraps a transport, failing if the fragment size of the configuration is zero.
+    pub fn new(inner: Box<dyn Transport>, config: FragmentationConfig) -> Result<Self> {
+        if config.fragment_size == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "The fragment size must not be zero",
+            )
+            .into());
+        }
+        Ok(FragmentingTransport {
+            inner,
+            config,
+            next_id: 0,
+            sequences: HashMap::new(),
+            outgoing: HashMap::new(),
+            peers: HashMap::new(),
+            received: VecDeque::new(),
+        })
+    }
+
+    // Numbers the payloads of the ordered and sequenced streams.
+    fn order(&mut self, addr: SocketAddr, delivery: Delivery) -> Order {
+        let (delivery, stream) = match delivery {
+            Delivery::Unreliable | Delivery::UnreliableSequenced(_) => (UNRELIABLE, None),
+            Delivery::ReliableUnordered => (RELIABLE, None),
+            Delivery::ReliableOrdered(stream) => (ORDERED, Some(stream)),
+            Delivery::ReliableSequenced(stream) => (SEQUENCED, Some(stream)),
+        };
+        match stream {
+            Some(stream) => {
+                let stream = stream.unwrap_or(DEFAULT_STREAM);
+                let next = self.sequences.entry((addr, delivery, stream)).or_insert(0);
+                let sequence = *next;
+                *next = next.wrapping_add(1);
+                Order {
+                    delivery,
+                    stream,
+                    sequence,
+                }
+            }
+            None => Order {
+                delivery,
+                stream: 0,
+                sequence: 0,
+            },
+        }
+    }
+
+    fn send_at(
+        &mut self,
+        now: Instant,
+        addr: SocketAddr,
+        payload: Vec<u8>,
+        delivery: Delivery,
+    ) -> Result<()> {
+        if payload.len() <= self.config.fragment_size {
+            let order = self.order(addr, delivery);
+            let mut packet = Vec::with_capacity(payload.len() + 1 + ORDER_HEADER);
+            if order.delivery == ORDERED || order.delivery == SEQUENCED {
+                packet.push(WHOLE_IN_ORDER);
+                order.write(&mut packet);
+            } else {
+                packet.push(WHOLE);
+            }
+            packet.extend_from_slice(&payload);
+            return self.inner.send(addr, packet, delivery);
+        }
+
+        let count = (payload.len() + self.config.fragment_size - 1) / self.config.fragment_size;
+        if count > usize::from(self.config.max_fragments) {
+            return Err(
+                io::Error::new(io::ErrorKind::InvalidInput, "The payload is too large").into(),
+            );
+        }
+        let order = self.order(addr, delivery);
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        let fragments = payload
+            .chunks(self.config.fragment_size)
+            .enumerate()
+            .map(|(index, data)| {
+                let mut packet = Vec::with_capacity(FRAGMENT_HEADER + data.len());
+                packet.push(FRAGMENT);
+                packet.extend_from_slice(&id.to_be_bytes());
+                packet.extend_from_slice(&(index as u16).to_be_bytes());
+                packet.extend_from_slice(&(count as u16).to_be_bytes());
+                order.write(&mut packet);
+                packet.extend_from_slice(data);
+                packet
+            })
+            .collect::<Vec<_>>();
+        for fragment in &fragments {
+            self.inner
+                .send(addr, fragment.clone(), Delivery::Unreliable)?;
+        }
+        if order.delivery != UNRELIABLE {
+            self.outgoing.insert(
+                (addr, id),
+                Outgoing {
+                    fragments: fragments.into_iter().map(Some).collect(),
+                    started: now,
+                    sent_at: now,
+                },
+            );
+        }
+        Ok(())
+    }
+
+    fn recv_at(&mut self, now: Instant) -> Option<TransportEvent> {
+        self.maintain(now);
+        while self.received.is_empty() {
+            match self.inner.recv()? {
+                TransportEvent::Packet { addr, payload } => {
+                    if let Err(reason) = self.receive(now, addr, payload) {
+                        warn!("Dropping a packet from {}: {}", addr, reason);
+                    }
+                }
+                event => return Some(event),
+            }
+        }
+        self.received.pop_front()
+    }
+
+    fn receive(
+        &mut self,
+        now: Instant,
+        addr: SocketAddr,
+        mut payload: Vec<u8>,
+    ) -> std::result::Result<(), &'static str> {
+        let kind = *payload.first().ok_or("Empty packet")?;
+        match kind {
+            WHOLE => {
+                payload.remove(0);
+                self.received
+                    .push_back(TransportEvent::Packet { addr, payload });
+            }
+            WHOLE_IN_ORDER => {
+                if payload.len() < 1 + ORDER_HEADER {
+                    return Err("Truncated order");
+                }
+                let order = Order::read(&payload[1..=ORDER_HEADER])?;
+                let peer = self.peers.entry(addr).or_default();
+                let payload = payload.split_off(1 + ORDER_HEADER);
+                deliver(
+                    peer,
+                    &mut self.received,
+                    self.config.max_buffered,
+                    addr,
+                    order,
+                    payload,
+                )?;
+            }
+            FRAGMENT => {
+                if payload.len() < FRAGMENT_HEADER {
+                    return Err("Truncated fragment header");
+                }
+                let id = read_u32(&payload[1..5]);
+                let index = read_u16(&payload[5..7]);
+                let count = read_u16(&payload[7..9]);
+                let order = Order::read(&payload[9..FRAGMENT_HEADER])?;
+                if index >= count || count > self.config.max_fragments {
+                    return Err("Invalid fragment index");
+                }
+                // All the fragments but the last one are full.
+                let data = &payload[FRAGMENT_HEADER..];
+                let size = self.config.fragment_size;
+                if data.is_empty() || data.len() > size || (index + 1 < count && data.len() != size)
+                {
+                    return Err("Invalid fragment length");
+                }
+
+                let peer = self.peers.entry(addr).or_default();
+                let known = peer.completed.contains_key(&id)
+                    || peer.reassemblies.get(&id).map_or(false, |reassembly| {
+                        reassembly.fragments.contains_key(&index)
+                    });
+                if !known {
+                    if !peer.reassemblies.contains_key(&id)
+                        && peer.reassemblies.len() >= self.config.max_reassemblies
+                    {
+                        return Err("Too many payloads being reassembled");
+                    }
+                    if peer.buffered + data.len() > self.config.max_buffered {
+                        return Err("Too many bytes buffered");
+                    }
+                }
+                // Acked once kept, so that the dropped fragments are sent again.
+                if order.delivery != UNRELIABLE {
+                    let mut ack = Vec::with_capacity(7);
+                    ack.push(ACK);
+                    ack.extend_from_slice(&payload[1..7]);
+                    if let Err(e) = self.inner.send(addr, ack, Delivery::Unreliable) {
+                        error!("Failed to ack a fragment: {}", e);
+                    }
+                }
+                if known {
+                    return Ok(());
+                }
+
+                let reassembly = peer.reassemblies.entry(id).or_insert_with(|| Reassembly {
+                    fragments: BTreeMap::new(),
+                    count,
+                    order,
+                    started: now,
+                });
+                if reassembly.count != count || reassembly.order != order {
+                    return Err("Inconsistent fragment");
+                }
+                reassembly.fragments.insert(index, data.to_vec());
+                peer.buffered += data.len();
+                if reassembly.fragments.len() == usize::from(count) {
+                    let reassembly = peer
+                        .reassemblies
+                        .remove(&id)
+                        .expect("Unreachable: The reassembly was just updated");
+                    let payload = reassembly
+                        .fragments
+                        .into_iter()
+                        .flat_map(|(_, fragment)| fragment)
+                        .collect::<Vec<_>>();
+                    peer.buffered -= payload.len();
+                    peer.completed.insert(id, now);
+                    deliver(
+                        peer,
+                        &mut self.received,
+                        self.config.max_buffered,
+                        addr,
+                        reassembly.order,
+                        payload,
+                    )?;
+                }
+            }
+            ACK => {
+                if payload.len() < 7 {
+                    return Err("Truncated ack");
+                }
+                let id = read_u32(&payload[1..5]);
+                let index = usize::from(read_u16(&payload[5..7]));
+                if let Some(outgoing) = self.outgoing.get_mut(&(addr, id)) {
+                    if let Some(fragment) = outgoing.fragments.get_mut(index) {
+                        *fragment = None;
+                    }
+                    if outgoing.fragments.iter().all(Option::is_none) {
+                        self.outgoing.remove(&(addr, id));
+                    }
+                }
+            }
+            _ => return Err("Unknown packet kind"),
+        }
+        Ok(())
+    }
+
+    // Sends the unacked fragments again, and forgets the payloads that timed out.
+    fn maintain(&mut self, now: Instant) {
+        let timeout = self.config.timeout;
+        for peer in self.peers.values_mut() {
+            let buffered = &mut peer.buffered;
+            peer.reassemblies.retain(|_, reassembly| {
+                let alive = now.duration_since(reassembly.started) < timeout;
+                if !alive {
+                    *buffered -= reassembly.fragments.values().map(Vec::len).sum::<usize>();
+                }
+                alive
+            });
+            peer.completed
+                .retain(|_, completed| now.duration_since(*completed) < timeout);
+        }
+        self.peers.retain(|_, peer| !peer.is_empty());
+
+        let inner = &mut self.inner;
+        let resend_after = self.config.resend_after;
+        self.outgoing.retain(|(addr, _), outgoing| {
+            if now.duration_since(outgoing.started) >= timeout {
+                warn!("A payload sent to {} was not acked in time", addr);
+                return false;
+            }
+            if now.duration_since(outgoing.sent_at) >= resend_after {
+                outgoing.sent_at = now;
+                for fragment in outgoing.fragments.iter().flatten() {
+                    if let Err(e) = inner.send(*addr, fragment.clone(), Delivery::Unreliable) {
+                        error!("Failed to resend a fragment: {}", e);
+                    }
+                }
+            }
+            true
+        });
+    }
+}
+
+// Queues a payload received whole or reassembled, after the earlier payloads of its stream.
+fn deliver(
+    peer: &mut Peer,
+    received: &mut VecDeque<TransportEvent>,
+    max_buffered: usize,
+    addr: SocketAddr,
+    order: Order,
+    payload: Vec<u8>,
+) -> std::result::Result<(), &'static str> {
+    if order.delivery != ORDERED && order.delivery != SEQUENCED {
+        received.push_back(TransportEvent::Packet { addr, payload });
+        return Ok(());
+    }
+    let stream = peer
+        .streams
+        .entry((order.delivery, order.stream))
+        .or_default();
+    let ahead = order.sequence.wrapping_sub(stream.next);
+    if order.delivery == SEQUENCED {
+        // The older payloads arriving late are dropped.
+        if ahead < 1 << 31 {
+            stream.next = order.sequence.wrapping_add(1);
+            received.push_back(TransportEvent::Packet { addr, payload });
+        }
+    } else if ahead == 0 {
+        received.push_back(TransportEvent::Packet { addr, payload });
+        stream.next = stream.next.wrapping_add(1);
+        while let Some(payload) = stream.waiting.remove(&stream.next) {
+            peer.buffered -= payload.len();
+            received.push_back(TransportEvent::Packet { addr, payload });
+            stream.next = stream.next.wrapping_add(1);
+        }
+    } else if ahead < ORDER_WINDOW {
+        if !stream.waiting.contains_key(&order.sequence) {
+            if peer.buffered + payload.len() > max_buffered {
+                return Err("Too many bytes buffered");
+            }
+            peer.buffered += payload.len();
+            stream.waiting.insert(order.sequence, payload);
+        }
+    } else {
+        return Err("Out of the order of its stream");
+    }
+    Ok(())
+}
+
+fn read_u16(bytes: &[u8]) -> u16 {
+    u16::from_be_bytes([bytes[0], bytes[1]])
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+impl Transport for FragmentingTransport {
+    fn send(&mut self, addr: SocketAddr, payload: Vec<u8>, delivery: Delivery) -> Result<()> {
+        self.send_at(Instant::now(), addr, payload, delivery)
+    }
+
+    fn recv(&mut self) -> Option<TransportEvent> {
+        self.recv_at(Instant::now())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.inner.flush()
+    }
+
+    fn disconnect(&mut self, addr: SocketAddr) {
+        self.outgoing.retain(|(sent_to, _), _| *sent_to != addr);
+        self.sequences.retain(|(sent_to, _, _), _| *sent_to != addr);
+        self.peers.remove(&addr);
+        self.inner.disconnect(addr);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use super::*;
+
+    // One direction of a lossy link, dropping the packets whose number is listed.
+    #[derive(Clone, Default)]
+    struct Wire {
+        packets: Arc<Mutex<VecDeque<TransportEvent>>>,
+        sent: Arc<Mutex<usize>>,
+        drop: Vec<usize>,
+    }
+
+    struct Endpoint {
+        out: Wire,
+        inc: Wire,
+    }
+
+    impl Transport for Endpoint {
+        fn send(&mut self, addr: SocketAddr, payload: Vec<u8>, _: Delivery) -> Result<()> {
+            let mut sent = self.out.sent.lock().unwrap();
+            if !self.out.drop.contains(&*sent) {
+                let packet = TransportEvent::Packet { addr, payload };
+                self.out.packets.lock().unwrap().push_back(packet);
+            }
+            *sent += 1;
+            Ok(())
+        }
+
+        fn recv(&mut self) -> Option<TransportEvent> {
+            self.inc.packets.lock().unwrap().pop_front()
+        }
+    }
+
+    // The two ends of a link, the packets of the sender whose number is listed being lost.
+    fn link(
+        drop: Vec<usize>,
+        config: FragmentationConfig,
+    ) -> (FragmentingTransport, FragmentingTransport) {
+        let forward = Wire {
+            drop,
+            ..Default::default()
+        };
+        let backward = Wire::default();
+        let sender = FragmentingTransport::new(
+            Box::new(Endpoint {
+                out: forward.clone(),
+                inc: backward.clone(),
+            }),
+            config.clone(),
+        )
+        .unwrap();
+        let receiver = FragmentingTransport::new(
+            Box::new(Endpoint {
+                out: backward,
+                inc: forward,
+            }),
+            config,
+        )
+        .unwrap();
+        (sender, receiver)
+    }
+
+    fn fragment(id: u32, index: u16, count: u16, data: &[u8]) -> Vec<u8> {
+        let mut packet = vec![FRAGMENT];
+        packet.extend_from_slice(&id.to_be_bytes());
+        packet.extend_from_slice(&index.to_be_bytes());
+        packet.extend_from_slice(&count.to_be_bytes());
+        Order {
+            delivery: RELIABLE,
+            stream: 0,
+            sequence: 0,
+        }
+        .write(&mut packet);
+        packet.extend_from_slice(data);
+        packet
+    }
+
+    #[test]
+    fn lost_fragments_are_resent() {
+        let addr: SocketAddr = "127.0.0.1:3456".parse().unwrap();
+        let config = FragmentationConfig {
+            fragment_size: 100,
+            ..Default::default()
+        };
+        let (mut sender, mut receiver) = link(vec![1, 3], config);
+
+        let payload = (0..450).map(|i| i as u8).collect::<Vec<_>>();
+        let start = Instant::now();
+        sender
+            .send_at(start, addr, payload.clone(), Delivery::ReliableUnordered)
+            .unwrap();
+        assert_eq!(receiver.recv_at(start), None);
+
+        // The acks of the received fragments arrive, and the lost ones are sent again.
+        let later = start + Duration::from_millis(250);
+        assert_eq!(sender.recv_at(later), None);
+        assert_eq!(
+            sender.outgoing[&(addr, 0)]
+                .fragments
+                .iter()
+                .flatten()
+                .count(),
+            2
+        );
+        assert_eq!(
+            receiver.recv_at(later),
+            Some(TransportEvent::Packet { addr, payload })
+        );
+        assert_eq!(sender.recv_at(later), None);
+        assert!(sender.outgoing.is_empty());
+    }
+
+    #[test]
+    fn fragmented_payloads_keep_their_order() {
+        let addr: SocketAddr = "127.0.0.1:3456".parse().unwrap();
+        let config = FragmentationConfig {
+            fragment_size: 100,
+            ..Default::default()
+        };
+        // The second fragment of the large payload is lost once.
+        let (mut sender, mut receiver) = link(vec![1], config);
+
+        let large = vec![1; 250];
+        let small = vec![2; 10];
+        let start = Instant::now();
+        let delivery = Delivery::ReliableOrdered(None);
+        sender
+            .send_at(start, addr, large.clone(), delivery)
+            .unwrap();
+        sender
+            .send_at(start, addr, small.clone(), delivery)
+            .unwrap();
+        assert_eq!(receiver.recv_at(start), None);
+
+        let later = start + Duration::from_millis(250);
+        assert_eq!(sender.recv_at(later), None);
+        assert_eq!(
+            receiver.recv_at(later),
+            Some(TransportEvent::Packet {
+                addr,
+                payload: large
+            })
+        );
+        assert_eq!(
+            receiver.recv_at(later),
+            Some(TransportEvent::Packet {
+                addr,
+                payload: small
+            })
+        );
+        assert_eq!(receiver.peers[&addr].buffered, 0);
+    }
+
+    #[test]
+    fn reassemblies_are_bounded() {
+        let addr: SocketAddr = "127.0.0.1:3456".parse().unwrap();
+        let config = FragmentationConfig {
+            fragment_size: 100,
+            max_reassemblies: 2,
+            max_buffered: 200,
+            ..Default::default()
+        };
+        let (_, mut receiver) = link(Vec::new(), config);
+        let now = Instant::now();
+        let full = [0; 100];
+
+        // Only the last fragment may be shorter.
+        assert!(receiver
+            .receive(now, addr, fragment(0, 0, 2, &full[..50]))
+            .is_err());
+        assert!(receiver
+            .receive(now, addr, fragment(0, 1, 2, &[0; 101]))
+            .is_err());
+        assert!(receiver
+            .receive(now, addr, fragment(0, 1, 2, &full[..50]))
+            .is_ok());
+
+        assert!(receiver
+            .receive(now, addr, fragment(1, 0, 3, &full))
+            .is_ok());
+        assert!(receiver
+            .receive(now, addr, fragment(2, 0, 2, &full))
+            .is_err());
+        assert!(receiver
+            .receive(now, addr, fragment(1, 1, 3, &full))
+            .is_err());
+        assert_eq!(receiver.peers[&addr].buffered, 150);
+
+        // The payloads expire, freeing their bytes.
+        let later = now + Duration::from_secs(11);
+        assert_eq!(receiver.recv_at(later), None);
+        assert!(receiver.peers.is_empty());
+        assert!(receiver
+            .receive(later, addr, fragment(2, 0, 2, &full))
+            .is_ok());
+    }
+
+    #[test]
+    fn zero_fragment_sizes_are_refused() {
+        let config = FragmentationConfig {
+            fragment_size: 0,
+            ..Default::default()
+        };
+        let endpoint = Endpoint {
+            out: Wire::default(),
+            inc: Wire::default(),
+        };
+        assert!(FragmentingTransport::new(Box::new(endpoint), config).is_err());
+    }
+}
@@ -0,0 +1,153 @@
+//! The transports carrying the packets between the endpoints.
+//!
+//! The `NetSocketSystem` only sees payloads and addresses, so the same events can be sent over
+//! UDP, TCP or WebSockets by changing the `TransportKind` of the `ServerConfig`. Transports can
+//! wrap others, to simulate network conditions, to encrypt or to pack the payloads.
+
+use std::{
+    io,
+    net::{SocketAddr, TcpStream},
+    sync::mpsc::{channel, Receiver, TryRecvError},
+    thread,
+    time::Duration,
+};
+
+use crate::{error::Result, server::ServerConfig};
+
+pub use self::{
+    encryption::{AuthToken, EncryptedTransport, EncryptionConfig},
+    fragmentation::{FragmentationConfig, FragmentingTransport},
+    packing::{PackingConfig, PackingTransport},
+    simulator::{NetworkConditions, SimulatedTransport},
+    tcp::TcpTransport,
+    udp::UdpTransport,
+    websocket::WebSocketTransport,
+};
+
+mod encryption;
+mod fragmentation;
+mod packing;
+mod simulator;
+mod tcp;
+mod udp;
+mod websocket;
+
+/// The largest payload the stream transports accept, to refuse garbage lengths.
+pub const MAX_STREAM_PAYLOAD: usize = 16 * 1024 * 1024;
+
+/// How long the stream transports try to connect to an endpoint.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A TCP stream connecting on a thread of its own, so that connecting doesn't block the
+/// `NetSocketSystem`.
+struct PendingStream(Receiver<io::Result<TcpStream>>);
+
+impl PendingStream {
+    fn connect(addr: SocketAddr) -> Self {
+        let (sender, receiver) = channel();
+        thread::spawn(move || {
+            // The transport may have given up on the connection already.
+            let _ = sender.send(TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT));
+        });
+        PendingStream(receiver)
+    }
+
+    // The stream once it's connected, `None` until then.
+    fn poll(&self) -> io::Result<Option<TcpStream>> {
+        match self.0.try_recv() {
+            Ok(stream) => stream.map(Some),
+            Err(TryRecvError::Empty) => Ok(None),
+            Err(TryRecvError::Disconnected) => Err(io::Error::new(
+                io::ErrorKind::Other,
+                "The connecting thread stopped",
+            )),
+        }
+    }
+}
+
+/// How a payload should be delivered, see `NetPacket` for the guarantees of each.
+///
+/// The stream transports deliver every payload reliably and in order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Delivery {
+    /// May be dropped, duplicated or arrive out of order.
+    Unreliable,
+    /// May be dropped, older payloads of the stream arriving late are dropped.
+    UnreliableSequenced(Option<u8>),
+    /// Always arrives, in any order.
+    ReliableUnordered,
+    /// Always arrives, in the order of the stream.
+    ReliableOrdered(Option<u8>),
+    /// Always arrives, older payloads of the stream arriving late are dropped.
+    ReliableSequenced(Option<u8>),
+}
+
+/// Something that happened on a transport.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TransportEvent {
+    /// A payload was received.
+    Packet {
+        /// The address it came from.
+        addr: SocketAddr,
+        /// The payload.
+        payload: Vec<u8>,
+    },
+    /// A remote endpoint connected, or was connected to.
+    Connected(SocketAddr),
+    /// A remote endpoint disconnected or timed out.
+    Disconnected(SocketAddr),
+}
+
+/// A way to exchange payloads with remote endpoints.
+///
+/// The stream transports identify the remote endpoints by the address of their side of the
+/// stream, which for the clients connecting to a server is not the address they are bound to.
+/// Servers should answer the address the packets came from.
+pub trait Transport: Send {
+    /// Sends a payload to the endpoint at `addr`, connecting to it first if needed.
+    fn send(&mut self, addr: SocketAddr, payload: Vec<u8>, delivery: Delivery) -> Result<()>;
+
+    /// Returns the next event, if any, without blocking.
+    fn recv(&mut self) -> Option<TransportEvent>;
+
+    /// Sends what the transport buffered, called once per frame after the events were sent.
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Closes the connection to an endpoint, for the transports that have connections.
+    fn disconnect(&mut self, _addr: SocketAddr) {}
+}
+
+/// The transports available to the `NetSocketSystem`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TransportKind {
+    /// UDP, with the reliability handled by `laminar` and the large payloads split by a
+    /// `FragmentingTransport`.
+    Udp,
+    /// TCP, with each payload prefixed by its length.
+    Tcp,
+    /// WebSockets, with each payload sent as a binary message. Unlike the others, this transport
+    /// can be reached from a browser.
+    WebSocket,
+}
+
+impl Default for TransportKind {
+    fn default() -> Self {
+        TransportKind::Udp
+    }
+}
+
+impl TransportKind {
+    /// Binds a transport of this kind to the address of the configuration.
+    pub fn bind(self, config: &ServerConfig) -> Result<Box<dyn Transport>> {
+        Ok(match self {
+            TransportKind::Udp => Box::new(FragmentingTransport::new(
+                Box::new(UdpTransport::bind(config)?),
+                config.fragmentation.clone(),
+            )?),
+            TransportKind::Tcp => Box::new(TcpTransport::bind(config.udp_socket_addr)?),
+            TransportKind::WebSocket => Box::new(WebSocketTransport::bind(config.udp_socket_addr)?),
+        })
+    }
+}
@@ -0,0 +1,269 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    io,
+    net::SocketAddr,
+};
+
+use log::warn;
+
+use crate::error::Result;
+
+use super::{Delivery, Transport, TransportEvent, MAX_STREAM_PAYLOAD};
+
+// The body of the packet is compressed with LZ4, prefixed by its decompressed size.
+const COMPRESSED: u8 = 1;
+// The body is a single payload, instead of payloads prefixed by their length.
+const SINGLE: u8 = 2;
+
+/// How the `PackingTransport` aggregates and compresses the payloads.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PackingConfig {
+    /// The largest packet the payloads sent in a frame are aggregated into. The default 1200
+    /// bytes fit in the MTU of most networks with room for the headers.
+    pub mtu: usize,
+    /// The size above which a packet is compressed, `None` to never compress.
+    pub compress_above: Option<usize>,
+    /// The largest packet accepted once decompressed, so that a packet can't claim a garbage
+    /// size to allocate.
+    pub max_decompressed: usize,
+}
+
+impl Default for PackingConfig {
+    fn default() -> Self {
+        PackingConfig {
+            mtu: 1200,
+            compress_above: Some(128),
+            max_decompressed: MAX_STREAM_PAYLOAD,
+        }
+    }
+}
+
+/// A transport aggregating the small payloads sent during a frame into MTU-sized packets, and
+/// compressing the large packets, to save system calls and headers.
+///
+/// The payloads are aggregated with the ones sent to the same address with the same delivery,
+/// so they keep their guarantees. Both endpoints must pack.
+pub struct PackingTransport {
+    inner: Box<dyn Transport>,
+    config: PackingConfig,
+    batches: HashMap<(SocketAddr, Delivery), Vec<Vec<u8>>>,
+    received: VecDeque<TransportEvent>,
+}
+
+impl PackingTransport {
+    /// Wraps a transport.
+    pub fn new(inner: Box<dyn Transport>, config: PackingConfig) -> Self {
+        PackingTransport {
+            inner,
+            config,
+            batches: HashMap::new(),
+            received: VecDeque::new(),
+        }
+    }
+
+    fn send_batch(
+        &mut self,
+        addr: SocketAddr,
+        delivery: Delivery,
+        batch: Vec<Vec<u8>>,
+    ) -> Result<()> {
+        if batch.is_empty() {
+            return Ok(());
+        }
+        let (mut flags, body) = if batch.len() == 1 {
+            (
+                SINGLE,
+                batch.into_iter().next().expect("Unreachable: One payload"),
+            )
+        } else {
+            let mut body = Vec::with_capacity(batch.iter().map(|p| p.len() + 2).sum());
+            for payload in batch {
+                body.extend_from_slice(&(payload.len() as u16).to_be_bytes());
+                body.extend_from_slice(&payload);
+            }
+            (0, body)
+        };
+        let body = match self.config.compress_above {
+            Some(threshold) if body.len() > threshold => {
+                let compressed = lz4::block::compress(&body, None, true)?;
+                if compressed.len() < body.len() {
+                    flags |= COMPRESSED;
+                    compressed
+                } else {
+                    body
+                }
+            }
+            _ => body,
+        };
+        let mut packet = Vec::with_capacity(body.len() + 1);
+        packet.push(flags);
+        packet.extend_from_slice(&body);
+        self.inner.send(addr, packet, delivery)
+    }
+}
+
+// Splits a packet into its payloads, refusing those decompressing to more than `max` bytes.
+fn unpack(packet: &[u8], max: usize) -> io::Result<Vec<Vec<u8>>> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "Invalid packed payload");
+    let (flags, body) = packet.split_first().ok_or_else(invalid)?;
+    let decompressed;
+    let body = if flags & COMPRESSED != 0 {
+        if body.len() < 4 {
+            return Err(invalid());
+        }
+        // The size prefix written by `compress`, in little endian.
+        let size = u32::from_le_bytes([body[0], body[1], body[2], body[3]]) as usize;
+        if size > max || size > i32::max_value() as usize {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Packed payload of {} bytes larger than {} bytes", size, max),
+            ));
+        }
+        decompressed = lz4::block::decompress(&body[4..], Some(size as i32))?;
+        &decompressed[..]
+    } else {
+        body
+    };
+    if flags & SINGLE != 0 {
+        return Ok(vec![body.to_vec()]);
+    }
+    let mut payloads = Vec::new();
+    let mut rest = body;
+    while !rest.is_empty() {
+        if rest.len() < 2 {
+            return Err(invalid());
+        }
+        let length = usize::from(u16::from_be_bytes([rest[0], rest[1]]));
+        if rest.len() < 2 + length {
+            return Err(invalid());
+        }
+        payloads.push(rest[2..2 + length].to_vec());
+        rest = &rest[2 + length..];
+    }
+    Ok(payloads)
+}
+
+impl Transport for PackingTransport {
+    fn send(&mut self, addr: SocketAddr, payload: Vec<u8>, delivery: Delivery) -> Result<()> {
+        // A payload too large to share a packet is sent alone right away, after the previous
+        // ones so that their order is kept.
+        if payload.len() + 2 > self.config.mtu {
+            if let Some(batch) = self.batches.remove(&(addr, delivery)) {
+                self.send_batch(addr, delivery, batch)?;
+            }
+            return self.send_batch(addr, delivery, vec![payload]);
+        }
+        let mtu = self.config.mtu;
+        let batch = self
+            .batches
+            .entry((addr, delivery))
+            .or_insert_with(Vec::new);
+        let size = 1 + batch.iter().map(|p| p.len() + 2).sum::<usize>();
+        if size + payload.len() + 2 > mtu {
+            let full = std::mem::replace(batch, vec![payload]);
+            return self.send_batch(addr, delivery, full);
+        }
+        batch.push(payload);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        let batches = self.batches.drain().collect::<Vec<_>>();
+        for ((addr, delivery), batch) in batches {
+            self.send_batch(addr, delivery, batch)?;
+        }
+        self.inner.flush()
+    }
+
+    fn recv(&mut self) -> Option<TransportEvent> {
+        while self.received.is_empty() {
+            match self.inner.recv()? {
+                TransportEvent::Packet { addr, payload } => {
+                    match unpack(&payload, self.config.max_decompressed) {
+                        Ok(payloads) => self.received.extend(
+                            payloads
+                                .into_iter()
+                                .map(|payload| TransportEvent::Packet { addr, payload }),
+                        ),
+                        Err(e) => warn!("Dropping a packet from {}: {}", addr, e),
+                    }
+                }
+                event => return Some(event),
+            }
+        }
+        self.received.pop_front()
+    }
+
+    fn disconnect(&mut self, addr: SocketAddr) {
+        self.batches
+            .retain(|(batch_addr, _), _| *batch_addr != addr);
+        self.inner.disconnect(addr);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Loopback(VecDeque<TransportEvent>);
+
+    impl Transport for Loopback {
+        fn send(&mut self, addr: SocketAddr, payload: Vec<u8>, _: Delivery) -> Result<()> {
+            self.0.push_back(TransportEvent::Packet { addr, payload });
+            Ok(())
+        }
+
+        fn recv(&mut self) -> Option<TransportEvent> {
+            self.0.pop_front()
+        }
+    }
+
+    #[test]
+    fn payloads_survive_packing() {
+        let addr: SocketAddr = "127.0.0.1:3456".parse().unwrap();
+        let mut payloads = (0..50u8).map(|i| vec![i; 40]).collect::<Vec<_>>();
+        // Too large to be aggregated, and compressible.
+        payloads.push(vec![7; 5000]);
+        payloads.push(vec![1, 2, 3]);
+
+        let mut sender = PackingTransport::new(Box::new(Loopback::default()), Default::default());
+        for payload in &payloads {
+            sender
+                .send(addr, payload.clone(), Delivery::Unreliable)
+                .unwrap();
+        }
+        sender.flush().unwrap();
+
+        let mut packets = 0;
+        let mut receiver = PackingTransport::new(Box::new(Loopback::default()), Default::default());
+        while let Some(TransportEvent::Packet { addr, payload }) = sender.inner.recv() {
+            assert!(payload.len() <= 1200);
+            packets += 1;
+            receiver
+                .inner
+                .send(addr, payload, Delivery::Unreliable)
+                .unwrap();
+        }
+        assert!(packets < 10);
+
+        let mut received = Vec::new();
+        while let Some(TransportEvent::Packet { payload, .. }) = receiver.recv() {
+            received.push(payload);
+        }
+        assert_eq!(received, payloads);
+    }
+
+    #[test]
+    fn oversized_packets_are_refused() {
+        let body = vec![7; 5000];
+        let mut packet = vec![SINGLE | COMPRESSED];
+        packet.extend_from_slice(&lz4::block::compress(&body, None, true).unwrap());
+        assert_eq!(unpack(&packet, 5000).unwrap(), vec![body]);
+        assert!(unpack(&packet, 4999).is_err());
+
+        // A size prefix of 4 GiB.
+        let packet = [SINGLE | COMPRESSED, 0xff, 0xff, 0xff, 0xff, 0];
+        assert!(unpack(&packet, MAX_STREAM_PAYLOAD).is_err());
+    }
+}
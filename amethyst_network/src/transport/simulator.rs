@@ -0,0 +1,362 @@
+use std::{
+    cmp::Ordering,
+    collections::BinaryHeap,
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+use log::error;
+use rand::{Rng, SeedableRng};
+use rand_pcg::Pcg32;
+
+use crate::error::Result;
+
+use super::{Delivery, Transport, TransportEvent};
+
+/// The conditions of the network simulated by the `SimulatedTransport`, in each direction.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NetworkConditions {
+    /// How long the payloads take to arrive.
+    pub latency: Duration,
+    /// The most the latency varies, up or down, for each payload.
+    pub jitter: Duration,
+    /// The probability for an unreliable payload to be lost, between 0 and 1.
+    pub loss: f32,
+    /// The probability for an unreliable payload to arrive twice, between 0 and 1.
+    pub duplication: f32,
+    /// The probability for an unreliable payload to be held back, arriving after the ones sent
+    /// after it.
+    pub reordering: f32,
+    /// The seed of the random decisions, to reproduce a run.
+    pub seed: u64,
+}
+
+impl Default for NetworkConditions {
+    fn default() -> Self {
+        NetworkConditions {
+            latency: Duration::from_millis(50),
+            jitter: Duration::from_millis(10),
+            loss: 0.01,
+            duplication: 0.0,
+            reordering: 0.0,
+            seed: 0,
+        }
+    }
+}
+
+// A payload waiting to be delivered, the earliest first in the heap.
+struct Delayed<T> {
+    at: Instant,
+    order: u64,
+    item: T,
+}
+
+impl<T> PartialEq for Delayed<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.at == other.at && self.order == other.order
+    }
+}
+
+impl<T> Eq for Delayed<T> {}
+
+impl<T> PartialOrd for Delayed<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for Delayed<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (other.at, other.order).cmp(&(self.at, self.order))
+    }
+}
+
+struct Link<T> {
+    queue: BinaryHeap<Delayed<T>>,
+    order: u64,
+}
+
+impl<T> Link<T> {
+    fn new() -> Self {
+        Link {
+            queue: BinaryHeap::new(),
+            order: 0,
+        }
+    }
+
+    fn push(&mut self, at: Instant, item: T) {
+        self.order += 1;
+        self.queue.push(Delayed {
+            at,
+            order: self.order,
+            item,
+        });
+    }
+
+    fn pop(&mut self, now: Instant) -> Option<T> {
+        if self.queue.peek().map_or(false, |delayed| delayed.at <= now) {
+            self.queue.pop().map(|delayed| delayed.item)
+        } else {
+            None
+        }
+    }
+}
+
+/// A transport delaying, losing, duplicating and reordering the payloads of another one, to test
+/// the netcode on localhost under realistic conditions.
+///
+/// The transports keep their guarantees: only the unreliable payloads are lost, duplicated and
+/// reordered, the reliable ones are delayed, and those of the ordered and sequenced deliveries
+/// arrive in the order they were sent. As the received payloads can't be told apart by their
+/// delivery, they are only delayed by the latency, so the round trip time is four times the
+/// latency. The connection events are not delayed.
+pub struct SimulatedTransport {
+    inner: Box<dyn Transport>,
+    conditions: NetworkConditions,
+    rng: Pcg32,
+    outgoing: Link<(SocketAddr, Vec<u8>, Delivery)>,
+    incoming: Link<TransportEvent>,
+    // When the last ordered payload is sent, which the next ones can't overtake.
+    last_ordered: Option<Instant>,
+}
+
+impl SimulatedTransport {
+    /// Wraps a transport.
+    pub fn new(inner: Box<dyn Transport>, conditions: NetworkConditions) -> Self {
+        SimulatedTransport {
+            inner,
+            rng: Pcg32::seed_from_u64(conditions.seed),
+            conditions,
+            outgoing: Link::new(),
+            incoming: Link::new(),
+            last_ordered: None,
+        }
+    }
+
+    // When the copies of a payload are sent, none if it is lost.
+    fn schedule(&mut self, now: Instant, delivery: Delivery) -> Vec<Instant> {
+        let (unreliable, ordered) = match delivery {
+            Delivery::Unreliable | Delivery::UnreliableSequenced(_) => (true, false),
+            Delivery::ReliableUnordered => (false, false),
+            Delivery::ReliableOrdered(_) | Delivery::ReliableSequenced(_) => (false, true),
+        };
+        if unreliable && self.rng.gen::<f32>() < self.conditions.loss {
+            return Vec::new();
+        }
+        let copies = if unreliable && self.rng.gen::<f32>() < self.conditions.duplication {
+            2
+        } else {
+            1
+        };
+        let mut times = (0..copies)
+            .map(|_| {
+                let jitter = self.conditions.jitter.as_micros() as i64;
+                let jitter = if jitter > 0 {
+                    self.rng.gen_range(-jitter, jitter + 1)
+                } else {
+                    0
+                };
+                let mut delay = (self.conditions.latency.as_micros() as i64 + jitter).max(0);
+                if unreliable && self.rng.gen::<f32>() < self.conditions.reordering {
+                    // Held back long enough for the next payloads to overtake it.
+                    delay += self.conditions.latency.as_micros() as i64 + 2 * jitter.abs() + 1;
+                }
+                now + Duration::from_micros(delay as u64)
+            })
+            .collect::<Vec<_>>();
+        if ordered {
+            // The payloads sent at the same time leave in the order they were sent.
+            for at in &mut times {
+                *at = self.last_ordered.map_or(*at, |last| last.max(*at));
+                self.last_ordered = Some(*at);
+            }
+        }
+        times
+    }
+
+    fn send_at(
+        &mut self,
+        now: Instant,
+        addr: SocketAddr,
+        payload: Vec<u8>,
+        delivery: Delivery,
+    ) -> Result<()> {
+        self.send_due(now)?;
+        for at in self.schedule(now, delivery) {
+            self.outgoing.push(at, (addr, payload.clone(), delivery));
+        }
+        Ok(())
+    }
+
+    fn recv_at(&mut self, now: Instant) -> Option<TransportEvent> {
+        if let Err(e) = self.send_due(now) {
+            error!("Failed to send a delayed payload: {}", e);
+        }
+        while let Some(event) = self.inner.recv() {
+            match event {
+                TransportEvent::Packet { addr, payload } => self.incoming.push(
+                    now + self.conditions.latency,
+                    TransportEvent::Packet { addr, payload },
+                ),
+                // Like the real network, the connection events come right away.
+                event => self.incoming.push(now, event),
+            }
+        }
+        self.incoming.pop(now)
+    }
+
+    fn send_due(&mut self, now: Instant) -> Result<()> {
+        while let Some((addr, payload, delivery)) = self.outgoing.pop(now) {
+            self.inner.send(addr, payload, delivery)?;
+        }
+        Ok(())
+    }
+}
+
+impl Transport for SimulatedTransport {
+    fn send(&mut self, addr: SocketAddr, payload: Vec<u8>, delivery: Delivery) -> Result<()> {
+        self.send_at(Instant::now(), addr, payload, delivery)
+    }
+
+    fn recv(&mut self) -> Option<TransportEvent> {
+        self.recv_at(Instant::now())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.inner.flush()
+    }
+
+    fn disconnect(&mut self, addr: SocketAddr) {
+        self.inner.disconnect(addr);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use super::*;
+
+    // Loops the sent payloads back.
+    #[derive(Default)]
+    struct Loopback(VecDeque<TransportEvent>);
+
+    impl Transport for Loopback {
+        fn send(&mut self, addr: SocketAddr, payload: Vec<u8>, _: Delivery) -> Result<()> {
+            self.0.push_back(TransportEvent::Packet { addr, payload });
+            Ok(())
+        }
+
+        fn recv(&mut self) -> Option<TransportEvent> {
+            self.0.pop_front()
+        }
+    }
+
+    fn run(conditions: NetworkConditions, count: u8, delivery: Delivery) -> Vec<u8> {
+        let mut transport = SimulatedTransport::new(Box::new(Loopback::default()), conditions);
+        let addr = "127.0.0.1:3456".parse().unwrap();
+        let start = Instant::now();
+        for i in 0..count {
+            transport.send_at(start, addr, vec![i], delivery).unwrap();
+        }
+        let mut received = Vec::new();
+        for step in 0..100 {
+            let now = start + Duration::from_millis(step * 10);
+            while let Some(event) = transport.recv_at(now) {
+                if let TransportEvent::Packet { payload, .. } = event {
+                    received.push(payload[0]);
+                }
+            }
+        }
+        received
+    }
+
+    #[test]
+    fn payloads_are_delayed() {
+        let mut transport = SimulatedTransport::new(
+            Box::new(Loopback::default()),
+            NetworkConditions {
+                latency: Duration::from_millis(50),
+                jitter: Duration::from_millis(0),
+                loss: 0.0,
+                ..Default::default()
+            },
+        );
+        let addr = "127.0.0.1:3456".parse().unwrap();
+        let start = Instant::now();
+        transport
+            .send_at(start, addr, vec![1], Delivery::Unreliable)
+            .unwrap();
+        assert!(transport
+            .recv_at(start + Duration::from_millis(40))
+            .is_none());
+        // Sent after 50ms, received 50ms later.
+        assert!(transport
+            .recv_at(start + Duration::from_millis(60))
+            .is_none());
+        assert!(transport
+            .recv_at(start + Duration::from_millis(110))
+            .is_some());
+    }
+
+    #[test]
+    fn unreliable_payloads_are_lost_duplicated_and_reordered() {
+        let perfect = NetworkConditions {
+            jitter: Duration::from_millis(0),
+            loss: 0.0,
+            ..Default::default()
+        };
+        assert_eq!(
+            run(perfect.clone(), 100, Delivery::Unreliable),
+            (0..100).collect::<Vec<_>>()
+        );
+
+        let lossy = run(
+            NetworkConditions {
+                loss: 0.5,
+                ..perfect.clone()
+            },
+            100,
+            Delivery::Unreliable,
+        );
+        assert!(lossy.len() < 80 && lossy.len() > 20);
+
+        let duplicated = run(
+            NetworkConditions {
+                duplication: 0.5,
+                ..perfect.clone()
+            },
+            100,
+            Delivery::Unreliable,
+        );
+        assert!(duplicated.len() > 120);
+
+        let mut reordered = run(
+            NetworkConditions {
+                reordering: 0.5,
+                ..perfect
+            },
+            100,
+            Delivery::Unreliable,
+        );
+        assert_ne!(reordered, (0..100).collect::<Vec<_>>());
+        reordered.sort();
+        assert_eq!(reordered, (0..100).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn reliable_payloads_are_only_delayed() {
+        let conditions = NetworkConditions {
+            loss: 0.5,
+            duplication: 0.5,
+            reordering: 0.5,
+            ..Default::default()
+        };
+        let ordered = run(conditions.clone(), 100, Delivery::ReliableOrdered(None));
+        assert_eq!(ordered, (0..100).collect::<Vec<_>>());
+
+        let mut unordered = run(conditions, 100, Delivery::ReliableUnordered);
+        unordered.sort();
+        assert_eq!(unordered, (0..100).collect::<Vec<_>>());
+    }
+}
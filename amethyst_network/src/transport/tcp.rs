@@ -0,0 +1,275 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    io::{self, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+};
+
+use log::warn;
+
+use crate::error::Result;
+
+use super::{Delivery, PendingStream, Transport, TransportEvent, MAX_STREAM_PAYLOAD};
+
+struct Connection {
+    stream: TcpStream,
+    incoming: Vec<u8>,
+    outgoing: Vec<u8>,
+}
+
+impl Connection {
+    fn new(stream: TcpStream) -> io::Result<Self> {
+        stream.set_nonblocking(true)?;
+        stream.set_nodelay(true)?;
+        Ok(Connection {
+            stream,
+            incoming: Vec::new(),
+            outgoing: Vec::new(),
+        })
+    }
+
+    // Writes as much of the outgoing data as the socket takes.
+    fn flush(&mut self) -> io::Result<()> {
+        while !self.outgoing.is_empty() {
+            match self.stream.write(&self.outgoing) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(written) => {
+                    self.outgoing.drain(..written);
+                }
+                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(())
+    }
+
+    // Reads what is available and splits it into payloads. Returns whether the stream is open.
+    fn read(
+        &mut self,
+        addr: SocketAddr,
+        events: &mut VecDeque<TransportEvent>,
+    ) -> io::Result<bool> {
+        let mut buffer = [0; 4096];
+        let mut open = true;
+        loop {
+            match self.stream.read(&mut buffer) {
+                Ok(0) => {
+                    open = false;
+                    break;
+                }
+                Ok(read) => self.incoming.extend_from_slice(&buffer[..read]),
+                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+        while self.incoming.len() >= 4 {
+            let mut length = [0; 4];
+            length.copy_from_slice(&self.incoming[..4]);
+            let length = u32::from_be_bytes(length) as usize;
+            if length > MAX_STREAM_PAYLOAD {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "The payload is too large",
+                ));
+            }
+            if self.incoming.len() < 4 + length {
+                break;
+            }
+            let payload = self.incoming[4..4 + length].to_vec();
+            self.incoming.drain(..4 + length);
+            events.push_back(TransportEvent::Packet { addr, payload });
+        }
+        Ok(open)
+    }
+}
+
+/// The TCP transport, sending each payload prefixed by its length in bytes.
+///
+/// It listens for connections on the address it is bound to, and connects to the endpoints it
+/// sends to. Connecting happens in the background, the payloads sent meanwhile are written once
+/// the stream is connected.
+pub struct TcpTransport {
+    listener: TcpListener,
+    connections: HashMap<SocketAddr, Connection>,
+    // The streams being connected, with the data to write once they are.
+    connecting: HashMap<SocketAddr, (PendingStream, Vec<u8>)>,
+    events: VecDeque<TransportEvent>,
+}
+
+impl TcpTransport {
+    /// Listens on the given address.
+    pub fn bind(addr: SocketAddr) -> Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(TcpTransport {
+            listener,
+            connections: HashMap::new(),
+            connecting: HashMap::new(),
+            events: VecDeque::new(),
+        })
+    }
+
+    /// The address the transport listens on.
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    fn poll(&mut self) {
+        let mut connected = Vec::new();
+        for (addr, (pending, _)) in &self.connecting {
+            match pending.poll() {
+                Ok(Some(stream)) => connected.push((*addr, Ok(stream))),
+                Ok(None) => {}
+                Err(err) => connected.push((*addr, Err(err))),
+            }
+        }
+        for (addr, stream) in connected {
+            let (_, outgoing) = self
+                .connecting
+                .remove(&addr)
+                .expect("Unreachable: The stream was just polled");
+            match stream.and_then(Connection::new) {
+                Ok(mut connection) => {
+                    connection.outgoing = outgoing;
+                    self.connections.insert(addr, connection);
+                    self.events.push_back(TransportEvent::Connected(addr));
+                }
+                Err(err) => {
+                    warn!("Failed to connect to {}: {}", addr, err);
+                    self.events.push_back(TransportEvent::Disconnected(addr));
+                }
+            }
+        }
+
+        loop {
+            match self.listener.accept() {
+                Ok((stream, addr)) => match Connection::new(stream) {
+                    Ok(connection) => {
+                        self.connections.insert(addr, connection);
+                        self.events.push_back(TransportEvent::Connected(addr));
+                    }
+                    Err(err) => warn!("Failed to accept a connection from {}: {}", addr, err),
+                },
+                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) => {
+                    warn!("Failed to accept a connection: {}", err);
+                    break;
+                }
+            }
+        }
+
+        let events = &mut self.events;
+        let mut closed = Vec::new();
+        for (addr, connection) in &mut self.connections {
+            match connection
+                .flush()
+                .and_then(|_| connection.read(*addr, events))
+            {
+                Ok(true) => {}
+                Ok(false) => closed.push(*addr),
+                Err(err) => {
+                    warn!("Closing the connection to {}: {}", addr, err);
+                    closed.push(*addr);
+                }
+            }
+        }
+        for addr in closed {
+            self.disconnect(addr);
+        }
+    }
+}
+
+impl Transport for TcpTransport {
+    fn send(&mut self, addr: SocketAddr, payload: Vec<u8>, _delivery: Delivery) -> Result<()> {
+        if payload.len() > MAX_STREAM_PAYLOAD {
+            return Err(
+                io::Error::new(io::ErrorKind::InvalidInput, "The payload is too large").into(),
+            );
+        }
+        let length = (payload.len() as u32).to_be_bytes();
+        let connection = match self.connections.get_mut(&addr) {
+            Some(connection) => connection,
+            None => {
+                let (_, outgoing) = self
+                    .connecting
+                    .entry(addr)
+                    .or_insert_with(|| (PendingStream::connect(addr), Vec::new()));
+                outgoing.extend_from_slice(&length);
+                outgoing.extend_from_slice(&payload);
+                return Ok(());
+            }
+        };
+        connection.outgoing.extend_from_slice(&length);
+        connection.outgoing.extend_from_slice(&payload);
+        if let Err(err) = connection.flush() {
+            self.disconnect(addr);
+            return Err(err.into());
+        }
+        Ok(())
+    }
+
+    fn recv(&mut self) -> Option<TransportEvent> {
+        if self.events.is_empty() {
+            self.poll();
+        }
+        self.events.pop_front()
+    }
+
+    fn disconnect(&mut self, addr: SocketAddr) {
+        let connecting = self.connecting.remove(&addr).is_some();
+        if self.connections.remove(&addr).is_some() || connecting {
+            self.events.push_back(TransportEvent::Disconnected(addr));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{thread::sleep, time::Duration};
+
+    use super::*;
+
+    // Also polls `other`, which writes what it sent once it's connected.
+    fn wait_for_packet(
+        transport: &mut TcpTransport,
+        other: &mut TcpTransport,
+    ) -> (SocketAddr, Vec<u8>) {
+        for _ in 0..100 {
+            while other.recv().is_some() {}
+            while let Some(event) = transport.recv() {
+                if let TransportEvent::Packet { addr, payload } = event {
+                    return (addr, payload);
+                }
+            }
+            sleep(Duration::from_millis(10));
+        }
+        panic!("No packet was received");
+    }
+
+    #[test]
+    fn send_and_answer() {
+        let localhost = "127.0.0.1:0".parse().unwrap();
+        let mut server = TcpTransport::bind(localhost).unwrap();
+        let mut client = TcpTransport::bind(localhost).unwrap();
+        let server_addr = server.local_addr().unwrap();
+
+        client
+            .send(
+                server_addr,
+                b"ping".to_vec(),
+                Delivery::ReliableOrdered(None),
+            )
+            .unwrap();
+        let (client_addr, payload) = wait_for_packet(&mut server, &mut client);
+        assert_eq!(payload, b"ping".to_vec());
+
+        server
+            .send(client_addr, b"pong".to_vec(), Delivery::Unreliable)
+            .unwrap();
+        assert_eq!(
+            wait_for_packet(&mut client, &mut server),
+            (server_addr, b"pong".to_vec())
+        );
+    }
+}
@@ -0,0 +1,54 @@
+use std::net::SocketAddr;
+
+use crossbeam_channel::Receiver;
+use laminar::{Packet, SocketEvent};
+
+use crate::{
+    error::Result,
+    server::{Host, ServerConfig},
+};
+
+use super::{Delivery, Transport, TransportEvent};
+
+/// The UDP transport, sending the packets through a `laminar` socket polled on its own thread.
+pub struct UdpTransport {
+    host: Host,
+    receiver: Receiver<SocketEvent>,
+}
+
+impl UdpTransport {
+    /// Binds the socket to the address of the configuration and starts polling it.
+    pub fn bind(config: &ServerConfig) -> Result<Self> {
+        let host = Host::run(config)?;
+        let receiver = host.udp_receive_handle();
+        Ok(UdpTransport { host, receiver })
+    }
+}
+
+impl Transport for UdpTransport {
+    fn send(&mut self, addr: SocketAddr, payload: Vec<u8>, delivery: Delivery) -> Result<()> {
+        let packet = match delivery {
+            Delivery::Unreliable => Packet::unreliable(addr, payload),
+            Delivery::UnreliableSequenced(stream) => {
+                Packet::unreliable_sequenced(addr, payload, stream)
+            }
+            Delivery::ReliableUnordered => Packet::reliable_unordered(addr, payload),
+            Delivery::ReliableOrdered(stream) => Packet::reliable_ordered(addr, payload, stream),
+            Delivery::ReliableSequenced(stream) => {
+                Packet::reliable_sequenced(addr, payload, stream)
+            }
+        };
+        self.host.send_udp(packet)
+    }
+
+    fn recv(&mut self) -> Option<TransportEvent> {
+        self.receiver.try_recv().ok().map(|event| match event {
+            SocketEvent::Packet(packet) => TransportEvent::Packet {
+                addr: packet.addr(),
+                payload: packet.payload().to_vec(),
+            },
+            SocketEvent::Connect(addr) => TransportEvent::Connected(addr),
+            SocketEvent::Timeout(addr) => TransportEvent::Disconnected(addr),
+        })
+    }
+}
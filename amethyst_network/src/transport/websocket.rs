@@ -0,0 +1,293 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    io,
+    net::{SocketAddr, TcpListener, TcpStream},
+};
+
+use log::warn;
+use tungstenite::{
+    handshake::{
+        client::ClientHandshake,
+        server::{NoCallback, ServerHandshake},
+        HandshakeError, MidHandshake,
+    },
+    Message, WebSocket,
+};
+use url::Url;
+
+use crate::error::{Error, Result};
+
+use super::{Delivery, PendingStream, Transport, TransportEvent, MAX_STREAM_PAYLOAD};
+
+enum Connection {
+    Accepting(MidHandshake<ServerHandshake<TcpStream, NoCallback>>),
+    // The payloads sent before the stream connected and the handshake completed are sent once
+    // they did.
+    Opening(PendingStream, Vec<Vec<u8>>),
+    Connecting(MidHandshake<ClientHandshake<TcpStream>>, Vec<Vec<u8>>),
+    Open(WebSocket<TcpStream>),
+}
+
+fn would_block(err: &tungstenite::Error) -> bool {
+    match err {
+        tungstenite::Error::Io(err) => err.kind() == io::ErrorKind::WouldBlock,
+        _ => false,
+    }
+}
+
+impl Connection {
+    // Advances the handshake, then writes the queued messages and reads the received ones.
+    fn poll(
+        self,
+        addr: SocketAddr,
+        events: &mut VecDeque<TransportEvent>,
+    ) -> std::result::Result<Connection, tungstenite::Error> {
+        let mut socket = match self {
+            Connection::Opening(pending, queued) => {
+                let stream = match pending.poll()? {
+                    Some(stream) => stream,
+                    None => return Ok(Connection::Opening(pending, queued)),
+                };
+                stream.set_nonblocking(true)?;
+                stream.set_nodelay(true)?;
+                let url = Url::parse(&format!("ws://{}/", addr))
+                    .expect("Unreachable: A socket address is a valid host");
+                let connection = match tungstenite::client(url, stream) {
+                    Ok((socket, _)) => Connection::Open(open(socket, queued, addr, events)?),
+                    Err(HandshakeError::Interrupted(handshake)) => {
+                        Connection::Connecting(handshake, queued)
+                    }
+                    Err(HandshakeError::Failure(err)) => return Err(err),
+                };
+                return connection.poll(addr, events);
+            }
+            Connection::Accepting(handshake) => match handshake.handshake() {
+                Ok(socket) => {
+                    events.push_back(TransportEvent::Connected(addr));
+                    socket
+                }
+                Err(HandshakeError::Interrupted(handshake)) => {
+                    return Ok(Connection::Accepting(handshake))
+                }
+                Err(HandshakeError::Failure(err)) => return Err(err),
+            },
+            Connection::Connecting(handshake, queued) => match handshake.handshake() {
+                Ok((socket, _)) => open(socket, queued, addr, events)?,
+                Err(HandshakeError::Interrupted(handshake)) => {
+                    return Ok(Connection::Connecting(handshake, queued))
+                }
+                Err(HandshakeError::Failure(err)) => return Err(err),
+            },
+            Connection::Open(socket) => socket,
+        };
+
+        if let Err(err) = socket.write_pending() {
+            if !would_block(&err) {
+                return Err(err);
+            }
+        }
+        loop {
+            match socket.read_message() {
+                Ok(Message::Binary(payload)) => {
+                    events.push_back(TransportEvent::Packet { addr, payload })
+                }
+                Ok(Message::Close(_)) => return Err(tungstenite::Error::AlreadyClosed),
+                // Pings are answered by `tungstenite`, and text isn't sent by this transport.
+                Ok(_) => {}
+                Err(ref err) if would_block(err) => break,
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(Connection::Open(socket))
+    }
+}
+
+// Reports the connection and sends the payloads queued while it was connecting.
+fn open(
+    mut socket: WebSocket<TcpStream>,
+    queued: Vec<Vec<u8>>,
+    addr: SocketAddr,
+    events: &mut VecDeque<TransportEvent>,
+) -> std::result::Result<WebSocket<TcpStream>, tungstenite::Error> {
+    events.push_back(TransportEvent::Connected(addr));
+    for payload in queued {
+        write(&mut socket, payload)?;
+    }
+    Ok(socket)
+}
+
+// Queues a payload, which is written when the socket takes it.
+fn write(
+    socket: &mut WebSocket<TcpStream>,
+    payload: Vec<u8>,
+) -> std::result::Result<(), tungstenite::Error> {
+    match socket.write_message(Message::Binary(payload)) {
+        Err(ref err) if would_block(err) => Ok(()),
+        result => result,
+    }
+}
+
+/// The WebSocket transport, sending each payload as a binary message.
+///
+/// It accepts WebSocket connections on the address it is bound to, so browsers can connect to
+/// it, and connects to the `ws://` URL of the endpoints it sends to. Connecting happens in the
+/// background, the payloads sent meanwhile are sent once the handshake completes.
+pub struct WebSocketTransport {
+    listener: TcpListener,
+    // `None` while a connection is being polled.
+    connections: HashMap<SocketAddr, Option<Connection>>,
+    events: VecDeque<TransportEvent>,
+}
+
+impl WebSocketTransport {
+    /// Listens on the given address.
+    pub fn bind(addr: SocketAddr) -> Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(WebSocketTransport {
+            listener,
+            connections: HashMap::new(),
+            events: VecDeque::new(),
+        })
+    }
+
+    /// The address the transport listens on.
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    fn accept(&mut self, stream: TcpStream, addr: SocketAddr) -> Result<()> {
+        stream.set_nonblocking(true)?;
+        stream.set_nodelay(true)?;
+        let connection = match tungstenite::accept(stream) {
+            Ok(socket) => {
+                self.events.push_back(TransportEvent::Connected(addr));
+                Connection::Open(socket)
+            }
+            Err(HandshakeError::Interrupted(handshake)) => Connection::Accepting(handshake),
+            Err(HandshakeError::Failure(err)) => return Err(err.into()),
+        };
+        self.connections.insert(addr, Some(connection));
+        Ok(())
+    }
+
+    fn poll(&mut self) {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, addr)) => {
+                    if let Err(err) = self.accept(stream, addr) {
+                        warn!("Failed to accept a connection from {}: {}", addr, err);
+                    }
+                }
+                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) => {
+                    warn!("Failed to accept a connection: {}", err);
+                    break;
+                }
+            }
+        }
+
+        let mut closed = Vec::new();
+        for (addr, slot) in &mut self.connections {
+            let connection = slot.take().expect("Unreachable: Connections are put back");
+            match connection.poll(*addr, &mut self.events) {
+                Ok(connection) => *slot = Some(connection),
+                Err(err) => {
+                    match err {
+                        tungstenite::Error::AlreadyClosed => {}
+                        err => warn!("Closing the connection to {}: {}", addr, err),
+                    }
+                    closed.push(*addr);
+                }
+            }
+        }
+        for addr in closed {
+            self.connections.remove(&addr);
+            self.events.push_back(TransportEvent::Disconnected(addr));
+        }
+    }
+}
+
+impl Transport for WebSocketTransport {
+    fn send(&mut self, addr: SocketAddr, payload: Vec<u8>, _delivery: Delivery) -> Result<()> {
+        if payload.len() > MAX_STREAM_PAYLOAD {
+            return Err(
+                io::Error::new(io::ErrorKind::InvalidInput, "The payload is too large").into(),
+            );
+        }
+        let result = match self.connections.entry(addr).or_insert_with(|| {
+            Some(Connection::Opening(
+                PendingStream::connect(addr),
+                Vec::new(),
+            ))
+        }) {
+            Some(Connection::Open(socket)) => write(socket, payload),
+            Some(Connection::Opening(_, queued)) | Some(Connection::Connecting(_, queued)) => {
+                queued.push(payload);
+                Ok(())
+            }
+            _ => Err(tungstenite::Error::AlreadyClosed),
+        };
+        result.map_err(|err| {
+            self.disconnect(addr);
+            Error::from(err)
+        })
+    }
+
+    fn recv(&mut self) -> Option<TransportEvent> {
+        if self.events.is_empty() {
+            self.poll();
+        }
+        self.events.pop_front()
+    }
+
+    fn disconnect(&mut self, addr: SocketAddr) {
+        if let Some(connection) = self.connections.remove(&addr) {
+            if let Some(Connection::Open(mut socket)) = connection {
+                // The socket is dropped right after, so whether the close frame was written
+                // doesn't matter.
+                let _ = socket.close(None);
+                let _ = socket.write_pending();
+            }
+            self.events.push_back(TransportEvent::Disconnected(addr));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{thread::sleep, time::Duration};
+
+    use super::*;
+
+    #[test]
+    fn send_over_websocket() {
+        let localhost = "127.0.0.1:0".parse().unwrap();
+        let mut server = WebSocketTransport::bind(localhost).unwrap();
+        let mut client = WebSocketTransport::bind(localhost).unwrap();
+        let server_addr = server.local_addr().unwrap();
+
+        client
+            .send(
+                server_addr,
+                b"hello".to_vec(),
+                Delivery::ReliableOrdered(None),
+            )
+            .unwrap();
+        let mut received = None;
+        for _ in 0..100 {
+            // The client finishes the handshake while polling.
+            while client.recv().is_some() {}
+            while let Some(event) = server.recv() {
+                if let TransportEvent::Packet { payload, .. } = event {
+                    received = Some(payload);
+                }
+            }
+            if received.is_some() {
+                break;
+            }
+            sleep(Duration::from_millis(10));
+        }
+        assert_eq!(received, Some(b"hello".to_vec()));
+    }
+}
@@ -0,0 +1,699 @@
+//! Voice chat between the players, available with the `voice` feature.
+//!
+//! The clients capture their `Microphone`, encode it with Opus in frames of 20 ms, and send the
+//! frames to the server on the `VOICE_CHANNEL`. The server forwards them to the other clients
+//! as the voice of the `ControlledEntity` of the connection, and the clients play each voice
+//! from an `AudioEmitter` on the replicated entity of the speaker, on the voice bus of the
+//! `Mixer`, so it is heard from where the player is.
+
+use std::{
+    collections::{HashMap, HashSet},
+    marker::PhantomData,
+    time::Duration,
+};
+
+use log::{error, warn};
+use opus::{Application, Bitrate, Channels};
+use serde::{Deserialize, Serialize};
+use shrev::ReaderId;
+
+use amethyst_audio::{input::Microphone, AudioEmitter, LiveStream, Mixer};
+use amethyst_core::{
+    bundle::{BundleBuilder, BundleRequirement, SystemBundle},
+    ecs::{
+        Component, DenseVecStorage, Entities, Entity, Join, Read, ReadStorage, Resources, System,
+        SystemData, WriteStorage,
+    },
+};
+use amethyst_error::Error;
+
+use crate::{
+    channel::VOICE_CHANNEL, prediction::ControlledEntity, replication::ReplicationClient,
+    NetConnection, NetEvent, NetPacket, NetworkId,
+};
+
+/// The sample rates Opus encodes, one of which the `Microphone` must capture at.
+pub const VOICE_SAMPLE_RATES: [u32; 5] = [8_000, 12_000, 16_000, 24_000, 48_000];
+
+// How many frames a second, of 20 ms.
+const FRAMES_PER_SECOND: u32 = 50;
+// The largest encoded frame.
+const MAX_FRAME_BYTES: usize = 1276;
+// How many lost frames are concealed by the decoder, beyond which the gap is just skipped.
+const MAX_CONCEALED: u32 = 3;
+
+/// The messages of the voice chat.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum VoiceMessage {
+    /// A frame of the microphone of a client, sent to the server.
+    Speak {
+        /// The number of the frame.
+        sequence: u32,
+        /// The Opus frame.
+        frame: Vec<u8>,
+    },
+    /// A frame forwarded by the server.
+    Voice {
+        /// The entity of the player speaking.
+        speaker: NetworkId,
+        /// The number of the frame.
+        sequence: u32,
+        /// The Opus frame.
+        frame: Vec<u8>,
+    },
+}
+
+/// A network event type the voice messages can be sent as.
+pub trait VoiceEvent: Send + Sync + 'static {
+    /// Wraps a message.
+    fn from_message(message: VoiceMessage) -> Self;
+
+    /// Returns the message of the event, if it is one.
+    fn as_message(&self) -> Option<&VoiceMessage>;
+}
+
+impl VoiceEvent for VoiceMessage {
+    fn from_message(message: VoiceMessage) -> Self {
+        message
+    }
+
+    fn as_message(&self) -> Option<&VoiceMessage> {
+        Some(self)
+    }
+}
+
+/// The voice chat state of a connection, added by the voice systems.
+pub struct VoicePeer<E: 'static> {
+    reader: ReaderId<NetEvent<E>>,
+}
+
+impl<E: Send + Sync + 'static> Component for VoicePeer<E> {
+    type Storage = DenseVecStorage<Self>;
+}
+
+fn add_peers<E: Send + Sync + 'static>(
+    entities: &Entities<'_>,
+    connections: &mut WriteStorage<'_, NetConnection<E>>,
+    peers: &mut WriteStorage<'_, VoicePeer<E>>,
+) {
+    let new = (&**entities, connections, !&*peers)
+        .join()
+        .map(|(entity, connection, _)| (entity, connection.receive_buffer.register_reader()))
+        .collect::<Vec<_>>();
+    for (entity, reader) in new {
+        peers
+            .insert(entity, VoicePeer { reader })
+            .expect("Unreachable: The entity is alive");
+    }
+}
+
+fn send<E: VoiceEvent>(connection: &mut NetConnection<E>, message: VoiceMessage) {
+    connection
+        .send_buffer
+        .single_write(NetEvent::Packet(NetPacket::on_channel(
+            E::from_message(message),
+            VOICE_CHANNEL,
+        )));
+}
+
+/// How the voice chat sounds and when it transmits.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VoiceConfig {
+    /// The bitrate of the encoded voice, in bits per second.
+    pub bitrate: i32,
+    /// How much of the voices is buffered before playing, to absorb the jitter of the network.
+    pub latency: Duration,
+    /// The loudness under which the frames of the microphone aren't sent, as the root mean
+    /// square of their samples, `None` to always transmit.
+    pub activation: Option<f32>,
+}
+
+impl Default for VoiceConfig {
+    fn default() -> Self {
+        VoiceConfig {
+            bitrate: 24_000,
+            latency: Duration::from_millis(60),
+            activation: Some(0.01),
+        }
+    }
+}
+
+/// Controls the voice chat of a client.
+#[derive(Clone, Debug, Default)]
+pub struct VoiceChat {
+    /// Stops sending the microphone, for push to talk or to mute the player.
+    pub muted: bool,
+    /// The speakers not played.
+    pub ignored: HashSet<NetworkId>,
+}
+
+/// Encodes the `Microphone` and sends it to the server.
+pub struct VoiceCaptureSystem<E> {
+    config: VoiceConfig,
+    encoder: Option<(u32, opus::Encoder)>,
+    // The sample rate the encoder couldn't be created for, not retried every frame.
+    failed: Option<u32>,
+    samples: Vec<f32>,
+    sequence: u32,
+    _marker: PhantomData<E>,
+}
+
+impl<E> VoiceCaptureSystem<E> {
+    /// Sends the voice with the given configuration.
+    pub fn new(config: VoiceConfig) -> Self {
+        VoiceCaptureSystem {
+            config,
+            encoder: None,
+            failed: None,
+            samples: Vec::new(),
+            sequence: 0,
+            _marker: PhantomData,
+        }
+    }
+
+    // The encoder for the sample rate of the microphone, created when it changes.
+    fn encoder(&mut self, sample_rate: u32) -> Option<&mut opus::Encoder> {
+        if self.encoder.as_ref().map(|(rate, _)| *rate) != Some(sample_rate) {
+            if self.failed == Some(sample_rate) {
+                return None;
+            }
+            self.failed = Some(sample_rate);
+            if !VOICE_SAMPLE_RATES.contains(&sample_rate) {
+                error!(
+                    "The voice chat can't encode the {} Hz of the microphone, only {:?} Hz",
+                    sample_rate, VOICE_SAMPLE_RATES
+                );
+                return None;
+            }
+            let encoder = opus::Encoder::new(sample_rate, Channels::Mono, Application::Voip)
+                .and_then(|mut encoder| {
+                    encoder.set_bitrate(Bitrate::Bits(self.config.bitrate))?;
+                    Ok(encoder)
+                });
+            match encoder {
+                Ok(encoder) => {
+                    self.encoder = Some((sample_rate, encoder));
+                    self.failed = None;
+                }
+                Err(e) => {
+                    error!("Failed to create the voice encoder: {}", e);
+                    return None;
+                }
+            }
+        }
+        self.encoder.as_mut().map(|(_, encoder)| encoder)
+    }
+
+    // Encodes the whole frames of the samples read, keeping the rest for the next frame. The
+    // quiet frames are skipped.
+    fn encode(&mut self, sample_rate: u32) -> Vec<Vec<u8>> {
+        let frame_len = (sample_rate / FRAMES_PER_SECOND) as usize;
+        let activation = self.config.activation;
+        let samples = std::mem::replace(&mut self.samples, Vec::new());
+        let encoder = match self.encoder(sample_rate) {
+            Some(encoder) => encoder,
+            None => return Vec::new(),
+        };
+        let mut frames = Vec::new();
+        let mut chunks = samples.chunks_exact(frame_len);
+        for chunk in &mut chunks {
+            let squares = chunk.iter().map(|sample| sample * sample).sum::<f32>();
+            if activation.map_or(false, |level| (squares / frame_len as f32).sqrt() < level) {
+                continue;
+            }
+            let mut frame = vec![0; MAX_FRAME_BYTES];
+            match encoder.encode_float(chunk, &mut frame) {
+                Ok(len) => {
+                    frame.truncate(len);
+                    frames.push(frame);
+                }
+                Err(e) => error!("Failed to encode the voice: {}", e),
+            }
+        }
+        self.samples = chunks.remainder().to_vec();
+        frames
+    }
+}
+
+impl<'a, E: VoiceEvent> System<'a> for VoiceCaptureSystem<E> {
+    type SystemData = (
+        Option<Read<'a, Microphone>>,
+        Read<'a, VoiceChat>,
+        WriteStorage<'a, NetConnection<E>>,
+    );
+
+    fn run(&mut self, (microphone, chat, mut connections): Self::SystemData) {
+        let microphone = match microphone {
+            Some(microphone) => microphone,
+            None => return,
+        };
+        microphone.read(&mut self.samples);
+        if chat.muted {
+            self.samples.clear();
+            return;
+        }
+
+        for frame in self.encode(microphone.sample_rate()) {
+            for connection in (&mut connections).join() {
+                send(
+                    connection,
+                    VoiceMessage::Speak {
+                        sequence: self.sequence,
+                        frame: frame.clone(),
+                    },
+                );
+            }
+            self.sequence = self.sequence.wrapping_add(1);
+        }
+    }
+
+    fn setup(&mut self, res: &mut Resources) {
+        Self::SystemData::setup(res);
+    }
+}
+
+/// Forwards the voice of each connection on the server to the other ones, as the voice of its
+/// `ControlledEntity`.
+pub struct VoiceRelaySystem<E> {
+    _marker: PhantomData<E>,
+}
+
+impl<E> Default for VoiceRelaySystem<E> {
+    fn default() -> Self {
+        VoiceRelaySystem {
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, E: VoiceEvent> System<'a> for VoiceRelaySystem<E> {
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, NetConnection<E>>,
+        WriteStorage<'a, VoicePeer<E>>,
+        ReadStorage<'a, ControlledEntity>,
+        ReadStorage<'a, NetworkId>,
+    );
+
+    fn run(&mut self, (entities, mut connections, mut peers, controlled, ids): Self::SystemData) {
+        add_peers(&entities, &mut connections, &mut peers);
+
+        let mut voices = Vec::new();
+        for (entity, connection, peer, controlled) in
+            (&*entities, &connections, &mut peers, controlled.maybe()).join()
+        {
+            // The connections without a player are read too, so their events don't pile up.
+            let speaker = controlled.and_then(|controlled| ids.get(controlled.0).cloned());
+            for event in connection.receive_buffer.read(&mut peer.reader) {
+                if let (Some(speaker), NetEvent::Packet(packet)) = (speaker, event) {
+                    if let Some(VoiceMessage::Speak { sequence, frame }) =
+                        packet.content().as_message()
+                    {
+                        voices.push((entity, speaker, *sequence, frame.clone()));
+                    }
+                }
+            }
+        }
+
+        for (from, speaker, sequence, frame) in voices {
+            for (entity, connection) in (&*entities, &mut connections).join() {
+                if entity != from {
+                    send(
+                        connection,
+                        VoiceMessage::Voice {
+                            speaker,
+                            sequence,
+                            frame: frame.clone(),
+                        },
+                    );
+                }
+            }
+        }
+    }
+}
+
+// A player heard by a client.
+struct Speaker {
+    decoder: opus::Decoder,
+    stream: LiveStream,
+    entity: Option<Entity>,
+    last: u32,
+}
+
+/// Decodes the voices forwarded by the server and plays them from the replicated entities of the
+/// speakers.
+pub struct VoicePlaybackSystem<E> {
+    config: VoiceConfig,
+    sample_rate: u32,
+    speakers: HashMap<NetworkId, Speaker>,
+    _marker: PhantomData<E>,
+}
+
+impl<E> VoicePlaybackSystem<E> {
+    /// Plays the voices with the given configuration, decoded at `sample_rate`, which must be one
+    /// of `VOICE_SAMPLE_RATES`.
+    pub fn new(config: VoiceConfig, sample_rate: u32) -> Self {
+        VoicePlaybackSystem {
+            config,
+            sample_rate,
+            speakers: HashMap::new(),
+            _marker: PhantomData,
+        }
+    }
+
+    fn decode(&mut self, speaker: NetworkId, sequence: u32, frame: &[u8]) {
+        let sample_rate = self.sample_rate;
+        if !self.speakers.contains_key(&speaker) {
+            match opus::Decoder::new(sample_rate, Channels::Mono) {
+                Ok(decoder) => {
+                    self.speakers.insert(
+                        speaker,
+                        Speaker {
+                            decoder,
+                            stream: LiveStream::new(sample_rate, self.config.latency),
+                            entity: None,
+                            last: sequence.wrapping_sub(1),
+                        },
+                    );
+                }
+                Err(e) => {
+                    error!("Failed to create a voice decoder: {}", e);
+                    return;
+                }
+            }
+        }
+        let speaker = self
+            .speakers
+            .get_mut(&speaker)
+            .expect("Unreachable: The speaker was just added");
+        let gap = sequence.wrapping_sub(speaker.last);
+        if gap == 0 || gap > u32::max_value() / 2 {
+            // A late frame, already concealed.
+            return;
+        }
+        speaker.last = sequence;
+
+        let mut samples = vec![0.0; (sample_rate / FRAMES_PER_SECOND) as usize * 6];
+        if gap > 1 && gap - 1 <= MAX_CONCEALED {
+            for _ in 1..gap {
+                match speaker.decoder.decode_float(&[], &mut samples, false) {
+                    Ok(len) => speaker.stream.push(&samples[..len]),
+                    Err(e) => warn!("Failed to conceal a lost voice frame: {}", e),
+                }
+            }
+        }
+        match speaker.decoder.decode_float(frame, &mut samples, false) {
+            Ok(len) => speaker.stream.push(&samples[..len]),
+            Err(e) => warn!("Failed to decode a voice frame: {}", e),
+        }
+    }
+}
+
+impl<'a, E: VoiceEvent> System<'a> for VoicePlaybackSystem<E> {
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, NetConnection<E>>,
+        WriteStorage<'a, VoicePeer<E>>,
+        Read<'a, ReplicationClient>,
+        Read<'a, VoiceChat>,
+        Read<'a, Mixer>,
+        WriteStorage<'a, AudioEmitter>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, mut connections, mut peers, client, chat, mixer, mut emitters): Self::SystemData,
+    ) {
+        add_peers(&entities, &mut connections, &mut peers);
+
+        let mut frames = Vec::new();
+        for (connection, peer) in (&connections, &mut peers).join() {
+            for event in connection.receive_buffer.read(&mut peer.reader) {
+                if let NetEvent::Packet(packet) = event {
+                    if let Some(VoiceMessage::Voice {
+                        speaker,
+                        sequence,
+                        frame,
+                    }) = packet.content().as_message()
+                    {
+                        if !chat.ignored.contains(speaker) {
+                            frames.push((*speaker, *sequence, frame.clone()));
+                        }
+                    }
+                }
+            }
+        }
+        for (speaker, sequence, frame) in frames {
+            self.decode(speaker, sequence, &frame);
+        }
+
+        // The voices follow the entities of the speakers, and stop with them.
+        let latency = self.config.latency;
+        self.speakers.retain(|id, speaker| {
+            let entity = client.entity(*id);
+            if entity.is_none() || chat.ignored.contains(id) {
+                speaker.stream.close();
+                return false;
+            }
+            if speaker.entity != entity {
+                let entity = entity.expect("Unreachable: The entity was checked");
+                if !emitters.contains(entity) {
+                    if let Err(e) = emitters.insert(entity, AudioEmitter::new()) {
+                        error!("Failed to add an emitter to a speaker: {}", e);
+                        return true;
+                    }
+                }
+                if let Some(emitter) = emitters.get_mut(entity) {
+                    if speaker.entity.is_some() {
+                        // The entity was replaced, the voice moves to the new one.
+                        speaker.stream.close();
+                        speaker.stream = LiveStream::new(speaker.stream.sample_rate(), latency);
+                    }
+                    emitter.play_live(&speaker.stream, mixer.voice());
+                }
+                speaker.entity = Some(entity);
+            }
+            true
+        });
+    }
+
+    fn setup(&mut self, res: &mut Resources) {
+        Self::SystemData::setup(res);
+    }
+}
+
+/// Adds the voice chat, to the clients or to the server.
+///
+/// The clients capture the `Microphone` resource, which the game opens at one of the
+/// `VOICE_SAMPLE_RATES`, and need the `ReplicationClientBundle` to find the entities of the
+/// speakers. The server needs the `ControlledEntity` of the connections to name the speakers.
+pub struct VoiceBundle<E> {
+    // The configuration and the sample rate of the playback, on the clients.
+    client: Option<(VoiceConfig, u32)>,
+    _marker: PhantomData<E>,
+}
+
+impl<E> VoiceBundle<E> {
+    /// Adds the systems sending the microphone and playing the voices of the other players.
+    pub fn client(config: VoiceConfig) -> Self {
+        VoiceBundle {
+            client: Some((config, 48_000)),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Adds the system forwarding the voices to the other clients.
+    pub fn server() -> Self {
+        VoiceBundle {
+            client: None,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Decodes the voices at another of the `VOICE_SAMPLE_RATES` than 48 kHz, on the clients.
+    pub fn with_sample_rate(mut self, sample_rate: u32) -> Self {
+        if let Some((_, ref mut rate)) = self.client {
+            *rate = sample_rate;
+        }
+        self
+    }
+}
+
+impl<'a, 'b, E: VoiceEvent> SystemBundle<'a, 'b> for VoiceBundle<E> {
+    fn build(self, builder: &mut BundleBuilder<'a, 'b>) -> Result<(), Error> {
+        match self.client {
+            Some((config, sample_rate)) => {
+                if !VOICE_SAMPLE_RATES.contains(&sample_rate) {
+                    return Err(Error::from_string(format!(
+                        "The voices can't be decoded at {} Hz",
+                        sample_rate
+                    )));
+                }
+                builder.add(
+                    VoiceCaptureSystem::<E>::new(config.clone()),
+                    "voice_capture",
+                    &[],
+                );
+                builder.add(
+                    VoicePlaybackSystem::<E>::new(config, sample_rate),
+                    "voice_playback",
+                    &["net_socket"],
+                );
+            }
+            None => builder.add(
+                VoiceRelaySystem::<E>::default(),
+                "voice_relay",
+                &["net_socket"],
+            ),
+        }
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        "VoiceBundle"
+    }
+
+    fn requirements(&self) -> Vec<BundleRequirement> {
+        vec![BundleRequirement::new("net_socket", "NetworkBundle")]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeMap, sync::Arc};
+
+    use amethyst_core::ecs::{Builder, RunNow, World, Write};
+
+    use super::*;
+
+    #[test]
+    fn voices_are_forwarded_to_the_other_clients() {
+        let mut world = World::new();
+        let mut relay = VoiceRelaySystem::<VoiceMessage>::default();
+        System::setup(&mut relay, &mut world.res);
+
+        let player = world.create_entity().with(NetworkId(7)).build();
+        let speaking = world
+            .create_entity()
+            .with(NetConnection::<VoiceMessage>::new(
+                "127.0.0.1:1".parse().unwrap(),
+            ))
+            .with(ControlledEntity(player))
+            .build();
+        let listening = world
+            .create_entity()
+            .with(NetConnection::<VoiceMessage>::new(
+                "127.0.0.1:2".parse().unwrap(),
+            ))
+            .build();
+        relay.run_now(&world.res);
+
+        world
+            .write_storage::<NetConnection<VoiceMessage>>()
+            .get_mut(speaking)
+            .unwrap()
+            .receive_buffer
+            .single_write(NetEvent::Packet(NetPacket::unreliable(
+                VoiceMessage::Speak {
+                    sequence: 3,
+                    frame: vec![1, 2],
+                },
+            )));
+        relay.run_now(&world.res);
+
+        let mut connections = world.write_storage::<NetConnection<VoiceMessage>>();
+        let mut sent = |entity| {
+            connections
+                .get_mut(entity)
+                .unwrap()
+                .send_buffer_early_read()
+                .filter_map(|event| match event {
+                    NetEvent::Packet(packet) => packet.content().as_message().cloned(),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            sent(listening),
+            vec![VoiceMessage::Voice {
+                speaker: NetworkId(7),
+                sequence: 3,
+                frame: vec![1, 2],
+            }]
+        );
+        assert!(sent(speaking).is_empty());
+    }
+
+    #[test]
+    fn voices_are_encoded_and_played_from_the_speakers() {
+        let config = VoiceConfig {
+            activation: None,
+            ..Default::default()
+        };
+        let mut capture = VoiceCaptureSystem::<VoiceMessage>::new(config.clone());
+        // Two frames and a half at 16 kHz.
+        capture.samples = (0..800)
+            .map(|i| (i as f32 * 0.05).sin() * 0.5)
+            .collect::<Vec<_>>();
+        let frames = capture.encode(16_000);
+        assert_eq!(frames.len(), 2);
+        assert_eq!(capture.samples.len(), 160);
+        capture.samples = vec![0.0; 882];
+        assert!(capture.encode(44_100).is_empty());
+        assert_eq!(capture.failed, Some(44_100));
+
+        let mut world = World::new();
+        let mut playback = VoicePlaybackSystem::<VoiceMessage>::new(config, 16_000);
+        System::setup(&mut playback, &mut world.res);
+        let connection = world
+            .create_entity()
+            .with(NetConnection::<VoiceMessage>::new(
+                "127.0.0.1:1".parse().unwrap(),
+            ))
+            .build();
+        playback.run_now(&world.res);
+        world.exec(
+            |(entities, mut ids, mut client): (
+                Entities<'_>,
+                WriteStorage<'_, NetworkId>,
+                Write<'_, ReplicationClient>,
+            )| {
+                let mut state = BTreeMap::new();
+                state.insert(NetworkId(7), BTreeMap::new());
+                client.receive(1, Arc::new(state), &entities, &mut ids);
+            },
+        );
+        world.maintain();
+        let speaker = world
+            .read_resource::<ReplicationClient>()
+            .entity(NetworkId(7))
+            .unwrap();
+
+        // The frame lost between the second and the last one is concealed.
+        let voices = vec![(0, &frames[0]), (1, &frames[1]), (3, &frames[0])];
+        for (sequence, frame) in voices {
+            world
+                .write_storage::<NetConnection<VoiceMessage>>()
+                .get_mut(connection)
+                .unwrap()
+                .receive_buffer
+                .single_write(NetEvent::Packet(NetPacket::unreliable(
+                    VoiceMessage::Voice {
+                        speaker: NetworkId(7),
+                        sequence,
+                        frame: frame.clone(),
+                    },
+                )));
+        }
+        playback.run_now(&world.res);
+        assert!(world.read_storage::<AudioEmitter>().contains(speaker));
+        assert_eq!(playback.speakers[&NetworkId(7)].stream.buffered(), 4 * 320);
+
+        world
+            .write_resource::<VoiceChat>()
+            .ignored
+            .insert(NetworkId(7));
+        playback.run_now(&world.res);
+        assert!(playback.speakers.is_empty());
+    }
+}
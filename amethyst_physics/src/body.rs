@@ -0,0 +1,118 @@
+//! The rigid bodies simulated by the physics world.
+
+use serde::{Deserialize, Serialize};
+
+use amethyst_assets::PrefabData;
+use amethyst_core::{
+    ecs::{Component, DenseVecStorage, Entity, WriteStorage},
+    math::Vector3,
+};
+use amethyst_derive::PrefabData;
+use amethyst_error::Error;
+
+/// How a rigid body is moved.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BodyStatus {
+    /// Moved by the forces and the contacts, the `Transform` following the simulation.
+    Dynamic,
+    /// Moved by the game through its `Transform` and velocity, pushing the dynamic bodies without
+    /// being pushed back.
+    Kinematic,
+    /// Never moves.
+    Static,
+}
+
+impl Default for BodyStatus {
+    fn default() -> Self {
+        BodyStatus::Dynamic
+    }
+}
+
+impl BodyStatus {
+    pub(crate) fn to_nphysics(self) -> nphysics3d::object::BodyStatus {
+        match self {
+            BodyStatus::Dynamic => nphysics3d::object::BodyStatus::Dynamic,
+            BodyStatus::Kinematic => nphysics3d::object::BodyStatus::Kinematic,
+            BodyStatus::Static => nphysics3d::object::BodyStatus::Static,
+        }
+    }
+}
+
+/// A rigid body, simulated at the position of the `Transform` of the entity.
+///
+/// The body gets its mass from the density of the `Collider` of the entity, plus its own `mass`.
+/// The velocities are kept up to date by the `PhysicsStepSystem`, and changing them changes the
+/// velocities of the simulated body. The entities with a body shouldn't have a `Parent`, their
+/// `Transform` being the position of the body in the world.
+///
+/// ```rust,ignore
+/// world
+///     .create_entity()
+///     .with(Transform::default())
+///     .with(RigidBody::dynamic())
+///     .with(Collider::new(ColliderShape::Ball { radius: 0.5 }))
+///     .build();
+/// ```
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, PrefabData)]
+#[prefab(Component)]
+#[serde(default)]
+pub struct RigidBody {
+    /// How the body is moved.
+    pub status: BodyStatus,
+    /// The mass of the body, added to the mass of its collider.
+    pub mass: f32,
+    /// The linear velocity, in units per second.
+    pub linear_velocity: Vector3<f32>,
+    /// The angular velocity, as an axis scaled by the radians per second.
+    pub angular_velocity: Vector3<f32>,
+}
+
+impl Default for RigidBody {
+    fn default() -> Self {
+        RigidBody {
+            status: BodyStatus::Dynamic,
+            mass: 0.0,
+            linear_velocity: Vector3::zeros(),
+            angular_velocity: Vector3::zeros(),
+        }
+    }
+}
+
+impl RigidBody {
+    /// A dynamic body at rest.
+    pub fn dynamic() -> Self {
+        RigidBody::default()
+    }
+
+    /// A kinematic body at rest.
+    pub fn kinematic() -> Self {
+        RigidBody {
+            status: BodyStatus::Kinematic,
+            ..Default::default()
+        }
+    }
+
+    /// A static body.
+    pub fn fixed() -> Self {
+        RigidBody {
+            status: BodyStatus::Static,
+            ..Default::default()
+        }
+    }
+
+    /// Adds a mass to the one of the collider.
+    pub fn with_mass(mut self, mass: f32) -> Self {
+        self.mass = mass;
+        self
+    }
+
+    /// Sets the initial linear velocity.
+    pub fn with_linear_velocity(mut self, velocity: Vector3<f32>) -> Self {
+        self.linear_velocity = velocity;
+        self
+    }
+}
+
+impl Component for RigidBody {
+    type Storage = DenseVecStorage<Self>;
+}
@@ -0,0 +1,97 @@
+//! The bundle adding the physics to the fixed time step.
+
+use amethyst_core::{
+    bundle::{BundleBuilder, SystemBundle},
+    math::Vector3,
+};
+use amethyst_error::Error;
+
+#[cfg(feature = "renderer")]
+use crate::sprite::SpriteColliderSystem;
+use crate::{character::CharacterControllerSystem, systems::PhysicsStepSystem};
+
+/// Adds the `PhysicsStepSystem`, which should be added to the fixed time step with
+/// `GameDataBuilder::with_fixed_bundle` so the simulation is stable:
+///
+/// ~~~ignore
+/// let game_data = GameDataBuilder::default()
+///     .with_bundle(TransformBundle::new())?
+///     .with_fixed_bundle(PhysicsBundle::new())?;
+/// ~~~
+///
+/// The `PhysicsWorld` is created if it wasn't added before, the gravity of the bundle replacing
+/// its own if it was set. The bundle also adds the `CharacterControllerSystem`, moving the
+/// `CharacterController`s before the step, and with the `renderer` feature the
+/// `SpriteColliderSystem`.
+#[derive(Debug)]
+pub struct PhysicsBundle {
+    gravity: Option<Vector3<f32>>,
+    units_per_meter: f32,
+    planar: bool,
+}
+
+impl Default for PhysicsBundle {
+    fn default() -> Self {
+        PhysicsBundle {
+            gravity: None,
+            units_per_meter: 1.0,
+            planar: false,
+        }
+    }
+}
+
+impl PhysicsBundle {
+    /// Creates the bundle, with the gravity pulling 9.81 downwards along Y.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Sets how many units of the transforms are in a meter of the simulation, 1 by default.
+    ///
+    /// The simulation is stable for objects from a few centimeters to a few dozen meters, so
+    /// games whose transforms are in pixels should set the pixels in a meter of their world.
+    pub fn with_units_per_meter(mut self, units_per_meter: f32) -> Self {
+        self.units_per_meter = units_per_meter;
+        self
+    }
+
+    /// Restricts the simulation to the XY plane: the bodies only move along X and Y and only
+    /// rotate around Z, and the Z of the transforms is kept for the order of the sprites.
+    ///
+    /// The colliders are simulated as solids, a `ColliderShape::Cuboid` being a rectangle and a
+    /// `ColliderShape::Ball` a circle. The `SpriteCollider` gives the entities colliders of their
+    /// sprites.
+    pub fn in_2d(mut self) -> Self {
+        self.planar = true;
+        self
+    }
+
+    /// Sets the gravity of the `PhysicsWorld`.
+    pub fn with_gravity(mut self, gravity: Vector3<f32>) -> Self {
+        self.gravity = Some(gravity);
+        self
+    }
+}
+
+impl<'a, 'b> SystemBundle<'a, 'b> for PhysicsBundle {
+    fn build(self, builder: &mut BundleBuilder<'a, 'b>) -> Result<(), Error> {
+        let mut step =
+            PhysicsStepSystem::new(self.gravity).with_units_per_meter(self.units_per_meter);
+        if self.planar {
+            step = step.in_2d();
+        }
+        builder.add(CharacterControllerSystem, "character_controller", &[]);
+        let mut dependencies = vec!["character_controller"];
+        #[cfg(feature = "renderer")]
+        {
+            builder.add(SpriteColliderSystem, "sprite_collider", &[]);
+            dependencies.push("sprite_collider");
+        }
+        builder.add(step, "physics_step", &dependencies);
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        "PhysicsBundle"
+    }
+}
@@ -0,0 +1,455 @@
+//! The kinematic character controller, moving capsules that slide along the colliders.
+
+use serde::{Deserialize, Serialize};
+
+use amethyst_assets::PrefabData;
+use amethyst_core::{
+    ecs::{Component, DenseVecStorage, Entities, Entity, Join, Read, System, WriteStorage},
+    math::{Isometry3, Vector3},
+    Time, Transform,
+};
+use amethyst_derive::PrefabData;
+use amethyst_error::Error;
+
+use crate::{
+    collider::ColliderShape,
+    query::{PhysicsQuery, QueryFilter, QueryHit},
+};
+
+// The gap kept between the capsule and the colliders, so it doesn't start its moves overlapping
+// them.
+const SKIN_WIDTH: f32 = 0.01;
+// How many times a move can slide along the colliders in a step.
+const MAX_SLIDES: usize = 4;
+const EPSILON: f32 = 1.0e-5;
+
+/// Moves an entity as an upright capsule at its `velocity`, sliding along the colliders instead
+/// of going through them, walking up the slopes and the steps that aren't too high and sticking
+/// to the ground when walking down.
+///
+/// The game sets the velocity, gravity included, and the `CharacterControllerSystem` moves the
+/// `Transform` on the fixed time step. The entity can also have a kinematic `RigidBody` and a
+/// capsule `Collider` of the same size, for the other bodies to collide with it, which the
+/// controller ignores.
+///
+/// ~~~ignore
+/// let jumping = controller.is_grounded() && input.action_is_down("jump").unwrap_or(false);
+/// controller.velocity.x = input.axis_value("move").unwrap_or(0.0) as f32 * 5.0;
+/// controller.velocity.y = if jumping { 5.0 } else { controller.velocity.y - 9.81 * dt };
+/// ~~~
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, PrefabData)]
+#[prefab(Component)]
+#[serde(default)]
+pub struct CharacterController {
+    /// The radius of the capsule.
+    pub radius: f32,
+    /// Half the height of the cylinder of the capsule, without the ends.
+    pub half_height: f32,
+    /// The highest step that can be walked up.
+    pub step_height: f32,
+    /// The steepest slope that can be walked up, in radians.
+    pub max_slope: f32,
+    /// How far below the character the ground is looked for when walking down, to stick to it.
+    pub snap_distance: f32,
+    /// The velocity the character moves at, in units per second. Its vertical part is reset when
+    /// the character lands or hits a ceiling.
+    #[serde(skip)]
+    pub velocity: Vector3<f32>,
+    #[serde(skip)]
+    ground: Option<(Entity, Vector3<f32>)>,
+}
+
+impl Default for CharacterController {
+    fn default() -> Self {
+        CharacterController::new(0.5, 0.5)
+    }
+}
+
+impl CharacterController {
+    /// A controller of the given capsule, walking up steps of 0.3 and slopes of 45 degrees.
+    pub fn new(radius: f32, half_height: f32) -> Self {
+        CharacterController {
+            radius,
+            half_height,
+            step_height: 0.3,
+            max_slope: std::f32::consts::FRAC_PI_4,
+            snap_distance: 0.2,
+            velocity: Vector3::zeros(),
+            ground: None,
+        }
+    }
+
+    /// Whether the character stood on the ground after its last move.
+    pub fn is_grounded(&self) -> bool {
+        self.ground.is_some()
+    }
+
+    /// The entity the character stood on after its last move.
+    pub fn ground(&self) -> Option<Entity> {
+        self.ground.map(|(entity, _)| entity)
+    }
+
+    /// The normal of the ground the character stood on after its last move.
+    pub fn ground_normal(&self) -> Option<Vector3<f32>> {
+        self.ground.map(|(_, normal)| normal)
+    }
+}
+
+impl Component for CharacterController {
+    type Storage = DenseVecStorage<Self>;
+}
+
+// Moves a capsule through the colliders.
+struct Mover<'q, 'a> {
+    query: &'q PhysicsQuery<'a>,
+    shape: ColliderShape,
+    filter: QueryFilter,
+    // The lowest vertical part of the normal of a walkable slope.
+    min_ground_y: f32,
+}
+
+impl<'q, 'a> Mover<'q, 'a> {
+    fn cast(&self, position: &Isometry3<f32>, motion: Vector3<f32>) -> Option<QueryHit> {
+        let distance = motion.norm();
+        if distance < EPSILON {
+            return None;
+        }
+        self.query.shape_cast(
+            &self.shape,
+            position,
+            motion,
+            distance + SKIN_WIDTH,
+            &self.filter,
+        )
+    }
+
+    // Moves as far as the colliders allow, sliding along them. The horizontal moves only slide
+    // horizontally along the slopes too steep to walk up. Returns the normals of the colliders
+    // hit.
+    fn slide(
+        &self,
+        position: &mut Isometry3<f32>,
+        mut motion: Vector3<f32>,
+        horizontal: bool,
+    ) -> Vec<Vector3<f32>> {
+        let mut normals = Vec::new();
+        for _ in 0..MAX_SLIDES {
+            let length = motion.norm();
+            if length < EPSILON {
+                break;
+            }
+            let direction = motion / length;
+            let hit = match self.cast(position, motion) {
+                Some(hit) => hit,
+                None => {
+                    position.translation.vector += motion;
+                    break;
+                }
+            };
+            let travel = (hit.distance - SKIN_WIDTH).max(0.0).min(length);
+            position.translation.vector += direction * travel;
+            normals.push(hit.normal);
+
+            let mut normal = hit.normal;
+            if horizontal && normal.y < self.min_ground_y {
+                normal.y = 0.0;
+                normal = normal.try_normalize(EPSILON).unwrap_or(-direction);
+            }
+            motion = direction * (length - travel);
+            motion -= normal * motion.dot(&normal).min(0.0);
+        }
+        normals
+    }
+
+    // The walkable ground within `distance` below the capsule.
+    fn ground(&self, position: &Isometry3<f32>, distance: f32) -> Option<QueryHit> {
+        self.cast(position, Vector3::new(0.0, -distance, 0.0))
+            .filter(|hit| hit.normal.y >= self.min_ground_y)
+    }
+}
+
+fn horizontal_distance(from: &Isometry3<f32>, to: &Isometry3<f32>) -> f32 {
+    let mut offset = to.translation.vector - from.translation.vector;
+    offset.y = 0.0;
+    offset.norm()
+}
+
+/// Moves the `CharacterController`s, see its documentation. Added by the `PhysicsBundle`, it
+/// runs on the fixed time step before the `PhysicsStepSystem`.
+#[derive(Debug, Default)]
+pub struct CharacterControllerSystem;
+
+impl<'a> System<'a> for CharacterControllerSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, Time>,
+        PhysicsQuery<'a>,
+        WriteStorage<'a, CharacterController>,
+        WriteStorage<'a, Transform>,
+    );
+
+    fn run(&mut self, (entities, time, query, mut controllers, mut transforms): Self::SystemData) {
+        let timestep = time.fixed_seconds();
+        for (entity, controller, transform) in
+            (&*entities, &mut controllers, &mut transforms).join()
+        {
+            let mover = Mover {
+                query: &query,
+                shape: ColliderShape::Capsule {
+                    half_height: controller.half_height,
+                    radius: controller.radius,
+                },
+                filter: QueryFilter::new().excluding(entity),
+                min_ground_y: controller.max_slope.cos(),
+            };
+            let start = Isometry3::new(*transform.translation(), Vector3::zeros());
+            let motion = controller.velocity * timestep;
+            let vertical = Vector3::new(0.0, motion.y, 0.0);
+            let horizontal = motion - vertical;
+
+            let mut position = start;
+            let normals = mover.slide(&mut position, horizontal, true);
+            let blocked = normals.iter().any(|normal| normal.y < mover.min_ground_y);
+            if blocked && controller.ground.is_some() && controller.step_height > 0.0 {
+                // Tries again from the height of a step, then goes down on it.
+                let mut stepped = start;
+                let up = Vector3::new(0.0, controller.step_height, 0.0);
+                let rise = mover
+                    .cast(&stepped, up)
+                    .map_or(controller.step_height, |hit| {
+                        (hit.distance - SKIN_WIDTH)
+                            .max(0.0)
+                            .min(controller.step_height)
+                    });
+                stepped.translation.vector.y += rise;
+                mover.slide(&mut stepped, horizontal, true);
+                if let Some(step) = mover.ground(&stepped, rise + SKIN_WIDTH) {
+                    stepped.translation.vector.y -= (step.distance - SKIN_WIDTH).max(0.0);
+                    if horizontal_distance(&start, &stepped)
+                        > horizontal_distance(&start, &position) + EPSILON
+                    {
+                        position = stepped;
+                    }
+                }
+            }
+
+            let normals = mover.slide(&mut position, vertical, false);
+            if controller.velocity.y > 0.0 && normals.iter().any(|normal| normal.y < -EPSILON) {
+                controller.velocity.y = 0.0;
+            }
+
+            // Sticks to the ground when walking down, unless jumping.
+            let snap = if controller.ground.is_some() && controller.velocity.y <= 0.0 {
+                controller.snap_distance
+            } else {
+                0.0
+            };
+            controller.ground = match mover.ground(&position, snap + 2.0 * SKIN_WIDTH) {
+                Some(ground) => {
+                    position.translation.vector.y -= (ground.distance - SKIN_WIDTH).max(0.0);
+                    controller.velocity.y = controller.velocity.y.max(0.0);
+                    Some((ground.entity, ground.normal))
+                }
+                None => None,
+            };
+            transform.set_translation(position.translation.vector);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::ecs::{Builder, RunNow, World};
+
+    use super::*;
+    use crate::{collider::Collider, systems::PhysicsStepSystem};
+
+    #[test]
+    fn characters_land_and_are_stopped_by_walls() {
+        let mut world = World::new();
+        let mut controllers = CharacterControllerSystem;
+        let mut physics = PhysicsStepSystem::default();
+        System::setup(&mut controllers, &mut world.res);
+        System::setup(&mut physics, &mut world.res);
+        world.add_resource(Time::default());
+
+        let mut ground = Transform::default();
+        ground.set_translation_y(-1.0);
+        let ground = world
+            .create_entity()
+            .with(ground)
+            .with(Collider::new(ColliderShape::Cuboid {
+                half_extents: Vector3::new(10.0, 1.0, 10.0),
+            }))
+            .build();
+        let mut wall = Transform::default();
+        wall.set_translation_x(3.0);
+        world
+            .create_entity()
+            .with(wall)
+            .with(Collider::new(ColliderShape::Cuboid {
+                half_extents: Vector3::new(1.0, 5.0, 10.0),
+            }))
+            .build();
+
+        let mut transform = Transform::default();
+        transform.set_translation_y(2.0);
+        let mut controller = CharacterController::new(0.5, 0.5);
+        controller.velocity = Vector3::new(0.0, -5.0, 0.0);
+        let character = world
+            .create_entity()
+            .with(transform)
+            .with(controller)
+            .build();
+
+        for _ in 0..60 {
+            physics.run_now(&world.res);
+            controllers.run_now(&world.res);
+        }
+        {
+            let controller = world.read_storage::<CharacterController>();
+            let controller = controller.get(character).unwrap();
+            assert_eq!(controller.ground(), Some(ground));
+            assert_eq!(controller.velocity.y, 0.0);
+        }
+        let y = world
+            .read_storage::<Transform>()
+            .get(character)
+            .unwrap()
+            .translation()
+            .y;
+        assert!((y - 1.0).abs() < 0.05);
+
+        world
+            .write_storage::<CharacterController>()
+            .get_mut(character)
+            .unwrap()
+            .velocity = Vector3::new(5.0, 0.0, 0.0);
+        for _ in 0..60 {
+            physics.run_now(&world.res);
+            controllers.run_now(&world.res);
+        }
+        let x = world
+            .read_storage::<Transform>()
+            .get(character)
+            .unwrap()
+            .translation()
+            .x;
+        assert!(x < 1.5 && x > 1.4);
+    }
+
+    fn cuboid(world: &mut World, position: Vector3<f32>, half_extents: Vector3<f32>) -> Entity {
+        let mut transform = Transform::default();
+        transform.set_translation(position);
+        world
+            .create_entity()
+            .with(transform)
+            .with(Collider::new(ColliderShape::Cuboid { half_extents }))
+            .build()
+    }
+
+    // The world with a ground whose top is at 0, and a character standing on it at `x`.
+    fn walking(x: f32, velocity: Vector3<f32>) -> (World, Entity, Entity) {
+        let mut world = World::new();
+        System::setup(&mut CharacterControllerSystem, &mut world.res);
+        System::setup(&mut PhysicsStepSystem::default(), &mut world.res);
+        world.add_resource(Time::default());
+        let ground = cuboid(
+            &mut world,
+            Vector3::new(0.0, -1.0, 0.0),
+            Vector3::new(20.0, 1.0, 10.0),
+        );
+
+        let mut transform = Transform::default();
+        transform.set_translation(Vector3::new(x, 1.005, 0.0));
+        let mut controller = CharacterController::new(0.5, 0.5);
+        controller.velocity = velocity;
+        let character = world
+            .create_entity()
+            .with(transform)
+            .with(controller)
+            .build();
+        (world, ground, character)
+    }
+
+    // Runs the systems for `frames` fixed steps, and returns the position of the character and
+    // what it stands on.
+    fn walk(world: &mut World, character: Entity, frames: usize) -> (Vector3<f32>, Option<Entity>) {
+        let mut physics = PhysicsStepSystem::default();
+        System::setup(&mut physics, &mut world.res);
+        for _ in 0..frames {
+            physics.run_now(&world.res);
+            CharacterControllerSystem.run_now(&world.res);
+        }
+        let position = *world
+            .read_storage::<Transform>()
+            .get(character)
+            .unwrap()
+            .translation();
+        let ground = world
+            .read_storage::<CharacterController>()
+            .get(character)
+            .unwrap()
+            .ground();
+        (position, ground)
+    }
+
+    #[test]
+    fn characters_walk_up_steps_and_stick_to_the_ground_walking_down() {
+        // Without gravity, only the snapping brings the character down from the step.
+        let (mut world, ground, character) = walking(0.0, Vector3::new(3.0, 0.0, 0.0));
+        let step = cuboid(
+            &mut world,
+            Vector3::new(3.0, 0.1, 0.0),
+            Vector3::new(1.0, 0.1, 10.0),
+        );
+        let _ = cuboid(
+            &mut world,
+            Vector3::new(12.0, 0.25, 0.0),
+            Vector3::new(1.0, 0.25, 10.0),
+        );
+
+        let (position, standing) = walk(&mut world, character, 60);
+        assert!(position.x > 2.0 && position.x < 4.0);
+        assert!((position.y - 1.2).abs() < 0.05);
+        assert_eq!(standing, Some(step));
+
+        let (position, standing) = walk(&mut world, character, 60);
+        assert!(position.x > 4.5);
+        assert!((position.y - 1.0).abs() < 0.05);
+        assert_eq!(standing, Some(ground));
+
+        // Higher than a step, the block is a wall.
+        let (position, standing) = walk(&mut world, character, 120);
+        assert!(position.x < 10.6);
+        assert!((position.y - 1.0).abs() < 0.05);
+        assert_eq!(standing, Some(ground));
+    }
+
+    #[test]
+    fn characters_walk_up_the_slopes_that_are_not_too_steep() {
+        let climbed = |angle: f32| {
+            let (mut world, _, character) = walking(3.0, Vector3::new(3.0, -1.0, 0.0));
+            // A plank rising from the ground at 5 towards +x.
+            let mut transform = Transform::default();
+            transform.set_translation(Vector3::new(5.0 + 1.0 / angle.sin(), 0.0, 0.0));
+            transform.set_rotation_z_axis(angle);
+            let ramp = world
+                .create_entity()
+                .with(transform)
+                .with(Collider::new(ColliderShape::Cuboid {
+                    half_extents: Vector3::new(5.0, 1.0, 10.0),
+                }))
+                .build();
+            let (position, standing) = walk(&mut world, character, 90);
+            (position.y, standing == Some(ramp))
+        };
+
+        let (y, on_ramp) = climbed(30.0f32.to_radians());
+        assert!(y > 1.5);
+        assert!(on_ramp);
+        let (y, on_ramp) = climbed(60.0f32.to_radians());
+        assert!(y < 1.1);
+        assert!(!on_ramp);
+    }
+}
@@ -0,0 +1,194 @@
+//! The shapes the bodies collide with.
+
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use amethyst_assets::PrefabData;
+use amethyst_core::{
+    ecs::{Component, DenseVecStorage, Entity, WriteStorage},
+    math::{Isometry3, Point2, Point3, Unit, Vector2, Vector3},
+};
+use amethyst_derive::PrefabData;
+use amethyst_error::Error;
+use ncollide3d::shape::{Ball, Capsule, ConvexHull, Cuboid, Plane, ShapeHandle, TriMesh};
+
+/// The shape of a collider, centered on the entity unless the collider has an offset.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ColliderShape {
+    /// A sphere.
+    Ball {
+        /// The radius.
+        radius: f32,
+    },
+    /// A box.
+    Cuboid {
+        /// Half the size of the box on each axis.
+        half_extents: Vector3<f32>,
+    },
+    /// A cylinder along the Y axis with a half sphere at each end.
+    Capsule {
+        /// Half the height of the cylinder, without the ends.
+        half_height: f32,
+        /// The radius of the cylinder and the ends.
+        radius: f32,
+    },
+    /// An infinite plane, for the ground of static colliders.
+    Plane {
+        /// The normal of the plane, pointing out of the solid half-space.
+        normal: Vector3<f32>,
+    },
+    /// The smallest convex shape containing the points.
+    ConvexHull {
+        /// The points, the ones inside the hull being ignored.
+        points: Vec<Point3<f32>>,
+    },
+    /// A mesh of triangles, which is hollow. It is meant for the static colliders, as the
+    /// levels, which the dynamic bodies collide with better than with each other.
+    TriMesh {
+        /// The vertices.
+        vertices: Vec<Point3<f32>>,
+        /// The indices of the vertices of each triangle.
+        indices: Vec<[usize; 3]>,
+    },
+    /// A convex polygon in the XY plane, for the 2D physics. It is simulated as a prism as deep
+    /// along Z as it is large.
+    Polygon {
+        /// The corners of the polygon.
+        points: Vec<Point2<f32>>,
+    },
+}
+
+impl ColliderShape {
+    // The shape of the simulation, with the sizes multiplied by `scale`.
+    pub(crate) fn handle(&self, scale: f32) -> ShapeHandle<f32> {
+        match self {
+            ColliderShape::Ball { radius } => ShapeHandle::new(Ball::new(radius * scale)),
+            ColliderShape::Cuboid { half_extents } => {
+                ShapeHandle::new(Cuboid::new(half_extents * scale))
+            }
+            ColliderShape::Capsule {
+                half_height,
+                radius,
+            } => ShapeHandle::new(Capsule::new(half_height * scale, radius * scale)),
+            ColliderShape::Plane { normal } => ShapeHandle::new(Plane::new(
+                Unit::try_new(*normal, 1.0e-6).unwrap_or_else(Vector3::y_axis),
+            )),
+            ColliderShape::ConvexHull { points } => {
+                let points = points.iter().map(|point| point * scale).collect::<Vec<_>>();
+                hull(&points)
+            }
+            ColliderShape::TriMesh { vertices, indices } => ShapeHandle::new(TriMesh::new(
+                vertices.iter().map(|vertex| vertex * scale).collect(),
+                indices
+                    .iter()
+                    .map(|&[a, b, c]| Point3::new(a, b, c))
+                    .collect(),
+                None,
+            )),
+            ColliderShape::Polygon { points } => {
+                let (min, max) = points.iter().fold(
+                    (
+                        Vector2::repeat(std::f32::MAX),
+                        Vector2::repeat(std::f32::MIN),
+                    ),
+                    |(min, max), point| (min.inf(&point.coords), max.sup(&point.coords)),
+                );
+                let depth = (max - min).amax() * scale / 2.0;
+                let prism = points
+                    .iter()
+                    .flat_map(|point| {
+                        let (x, y) = (point.x * scale, point.y * scale);
+                        vec![Point3::new(x, y, -depth), Point3::new(x, y, depth)]
+                    })
+                    .collect::<Vec<_>>();
+                hull(&prism)
+            }
+        }
+    }
+}
+
+fn hull(points: &[Point3<f32>]) -> ShapeHandle<f32> {
+    match ConvexHull::try_from_points(points) {
+        Some(hull) => ShapeHandle::new(hull),
+        None => {
+            warn!("The hull of a collider is degenerate, a point is used instead");
+            ShapeHandle::new(Ball::new(std::f32::EPSILON))
+        }
+    }
+}
+
+/// A collider, attached to the `RigidBody` of the entity, or static at its `Transform` without one.
+///
+/// Sensors don't collide, they only detect the colliders overlapping them.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, PrefabData)]
+#[prefab(Component)]
+pub struct Collider {
+    /// The shape.
+    pub shape: ColliderShape,
+    /// The position of the shape relative to the entity.
+    #[serde(default = "Isometry3::identity")]
+    pub offset: Isometry3<f32>,
+    /// The density, from which the mass of the body is computed.
+    #[serde(default = "default_density")]
+    pub density: f32,
+    /// The friction coefficient.
+    #[serde(default = "default_friction")]
+    pub friction: f32,
+    /// How much of the speed is kept when bouncing, between 0 and 1.
+    #[serde(default)]
+    pub restitution: f32,
+    /// Whether the collider only detects the overlaps.
+    #[serde(default)]
+    pub sensor: bool,
+}
+
+pub(crate) fn default_density() -> f32 {
+    1.0
+}
+
+pub(crate) fn default_friction() -> f32 {
+    0.5
+}
+
+impl Collider {
+    /// A collider of the given shape, with a density of 1 and a friction of 0.5.
+    pub fn new(shape: ColliderShape) -> Self {
+        Collider {
+            shape,
+            offset: Isometry3::identity(),
+            density: default_density(),
+            friction: default_friction(),
+            restitution: 0.0,
+            sensor: false,
+        }
+    }
+
+    /// Makes the collider a sensor.
+    pub fn sensor(mut self) -> Self {
+        self.sensor = true;
+        self
+    }
+
+    /// Moves the shape relative to the entity.
+    pub fn with_offset(mut self, offset: Isometry3<f32>) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Sets the density.
+    pub fn with_density(mut self, density: f32) -> Self {
+        self.density = density;
+        self
+    }
+
+    /// Sets the friction and the restitution.
+    pub fn with_material(mut self, friction: f32, restitution: f32) -> Self {
+        self.friction = friction;
+        self.restitution = restitution;
+        self
+    }
+}
+
+impl Component for Collider {
+    type Storage = DenseVecStorage<Self>;
+}
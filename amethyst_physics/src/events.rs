@@ -0,0 +1,74 @@
+//! The events sent when colliders touch, when they enter sensors and when joints break.
+
+use amethyst_core::{
+    ecs::Entity,
+    math::{Point3, Vector3},
+};
+
+/// A point where two colliders touch.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ContactPoint {
+    /// The point, on the surface of the first collider.
+    pub point: Point3<f32>,
+    /// The normal of the contact, pointing from the first collider to the second.
+    pub normal: Vector3<f32>,
+    /// How deep the colliders overlap.
+    pub depth: f32,
+}
+
+/// Sent through an `EventChannel<CollisionEvent>` by the `PhysicsStepSystem` when two colliders
+/// that aren't sensors start or stop touching.
+///
+/// No event is sent for the entities removed while they touch.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CollisionEvent {
+    /// The colliders of the entities started touching.
+    Started {
+        /// The entities.
+        entities: (Entity, Entity),
+        /// Where they touch.
+        contacts: Vec<ContactPoint>,
+        /// An estimate of the impulse of the impact: the speed at which they approached along
+        /// the normal of the deepest contact, times their reduced mass.
+        impulse: f32,
+    },
+    /// The colliders of the entities stopped touching.
+    Stopped {
+        /// The entities.
+        entities: (Entity, Entity),
+    },
+}
+
+/// Sent through an `EventChannel<TriggerEvent>` by the `PhysicsStepSystem` when a collider enters
+/// or exits a sensor, see `Collider::sensor`.
+///
+/// No event is sent for the entities removed while they overlap.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TriggerEvent {
+    /// A collider entered a sensor.
+    Entered {
+        /// The entity with the sensor.
+        trigger: Entity,
+        /// The entity that entered it.
+        other: Entity,
+    },
+    /// A collider exited a sensor.
+    Exited {
+        /// The entity with the sensor.
+        trigger: Entity,
+        /// The entity that exited it.
+        other: Entity,
+    },
+}
+
+/// Sent through an `EventChannel<JointEvent>` by the `PhysicsStepSystem` about the `Joint`s.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JointEvent {
+    /// A joint broke, and its component was removed.
+    Broken {
+        /// The entity which had the joint.
+        joint: Entity,
+        /// The entity it connected to.
+        other: Entity,
+    },
+}
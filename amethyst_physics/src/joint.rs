@@ -0,0 +1,173 @@
+//! The joints between the bodies of two entities.
+
+use serde::{Deserialize, Serialize};
+
+use amethyst_assets::PrefabData;
+use amethyst_core::{
+    ecs::{Component, DenseVecStorage, Entity, WriteStorage},
+    math::{Point3, Vector3},
+};
+use amethyst_error::{format_err, Error};
+
+/// How a `Joint` constrains the bodies.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum JointKind {
+    /// Keeps the anchors together, the bodies turning freely around them, as a shoulder.
+    Ball,
+    /// Keeps the anchors together, the bodies only turning around the axis, as a door.
+    Hinge {
+        /// The axis, in the space of the entity of the joint.
+        axis: Vector3<f32>,
+    },
+    /// Lets the anchors only slide along the axis, the bodies not turning, as a piston.
+    Prismatic {
+        /// The axis, in the space of the entity of the joint.
+        axis: Vector3<f32>,
+    },
+    /// Pulls or pushes the anchors towards being `rest_length` apart, as a suspension.
+    Spring {
+        /// The distance between the anchors at rest.
+        rest_length: f32,
+        /// The force of the spring per meter it is stretched or compressed.
+        stiffness: f32,
+        /// The force slowing the spring per meter per second it stretches or compresses.
+        damping: f32,
+    },
+}
+
+/// A joint from the body of the entity to the one of `other`, simulated once both entities are,
+/// see `RigidBody`. An entity with a collider and without a body is a fixed point of the world.
+/// An entity has a single joint, ragdolls and chains giving each part a joint to the previous one.
+///
+/// The joint breaks when its anchors get further apart than the `break_distance`, as they do
+/// when it is pulled harder than the solver can hold, or when a spring stretches that far. The
+/// `PhysicsStepSystem` then removes the component and sends a `JointEvent::Broken`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Joint {
+    /// How the bodies are constrained.
+    pub kind: JointKind,
+    /// The entity the joint connects to.
+    pub other: Entity,
+    /// The anchor on the entity, in its space.
+    pub anchor: Point3<f32>,
+    /// The anchor on the other entity, in its space.
+    pub other_anchor: Point3<f32>,
+    /// How far apart the anchors get before the joint breaks, if it can break.
+    pub break_distance: Option<f32>,
+}
+
+impl Joint {
+    /// Creates a joint to `other`, with the anchors at the origins of both entities.
+    pub fn new(kind: JointKind, other: Entity) -> Self {
+        Joint {
+            kind,
+            other,
+            anchor: Point3::origin(),
+            other_anchor: Point3::origin(),
+            break_distance: None,
+        }
+    }
+
+    /// Sets the anchors, in the spaces of the entity and of the other entity.
+    pub fn with_anchors(mut self, anchor: Point3<f32>, other_anchor: Point3<f32>) -> Self {
+        self.anchor = anchor;
+        self.other_anchor = other_anchor;
+        self
+    }
+
+    /// Makes the joint break once its anchors are further than `distance` apart.
+    pub fn breaking_at(mut self, distance: f32) -> Self {
+        self.break_distance = Some(distance);
+        self
+    }
+}
+
+impl Component for Joint {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// Prefab data adding a `Joint` to the entity, connected to the entity of the prefab at the
+/// index `other`:
+///
+/// ~~~ron
+/// joint: Some((kind: Hinge(axis: [0.0, 1.0, 0.0]), other: 0, anchor: [-0.5, 0.0, 0.0])),
+/// ~~~
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct JointPrefab {
+    /// How the bodies are constrained.
+    pub kind: JointKind,
+    /// The index of the other entity in the prefab.
+    pub other: usize,
+    /// The anchor on the entity, in its space.
+    #[serde(default = "Point3::origin")]
+    pub anchor: Point3<f32>,
+    /// The anchor on the other entity, in its space.
+    #[serde(default = "Point3::origin")]
+    pub other_anchor: Point3<f32>,
+    /// How far apart the anchors get before the joint breaks, if it can break.
+    #[serde(default)]
+    pub break_distance: Option<f32>,
+}
+
+impl<'a> PrefabData<'a> for JointPrefab {
+    type SystemData = WriteStorage<'a, Joint>;
+    type Result = ();
+
+    fn add_to_entity(
+        &self,
+        entity: Entity,
+        joints: &mut Self::SystemData,
+        entities: &[Entity],
+        _: &[Entity],
+    ) -> Result<(), Error> {
+        let other = *entities.get(self.other).ok_or_else(|| {
+            format_err!(
+                "The joint connects to the entity {} of a prefab of {} entities",
+                self.other,
+                entities.len()
+            )
+        })?;
+        joints.insert(
+            entity,
+            Joint {
+                kind: self.kind.clone(),
+                other,
+                anchor: self.anchor,
+                other_anchor: self.other_anchor,
+                break_distance: self.break_distance,
+            },
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::ecs::{Builder, World};
+
+    use super::*;
+
+    #[test]
+    fn joints_to_missing_entities_are_refused() {
+        let mut world = World::new();
+        world.register::<Joint>();
+        let entities = [world.create_entity().build(), world.create_entity().build()];
+        let prefab = JointPrefab {
+            kind: JointKind::Ball,
+            other: 1,
+            anchor: Point3::origin(),
+            other_anchor: Point3::origin(),
+            break_distance: None,
+        };
+        let mut joints = world.write_storage::<Joint>();
+        prefab
+            .add_to_entity(entities[0], &mut joints, &entities, &[])
+            .unwrap();
+        assert_eq!(joints.get(entities[0]).unwrap().other, entities[1]);
+
+        let missing = JointPrefab { other: 2, ..prefab };
+        assert!(missing
+            .add_to_entity(entities[0], &mut joints, &entities, &[])
+            .is_err());
+    }
+}
@@ -0,0 +1,51 @@
+//! Physics for Amethyst, simulated by `nphysics`.
+//!
+//! Entities with a `Transform` get simulated when they have a `RigidBody`, a `Collider` or both,
+//! which can be loaded from prefabs with `PhysicsPrefab`. The `PhysicsBundle` steps the
+//! simulation on the fixed time step and keeps it in sync with the transforms: the game moves
+//! the kinematic and static entities, and the simulation moves the dynamic ones. Gameplay reacts
+//! to the contacts through the `CollisionEvent` and `TriggerEvent` channels, and casts rays and
+//! shapes with the `PhysicsQuery`. The `CharacterController` moves players and creatures that
+//! slide along the walls and walk up the slopes and the steps. The `Joint`s connect the bodies
+//! with hinges, ball joints, prismatic joints and springs.
+//!
+//! The simulation can be restricted to 2D, with the colliders of the sprites and the outlines of
+//! the colliders drawn with the debug lines when the `renderer` feature is enabled. The `picking`
+//! feature adds the `PickingSystem`, which finds the entities under the mouse through the camera.
+
+#![warn(missing_docs, rust_2018_idioms, rust_2018_compatibility)]
+
+pub use ncollide3d;
+pub use nphysics3d;
+
+#[cfg(feature = "picking")]
+pub use self::picking::{Hovered, PickEvent, PickEventType, PickingSystem, ScreenPicker};
+#[cfg(feature = "renderer")]
+pub use self::sprite::{PhysicsDebugSystem, SpriteCollider, SpriteColliderSystem};
+pub use self::{
+    body::{BodyStatus, RigidBody},
+    bundle::PhysicsBundle,
+    character::{CharacterController, CharacterControllerSystem},
+    collider::{Collider, ColliderShape},
+    events::{CollisionEvent, ContactPoint, JointEvent, TriggerEvent},
+    joint::{Joint, JointKind, JointPrefab},
+    prefab::PhysicsPrefab,
+    query::{PhysicsQuery, QueryFilter, QueryHit},
+    systems::PhysicsStepSystem,
+    world::PhysicsWorld,
+};
+
+mod body;
+mod bundle;
+mod character;
+mod collider;
+mod events;
+mod joint;
+#[cfg(feature = "picking")]
+mod picking;
+mod prefab;
+mod query;
+#[cfg(feature = "renderer")]
+mod sprite;
+mod systems;
+mod world;
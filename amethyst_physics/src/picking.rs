@@ -0,0 +1,387 @@
+//! Picking of the entities under the mouse, through the camera.
+
+use std::{cmp::Ordering, hash::Hash, marker::PhantomData};
+
+use amethyst_core::{
+    ecs::prelude::{Entity, Join, Read, ReadExpect, ReadStorage, System, Write},
+    math::{Point2, Point3, Unit, Vector3},
+    shrev::EventChannel,
+    GlobalTransform,
+};
+use amethyst_input::InputHandler;
+use amethyst_renderer::{ActiveCamera, Camera, MouseButton, ScreenDimensions};
+use shred_derive::SystemData;
+
+use crate::query::{PhysicsQuery, QueryFilter, QueryHit};
+
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+// The radius of the point picking the colliders in 2D.
+const PICK_RADIUS: f32 = 1.0e-3;
+
+/// Utility `SystemData` to find the entity seen at a position on the screen.
+///
+/// The ray goes from the `ActiveCamera`, or the first camera if there is no active camera, through
+/// the position and hits the colliders. In 2D, the colliders are picked where the ray crosses the
+/// XY plane. The entities which should only be picked, as the ones without physics, can be given
+/// a sensor `Collider` as their bounding volume and be picked with `QueryFilter::with_sensors`.
+#[derive(SystemData)]
+pub struct ScreenPicker<'a> {
+    query: PhysicsQuery<'a>,
+    active_camera: Option<Read<'a, ActiveCamera>>,
+    cameras: ReadStorage<'a, Camera>,
+    globals: ReadStorage<'a, GlobalTransform>,
+    screen_dimensions: ReadExpect<'a, ScreenDimensions>,
+}
+
+impl<'a> ScreenPicker<'a> {
+    /// The ray from the camera through the pixel at `screen_position`, from the top left corner
+    /// of the screen like the mouse position of the `InputHandler`.
+    ///
+    /// Returns `None` without a camera.
+    pub fn ray(&self, screen_position: Point2<f32>) -> Option<(Point3<f32>, Unit<Vector3<f32>>)> {
+        let entity = self
+            .active_camera
+            .as_ref()
+            .and_then(|active| active.entity)
+            .filter(|entity| self.cameras.contains(*entity) && self.globals.contains(*entity));
+        let (camera, transform) = match entity {
+            Some(entity) => (self.cameras.get(entity)?, self.globals.get(entity)?),
+            None => (&self.cameras, &self.globals).join().next()?,
+        };
+        camera.screen_ray(screen_position, transform, &self.screen_dimensions)
+    }
+
+    /// The closest collider seen at `screen_position`, accepted by the `filter`. In 2D, it is the
+    /// collider of the entity with the highest z, drawn in front of the others.
+    pub fn pick(&self, screen_position: Point2<f32>, filter: &QueryFilter) -> Option<QueryHit> {
+        let (origin, direction) = self.ray(screen_position)?;
+        if !self.query.is_2d() {
+            return self
+                .query
+                .raycast(origin, direction.into_inner(), std::f32::MAX, filter);
+        }
+        let point = if direction.z.abs() > 1.0e-6 {
+            origin + direction.into_inner() * (-origin.z / direction.z)
+        } else {
+            origin
+        };
+        let depth = |entity: Entity| {
+            self.globals
+                .get(entity)
+                .map_or(std::f32::MIN, |global| global.0[(2, 3)])
+        };
+        self.query
+            .overlap_sphere(point, PICK_RADIUS, filter)
+            .into_iter()
+            .max_by(|a, b| depth(*a).partial_cmp(&depth(*b)).unwrap_or(Ordering::Equal))
+            .map(|entity| QueryHit {
+                entity,
+                point,
+                normal: Vector3::z(),
+                distance: (point - origin).norm(),
+            })
+    }
+}
+
+/// The type of a `PickEvent`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PickEventType {
+    /// The mouse started hovering the entity.
+    HoverStart,
+    /// The mouse stopped hovering the entity.
+    HoverStop,
+    /// A mouse button was pressed on the entity.
+    ClickStart(MouseButton),
+    /// A mouse button was pressed and released on the entity.
+    Click(MouseButton),
+}
+
+/// An event sent by the `PickingSystem`, about an entity under the mouse.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PickEvent {
+    /// What happened.
+    pub event_type: PickEventType,
+    /// The entity picked.
+    pub target: Entity,
+}
+
+/// The entity under the mouse, as found by the `PickingSystem`.
+#[derive(Clone, Debug, Default)]
+pub struct Hovered {
+    /// Where the ray from the mouse hit the entity, if any.
+    pub hit: Option<QueryHit>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct ButtonState {
+    button: MouseButton,
+    was_down: bool,
+    pressed_on: Option<Entity>,
+}
+
+/// Picks the entity under the mouse with the `ScreenPicker`, stores it in the `Hovered`
+/// resource and sends `PickEvent`s when it changes and when it is clicked.
+///
+/// The generic types A and B represent the A and B generic parameter of the InputHandler<A,B>.
+/// It should run after the physics, so that the colliders are where the entities are drawn.
+pub struct PickingSystem<A, B> {
+    filter: QueryFilter,
+    buttons: Vec<ButtonState>,
+    _marker: PhantomData<(A, B)>,
+}
+
+impl<A, B> PickingSystem<A, B> {
+    /// Creates a new `PickingSystem`, picking all the colliders, the sensors included.
+    pub fn new() -> Self {
+        Self::with_filter(QueryFilter::new().with_sensors())
+    }
+
+    /// Creates a new `PickingSystem`, picking the colliders accepted by the `filter`.
+    pub fn with_filter(filter: QueryFilter) -> Self {
+        let buttons = [MouseButton::Left, MouseButton::Right, MouseButton::Middle]
+            .iter()
+            .map(|&button| ButtonState {
+                button,
+                was_down: false,
+                pressed_on: None,
+            })
+            .collect();
+        PickingSystem {
+            filter,
+            buttons,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<A, B> Default for PickingSystem<A, B> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, A, B> System<'a> for PickingSystem<A, B>
+where
+    A: Send + Sync + Eq + Hash + Clone + 'static,
+    B: Send + Sync + Eq + Hash + Clone + 'static,
+{
+    type SystemData = (
+        ScreenPicker<'a>,
+        Read<'a, InputHandler<A, B>>,
+        Write<'a, Hovered>,
+        Write<'a, EventChannel<PickEvent>>,
+    );
+
+    fn run(&mut self, (picker, input, mut hovered, mut events): Self::SystemData) {
+        #[cfg(feature = "profiler")]
+        profile_scope!("picking_system");
+
+        let hit = input
+            .mouse_position()
+            .and_then(|(x, y)| picker.pick(Point2::new(x as f32, y as f32), &self.filter));
+        let target = hit.map(|hit| hit.entity);
+        let last_target = hovered.hit.map(|hit| hit.entity);
+
+        let mut picks = Vec::new();
+        if target != last_target {
+            if let Some(last_target) = last_target {
+                picks.push((PickEventType::HoverStop, last_target));
+            }
+            if let Some(target) = target {
+                picks.push((PickEventType::HoverStart, target));
+            }
+        }
+        for state in &mut self.buttons {
+            let down = input.mouse_button_is_down(state.button);
+            if down && !state.was_down {
+                state.pressed_on = target;
+                if let Some(target) = target {
+                    picks.push((PickEventType::ClickStart(state.button), target));
+                }
+            } else if !down && state.was_down {
+                if let Some(pressed_on) = state.pressed_on.take() {
+                    if target == Some(pressed_on) {
+                        picks.push((PickEventType::Click(state.button), pressed_on));
+                    }
+                }
+            }
+            state.was_down = down;
+        }
+
+        hovered.hit = hit;
+        events.iter_write(
+            picks
+                .into_iter()
+                .map(|(event_type, target)| PickEvent { event_type, target }),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::{
+        ecs::{Builder, RunNow, World},
+        math::{Isometry3, Matrix4},
+    };
+    use amethyst_input::InputEvent;
+    use amethyst_renderer::{ElementState, Event, Projection, WindowEvent};
+    use winit::{dpi::LogicalPosition, DeviceId, ModifiersState, WindowId};
+
+    use super::*;
+    use crate::{
+        collider::{Collider, ColliderShape},
+        world::PhysicsWorld,
+    };
+
+    #[test]
+    fn picks_the_collider_under_the_screen_position() {
+        let mut world = World::new();
+        world.register::<Camera>();
+        world.register::<GlobalTransform>();
+        let ball = world.create_entity().build();
+        let mut physics = PhysicsWorld::default();
+        physics.insert(
+            ball,
+            Isometry3::new(Vector3::new(0.0, 0.0, -10.0), Vector3::zeros()),
+            None,
+            Some(&Collider::new(ColliderShape::Ball { radius: 1.0 })),
+        );
+        physics.step(0.0);
+        world.add_resource(physics);
+        world.add_resource(ScreenDimensions::new(200, 100, 1.0));
+        world
+            .create_entity()
+            .with(Camera::from(Projection::perspective(2.0, 1.0)))
+            .with(GlobalTransform(Matrix4::identity()))
+            .build();
+
+        let picker = world.system_data::<ScreenPicker<'_>>();
+        let filter = QueryFilter::new();
+        let hit = picker.pick(Point2::new(100.0, 50.0), &filter).unwrap();
+        assert_eq!(hit.entity, ball);
+        assert!((hit.point - Point3::new(0.0, 0.0, -9.0)).norm() < 1.0e-3);
+        assert!(picker.pick(Point2::new(10.0, 10.0), &filter).is_none());
+    }
+
+    type Input = InputHandler<String, String>;
+
+    fn send(world: &mut World, event: WindowEvent) {
+        let event = Event::WindowEvent {
+            window_id: unsafe { WindowId::dummy() },
+            event,
+        };
+        world.write_resource::<Input>().send_event(
+            &event,
+            &mut EventChannel::<InputEvent<String>>::new(),
+            1.0,
+        );
+    }
+
+    fn modifiers() -> ModifiersState {
+        ModifiersState {
+            shift: false,
+            ctrl: false,
+            alt: false,
+            logo: false,
+        }
+    }
+
+    fn move_to(world: &mut World, x: f64, y: f64) {
+        send(
+            world,
+            WindowEvent::CursorMoved {
+                device_id: unsafe { DeviceId::dummy() },
+                position: LogicalPosition::new(x, y),
+                modifiers: modifiers(),
+            },
+        );
+    }
+
+    fn left_button(world: &mut World, state: ElementState) {
+        send(
+            world,
+            WindowEvent::MouseInput {
+                device_id: unsafe { DeviceId::dummy() },
+                state,
+                button: MouseButton::Left,
+                modifiers: modifiers(),
+            },
+        );
+    }
+
+    #[test]
+    fn the_sprite_in_front_is_picked_and_clicked() {
+        let mut world = World::new();
+        let mut system = PickingSystem::<String, String>::new();
+        System::setup(&mut system, &mut world.res);
+        world.add_resource(ScreenDimensions::new(200, 100, 1.0));
+        let mut reader = world
+            .write_resource::<EventChannel<PickEvent>>()
+            .register_reader();
+
+        // Overlapping sprites, the one in the middle of the physics world being in front.
+        let mut physics = PhysicsWorld::default();
+        physics.configure(1.0, true);
+        let mut sprites = Vec::new();
+        for &z in &[0.0, 2.0, 1.0] {
+            let translation = Vector3::new(0.0, 0.0, z);
+            let sprite = world
+                .create_entity()
+                .with(GlobalTransform(Matrix4::new_translation(&translation)))
+                .build();
+            physics.insert(
+                sprite,
+                Isometry3::new(translation, Vector3::zeros()),
+                None,
+                Some(&Collider::new(ColliderShape::Cuboid {
+                    half_extents: Vector3::new(10.0, 10.0, 1.0),
+                })),
+            );
+            sprites.push(sprite);
+        }
+        physics.step(0.0);
+        world.add_resource(physics);
+        world
+            .create_entity()
+            .with(Camera::from(Projection::orthographic(
+                -100.0, 100.0, -50.0, 50.0,
+            )))
+            .with(GlobalTransform(Matrix4::new_translation(&Vector3::new(
+                0.0, 0.0, 10.0,
+            ))))
+            .build();
+        let front = sprites[1];
+        let mut run = |world: &mut World| {
+            system.run_now(&world.res);
+            world
+                .read_resource::<EventChannel<PickEvent>>()
+                .read(&mut reader)
+                .map(|event| (event.event_type, event.target))
+                .collect::<Vec<_>>()
+        };
+
+        move_to(&mut world, 100.0, 50.0);
+        assert_eq!(run(&mut world), vec![(PickEventType::HoverStart, front)]);
+        assert_eq!(
+            world.read_resource::<Hovered>().hit.map(|hit| hit.entity),
+            Some(front)
+        );
+
+        left_button(&mut world, ElementState::Pressed);
+        assert_eq!(
+            run(&mut world),
+            vec![(PickEventType::ClickStart(MouseButton::Left), front)]
+        );
+        left_button(&mut world, ElementState::Released);
+        assert_eq!(
+            run(&mut world),
+            vec![(PickEventType::Click(MouseButton::Left), front)]
+        );
+
+        move_to(&mut world, 10.0, 10.0);
+        assert_eq!(run(&mut world), vec![(PickEventType::HoverStop, front)]);
+        assert!(world.read_resource::<Hovered>().hit.is_none());
+    }
+}
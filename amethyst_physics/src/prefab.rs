@@ -0,0 +1,36 @@
+//! The prefab data of the physics components.
+
+use serde::{Deserialize, Serialize};
+
+use amethyst_assets::{PrefabData, ProgressCounter};
+use amethyst_core::ecs::{Entity, WriteStorage};
+use amethyst_derive::PrefabData;
+use amethyst_error::Error;
+
+use crate::{
+    body::RigidBody, character::CharacterController, collider::Collider, joint::JointPrefab,
+};
+
+/// Prefab data adding a `RigidBody`, a `Collider`, a `CharacterController` and a `Joint` to an
+/// entity, which also needs a `Transform`:
+///
+/// ~~~ron
+/// physics: (
+///     body: Some((status: Dynamic, mass: 2.0)),
+///     collider: Some((shape: Ball(radius: 0.5), restitution: 0.8)),
+/// ),
+/// ~~~
+///
+/// An entity with a collider and no body is a static collider, as the ground.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PrefabData)]
+#[serde(default)]
+pub struct PhysicsPrefab {
+    /// The body simulated for the entity.
+    pub body: Option<RigidBody>,
+    /// The shape of the entity.
+    pub collider: Option<Collider>,
+    /// The controller moving the entity.
+    pub character: Option<CharacterController>,
+    /// The joint to another entity of the prefab.
+    pub joint: Option<JointPrefab>,
+}
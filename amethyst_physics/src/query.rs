@@ -0,0 +1,338 @@
+//! Raycasts, shape casts and overlap queries against the colliders.
+
+use std::cmp::Ordering;
+
+use amethyst_core::{
+    ecs::{Entity, Read},
+    math::{Isometry3, Point3, Vector3},
+};
+use ncollide3d::{
+    bounding_volume::BoundingVolume,
+    pipeline::CollisionGroups,
+    query::{self, Proximity, Ray},
+};
+use nphysics3d::object::ColliderHandle;
+use shred_derive::SystemData;
+
+use crate::{collider::ColliderShape, world::PhysicsWorld};
+
+// How far apart the shapes may be when looking for the point where a shape cast hit.
+const CONTACT_PREDICTION: f32 = 0.01;
+
+/// Which colliders a query may hit. By default, all of them but the sensors.
+#[derive(Clone, Debug, Default)]
+pub struct QueryFilter {
+    excluded: Vec<Entity>,
+    sensors: bool,
+}
+
+impl QueryFilter {
+    /// A filter accepting all the colliders but the sensors.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Ignores the collider of an entity, as the one the query starts from.
+    pub fn excluding(mut self, entity: Entity) -> Self {
+        self.excluded.push(entity);
+        self
+    }
+
+    /// Accepts the sensors too.
+    pub fn with_sensors(mut self) -> Self {
+        self.sensors = true;
+        self
+    }
+}
+
+/// Where a query hit a collider.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct QueryHit {
+    /// The entity of the collider.
+    pub entity: Entity,
+    /// The point hit, on the surface of the collider.
+    pub point: Point3<f32>,
+    /// The normal of the surface at the point.
+    pub normal: Vector3<f32>,
+    /// How far the ray or the shape went before hitting the collider.
+    pub distance: f32,
+}
+
+/// Utility `SystemData` to query the colliders of the `PhysicsWorld`, from any system.
+///
+/// The queries see the colliders as they were after the last step of the `PhysicsStepSystem`.
+/// Their positions and distances are in the units of the transforms, and in 2D they happen in the
+/// XY plane.
+///
+/// ~~~ignore
+/// fn run(&mut self, (query, transforms, players): Self::SystemData) {
+///     for (entity, transform, _) in (&*entities, &transforms, &players).join() {
+///         let feet = Point3::from(*transform.translation());
+///         let filter = QueryFilter::new().excluding(entity);
+///         let grounded = query.raycast(feet, -Vector3::y(), 0.1, &filter).is_some();
+///     }
+/// }
+/// ~~~
+#[derive(SystemData)]
+pub struct PhysicsQuery<'a> {
+    physics: Read<'a, PhysicsWorld>,
+}
+
+impl<'a> PhysicsQuery<'a> {
+    /// Whether the queries happen in the XY plane.
+    pub fn is_2d(&self) -> bool {
+        self.physics.is_2d()
+    }
+
+    /// The first collider hit by a ray from `origin` along `direction`, up to `max_distance`.
+    pub fn raycast(
+        &self,
+        origin: Point3<f32>,
+        direction: Vector3<f32>,
+        max_distance: f32,
+        filter: &QueryFilter,
+    ) -> Option<QueryHit> {
+        self.raycast_all(origin, direction, max_distance, filter)
+            .into_iter()
+            .next()
+    }
+
+    /// All the colliders hit by a ray from `origin` along `direction` up to `max_distance`,
+    /// from the closest.
+    pub fn raycast_all(
+        &self,
+        origin: Point3<f32>,
+        direction: Vector3<f32>,
+        max_distance: f32,
+        filter: &QueryFilter,
+    ) -> Vec<QueryHit> {
+        let units = self.physics.units;
+        let direction = match units.vector_to_physics(direction).try_normalize(1.0e-6) {
+            Some(direction) => direction,
+            None => return Vec::new(),
+        };
+        let ray = Ray::new(units.point_to_physics(origin), direction);
+        let max_distance = max_distance / units.scale;
+        let groups = CollisionGroups::new();
+        let mut hits = self
+            .physics
+            .world()
+            .collider_world()
+            .interferences_with_ray(&ray, &groups)
+            .filter(|(_, intersection)| intersection.toi <= max_distance)
+            .filter_map(|(collider, intersection)| {
+                Some(QueryHit {
+                    entity: self.accepted(collider.handle(), filter)?,
+                    point: units.point_from_physics(ray.point_at(intersection.toi), origin.z),
+                    normal: intersection.normal,
+                    distance: intersection.toi * units.scale,
+                })
+            })
+            .collect::<Vec<_>>();
+        hits.sort_by(|a, b| {
+            a.distance
+                .partial_cmp(&b.distance)
+                .unwrap_or(Ordering::Equal)
+        });
+        hits
+    }
+
+    /// The first collider hit by a sphere moved from `origin` along `direction`, up to
+    /// `max_distance`.
+    pub fn sphere_cast(
+        &self,
+        origin: Point3<f32>,
+        radius: f32,
+        direction: Vector3<f32>,
+        max_distance: f32,
+        filter: &QueryFilter,
+    ) -> Option<QueryHit> {
+        self.shape_cast(
+            &ColliderShape::Ball { radius },
+            &Isometry3::new(origin.coords, Vector3::zeros()),
+            direction,
+            max_distance,
+            filter,
+        )
+    }
+
+    /// The first collider hit by a shape moved from `position` along `direction`, up to
+    /// `max_distance`. The shape shouldn't be a plane.
+    ///
+    /// The colliders the shape overlaps at `position` are hit at a distance of 0.
+    pub fn shape_cast(
+        &self,
+        shape: &ColliderShape,
+        position: &Isometry3<f32>,
+        direction: Vector3<f32>,
+        max_distance: f32,
+        filter: &QueryFilter,
+    ) -> Option<QueryHit> {
+        let units = self.physics.units;
+        let z = position.translation.vector.z;
+        let direction = units.vector_to_physics(direction).try_normalize(1.0e-6)?;
+        let max_distance = max_distance / units.scale;
+        let shape = units.shape(shape);
+        let position = &units.to_physics(position);
+        let mut end = *position;
+        end.translation.vector += direction * max_distance;
+        let swept = shape
+            .as_ref()
+            .aabb(position)
+            .merged(&shape.as_ref().aabb(&end));
+        let groups = CollisionGroups::new();
+
+        self.physics
+            .world()
+            .collider_world()
+            .interferences_with_aabb(&swept, &groups)
+            .filter_map(|collider| {
+                let entity = self.accepted(collider.handle(), filter)?;
+                let distance = query::time_of_impact(
+                    position,
+                    &direction,
+                    shape.as_ref(),
+                    collider.position(),
+                    &Vector3::zeros(),
+                    collider.shape().as_ref(),
+                )
+                .filter(|distance| *distance <= max_distance)?;
+
+                let mut at = *position;
+                at.translation.vector += direction * distance;
+                let (point, normal) = match query::contact(
+                    &at,
+                    shape.as_ref(),
+                    collider.position(),
+                    collider.shape().as_ref(),
+                    CONTACT_PREDICTION,
+                ) {
+                    Some(contact) => (contact.world2, -contact.normal.into_inner()),
+                    None => (Point3::from(at.translation.vector), -direction),
+                };
+                Some(QueryHit {
+                    entity,
+                    point: units.point_from_physics(point, z),
+                    normal,
+                    distance: distance * units.scale,
+                })
+            })
+            .min_by(|a, b| {
+                a.distance
+                    .partial_cmp(&b.distance)
+                    .unwrap_or(Ordering::Equal)
+            })
+    }
+
+    /// The entities whose colliders overlap a sphere.
+    pub fn overlap_sphere(
+        &self,
+        center: Point3<f32>,
+        radius: f32,
+        filter: &QueryFilter,
+    ) -> Vec<Entity> {
+        self.overlap(
+            &ColliderShape::Ball { radius },
+            &Isometry3::new(center.coords, Vector3::zeros()),
+            filter,
+        )
+    }
+
+    /// The entities whose colliders overlap a shape at `position`.
+    pub fn overlap(
+        &self,
+        shape: &ColliderShape,
+        position: &Isometry3<f32>,
+        filter: &QueryFilter,
+    ) -> Vec<Entity> {
+        let units = self.physics.units;
+        let shape = units.shape(shape);
+        let position = &units.to_physics(position);
+        let aabb = shape.as_ref().aabb(position);
+        let groups = CollisionGroups::new();
+        self.physics
+            .world()
+            .collider_world()
+            .interferences_with_aabb(&aabb, &groups)
+            .filter(|collider| {
+                query::proximity(
+                    position,
+                    shape.as_ref(),
+                    collider.position(),
+                    collider.shape().as_ref(),
+                    0.0,
+                ) == Proximity::Intersecting
+            })
+            .filter_map(|collider| self.accepted(collider.handle(), filter))
+            .collect()
+    }
+
+    // The entity of a collider, if the filter accepts it.
+    fn accepted(&self, collider: ColliderHandle, filter: &QueryFilter) -> Option<Entity> {
+        let entity = self.physics.entity(collider)?;
+        if filter.excluded.contains(&entity) || (!filter.sensors && self.physics.is_sensor(entity))
+        {
+            return None;
+        }
+        Some(entity)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::ecs::{Builder, World};
+
+    use super::*;
+    use crate::collider::Collider;
+
+    #[test]
+    fn queries_hit_the_colliders() {
+        let mut world = World::new();
+        let wall = world.create_entity().build();
+        let sensor = world.create_entity().build();
+        let mut physics = PhysicsWorld::default();
+        physics.insert(
+            wall,
+            Isometry3::new(Vector3::new(5.0, 0.0, 0.0), Vector3::zeros()),
+            None,
+            Some(&Collider::new(ColliderShape::Cuboid {
+                half_extents: Vector3::new(1.0, 1.0, 1.0),
+            })),
+        );
+        physics.insert(
+            sensor,
+            Isometry3::identity(),
+            None,
+            Some(&Collider::new(ColliderShape::Ball { radius: 1.0 }).sensor()),
+        );
+        // The colliders are found once the world was updated.
+        physics.step(0.0);
+        world.add_resource(physics);
+
+        let query = world.system_data::<PhysicsQuery<'_>>();
+        let filter = QueryFilter::new();
+        let hit = query
+            .raycast(Point3::origin(), Vector3::x(), 10.0, &filter)
+            .unwrap();
+        assert_eq!(hit.entity, wall);
+        assert!((hit.distance - 4.0).abs() < 1.0e-4);
+        assert!((hit.normal + Vector3::x()).norm() < 1.0e-4);
+        assert!(query
+            .raycast(Point3::origin(), Vector3::x(), 3.0, &filter)
+            .is_none());
+
+        let hit = query
+            .sphere_cast(Point3::origin(), 0.5, Vector3::x(), 10.0, &filter)
+            .unwrap();
+        assert!((hit.distance - 3.5).abs() < 1.0e-3);
+
+        assert_eq!(
+            query.overlap_sphere(Point3::origin(), 0.5, &filter),
+            Vec::new()
+        );
+        assert_eq!(
+            query.overlap_sphere(Point3::origin(), 0.5, &QueryFilter::new().with_sensors()),
+            vec![sensor]
+        );
+    }
+}
@@ -0,0 +1,322 @@
+//! The colliders of the sprites, and the debug drawing of the colliders.
+
+use serde::{Deserialize, Serialize};
+
+use amethyst_assets::{AssetStorage, PrefabData};
+use amethyst_core::{
+    ecs::{
+        Component, DenseVecStorage, Entities, Entity, Join, Read, ReadExpect, ReadStorage, System,
+        Write, WriteStorage,
+    },
+    math::{Isometry3, Point2, Point3, Vector2, Vector3},
+    Transform,
+};
+use amethyst_derive::PrefabData;
+use amethyst_error::Error;
+use amethyst_renderer::{DebugLines, Rgba, Sprite, SpriteRender, SpriteShape, SpriteSheet};
+
+use crate::{
+    body::{BodyStatus, RigidBody},
+    collider::{default_density, default_friction, Collider, ColliderShape},
+    world::PhysicsWorld,
+};
+
+/// Gives an entity with a `SpriteRender` the `Collider` of its sprite, which is the
+/// `collision` shape of the sprite in the sprite sheet, or its bounds without one. The collider
+/// follows the animations of the sprite.
+///
+/// The sprites are in the units of the transforms, so the `PhysicsBundle` of the sprites should
+/// set its units per meter, see `PhysicsBundle::with_units_per_meter`. The scale of the
+/// transforms and the `Flipped` component are ignored.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, PrefabData)]
+#[prefab(Component)]
+#[serde(default)]
+pub struct SpriteCollider {
+    /// The density of the collider.
+    pub density: f32,
+    /// The friction coefficient of the collider.
+    pub friction: f32,
+    /// How much of the speed is kept when bouncing, between 0 and 1.
+    pub restitution: f32,
+    /// Whether the collider only detects the overlaps.
+    pub sensor: bool,
+}
+
+impl Default for SpriteCollider {
+    fn default() -> Self {
+        SpriteCollider {
+            density: default_density(),
+            friction: default_friction(),
+            restitution: 0.0,
+            sensor: false,
+        }
+    }
+}
+
+impl Component for SpriteCollider {
+    type Storage = DenseVecStorage<Self>;
+}
+
+impl SpriteCollider {
+    /// The collider of a sprite, with the material of this component.
+    pub fn collider(&self, sprite: &Sprite) -> Collider {
+        let (shape, offset) = sprite_shape(sprite);
+        Collider {
+            shape,
+            offset,
+            density: self.density,
+            friction: self.friction,
+            restitution: self.restitution,
+            sensor: self.sensor,
+        }
+    }
+}
+
+// The shape of a sprite and its offset from the entity. The sprites are drawn centered on the
+// entity, shifted by their offsets.
+fn sprite_shape(sprite: &Sprite) -> (ColliderShape, Isometry3<f32>) {
+    // From the pixels of the sprite to the entity.
+    let local = |x: f32, y: f32| {
+        Vector2::new(
+            x - sprite.width / 2.0 - sprite.offsets[0],
+            sprite.height / 2.0 - y - sprite.offsets[1],
+        )
+    };
+    let rectangle = |center: Vector2<f32>, width: f32, height: f32| {
+        (
+            ColliderShape::Cuboid {
+                half_extents: Vector3::new(width, height, width.max(height)) / 2.0,
+            },
+            Isometry3::new(Vector3::new(center.x, center.y, 0.0), Vector3::zeros()),
+        )
+    };
+    match &sprite.collision {
+        None => rectangle(
+            local(sprite.width / 2.0, sprite.height / 2.0),
+            sprite.width,
+            sprite.height,
+        ),
+        Some(SpriteShape::Rectangle {
+            x,
+            y,
+            width,
+            height,
+        }) => rectangle(local(x + width / 2.0, y + height / 2.0), *width, *height),
+        Some(SpriteShape::Circle { x, y, radius }) => {
+            let center = local(*x, *y);
+            (
+                ColliderShape::Ball { radius: *radius },
+                Isometry3::new(Vector3::new(center.x, center.y, 0.0), Vector3::zeros()),
+            )
+        }
+        Some(SpriteShape::Polygon(points)) => (
+            ColliderShape::Polygon {
+                points: points
+                    .iter()
+                    .map(|point| Point2::from(local(point[0], point[1])))
+                    .collect(),
+            },
+            Isometry3::identity(),
+        ),
+    }
+}
+
+/// Gives the entities with a `SpriteCollider` the `Collider` of their sprite. Added by the
+/// `PhysicsBundle` with the `renderer` feature.
+#[derive(Debug, Default)]
+pub struct SpriteColliderSystem;
+
+impl<'a> System<'a> for SpriteColliderSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, AssetStorage<SpriteSheet>>,
+        ReadStorage<'a, SpriteRender>,
+        ReadStorage<'a, SpriteCollider>,
+        WriteStorage<'a, Collider>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, sheets, renders, sprite_colliders, mut colliders): Self::SystemData,
+    ) {
+        for (entity, render, sprite_collider) in (&*entities, &renders, &sprite_colliders).join() {
+            let sprite = match sheets
+                .get(&render.sprite_sheet)
+                .and_then(|sheet| sheet.sprites.get(render.sprite_number))
+            {
+                Some(sprite) => sprite,
+                None => continue,
+            };
+            let collider = sprite_collider.collider(sprite);
+            if colliders.get(entity) != Some(&collider) {
+                colliders
+                    .insert(entity, collider)
+                    .expect("Unreachable: The entity is alive");
+            }
+        }
+    }
+}
+
+// How many segments the circles are drawn with.
+const CIRCLE_SEGMENTS: usize = 24;
+
+/// Draws the outlines of the colliders through the `DebugLines` resource, for the
+/// `DrawDebugLines` pass. It should run on every frame, not in the fixed update:
+///
+/// ~~~ignore
+/// let game_data = GameDataBuilder::default()
+///     .with(PhysicsDebugSystem::default(), "physics_debug", &[]);
+/// ~~~
+///
+/// The static colliders are gray, the kinematic ones blue, the dynamic ones green and the sensors
+/// yellow. In 2D the outlines are drawn in the XY plane.
+#[derive(Debug, Default)]
+pub struct PhysicsDebugSystem;
+
+impl<'a> System<'a> for PhysicsDebugSystem {
+    type SystemData = (
+        ReadExpect<'a, PhysicsWorld>,
+        ReadStorage<'a, Collider>,
+        ReadStorage<'a, RigidBody>,
+        ReadStorage<'a, Transform>,
+        Write<'a, DebugLines>,
+    );
+
+    fn run(&mut self, (physics, colliders, bodies, transforms, mut lines): Self::SystemData) {
+        for (collider, body, transform) in (&colliders, bodies.maybe(), &transforms).join() {
+            let color = match (collider.sensor, body.map(|body| body.status)) {
+                (true, _) => Rgba(1.0, 1.0, 0.0, 1.0),
+                (false, Some(BodyStatus::Dynamic)) => Rgba(0.0, 1.0, 0.0, 1.0),
+                (false, Some(BodyStatus::Kinematic)) => Rgba(0.0, 0.5, 1.0, 1.0),
+                (false, _) => Rgba(0.5, 0.5, 0.5, 1.0),
+            };
+            let position = transform.isometry() * collider.offset;
+            for (start, end) in outline(&collider.shape, physics.is_2d()) {
+                lines.draw_line(position * start, position * end, color);
+            }
+        }
+    }
+}
+
+// The segments outlining a shape, only in the XY plane in 2D.
+fn outline(shape: &ColliderShape, planar: bool) -> Vec<(Point3<f32>, Point3<f32>)> {
+    let circle = |center: Point3<f32>, radius: f32, axes: (Vector3<f32>, Vector3<f32>)| {
+        (0..CIRCLE_SEGMENTS)
+            .map(|segment| {
+                let point = |segment: usize| {
+                    let angle =
+                        segment as f32 / CIRCLE_SEGMENTS as f32 * std::f32::consts::PI * 2.0;
+                    center + (axes.0 * angle.cos() + axes.1 * angle.sin()) * radius
+                };
+                (point(segment), point(segment + 1))
+            })
+            .collect::<Vec<_>>()
+    };
+    let (x, y, z) = (Vector3::x(), Vector3::y(), Vector3::z());
+    match shape {
+        ColliderShape::Ball { radius } => {
+            let mut segments = circle(Point3::origin(), *radius, (x, y));
+            if !planar {
+                segments.extend(circle(Point3::origin(), *radius, (x, z)));
+                segments.extend(circle(Point3::origin(), *radius, (y, z)));
+            }
+            segments
+        }
+        ColliderShape::Cuboid { half_extents } => {
+            let corner = |x: f32, y: f32, z: f32| {
+                Point3::new(x * half_extents.x, y * half_extents.y, z * half_extents.z)
+            };
+            let depths: &[f32] = if planar { &[0.0] } else { &[-1.0, 1.0] };
+            let mut segments = Vec::new();
+            for &z in depths {
+                segments.push((corner(-1.0, -1.0, z), corner(1.0, -1.0, z)));
+                segments.push((corner(1.0, -1.0, z), corner(1.0, 1.0, z)));
+                segments.push((corner(1.0, 1.0, z), corner(-1.0, 1.0, z)));
+                segments.push((corner(-1.0, 1.0, z), corner(-1.0, -1.0, z)));
+            }
+            if !planar {
+                for &(x, y) in &[(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)] {
+                    segments.push((corner(x, y, -1.0), corner(x, y, 1.0)));
+                }
+            }
+            segments
+        }
+        ColliderShape::Capsule {
+            half_height,
+            radius,
+        } => {
+            let top = Point3::new(0.0, *half_height, 0.0);
+            let bottom = Point3::new(0.0, -*half_height, 0.0);
+            let mut segments = circle(top, *radius, (x, y));
+            segments.extend(circle(bottom, *radius, (x, y)));
+            segments.push((top + x * *radius, bottom + x * *radius));
+            segments.push((top - x * *radius, bottom - x * *radius));
+            if !planar {
+                segments.push((top + z * *radius, bottom + z * *radius));
+                segments.push((top - z * *radius, bottom - z * *radius));
+            }
+            segments
+        }
+        ColliderShape::Polygon { points } => (0..points.len())
+            .map(|index| {
+                let point = |index: usize| {
+                    let point = points[index % points.len()];
+                    Point3::new(point.x, point.y, 0.0)
+                };
+                (point(index), point(index + 1))
+            })
+            .collect(),
+        ColliderShape::TriMesh { vertices, indices } => indices
+            .iter()
+            .flat_map(|&[a, b, c]| vec![(a, b), (b, c), (c, a)])
+            .filter_map(|(a, b)| Some((*vertices.get(a)?, *vertices.get(b)?)))
+            .collect(),
+        // The box around the points, finding the edges of the hull being slow.
+        ColliderShape::ConvexHull { points } if !points.is_empty() => {
+            let (min, max) = points
+                .iter()
+                .fold((points[0], points[0]), |(min, max), point| {
+                    (min.inf(point), max.sup(point))
+                });
+            let center = Point3::from((min.coords + max.coords) / 2.0);
+            outline(
+                &ColliderShape::Cuboid {
+                    half_extents: (max - min) / 2.0,
+                },
+                planar,
+            )
+            .into_iter()
+            .map(|(start, end)| (start + center.coords, end + center.coords))
+            .collect()
+        }
+        // The planes are infinite.
+        ColliderShape::Plane { .. } | ColliderShape::ConvexHull { .. } => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sprite_shapes_are_centered_on_the_entity() {
+        let mut sprite = Sprite::from_pixel_values(64, 64, 32, 16, 0, 0, [0.0, 0.0]);
+        let (shape, offset) = sprite_shape(&sprite);
+        assert_eq!(
+            shape,
+            ColliderShape::Cuboid {
+                half_extents: Vector3::new(16.0, 8.0, 16.0),
+            }
+        );
+        assert_eq!(offset, Isometry3::identity());
+
+        sprite.collision = Some(SpriteShape::Circle {
+            x: 8.0,
+            y: 4.0,
+            radius: 4.0,
+        });
+        let (shape, offset) = sprite_shape(&sprite);
+        assert_eq!(shape, ColliderShape::Ball { radius: 4.0 });
+        assert_eq!(offset.translation.vector, Vector3::new(-8.0, 4.0, 0.0));
+    }
+}
@@ -0,0 +1,395 @@
+//! The systems stepping the physics world.
+
+use amethyst_core::{
+    changes::ComponentChanges,
+    ecs::{
+        Entities, Entity, Join, Read, ReadStorage, Resources, System, SystemData, Write,
+        WriteStorage,
+    },
+    math::Vector3,
+    shrev::EventChannel,
+    Time, Transform,
+};
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use crate::{
+    body::RigidBody,
+    collider::Collider,
+    events::{CollisionEvent, JointEvent, TriggerEvent},
+    joint::Joint,
+    world::PhysicsWorld,
+};
+
+/// Steps the `PhysicsWorld` by the fixed time step, after creating, updating and removing the
+/// simulated bodies and colliders to match the components.
+///
+/// The transforms moved by the game teleport their bodies, and the simulated bodies then move
+/// their transforms. The contacts and the sensors of the step are then sent as `CollisionEvent`s
+/// and `TriggerEvent`s, and the broken joints as `JointEvent`s. It should run in the fixed
+/// update, see `PhysicsBundle`.
+pub struct PhysicsStepSystem {
+    gravity: Option<Vector3<f32>>,
+    units_per_meter: f32,
+    planar: bool,
+    changes: Option<ComponentChanges<Transform>>,
+    collisions: Vec<CollisionEvent>,
+    triggers: Vec<TriggerEvent>,
+    broken_joints: Vec<JointEvent>,
+}
+
+impl PhysicsStepSystem {
+    /// Creates the system, which sets the gravity of the `PhysicsWorld` if one is given, and its
+    /// units.
+    pub fn new(gravity: Option<Vector3<f32>>) -> Self {
+        PhysicsStepSystem {
+            gravity,
+            units_per_meter: 1.0,
+            planar: false,
+            changes: None,
+            collisions: Vec::new(),
+            triggers: Vec::new(),
+            broken_joints: Vec::new(),
+        }
+    }
+
+    /// Sets how many units of the transforms are in a meter of the simulation.
+    pub fn with_units_per_meter(mut self, units_per_meter: f32) -> Self {
+        self.units_per_meter = units_per_meter;
+        self
+    }
+
+    /// Restricts the simulation to the XY plane.
+    pub fn in_2d(mut self) -> Self {
+        self.planar = true;
+        self
+    }
+}
+
+impl Default for PhysicsStepSystem {
+    fn default() -> Self {
+        PhysicsStepSystem::new(None)
+    }
+}
+
+impl<'a> System<'a> for PhysicsStepSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, Time>,
+        Write<'a, PhysicsWorld>,
+        WriteStorage<'a, RigidBody>,
+        ReadStorage<'a, Collider>,
+        WriteStorage<'a, Joint>,
+        WriteStorage<'a, Transform>,
+        Write<'a, EventChannel<CollisionEvent>>,
+        Write<'a, EventChannel<TriggerEvent>>,
+        Write<'a, EventChannel<JointEvent>>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            time,
+            mut physics,
+            mut bodies,
+            colliders,
+            mut joints,
+            mut transforms,
+            mut collision_events,
+            mut trigger_events,
+            mut joint_events,
+        ): Self::SystemData,
+    ) {
+        #[cfg(feature = "profiler")]
+        profile_scope!("physics_step_system");
+
+        let changes = self.changes.as_mut().expect("setup was not called");
+        changes.update(&transforms);
+
+        // The entities that lost their components, or whose body changed, are removed, the
+        // latter being created again below.
+        let mut removed = Vec::new();
+        let mut changed_colliders = Vec::new();
+        for (entity, entry) in &physics.entries {
+            let body = bodies.get(*entity);
+            let collider = colliders.get(*entity);
+            let body_changed = match (&entry.body, body) {
+                (Some((_, old)), Some(new)) => old.status != new.status || old.mass != new.mass,
+                (None, None) => false,
+                _ => true,
+            };
+            if !entities.is_alive(*entity)
+                || !transforms.contains(*entity)
+                || (body.is_none() && collider.is_none())
+                || body_changed
+            {
+                removed.push(*entity);
+            } else if entry.collider.as_ref().map(|(_, old)| old) != collider {
+                changed_colliders.push(*entity);
+            }
+        }
+        // The joints whose component was removed or changed are removed too.
+        let removed_joints = physics
+            .joints
+            .iter()
+            .filter(|(entity, entry)| joints.get(**entity) != Some(&entry.desc))
+            .map(|(entity, _)| *entity)
+            .collect::<Vec<_>>();
+        for entity in removed_joints {
+            physics.remove_joint(entity);
+        }
+        for entity in removed {
+            physics.remove(entity);
+        }
+        for entity in changed_colliders {
+            physics.replace_collider(entity, colliders.get(entity));
+        }
+
+        let new = (&*entities, &transforms, bodies.maybe(), colliders.maybe())
+            .join()
+            .filter(|(entity, _, body, collider)| {
+                (body.is_some() || collider.is_some()) && !physics.entries.contains_key(entity)
+            })
+            .map(|(entity, transform, body, collider)| {
+                (
+                    entity,
+                    *transform.isometry(),
+                    body.cloned(),
+                    collider.cloned(),
+                )
+            })
+            .collect::<Vec<_>>();
+        for (entity, position, body, collider) in new {
+            physics.insert(entity, position, body.as_ref(), collider.as_ref());
+        }
+
+        for (entity, transform, _) in (&*entities, &transforms, changes.changed()).join() {
+            physics.set_position(entity, *transform.isometry());
+        }
+        for (entity, body) in (&*entities, &bodies).join() {
+            physics.set_velocity(entity, body);
+        }
+        for (entity, joint) in (&*entities, &joints).join() {
+            physics.insert_joint(entity, joint);
+        }
+
+        physics.step(time.fixed_seconds());
+        physics.events(&mut self.collisions, &mut self.triggers);
+        collision_events.iter_write(self.collisions.drain(..));
+        trigger_events.iter_write(self.triggers.drain(..));
+        physics.break_joints(&mut self.broken_joints);
+        for event in &self.broken_joints {
+            let JointEvent::Broken { joint, .. } = *event;
+            joints.remove(joint);
+        }
+        joint_events.iter_write(self.broken_joints.drain(..));
+
+        let moving = physics.entries.keys().cloned().collect::<Vec<Entity>>();
+        for entity in moving {
+            if let Some((position, velocity)) = physics.moved(entity) {
+                if let Some(transform) = transforms.get_mut(entity) {
+                    *transform.isometry_mut() = position;
+                }
+                if let Some(body) = bodies.get_mut(entity) {
+                    body.linear_velocity = velocity.linear;
+                    body.angular_velocity = velocity.angular;
+                }
+            }
+        }
+        // The transforms moved by the simulation aren't moved by the game.
+        changes.update(&transforms);
+    }
+
+    fn setup(&mut self, res: &mut Resources) {
+        let mut physics = res.entry::<PhysicsWorld>().or_insert_with(Default::default);
+        if let Some(gravity) = self.gravity {
+            physics.set_gravity(gravity);
+        }
+        physics.configure(self.units_per_meter, self.planar);
+        drop(physics);
+        Self::SystemData::setup(res);
+        self.changes = Some(ComponentChanges::new(res));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::{
+        ecs::{Builder, RunNow, World},
+        math::Point3,
+    };
+
+    use super::*;
+    use crate::{collider::ColliderShape, joint::JointKind};
+
+    #[test]
+    fn dynamic_bodies_fall() {
+        let mut world = World::new();
+        let mut system = PhysicsStepSystem::default();
+        System::setup(&mut system, &mut world.res);
+        world.add_resource(Time::default());
+
+        let ball = world
+            .create_entity()
+            .with(Transform::default())
+            .with(RigidBody::dynamic())
+            .with(Collider::new(ColliderShape::Ball { radius: 0.5 }))
+            .build();
+        let ground = world
+            .create_entity()
+            .with(Transform::default())
+            .with(Collider::new(ColliderShape::Ball { radius: 0.5 }))
+            .build();
+        world
+            .write_storage::<Transform>()
+            .get_mut(ground)
+            .unwrap()
+            .set_translation_y(-10.0);
+
+        for _ in 0..10 {
+            system.run_now(&world.res);
+            world.maintain();
+        }
+        let transforms = world.read_storage::<Transform>();
+        assert!(transforms.get(ball).unwrap().translation().y < 0.0);
+        assert!(
+            world
+                .read_storage::<RigidBody>()
+                .get(ball)
+                .unwrap()
+                .linear_velocity
+                .y
+                < 0.0
+        );
+        assert_eq!(transforms.get(ground).unwrap().translation().y, -10.0);
+    }
+
+    #[test]
+    fn collisions_are_sent() {
+        let mut world = World::new();
+        let mut system = PhysicsStepSystem::default();
+        System::setup(&mut system, &mut world.res);
+        world.add_resource(Time::default());
+        let mut reader = world
+            .write_resource::<EventChannel<CollisionEvent>>()
+            .register_reader();
+
+        let ball = world
+            .create_entity()
+            .with(Transform::default())
+            .with(RigidBody::dynamic())
+            .with(Collider::new(ColliderShape::Ball { radius: 0.5 }))
+            .build();
+        let mut position = Transform::default();
+        position.set_translation_y(-1.05);
+        let ground = world
+            .create_entity()
+            .with(position)
+            .with(Collider::new(ColliderShape::Ball { radius: 0.5 }))
+            .build();
+
+        for _ in 0..60 {
+            system.run_now(&world.res);
+        }
+        let events = world.read_resource::<EventChannel<CollisionEvent>>();
+        match events.read(&mut reader).next() {
+            Some(CollisionEvent::Started {
+                entities,
+                contacts,
+                impulse,
+            }) => {
+                assert!(*entities == (ball, ground) || *entities == (ground, ball));
+                assert!(!contacts.is_empty());
+                assert!(*impulse > 0.0);
+            }
+            event => panic!("Unexpected event {:?}", event),
+        }
+    }
+
+    #[test]
+    fn bodies_stay_in_the_plane_in_2d() {
+        let mut world = World::new();
+        let mut system = PhysicsStepSystem::default()
+            .with_units_per_meter(100.0)
+            .in_2d();
+        System::setup(&mut system, &mut world.res);
+        world.add_resource(Time::default());
+
+        let mut transform = Transform::default();
+        transform.set_translation_z(5.0);
+        let ball = world
+            .create_entity()
+            .with(transform)
+            .with(RigidBody::dynamic().with_linear_velocity(Vector3::new(0.0, 0.0, 100.0)))
+            .with(Collider::new(ColliderShape::Ball { radius: 16.0 }))
+            .build();
+
+        for _ in 0..60 {
+            system.run_now(&world.res);
+        }
+        let transforms = world.read_storage::<Transform>();
+        let translation = transforms.get(ball).unwrap().translation();
+        // A second of falling, in centimeters.
+        assert!(translation.y < -400.0 && translation.y > -600.0);
+        assert_eq!(translation.z, 5.0);
+    }
+
+    #[test]
+    fn joints_hold_until_they_break() {
+        let mut world = World::new();
+        let mut system = PhysicsStepSystem::default();
+        System::setup(&mut system, &mut world.res);
+        world.add_resource(Time::default());
+        let mut reader = world
+            .write_resource::<EventChannel<JointEvent>>()
+            .register_reader();
+
+        let hang = |world: &mut World, x: f32, kind: JointKind| {
+            let mut position = Transform::default();
+            position.set_translation_x(x);
+            let anchor = world
+                .create_entity()
+                .with(position.clone())
+                .with(Collider::new(ColliderShape::Ball { radius: 0.1 }).sensor())
+                .build();
+            position.set_translation_y(-1.0);
+            world
+                .create_entity()
+                .with(position)
+                .with(RigidBody::dynamic())
+                .with(Collider::new(ColliderShape::Ball { radius: 0.2 }))
+                .with(
+                    Joint::new(kind, anchor)
+                        .with_anchors(Point3::new(0.0, 1.0, 0.0), Point3::origin())
+                        .breaking_at(0.5),
+                )
+                .build()
+        };
+        let hanging = hang(&mut world, 0.0, JointKind::Ball);
+        let falling = hang(
+            &mut world,
+            5.0,
+            JointKind::Spring {
+                rest_length: 0.0,
+                stiffness: 0.01,
+                damping: 0.0,
+            },
+        );
+
+        for _ in 0..60 {
+            system.run_now(&world.res);
+        }
+        let transforms = world.read_storage::<Transform>();
+        assert!((transforms.get(hanging).unwrap().translation().y + 1.0).abs() < 0.1);
+        assert!(transforms.get(falling).unwrap().translation().y < -1.5);
+        let joints = world.read_storage::<Joint>();
+        assert!(joints.contains(hanging));
+        assert!(!joints.contains(falling));
+        let events = world.read_resource::<EventChannel<JointEvent>>();
+        match events.read(&mut reader).next() {
+            Some(JointEvent::Broken { joint, .. }) => assert_eq!(*joint, falling),
+            event => panic!("Unexpected event {:?}", event),
+        }
+    }
+}
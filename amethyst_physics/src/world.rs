@@ -0,0 +1,740 @@
+//! The resource holding the simulation.
+
+use std::collections::HashMap;
+
+use amethyst_core::{
+    ecs::Entity,
+    math::{Isometry3, Point3, Unit, UnitQuaternion, Vector3},
+};
+use ncollide3d::{
+    events::{ContactEvent, ProximityEvent},
+    query::Proximity,
+    shape::ShapeHandle,
+};
+use nphysics3d::{
+    algebra::Velocity3,
+    joint::{BallConstraint, ConstraintHandle, PrismaticConstraint, RevoluteConstraint},
+    material::{BasicMaterial, MaterialHandle},
+    object::{BodyHandle, BodyPartHandle, BodyStatus, ColliderDesc, ColliderHandle, RigidBodyDesc},
+    world::World,
+};
+
+use crate::{
+    body::{BodyStatus as Status, RigidBody},
+    collider::{Collider, ColliderShape},
+    events::{CollisionEvent, ContactPoint, JointEvent, TriggerEvent},
+    joint::{Joint, JointKind},
+};
+
+// What the physics world created for an entity, with the components it was created from.
+pub(crate) struct PhysicsEntry {
+    pub body: Option<(BodyHandle, RigidBody)>,
+    pub collider: Option<(ColliderHandle, Collider)>,
+    // The position of the entity, from which a collider without a body is offset.
+    pub position: Isometry3<f32>,
+}
+
+// What the physics world created for the joint of an entity, with the component it was created
+// from.
+pub(crate) struct JointEntry {
+    pub desc: Joint,
+    // The bodies of the entities, the world standing for an entity without one.
+    bodies: [Option<BodyHandle>; 2],
+    // The anchors, in the spaces of the bodies.
+    anchors: [Point3<f32>; 2],
+    // The axis of a hinge or of a prismatic joint, in the space of the first body.
+    axis: Vector3<f32>,
+    // The constraint of the solver, the springs being forces instead.
+    constraint: Option<ConstraintHandle>,
+}
+
+// Converts between the units of the transforms and the ones of the simulation.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Units {
+    // The units of the transforms in a meter of the simulation.
+    pub scale: f32,
+    // Whether the simulation is restricted to the XY plane.
+    pub planar: bool,
+}
+
+impl Units {
+    pub fn to_physics(self, position: &Isometry3<f32>) -> Isometry3<f32> {
+        let mut position = *position;
+        position.translation.vector /= self.scale;
+        if self.planar {
+            position.translation.vector.z = 0.0;
+        }
+        position
+    }
+
+    // The position of the transforms, at the depth `z` in 2D.
+    pub fn from_physics(self, position: &Isometry3<f32>, z: f32) -> Isometry3<f32> {
+        let mut position = *position;
+        position.translation.vector *= self.scale;
+        if self.planar {
+            position.translation.vector.z = z;
+        }
+        position
+    }
+
+    pub fn point_to_physics(self, point: Point3<f32>) -> Point3<f32> {
+        let mut point = point / self.scale;
+        if self.planar {
+            point.z = 0.0;
+        }
+        point
+    }
+
+    pub fn point_from_physics(self, point: Point3<f32>, z: f32) -> Point3<f32> {
+        let mut point = point * self.scale;
+        if self.planar {
+            point.z = z;
+        }
+        point
+    }
+
+    pub fn vector_to_physics(self, vector: Vector3<f32>) -> Vector3<f32> {
+        let mut vector = vector / self.scale;
+        if self.planar {
+            vector.z = 0.0;
+        }
+        vector
+    }
+
+    // The offset of a collider from its body.
+    pub fn offset_to_physics(self, offset: &Isometry3<f32>) -> Isometry3<f32> {
+        let mut offset = *offset;
+        offset.translation.vector /= self.scale;
+        offset
+    }
+
+    pub fn shape(self, shape: &ColliderShape) -> ShapeHandle<f32> {
+        shape.handle(1.0 / self.scale)
+    }
+}
+
+/// The `nphysics` world simulating the `RigidBody` and `Collider` entities, stepped by the
+/// `PhysicsStepSystem`. The gravity is 9.81 downwards along Y by default.
+///
+/// The positions, the sizes and the velocities of the components are in the units of the
+/// transforms, which are converted to the meters of the simulation, see
+/// `PhysicsBundle::with_units_per_meter`. The gravity is in meters. In 2D, the bodies only move
+/// in the XY plane and only rotate around Z, see `PhysicsBundle::in_2d`.
+///
+/// The `nphysics` world is available, for its features this crate doesn't wrap. It is in meters.
+/// The bodies and colliders made from the components should only be changed through the
+/// components.
+pub struct PhysicsWorld {
+    world: World<f32>,
+    pub(crate) entries: HashMap<Entity, PhysicsEntry>,
+    pub(crate) joints: HashMap<Entity, JointEntry>,
+    colliders: HashMap<ColliderHandle, Entity>,
+    pub(crate) units: Units,
+}
+
+impl Default for PhysicsWorld {
+    fn default() -> Self {
+        PhysicsWorld::new(Vector3::new(0.0, -9.81, 0.0))
+    }
+}
+
+impl PhysicsWorld {
+    /// Creates an empty world with the given gravity.
+    pub fn new(gravity: Vector3<f32>) -> Self {
+        let mut world = World::new();
+        world.set_gravity(gravity);
+        PhysicsWorld {
+            world,
+            entries: HashMap::new(),
+            joints: HashMap::new(),
+            colliders: HashMap::new(),
+            units: Units {
+                scale: 1.0,
+                planar: false,
+            },
+        }
+    }
+
+    /// The gravity.
+    pub fn gravity(&self) -> &Vector3<f32> {
+        self.world.gravity()
+    }
+
+    /// Changes the gravity.
+    pub fn set_gravity(&mut self, gravity: Vector3<f32>) {
+        self.world.set_gravity(gravity);
+    }
+
+    /// How many units of the transforms are in a meter of the simulation.
+    pub fn units_per_meter(&self) -> f32 {
+        self.units.scale
+    }
+
+    /// Whether the simulation is in 2D, in the XY plane.
+    pub fn is_2d(&self) -> bool {
+        self.units.planar
+    }
+
+    // Changes the units and the dimensions, before the components are simulated.
+    pub(crate) fn configure(&mut self, units_per_meter: f32, planar: bool) {
+        self.units = Units {
+            scale: units_per_meter,
+            planar,
+        };
+    }
+
+    /// The `nphysics` world.
+    pub fn world(&self) -> &World<f32> {
+        &self.world
+    }
+
+    /// The `nphysics` world, mutably.
+    pub fn world_mut(&mut self) -> &mut World<f32> {
+        &mut self.world
+    }
+
+    /// The entity of a collider.
+    pub fn entity(&self, collider: ColliderHandle) -> Option<Entity> {
+        self.colliders.get(&collider).cloned()
+    }
+
+    /// The body of an entity.
+    pub fn body(&self, entity: Entity) -> Option<BodyHandle> {
+        self.entries
+            .get(&entity)
+            .and_then(|entry| entry.body.as_ref())
+            .map(|(handle, _)| *handle)
+    }
+
+    /// The collider of an entity.
+    pub fn collider(&self, entity: Entity) -> Option<ColliderHandle> {
+        self.entries
+            .get(&entity)
+            .and_then(|entry| entry.collider.as_ref())
+            .map(|(handle, _)| *handle)
+    }
+
+    // Creates the body and the collider of an entity at `position`.
+    pub(crate) fn insert(
+        &mut self,
+        entity: Entity,
+        position: Isometry3<f32>,
+        body: Option<&RigidBody>,
+        collider: Option<&Collider>,
+    ) {
+        let units = self.units;
+        let body = body.map(|desc| {
+            let handle = RigidBodyDesc::new()
+                .position(units.to_physics(&position))
+                .velocity(Velocity3::new(
+                    units.vector_to_physics(desc.linear_velocity),
+                    desc.angular_velocity,
+                ))
+                .status(desc.status.to_nphysics())
+                .mass(desc.mass)
+                .build(&mut self.world)
+                .handle();
+            (handle, desc.clone())
+        });
+        let mut entry = PhysicsEntry {
+            body,
+            collider: None,
+            position,
+        };
+        if let Some(collider) = collider {
+            self.insert_collider(entity, &mut entry, collider);
+        }
+        self.entries.insert(entity, entry);
+    }
+
+    fn insert_collider(&mut self, entity: Entity, entry: &mut PhysicsEntry, desc: &Collider) {
+        let units = self.units;
+        let collider = ColliderDesc::new(units.shape(&desc.shape))
+            .density(desc.density)
+            .material(MaterialHandle::new(BasicMaterial::new(
+                desc.restitution,
+                desc.friction,
+            )))
+            .sensor(desc.sensor);
+        let handle = match entry.body {
+            Some((body, _)) => {
+                let part = self
+                    .world
+                    .rigid_body(body)
+                    .expect("Unreachable: The body was created with the entry")
+                    .part_handle();
+                collider
+                    .position(units.offset_to_physics(&desc.offset))
+                    .build_with_parent(part, &mut self.world)
+                    .expect("Unreachable: The parent is a rigid body of the world")
+                    .handle()
+            }
+            // Without a body, the collider is attached to the ground.
+            None => collider
+                .position(units.to_physics(&(entry.position * desc.offset)))
+                .build(&mut self.world)
+                .handle(),
+        };
+        self.colliders.insert(handle, entity);
+        entry.collider = Some((handle, desc.clone()));
+    }
+
+    // Replaces the collider of an entity, after its component changed.
+    pub(crate) fn replace_collider(&mut self, entity: Entity, collider: Option<&Collider>) {
+        let mut entry = match self.entries.remove(&entity) {
+            Some(entry) => entry,
+            None => return,
+        };
+        if let Some((handle, _)) = entry.collider.take() {
+            self.world.remove_colliders(&[handle]);
+            self.colliders.remove(&handle);
+        }
+        if let Some(collider) = collider {
+            self.insert_collider(entity, &mut entry, collider);
+        }
+        self.entries.insert(entity, entry);
+    }
+
+    // Removes the body and the collider of an entity, with the joints connected to it.
+    pub(crate) fn remove(&mut self, entity: Entity) {
+        self.remove_joints_of(entity);
+        let entry = match self.entries.remove(&entity) {
+            Some(entry) => entry,
+            None => return,
+        };
+        if let Some((handle, _)) = entry.collider {
+            self.colliders.remove(&handle);
+            // The colliders of a body are removed with it.
+            if entry.body.is_none() {
+                self.world.remove_colliders(&[handle]);
+            }
+        }
+        if let Some((handle, _)) = entry.body {
+            self.world.remove_bodies(&[handle]);
+        }
+    }
+
+    // Moves the body or the collider of an entity, after the game moved its transform.
+    pub(crate) fn set_position(&mut self, entity: Entity, position: Isometry3<f32>) {
+        let units = self.units;
+        let entry = match self.entries.get_mut(&entity) {
+            Some(entry) => entry,
+            None => return,
+        };
+        entry.position = position;
+        match (&entry.body, &entry.collider) {
+            (Some((handle, _)), _) => {
+                if let Some(body) = self.world.rigid_body_mut(*handle) {
+                    body.set_position(units.to_physics(&position));
+                    body.activate();
+                }
+            }
+            (None, Some((handle, desc))) => {
+                self.world
+                    .collider_world_mut()
+                    .set_position(*handle, units.to_physics(&(position * desc.offset)));
+                // The joints anchored to the entity are created again at its new position.
+                self.remove_joints_of(entity);
+            }
+            (None, None) => {}
+        }
+    }
+
+    // Gives the body of an entity the velocities of its component, if the game changed them.
+    pub(crate) fn set_velocity(&mut self, entity: Entity, desc: &RigidBody) {
+        let units = self.units;
+        let entry = match self.entries.get_mut(&entity) {
+            Some(entry) => entry,
+            None => return,
+        };
+        if let Some((handle, ref mut old)) = entry.body {
+            if old.linear_velocity == desc.linear_velocity
+                && old.angular_velocity == desc.angular_velocity
+            {
+                return;
+            }
+            old.linear_velocity = desc.linear_velocity;
+            old.angular_velocity = desc.angular_velocity;
+            if let Some(body) = self.world.rigid_body_mut(handle) {
+                body.set_velocity(Velocity3::new(
+                    units.vector_to_physics(desc.linear_velocity),
+                    desc.angular_velocity,
+                ));
+                body.activate();
+            }
+        }
+    }
+
+    // The position and the velocities of the moving body of an entity after a step, which
+    // become the ones of the entity.
+    pub(crate) fn moved(&mut self, entity: Entity) -> Option<(Isometry3<f32>, Velocity3<f32>)> {
+        let units = self.units;
+        let entry = self.entries.get_mut(&entity)?;
+        let (handle, ref mut desc) = entry.body.as_mut()?;
+        let body = self.world.rigid_body(*handle)?;
+        if body.status() == BodyStatus::Static {
+            return None;
+        }
+        let velocity = Velocity3::new(
+            body.velocity().linear * units.scale,
+            body.velocity().angular,
+        );
+        desc.linear_velocity = velocity.linear;
+        desc.angular_velocity = velocity.angular;
+        entry.position = units.from_physics(body.position(), entry.position.translation.vector.z);
+        Some((entry.position, velocity))
+    }
+
+    // The linear velocity and the inverse of the mass of the body of an entity, before the step.
+    fn motion(&self, entity: Entity) -> (Vector3<f32>, f32) {
+        match self
+            .entries
+            .get(&entity)
+            .and_then(|entry| entry.body.as_ref())
+        {
+            Some((handle, desc)) if desc.status == Status::Dynamic => {
+                let inv_mass = self
+                    .world
+                    .rigid_body(*handle)
+                    .map_or(0.0, |body| body.inv_augmented_mass().linear);
+                (desc.linear_velocity, inv_mass)
+            }
+            Some((_, desc)) => (desc.linear_velocity, 0.0),
+            None => (Vector3::zeros(), 0.0),
+        }
+    }
+
+    // Creates the joint of an entity, once both entities are simulated and one has a body.
+    pub(crate) fn insert_joint(&mut self, entity: Entity, desc: &Joint) {
+        if self.joints.contains_key(&entity) {
+            return;
+        }
+        let ((body1, frame1), (body2, frame2)) = match (self.frame(entity), self.frame(desc.other))
+        {
+            (Some(frame1), Some(frame2)) => (frame1, frame2),
+            _ => return,
+        };
+        if body1.is_none() && body2.is_none() {
+            return;
+        }
+        let scale = self.units.scale;
+        let anchors = [
+            frame1 * (desc.anchor / scale),
+            frame2 * (desc.other_anchor / scale),
+        ];
+        let axis = match desc.kind {
+            JointKind::Hinge { axis } | JointKind::Prismatic { axis } => {
+                Unit::try_new(frame1.rotation * axis, 1.0e-6).unwrap_or_else(Vector3::y_axis)
+            }
+            _ => Vector3::y_axis(),
+        };
+        let (part1, part2) = (self.part(body1), self.part(body2));
+        let constraint = match desc.kind {
+            JointKind::Ball => Some(
+                self.world
+                    .add_constraint(BallConstraint::new(part1, part2, anchors[0], anchors[1])),
+            ),
+            JointKind::Hinge { .. } => {
+                let world_axis = self.pose(body1).rotation * axis.into_inner();
+                let other_axis =
+                    Unit::new_normalize(self.pose(body2).rotation.inverse() * world_axis);
+                Some(self.world.add_constraint(RevoluteConstraint::new(
+                    part1, part2, anchors[0], axis, anchors[1], other_axis,
+                )))
+            }
+            JointKind::Prismatic { .. } => Some(self.world.add_constraint(
+                PrismaticConstraint::new(part1, part2, anchors[0], axis, anchors[1]),
+            )),
+            JointKind::Spring { .. } => None,
+        };
+        self.joints.insert(
+            entity,
+            JointEntry {
+                desc: desc.clone(),
+                bodies: [body1, body2],
+                anchors,
+                axis: axis.into_inner(),
+                constraint,
+            },
+        );
+    }
+
+    // Removes the joint of an entity.
+    pub(crate) fn remove_joint(&mut self, entity: Entity) {
+        if let Some(JointEntry {
+            constraint: Some(handle),
+            ..
+        }) = self.joints.remove(&entity)
+        {
+            self.world.remove_constraint(handle);
+        }
+    }
+
+    // Removes the joints from and to an entity.
+    fn remove_joints_of(&mut self, entity: Entity) {
+        let joints = self
+            .joints
+            .iter()
+            .filter(|(joint, entry)| **joint == entity || entry.desc.other == entity)
+            .map(|(joint, _)| *joint)
+            .collect::<Vec<_>>();
+        for joint in joints {
+            self.remove_joint(joint);
+        }
+    }
+
+    // The body of an entity and the position of the entity in the space of the body, the world
+    // standing for an entity without a body.
+    fn frame(&self, entity: Entity) -> Option<(Option<BodyHandle>, Isometry3<f32>)> {
+        let entry = self.entries.get(&entity)?;
+        Some(match entry.body {
+            Some((handle, _)) => (Some(handle), Isometry3::identity()),
+            None => (None, self.units.to_physics(&entry.position)),
+        })
+    }
+
+    fn part(&self, body: Option<BodyHandle>) -> BodyPartHandle {
+        body.and_then(|handle| self.world.rigid_body(handle))
+            .map_or_else(BodyPartHandle::ground, |body| body.part_handle())
+    }
+
+    fn pose(&self, body: Option<BodyHandle>) -> Isometry3<f32> {
+        body.and_then(|handle| self.world.rigid_body(handle))
+            .map_or_else(Isometry3::identity, |body| *body.position())
+    }
+
+    // How far the anchors of a joint are from where the joint holds them.
+    fn stretch(&self, entry: &JointEntry) -> f32 {
+        let pose = self.pose(entry.bodies[0]);
+        let delta = self.pose(entry.bodies[1]) * entry.anchors[1] - pose * entry.anchors[0];
+        match entry.desc.kind {
+            JointKind::Ball | JointKind::Hinge { .. } => delta.norm(),
+            JointKind::Prismatic { .. } => {
+                let axis = pose.rotation * entry.axis;
+                (delta - axis * delta.dot(&axis)).norm()
+            }
+            JointKind::Spring { rest_length, .. } => {
+                (delta.norm() - rest_length / self.units.scale).max(0.0)
+            }
+        }
+    }
+
+    // Removes the joints stretched past their break distance by the last step.
+    pub(crate) fn break_joints(&mut self, events: &mut Vec<JointEvent>) {
+        let scale = self.units.scale;
+        let broken = self
+            .joints
+            .iter()
+            .filter(|(_, entry)| {
+                entry
+                    .desc
+                    .break_distance
+                    .map_or(false, |distance| self.stretch(entry) > distance / scale)
+            })
+            .map(|(joint, entry)| (*joint, entry.desc.other))
+            .collect::<Vec<_>>();
+        for (joint, other) in broken {
+            self.remove_joint(joint);
+            events.push(JointEvent::Broken { joint, other });
+        }
+    }
+
+    // Changes the velocities of the bodies by the impulses of the springs over a step.
+    fn apply_springs(&mut self, timestep: f32) {
+        let scale = self.units.scale;
+        let springs = self
+            .joints
+            .values()
+            .filter_map(|entry| match entry.desc.kind {
+                JointKind::Spring {
+                    rest_length,
+                    stiffness,
+                    damping,
+                } => Some((
+                    entry.bodies,
+                    entry.anchors,
+                    rest_length / scale,
+                    stiffness,
+                    damping,
+                )),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        for (bodies, anchors, rest_length, stiffness, damping) in springs {
+            let point1 = self.pose(bodies[0]) * anchors[0];
+            let point2 = self.pose(bodies[1]) * anchors[1];
+            let delta = point2 - point1;
+            let direction = match delta.try_normalize(1.0e-6) {
+                Some(direction) => direction,
+                None => continue,
+            };
+            let speed = (self.point_velocity(bodies[1], &point2)
+                - self.point_velocity(bodies[0], &point1))
+            .dot(&direction);
+            let force = stiffness * (delta.norm() - rest_length) + damping * speed;
+            self.push(bodies[0], &point1, direction * force * timestep);
+            self.push(bodies[1], &point2, -direction * force * timestep);
+        }
+    }
+
+    fn point_velocity(&self, body: Option<BodyHandle>, point: &Point3<f32>) -> Vector3<f32> {
+        body.and_then(|handle| self.world.rigid_body(handle))
+            .map_or_else(Vector3::zeros, |body| {
+                let arm = point.coords - body.position().translation.vector;
+                body.velocity().linear + body.velocity().angular.cross(&arm)
+            })
+    }
+
+    // Applies an impulse at a point of a dynamic body.
+    fn push(&mut self, body: Option<BodyHandle>, point: &Point3<f32>, impulse: Vector3<f32>) {
+        let body = match body.and_then(|handle| self.world.rigid_body_mut(handle)) {
+            Some(body) => body,
+            None => return,
+        };
+        if body.status() != BodyStatus::Dynamic {
+            return;
+        }
+        let inv_mass = *body.inv_augmented_mass();
+        let arm = point.coords - body.position().translation.vector;
+        let mut velocity = *body.velocity();
+        velocity.linear += impulse * inv_mass.linear;
+        velocity.angular += inv_mass.angular * arm.cross(&impulse);
+        body.set_velocity(velocity);
+        body.activate();
+    }
+
+    pub(crate) fn is_sensor(&self, entity: Entity) -> bool {
+        self.entries
+            .get(&entity)
+            .and_then(|entry| entry.collider.as_ref())
+            .map_or(false, |(_, desc)| desc.sensor)
+    }
+
+    // The events of the last step, which must be collected before the velocities of the
+    // entries are updated.
+    pub(crate) fn events(
+        &self,
+        collisions: &mut Vec<CollisionEvent>,
+        triggers: &mut Vec<TriggerEvent>,
+    ) {
+        for event in self.world.contact_events() {
+            let (collider1, collider2, started) = match *event {
+                ContactEvent::Started(collider1, collider2) => (collider1, collider2, true),
+                ContactEvent::Stopped(collider1, collider2) => (collider1, collider2, false),
+            };
+            let entities = match (self.entity(collider1), self.entity(collider2)) {
+                (Some(entity1), Some(entity2)) => (entity1, entity2),
+                _ => continue,
+            };
+            if !started {
+                collisions.push(CollisionEvent::Stopped { entities });
+                continue;
+            }
+
+            // In 2D, the contacts are at the depth of the first entity.
+            let z = self
+                .entries
+                .get(&entities.0)
+                .map_or(0.0, |entry| entry.position.translation.vector.z);
+            let mut manifolds = Vec::new();
+            if let Some((_, _, algorithm)) = self
+                .world
+                .collider_world()
+                .contact_pair(collider1, collider2, true)
+            {
+                algorithm.contacts(&mut manifolds);
+            }
+            let contacts = manifolds
+                .iter()
+                .flat_map(|manifold| manifold.contacts())
+                .map(|tracked| ContactPoint {
+                    point: self.units.point_from_physics(tracked.contact.world1, z),
+                    normal: tracked.contact.normal.into_inner(),
+                    depth: tracked.contact.depth * self.units.scale,
+                })
+                .collect::<Vec<_>>();
+
+            let (velocity1, inv_mass1) = self.motion(entities.0);
+            let (velocity2, inv_mass2) = self.motion(entities.1);
+            let impulse = contacts
+                .iter()
+                .max_by(|a, b| {
+                    a.depth
+                        .partial_cmp(&b.depth)
+                        .unwrap_or(std::cmp::Ordering::Equal)
+                })
+                .filter(|_| inv_mass1 + inv_mass2 > 0.0)
+                .map_or(0.0, |deepest| {
+                    let speed = (velocity1 - velocity2).dot(&deepest.normal).max(0.0);
+                    speed / (inv_mass1 + inv_mass2)
+                });
+            collisions.push(CollisionEvent::Started {
+                entities,
+                contacts,
+                impulse,
+            });
+        }
+
+        for event in self.world.proximity_events() {
+            let ProximityEvent {
+                collider1,
+                collider2,
+                prev_status,
+                new_status,
+            } = *event;
+            let (mut trigger, mut other) = match (self.entity(collider1), self.entity(collider2)) {
+                (Some(entity1), Some(entity2)) => (entity1, entity2),
+                _ => continue,
+            };
+            if !self.is_sensor(trigger) {
+                std::mem::swap(&mut trigger, &mut other);
+            }
+            let was_inside = prev_status == Proximity::Intersecting;
+            let is_inside = new_status == Proximity::Intersecting;
+            if is_inside && !was_inside {
+                triggers.push(TriggerEvent::Entered { trigger, other });
+            } else if was_inside && !is_inside {
+                triggers.push(TriggerEvent::Exited { trigger, other });
+            }
+        }
+    }
+
+    pub(crate) fn step(&mut self, timestep: f32) {
+        self.apply_springs(timestep);
+        if self.units.planar {
+            for handle in self.dynamic_bodies() {
+                if let Some(body) = self.world.rigid_body_mut(handle) {
+                    let mut velocity = *body.velocity();
+                    velocity.linear.z = 0.0;
+                    velocity.angular.x = 0.0;
+                    velocity.angular.y = 0.0;
+                    body.set_velocity(velocity);
+                }
+            }
+        }
+        self.world.set_timestep(timestep);
+        self.world.step();
+        if self.units.planar {
+            // The solver drifts out of the plane, the bodies are put back in it.
+            for handle in self.dynamic_bodies() {
+                if let Some(body) = self.world.rigid_body_mut(handle) {
+                    let mut position = *body.position();
+                    position.translation.vector.z = 0.0;
+                    let (_, _, angle) = position.rotation.euler_angles();
+                    position.rotation = UnitQuaternion::from_euler_angles(0.0, 0.0, angle);
+                    body.set_position(position);
+                }
+            }
+        }
+    }
+
+    fn dynamic_bodies(&self) -> Vec<BodyHandle> {
+        self.entries
+            .values()
+            .filter_map(|entry| match &entry.body {
+                Some((handle, desc)) if desc.status == Status::Dynamic => Some(*handle),
+                _ => None,
+            })
+            .collect()
+    }
+}
@@ -3,7 +3,7 @@
 use amethyst_assets::PrefabData;
 use amethyst_core::{
     ecs::prelude::{Component, Entity, HashMapStorage, Write, WriteStorage},
-    math::{Matrix4, Orthographic3, Perspective3, Point2, Point3},
+    math::{Matrix4, Orthographic3, Perspective3, Point2, Point3, Unit, Vector3},
     GlobalTransform,
 };
 use amethyst_error::Error;
@@ -100,6 +100,49 @@ impl Camera {
 
         Point3::from_homogeneous(vector).expect("Vector is not homogeneous")
     }
+
+    /// The ray in world space going through the pixel at `screen_position`, from the top left
+    /// corner of the screen, as its origin on the near plane and its direction.
+    ///
+    /// Returns `None` when the projection or the camera transform is not invertible.
+    pub fn screen_ray(
+        &self,
+        screen_position: Point2<f32>,
+        camera_transform: &GlobalTransform,
+        screen_dimensions: &ScreenDimensions,
+    ) -> Option<(Point3<f32>, Unit<Vector3<f32>>)> {
+        let screen_x = 2.0 * screen_position.x / screen_dimensions.width() - 1.0;
+        let screen_y = 1.0 - 2.0 * screen_position.y / screen_dimensions.height();
+        let unproject = camera_transform.0 * self.proj.try_inverse()?;
+        let near = Point3::from_homogeneous(
+            unproject * Point3::new(screen_x, screen_y, -1.0).to_homogeneous(),
+        )?;
+        let far = Point3::from_homogeneous(
+            unproject * Point3::new(screen_x, screen_y, 1.0).to_homogeneous(),
+        )?;
+        Unit::try_new(far - near, 1.0e-6).map(|direction| (near, direction))
+    }
+
+    /// Transforms position from world space to screen space, in pixels from the top left corner
+    /// of the screen like the positions taken by `position_from_screen`.
+    ///
+    /// Returns `None` when the position is behind the camera.
+    pub fn position_to_screen(
+        &self,
+        world_position: Point3<f32>,
+        camera_transform: &GlobalTransform,
+        screen_dimensions: &ScreenDimensions,
+    ) -> Option<Point2<f32>> {
+        let view = camera_transform.0.try_inverse()?;
+        let clip = self.proj * view * world_position.to_homogeneous();
+        if clip.w <= 0.0 {
+            return None;
+        }
+        Some(Point2::new(
+            (clip.x / clip.w + 1.0) * screen_dimensions.width() / 2.0,
+            (1.0 - clip.y / clip.w) * screen_dimensions.height() / 2.0,
+        ))
+    }
 }
 
 impl Component for Camera {
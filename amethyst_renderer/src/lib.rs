@@ -58,7 +58,7 @@ pub use crate::{
         PolyStages, Stage, StageBuilder, Target, TargetBuilder, Targets,
     },
     renderer::Renderer,
-    resources::{AmbientColor, ScreenDimensions, WindowMessages},
+    resources::{AmbientColor, RenderStats, ScreenDimensions, WindowMessages},
     screen_space::{ScreenSpace, ScreenSpaceSettings},
     shape::{InternalShape, Shape, ShapePrefab, ShapeUpload},
     skinning::{
@@ -67,8 +67,8 @@ pub use crate::{
     },
     sprite::{
         Flipped, Sprite, SpriteGrid, SpriteList, SpritePosition, SpriteRender, SpriteRenderPrefab,
-        SpriteScenePrefab, SpriteSheet, SpriteSheetFormat, SpriteSheetHandle, SpriteSheetPrefab,
-        Sprites, TextureCoordinates,
+        SpriteScenePrefab, SpriteShape, SpriteSheet, SpriteSheetFormat, SpriteSheetHandle,
+        SpriteSheetPrefab, Sprites, TextureCoordinates,
     },
     sprite_visibility::{SpriteVisibility, SpriteVisibilitySortingSystem},
     system::RenderSystem,
@@ -4,6 +4,8 @@
 
 pub use self::pso::{Data, Init, Meta};
 
+use std::sync::atomic::{AtomicUsize, Ordering};
+
 use amethyst_error::{Error, ResultExt};
 
 use derivative::Derivative;
@@ -145,10 +147,19 @@ impl Effect {
     }
 
     pub fn draw(&mut self, slice: &Slice, enc: &mut Encoder) {
+        DRAW_CALLS.fetch_add(1, Ordering::Relaxed);
         enc.draw(&slice, &self.pso, &self.data);
     }
 }
 
+/// The draws of all the effects since the last frame, taken by the `RenderSystem`.
+static DRAW_CALLS: AtomicUsize = AtomicUsize::new(0);
+
+/// Takes the number of draws since the last call.
+pub(crate) fn take_draw_calls() -> usize {
+    DRAW_CALLS.swap(0, Ordering::Relaxed)
+}
+
 pub struct NewEffect<'f> {
     pub factory: &'f mut Factory,
     out: &'f Target,
@@ -23,6 +23,8 @@ pub use self::{
     target::{ColorBuffer, DepthBuffer, Target, TargetBuilder, Targets},
 };
 
+pub(crate) use self::effect::take_draw_calls;
+
 pub mod pass;
 
 mod effect;
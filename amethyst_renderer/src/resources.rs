@@ -36,6 +36,13 @@ impl<'a> PrefabData<'a> for AmbientColor {
     }
 }
 
+/// The statistics of the last frame drawn by the `RenderSystem`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RenderStats {
+    /// The draw calls of all the passes.
+    pub draw_calls: usize,
+}
+
 /// This specs resource permits sending commands to the
 /// renderer internal window.
 #[derive(Default)]
@@ -69,6 +69,39 @@ pub struct Sprite {
     pub offsets: [f32; 2],
     /// Texture coordinates of the sprite
     pub tex_coords: TextureCoordinates,
+    /// The shape of the sprite for the collisions, if it isn't the bounds of the sprite
+    #[serde(default)]
+    pub collision: Option<SpriteShape>,
+}
+
+/// The shape of a sprite for the collisions, used by the 2D physics.
+///
+/// Coordinates are in pixels from the top left of the sprite. X increases to the right, Y
+/// increases downwards.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum SpriteShape {
+    /// A rectangle.
+    Rectangle {
+        /// Pixel X coordinate of the left side.
+        x: f32,
+        /// Pixel Y coordinate of the top.
+        y: f32,
+        /// Width of the rectangle.
+        width: f32,
+        /// Height of the rectangle.
+        height: f32,
+    },
+    /// A circle.
+    Circle {
+        /// Pixel X coordinate of the center.
+        x: f32,
+        /// Pixel Y coordinate of the center.
+        y: f32,
+        /// Radius of the circle.
+        radius: f32,
+    },
+    /// A convex polygon, given by its corners.
+    Polygon(Vec<[f32; 2]>),
 }
 
 /// Texture coordinates of the sprite
@@ -149,6 +182,7 @@ impl Sprite {
             height: sprite_h as f32,
             offsets,
             tex_coords,
+            collision: None,
         }
     }
 }
@@ -166,6 +200,7 @@ impl From<((f32, f32), [f32; 2], [f32; 4])> for Sprite {
             height,
             offsets,
             tex_coords: TextureCoordinates::from(tex_coords),
+            collision: None,
         }
     }
 }
@@ -331,6 +366,7 @@ mod test {
                     bottom: 0.75,
                     top: 1.0,
                 },
+                collision: None,
             },
             ((10., 40.), [5., 20.], [0.0, 0.5, 0.75, 1.0]).into()
         );
@@ -349,6 +385,7 @@ mod test {
                     bottom: 0.75,
                     top: 1.0,
                 },
+                collision: None,
             },
             ((10., 40.), [0.0, 0.5, 0.75, 1.0]).into()
         );
@@ -8,7 +8,9 @@ use amethyst_core::{
 };
 use amethyst_error::Error;
 
-use crate::{Sprite, SpriteRender, SpriteSheet, SpriteSheetHandle, TextureFormat, TexturePrefab};
+use crate::{
+    Sprite, SpriteRender, SpriteShape, SpriteSheet, SpriteSheetHandle, TextureFormat, TexturePrefab,
+};
 
 /// Represents one sprite in `SpriteList`.
 /// Positions originate in the top-left corner (bitmap image convention).
@@ -24,6 +26,9 @@ pub struct SpritePosition {
     pub height: u32,
     /// Number of pixels to shift the sprite to the left and down relative to the entity holding it
     pub offsets: Option<[f32; 2]>,
+    /// The shape of the sprite for the collisions, if it isn't the bounds of the sprite
+    #[serde(default)]
+    pub collision: Option<SpriteShape>,
 }
 
 /// `SpriteList` controls how a sprite list is generated when using `Sprites::List` in a
@@ -325,8 +330,9 @@ impl SpriteList {
     pub fn build_sprites(&self) -> Vec<Sprite> {
         self.sprites
             .iter()
-            .map(|pos| {
-                Sprite::from_pixel_values(
+            .map(|pos| Sprite {
+                collision: pos.collision.clone(),
+                ..Sprite::from_pixel_values(
                     self.texture_width,
                     self.texture_height,
                     pos.width,
@@ -492,6 +498,7 @@ mod tests {
                         width: 1,
                         height: 1,
                         offsets: None,
+                        collision: None,
                     },
                     SpritePosition {
                         x: 1,
@@ -499,6 +506,7 @@ mod tests {
                         width: 1,
                         height: 1,
                         offsets: None,
+                        collision: None,
                     },
                     SpritePosition {
                         x: 2,
@@ -506,6 +514,7 @@ mod tests {
                         width: 1,
                         height: 1,
                         offsets: None,
+                        collision: None,
                     },
                 ],
             })],
@@ -24,9 +24,10 @@ use crate::{
     formats::{create_mesh_asset, create_texture_asset},
     mesh::Mesh,
     mtl::{Material, MaterialDefaults},
+    pipe::take_draw_calls,
     pipe::{PipelineBuild, PipelineData, PolyPipeline},
     renderer::Renderer,
-    resources::{ScreenDimensions, WindowMessages},
+    resources::{RenderStats, ScreenDimensions, WindowMessages},
     tex::Texture,
 };
 
@@ -149,8 +150,9 @@ where
         screen_dimensions.update_hidpi_factor(hidpi);
     }
 
-    fn render(&mut self, (mut event_handler, data): RenderData<'_, P>) {
+    fn render(&mut self, (mut event_handler, mut stats, data): RenderData<'_, P>) {
         self.renderer.draw(&mut self.pipe, data);
+        stats.draw_calls = take_draw_calls();
         let events = &mut self.event_vec;
         self.renderer.events_mut().poll_events(|new_event| {
             compress_events(events, new_event);
@@ -171,6 +173,7 @@ type WindowData<'a> = (Write<'a, WindowMessages>, WriteExpect<'a, ScreenDimensio
 
 type RenderData<'a, P> = (
     Write<'a, EventChannel<Event>>,
+    Write<'a, RenderStats>,
     <P as PipelineData<'a>>::Data,
 );
 
@@ -0,0 +1,71 @@
+//! The bundle of the scripts.
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use amethyst_assets::Processor;
+use amethyst_core::{
+    bundle::{BundleBuilder, SystemBundle},
+    ecs::prelude::Component,
+};
+use amethyst_error::Error;
+
+#[cfg(feature = "wasm")]
+use crate::wasm::{Mod, ModLimits, ModSystem};
+use crate::{components::ScriptComponents, script::Script, system::ScriptSystem};
+
+/// Adds the `Processor` of the `Script`s, and the `ScriptSystem` as a thread local system, with
+/// the components the scripts may read and write. With the `wasm` feature, the bundle may add
+/// the `ModSystem` too, the mods reading and writing the same components.
+#[derive(Default)]
+pub struct ScriptingBundle {
+    components: ScriptComponents,
+    #[cfg(feature = "wasm")]
+    mods: Option<ModLimits>,
+}
+
+impl ScriptingBundle {
+    /// Creates the bundle, without components for the scripts.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Lets the scripts read and write the components of a type, under a name.
+    pub fn with_component<T, S>(mut self, name: S) -> Self
+    where
+        T: Component + Serialize + DeserializeOwned,
+        T::Storage: Default,
+        S: Into<String>,
+    {
+        self.components.register::<T, S>(name);
+        self
+    }
+
+    /// Adds the `Processor` of the `Mod`s and the `ModSystem`, running the WASM mods.
+    #[cfg(feature = "wasm")]
+    pub fn with_mods(self) -> Self {
+        self.with_mod_limits(ModLimits::default())
+    }
+
+    /// Adds the `Processor` of the `Mod`s and the `ModSystem`, with the limits of the mods.
+    #[cfg(feature = "wasm")]
+    pub fn with_mod_limits(mut self, limits: ModLimits) -> Self {
+        self.mods = Some(limits);
+        self
+    }
+}
+
+impl<'a, 'b> SystemBundle<'a, 'b> for ScriptingBundle {
+    fn build(self, builder: &mut BundleBuilder<'a, 'b>) -> Result<(), Error> {
+        #[cfg(feature = "wasm")]
+        {
+            if let Some(limits) = self.mods {
+                builder.add(Processor::<Mod>::new(), "mod_processor", &[]);
+                builder
+                    .add_thread_local(ModSystem::new(self.components.clone()).with_limits(limits));
+            }
+        }
+        builder.add(Processor::<Script>::new(), "script_processor", &[]);
+        builder.add_thread_local(ScriptSystem::new(self.components));
+        Ok(())
+    }
+}
@@ -0,0 +1,86 @@
+//! The components the scripts read and write.
+
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+use amethyst_core::{
+    ecs::prelude::{Component, Entity, Resources},
+    reflect::ComponentRegistry,
+};
+use amethyst_error::Error;
+
+/// The components the scripts read and write, by their names in the scripts, in a
+/// `ComponentRegistry`.
+///
+/// The components are converted to and from the Lua tables through their serialization, as the
+/// prefabs are, a script setting a whole component at once. The clones of the registry share
+/// its components, for the Lua scripts and the WASM mods.
+#[derive(Clone, Default)]
+pub struct ScriptComponents {
+    registry: ComponentRegistry,
+}
+
+impl ScriptComponents {
+    /// Creates an empty registry.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Registers the components of a type under a name.
+    pub fn register<T, S>(&mut self, name: S)
+    where
+        T: Component + Serialize + DeserializeOwned,
+        T::Storage: Default,
+        S: Into<String>,
+    {
+        self.registry.register_with_fields::<T, S>(name, Vec::new());
+    }
+
+    /// Whether components are registered under a name.
+    pub fn contains(&self, name: &str) -> bool {
+        self.registry.contains(name)
+    }
+
+    /// The names of the components.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.registry.components().map(|info| info.name.as_str())
+    }
+
+    /// The registry of the components.
+    pub fn registry(&self) -> &ComponentRegistry {
+        &self.registry
+    }
+
+    pub(crate) fn setup(&self, res: &mut Resources) {
+        self.registry.setup(res);
+    }
+
+    pub(crate) fn get(
+        &self,
+        res: &Resources,
+        name: &str,
+        entity: Entity,
+    ) -> Result<Option<Value>, Error> {
+        self.registry.get(res, name, entity)
+    }
+
+    pub(crate) fn set(
+        &self,
+        res: &Resources,
+        name: &str,
+        entity: Entity,
+        value: Value,
+    ) -> Result<(), Error> {
+        self.registry.set(res, name, entity, value)
+    }
+
+    pub(crate) fn remove(&self, res: &Resources, name: &str, entity: Entity) -> Result<(), Error> {
+        self.registry.remove(res, name, entity)
+    }
+}
+
+impl From<ComponentRegistry> for ScriptComponents {
+    fn from(registry: ComponentRegistry) -> Self {
+        ScriptComponents { registry }
+    }
+}
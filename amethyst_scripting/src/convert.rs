@@ -0,0 +1,115 @@
+//! The conversions of the values between Lua and the components.
+
+use rlua::{Context, Value as LuaValue};
+use serde_json::{Map, Number, Value};
+
+// The deepest nesting of the tables converted, so that the tables referencing themselves fail
+// instead of overflowing the stack.
+const MAX_DEPTH: usize = 64;
+
+// The Lua value of a JSON value, the arrays and the objects being tables.
+pub(crate) fn to_lua<'lua>(ctx: Context<'lua>, value: &Value) -> rlua::Result<LuaValue<'lua>> {
+    Ok(match value {
+        Value::Null => LuaValue::Nil,
+        Value::Bool(value) => LuaValue::Boolean(*value),
+        Value::Number(number) => match number.as_i64() {
+            Some(integer) => LuaValue::Integer(integer),
+            None => LuaValue::Number(number.as_f64().unwrap_or(0.0)),
+        },
+        Value::String(string) => LuaValue::String(ctx.create_string(string)?),
+        Value::Array(values) => {
+            let table = ctx.create_table()?;
+            for (i, value) in values.iter().enumerate() {
+                table.set(i + 1, to_lua(ctx, value)?)?;
+            }
+            LuaValue::Table(table)
+        }
+        Value::Object(values) => {
+            let table = ctx.create_table()?;
+            for (key, value) in values {
+                table.set(key.as_str(), to_lua(ctx, value)?)?;
+            }
+            LuaValue::Table(table)
+        }
+    })
+}
+
+// The JSON value of a Lua value, the tables being arrays when their keys are `1` to their length,
+// and objects otherwise. The empty tables are empty arrays.
+pub(crate) fn from_lua(value: LuaValue<'_>) -> rlua::Result<Value> {
+    from_lua_at(value, 0)
+}
+
+fn from_lua_at(value: LuaValue<'_>, depth: usize) -> rlua::Result<Value> {
+    Ok(match value {
+        LuaValue::Nil => Value::Null,
+        LuaValue::Boolean(value) => Value::Bool(value),
+        LuaValue::Integer(integer) => Value::from(integer),
+        LuaValue::Number(number) => Number::from_f64(number).map_or(Value::Null, Value::Number),
+        LuaValue::String(string) => Value::String(string.to_str()?.to_owned()),
+        LuaValue::Table(table) => {
+            if depth == MAX_DEPTH {
+                return Err(rlua::Error::RuntimeError(format!(
+                    "The tables are nested more than {} deep, or reference themselves",
+                    MAX_DEPTH
+                )));
+            }
+            let length = table.raw_len();
+            let pairs = table
+                .pairs::<LuaValue<'_>, LuaValue<'_>>()
+                .collect::<rlua::Result<Vec<_>>>()?;
+            if pairs.len() as i64 == length {
+                let mut values = Vec::with_capacity(pairs.len());
+                for i in 1..=length {
+                    values.push(from_lua_at(table.raw_get(i)?, depth + 1)?);
+                }
+                Value::Array(values)
+            } else {
+                let mut values = Map::new();
+                for (key, value) in pairs {
+                    let key = match key {
+                        LuaValue::String(key) => key.to_str()?.to_owned(),
+                        LuaValue::Integer(key) => key.to_string(),
+                        _ => {
+                            return Err(rlua::Error::RuntimeError(
+                                "The keys of the tables must be strings or integers".to_string(),
+                            ));
+                        }
+                    };
+                    values.insert(key, from_lua_at(value, depth + 1)?);
+                }
+                Value::Object(values)
+            }
+        }
+        _ => {
+            return Err(rlua::Error::RuntimeError(
+                "Only nil, booleans, numbers, strings and tables convert to components".to_string(),
+            ));
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use rlua::Lua;
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn tables_convert_unless_they_reference_themselves() {
+        Lua::new().context(|ctx| {
+            let table = ctx
+                .load("return { 1, 2, { name = 'a' } }")
+                .eval::<LuaValue<'_>>()
+                .unwrap();
+            assert_eq!(from_lua(table).unwrap(), json!([1, 2, { "name": "a" }]));
+
+            let table = ctx
+                .load("local t = {} t.self = t return t")
+                .eval::<LuaValue<'_>>()
+                .unwrap();
+            assert!(from_lua(table).is_err());
+        });
+    }
+}
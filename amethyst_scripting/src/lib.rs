@@ -0,0 +1,30 @@
+//! Lua scripting for Amethyst, the scripts reaching into the world.
+//!
+//! The `Script`s are assets, loaded from `.lua` files with the `LuaFormat` and reloaded with the
+//! hot reloading of the assets. The `ScriptSystem` runs the script of each entity with a
+//! `ScriptHandle`, which spawns entities, reads and writes the `ScriptComponents`, subscribes to
+//! the `ScriptEvent`s and calls back on every frame, so that the games can be changed without
+//! recompiling them.
+//!
+//! With the `wasm` feature, the `ModSystem` runs the `Mod`s, WASM modules loaded with the
+//! `WasmFormat`, in a sandbox where they only reach the entities and the `ScriptComponents`,
+//! for the content made by the players.
+
+#![warn(missing_docs, rust_2018_idioms, rust_2018_compatibility)]
+
+#[cfg(feature = "wasm")]
+pub use self::wasm::{Mod, ModHandle, ModLimits, ModSystem, WasmFormat};
+pub use self::{
+    bundle::ScriptingBundle,
+    components::ScriptComponents,
+    script::{LuaFormat, Script, ScriptHandle},
+    system::{ScriptEvent, ScriptSystem},
+};
+
+mod bundle;
+mod components;
+mod convert;
+mod script;
+mod system;
+#[cfg(feature = "wasm")]
+mod wasm;
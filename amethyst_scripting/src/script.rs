@@ -0,0 +1,54 @@
+//! The scripts, loaded as assets.
+
+use amethyst_assets::{Asset, Handle, ProcessingState, SimpleFormat};
+use amethyst_core::ecs::prelude::VecStorage;
+use amethyst_error::{format_err, Error, ResultExt};
+
+/// A Lua script, run by the `ScriptSystem` for each entity with a handle to it.
+///
+/// The scripts loaded from a `Loader` are reloaded when their files change under a
+/// `HotReloadStrategy`, the scripts of the entities being run again.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Script {
+    /// The Lua source of the script.
+    pub source: String,
+}
+
+impl Script {
+    /// Creates a script from its source.
+    pub fn new<S: Into<String>>(source: S) -> Self {
+        Script {
+            source: source.into(),
+        }
+    }
+}
+
+/// A handle to a `Script` asset.
+pub type ScriptHandle = Handle<Script>;
+
+impl Asset for Script {
+    const NAME: &'static str = "scripting::Script";
+    type Data = Self;
+    type HandleStorage = VecStorage<ScriptHandle>;
+}
+
+impl Into<Result<ProcessingState<Script>, Error>> for Script {
+    fn into(self) -> Result<ProcessingState<Script>, Error> {
+        Ok(ProcessingState::Loaded(self))
+    }
+}
+
+/// Loads the `.lua` files, as their UTF-8 source.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LuaFormat;
+
+impl SimpleFormat<Script> for LuaFormat {
+    const NAME: &'static str = "Lua";
+    type Options = ();
+
+    fn import(&self, bytes: Vec<u8>, _: ()) -> Result<Script, Error> {
+        let source = String::from_utf8(bytes)
+            .with_context(|_| format_err!("The script is not valid UTF-8"))?;
+        Ok(Script { source })
+    }
+}
@@ -0,0 +1,449 @@
+//! The system running the scripts.
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+};
+
+use log::error;
+use rlua::{Context, Function, Lua, RegistryKey, Table, Value as LuaValue};
+use serde_json::Value;
+
+use amethyst_assets::AssetStorage;
+use amethyst_core::{
+    ecs::prelude::{Entities, Entity, Join, Read, ReadStorage, Resources, RunNow, SystemData},
+    shrev::{EventChannel, ReaderId},
+    timing::Time,
+};
+use amethyst_error::Error;
+
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use crate::{
+    components::ScriptComponents,
+    convert::{from_lua, to_lua},
+    script::{Script, ScriptHandle},
+};
+
+/// An event between the scripts and the game, sent through the `EventChannel<ScriptEvent>`.
+///
+/// The scripts subscribed to the name of an event get its data, with the entity of the script
+/// which sent it, if a script did.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScriptEvent {
+    /// The name the scripts subscribe to.
+    pub name: String,
+    /// The entity of the script which sent the event.
+    pub entity: Option<Entity>,
+    /// The data of the event, a table in the scripts.
+    pub data: Value,
+}
+
+impl ScriptEvent {
+    /// Creates the event of the game, for the scripts.
+    pub fn new<S: Into<String>>(name: S, data: Value) -> Self {
+        ScriptEvent {
+            name: name.into(),
+            entity: None,
+            data,
+        }
+    }
+}
+
+// A script running for an entity, with the callbacks it registered.
+struct Instance {
+    source: String,
+    updates: Vec<RegistryKey>,
+    subscriptions: Vec<(String, RegistryKey)>,
+}
+
+// A callback registered by the script of an entity, on every frame or on the events of a name.
+struct Registration {
+    entity: Entity,
+    event: Option<String>,
+    callback: RegistryKey,
+}
+
+/// Runs the `Script`s of the entities with a `ScriptHandle`, in a Lua state shared by all of
+/// them. It must be added as a thread local system, as the scripts reach into the whole world:
+///
+/// ~~~ignore
+/// let game_data = GameDataBuilder::default()
+///     .with_bundle(ScriptingBundle::new().with_component::<Health>("Health"))?;
+/// ~~~
+///
+/// Each script runs once its asset is loaded, and again when it is reloaded, in its own
+/// environment where `entity` is its entity. The scripts reach into the world through the
+/// `world` table, which is only valid while the scripts run, during the system:
+///
+/// * `world.spawn()` creates an entity, and `world.delete(entity)` deletes one;
+/// * `world.get(entity, name)`, `world.set(entity, name, table)` and `world.remove(entity, name)`
+///   read and write the `ScriptComponents`;
+/// * `world.on_update(function(dt) ... end)` calls a function on every frame;
+/// * `world.subscribe(name, function(data, sender) ... end)` calls a function on the
+///   `ScriptEvent`s of a name, which `world.emit(name, data)` sends on the next frame.
+///
+/// The entities are integers made of their ids and generations. The errors of the scripts are
+/// logged, the other scripts going on.
+pub struct ScriptSystem {
+    lua: Lua,
+    components: ScriptComponents,
+    instances: HashMap<Entity, Instance>,
+    reader: Option<ReaderId<ScriptEvent>>,
+}
+
+impl ScriptSystem {
+    /// Creates the system of the scripts reading and writing the components.
+    pub fn new(components: ScriptComponents) -> Self {
+        ScriptSystem {
+            lua: Lua::new(),
+            components,
+            instances: HashMap::new(),
+            reader: None,
+        }
+    }
+}
+
+impl<'a> RunNow<'a> for ScriptSystem {
+    fn setup(&mut self, res: &mut Resources) {
+        <(
+            Read<'_, AssetStorage<Script>>,
+            ReadStorage<'_, ScriptHandle>,
+            Read<'_, Time>,
+        )>::setup(res);
+        self.components.setup(res);
+        self.reader = Some(
+            res.entry::<EventChannel<ScriptEvent>>()
+                .or_insert_with(EventChannel::new)
+                .register_reader(),
+        );
+    }
+
+    fn run_now(&mut self, res: &'a Resources) {
+        #[cfg(feature = "profiler")]
+        profile_scope!("script_system");
+
+        let (scripts, handles, time) = <(
+            Read<'_, AssetStorage<Script>>,
+            ReadStorage<'_, ScriptHandle>,
+            Read<'_, Time>,
+        )>::fetch(res);
+        let entities = Entities::fetch(res);
+        let delta = time.delta_seconds();
+
+        // The scripts of the entities which are gone or changed stop, the new ones start.
+        let mut started = Vec::new();
+        let alive = (&*entities, &handles)
+            .join()
+            .filter_map(|(entity, handle)| scripts.get(handle).map(|script| (entity, script)))
+            .collect::<HashMap<_, _>>();
+        self.instances.retain(|entity, instance| {
+            alive
+                .get(entity)
+                .map_or(false, |script| script.source == instance.source)
+        });
+        for (&entity, script) in &alive {
+            if !self.instances.contains_key(&entity) {
+                started.push((entity, script.source.clone()));
+            }
+        }
+
+        let events =
+            {
+                let channel = res.fetch::<EventChannel<ScriptEvent>>();
+                channel
+                    .read(self.reader.as_mut().expect(
+                        "`ScriptSystem::setup` was not called before `ScriptSystem::run_now`",
+                    ))
+                    .cloned()
+                    .collect::<Vec<_>>()
+            };
+
+        let (components, instances) = (&self.components, &mut self.instances);
+        let current = Cell::new(None);
+        let registrations = RefCell::new(Vec::new());
+        let emitted = RefCell::new(Vec::new());
+        // The functions of the `world` table borrow the world for the scope of the scripts.
+        let lookup = |id: i64| {
+            let entity = entities.entity(id as u32);
+            if entities.is_alive(entity) && script_id(entity) == id {
+                Ok(entity)
+            } else {
+                Err(rlua::Error::RuntimeError(format!(
+                    "The entity {} is dead",
+                    id
+                )))
+            }
+        };
+        let external = |error: Error| rlua::Error::RuntimeError(error.to_string());
+        let current_entity = || {
+            current
+                .get()
+                .ok_or_else(|| rlua::Error::RuntimeError("No script is running".into()))
+        };
+        self.lua.context(|ctx| {
+            let result = ctx.scope(|scope| -> rlua::Result<()> {
+                let world = ctx.create_table()?;
+                world.set(
+                    "spawn",
+                    scope.create_function(|_, ()| Ok(script_id(entities.create())))?,
+                )?;
+                world.set(
+                    "delete",
+                    scope.create_function(|_, id: i64| {
+                        let _ = entities.delete(lookup(id)?);
+                        Ok(())
+                    })?,
+                )?;
+                world.set(
+                    "get",
+                    scope.create_function(|ctx, (id, name): (i64, String)| {
+                        match components.get(res, &name, lookup(id)?).map_err(external)? {
+                            Some(value) => to_lua(ctx, &value),
+                            None => Ok(LuaValue::Nil),
+                        }
+                    })?,
+                )?;
+                world.set(
+                    "set",
+                    scope.create_function(
+                        |_, (id, name, value): (i64, String, LuaValue<'_>)| {
+                            components
+                                .set(res, &name, lookup(id)?, from_lua(value)?)
+                                .map_err(external)
+                        },
+                    )?,
+                )?;
+                world.set(
+                    "remove",
+                    scope.create_function(|_, (id, name): (i64, String)| {
+                        components.remove(res, &name, lookup(id)?).map_err(external)
+                    })?,
+                )?;
+                world.set(
+                    "on_update",
+                    scope.create_function(|ctx, callback: Function<'_>| {
+                        registrations.borrow_mut().push(Registration {
+                            entity: current_entity()?,
+                            event: None,
+                            callback: ctx.create_registry_value(callback)?,
+                        });
+                        Ok(())
+                    })?,
+                )?;
+                world.set(
+                    "subscribe",
+                    scope.create_function(|ctx, (name, callback): (String, Function<'_>)| {
+                        registrations.borrow_mut().push(Registration {
+                            entity: current_entity()?,
+                            event: Some(name),
+                            callback: ctx.create_registry_value(callback)?,
+                        });
+                        Ok(())
+                    })?,
+                )?;
+                world.set(
+                    "emit",
+                    scope.create_function(|_, (name, data): (String, LuaValue<'_>)| {
+                        emitted.borrow_mut().push(ScriptEvent {
+                            name,
+                            entity: current.get(),
+                            data: from_lua(data)?,
+                        });
+                        Ok(())
+                    })?,
+                )?;
+                ctx.globals().set("world", world)?;
+
+                // Runs a part of the scripts of an entity, adding the callbacks it registers.
+                let run = |entity: Entity,
+                           instances: &mut HashMap<Entity, Instance>,
+                           f: &mut dyn FnMut() -> rlua::Result<()>| {
+                    current.set(Some(entity));
+                    if let Err(e) = f() {
+                        error!("The script of {:?} failed: {}", entity, e);
+                    }
+                    current.set(None);
+                    for registration in registrations.borrow_mut().drain(..) {
+                        if let Some(instance) = instances.get_mut(&registration.entity) {
+                            match registration.event {
+                                Some(name) => {
+                                    instance.subscriptions.push((name, registration.callback))
+                                }
+                                None => instance.updates.push(registration.callback),
+                            }
+                        }
+                    }
+                };
+
+                for (entity, source) in started {
+                    instances.insert(
+                        entity,
+                        Instance {
+                            source: source.clone(),
+                            updates: Vec::new(),
+                            subscriptions: Vec::new(),
+                        },
+                    );
+                    run(entity, instances, &mut || {
+                        let env = environment(ctx, entity)?;
+                        ctx.load(&source)
+                            .set_name(&format!("script of {:?}", entity))?
+                            .set_environment(env)?
+                            .exec()
+                    });
+                }
+
+                for event in &events {
+                    let data = to_lua(ctx, &event.data)?;
+                    let sender = event.entity.map(script_id);
+                    let subscribed = instances
+                        .iter()
+                        .flat_map(|(&entity, instance)| {
+                            instance
+                                .subscriptions
+                                .iter()
+                                .filter(|(name, _)| *name == event.name)
+                                .map(move |(_, callback)| (entity, callback))
+                        })
+                        .map(|(entity, callback)| {
+                            Ok((entity, ctx.registry_value::<Function<'_>>(callback)?))
+                        })
+                        .collect::<rlua::Result<Vec<_>>>()?;
+                    for (entity, callback) in subscribed {
+                        run(entity, instances, &mut || {
+                            callback.call::<_, ()>((data.clone(), sender))
+                        });
+                    }
+                }
+
+                let updates = instances
+                    .iter()
+                    .flat_map(|(&entity, instance)| {
+                        instance
+                            .updates
+                            .iter()
+                            .map(move |callback| (entity, callback))
+                    })
+                    .map(|(entity, callback)| {
+                        Ok((entity, ctx.registry_value::<Function<'_>>(callback)?))
+                    })
+                    .collect::<rlua::Result<Vec<_>>>()?;
+                for (entity, callback) in updates {
+                    run(entity, instances, &mut || callback.call::<_, ()>(delta));
+                }
+                Ok(())
+            });
+            if let Err(e) = result {
+                error!("The scripts failed: {}", e);
+            }
+            // The callbacks of the scripts which stopped are collected.
+            ctx.expire_registry_values();
+        });
+
+        res.fetch_mut::<EventChannel<ScriptEvent>>()
+            .iter_write(emitted.into_inner());
+    }
+}
+
+// The environment of the script of an entity, its globals falling back on the shared ones.
+fn environment<'lua>(ctx: Context<'lua>, entity: Entity) -> rlua::Result<Table<'lua>> {
+    let environment = ctx.create_table()?;
+    let fallback = ctx.create_table()?;
+    fallback.set("__index", ctx.globals())?;
+    environment.set_metatable(Some(fallback));
+    environment.set("entity", script_id(entity))?;
+    Ok(environment)
+}
+
+// The integer of an entity in the scripts, with its generation in the high bits so that a deleted
+// entity isn't mistaken for the one reusing its id.
+fn script_id(entity: Entity) -> i64 {
+    (i64::from(entity.gen().id()) << 32) | i64::from(entity.id())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use serde::{Deserialize, Serialize};
+    use serde_json::json;
+
+    use amethyst_assets::Loader;
+    use amethyst_core::ecs::{Builder, Component, DenseVecStorage, World};
+    use rayon::ThreadPoolBuilder;
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Health {
+        value: u32,
+    }
+
+    impl Component for Health {
+        type Storage = DenseVecStorage<Self>;
+    }
+
+    const SCRIPT: &str = r#"
+        world.set(entity, "Health", { value = 10 })
+        world.set(world.spawn(), "Health", { value = 1 })
+        world.subscribe("hit", function(data)
+            local health = world.get(entity, "Health")
+            health.value = health.value - data.damage
+            world.set(entity, "Health", health)
+        end)
+        world.on_update(function(dt)
+            world.emit("tick", { dt = dt })
+        end)
+    "#;
+
+    #[test]
+    fn scripts_reach_into_the_world() {
+        let mut world = World::new();
+        let mut components = ScriptComponents::new();
+        components.register::<Health, _>("Health");
+        let mut system = ScriptSystem::new(components);
+        RunNow::setup(&mut system, &mut world.res);
+        world.write_resource::<Time>().set_delta_seconds(0.5);
+        let mut reader = world
+            .write_resource::<EventChannel<ScriptEvent>>()
+            .register_reader();
+
+        let pool = Arc::new(ThreadPoolBuilder::new().build().unwrap());
+        let handle = {
+            let loader = Loader::new(".", pool.clone());
+            let scripts = world.read_resource::<AssetStorage<Script>>();
+            loader.load_from_data(Script::new(SCRIPT), (), &*scripts)
+        };
+        world
+            .write_resource::<AssetStorage<Script>>()
+            .process(Into::into, 0, &pool, None);
+        let scripted = world.create_entity().with(handle).build();
+
+        system.run_now(&world.res);
+        assert_eq!(world.read_storage::<Health>().join().count(), 2);
+        assert_eq!(
+            world.read_storage::<Health>().get(scripted),
+            Some(&Health { value: 10 })
+        );
+
+        world
+            .write_resource::<EventChannel<ScriptEvent>>()
+            .single_write(ScriptEvent::new("hit", json!({ "damage": 3 })));
+        system.run_now(&world.res);
+        assert_eq!(
+            world.read_storage::<Health>().get(scripted),
+            Some(&Health { value: 7 })
+        );
+        let events = world
+            .read_resource::<EventChannel<ScriptEvent>>()
+            .read(&mut reader)
+            .filter(|event| event.name == "tick")
+            .cloned()
+            .collect::<Vec<_>>();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].entity, Some(scripted));
+        assert_eq!(events[0].data, json!({ "dt": 0.5 }));
+    }
+}
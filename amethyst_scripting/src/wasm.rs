@@ -0,0 +1,704 @@
+//! The mods, WASM modules run in a sandbox.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt, mem,
+};
+
+use log::{error, info};
+use parity_wasm::elements::Module as RawModule;
+use pwasm_utils::rules;
+use wasmi::{
+    Error as WasmError, Externals, FuncInstance, FuncRef, HostError, ImportsBuilder, MemoryRef,
+    Module, ModuleImportResolver, ModuleInstance, ModuleRef, RuntimeArgs, RuntimeValue, Signature,
+    Trap, TrapKind,
+};
+
+use amethyst_assets::{Asset, AssetStorage, Handle, ProcessingState, SimpleFormat};
+use amethyst_core::{
+    ecs::prelude::{
+        Entities, Entity, Join, Read, ReadStorage, Resources, RunNow, SystemData, VecStorage,
+    },
+    timing::Time,
+};
+use amethyst_error::{format_err, Error, ResultExt};
+
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use crate::components::ScriptComponents;
+
+/// A WASM module, run by the `ModSystem` for each entity with a handle to it.
+///
+/// The mods loaded from a `Loader` are reloaded when their files change under a
+/// `HotReloadStrategy`, the mods of the entities being started again.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Mod {
+    /// The binary of the module.
+    pub bytes: Vec<u8>,
+}
+
+/// A handle to a `Mod` asset.
+pub type ModHandle = Handle<Mod>;
+
+impl Asset for Mod {
+    const NAME: &'static str = "scripting::Mod";
+    type Data = Self;
+    type HandleStorage = VecStorage<ModHandle>;
+}
+
+impl Into<Result<ProcessingState<Mod>, Error>> for Mod {
+    fn into(self) -> Result<ProcessingState<Mod>, Error> {
+        Ok(ProcessingState::Loaded(self))
+    }
+}
+
+/// Loads the `.wasm` files, which must be valid modules.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct WasmFormat;
+
+impl SimpleFormat<Mod> for WasmFormat {
+    const NAME: &'static str = "WASM";
+    type Options = ();
+
+    fn import(&self, bytes: Vec<u8>, _: ()) -> Result<Mod, Error> {
+        Module::from_buffer(&bytes)
+            .with_context(|_| format_err!("The mod is not a valid WASM module"))?;
+        Ok(Mod { bytes })
+    }
+}
+
+// The functions of the `amethyst` module, the only functions the mods may import.
+const SPAWN: usize = 0;
+const DELETE: usize = 1;
+const GET: usize = 2;
+const SET: usize = 3;
+const REMOVE: usize = 4;
+const LOG: usize = 5;
+// Imported by the instructions counting the cost of the code, injected before it's loaded.
+const GAS: usize = 6;
+
+/// The limits of each mod, which is stopped once it exceeds them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ModLimits {
+    /// The number of instructions a mod may run in a call, to `init` or to `update`. Defaults to
+    /// a million.
+    pub instructions: u64,
+    /// The number of 64 KiB pages of memory a mod may declare as its maximum. The mods must
+    /// declare a maximum, 256 pages by default.
+    pub memory_pages: u32,
+    /// The number of entities a mod may spawn in a call. Defaults to 64.
+    pub spawns: usize,
+}
+
+impl Default for ModLimits {
+    fn default() -> Self {
+        ModLimits {
+            instructions: 1_000_000,
+            memory_pages: 256,
+            spawns: 64,
+        }
+    }
+}
+
+// The trap of a mod exceeding one of its limits.
+#[derive(Debug)]
+struct LimitExceeded(&'static str);
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "The mod exceeded its limit of {}", self.0)
+    }
+}
+
+impl HostError for LimitExceeded {}
+
+struct Imports;
+
+impl ModuleImportResolver for Imports {
+    fn resolve_func(&self, name: &str, signature: &Signature) -> Result<FuncRef, WasmError> {
+        use wasmi::ValueType::I32;
+
+        let (index, expected) = match name {
+            "spawn" => (SPAWN, Signature::new(&[][..], Some(I32))),
+            "delete" => (DELETE, Signature::new(&[I32][..], None)),
+            "get" => (GET, Signature::new(&[I32; 5][..], Some(I32))),
+            "set" => (SET, Signature::new(&[I32; 5][..], Some(I32))),
+            "remove" => (REMOVE, Signature::new(&[I32; 3][..], Some(I32))),
+            "log" => (LOG, Signature::new(&[I32; 2][..], None)),
+            _ => {
+                return Err(WasmError::Instantiation(format!(
+                    "The engine has no function {}",
+                    name
+                )))
+            }
+        };
+        if *signature != expected {
+            return Err(WasmError::Instantiation(format!(
+                "The function {} is imported as {:?} instead of {:?}",
+                name, signature, expected
+            )));
+        }
+        Ok(FuncInstance::alloc_host(expected, index))
+    }
+}
+
+struct GasImport;
+
+impl ModuleImportResolver for GasImport {
+    fn resolve_func(&self, name: &str, signature: &Signature) -> Result<FuncRef, WasmError> {
+        let expected = Signature::new(&[wasmi::ValueType::I32][..], None);
+        if name != "gas" || *signature != expected {
+            return Err(WasmError::Instantiation(format!(
+                "The engine has no function env.{}",
+                name
+            )));
+        }
+        Ok(FuncInstance::alloc_host(expected, GAS))
+    }
+}
+
+// The engine, as the mods call into it, with the memory, the entities and what's left of the
+// limits of the running mod.
+struct Host<'a> {
+    res: &'a Resources,
+    entities: &'a Entities<'a>,
+    components: &'a ScriptComponents,
+    limits: ModLimits,
+    memory: Option<MemoryRef>,
+    owned: HashSet<Entity>,
+    instructions: u64,
+    spawns: usize,
+}
+
+impl<'a> Host<'a> {
+    // Gives the host to a mod for a call.
+    fn enter(&mut self, module: Option<&ModuleRef>, owned: &mut HashSet<Entity>) {
+        self.memory = module.and_then(memory);
+        mem::swap(&mut self.owned, owned);
+        self.instructions = self.limits.instructions;
+        self.spawns = self.limits.spawns;
+    }
+
+    fn leave(&mut self, owned: &mut HashSet<Entity>) {
+        mem::swap(&mut self.owned, owned);
+    }
+
+    // The mods only reach their own entity and the entities they spawned, which are matched
+    // with their generation, so that an id whose entity was deleted doesn't reach the next one.
+    fn entity(&self, id: i32) -> Option<Entity> {
+        let entity = self.entities.entity(id as u32);
+        if self.owned.contains(&entity) && self.entities.is_alive(entity) {
+            Some(entity)
+        } else {
+            None
+        }
+    }
+
+    fn read(&self, pointer: i32, length: i32) -> Result<Vec<u8>, Trap> {
+        self.memory
+            .as_ref()
+            .and_then(|memory| memory.get(pointer as u32, length as usize).ok())
+            .ok_or_else(|| Trap::new(TrapKind::MemoryAccessOutOfBounds))
+    }
+
+    fn read_str(&self, pointer: i32, length: i32) -> Result<String, Trap> {
+        String::from_utf8(self.read(pointer, length)?)
+            .map_err(|_| Trap::new(TrapKind::MemoryAccessOutOfBounds))
+    }
+
+    fn write(&self, pointer: i32, bytes: &[u8]) -> Result<(), Trap> {
+        self.memory
+            .as_ref()
+            .and_then(|memory| memory.set(pointer as u32, bytes).ok())
+            .ok_or_else(|| Trap::new(TrapKind::MemoryAccessOutOfBounds))
+    }
+}
+
+impl<'a> Externals for Host<'a> {
+    fn invoke_index(
+        &mut self,
+        index: usize,
+        args: RuntimeArgs<'_>,
+    ) -> Result<Option<RuntimeValue>, Trap> {
+        let result = match index {
+            SPAWN => {
+                if self.spawns == 0 {
+                    return Err(Trap::new(TrapKind::Host(Box::new(LimitExceeded("spawns")))));
+                }
+                self.spawns -= 1;
+                let entity = self.entities.create();
+                self.owned.insert(entity);
+                Some(entity.id() as i32)
+            }
+            DELETE => {
+                if let Some(entity) = self.entity(args.nth_checked(0)?) {
+                    let _ = self.entities.delete(entity);
+                    self.owned.remove(&entity);
+                }
+                None
+            }
+            GET => {
+                let name = self.read_str(args.nth_checked(1)?, args.nth_checked(2)?)?;
+                let (pointer, capacity) =
+                    (args.nth_checked::<i32>(3)?, args.nth_checked::<i32>(4)?);
+                let value = self.entity(args.nth_checked(0)?).and_then(|entity| {
+                    self.components
+                        .get(self.res, &name, entity)
+                        .map_err(|e| error!("A mod failed to get {}: {}", name, e))
+                        .ok()
+                });
+                match value {
+                    Some(Some(value)) => {
+                        let bytes = value.to_string().into_bytes();
+                        if bytes.len() <= capacity as usize {
+                            self.write(pointer, &bytes)?;
+                        }
+                        Some(bytes.len() as i32)
+                    }
+                    _ => Some(-1),
+                }
+            }
+            SET => {
+                let name = self.read_str(args.nth_checked(1)?, args.nth_checked(2)?)?;
+                let json = self.read(args.nth_checked(3)?, args.nth_checked(4)?)?;
+                let set = self.entity(args.nth_checked(0)?).and_then(|entity| {
+                    serde_json::from_slice::<serde_json::Value>(&json)
+                        .map_err(Error::from)
+                        .and_then(|value| self.components.set(self.res, &name, entity, value))
+                        .map_err(|e| error!("A mod failed to set {}: {}", name, e))
+                        .ok()
+                });
+                Some(if set.is_some() { 0 } else { -1 })
+            }
+            REMOVE => {
+                let name = self.read_str(args.nth_checked(1)?, args.nth_checked(2)?)?;
+                let removed = self.entity(args.nth_checked(0)?).and_then(|entity| {
+                    self.components
+                        .remove(self.res, &name, entity)
+                        .map_err(|e| error!("A mod failed to remove {}: {}", name, e))
+                        .ok()
+                });
+                Some(if removed.is_some() { 0 } else { -1 })
+            }
+            LOG => {
+                info!(
+                    "{}",
+                    self.read_str(args.nth_checked(0)?, args.nth_checked(1)?)?
+                );
+                None
+            }
+            GAS => {
+                let cost = u64::from(args.nth_checked::<i32>(0)? as u32);
+                match self.instructions.checked_sub(cost) {
+                    Some(left) => self.instructions = left,
+                    None => {
+                        return Err(Trap::new(TrapKind::Host(Box::new(LimitExceeded(
+                            "instructions",
+                        )))))
+                    }
+                }
+                None
+            }
+            _ => return Err(Trap::new(TrapKind::UnexpectedSignature)),
+        };
+        Ok(result.map(RuntimeValue::I32))
+    }
+}
+
+// A mod running for an entity, stopped once it fails, with the entities it may reach.
+struct Instance {
+    bytes: Vec<u8>,
+    module: Option<ModuleRef>,
+    owned: HashSet<Entity>,
+}
+
+/// Runs the `Mod`s of the entities with a `ModHandle`, each entity with its own instance of its
+/// mod. It must be added as a thread local system, as the mods reach into the whole world:
+///
+/// ~~~ignore
+/// let game_data = GameDataBuilder::default()
+///     .with_bundle(ScriptingBundle::new().with_component::<Health>("Health").with_mods())?;
+/// ~~~
+///
+/// The mods are sandboxed: they only import the functions of the `amethyst` module, with the
+/// entities as the `i32`s of their ids and the strings as pointers and lengths into the memory
+/// they export as `memory`. A mod only reaches its own entity and the entities it spawned, the
+/// others being treated as missing:
+///
+/// * `spawn() -> i32` creates an entity, and `delete(entity: i32)` deletes one;
+/// * `get(entity, name, name_len, buffer, buffer_len) -> i32` writes a component of the
+///   `ScriptComponents` as JSON into the buffer, if it fits, returning its length, or `-1`;
+/// * `set(entity, name, name_len, json, json_len) -> i32` and
+///   `remove(entity, name, name_len) -> i32` write the components, returning `0`, or `-1`;
+/// * `log(message, message_len)` logs a message.
+///
+/// The mods may export `init(entity: i32)`, called when they start, and
+/// `update(entity: i32, dt: f32)`, called on every frame. A mod which traps or exceeds its
+/// `ModLimits` is logged and stopped until it is reloaded.
+pub struct ModSystem {
+    components: ScriptComponents,
+    limits: ModLimits,
+    instances: HashMap<Entity, Instance>,
+}
+
+impl ModSystem {
+    /// Creates the system of the mods reading and writing the components, with the default
+    /// limits.
+    pub fn new(components: ScriptComponents) -> Self {
+        ModSystem {
+            components,
+            limits: ModLimits::default(),
+            instances: HashMap::new(),
+        }
+    }
+
+    /// Sets the limits of the mods.
+    pub fn with_limits(mut self, limits: ModLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+}
+
+impl<'a> RunNow<'a> for ModSystem {
+    fn setup(&mut self, res: &mut Resources) {
+        <(
+            Read<'_, AssetStorage<Mod>>,
+            ReadStorage<'_, ModHandle>,
+            Read<'_, Time>,
+        )>::setup(res);
+        self.components.setup(res);
+    }
+
+    fn run_now(&mut self, res: &'a Resources) {
+        #[cfg(feature = "profiler")]
+        profile_scope!("mod_system");
+
+        let (mods, handles, time) = <(
+            Read<'_, AssetStorage<Mod>>,
+            ReadStorage<'_, ModHandle>,
+            Read<'_, Time>,
+        )>::fetch(res);
+        let entities = Entities::fetch(res);
+        let mut host = Host {
+            res,
+            entities: &entities,
+            components: &self.components,
+            limits: self.limits,
+            memory: None,
+            owned: HashSet::new(),
+            instructions: 0,
+            spawns: 0,
+        };
+
+        // The mods of the entities which are gone or changed stop, the new ones start.
+        let alive = (&*entities, &handles)
+            .join()
+            .filter_map(|(entity, handle)| mods.get(handle).map(|m| (entity, m)))
+            .collect::<HashMap<_, _>>();
+        self.instances.retain(|entity, instance| {
+            alive
+                .get(entity)
+                .map_or(false, |m| m.bytes == instance.bytes)
+        });
+        for (&entity, m) in &alive {
+            if !self.instances.contains_key(&entity) {
+                let mut owned = Some(entity).into_iter().collect();
+                let module = start(entity, &m.bytes, &mut host, &mut owned)
+                    .map_err(|e| error!("The mod of {:?} failed to start: {}", entity, e))
+                    .ok();
+                self.instances.insert(
+                    entity,
+                    Instance {
+                        bytes: m.bytes.clone(),
+                        module,
+                        owned,
+                    },
+                );
+            }
+        }
+
+        let args = |entity: Entity| {
+            [
+                RuntimeValue::I32(entity.id() as i32),
+                RuntimeValue::from(time.delta_seconds()),
+            ]
+        };
+        for (&entity, instance) in &mut self.instances {
+            let failed = match instance.module {
+                Some(ref module) if module.export_by_name("update").is_some() => {
+                    host.enter(Some(module), &mut instance.owned);
+                    let result = module.invoke_export("update", &args(entity), &mut host);
+                    host.leave(&mut instance.owned);
+                    result
+                        .map_err(|e| error!("The mod of {:?} failed: {}", entity, e))
+                        .is_err()
+                }
+                _ => false,
+            };
+            if failed {
+                instance.module = None;
+            }
+        }
+    }
+}
+
+// Instantiates a mod, and initializes it for its entity.
+fn start(
+    entity: Entity,
+    bytes: &[u8],
+    host: &mut Host<'_>,
+    owned: &mut HashSet<Entity>,
+) -> Result<ModuleRef, Error> {
+    let module = metered(bytes, &host.limits)?;
+    let imports = ImportsBuilder::new()
+        .with_resolver("amethyst", &Imports)
+        .with_resolver("env", &GasImport);
+    let instance = ModuleInstance::new(&module, &imports)?;
+    host.enter(Some(instance.not_started_instance()), owned);
+    let started = instance.run_start(host).and_then(|instance| {
+        if instance.export_by_name("init").is_some() {
+            instance.invoke_export("init", &[RuntimeValue::I32(entity.id() as i32)], host)?;
+        }
+        Ok(instance)
+    });
+    host.leave(owned);
+    Ok(started?)
+}
+
+// Checks the memory a mod declares, and injects the counting of its instructions.
+fn metered(bytes: &[u8], limits: &ModLimits) -> Result<Module, Error> {
+    let module: RawModule = parity_wasm::deserialize_buffer(bytes)
+        .map_err(|e| format_err!("The mod is not a valid WASM module: {:?}", e))?;
+    let memories = module
+        .memory_section()
+        .map_or(&[][..], |section| section.entries());
+    for memory in memories {
+        match memory.limits().maximum() {
+            Some(maximum) if maximum <= limits.memory_pages => {}
+            maximum => {
+                return Err(format_err!(
+                    "The mod declares a maximum of {:?} pages of memory, over the limit of {}",
+                    maximum,
+                    limits.memory_pages
+                ))
+            }
+        }
+    }
+    let module = pwasm_utils::inject_gas_counter(module, &rules::Set::default())
+        .map_err(|_| format_err!("Failed to count the instructions of the mod"))?;
+    Ok(Module::from_parity_wasm_module(module)?)
+}
+
+fn memory(module: &ModuleRef) -> Option<MemoryRef> {
+    module
+        .export_by_name("memory")
+        .and_then(|export| export.as_memory().cloned())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use serde::{Deserialize, Serialize};
+
+    use amethyst_assets::Loader;
+    use amethyst_core::ecs::{Builder, Component, DenseVecStorage, World};
+    use rayon::ThreadPoolBuilder;
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Health {
+        value: u32,
+    }
+
+    impl Component for Health {
+        type Storage = DenseVecStorage<Self>;
+    }
+
+    // A mod whose `init` sets the `Health` of its entity, and spawns an entity.
+    #[rustfmt::skip]
+    const MOD: &[u8] = &[
+        // The header.
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+        // The types `() -> i32`, `(i32)` and `(i32, i32, i32, i32, i32) -> i32`.
+        0x01, 0x12, 0x03, 0x60, 0x00, 0x01, 0x7f, 0x60, 0x01, 0x7f, 0x00, 0x60, 0x05, 0x7f, 0x7f,
+        0x7f, 0x7f, 0x7f, 0x01, 0x7f,
+        // The imports of `amethyst.spawn` and `amethyst.set`.
+        0x02, 0x21, 0x02, 0x08, 0x61, 0x6d, 0x65, 0x74, 0x68, 0x79, 0x73, 0x74, 0x05, 0x73, 0x70,
+        0x61, 0x77, 0x6e, 0x00, 0x00, 0x08, 0x61, 0x6d, 0x65, 0x74, 0x68, 0x79, 0x73, 0x74, 0x03,
+        0x73, 0x65, 0x74, 0x00, 0x02,
+        // The function `init`, its memory of at most a page and their exports.
+        0x03, 0x02, 0x01, 0x01,
+        0x05, 0x04, 0x01, 0x01, 0x01, 0x01,
+        0x07, 0x11, 0x02, 0x04, 0x69, 0x6e, 0x69, 0x74, 0x00, 0x02, 0x06, 0x6d, 0x65, 0x6d, 0x6f,
+        0x72, 0x79, 0x02, 0x00,
+        // `init`, calling `set(entity, 0, 6, 6, 12)` and `spawn()`.
+        0x0a, 0x14, 0x01, 0x12, 0x00, 0x20, 0x00, 0x41, 0x00, 0x41, 0x06, 0x41, 0x06, 0x41, 0x0c,
+        0x10, 0x01, 0x1a, 0x10, 0x00, 0x1a, 0x0b,
+        // The data `Health{"value":10}`.
+        0x0b, 0x18, 0x01, 0x00, 0x41, 0x00, 0x0b, 0x12, 0x48, 0x65, 0x61, 0x6c, 0x74, 0x68, 0x7b,
+        0x22, 0x76, 0x61, 0x6c, 0x75, 0x65, 0x22, 0x3a, 0x31, 0x30, 0x7d,
+    ];
+
+    // Builds a mod importing `spawn` and `delete`, with a memory of at most `max_pages`, whose
+    // `init` runs `code`.
+    fn mod_with(max_pages: Option<u8>, code: &[u8]) -> Vec<u8> {
+        fn section(id: u8, content: &[u8]) -> Vec<u8> {
+            let mut section = vec![id, content.len() as u8];
+            section.extend_from_slice(content);
+            section
+        }
+
+        let mut bytes = vec![0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+        // The types `() -> i32` and `(i32)`.
+        bytes.extend(section(
+            1,
+            &[0x02, 0x60, 0x00, 0x01, 0x7f, 0x60, 0x01, 0x7f, 0x00],
+        ));
+        let mut imports = vec![0x02];
+        for &(name, ty) in &[(&b"spawn"[..], 0x00), (&b"delete"[..], 0x01)] {
+            imports.push(8);
+            imports.extend_from_slice(b"amethyst");
+            imports.push(name.len() as u8);
+            imports.extend_from_slice(name);
+            imports.extend_from_slice(&[0x00, ty]);
+        }
+        bytes.extend(section(2, &imports));
+        bytes.extend(section(3, &[0x01, 0x01]));
+        bytes.extend(section(
+            5,
+            &match max_pages {
+                Some(max) => vec![0x01, 0x01, 0x01, max],
+                None => vec![0x01, 0x00, 0x01],
+            },
+        ));
+        bytes.extend(section(
+            7,
+            &[
+                0x02, 0x04, 0x69, 0x6e, 0x69, 0x74, 0x00, 0x02, 0x06, 0x6d, 0x65, 0x6d, 0x6f, 0x72,
+                0x79, 0x02, 0x00,
+            ],
+        ));
+        let mut body = vec![0x00];
+        body.extend_from_slice(code);
+        body.push(0x0b);
+        let mut functions = vec![0x01, body.len() as u8];
+        functions.extend(body);
+        bytes.extend(section(10, &functions));
+        bytes
+    }
+
+    fn setup(limits: ModLimits) -> (World, ModSystem) {
+        let mut world = World::new();
+        let mut components = ScriptComponents::new();
+        components.register::<Health, _>("Health");
+        let mut system = ModSystem::new(components).with_limits(limits);
+        RunNow::setup(&mut system, &mut world.res);
+        (world, system)
+    }
+
+    // Loads a mod, for a new entity.
+    fn modded(world: &mut World, bytes: &[u8]) -> Entity {
+        let pool = Arc::new(ThreadPoolBuilder::new().build().unwrap());
+        let handle = {
+            let loader = Loader::new(".", pool.clone());
+            let mods = world.read_resource::<AssetStorage<Mod>>();
+            let data = WasmFormat.import(bytes.to_vec(), ()).unwrap();
+            loader.load_from_data(data, (), &*mods)
+        };
+        world
+            .write_resource::<AssetStorage<Mod>>()
+            .process(Into::into, 0, &pool, None);
+        world.create_entity().with(handle).build()
+    }
+
+    fn stopped(system: &ModSystem, entity: Entity) -> bool {
+        system.instances[&entity].module.is_none()
+    }
+
+    #[test]
+    fn mods_reach_into_the_world() {
+        let (mut world, mut system) = setup(ModLimits::default());
+        let modded = modded(&mut world, MOD);
+
+        system.run_now(&world.res);
+        system.run_now(&world.res);
+        world.maintain();
+        assert_eq!(world.entities().join().count(), 2);
+        assert_eq!(
+            world.read_storage::<Health>().get(modded),
+            Some(&Health { value: 10 })
+        );
+        assert!(!stopped(&system, modded));
+    }
+
+    #[test]
+    fn mods_only_reach_their_entities() {
+        let (mut world, mut system) = setup(ModLimits::default());
+        let camera = world.create_entity().build();
+        // Deletes the camera, then an entity it spawns.
+        let code = [0x41, camera.id() as u8, 0x10, 0x01, 0x10, 0x00, 0x10, 0x01];
+        let modded = modded(&mut world, &mod_with(Some(1), &code));
+
+        system.run_now(&world.res);
+        world.maintain();
+        assert!(world.is_alive(camera));
+        assert_eq!(world.entities().join().count(), 2);
+        assert!(!stopped(&system, modded));
+    }
+
+    #[test]
+    fn mods_running_too_long_are_stopped() {
+        let limits = ModLimits {
+            instructions: 1000,
+            ..ModLimits::default()
+        };
+        let (mut world, mut system) = setup(limits);
+        // `loop br 0 end`, never returning.
+        let modded = modded(
+            &mut world,
+            &mod_with(Some(1), &[0x03, 0x40, 0x0c, 0x00, 0x0b]),
+        );
+
+        system.run_now(&world.res);
+        assert!(stopped(&system, modded));
+    }
+
+    #[test]
+    fn mods_with_too_much_memory_are_stopped() {
+        let limits = ModLimits {
+            memory_pages: 1,
+            ..ModLimits::default()
+        };
+        let (mut world, mut system) = setup(limits);
+        let fitting = modded(&mut world, &mod_with(Some(1), &[]));
+        let over = modded(&mut world, &mod_with(Some(2), &[]));
+        let unbounded = modded(&mut world, &mod_with(None, &[]));
+
+        system.run_now(&world.res);
+        assert!(!stopped(&system, fitting));
+        assert!(stopped(&system, over));
+        assert!(stopped(&system, unbounded));
+    }
+
+    #[test]
+    fn mods_spawning_too_much_are_stopped() {
+        let limits = ModLimits {
+            spawns: 2,
+            ..ModLimits::default()
+        };
+        let (mut world, mut system) = setup(limits);
+        let twice = [0x10, 0x00, 0x1a, 0x10, 0x00, 0x1a];
+        let thrice = [0x10, 0x00, 0x1a, 0x10, 0x00, 0x1a, 0x10, 0x00, 0x1a];
+        let twice = modded(&mut world, &mod_with(Some(1), &twice));
+        let thrice = modded(&mut world, &mod_with(Some(1), &thrice));
+
+        system.run_now(&world.res);
+        world.maintain();
+        assert!(!stopped(&system, twice));
+        assert!(stopped(&system, thrice));
+        assert_eq!(world.entities().join().count(), 2 + 2 + 2);
+    }
+}
@@ -3,7 +3,7 @@ use std::{any::Any, hash::Hash, marker::PhantomData, panic, path::PathBuf, sync:
 use amethyst::{
     self,
     animation::AnimationBundle,
-    core::{transform::TransformBundle, EventReader, SystemBundle},
+    core::{transform::TransformBundle, BundleBuilder, EventReader, SystemBundle},
     ecs::prelude::*,
     error::Error,
     input::InputBundle,
@@ -721,7 +721,7 @@ mod test {
 
     use amethyst::{
         assets::{Asset, AssetStorage, Handle, Loader, ProcessingState, Processor},
-        core::bundle::SystemBundle,
+        core::bundle::{BundleBuilder, SystemBundle},
         ecs::prelude::*,
         error::Error,
         prelude::*,
@@ -1200,7 +1200,7 @@ mod test {
     #[derive(Debug)]
     struct BundleZero;
     impl<'a, 'b> SystemBundle<'a, 'b> for BundleZero {
-        fn build(self, builder: &mut DispatcherBuilder<'a, 'b>) -> Result<(), Error> {
+        fn build(self, builder: &mut BundleBuilder<'a, 'b>) -> Result<(), Error> {
             builder.add(SystemZero, "system_zero", &[]);
             Ok(())
         }
@@ -1209,7 +1209,7 @@ mod test {
     #[derive(Debug)]
     struct BundleOne;
     impl<'a, 'b> SystemBundle<'a, 'b> for BundleOne {
-        fn build(self, builder: &mut DispatcherBuilder<'a, 'b>) -> Result<(), Error> {
+        fn build(self, builder: &mut BundleBuilder<'a, 'b>) -> Result<(), Error> {
             builder.add(SystemOne, "system_one", &["system_zero"]);
             builder.add(SystemNonDefault, "system_non_default", &[]);
             Ok(())
@@ -1219,7 +1219,7 @@ mod test {
     #[derive(Debug)]
     struct BundleAsset;
     impl<'a, 'b> SystemBundle<'a, 'b> for BundleAsset {
-        fn build(self, builder: &mut DispatcherBuilder<'a, 'b>) -> Result<(), Error> {
+        fn build(self, builder: &mut BundleBuilder<'a, 'b>) -> Result<(), Error> {
             builder.add(Processor::<AssetZero>::new(), "asset_zero_processor", &[]);
             Ok(())
         }
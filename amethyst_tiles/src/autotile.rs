@@ -0,0 +1,187 @@
+//! The terrains of the auto-tiling, picking their tiles from their neighbours.
+
+use serde::{Deserialize, Serialize};
+
+use crate::map::Tile;
+
+/// A terrain of a `TileMap`, as grass or water, whose tiles are picked by the map from the
+/// neighbours of each tile, see `TileMap::set_terrain`.
+///
+/// The neighbours of a tile are the eight tiles around it, in the columns and the rows of the
+/// map, those which are of the same terrain setting their bit in the mask of the tile. A corner
+/// only counts when both of the sides next to it are of the terrain too, so that a set of the 47
+/// tiles of the corners and sides matches every mask, and a set of 16 tiles of the sides only
+/// needs the side bits. The neighbours out of the map are of the terrain, so that the terrains
+/// go on past the edges of the map.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Terrain {
+    /// The name of the terrain.
+    pub name: String,
+    /// The index of the tile set of the tiles in the `TileMap`.
+    pub tileset: usize,
+    /// The tiles of the masks.
+    pub rules: Vec<TerrainRule>,
+}
+
+/// The tiles of a `Terrain` for the tiles whose neighbours are `mask`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TerrainRule {
+    /// The neighbours of the same terrain, as the bits `TerrainRule::NORTH` to
+    /// `TerrainRule::NORTH_WEST`.
+    pub mask: u8,
+    /// The sprites of the tiles, one of which is picked for each position.
+    pub sprites: Vec<usize>,
+}
+
+impl TerrainRule {
+    /// The tile above.
+    pub const NORTH: u8 = 1;
+    /// The tile above on the right.
+    pub const NORTH_EAST: u8 = 1 << 1;
+    /// The tile on the right.
+    pub const EAST: u8 = 1 << 2;
+    /// The tile below on the right.
+    pub const SOUTH_EAST: u8 = 1 << 3;
+    /// The tile below.
+    pub const SOUTH: u8 = 1 << 4;
+    /// The tile below on the left.
+    pub const SOUTH_WEST: u8 = 1 << 5;
+    /// The tile on the left.
+    pub const WEST: u8 = 1 << 6;
+    /// The tile above on the left.
+    pub const NORTH_WEST: u8 = 1 << 7;
+
+    /// A rule of a single sprite.
+    pub fn new(mask: u8, sprite: usize) -> Self {
+        TerrainRule {
+            mask,
+            sprites: vec![sprite],
+        }
+    }
+}
+
+// The bits of the neighbours with their offsets, the rows going down.
+pub(crate) const NEIGHBOURS: [(u8, i64, i64); 8] = [
+    (TerrainRule::NORTH, 0, -1),
+    (TerrainRule::NORTH_EAST, 1, -1),
+    (TerrainRule::EAST, 1, 0),
+    (TerrainRule::SOUTH_EAST, 1, 1),
+    (TerrainRule::SOUTH, 0, 1),
+    (TerrainRule::SOUTH_WEST, -1, 1),
+    (TerrainRule::WEST, -1, 0),
+    (TerrainRule::NORTH_WEST, -1, -1),
+];
+
+impl Terrain {
+    /// Creates a terrain of the tiles of a tile set.
+    pub fn new<S: Into<String>>(name: S, tileset: usize) -> Self {
+        Terrain {
+            name: name.into(),
+            tileset,
+            rules: Vec::new(),
+        }
+    }
+
+    /// Adds the tile of a mask.
+    pub fn with_rule(mut self, mask: u8, sprite: usize) -> Self {
+        self.rules.push(TerrainRule::new(mask, sprite));
+        self
+    }
+
+    /// Whether the tile is one of the tiles of the terrain.
+    pub fn contains(&self, tile: Tile) -> bool {
+        tile.tileset == self.tileset
+            && self
+                .rules
+                .iter()
+                .any(|rule| rule.sprites.contains(&tile.sprite))
+    }
+
+    /// The sprite of the tile in the column `x` and the row `y` whose neighbours are `mask`.
+    ///
+    /// It is the sprite of the rule of the mask, or else of the rule of the most neighbours all
+    /// in the mask, or else of the first rule. The sprites of a rule are picked by the position,
+    /// so that the same tile stays when its neighbours change.
+    pub fn pick(&self, mask: u8, x: u32, y: u32) -> Option<usize> {
+        let mask = corners(mask);
+        let rule = self
+            .rules
+            .iter()
+            .find(|rule| rule.mask == mask)
+            .or_else(|| {
+                self.rules
+                    .iter()
+                    .filter(|rule| rule.mask & mask == rule.mask)
+                    .max_by_key(|rule| rule.mask.count_ones())
+            })
+            .or_else(|| self.rules.first())?;
+        if rule.sprites.is_empty() {
+            return None;
+        }
+        let hash = (x as usize).wrapping_mul(73_856_093) ^ (y as usize).wrapping_mul(19_349_663);
+        Some(rule.sprites[hash % rule.sprites.len()])
+    }
+}
+
+// Removes the corners whose sides aren't both set.
+fn corners(mask: u8) -> u8 {
+    let mut mask = mask;
+    for &(corner, sides) in &[
+        (
+            TerrainRule::NORTH_EAST,
+            TerrainRule::NORTH | TerrainRule::EAST,
+        ),
+        (
+            TerrainRule::SOUTH_EAST,
+            TerrainRule::SOUTH | TerrainRule::EAST,
+        ),
+        (
+            TerrainRule::SOUTH_WEST,
+            TerrainRule::SOUTH | TerrainRule::WEST,
+        ),
+        (
+            TerrainRule::NORTH_WEST,
+            TerrainRule::NORTH | TerrainRule::WEST,
+        ),
+    ] {
+        if mask & sides != sides {
+            mask &= !corner;
+        }
+    }
+    mask
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::{TileLayer, TileMap};
+
+    #[test]
+    fn terrains_join_their_neighbours() {
+        let (east, west) = (TerrainRule::EAST, TerrainRule::WEST);
+        let terrain = Terrain::new("ground", 0)
+            .with_rule(0, 0)
+            .with_rule(east, 1)
+            .with_rule(west, 2)
+            .with_rule(east | west, 3);
+        assert_eq!(terrain.pick(east | TerrainRule::NORTH_EAST, 0, 0), Some(1));
+        assert_eq!(terrain.pick(!0, 0, 0), Some(3));
+
+        let mut map = TileMap::new(5, 3, [16.0, 16.0]);
+        let ground = map.terrains.len();
+        map.terrains.push(terrain);
+        let layer = map.add_layer(TileLayer::new("ground", 5, 3));
+        for x in 1..4 {
+            assert!(map.set_terrain(layer, x, 1, Some(ground)));
+        }
+        let sprites = |map: &TileMap| {
+            (0..5)
+                .map(|x| map.tile(layer, x, 1).map(|tile| tile.sprite))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(sprites(&map), vec![None, Some(1), Some(3), Some(2), None]);
+        map.set_terrain(layer, 3, 1, None);
+        assert_eq!(sprites(&map), vec![None, Some(1), Some(2), None, None]);
+        assert_eq!(map.terrain(layer, 2, 1), Some(ground));
+    }
+}
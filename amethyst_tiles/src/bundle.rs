@@ -0,0 +1,38 @@
+//! The bundle drawing the tile maps.
+
+use amethyst_core::bundle::{BundleBuilder, SystemBundle};
+use amethyst_error::Error;
+
+#[cfg(feature = "physics")]
+use crate::colliders::TileColliderSystem;
+use crate::{collision::TileCollisionSystem, system::TileMapSystem};
+
+/// Adds the `TileMapSystem`, drawing the `TileMap`s, and the `TileCollisionSystem`, with the
+/// `TileColliderSystem` under the `physics` feature. The maps loaded with the `TiledFormat` also
+/// need a `TiledLoaderSystem`, which isn't added as it depends on the data of the game.
+///
+/// The maps are drawn by a `DrawFlat::<PosTex>` pass with transparency, which the pipeline of the
+/// game needs.
+#[derive(Debug, Default)]
+pub struct TilesBundle;
+
+impl TilesBundle {
+    /// Creates the bundle.
+    pub fn new() -> Self {
+        TilesBundle
+    }
+}
+
+impl<'a, 'b> SystemBundle<'a, 'b> for TilesBundle {
+    fn build(self, builder: &mut BundleBuilder<'a, 'b>) -> Result<(), Error> {
+        builder.add(TileMapSystem::new(), "tile_map", &[]);
+        builder.add(TileCollisionSystem::new(), "tile_collision", &[]);
+        #[cfg(feature = "physics")]
+        builder.add(
+            TileColliderSystem::new(),
+            "tile_colliders",
+            &["tile_collision"],
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,434 @@
+//! The worlds streamed in chunks around the camera.
+
+use std::{collections::HashMap, marker::PhantomData};
+
+use serde::de::DeserializeOwned;
+
+use amethyst_assets::{AssetStorage, Handle, Loader, Prefab, PrefabData};
+use amethyst_core::{
+    ecs::prelude::{
+        Component, DenseVecStorage, Entities, Entity, Join, Read, ReadExpect, ReadStorage, System,
+        Write, WriteStorage,
+    },
+    math::{Matrix4, Point3, Vector3},
+    shrev::EventChannel,
+    Despawn, GlobalTransform, Parent, Transform,
+};
+use amethyst_renderer::{ActiveCamera, Camera};
+
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use crate::{
+    format::{TiledFormat, TiledPrefab},
+    map::TileMap,
+};
+
+/// The column and the row of a chunk of a `ChunkWorld`, from the chunk at the origin of the world,
+/// the rows going down.
+pub type ChunkCoords = (i32, i32);
+
+/// What a chunk of a `ChunkWorld` is made of, given by its `ChunkSource`.
+pub enum Chunk<T> {
+    /// A map made at once, as the chunks of the procedural worlds.
+    Map(TileMap),
+    /// A prefab, loaded in the background, as the Tiled maps of the `TiledChunks`. The entity of
+    /// the chunk is the main entity of the prefab.
+    Prefab(Handle<Prefab<T>>),
+    /// Nothing, for the chunks without tiles. The entity of the chunk still holds the entities
+    /// the game adds to it.
+    Empty,
+}
+
+/// Makes the chunks of a `ChunkWorld` when they are loaded.
+pub trait ChunkSource<T>: Send + Sync {
+    /// The chunk at `coords`, the prefabs being loaded with the `loader` in the `storage`.
+    fn chunk(
+        &mut self,
+        coords: ChunkCoords,
+        loader: &Loader,
+        storage: &AssetStorage<Prefab<T>>,
+    ) -> Chunk<T>;
+}
+
+/// A `ChunkSource` making the chunks with a function, as the procedural worlds do. The function
+/// returns the map of a chunk, or `None` for an empty chunk.
+pub struct GeneratedChunks<F>(pub F);
+
+impl<T, F> ChunkSource<T> for GeneratedChunks<F>
+where
+    F: FnMut(ChunkCoords) -> Option<TileMap> + Send + Sync,
+{
+    fn chunk(&mut self, coords: ChunkCoords, _: &Loader, _: &AssetStorage<Prefab<T>>) -> Chunk<T> {
+        (self.0)(coords).map_or(Chunk::Empty, Chunk::Map)
+    }
+}
+
+/// A `ChunkSource` loading each chunk from a Tiled map with the `TiledFormat`, in the background.
+/// The path of a chunk is the `path` whose `{x}` and `{y}` are replaced by the column and the row
+/// of the chunk, as `"chunks/{x}_{y}.tmx"`.
+#[derive(Clone, Debug)]
+pub struct TiledChunks {
+    path: String,
+}
+
+impl TiledChunks {
+    /// Loads the chunks from the maps at `path`, with the `{x}` and `{y}` of the chunks.
+    pub fn new<S: Into<String>>(path: S) -> Self {
+        TiledChunks { path: path.into() }
+    }
+}
+
+impl<D> ChunkSource<TiledPrefab<D>> for TiledChunks
+where
+    D: DeserializeOwned + Send + Sync + 'static,
+{
+    fn chunk(
+        &mut self,
+        (x, y): ChunkCoords,
+        loader: &Loader,
+        storage: &AssetStorage<Prefab<TiledPrefab<D>>>,
+    ) -> Chunk<TiledPrefab<D>> {
+        let path = self
+            .path
+            .replace("{x}", &x.to_string())
+            .replace("{y}", &y.to_string());
+        Chunk::Prefab(loader.load(path, TiledFormat, (), (), storage))
+    }
+}
+
+/// A world of chunks streamed around the camera by the `ChunkStreamingSystem`, or around the
+/// `focus` entity if it has one.
+///
+/// Each chunk is an entity, a child of the entity of the world, placed right and down from it
+/// by its coordinates, the chunks being `chunk_size` large. The chunks in `radius` chunks of the
+/// chunk of the camera are loaded, and the chunks further than `radius + margin` chunks are
+/// unloaded, being despawned with all their descendants. The entities of a chunk, as the
+/// creatures walking on it, are its children, to be unloaded with it.
+pub struct ChunkWorld<T = TiledPrefab> {
+    /// The width and the height of a chunk, in the units of the transforms.
+    pub chunk_size: [f32; 2],
+    /// How many chunks around the one of the camera are loaded, in each direction.
+    pub radius: u32,
+    /// How many chunks further than the `radius` the chunks stay loaded, so that the ones on an
+    /// edge aren't loaded again on every crossing.
+    pub margin: u32,
+    /// How many chunks are loaded per frame at most, the closest first.
+    pub loads_per_frame: usize,
+    /// The entity the chunks are loaded around, instead of the camera.
+    pub focus: Option<Entity>,
+    source: Box<dyn ChunkSource<T>>,
+    chunks: HashMap<ChunkCoords, Entity>,
+}
+
+impl<T> ChunkWorld<T> {
+    /// Creates a world of chunks of `chunk_size`, made by the `source`, loading a chunk around
+    /// the one of the camera with a margin of one and four chunks per frame.
+    pub fn new<S>(chunk_size: [f32; 2], source: S) -> Self
+    where
+        S: ChunkSource<T> + 'static,
+    {
+        ChunkWorld {
+            chunk_size,
+            radius: 1,
+            margin: 1,
+            loads_per_frame: 4,
+            focus: None,
+            source: Box::new(source),
+            chunks: HashMap::new(),
+        }
+    }
+
+    /// Sets the `radius` and the `margin` of the loaded chunks.
+    pub fn with_radius(mut self, radius: u32, margin: u32) -> Self {
+        self.radius = radius;
+        self.margin = margin;
+        self
+    }
+
+    /// Loads the chunks around the `focus` entity instead of the camera.
+    pub fn with_focus(mut self, focus: Entity) -> Self {
+        self.focus = Some(focus);
+        self
+    }
+
+    /// The entity of a loaded chunk.
+    pub fn chunk(&self, coords: ChunkCoords) -> Option<Entity> {
+        self.chunks.get(&coords).cloned()
+    }
+
+    /// The loaded chunks.
+    pub fn chunks<'a>(&'a self) -> impl Iterator<Item = (ChunkCoords, Entity)> + 'a {
+        self.chunks
+            .iter()
+            .map(|(coords, entity)| (*coords, *entity))
+    }
+
+    /// The coordinates of the chunk at a position relative to the world.
+    pub fn chunk_at(&self, position: Point3<f32>) -> ChunkCoords {
+        (
+            (position.x / self.chunk_size[0]).floor() as i32,
+            (-position.y / self.chunk_size[1]).floor() as i32,
+        )
+    }
+
+    /// The position of the top left corner of a chunk relative to the world.
+    pub fn chunk_position(&self, (x, y): ChunkCoords) -> Vector3<f32> {
+        Vector3::new(
+            x as f32 * self.chunk_size[0],
+            -(y as f32) * self.chunk_size[1],
+            0.0,
+        )
+    }
+}
+
+impl<T> Component for ChunkWorld<T>
+where
+    T: Send + Sync + 'static,
+{
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// An event of the `ChunkStreamingSystem`, when a chunk of a `ChunkWorld` is loaded or unloaded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChunkEvent {
+    /// A chunk was created, its prefab being loaded if it has one.
+    Loaded {
+        /// The entity of the `ChunkWorld`.
+        world: Entity,
+        /// The coordinates of the chunk.
+        coords: ChunkCoords,
+        /// The entity of the chunk.
+        chunk: Entity,
+    },
+    /// A chunk was despawned, with its descendants.
+    Unloaded {
+        /// The entity of the `ChunkWorld`.
+        world: Entity,
+        /// The coordinates of the chunk.
+        coords: ChunkCoords,
+        /// The entity of the chunk, which is deleted.
+        chunk: Entity,
+    },
+}
+
+/// Loads and unloads the chunks of the `ChunkWorld<T>`s around the `ActiveCamera`, or the first
+/// camera if there is no active camera, and sends the `ChunkEvent`s.
+///
+/// The chunks loaded from prefabs need the `PrefabLoaderSystem<T>`, which is the
+/// `TiledLoaderSystem` for the `TiledChunks`, and the unloaded chunks are deleted by the
+/// `DespawnSystem` of the `TransformBundle`.
+pub struct ChunkStreamingSystem<T = TiledPrefab> {
+    _marker: PhantomData<T>,
+}
+
+impl<T> ChunkStreamingSystem<T> {
+    /// Creates a new `ChunkStreamingSystem`.
+    pub fn new() -> Self {
+        ChunkStreamingSystem {
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> Default for ChunkStreamingSystem<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, T> System<'a> for ChunkStreamingSystem<T>
+where
+    T: PrefabData<'a> + Send + Sync + 'static,
+{
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, ChunkWorld<T>>,
+        Option<Read<'a, ActiveCamera>>,
+        ReadStorage<'a, Camera>,
+        ReadStorage<'a, GlobalTransform>,
+        ReadExpect<'a, Loader>,
+        Read<'a, AssetStorage<Prefab<T>>>,
+        WriteStorage<'a, Transform>,
+        WriteStorage<'a, Parent>,
+        WriteStorage<'a, TileMap>,
+        WriteStorage<'a, Handle<Prefab<T>>>,
+        WriteStorage<'a, Despawn>,
+        Write<'a, EventChannel<ChunkEvent>>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            mut worlds,
+            active_camera,
+            cameras,
+            globals,
+            loader,
+            prefabs,
+            mut transforms,
+            mut parents,
+            mut maps,
+            mut handles,
+            mut despawns,
+            mut events,
+        ): Self::SystemData,
+    ) {
+        #[cfg(feature = "profiler")]
+        profile_scope!("chunk_streaming_system");
+
+        let camera = active_camera
+            .as_ref()
+            .and_then(|active| active.entity)
+            .filter(|entity| cameras.contains(*entity))
+            .or_else(|| {
+                (&*entities, &cameras)
+                    .join()
+                    .next()
+                    .map(|(entity, _)| entity)
+            });
+
+        let mut chunk_events = Vec::new();
+        for (world_entity, world) in (&*entities, &mut worlds).join() {
+            let focus = world
+                .focus
+                .or(camera)
+                .and_then(|focus| globals.get(focus))
+                .map(|global| Point3::new(global.0[(0, 3)], global.0[(1, 3)], global.0[(2, 3)]));
+            let focus = match focus {
+                Some(focus) => focus,
+                None => continue,
+            };
+            let inverse = globals
+                .get(world_entity)
+                .and_then(|global| global.0.try_inverse())
+                .unwrap_or_else(Matrix4::identity);
+            let (cx, cy) = world.chunk_at(inverse.transform_point(&focus));
+            let distance = |(x, y): ChunkCoords| ((x - cx).abs()).max((y - cy).abs()) as u32;
+
+            let far = world
+                .chunks
+                .keys()
+                .filter(|coords| distance(**coords) > world.radius + world.margin)
+                .cloned()
+                .collect::<Vec<_>>();
+            for coords in far {
+                if let Some(chunk) = world.chunks.remove(&coords) {
+                    despawns
+                        .insert(chunk, Despawn)
+                        .expect("Unreachable: The chunk is alive");
+                    chunk_events.push(ChunkEvent::Unloaded {
+                        world: world_entity,
+                        coords,
+                        chunk,
+                    });
+                }
+            }
+
+            let radius = world.radius as i32;
+            let mut missing = (cy - radius..=cy + radius)
+                .flat_map(|y| (cx - radius..=cx + radius).map(move |x| (x, y)))
+                .filter(|coords| !world.chunks.contains_key(coords))
+                .collect::<Vec<_>>();
+            missing.sort_by_key(|coords| distance(*coords));
+            missing.truncate(world.loads_per_frame);
+            for coords in missing {
+                let mut transform = Transform::default();
+                *transform.translation_mut() = world.chunk_position(coords);
+                let chunk = entities
+                    .build_entity()
+                    .with(transform, &mut transforms)
+                    .with(
+                        Parent {
+                            entity: world_entity,
+                        },
+                        &mut parents,
+                    )
+                    .build();
+                match world.source.chunk(coords, &loader, &prefabs) {
+                    Chunk::Map(map) => {
+                        maps.insert(chunk, map)
+                            .expect("Unreachable: The chunk is alive");
+                    }
+                    Chunk::Prefab(handle) => {
+                        handles
+                            .insert(chunk, handle)
+                            .expect("Unreachable: The chunk is alive");
+                    }
+                    Chunk::Empty => {}
+                }
+                world.chunks.insert(coords, chunk);
+                chunk_events.push(ChunkEvent::Loaded {
+                    world: world_entity,
+                    coords,
+                    chunk,
+                });
+            }
+        }
+        events.iter_write(chunk_events.drain(..));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use amethyst_core::ecs::{Builder, RunNow, World};
+    use rayon::ThreadPoolBuilder;
+
+    use super::*;
+
+    #[test]
+    fn chunks_are_streamed_around_the_focus() {
+        let mut world = World::new();
+        let mut system = ChunkStreamingSystem::<TiledPrefab>::new();
+        System::setup(&mut system, &mut world.res);
+        world.add_resource(Loader::new(
+            ".",
+            Arc::new(ThreadPoolBuilder::new().build().unwrap()),
+        ));
+        let focus = world
+            .create_entity()
+            .with(GlobalTransform(Matrix4::identity()))
+            .build();
+        let source = GeneratedChunks(|(x, _): ChunkCoords| {
+            if x % 2 == 0 {
+                Some(TileMap::new(4, 4, [8.0, 8.0]))
+            } else {
+                None
+            }
+        });
+        let mut chunks = ChunkWorld::<TiledPrefab>::new([32.0, 32.0], source).with_focus(focus);
+        chunks.loads_per_frame = 9;
+        let chunks = world.create_entity().with(chunks).build();
+
+        system.run_now(&world.res);
+        let loaded = |world: &World| {
+            world
+                .read_storage::<ChunkWorld>()
+                .get(chunks)
+                .unwrap()
+                .chunks()
+                .count()
+        };
+        assert_eq!(loaded(&world), 9);
+        assert_eq!(world.read_storage::<TileMap>().join().count(), 3);
+
+        // Two chunks to the right, the chunks of the left column are further than the margin.
+        world
+            .write_storage::<GlobalTransform>()
+            .get_mut(focus)
+            .unwrap()
+            .0 = Matrix4::new_translation(&Vector3::new(80.0, -16.0, 0.0));
+        system.run_now(&world.res);
+        assert_eq!(loaded(&world), 12);
+        assert_eq!(world.read_storage::<Despawn>().join().count(), 3);
+        assert_eq!(world.read_storage::<TileMap>().join().count(), 6);
+        let storage = world.read_storage::<ChunkWorld>();
+        let chunk_world = storage.get(chunks).unwrap();
+        assert!(chunk_world.chunk((0, 0)).is_some());
+        assert!(chunk_world.chunk((-1, 0)).is_none());
+        assert!(chunk_world.chunk((3, 1)).is_some());
+    }
+}
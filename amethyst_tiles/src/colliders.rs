@@ -0,0 +1,130 @@
+//! The physics colliders of the tile maps.
+
+use std::collections::HashMap;
+
+use amethyst_core::{
+    ecs::prelude::{Entities, Entity, Read, System, WriteStorage},
+    math::{Isometry3, Vector3},
+    Transform,
+};
+use amethyst_physics::{Collider, ColliderShape};
+
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use crate::collision::{TileCollision, TileShape};
+
+// The colliders of a map, and what they were made from.
+struct MapColliders {
+    revision: u64,
+    position: Vector3<f32>,
+    entities: Vec<Entity>,
+}
+
+/// Gives the shapes of the `TileCollision` static physics `Collider`s, on entities at the
+/// position of their map. The colliders are made again when the tiles or the position of the map
+/// change, the rotation and the scale of the maps being ignored.
+///
+/// Added by the `TilesBundle` with the `physics` feature.
+#[derive(Default)]
+pub struct TileColliderSystem {
+    maps: HashMap<Entity, MapColliders>,
+}
+
+impl TileColliderSystem {
+    /// Creates a new `TileColliderSystem`.
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl<'a> System<'a> for TileColliderSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, TileCollision>,
+        WriteStorage<'a, Transform>,
+        WriteStorage<'a, Collider>,
+    );
+
+    fn run(&mut self, (entities, collision, mut transforms, mut colliders): Self::SystemData) {
+        #[cfg(feature = "profiler")]
+        profile_scope!("tile_collider_system");
+
+        let removed = self
+            .maps
+            .keys()
+            .filter(|map| collision.map(**map).is_none())
+            .cloned()
+            .collect::<Vec<_>>();
+        for map in removed {
+            for entity in self
+                .maps
+                .remove(&map)
+                .into_iter()
+                .flat_map(|map| map.entities)
+            {
+                let _ = entities.delete(entity);
+            }
+        }
+
+        for (map, shapes) in collision.maps() {
+            let matrix = shapes.transform();
+            let position = Vector3::new(matrix[(0, 3)], matrix[(1, 3)], matrix[(2, 3)]);
+            if let Some(built) = self.maps.get(&map) {
+                if built.revision == shapes.revision && built.position == position {
+                    continue;
+                }
+            }
+            for entity in self
+                .maps
+                .remove(&map)
+                .into_iter()
+                .flat_map(|map| map.entities)
+            {
+                let _ = entities.delete(entity);
+            }
+            let built = shapes
+                .shapes()
+                .iter()
+                .map(|shape| {
+                    let mut transform = Transform::default();
+                    *transform.translation_mut() = position;
+                    entities
+                        .build_entity()
+                        .with(transform, &mut transforms)
+                        .with(collider(shape), &mut colliders)
+                        .build()
+                })
+                .collect();
+            self.maps.insert(
+                map,
+                MapColliders {
+                    revision: shapes.revision,
+                    position,
+                    entities: built,
+                },
+            );
+        }
+    }
+}
+
+// The collider of a shape, as the colliders of the sprites.
+fn collider(shape: &TileShape) -> Collider {
+    let at = |x: f32, y: f32| Isometry3::new(Vector3::new(x, y, 0.0), Vector3::zeros());
+    match shape {
+        TileShape::Rectangle { min, max } => {
+            let size = max - min;
+            Collider::new(ColliderShape::Cuboid {
+                half_extents: Vector3::new(size.x, size.y, size.x.max(size.y)) / 2.0,
+            })
+            .with_offset(at((min.x + max.x) / 2.0, (min.y + max.y) / 2.0))
+        }
+        TileShape::Circle { center, radius } => {
+            Collider::new(ColliderShape::Ball { radius: *radius })
+                .with_offset(at(center.x, center.y))
+        }
+        TileShape::Polygon(points) => Collider::new(ColliderShape::Polygon {
+            points: points.clone(),
+        }),
+    }
+}
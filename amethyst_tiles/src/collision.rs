@@ -0,0 +1,480 @@
+//! The collisions with the tiles of the tile maps.
+
+use std::collections::HashMap;
+
+use serde_json::Value;
+
+use amethyst_assets::AssetStorage;
+use amethyst_core::{
+    ecs::prelude::{Entities, Entity, Join, Read, ReadStorage, System, Write},
+    math::{Matrix4, Point2, Point3, Vector2},
+    GlobalTransform,
+};
+use amethyst_renderer::{Sprite, SpriteShape, SpriteSheet};
+
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use crate::{
+    map::{TileLayer, TileMap},
+    orientation::Orientation,
+};
+
+/// The boolean property of the layers whose tiles are solid.
+pub const COLLISION_PROPERTY: &str = "collision";
+
+/// A solid shape of a tile map, in the space of its entity.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TileShape {
+    /// A rectangle, as the merged solid tiles of the orthogonal maps.
+    Rectangle {
+        /// The bottom left corner.
+        min: Point2<f32>,
+        /// The top right corner.
+        max: Point2<f32>,
+    },
+    /// A circle.
+    Circle {
+        /// The center.
+        center: Point2<f32>,
+        /// The radius.
+        radius: f32,
+    },
+    /// A convex polygon, given by its corners.
+    Polygon(Vec<Point2<f32>>),
+}
+
+impl TileShape {
+    /// The bottom left and top right corners of the smallest rectangle containing the shape.
+    pub fn bounds(&self) -> (Point2<f32>, Point2<f32>) {
+        match self {
+            TileShape::Rectangle { min, max } => (*min, *max),
+            TileShape::Circle { center, radius } => (
+                center - Vector2::repeat(*radius),
+                center + Vector2::repeat(*radius),
+            ),
+            TileShape::Polygon(points) => points.iter().fold(
+                (
+                    Point2::new(std::f32::MAX, std::f32::MAX),
+                    Point2::new(std::f32::MIN, std::f32::MIN),
+                ),
+                |(min, max), point| {
+                    (
+                        Point2::new(min.x.min(point.x), min.y.min(point.y)),
+                        Point2::new(max.x.max(point.x), max.y.max(point.y)),
+                    )
+                },
+            ),
+        }
+    }
+
+    /// Whether the point is in the shape.
+    pub fn contains(&self, point: Point2<f32>) -> bool {
+        match self {
+            TileShape::Rectangle { min, max } => {
+                point.x >= min.x && point.y >= min.y && point.x <= max.x && point.y <= max.y
+            }
+            TileShape::Circle { center, radius } => (point - center).norm() <= *radius,
+            TileShape::Polygon(points) => {
+                let sides = edges(points)
+                    .map(|(start, end)| (end - start).perp(&(point - start)))
+                    .collect::<Vec<_>>();
+                sides.iter().all(|side| *side >= 0.0) || sides.iter().all(|side| *side <= 0.0)
+            }
+        }
+    }
+
+    /// Whether the shape overlaps the rectangle from the bottom left corner `min` to the top
+    /// right corner `max`.
+    pub fn overlaps(&self, min: Point2<f32>, max: Point2<f32>) -> bool {
+        let (shape_min, shape_max) = self.bounds();
+        if shape_min.x > max.x || shape_min.y > max.y || shape_max.x < min.x || shape_max.y < min.y
+        {
+            return false;
+        }
+        match self {
+            TileShape::Rectangle { .. } => true,
+            TileShape::Circle { center, radius } => {
+                let closest = Point2::new(
+                    center.x.max(min.x).min(max.x),
+                    center.y.max(min.y).min(max.y),
+                );
+                (closest - center).norm() <= *radius
+            }
+            // The bounds separate the shapes along the axes of the rectangle, and the polygon
+            // has the other axes which could separate them.
+            TileShape::Polygon(points) => {
+                let corners = [
+                    min,
+                    Point2::new(max.x, min.y),
+                    max,
+                    Point2::new(min.x, max.y),
+                ];
+                edges(points).all(|(start, end)| {
+                    let normal = Vector2::new(start.y - end.y, end.x - start.x);
+                    let range = |points: &mut dyn Iterator<Item = Point2<f32>>| {
+                        points.fold((std::f32::MAX, std::f32::MIN), |(low, high), point| {
+                            let projection = normal.dot(&point.coords);
+                            (low.min(projection), high.max(projection))
+                        })
+                    };
+                    let polygon = range(&mut points.iter().cloned());
+                    let rectangle = range(&mut corners.iter().cloned());
+                    polygon.0 <= rectangle.1 && rectangle.0 <= polygon.1
+                })
+            }
+        }
+    }
+}
+
+fn edges<'a>(points: &'a [Point2<f32>]) -> impl Iterator<Item = (Point2<f32>, Point2<f32>)> + 'a {
+    points
+        .iter()
+        .zip(points.iter().cycle().skip(1))
+        .map(|(start, end)| (*start, *end))
+}
+
+/// The solid shapes of a `TileMap`, see `TileCollision`.
+#[derive(Clone, Debug)]
+pub struct MapCollision {
+    shapes: Vec<TileShape>,
+    transform: Matrix4<f32>,
+    inverse: Matrix4<f32>,
+    pub(crate) revision: u64,
+}
+
+impl MapCollision {
+    /// The shapes, in the space of the map.
+    pub fn shapes(&self) -> &[TileShape] {
+        &self.shapes
+    }
+
+    /// The global transform of the map.
+    pub fn transform(&self) -> &Matrix4<f32> {
+        &self.transform
+    }
+
+    /// Whether a point in the space of the map is in a shape.
+    pub fn is_solid(&self, point: Point2<f32>) -> bool {
+        self.shapes.iter().any(|shape| shape.contains(point))
+    }
+
+    /// Whether a rectangle in the space of the map, from its bottom left corner to its top right
+    /// one, overlaps a shape.
+    pub fn overlaps(&self, min: Point2<f32>, max: Point2<f32>) -> bool {
+        self.shapes.iter().any(|shape| shape.overlaps(min, max))
+    }
+
+    fn local(&self, point: Point2<f32>) -> Point2<f32> {
+        let local = self
+            .inverse
+            .transform_point(&Point3::new(point.x, point.y, 0.0));
+        Point2::new(local.x, local.y)
+    }
+}
+
+/// The solid shapes of the `TileMap`s, generated by the `TileCollisionSystem` when their tiles
+/// change, for the games colliding with the maps without the physics.
+///
+/// The tiles of the layers whose `collision` property is `true` are solid. On orthogonal maps,
+/// they are merged into rectangles, each grown right then down from its top left tile, the other
+/// maps having a polygon per tile. The tiles whose sprite has a `collision` shape in
+/// its sprite sheet are solid in every layer, with that shape.
+///
+/// The queries are on the XY plane of the world, through the transforms of the maps.
+#[derive(Debug, Default)]
+pub struct TileCollision {
+    maps: HashMap<Entity, MapCollision>,
+}
+
+impl TileCollision {
+    /// The shapes of a map, once its tile sets are loaded.
+    pub fn map(&self, entity: Entity) -> Option<&MapCollision> {
+        self.maps.get(&entity)
+    }
+
+    /// The shapes of all the maps.
+    pub fn maps<'a>(&'a self) -> impl Iterator<Item = (Entity, &'a MapCollision)> + 'a {
+        self.maps.iter().map(|(entity, map)| (*entity, map))
+    }
+
+    /// Whether a point is in a shape of a map.
+    pub fn is_solid(&self, point: Point2<f32>) -> bool {
+        self.maps.values().any(|map| map.is_solid(map.local(point)))
+    }
+
+    /// Whether a rectangle, from its bottom left corner to its top right one, overlaps a shape
+    /// of a map. On the rotated maps, the rectangle is grown to contain its corners.
+    pub fn overlaps(&self, min: Point2<f32>, max: Point2<f32>) -> bool {
+        self.maps.values().any(|map| {
+            let corners = [
+                map.local(min),
+                map.local(Point2::new(max.x, min.y)),
+                map.local(max),
+                map.local(Point2::new(min.x, max.y)),
+            ];
+            let local_min = corners.iter().fold(corners[0], |low, corner| {
+                Point2::new(low.x.min(corner.x), low.y.min(corner.y))
+            });
+            let local_max = corners.iter().fold(corners[0], |high, corner| {
+                Point2::new(high.x.max(corner.x), high.y.max(corner.y))
+            });
+            map.overlaps(local_min, local_max)
+        })
+    }
+}
+
+/// Generates the `TileCollision` of the `TileMap`s, when their tiles change and once the sprite
+/// sheets of their tile sets are loaded.
+#[derive(Debug, Default)]
+pub struct TileCollisionSystem;
+
+impl TileCollisionSystem {
+    /// Creates a new `TileCollisionSystem`.
+    pub fn new() -> Self {
+        TileCollisionSystem
+    }
+}
+
+impl<'a> System<'a> for TileCollisionSystem {
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, TileMap>,
+        ReadStorage<'a, GlobalTransform>,
+        Read<'a, AssetStorage<SpriteSheet>>,
+        Write<'a, TileCollision>,
+    );
+
+    fn run(&mut self, (entities, maps, globals, sheets, mut collision): Self::SystemData) {
+        #[cfg(feature = "profiler")]
+        profile_scope!("tile_collision_system");
+
+        collision.maps.retain(|entity, _| maps.contains(*entity));
+        for (entity, map) in (&*entities, &maps).join() {
+            let transform = globals
+                .get(entity)
+                .map_or_else(Matrix4::identity, |global| global.0);
+            let stale = collision
+                .maps
+                .get(&entity)
+                .map_or(true, |shapes| shapes.revision != map.revision());
+            if stale {
+                if let Some(shapes) = map_shapes(map, &sheets) {
+                    collision.maps.insert(
+                        entity,
+                        MapCollision {
+                            shapes,
+                            transform,
+                            inverse: Matrix4::identity(),
+                            revision: map.revision(),
+                        },
+                    );
+                }
+            }
+            if let Some(shapes) = collision.maps.get_mut(&entity) {
+                shapes.transform = transform;
+                shapes.inverse = transform.try_inverse().unwrap_or_else(Matrix4::identity);
+            }
+        }
+    }
+}
+
+// The solid shapes of a map, or `None` until the sprite sheets of its tile sets are loaded.
+fn map_shapes(map: &TileMap, sheets: &AssetStorage<SpriteSheet>) -> Option<Vec<TileShape>> {
+    let tilesets = map
+        .tilesets
+        .iter()
+        .map(|handle| sheets.get(handle))
+        .collect::<Option<Vec<_>>>()?;
+    // The grid of the solid tiles covers all the layers, which may be larger than the map.
+    let width = map
+        .layers()
+        .iter()
+        .map(TileLayer::width)
+        .fold(map.width(), u32::max);
+    let height = map
+        .layers()
+        .iter()
+        .map(TileLayer::height)
+        .fold(map.height(), u32::max);
+    let mut solid = vec![false; (width * height) as usize];
+    let mut shapes = Vec::new();
+    for layer in map.layers() {
+        let designated = layer
+            .properties
+            .get(COLLISION_PROPERTY)
+            .and_then(Value::as_bool)
+            .unwrap_or(false);
+        for (x, y, tile) in layer.tiles() {
+            let collision = tilesets
+                .get(tile.tileset)
+                .and_then(|sheet| sheet.sprites.get(tile.sprite))
+                .and_then(|sprite| sprite.collision.as_ref().map(|shape| (sprite, shape)));
+            match collision {
+                Some((sprite, shape)) => shapes.push(sprite_shape(map, x, y, sprite, shape)),
+                None if designated => solid[(y * width + x) as usize] = true,
+                None => {}
+            }
+        }
+    }
+
+    if map.orientation() == Orientation::Orthogonal {
+        shapes.extend(merge(&solid, width, height, map.tile_size));
+    } else {
+        let cells = (0..height).flat_map(|y| (0..width).map(move |x| (x, y)));
+        for (x, y) in cells.filter(|&(x, y)| solid[(y * width + x) as usize]) {
+            let points = map
+                .orientation()
+                .outline(map.tile_size, [width, height], x, y)
+                .into_iter()
+                .map(|[px, py]| Point2::new(px, -py))
+                .collect();
+            shapes.push(TileShape::Polygon(points));
+        }
+    }
+    Some(shapes)
+}
+
+// The collision shape of the sprite of a tile, drawn centered on the tile and shifted by its
+// offsets.
+fn sprite_shape(map: &TileMap, x: u32, y: u32, sprite: &Sprite, shape: &SpriteShape) -> TileShape {
+    let center = map.tile_position(x, y);
+    let local = |x: f32, y: f32| {
+        Point2::new(
+            center.x + x - sprite.width / 2.0 - sprite.offsets[0],
+            center.y + sprite.height / 2.0 - y - sprite.offsets[1],
+        )
+    };
+    match shape {
+        SpriteShape::Rectangle {
+            x,
+            y,
+            width,
+            height,
+        } => TileShape::Rectangle {
+            min: local(*x, y + height),
+            max: local(x + width, *y),
+        },
+        SpriteShape::Circle { x, y, radius } => TileShape::Circle {
+            center: local(*x, *y),
+            radius: *radius,
+        },
+        SpriteShape::Polygon(points) => TileShape::Polygon(
+            points
+                .iter()
+                .map(|point| local(point[0], point[1]))
+                .collect(),
+        ),
+    }
+}
+
+// Merges the solid tiles of an orthogonal map into rectangles, growing each rectangle right then
+// down from its first tile.
+fn merge(solid: &[bool], width: u32, height: u32, tile_size: [f32; 2]) -> Vec<TileShape> {
+    let index = |x: u32, y: u32| (y * width + x) as usize;
+    let mut merged = vec![false; solid.len()];
+    let free = |merged: &[bool], x, y| solid[index(x, y)] && !merged[index(x, y)];
+    let mut rectangles = Vec::new();
+    for top in 0..height {
+        for left in 0..width {
+            if !free(&merged, left, top) {
+                continue;
+            }
+            let mut right = left + 1;
+            while right < width && free(&merged, right, top) {
+                right += 1;
+            }
+            let mut bottom = top + 1;
+            while bottom < height && (left..right).all(|x| free(&merged, x, bottom)) {
+                bottom += 1;
+            }
+            for y in top..bottom {
+                for x in left..right {
+                    merged[index(x, y)] = true;
+                }
+            }
+            rectangles.push(TileShape::Rectangle {
+                min: Point2::new(left as f32 * tile_size[0], -(bottom as f32) * tile_size[1]),
+                max: Point2::new(right as f32 * tile_size[0], -(top as f32) * tile_size[1]),
+            });
+        }
+    }
+    rectangles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::Tile;
+
+    #[test]
+    fn solid_tiles_are_merged() {
+        let mut map = TileMap::new(4, 3, [16.0, 16.0]);
+        let mut walls = TileLayer::new("walls", 4, 3);
+        walls
+            .properties
+            .insert(COLLISION_PROPERTY.to_string(), Value::Bool(true));
+        let walls = map.add_layer(walls);
+        let ground = map.add_layer(TileLayer::new("ground", 4, 3));
+        // An L of a column and of the bottom row, and ground which isn't solid.
+        for &(x, y) in &[(0, 0), (0, 1), (0, 2), (1, 2), (2, 2), (3, 2)] {
+            map.set_tile(walls, x, y, Some(Tile::new(0, 0)));
+        }
+        map.set_tile(ground, 2, 0, Some(Tile::new(0, 0)));
+
+        let shapes = map_shapes(&map, &AssetStorage::default()).unwrap();
+        assert_eq!(
+            shapes,
+            vec![
+                TileShape::Rectangle {
+                    min: Point2::new(0.0, -48.0),
+                    max: Point2::new(16.0, 0.0),
+                },
+                TileShape::Rectangle {
+                    min: Point2::new(16.0, -48.0),
+                    max: Point2::new(64.0, -32.0),
+                },
+            ]
+        );
+
+        let mut collision = MapCollision {
+            shapes,
+            transform: Matrix4::identity(),
+            inverse: Matrix4::identity(),
+            revision: map.revision(),
+        };
+        assert!(collision.is_solid(Point2::new(8.0, -20.0)));
+        assert!(!collision.is_solid(Point2::new(40.0, -8.0)));
+        assert!(collision.overlaps(Point2::new(30.0, -34.0), Point2::new(40.0, -20.0)));
+        assert!(!collision.overlaps(Point2::new(20.0, -30.0), Point2::new(40.0, -10.0)));
+
+        collision.shapes = vec![TileShape::Polygon(vec![
+            Point2::new(0.0, 0.0),
+            Point2::new(10.0, 0.0),
+            Point2::new(0.0, 10.0),
+        ])];
+        assert!(collision.is_solid(Point2::new(2.0, 2.0)));
+        assert!(!collision.is_solid(Point2::new(6.0, 6.0)));
+        assert!(!collision.overlaps(Point2::new(6.0, 6.0), Point2::new(9.0, 9.0)));
+        assert!(collision.overlaps(Point2::new(4.0, 4.0), Point2::new(9.0, 9.0)));
+    }
+
+    #[test]
+    fn layers_larger_than_the_map_collide() {
+        let mut map = TileMap::new(2, 2, [16.0, 16.0]);
+        let mut walls = TileLayer::new("walls", 4, 1);
+        walls
+            .properties
+            .insert(COLLISION_PROPERTY.to_string(), Value::Bool(true));
+        let walls = map.add_layer(walls);
+        map.set_tile(walls, 3, 0, Some(Tile::new(0, 0)));
+
+        assert_eq!(
+            map_shapes(&map, &AssetStorage::default()).unwrap(),
+            vec![TileShape::Rectangle {
+                min: Point2::new(48.0, -16.0),
+                max: Point2::new(64.0, 0.0),
+            }]
+        );
+    }
+}
@@ -0,0 +1,696 @@
+//! The loading of the maps of the Tiled editor.
+
+use std::{path::Path, sync::Arc};
+
+use log::{debug, warn};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+
+use amethyst_assets::{Format, FormatValue, Prefab, PrefabData, ProgressCounter, Source};
+use amethyst_core::{
+    ecs::prelude::{Component, DenseVecStorage, Entity, WriteStorage},
+    math::{Point2, UnitQuaternion, Vector3},
+    Named, Transform,
+};
+use amethyst_error::{format_err, Error, ResultExt};
+use amethyst_renderer::{
+    SpriteList, SpritePosition, SpriteRender, SpriteShape, SpriteSheetPrefab, Sprites,
+    TextureFormat, TextureMetadata, TexturePrefab,
+};
+
+use crate::{
+    autotile::{Terrain, TerrainRule},
+    map::{Properties, Tile, TileLayer, TileMap},
+    orientation::{Orientation, StaggerAxis, StaggerIndex},
+};
+
+// The bits of the global tile ids flipping the tiles.
+const FLIP_FLAGS: u32 = 0xE000_0000;
+
+/// Loads the maps of the [Tiled](https://www.mapeditor.org) editor, in the `.tmx` format, with
+/// their tile sets, embedded or in `.tsx` files next to the map.
+///
+/// The main entity of the prefab gets the `TileMap`, with the orientation of the map, a layer per
+/// tile layer of the map and a sprite sheet per tile set, whose images are loaded relative to the
+/// map. Each object of the object layers becomes a child entity with a `Transform` at the center
+/// of the object, drawn over the tile layers at its `TileMap::depth`, a `TiledObject`, and the
+/// `SpriteRender` of its tile if it is a tile object. On isometric maps, the positions and the
+/// points of the objects are projected as the tiles are, their sizes staying along the columns
+/// and the rows of the map. The custom properties of the objects are also deserialized into `T`,
+/// the `PrefabData` adding the components of the game:
+///
+/// ~~~ignore
+/// #[derive(Clone, Default, Deserialize, PrefabData)]
+/// #[serde(default)]
+/// struct LevelData {
+///     enemy: Option<Enemy>,
+///     chest: Option<Chest>,
+/// }
+///
+/// let handle = loader.load("level.tmx", TiledFormat, (), &mut progress, &storage);
+/// ~~~
+///
+/// An object whose properties are `enemy: { "speed": 2.0 }`, as a property named `enemy` holding
+/// the JSON of the component, then gets an `Enemy`. The objects whose properties don't match `T`
+/// don't get any data from them.
+///
+/// The first collision object of each tile, drawn in the tile collision editor of Tiled, becomes
+/// the `collision` shape of its sprite, which the `TileCollision` uses with the tile layers whose
+/// `collision` property is `true`. The tiles whose properties are the name of a `terrain` and a
+/// `terrain_mask`, the integer of the bits of `TerrainRule`, are the rules of the `terrains` of
+/// the map, to paint them with `TileMap::set_terrain`.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct TiledFormat;
+
+impl<T> Format<Prefab<TiledPrefab<T>>> for TiledFormat
+where
+    T: DeserializeOwned + Send + Sync + 'static,
+{
+    const NAME: &'static str = "TILED";
+
+    type Options = ();
+
+    fn import(
+        &self,
+        name: String,
+        source: Arc<dyn Source>,
+        _options: (),
+        _create_reload: bool,
+    ) -> Result<FormatValue<Prefab<TiledPrefab<T>>>, Error> {
+        Ok(FormatValue::data(load_map(&*source, &name).with_context(
+            |_| format_err!("Failed to import tiled map {}", name),
+        )?))
+    }
+}
+
+/// A shape of a `TiledObject`, centered on its entity.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum TiledShape {
+    /// A rectangle of the size of the object.
+    Rectangle,
+    /// An ellipse of the size of the object.
+    Ellipse,
+    /// A closed polygon.
+    Polygon(Vec<[f32; 2]>),
+    /// An open line.
+    Polyline(Vec<[f32; 2]>),
+}
+
+/// An object of the object layers of a Tiled map, see `TiledFormat`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TiledObject {
+    /// The type of the object.
+    pub object_type: String,
+    /// The name of the object layer.
+    pub layer: String,
+    /// The width and height of the object.
+    pub size: [f32; 2],
+    /// The shape of the object.
+    pub shape: TiledShape,
+    /// The custom properties of the object.
+    pub properties: Properties,
+}
+
+impl Component for TiledObject {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// The `TileMap` of a `TiledPrefab`, with the tile sets to load.
+#[derive(Clone, Debug)]
+pub struct TileMapPrefab {
+    map: TileMap,
+    tilesets: Vec<SpriteSheetPrefab>,
+}
+
+impl<'a> PrefabData<'a> for TileMapPrefab {
+    type SystemData = (
+        WriteStorage<'a, TileMap>,
+        <SpriteSheetPrefab as PrefabData<'a>>::SystemData,
+    );
+    type Result = ();
+
+    fn add_to_entity(
+        &self,
+        entity: Entity,
+        system_data: &mut Self::SystemData,
+        _: &[Entity],
+        _: &[Entity],
+    ) -> Result<(), Error> {
+        let mut map = self.map.clone();
+        map.tilesets = self
+            .tilesets
+            .iter()
+            .filter_map(|tileset| match tileset {
+                SpriteSheetPrefab::Handle((_, handle)) => Some(handle.clone()),
+                _ => None,
+            })
+            .collect();
+        system_data.0.insert(entity, map)?;
+        Ok(())
+    }
+
+    fn load_sub_assets(
+        &mut self,
+        progress: &mut ProgressCounter,
+        system_data: &mut Self::SystemData,
+    ) -> Result<bool, Error> {
+        let mut ret = false;
+        for tileset in &mut self.tilesets {
+            if tileset.load_sub_assets(progress, &mut system_data.1)? {
+                ret = true;
+            }
+        }
+        Ok(ret)
+    }
+}
+
+/// `PrefabData` of the Tiled maps, see `TiledFormat`.
+#[derive(Clone, Debug)]
+pub struct TiledPrefab<T = ()> {
+    /// The position of the map or of the object.
+    pub transform: Option<Transform>,
+    /// The name of the object.
+    pub name: Option<Named>,
+    /// The map, on the main entity.
+    pub map: Option<TileMapPrefab>,
+    /// The object, on the other entities.
+    pub object: Option<TiledObject>,
+    /// The tile of a tile object, drawn with the tile set of the map.
+    pub tile: Option<Tile>,
+    /// The data of the game, deserialized from the properties of the object.
+    pub data: Option<T>,
+}
+
+impl<T> Default for TiledPrefab<T> {
+    fn default() -> Self {
+        TiledPrefab {
+            transform: None,
+            name: None,
+            map: None,
+            object: None,
+            tile: None,
+            data: None,
+        }
+    }
+}
+
+impl<'a, T> PrefabData<'a> for TiledPrefab<T>
+where
+    T: PrefabData<'a>,
+{
+    type SystemData = (
+        <Transform as PrefabData<'a>>::SystemData,
+        <Named as PrefabData<'a>>::SystemData,
+        <TileMapPrefab as PrefabData<'a>>::SystemData,
+        WriteStorage<'a, TiledObject>,
+        WriteStorage<'a, SpriteRender>,
+        T::SystemData,
+    );
+    type Result = ();
+
+    fn add_to_entity(
+        &self,
+        entity: Entity,
+        system_data: &mut Self::SystemData,
+        entities: &[Entity],
+        children: &[Entity],
+    ) -> Result<(), Error> {
+        let (
+            ref mut transforms,
+            ref mut names,
+            ref mut maps,
+            ref mut objects,
+            ref mut renders,
+            ref mut data,
+        ) = system_data;
+        if let Some(ref transform) = self.transform {
+            transform.add_to_entity(entity, transforms, entities, children)?;
+        }
+        if let Some(ref name) = self.name {
+            name.add_to_entity(entity, names, entities, children)?;
+        }
+        if let Some(ref map) = self.map {
+            map.add_to_entity(entity, maps, entities, children)?;
+        }
+        if let Some(ref object) = self.object {
+            objects.insert(entity, object.clone())?;
+        }
+        // The map is on the main entity, which is created first.
+        if let Some(tile) = self.tile {
+            let sheet = maps
+                .0
+                .get(entities[0])
+                .and_then(|map| map.tilesets.get(tile.tileset).cloned());
+            if let Some(sprite_sheet) = sheet {
+                renders.insert(
+                    entity,
+                    SpriteRender {
+                        sprite_sheet,
+                        sprite_number: tile.sprite,
+                    },
+                )?;
+            }
+        }
+        if let Some(ref object_data) = self.data {
+            object_data.add_to_entity(entity, data, entities, children)?;
+        }
+        Ok(())
+    }
+
+    fn load_sub_assets(
+        &mut self,
+        progress: &mut ProgressCounter,
+        system_data: &mut Self::SystemData,
+    ) -> Result<bool, Error> {
+        let mut ret = false;
+        if let Some(ref mut map) = self.map {
+            if map.load_sub_assets(progress, &mut system_data.2)? {
+                ret = true;
+            }
+        }
+        if let Some(ref mut data) = self.data {
+            if data.load_sub_assets(progress, &mut system_data.5)? {
+                ret = true;
+            }
+        }
+        Ok(ret)
+    }
+}
+
+fn load_map<T: DeserializeOwned>(
+    source: &dyn Source,
+    name: &str,
+) -> Result<Prefab<TiledPrefab<T>>, Error> {
+    debug!("Loading Tiled map {}", name);
+    let directory = Path::new(name).parent().unwrap_or_else(|| Path::new(""));
+    let text = String::from_utf8(source.load(name)?)?;
+    let text = inline_tilesets(&text, |path| {
+        let path = directory.join(path);
+        let bytes = source.load(&path.to_string_lossy())?;
+        Ok(String::from_utf8(bytes)?)
+    })?;
+    let map = tiled::parse(text.as_bytes()).map_err(|err| format_err!("{:?}", err))?;
+
+    let tile_size = [map.tile_width as f32, map.tile_height as f32];
+    let mut tile_map = TileMap::new(map.width, map.height, tile_size);
+    tile_map.set_orientation(orientation(&text, &map));
+    tile_map.properties = properties(&map.properties);
+    tile_map.terrains = terrains(&map.tilesets);
+    for layer in &map.layers {
+        let mut tiles = TileLayer::new(layer.name.clone(), map.width, map.height);
+        tiles.visible = layer.visible;
+        tiles.properties = properties(&layer.properties);
+        let index = tile_map.add_layer(tiles);
+        for (y, row) in layer.tiles.iter().enumerate() {
+            for (x, gid) in row.iter().enumerate() {
+                tile_map.set_tile(index, x as u32, y as u32, tile(&map, *gid));
+            }
+        }
+    }
+    let tilesets = map
+        .tilesets
+        .iter()
+        .map(|tileset| tileset_sheet(tileset, tile_size, directory, name))
+        .collect::<Result<_, Error>>()?;
+
+    let mut prefab = Prefab::new();
+    for group in &map.object_groups {
+        for object in group.objects.iter().filter(|object| object.visible) {
+            let entity = prefab.add(Some(0), None);
+            load_object(
+                &map,
+                &tile_map,
+                &group.name,
+                object,
+                prefab.data_or_default(entity),
+            );
+        }
+    }
+    prefab.main(Some(TiledPrefab {
+        map: Some(TileMapPrefab {
+            map: tile_map,
+            tilesets,
+        }),
+        ..Default::default()
+    }));
+    Ok(prefab)
+}
+
+fn load_object<T: DeserializeOwned>(
+    map: &tiled::Map,
+    tile_map: &TileMap,
+    layer: &str,
+    object: &tiled::Object,
+    prefab: &mut TiledPrefab<T>,
+) {
+    let (width, height) = (object.width, object.height);
+    // The objects are placed from their top left corner, the tile objects from their bottom left
+    // one, and they rotate clockwise around that corner.
+    let center = if object.gid != 0 {
+        [width / 2.0, -height / 2.0]
+    } else {
+        [width / 2.0, height / 2.0]
+    };
+    let (sin, cos) = object.rotation.to_radians().sin_cos();
+    let position = object_vector(
+        tile_map,
+        [
+            object.x + center[0] * cos - center[1] * sin,
+            object.y + center[0] * sin + center[1] * cos,
+        ],
+    );
+    // The isometric maps are projected from the top corner, in the middle of their bounds.
+    let position = match tile_map.orientation() {
+        Orientation::Isometric => Point2::new(
+            position[0] + tile_map.height() as f32 * tile_map.tile_size[0] / 2.0,
+            position[1],
+        ),
+        _ => Point2::new(position[0], position[1]),
+    };
+    let mut transform = Transform::default();
+    *transform.translation_mut() = Vector3::new(
+        position.x,
+        position.y,
+        tile_map.depth(tile_map.layers().len(), position),
+    );
+    *transform.rotation_mut() =
+        UnitQuaternion::from_axis_angle(&Vector3::z_axis(), -object.rotation.to_radians());
+    prefab.transform = Some(transform);
+    if !object.name.is_empty() {
+        prefab.name = Some(Named::new(object.name.clone()));
+    }
+
+    let points = |points: &[(f32, f32)]| {
+        points
+            .iter()
+            .map(|&(x, y)| object_vector(tile_map, [x - center[0], y - center[1]]))
+            .collect()
+    };
+    let shape = match object.shape {
+        tiled::ObjectShape::Rect { .. } => TiledShape::Rectangle,
+        tiled::ObjectShape::Ellipse { .. } => TiledShape::Ellipse,
+        tiled::ObjectShape::Polygon { ref points } => TiledShape::Polygon(points(points)),
+        tiled::ObjectShape::Polyline { ref points } => TiledShape::Polyline(points(points)),
+    };
+    let properties = properties(&object.properties);
+    if !properties.is_empty() {
+        match serde_json::from_value(Value::Object(properties.clone())) {
+            Ok(data) => prefab.data = Some(data),
+            Err(err) => debug!(
+                "The properties of object {} aren't data: {}",
+                object.id, err
+            ),
+        }
+    }
+    prefab.tile = tile(map, object.gid);
+    prefab.object = Some(TiledObject {
+        object_type: object.obj_type.clone(),
+        layer: layer.to_string(),
+        size: [width, height],
+        shape,
+        properties,
+    });
+}
+
+// Converts a vector in the pixels of the objects to the space of the map, Y going up. The objects
+// of the isometric maps are in the pixels of the tile height along the columns and the rows.
+fn object_vector(map: &TileMap, vector: [f32; 2]) -> [f32; 2] {
+    match map.orientation() {
+        Orientation::Isometric => {
+            let [width, height] = map.tile_size;
+            let (x, y) = (vector[0] / height, vector[1] / height);
+            [(x - y) * width / 2.0, -(x + y) * height / 2.0]
+        }
+        _ => [vector[0], -vector[1]],
+    }
+}
+
+// The orientation of a map, with the attributes of its tag that the parser skips.
+fn orientation(text: &str, map: &tiled::Map) -> Orientation {
+    let tag = text
+        .find("<map")
+        .and_then(|start| text[start..].find('>').map(|end| &text[start..start + end]))
+        .unwrap_or("");
+    let axis = match attribute(tag, "staggeraxis") {
+        Some("x") => StaggerAxis::X,
+        _ => StaggerAxis::Y,
+    };
+    let index = match attribute(tag, "staggerindex") {
+        Some("even") => StaggerIndex::Even,
+        _ => StaggerIndex::Odd,
+    };
+    match map.orientation {
+        tiled::Orientation::Orthogonal => Orientation::Orthogonal,
+        tiled::Orientation::Isometric => Orientation::Isometric,
+        tiled::Orientation::Staggered => Orientation::Staggered { axis, index },
+        tiled::Orientation::Hexagonal => Orientation::Hexagonal {
+            side_length: attribute(tag, "hexsidelength")
+                .and_then(|length| length.parse().ok())
+                .unwrap_or(0.0),
+            axis,
+            index,
+        },
+    }
+}
+
+// The terrains of the tiles whose properties are the name of a `terrain` and a `terrain_mask`.
+fn terrains(tilesets: &[tiled::Tileset]) -> Vec<Terrain> {
+    let mut terrains: Vec<Terrain> = Vec::new();
+    for (index, tileset) in tilesets.iter().enumerate() {
+        for tile in &tileset.tiles {
+            let name = match tile.properties.get("terrain") {
+                Some(tiled::PropertyValue::StringValue(name)) => name,
+                _ => continue,
+            };
+            let mask = match tile.properties.get("terrain_mask") {
+                Some(tiled::PropertyValue::IntValue(mask)) => *mask as u8,
+                _ => 0,
+            };
+            let terrain = match terrains
+                .iter()
+                .position(|terrain| terrain.tileset == index && terrain.name == *name)
+            {
+                Some(terrain) => terrain,
+                None => {
+                    terrains.push(Terrain::new(name.clone(), index));
+                    terrains.len() - 1
+                }
+            };
+            let rules = &mut terrains[terrain].rules;
+            match rules.iter_mut().find(|rule| rule.mask == mask) {
+                Some(rule) => rule.sprites.push(tile.id as usize),
+                None => rules.push(TerrainRule::new(mask, tile.id as usize)),
+            }
+        }
+    }
+    terrains
+}
+
+// The tile of a global tile id, 0 being no tile. The flipped tiles are drawn unflipped.
+fn tile(map: &tiled::Map, gid: u32) -> Option<Tile> {
+    let gid = gid & !FLIP_FLAGS;
+    if gid == 0 {
+        return None;
+    }
+    map.tilesets
+        .iter()
+        .enumerate()
+        .filter(|(_, tileset)| tileset.first_gid <= gid)
+        .max_by_key(|(_, tileset)| tileset.first_gid)
+        .map(|(index, tileset)| Tile::new(index, (gid - tileset.first_gid) as usize))
+}
+
+// The sprite sheet of a tile set, with a sprite per tile of its image. The tiles larger than the
+// tiles of the map are aligned on the bottom left corner of their place, as Tiled draws them.
+fn tileset_sheet(
+    tileset: &tiled::Tileset,
+    map_tile_size: [f32; 2],
+    directory: &Path,
+    map: &str,
+) -> Result<SpriteSheetPrefab, Error> {
+    let image = match tileset.images.first() {
+        Some(image) => image,
+        None => {
+            return Err(format_err!(
+                "The tile set {} has no image, the collections of images aren't supported",
+                tileset.name
+            ));
+        }
+    };
+    let path = directory.join(&image.source);
+    let format = match path.extension().and_then(|extension| extension.to_str()) {
+        Some("jpg") | Some("jpeg") => TextureFormat::Jpg,
+        Some("bmp") => TextureFormat::Bmp,
+        Some("tga") => TextureFormat::Tga,
+        Some("png") => TextureFormat::Png,
+        _ => {
+            warn!(
+                "Unknown format of image {}, loading it as PNG",
+                image.source
+            );
+            TextureFormat::Png
+        }
+    };
+
+    let (width, height) = (image.width.max(0) as u32, image.height.max(0) as u32);
+    let (tile_width, tile_height) = (tileset.tile_width, tileset.tile_height);
+    if tile_width == 0 || tile_height == 0 {
+        return Err(format_err!(
+            "The tiles of the tile set {} are {}x{}, they can't be empty",
+            tileset.name,
+            tile_width,
+            tile_height
+        ));
+    }
+    let (margin, spacing) = (tileset.margin, tileset.spacing);
+    let fit = |size: u32, tile: u32| {
+        size.saturating_add(spacing)
+            .saturating_sub(margin.saturating_mul(2))
+            / tile.saturating_add(spacing)
+    };
+    let (columns, rows) = (fit(width, tile_width), fit(height, tile_height));
+    let offsets = [
+        (map_tile_size[0] - tile_width as f32) / 2.0,
+        (map_tile_size[1] - tile_height as f32) / 2.0,
+    ];
+    let sprites = (0..rows)
+        .flat_map(|row| (0..columns).map(move |column| (column, row)))
+        .map(|(column, row)| SpritePosition {
+            x: margin + column * (tile_width + spacing),
+            y: margin + row * (tile_height + spacing),
+            width: tile_width,
+            height: tile_height,
+            offsets: Some(offsets).filter(|offsets| *offsets != [0.0; 2]),
+            collision: tileset
+                .tiles
+                .iter()
+                .find(|tile| tile.id == row * columns + column)
+                .and_then(tile_collision),
+        })
+        .collect();
+
+    Ok(SpriteSheetPrefab::Sheet {
+        texture: TexturePrefab::File(
+            path.to_string_lossy().into_owned(),
+            format,
+            TextureMetadata::srgb_scale(),
+        ),
+        sprites: vec![Sprites::List(SpriteList {
+            texture_width: width,
+            texture_height: height,
+            sprites,
+        })],
+        name: Some(format!("{}#{}", map, tileset.name)),
+    })
+}
+
+// The collision shape of a tile, as the first of the objects of the collision editor of Tiled.
+fn tile_collision(tile: &tiled::Tile) -> Option<SpriteShape> {
+    let object = tile.objectgroup.as_ref()?.objects.first()?;
+    let (x, y) = (object.x, object.y);
+    Some(match object.shape {
+        tiled::ObjectShape::Rect { width, height } => SpriteShape::Rectangle {
+            x,
+            y,
+            width,
+            height,
+        },
+        tiled::ObjectShape::Ellipse { width, height } => SpriteShape::Circle {
+            x: x + width / 2.0,
+            y: y + height / 2.0,
+            radius: width.min(height) / 2.0,
+        },
+        tiled::ObjectShape::Polygon { ref points }
+        | tiled::ObjectShape::Polyline { ref points } => {
+            SpriteShape::Polygon(points.iter().map(|&(px, py)| [x + px, y + py]).collect())
+        }
+    })
+}
+
+fn properties(properties: &tiled::Properties) -> Properties {
+    properties
+        .iter()
+        .map(|(name, value)| {
+            let value = match value {
+                tiled::PropertyValue::BoolValue(value) => Value::from(*value),
+                tiled::PropertyValue::FloatValue(value) => Value::from(f64::from(*value)),
+                tiled::PropertyValue::IntValue(value) => Value::from(*value),
+                tiled::PropertyValue::ColorValue(value) => Value::from(*value),
+                // The strings holding JSON objects and arrays are the values of the components.
+                tiled::PropertyValue::StringValue(value)
+                    if value.starts_with('{') || value.starts_with('[') =>
+                {
+                    serde_json::from_str(value).unwrap_or_else(|_| Value::from(value.clone()))
+                }
+                tiled::PropertyValue::StringValue(value) => Value::from(value.clone()),
+            };
+            (name.clone(), value)
+        })
+        .collect()
+}
+
+// Replaces the tile sets of a map which are in their own files with the content of the files.
+fn inline_tilesets<F>(map: &str, mut load: F) -> Result<String, Error>
+where
+    F: FnMut(&str) -> Result<String, Error>,
+{
+    let mut inlined = String::with_capacity(map.len());
+    let mut rest = map;
+    while let Some(start) = rest.find("<tileset") {
+        let end = match rest[start..].find('>') {
+            Some(end) => start + end + 1,
+            None => break,
+        };
+        let tag = &rest[start..end];
+        inlined.push_str(&rest[..start]);
+        match (attribute(tag, "source"), attribute(tag, "firstgid")) {
+            (Some(source), Some(first_gid)) => {
+                let tileset = load(source)?;
+                let tileset = &tileset[tileset
+                    .find("<tileset")
+                    .ok_or_else(|| format_err!("The tile set {} is invalid", source))?..];
+                inlined.push_str(&format!(
+                    "<tileset firstgid=\"{}\"{}",
+                    first_gid,
+                    &tileset["<tileset".len()..]
+                ));
+            }
+            _ => inlined.push_str(tag),
+        }
+        rest = &rest[end..];
+    }
+    inlined.push_str(rest);
+    Ok(inlined)
+}
+
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let pattern = format!(" {}=\"", name);
+    let start = tag.find(&pattern)? + pattern.len();
+    let end = start + tag[start..].find('"')?;
+    Some(&tag[start..end])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn external_tilesets_are_inlined() {
+        let map = r#"<map><tileset firstgid="5" source="ground.tsx"/><layer/></map>"#;
+        let inlined = inline_tilesets(map, |source| {
+            assert_eq!(source, "ground.tsx");
+            Ok(r#"<?xml version="1.0"?><tileset name="ground"><image/></tileset>"#.to_string())
+        })
+        .unwrap();
+        assert_eq!(
+            inlined,
+            r#"<map><tileset firstgid="5" name="ground"><image/></tileset><layer/></map>"#
+        );
+
+        let embedded = r#"<map><tileset firstgid="1" name="walls"><image/></tileset></map>"#;
+        assert_eq!(
+            inline_tilesets(embedded, |_| panic!("Nothing to load")).unwrap(),
+            embedded
+        );
+    }
+}
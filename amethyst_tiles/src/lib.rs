@@ -0,0 +1,47 @@
+//! Tile maps for Amethyst, with the loading of the maps of the Tiled editor.
+//!
+//! A `TileMap` is a grid of tiles in layers, orthogonal, isometric or hexagonal, drawn by the
+//! `TileMapSystem` in meshes of chunks of tiles, with the sprites of its tile sets and by a
+//! `DrawFlat::<PosTex>` pass with transparency. The maps are made by the game, or loaded
+//! with their objects from the `.tmx` files of [Tiled](https://www.mapeditor.org) with the
+//! `TiledFormat`.
+//!
+//! The solid tiles are merged into the shapes of the `TileCollision`, for the games colliding with
+//! the maps without the physics, which the `physics` feature turns into physics colliders.
+//!
+//! The large and infinite worlds are streamed in chunks around the camera by the
+//! `ChunkStreamingSystem`, the chunks being generated or loaded from Tiled maps.
+
+#![warn(missing_docs, rust_2018_idioms, rust_2018_compatibility)]
+
+#[cfg(feature = "physics")]
+pub use self::colliders::TileColliderSystem;
+pub use self::{
+    autotile::{Terrain, TerrainRule},
+    bundle::TilesBundle,
+    chunk::{
+        Chunk, ChunkCoords, ChunkEvent, ChunkSource, ChunkStreamingSystem, ChunkWorld,
+        GeneratedChunks, TiledChunks,
+    },
+    collision::{MapCollision, TileCollision, TileCollisionSystem, TileShape, COLLISION_PROPERTY},
+    format::{TileMapPrefab, TiledFormat, TiledObject, TiledPrefab, TiledShape},
+    map::{Properties, Tile, TileLayer, TileMap},
+    orientation::{Orientation, StaggerAxis, StaggerIndex},
+    system::{TileMapSystem, LAYER_DEPTH},
+};
+
+use amethyst_assets::PrefabLoaderSystem;
+
+mod autotile;
+mod bundle;
+mod chunk;
+#[cfg(feature = "physics")]
+mod colliders;
+mod collision;
+mod format;
+mod map;
+mod orientation;
+mod system;
+
+/// Loads the Tiled maps, with the data `T` of their objects.
+pub type TiledLoaderSystem<T = ()> = PrefabLoaderSystem<TiledPrefab<T>>;
@@ -0,0 +1,446 @@
+//! The tile maps, grids of tiles drawn with the sprites of their tile sets.
+
+use serde::{Deserialize, Serialize};
+
+use amethyst_core::{
+    ecs::prelude::{Component, DenseVecStorage},
+    math::{Point2, Point3, Vector3},
+    GlobalTransform,
+};
+use amethyst_renderer::{Camera, ScreenDimensions, SpriteSheetHandle};
+
+use crate::{
+    autotile::{Terrain, NEIGHBOURS},
+    orientation::Orientation,
+    system::LAYER_DEPTH,
+};
+
+/// The custom properties of the maps, the layers and the objects, as set in the editor.
+pub type Properties = serde_json::Map<String, serde_json::Value>;
+
+/// A tile of a `TileMap`, as a sprite of one of its tile sets.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Tile {
+    /// The index of the tile set in the `TileMap`.
+    pub tileset: usize,
+    /// The index of the sprite in the tile set.
+    pub sprite: usize,
+}
+
+impl Tile {
+    /// Creates a tile, as the sprite of a tile set.
+    pub fn new(tileset: usize, sprite: usize) -> Self {
+        Tile { tileset, sprite }
+    }
+}
+
+/// A grid of tiles of a `TileMap`, drawn over the layers before it.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TileLayer {
+    /// The name of the layer.
+    pub name: String,
+    /// Whether the tiles of the layer are drawn.
+    pub visible: bool,
+    /// The custom properties of the layer.
+    pub properties: Properties,
+    width: u32,
+    height: u32,
+    tiles: Vec<Option<Tile>>,
+}
+
+impl TileLayer {
+    /// Creates an empty, visible layer.
+    pub fn new<S: Into<String>>(name: S, width: u32, height: u32) -> Self {
+        TileLayer {
+            name: name.into(),
+            visible: true,
+            properties: Properties::new(),
+            width,
+            height,
+            tiles: vec![None; (width * height) as usize],
+        }
+    }
+
+    /// The width of the layer, in tiles.
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// The height of the layer, in tiles.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// The tile in the column `x` and the row `y`, the rows going down from the top one.
+    pub fn tile(&self, x: u32, y: u32) -> Option<Tile> {
+        self.index(x, y).and_then(|index| self.tiles[index])
+    }
+
+    /// The tiles of the layer, with their column and row.
+    pub fn tiles<'a>(&'a self) -> impl Iterator<Item = (u32, u32, Tile)> + 'a {
+        let width = self.width;
+        self.tiles
+            .iter()
+            .enumerate()
+            .filter_map(move |(index, tile)| {
+                tile.map(|tile| (index as u32 % width, index as u32 / width, tile))
+            })
+    }
+
+    // Sets a tile, returning whether the position is in the layer.
+    fn set(&mut self, x: u32, y: u32, tile: Option<Tile>) -> bool {
+        match self.index(x, y) {
+            Some(index) => {
+                self.tiles[index] = tile;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn index(&self, x: u32, y: u32) -> Option<usize> {
+        if x < self.width && y < self.height {
+            Some((y * self.width + x) as usize)
+        } else {
+            None
+        }
+    }
+}
+
+/// A map of tiles, drawn by the `TileMapSystem` with a mesh per chunk and tile set, children of
+/// the entity of the map. The tiles are laid out by the `Orientation` of the map, its bounds going
+/// right along X and down along Y from the position of its entity, with a layer above the other
+/// along Z.
+///
+/// Within a layer, the tiles lower on the map are drawn over the ones above them, as the tall
+/// tiles of isometric maps overlap the tiles behind them. The entities walking on the map are
+/// drawn in that order with the Z of their `depth`.
+///
+/// The tiles are changed through the map, so that only the meshes of their chunks are built again.
+#[derive(Clone, Debug)]
+pub struct TileMap {
+    /// The size of a tile, in the units of the transforms.
+    pub tile_size: [f32; 2],
+    /// The sprite sheets of the tile sets.
+    pub tilesets: Vec<SpriteSheetHandle>,
+    /// The custom properties of the map.
+    pub properties: Properties,
+    /// The terrains of the auto-tiling, see `set_terrain`.
+    pub terrains: Vec<Terrain>,
+    width: u32,
+    height: u32,
+    orientation: Orientation,
+    layers: Vec<TileLayer>,
+    changes: Changes,
+    revision: u64,
+}
+
+// The tiles changed since the meshes were last built.
+#[derive(Clone, Debug)]
+pub(crate) enum Changes {
+    All,
+    Tiles(Vec<(usize, u32, u32)>),
+}
+
+impl TileMap {
+    /// Creates an orthogonal map without layers, of `width` by `height` tiles of `tile_size`.
+    pub fn new(width: u32, height: u32, tile_size: [f32; 2]) -> Self {
+        TileMap {
+            tile_size,
+            tilesets: Vec::new(),
+            properties: Properties::new(),
+            terrains: Vec::new(),
+            width,
+            height,
+            orientation: Orientation::Orthogonal,
+            layers: Vec::new(),
+            changes: Changes::All,
+            revision: 0,
+        }
+    }
+
+    /// The width of the map, in tiles.
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// The height of the map, in tiles.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// How the tiles are laid out.
+    pub fn orientation(&self) -> Orientation {
+        self.orientation
+    }
+
+    /// Changes how the tiles are laid out, moving all their sprites.
+    pub fn set_orientation(&mut self, orientation: Orientation) {
+        self.orientation = orientation;
+        self.changes = Changes::All;
+        self.revision += 1;
+    }
+
+    /// The width and height of the bounds of the map.
+    pub fn size(&self) -> [f32; 2] {
+        self.orientation
+            .size(self.tile_size, [self.width, self.height])
+    }
+
+    /// Adds a layer over the others, returning its index.
+    pub fn add_layer(&mut self, layer: TileLayer) -> usize {
+        self.layers.push(layer);
+        self.changes = Changes::All;
+        self.revision += 1;
+        self.layers.len() - 1
+    }
+
+    /// The layers, from the bottom one.
+    pub fn layers(&self) -> &[TileLayer] {
+        &self.layers
+    }
+
+    /// The first layer with this name.
+    pub fn layer(&self, name: &str) -> Option<&TileLayer> {
+        self.layers.iter().find(|layer| layer.name == name)
+    }
+
+    /// The index of the first layer with this name.
+    pub fn layer_index(&self, name: &str) -> Option<usize> {
+        self.layers.iter().position(|layer| layer.name == name)
+    }
+
+    /// The tile in a layer, in the column `x` and the row `y`.
+    pub fn tile(&self, layer: usize, x: u32, y: u32) -> Option<Tile> {
+        self.layers.get(layer).and_then(|layer| layer.tile(x, y))
+    }
+
+    /// Sets or removes a tile in a layer, returning whether the position is in the map.
+    pub fn set_tile(&mut self, layer: usize, x: u32, y: u32, tile: Option<Tile>) -> bool {
+        let set = self
+            .layers
+            .get_mut(layer)
+            .map_or(false, |tiles| tiles.set(x, y, tile));
+        if set {
+            if let Changes::Tiles(ref mut changes) = self.changes {
+                changes.push((layer, x, y));
+            }
+            self.revision += 1;
+        }
+        set
+    }
+
+    /// The index of the terrain of a tile, if it is a tile of one of the `terrains`.
+    pub fn terrain(&self, layer: usize, x: u32, y: u32) -> Option<usize> {
+        let tile = self.tile(layer, x, y)?;
+        self.terrains
+            .iter()
+            .position(|terrain| terrain.contains(tile))
+    }
+
+    /// Paints the terrain of index `terrain` on a tile, or removes the tile with `None`, picking
+    /// the tiles of the tile and of the terrains around it from their neighbours, see `Terrain`.
+    /// Returns whether the position is in the map.
+    pub fn set_terrain(&mut self, layer: usize, x: u32, y: u32, terrain: Option<usize>) -> bool {
+        let tile = terrain
+            .and_then(|terrain| self.terrains.get(terrain))
+            .and_then(|terrain| {
+                terrain
+                    .pick(!0, x, y)
+                    .map(|sprite| Tile::new(terrain.tileset, sprite))
+            });
+        if !self.set_tile(layer, x, y, tile) {
+            return false;
+        }
+        for (_, dx, dy) in NEIGHBOURS.iter().chain(Some(&(0, 0, 0))) {
+            let (nx, ny) = (i64::from(x) + dx, i64::from(y) + dy);
+            if nx >= 0 && ny >= 0 && nx < i64::from(self.width) && ny < i64::from(self.height) {
+                self.pick_terrain(layer, nx as u32, ny as u32);
+            }
+        }
+        true
+    }
+
+    /// Picks the tiles of the terrains of a layer from their neighbours, as for the maps whose
+    /// terrains were painted with any of their tiles.
+    pub fn pick_terrains(&mut self, layer: usize) {
+        for y in 0..self.height {
+            for x in 0..self.width {
+                self.pick_terrain(layer, x, y);
+            }
+        }
+    }
+
+    // Picks the tile of a terrain from its neighbours.
+    fn pick_terrain(&mut self, layer: usize, x: u32, y: u32) {
+        let index = match self.terrain(layer, x, y) {
+            Some(index) => index,
+            None => return,
+        };
+        let mask = NEIGHBOURS
+            .iter()
+            .filter(|(_, dx, dy)| {
+                let (nx, ny) = (i64::from(x) + dx, i64::from(y) + dy);
+                nx < 0
+                    || ny < 0
+                    || nx >= i64::from(self.width)
+                    || ny >= i64::from(self.height)
+                    || self.terrain(layer, nx as u32, ny as u32) == Some(index)
+            })
+            .fold(0, |mask, (bit, _, _)| mask | bit);
+        let terrain = &self.terrains[index];
+        if let Some(sprite) = terrain.pick(mask, x, y) {
+            let tile = Tile::new(terrain.tileset, sprite);
+            if self.tile(layer, x, y) != Some(tile) {
+                self.set_tile(layer, x, y, Some(tile));
+            }
+        }
+    }
+
+    /// Shows or hides a layer.
+    pub fn set_visible(&mut self, layer: usize, visible: bool) {
+        if let Some(tiles) = self.layers.get_mut(layer) {
+            if tiles.visible != visible {
+                tiles.visible = visible;
+                self.changes = Changes::All;
+            }
+        }
+    }
+
+    /// The position of the center of a tile, relative to the map.
+    pub fn tile_position(&self, x: u32, y: u32) -> Vector3<f32> {
+        let [x, y] = self
+            .orientation
+            .center(self.tile_size, [self.width, self.height], x, y);
+        Vector3::new(x, -y, 0.0)
+    }
+
+    /// The column and the row of the tile at a position relative to the map, if it is on the map.
+    pub fn tile_at(&self, position: Point2<f32>) -> Option<(u32, u32)> {
+        self.orientation.tile_at(
+            self.tile_size,
+            [self.width, self.height],
+            [position.x, -position.y],
+        )
+    }
+
+    /// The Z relative to the map at which something at `position` is drawn over a layer, between
+    /// the layer and the next one. The lower on the map, the higher the Z, so that the sprites
+    /// standing lower are drawn over the ones behind them.
+    pub fn depth(&self, layer: usize, position: Point2<f32>) -> f32 {
+        let height = self.size()[1];
+        let lower = if height > 0.0 {
+            (-position.y / height).max(0.0).min(1.0)
+        } else {
+            0.0
+        };
+        LAYER_DEPTH * (layer as f32 + 0.5 * lower)
+    }
+
+    /// The tile seen at a position on the screen, from its top left corner as the mouse position
+    /// of the `InputHandler`, through a camera and on the map of the transform `map_transform`.
+    ///
+    /// Returns `None` when there is no tile under the position or when the camera looks along
+    /// the map.
+    pub fn tile_at_screen(
+        &self,
+        screen_position: Point2<f32>,
+        camera: &Camera,
+        camera_transform: &GlobalTransform,
+        map_transform: &GlobalTransform,
+        screen_dimensions: &ScreenDimensions,
+    ) -> Option<(u32, u32)> {
+        let (origin, direction) =
+            camera.screen_ray(screen_position, camera_transform, screen_dimensions)?;
+        let inverse = map_transform.0.try_inverse()?;
+        let origin = inverse.transform_point(&origin);
+        let direction = inverse.transform_vector(&direction);
+        if direction.z.abs() < 1.0e-6 {
+            return None;
+        }
+        let point = origin + direction * (-origin.z / direction.z);
+        self.tile_at(Point2::new(point.x, point.y))
+    }
+
+    /// The position on the screen of the center of a tile, from its top left corner, seen through
+    /// a camera on the map of the transform `map_transform`.
+    ///
+    /// Returns `None` when the tile is behind the camera.
+    pub fn tile_screen_position(
+        &self,
+        x: u32,
+        y: u32,
+        camera: &Camera,
+        camera_transform: &GlobalTransform,
+        map_transform: &GlobalTransform,
+        screen_dimensions: &ScreenDimensions,
+    ) -> Option<Point2<f32>> {
+        let position = map_transform
+            .0
+            .transform_point(&Point3::from(self.tile_position(x, y)));
+        camera.position_to_screen(position, camera_transform, screen_dimensions)
+    }
+
+    // Counts the changes of the tiles and of their layout.
+    pub(crate) fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    pub(crate) fn take_changes(&mut self) -> Changes {
+        std::mem::replace(&mut self.changes, Changes::Tiles(Vec::new()))
+    }
+}
+
+impl Component for TileMap {
+    type Storage = DenseVecStorage<Self>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tiles_are_changed_through_the_map() {
+        let mut map = TileMap::new(4, 3, [16.0, 8.0]);
+        let ground = map.add_layer(TileLayer::new("ground", 4, 3));
+        assert!(map.set_tile(ground, 3, 2, Some(Tile::new(0, 5))));
+        assert!(!map.set_tile(ground, 4, 0, Some(Tile::new(0, 5))));
+        assert_eq!(map.tile(ground, 3, 2), Some(Tile::new(0, 5)));
+        assert_eq!(
+            map.layer("ground").unwrap().tiles().collect::<Vec<_>>(),
+            vec![(3, 2, Tile::new(0, 5))]
+        );
+
+        match map.take_changes() {
+            Changes::All => {}
+            changes => panic!("Unexpected changes {:?}", changes),
+        }
+        map.set_tile(ground, 0, 1, None);
+        match map.take_changes() {
+            Changes::Tiles(tiles) => assert_eq!(tiles, vec![(ground, 0, 1)]),
+            changes => panic!("Unexpected changes {:?}", changes),
+        }
+    }
+
+    #[test]
+    fn positions_convert_to_tiles() {
+        let map = TileMap::new(4, 3, [16.0, 8.0]);
+        assert_eq!(map.tile_position(1, 2), Vector3::new(24.0, -20.0, 0.0));
+        assert_eq!(map.tile_at(Point2::new(24.0, -20.0)), Some((1, 2)));
+        assert_eq!(map.tile_at(Point2::new(24.0, 1.0)), None);
+    }
+
+    #[test]
+    fn lower_tiles_are_deeper() {
+        let mut map = TileMap::new(4, 3, [64.0, 32.0]);
+        map.set_orientation(Orientation::Isometric);
+        let depth = |x, y| {
+            let position = map.tile_position(x, y);
+            map.depth(1, Point2::new(position.x, position.y))
+        };
+        assert!(depth(1, 0) > depth(0, 0));
+        assert!(depth(0, 1) > depth(0, 0));
+        assert!(depth(3, 2) < 2.0 * LAYER_DEPTH);
+        assert!(depth(0, 0) > LAYER_DEPTH);
+    }
+}
@@ -0,0 +1,291 @@
+//! The grids of the tile maps: orthogonal, isometric and hexagonal.
+//!
+//! The positions are computed in the pixels of the map, from the top left corner of its bounds
+//! with Y going down as in Tiled, the `TileMap` flipping them into the space of its entity.
+
+use serde::{Deserialize, Serialize};
+
+/// The axis along which the rows or the columns of a staggered or hexagonal map are shifted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StaggerAxis {
+    /// Every other column is shifted down by half a tile, the hexagons having flat tops.
+    X,
+    /// Every other row is shifted right by half a tile, the hexagons having pointy tops.
+    Y,
+}
+
+/// Which rows or columns of a staggered or hexagonal map are shifted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StaggerIndex {
+    /// The odd ones, the first row or column not being shifted.
+    Odd,
+    /// The even ones, the first row or column being shifted.
+    Even,
+}
+
+/// How the tiles of a `TileMap` are laid out, as the orientations of the maps of Tiled.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Orientation {
+    /// A grid of rectangles, the columns going right and the rows going down.
+    Orthogonal,
+    /// A grid of diamonds, the columns going down to the right and the rows down to the left,
+    /// the first tile being at the top of the map.
+    Isometric,
+    /// Rows or columns of diamonds, every other one being shifted to fill a rectangle.
+    Staggered {
+        /// The axis along which the tiles are shifted.
+        axis: StaggerAxis,
+        /// Which rows or columns are shifted.
+        index: StaggerIndex,
+    },
+    /// Rows or columns of hexagons, every other one being shifted.
+    Hexagonal {
+        /// The length of the flat sides along the stagger axis, in the pixels of the tiles.
+        side_length: f32,
+        /// The axis along which the tiles are shifted.
+        axis: StaggerAxis,
+        /// Which rows or columns are shifted.
+        index: StaggerIndex,
+    },
+}
+
+impl Default for Orientation {
+    fn default() -> Self {
+        Orientation::Orthogonal
+    }
+}
+
+impl Orientation {
+    // The center of the tile in the column `x` and the row `y`.
+    pub(crate) fn center(self, tile: [f32; 2], map: [u32; 2], x: u32, y: u32) -> [f32; 2] {
+        let ([w, h], (column, row)) = (tile, (x as f32, y as f32));
+        match self.stagger() {
+            None if self == Orientation::Isometric => [
+                (column - row) * w / 2.0 + map[1] as f32 * w / 2.0,
+                (column + row + 1.0) * h / 2.0,
+            ],
+            None => [(column + 0.5) * w, (row + 0.5) * h],
+            Some((side, StaggerAxis::Y, index)) => {
+                let shift = if staggered(index, y) { w / 2.0 } else { 0.0 };
+                [(column + 0.5) * w + shift, row * (h + side) / 2.0 + h / 2.0]
+            }
+            Some((side, StaggerAxis::X, index)) => {
+                let shift = if staggered(index, x) { h / 2.0 } else { 0.0 };
+                [column * (w + side) / 2.0 + w / 2.0, (row + 0.5) * h + shift]
+            }
+        }
+    }
+
+    // The size of the bounds of a map.
+    pub(crate) fn size(self, tile: [f32; 2], map: [u32; 2]) -> [f32; 2] {
+        let ([w, h], [columns, rows]) = (tile, [map[0] as f32, map[1] as f32]);
+        match self.stagger() {
+            None if self == Orientation::Isometric => {
+                [(columns + rows) * w / 2.0, (columns + rows) * h / 2.0]
+            }
+            None => [columns * w, rows * h],
+            Some((side, StaggerAxis::Y, index)) => {
+                let shifted = map[1] > 1 || index == StaggerIndex::Even;
+                [
+                    columns * w + if shifted { w / 2.0 } else { 0.0 },
+                    (rows - 1.0).max(0.0) * (h + side) / 2.0 + h,
+                ]
+            }
+            Some((side, StaggerAxis::X, index)) => {
+                let shifted = map[0] > 1 || index == StaggerIndex::Even;
+                [
+                    (columns - 1.0).max(0.0) * (w + side) / 2.0 + w,
+                    rows * h + if shifted { h / 2.0 } else { 0.0 },
+                ]
+            }
+        }
+    }
+
+    // The column and the row of the tile containing a point, if it is on the map.
+    pub(crate) fn tile_at(
+        self,
+        tile: [f32; 2],
+        map: [u32; 2],
+        point: [f32; 2],
+    ) -> Option<(u32, u32)> {
+        let [w, h] = tile;
+        let (x, y) = match self.stagger() {
+            None if self == Orientation::Isometric => {
+                let px = point[0] - map[1] as f32 * w / 2.0;
+                (
+                    (point[1] / h + px / w).floor(),
+                    (point[1] / h - px / w).floor(),
+                )
+            }
+            None => ((point[0] / w).floor(), (point[1] / h).floor()),
+            Some((side, axis, _)) => {
+                // The tile of the point is one of the neighbours of the one it is roughly in,
+                // the one whose hexagon contains the point.
+                let (x, y) = match axis {
+                    StaggerAxis::Y => (
+                        (point[0] / w).floor(),
+                        (point[1] / ((h + side) / 2.0)).floor(),
+                    ),
+                    StaggerAxis::X => (
+                        (point[0] / ((w + side) / 2.0)).floor(),
+                        (point[1] / h).floor(),
+                    ),
+                };
+                let mut closest = None;
+                for ny in (y as i64 - 1)..=(y as i64 + 1) {
+                    for nx in (x as i64 - 1)..=(x as i64 + 1) {
+                        if nx < 0 || ny < 0 || nx >= map[0] as i64 || ny >= map[1] as i64 {
+                            continue;
+                        }
+                        let center = self.center(tile, map, nx as u32, ny as u32);
+                        let gauge = hex_gauge(
+                            tile,
+                            side,
+                            axis,
+                            [point[0] - center[0], point[1] - center[1]],
+                        );
+                        if closest.map_or(true, |(_, _, closest)| gauge < closest) {
+                            closest = Some((nx, ny, gauge));
+                        }
+                    }
+                }
+                match closest {
+                    Some((x, y, gauge)) if gauge <= 1.0 + 1.0e-4 => (x as f32, y as f32),
+                    _ => return None,
+                }
+            }
+        };
+        if x >= 0.0 && y >= 0.0 && x < map[0] as f32 && y < map[1] as f32 {
+            Some((x as u32, y as u32))
+        } else {
+            None
+        }
+    }
+
+    // The corners of the tile in the column `x` and the row `y`, counterclockwise on the screen.
+    pub(crate) fn outline(self, tile: [f32; 2], map: [u32; 2], x: u32, y: u32) -> Vec<[f32; 2]> {
+        let ([w, h], [cx, cy]) = (tile, self.center(tile, map, x, y));
+        let mut corners = match self.stagger() {
+            None if self == Orientation::Isometric => {
+                vec![[0.0, -h], [-w, 0.0], [0.0, h], [w, 0.0]]
+            }
+            None => vec![[-w, -h], [-w, h], [w, h], [w, -h]],
+            Some((side, StaggerAxis::Y, _)) => vec![
+                [0.0, -h],
+                [-w, -side],
+                [-w, side],
+                [0.0, h],
+                [w, side],
+                [w, -side],
+            ],
+            Some((side, StaggerAxis::X, _)) => vec![
+                [-w, 0.0],
+                [-side, h],
+                [side, h],
+                [w, 0.0],
+                [side, -h],
+                [-side, -h],
+            ],
+        };
+        // The staggered maps are hexagonal maps whose sides are points.
+        corners.dedup();
+        corners
+            .into_iter()
+            .map(|[dx, dy]| [cx + dx / 2.0, cy + dy / 2.0])
+            .collect()
+    }
+
+    // The side length, axis and index of the staggered and hexagonal maps, the staggered maps
+    // being hexagonal maps whose sides have no length.
+    fn stagger(self) -> Option<(f32, StaggerAxis, StaggerIndex)> {
+        match self {
+            Orientation::Orthogonal | Orientation::Isometric => None,
+            Orientation::Staggered { axis, index } => Some((0.0, axis, index)),
+            Orientation::Hexagonal {
+                side_length,
+                axis,
+                index,
+            } => Some((side_length, axis, index)),
+        }
+    }
+}
+
+fn staggered(index: StaggerIndex, i: u32) -> bool {
+    match index {
+        StaggerIndex::Odd => i % 2 == 1,
+        StaggerIndex::Even => i % 2 == 0,
+    }
+}
+
+// How far a point relative to the center of a hexagon is towards its outline, at 1.
+fn hex_gauge(tile: [f32; 2], side: f32, axis: StaggerAxis, point: [f32; 2]) -> f32 {
+    let ([w, h], [x, y]) = (tile, [point[0].abs(), point[1].abs()]);
+    match axis {
+        StaggerAxis::Y => (2.0 * x / w).max(2.0 * (h - side) * x / (w * h) + 2.0 * y / h),
+        StaggerAxis::X => (2.0 * y / h).max(2.0 * x / w + 2.0 * (w - side) * y / (w * h)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_centers_are_in_their_tiles() {
+        let orientations = [
+            Orientation::Orthogonal,
+            Orientation::Isometric,
+            Orientation::Staggered {
+                axis: StaggerAxis::X,
+                index: StaggerIndex::Even,
+            },
+            Orientation::Hexagonal {
+                side_length: 16.0,
+                axis: StaggerAxis::Y,
+                index: StaggerIndex::Odd,
+            },
+            Orientation::Hexagonal {
+                side_length: 8.0,
+                axis: StaggerAxis::X,
+                index: StaggerIndex::Odd,
+            },
+        ];
+        let (tile, map) = ([32.0, 28.0], [5, 4]);
+        for &orientation in &orientations {
+            let size = orientation.size(tile, map);
+            for y in 0..map[1] {
+                for x in 0..map[0] {
+                    let center = orientation.center(tile, map, x, y);
+                    assert!(center[0] > 0.0 && center[0] < size[0], "{:?}", orientation);
+                    assert!(center[1] > 0.0 && center[1] < size[1], "{:?}", orientation);
+                    assert_eq!(
+                        orientation.tile_at(tile, map, center),
+                        Some((x, y)),
+                        "{:?}",
+                        orientation
+                    );
+                }
+            }
+            assert_eq!(orientation.tile_at(tile, map, [-1.0, -1.0]), None);
+        }
+    }
+
+    #[test]
+    fn positions_follow_the_orientation() {
+        let (tile, map) = ([64.0, 32.0], [4, 3]);
+        let iso = Orientation::Isometric;
+        assert_eq!(iso.center(tile, map, 0, 0), [96.0, 16.0]);
+        assert_eq!(iso.center(tile, map, 1, 0), [128.0, 32.0]);
+        assert_eq!(iso.size(tile, map), [224.0, 112.0]);
+
+        let hex = Orientation::Hexagonal {
+            side_length: 16.0,
+            axis: StaggerAxis::Y,
+            index: StaggerIndex::Odd,
+        };
+        assert_eq!(hex.center(tile, map, 0, 1), [64.0, 40.0]);
+        // Above the pointy top of the hexagon of the second row, between the ones of the first.
+        assert_eq!(hex.tile_at(tile, map, [64.0, 2.0]), None);
+        assert_eq!(hex.tile_at(tile, map, [64.0, 30.0]), Some((0, 1)));
+    }
+}
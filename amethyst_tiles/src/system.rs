@@ -0,0 +1,362 @@
+//! The system drawing the tile maps.
+
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap, HashSet},
+};
+
+use amethyst_assets::{AssetStorage, Loader};
+use amethyst_core::{
+    ecs::prelude::{Entities, Entity, Join, Read, ReadExpect, System, WriteStorage},
+    math::{Point2, Vector2, Vector3},
+    transform::{Parent, Transform},
+};
+use amethyst_renderer::{
+    Material, MaterialDefaults, Mesh, MeshHandle, PosTex, Sprite, SpriteSheet, TextureHandle,
+    Transparent,
+};
+
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use crate::map::{Changes, TileMap};
+
+/// How far apart the layers of a map are along Z, the tiles of a layer being drawn in the first
+/// half of that space, see `TileMap::depth`.
+pub const LAYER_DEPTH: f32 = 0.01;
+
+/// The width and height of the chunks of a layer drawn as one mesh per tile set, in tiles.
+pub const CHUNK_TILES: u32 = 16;
+
+// A chunk of a map: its layer, column and row of chunks.
+type Chunk = (usize, u32, u32);
+
+// The vertices of the tiles of a chunk drawn with a tile set, with its texture.
+type Batch = (TextureHandle, Vec<PosTex>);
+
+// The meshes drawing a map.
+#[derive(Default)]
+struct MapMeshes {
+    // The entity of the mesh of each chunk and tile set.
+    meshes: HashMap<(Chunk, usize), Entity>,
+    // The chunks to build again, kept while the sprite sheets of their tiles are loading.
+    dirty: HashSet<Chunk>,
+}
+
+/// Draws the `TileMap`s, with a mesh for each tile set used in a chunk of `CHUNK_TILES` by
+/// `CHUNK_TILES` tiles of a layer, on an entity child of the entity of its map. The meshes of a
+/// chunk are built again when its tiles change, and deleted with their map.
+///
+/// The meshes are drawn with the textures of the tile sets by a `DrawFlat::<PosTex>` pass with
+/// transparency, the entities walking on the map by the `DrawFlat2D` pass at their
+/// `TileMap::depth`.
+#[derive(Default)]
+pub struct TileMapSystem {
+    maps: HashMap<Entity, MapMeshes>,
+}
+
+impl TileMapSystem {
+    /// Creates a new `TileMapSystem`.
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl<'a> System<'a> for TileMapSystem {
+    type SystemData = (
+        Entities<'a>,
+        ReadExpect<'a, Loader>,
+        Read<'a, AssetStorage<Mesh>>,
+        Read<'a, AssetStorage<SpriteSheet>>,
+        ReadExpect<'a, MaterialDefaults>,
+        WriteStorage<'a, TileMap>,
+        WriteStorage<'a, Transform>,
+        WriteStorage<'a, Parent>,
+        WriteStorage<'a, MeshHandle>,
+        WriteStorage<'a, Material>,
+        WriteStorage<'a, Transparent>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            loader,
+            mesh_storage,
+            sheets,
+            material_defaults,
+            mut maps,
+            mut transforms,
+            mut parents,
+            mut meshes,
+            mut materials,
+            mut transparent,
+        ): Self::SystemData,
+    ) {
+        #[cfg(feature = "profiler")]
+        profile_scope!("tile_map_system");
+
+        let removed = self
+            .maps
+            .keys()
+            .filter(|map| !maps.contains(**map))
+            .cloned()
+            .collect::<Vec<_>>();
+        for map in removed {
+            for (_, mesh) in self
+                .maps
+                .remove(&map)
+                .into_iter()
+                .flat_map(|map| map.meshes)
+            {
+                let _ = entities.delete(mesh);
+            }
+        }
+
+        for (entity, map) in (&*entities, &mut maps).join() {
+            let batches = self.maps.entry(entity).or_insert_with(MapMeshes::default);
+            match map.take_changes() {
+                Changes::All => {
+                    for (_, mesh) in batches.meshes.drain() {
+                        let _ = entities.delete(mesh);
+                    }
+                    batches.dirty.clear();
+                    for (layer, tiles) in map.layers().iter().enumerate() {
+                        for y in 0..chunks(tiles.height()) {
+                            for x in 0..chunks(tiles.width()) {
+                                batches.dirty.insert((layer, x, y));
+                            }
+                        }
+                    }
+                }
+                Changes::Tiles(cells) => batches.dirty.extend(
+                    cells
+                        .into_iter()
+                        .map(|(layer, x, y)| (layer, x / CHUNK_TILES, y / CHUNK_TILES)),
+                ),
+            }
+
+            for chunk in batches.dirty.drain().collect::<Vec<_>>() {
+                let quads = match chunk_quads(map, chunk, &sheets) {
+                    Some(quads) => quads,
+                    None => {
+                        batches.dirty.insert(chunk);
+                        continue;
+                    }
+                };
+                let stale = batches
+                    .meshes
+                    .keys()
+                    .filter(|(key, tileset)| *key == chunk && !quads.contains_key(tileset))
+                    .cloned()
+                    .collect::<Vec<_>>();
+                for key in stale {
+                    if let Some(mesh) = batches.meshes.remove(&key) {
+                        let _ = entities.delete(mesh);
+                    }
+                }
+
+                for (tileset, (texture, vertices)) in quads {
+                    let mesh = loader.load_from_data(vertices.into(), (), &mesh_storage);
+                    let material = Material {
+                        albedo: texture,
+                        ..material_defaults.0.clone()
+                    };
+                    match batches.meshes.get(&(chunk, tileset)).cloned() {
+                        Some(batch) => {
+                            meshes
+                                .insert(batch, mesh)
+                                .expect("Unreachable: Entity is alive");
+                            materials
+                                .insert(batch, material)
+                                .expect("Unreachable: Entity is alive");
+                        }
+                        None => {
+                            let mut transform = Transform::default();
+                            transform.set_translation_z(LAYER_DEPTH * chunk.0 as f32);
+                            let batch = entities
+                                .build_entity()
+                                .with(transform, &mut transforms)
+                                .with(Parent { entity }, &mut parents)
+                                .with(mesh, &mut meshes)
+                                .with(material, &mut materials)
+                                .with(Transparent, &mut transparent)
+                                .build();
+                            batches.meshes.insert((chunk, tileset), batch);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+// The number of chunks covering `tiles` tiles.
+fn chunks(tiles: u32) -> u32 {
+    (tiles + CHUNK_TILES - 1) / CHUNK_TILES
+}
+
+// The vertices of the visible tiles of a chunk by tile set, the tiles lower on the map after the
+// ones behind them, or `None` while a sprite sheet of the tiles is loading.
+fn chunk_quads(
+    map: &TileMap,
+    (layer, chunk_x, chunk_y): Chunk,
+    sheets: &AssetStorage<SpriteSheet>,
+) -> Option<BTreeMap<usize, Batch>> {
+    let mut batches = BTreeMap::new();
+    let tiles = match map.layers().get(layer) {
+        Some(tiles) if tiles.visible => tiles,
+        _ => return Some(batches),
+    };
+    let base = LAYER_DEPTH * layer as f32;
+    let right = ((chunk_x + 1) * CHUNK_TILES).min(tiles.width());
+    let bottom = ((chunk_y + 1) * CHUNK_TILES).min(tiles.height());
+
+    let mut quads = Vec::new();
+    for y in chunk_y * CHUNK_TILES..bottom {
+        for x in chunk_x * CHUNK_TILES..right {
+            let tile = match tiles.tile(x, y) {
+                Some(tile) => tile,
+                None => continue,
+            };
+            let sheet = match map.tilesets.get(tile.tileset) {
+                Some(handle) => sheets.get(handle)?,
+                None => continue,
+            };
+            if let Some(sprite) = sheet.sprites.get(tile.sprite) {
+                let center = map.tile_position(x, y);
+                let depth = map.depth(layer, Point2::new(center.x, center.y)) - base;
+                quads.push((depth, tile.tileset, &sheet.texture, sprite, center));
+            }
+        }
+    }
+    quads.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+
+    for (depth, tileset, texture, sprite, center) in quads {
+        let (_, vertices) = batches
+            .entry(tileset)
+            .or_insert_with(|| (texture.clone(), Vec::new()));
+        vertices.extend_from_slice(&quad(sprite, center, depth));
+    }
+    Some(batches)
+}
+
+// The two triangles of a sprite centered on `center` and moved by its offsets, as the
+// `DrawFlat2D` pass draws it.
+fn quad(sprite: &Sprite, center: Vector3<f32>, depth: f32) -> [PosTex; 6] {
+    let x = center.x - sprite.offsets[0];
+    let y = center.y - sprite.offsets[1];
+    let (half_width, half_height) = (sprite.width / 2.0, sprite.height / 2.0);
+    let coords = &sprite.tex_coords;
+    let vertex = |dx: f32, dy: f32, u: f32, v: f32| PosTex {
+        position: Vector3::new(x + dx, y + dy, depth),
+        tex_coord: Vector2::new(u, v),
+    };
+    let bottom_left = vertex(-half_width, -half_height, coords.left, coords.bottom);
+    let bottom_right = vertex(half_width, -half_height, coords.right, coords.bottom);
+    let top_left = vertex(-half_width, half_height, coords.left, coords.top);
+    let top_right = vertex(half_width, half_height, coords.right, coords.top);
+    [
+        bottom_left,
+        bottom_right,
+        top_right,
+        bottom_left,
+        top_right,
+        top_left,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use amethyst_core::ecs::{Builder, RunNow, World};
+    use amethyst_renderer::{Texture, TextureOffset};
+    use rayon::{ThreadPool, ThreadPoolBuilder};
+
+    use super::*;
+    use crate::map::{Tile, TileLayer};
+
+    #[test]
+    fn chunks_get_a_mesh_per_tile_set() {
+        let pool = Arc::new(ThreadPoolBuilder::new().build().unwrap());
+        let mut world = World::new();
+        let mut system = TileMapSystem::new();
+        System::setup(&mut system, &mut world.res);
+        world.add_resource(AssetStorage::<Texture>::default());
+        world.add_resource(AssetStorage::<Mesh>::default());
+        world.add_resource(AssetStorage::<SpriteSheet>::default());
+        world.add_resource(Loader::new(".", pool.clone()));
+        let (texture, sheet) = world.exec(
+            |(loader, textures, sheets): (
+                ReadExpect<'_, Loader>,
+                Read<'_, AssetStorage<Texture>>,
+                Read<'_, AssetStorage<SpriteSheet>>,
+            )| {
+                let texture: TextureHandle =
+                    loader.load_from_data([1., 1., 1., 1.].into(), (), &textures);
+                let sprites = (0..2)
+                    .map(|i| Sprite::from_pixel_values(32, 16, 16, 16, i * 16, 0, [0.0; 2]))
+                    .collect();
+                let sheet = SpriteSheet {
+                    texture: texture.clone(),
+                    sprites,
+                };
+                (texture, loader.load_from_data(sheet, (), &sheets))
+            },
+        );
+        world.add_resource(MaterialDefaults(material(texture)));
+
+        let mut map = TileMap::new(20, 2, [16.0, 16.0]);
+        map.tilesets.push(sheet);
+        let layer = map.add_layer(TileLayer::new("ground", 20, 2));
+        map.set_tile(layer, 0, 0, Some(Tile::new(0, 0)));
+        map.set_tile(layer, 1, 1, Some(Tile::new(0, 1)));
+        map.set_tile(layer, 17, 0, Some(Tile::new(0, 1)));
+        let map = world.create_entity().with(map).build();
+        let mut meshes = |world: &mut World, pool: &ThreadPool| {
+            world
+                .write_resource::<AssetStorage<SpriteSheet>>()
+                .process(Into::into, 0, pool, None);
+            system.run_now(&world.res);
+            world.maintain();
+            world.read_storage::<MeshHandle>().join().count()
+        };
+
+        assert_eq!(meshes(&mut world, &pool), 2);
+        world
+            .write_storage::<TileMap>()
+            .get_mut(map)
+            .unwrap()
+            .set_tile(layer, 0, 0, None);
+        assert_eq!(meshes(&mut world, &pool), 2);
+        world
+            .write_storage::<TileMap>()
+            .get_mut(map)
+            .unwrap()
+            .set_tile(layer, 1, 1, None);
+        assert_eq!(meshes(&mut world, &pool), 1);
+        world.delete_entity(map).unwrap();
+        assert_eq!(meshes(&mut world, &pool), 0);
+    }
+
+    fn material(texture: TextureHandle) -> Material {
+        Material {
+            alpha_cutoff: 0.01,
+            albedo: texture.clone(),
+            albedo_offset: TextureOffset::default(),
+            emission: texture.clone(),
+            emission_offset: TextureOffset::default(),
+            normal: texture.clone(),
+            normal_offset: TextureOffset::default(),
+            metallic: texture.clone(),
+            metallic_offset: TextureOffset::default(),
+            roughness: texture.clone(),
+            roughness_offset: TextureOffset::default(),
+            ambient_occlusion: texture.clone(),
+            ambient_occlusion_offset: TextureOffset::default(),
+            caveat: texture,
+            caveat_offset: TextureOffset::default(),
+        }
+    }
+}
@@ -0,0 +1,47 @@
+use std::ops::Range;
+
+use unicode_bidi::BidiInfo;
+
+/// The runs of a line of a text analysed with the Unicode bidirectional algorithm, in display
+/// order from left to right, with whether each run is right-to-left.
+///
+/// Lines are reordered once broken, so that the lines of a wrapped right-to-left paragraph stay
+/// in reading order. A line crossing paragraphs, as when line breaks are ignored, is reordered
+/// paragraph by paragraph.
+pub(crate) fn visual_runs(info: &BidiInfo<'_>, line: Range<usize>) -> Vec<(Range<usize>, bool)> {
+    let mut runs = Vec::new();
+    for paragraph in &info.paragraphs {
+        let start = line.start.max(paragraph.range.start);
+        let end = line.end.min(paragraph.range.end);
+        if start >= end {
+            continue;
+        }
+        let (levels, level_runs) = info.visual_runs(paragraph, start..end);
+        runs.extend(level_runs.into_iter().map(|run| {
+            let rtl = levels[run.start].is_rtl();
+            (run, rtl)
+        }));
+    }
+    runs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines_are_reordered_apart() {
+        // Two right-to-left words, each on its own line.
+        let text = "\u{5d0}\u{5d1} \u{5d2}\u{5d3}";
+        let info = BidiInfo::new(text, None);
+        assert_eq!(visual_runs(&info, 0..5), vec![(0..5, true)]);
+        assert_eq!(visual_runs(&info, 5..9), vec![(5..9, true)]);
+
+        let text = "abc \u{5d0}\u{5d1}";
+        let info = BidiInfo::new(text, None);
+        assert_eq!(
+            visual_runs(&info, 0..text.len()),
+            vec![(0..4, false), (4..8, true)]
+        );
+    }
+}
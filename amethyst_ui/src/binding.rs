@@ -0,0 +1,316 @@
+//! Bindings keeping ui widgets in sync with resources and components.
+
+use std::marker::PhantomData;
+
+use shred_derive::SystemData;
+
+use amethyst_core::ecs::prelude::{
+    Component, DenseVecStorage, Entities, Entity, Join, Read, ReadStorage, System, WriteStorage,
+};
+
+use crate::{UiFill, UiProgress, UiText};
+
+/// A value produced by a binding and written to the bound widget.
+#[derive(Clone, Debug, PartialEq)]
+pub enum UiBindingValue {
+    /// Replaces the text of the `UiText` of the entity.
+    Text(String),
+    /// Sets the value of the `UiProgress` of the entity, or of its `UiFill` if it has no
+    /// `UiProgress`.
+    Progress(f32),
+}
+
+impl From<String> for UiBindingValue {
+    fn from(text: String) -> Self {
+        UiBindingValue::Text(text)
+    }
+}
+
+impl<'a> From<&'a str> for UiBindingValue {
+    fn from(text: &'a str) -> Self {
+        UiBindingValue::Text(text.to_string())
+    }
+}
+
+impl From<f32> for UiBindingValue {
+    fn from(value: f32) -> Self {
+        UiBindingValue::Progress(value)
+    }
+}
+
+type BindingFn<T> = Box<dyn Fn(&T) -> UiBindingValue + Send + Sync>;
+
+/// Binds the widget of this entity to the resource `R`.
+///
+/// The `UiResourceBindingSystem<R>` evaluates the binding every frame and writes the value to the
+/// widget when it changed.
+pub struct UiResourceBinding<R> {
+    binding: BindingFn<R>,
+}
+
+impl<R: Send + Sync + 'static> UiResourceBinding<R> {
+    /// Creates a binding from a function reading the resource.
+    pub fn new<F, V>(binding: F) -> Self
+    where
+        F: Fn(&R) -> V + Send + Sync + 'static,
+        V: Into<UiBindingValue>,
+    {
+        UiResourceBinding {
+            binding: Box::new(move |resource| binding(resource).into()),
+        }
+    }
+
+    /// Binds the text of the `UiText` of this entity.
+    pub fn text<F>(binding: F) -> Self
+    where
+        F: Fn(&R) -> String + Send + Sync + 'static,
+    {
+        UiResourceBinding::new(binding)
+    }
+
+    /// Binds the value of the progress bar or `UiFill` of this entity.
+    pub fn progress<F>(binding: F) -> Self
+    where
+        F: Fn(&R) -> f32 + Send + Sync + 'static,
+    {
+        UiResourceBinding::new(binding)
+    }
+}
+
+impl<R: Send + Sync + 'static> Component for UiResourceBinding<R> {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// Binds the widget of this entity to the component `C` of another entity.
+///
+/// The `UiComponentBindingSystem<C>` evaluates the binding every frame and writes the value to
+/// the widget when it changed. Nothing is written while the source entity has no `C`.
+pub struct UiComponentBinding<C> {
+    /// The entity the component is read from.
+    pub source: Entity,
+    binding: BindingFn<C>,
+}
+
+impl<C: Component> UiComponentBinding<C> {
+    /// Creates a binding from a function reading the component of `source`.
+    pub fn new<F, V>(source: Entity, binding: F) -> Self
+    where
+        F: Fn(&C) -> V + Send + Sync + 'static,
+        V: Into<UiBindingValue>,
+    {
+        UiComponentBinding {
+            source,
+            binding: Box::new(move |component| binding(component).into()),
+        }
+    }
+
+    /// Binds the text of the `UiText` of this entity.
+    pub fn text<F>(source: Entity, binding: F) -> Self
+    where
+        F: Fn(&C) -> String + Send + Sync + 'static,
+    {
+        UiComponentBinding::new(source, binding)
+    }
+
+    /// Binds the value of the progress bar or `UiFill` of this entity.
+    pub fn progress<F>(source: Entity, binding: F) -> Self
+    where
+        F: Fn(&C) -> f32 + Send + Sync + 'static,
+    {
+        UiComponentBinding::new(source, binding)
+    }
+}
+
+impl<C: Component> Component for UiComponentBinding<C> {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// The widget storages written by the binding systems.
+#[derive(SystemData)]
+pub struct BoundWidgets<'a> {
+    texts: WriteStorage<'a, UiText>,
+    progresses: WriteStorage<'a, UiProgress>,
+    fills: WriteStorage<'a, UiFill>,
+}
+
+impl<'a> BoundWidgets<'a> {
+    #[allow(clippy::float_cmp)] // cmp just used to recognize change
+    fn apply(&mut self, entity: Entity, value: UiBindingValue) {
+        match value {
+            UiBindingValue::Text(text) => {
+                if let Some(ui_text) = self.texts.get_mut(entity) {
+                    if ui_text.text != text {
+                        ui_text.text = text;
+                    }
+                }
+            }
+            UiBindingValue::Progress(value) => {
+                if let Some(progress) = self.progresses.get_mut(entity) {
+                    if progress.value != value {
+                        progress.value = value;
+                    }
+                } else if let Some(fill) = self.fills.get_mut(entity) {
+                    if fill.value != value {
+                        fill.value = value;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Keeps the widgets with a `UiResourceBinding<R>` in sync with the resource `R`.
+///
+/// One such system has to be added for every bound resource type, before the
+/// `UiBundle` to have widgets updated in the same frame.
+pub struct UiResourceBindingSystem<R> {
+    _marker: PhantomData<R>,
+}
+
+impl<R> UiResourceBindingSystem<R> {
+    /// Creates a new `UiResourceBindingSystem`.
+    pub fn new() -> Self {
+        UiResourceBindingSystem {
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<R> Default for UiResourceBindingSystem<R> {
+    fn default() -> Self {
+        UiResourceBindingSystem::new()
+    }
+}
+
+impl<'a, R: Send + Sync + 'static> System<'a> for UiResourceBindingSystem<R> {
+    type SystemData = (
+        Entities<'a>,
+        Option<Read<'a, R>>,
+        ReadStorage<'a, UiResourceBinding<R>>,
+        BoundWidgets<'a>,
+    );
+
+    fn run(&mut self, (entities, resource, bindings, mut widgets): Self::SystemData) {
+        let resource = match resource {
+            Some(resource) => resource,
+            None => return,
+        };
+        for (entity, binding) in (&*entities, &bindings).join() {
+            widgets.apply(entity, (binding.binding)(&resource));
+        }
+    }
+}
+
+/// Keeps the widgets with a `UiComponentBinding<C>` in sync with the component `C` of their
+/// source entity.
+///
+/// One such system has to be added for every bound component type, before the
+/// `UiBundle` to have widgets updated in the same frame.
+pub struct UiComponentBindingSystem<C> {
+    _marker: PhantomData<C>,
+}
+
+impl<C> UiComponentBindingSystem<C> {
+    /// Creates a new `UiComponentBindingSystem`.
+    pub fn new() -> Self {
+        UiComponentBindingSystem {
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<C> Default for UiComponentBindingSystem<C> {
+    fn default() -> Self {
+        UiComponentBindingSystem::new()
+    }
+}
+
+impl<'a, C: Component> System<'a> for UiComponentBindingSystem<C> {
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, C>,
+        ReadStorage<'a, UiComponentBinding<C>>,
+        BoundWidgets<'a>,
+    );
+
+    fn run(&mut self, (entities, components, bindings, mut widgets): Self::SystemData) {
+        for (entity, binding) in (&*entities, &bindings).join() {
+            if let Some(component) = components.get(binding.source) {
+                widgets.apply(entity, (binding.binding)(component));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::ecs::prelude::{Builder, RunNow, World};
+
+    use super::*;
+    use crate::UiFillMode;
+
+    struct Loading(f32);
+
+    struct Health(f32);
+
+    impl Component for Health {
+        type Storage = DenseVecStorage<Self>;
+    }
+
+    fn fill(world: &World, entity: Entity) -> f32 {
+        world.read_storage::<UiFill>().get(entity).unwrap().value
+    }
+
+    #[test]
+    fn bound_widgets_follow_their_resource() {
+        let mut world = World::new();
+        let mut system = UiResourceBindingSystem::<Loading>::new();
+        System::setup(&mut system, &mut world.res);
+        let bar = world
+            .create_entity()
+            .with(UiFill::new(UiFillMode::LeftToRight, 0.))
+            .with(UiResourceBinding::<Loading>::progress(|loading| loading.0))
+            .build();
+
+        // Nothing is written while there is no resource.
+        system.run_now(&world.res);
+        assert_eq!(fill(&world, bar), 0.);
+
+        world.add_resource(Loading(0.5));
+        system.run_now(&world.res);
+        assert_eq!(fill(&world, bar), 0.5);
+    }
+
+    #[test]
+    fn bound_widgets_follow_the_component_of_their_source() {
+        let mut world = World::new();
+        let mut system = UiComponentBindingSystem::<Health>::new();
+        System::setup(&mut system, &mut world.res);
+        let player = world.create_entity().with(Health(30.)).build();
+        let fill_entity = world
+            .create_entity()
+            .with(UiFill::new(UiFillMode::LeftToRight, 0.))
+            .build();
+        let bar = world
+            .create_entity()
+            .with(UiProgress::new(0., fill_entity, None))
+            .with(UiFill::new(UiFillMode::LeftToRight, 0.))
+            .with(UiComponentBinding::progress(player, |health: &Health| {
+                health.0 / 100.
+            }))
+            .build();
+        let progress = |world: &World| world.read_storage::<UiProgress>().get(bar).unwrap().value;
+
+        system.run_now(&world.res);
+        // The progress bar is written rather than the fill of the same entity.
+        assert_eq!((progress(&world), fill(&world, bar)), (0.3, 0.));
+
+        world.write_storage::<Health>().get_mut(player).unwrap().0 = 80.;
+        system.run_now(&world.res);
+        assert_eq!(progress(&world), 0.8);
+
+        world.write_storage::<Health>().remove(player);
+        system.run_now(&world.res);
+        assert_eq!(progress(&world), 0.8);
+    }
+}
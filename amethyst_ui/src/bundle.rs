@@ -3,18 +3,26 @@
 use std::{hash::Hash, marker::PhantomData};
 
 use derive_new::new;
+use serde::{de::DeserializeOwned, Serialize};
 
 use amethyst_assets::Processor;
 use amethyst_audio::AudioFormat;
-use amethyst_core::{bundle::SystemBundle, ecs::prelude::DispatcherBuilder};
+use amethyst_core::{
+    bundle::{BundleBuilder, BundleRequirement, SystemBundle},
+    reflect::{ComponentRegistry, Reflect},
+    shred::Resource,
+};
 use amethyst_error::Error;
 use amethyst_renderer::{BlinkSystem, TextureFormat};
 
 use crate::{
-    CacheSelectionOrderSystem, FontAsset, FontFormat, NoCustomUi, ResizeSystem,
+    CacheSelectionOrderSystem, ConsoleCommand, FontAsset, FontFormat, NoCustomUi, ResizeSystem,
     SelectionKeyboardSystem, SelectionMouseSystem, TextEditingInputSystem, TextEditingMouseSystem,
-    ToNativeWidget, UiButtonActionRetriggerSystem, UiButtonSystem, UiLoaderSystem, UiMouseSystem,
-    UiSoundRetriggerSystem, UiSoundSystem, UiTransformSystem, WidgetId,
+    ToNativeWidget, UiButtonActionRetriggerSystem, UiButtonSystem, UiConsoleSystem, UiDragSystem,
+    UiInspectorSystem, UiListViewScrollSystem, UiLoaderSystem, UiLogPanelSystem, UiMouseSystem,
+    UiProgressBarSystem, UiSoundRetriggerSystem, UiSoundSystem, UiStyleSheet, UiStyleSystem,
+    UiTooltipSystem, UiTransformSystem, UiTransitionSystem, UiWindowSystem, UiWorldAnchorSystem,
+    WidgetId,
 };
 
 /// UI bundle
@@ -37,7 +45,7 @@ where
     W: WidgetId,
     G: Send + Sync + PartialEq + 'static,
 {
-    fn build(self, builder: &mut DispatcherBuilder<'a, 'b>) -> Result<(), Error> {
+    fn build(self, builder: &mut BundleBuilder<'a, 'b>) -> Result<(), Error> {
         builder.add(
             UiLoaderSystem::<
                 AudioFormat,
@@ -49,10 +57,41 @@ where
             "ui_loader",
             &[],
         );
+        builder.add(
+            Processor::<UiStyleSheet>::new(),
+            "ui_style_sheet_processor",
+            &[],
+        );
+        builder.add(
+            UiStyleSystem::new(),
+            "ui_style_system",
+            &["ui_loader", "ui_style_sheet_processor"],
+        );
+        builder.add(
+            UiTransitionSystem::new(),
+            "ui_transition_system",
+            &["ui_style_system"],
+        );
+        builder.add(
+            UiWindowSystem::new(),
+            "ui_window_system",
+            &["ui_transition_system"],
+        );
+        builder.add(
+            UiWorldAnchorSystem::new(),
+            "ui_world_anchor_system",
+            &["transform_system", "ui_transition_system"],
+        );
         builder.add(
             UiTransformSystem::default(),
             "ui_transform",
-            &["transform_system"],
+            &[
+                "transform_system",
+                "ui_style_system",
+                "ui_transition_system",
+                "ui_window_system",
+                "ui_world_anchor_system",
+            ],
         );
         builder.add(
             Processor::<FontAsset>::new(),
@@ -87,6 +126,11 @@ where
             &["ui_mouse_selection", "ui_keyboard_selection"],
         );
         builder.add(ResizeSystem::new(), "ui_resize_system", &[]);
+        builder.add(
+            UiListViewScrollSystem::new(),
+            "ui_list_view_scroll_system",
+            &["ui_transform"],
+        );
         builder.add(
             UiMouseSystem::<A, B>::new(),
             "ui_mouse_system",
@@ -97,6 +141,16 @@ where
             "ui_button_system",
             &["ui_mouse_system"],
         );
+        builder.add(
+            UiDragSystem::<A, B>::new(),
+            "ui_drag_system",
+            &["ui_mouse_system"],
+        );
+        builder.add(
+            UiTooltipSystem::new(),
+            "ui_tooltip_system",
+            &["ui_mouse_system"],
+        );
 
         builder.add(
             UiButtonActionRetriggerSystem::new(),
@@ -104,6 +158,7 @@ where
             &["ui_button_system"],
         );
         builder.add(UiSoundSystem::new(), "ui_sound_system", &[]);
+        builder.add(UiProgressBarSystem::new(), "ui_progress_bar_system", &[]);
         builder.add(
             UiSoundRetriggerSystem::new(),
             "ui_sound_retrigger_system",
@@ -115,4 +170,123 @@ where
 
         Ok(())
     }
+
+    fn name(&self) -> &'static str {
+        "UiBundle"
+    }
+
+    fn requirements(&self) -> Vec<BundleRequirement> {
+        vec![BundleRequirement::new(
+            "transform_system",
+            "TransformBundle",
+        )]
+    }
+}
+
+/// Bundle adding the `UiConsoleSystem`, showing the `UiConsole` developer console.
+///
+/// Has to be added after the `UiBundle`.
+#[derive(Default, new)]
+pub struct UiConsoleBundle {
+    #[new(default)]
+    commands: Vec<ConsoleCommand>,
+}
+
+impl UiConsoleBundle {
+    /// Registers a command in the console.
+    pub fn with_command(mut self, command: ConsoleCommand) -> Self {
+        self.commands.push(command);
+        self
+    }
+}
+
+impl<'a, 'b> SystemBundle<'a, 'b> for UiConsoleBundle {
+    fn build(self, builder: &mut BundleBuilder<'a, 'b>) -> Result<(), Error> {
+        builder.add(
+            UiConsoleSystem::new().with_commands(self.commands),
+            "ui_console_system",
+            &["ui_text_editing_input_system", "ui_mouse_system"],
+        );
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        "UiConsoleBundle"
+    }
+
+    fn requirements(&self) -> Vec<BundleRequirement> {
+        vec![
+            BundleRequirement::new("ui_text_editing_input_system", "UiBundle"),
+            BundleRequirement::new("ui_mouse_system", "UiBundle"),
+        ]
+    }
+}
+
+/// Bundle adding the `UiLogPanelSystem`, showing the recent log records in the `UiLogPanel`.
+#[derive(Debug, Default, new)]
+pub struct UiLogPanelBundle;
+
+impl<'a, 'b> SystemBundle<'a, 'b> for UiLogPanelBundle {
+    fn build(self, builder: &mut BundleBuilder<'a, 'b>) -> Result<(), Error> {
+        builder.add(UiLogPanelSystem::new(), "ui_log_panel_system", &[]);
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        "UiLogPanelBundle"
+    }
+}
+
+/// Bundle adding the `UiInspectorSystem`, showing the `UiInspector` overlay, with the components
+/// and the resources it inspects.
+///
+/// The inspector is a thread local system, running after the `UiBundle`.
+#[derive(Default)]
+pub struct UiInspectorBundle {
+    system: UiInspectorSystem,
+}
+
+impl UiInspectorBundle {
+    /// Creates the bundle, inspecting no components.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Inspects the components of a registry.
+    pub fn with_registry(mut self, registry: ComponentRegistry) -> Self {
+        self.system.registry = registry;
+        self
+    }
+
+    /// Inspects the components of a type under a name.
+    pub fn with_component<T, S>(mut self, name: S) -> Self
+    where
+        T: Reflect,
+        T::Storage: Default,
+        S: Into<String>,
+    {
+        self.system.registry.register::<T, S>(name);
+        self
+    }
+
+    /// Inspects a resource under a name.
+    pub fn with_resource<T, S>(mut self, name: S) -> Self
+    where
+        T: Resource + Serialize + DeserializeOwned,
+        S: Into<String>,
+    {
+        self.system = self.system.with_resource::<T, S>(name);
+        self
+    }
+}
+
+impl<'a, 'b> SystemBundle<'a, 'b> for UiInspectorBundle {
+    fn build(self, builder: &mut BundleBuilder<'a, 'b>) -> Result<(), Error> {
+        builder.add_thread_local(self.system);
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        "UiInspectorBundle"
+    }
 }
@@ -0,0 +1,98 @@
+use serde::{Deserialize, Serialize};
+
+/// How the ui is scaled to the size of the screen, see `UiCanvas`.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum CanvasScaling {
+    /// One ui unit is the given amount of pixels, whatever the size of the screen.
+    ConstantPixelSize(f32),
+    /// One ui unit is one logical pixel, the ui is scaled with the hidpi factor of the window.
+    ConstantPhysicalSize,
+    /// The ui is scaled so that the height of the screen is the given amount of ui units.
+    ScaleWithHeight(f32),
+    /// The ui is scaled so that the width of the screen is the given amount of ui units.
+    ScaleWithWidth(f32),
+    /// The ui is scaled so that the reference resolution, given as `(width, height)`, fits in the
+    /// screen. Parts of the screen may be outside of the reference resolution.
+    Fit(f32, f32),
+    /// The ui is scaled so that the reference resolution, given as `(width, height)`, covers the
+    /// screen. Parts of the reference resolution may be outside of the screen.
+    Fill(f32, f32),
+}
+
+impl Default for CanvasScaling {
+    fn default() -> Self {
+        CanvasScaling::ConstantPixelSize(1.0)
+    }
+}
+
+impl CanvasScaling {
+    /// Computes the amount of pixels per ui unit for a screen of the given size in pixels.
+    pub fn factor(&self, screen_size: (f32, f32), hidpi_factor: f32) -> f32 {
+        let (width, height) = screen_size;
+        let factor = match *self {
+            CanvasScaling::ConstantPixelSize(factor) => factor,
+            CanvasScaling::ConstantPhysicalSize => hidpi_factor,
+            CanvasScaling::ScaleWithHeight(reference) => height / reference,
+            CanvasScaling::ScaleWithWidth(reference) => width / reference,
+            CanvasScaling::Fit(ref_width, ref_height) => {
+                (width / ref_width).min(height / ref_height)
+            }
+            CanvasScaling::Fill(ref_width, ref_height) => {
+                (width / ref_width).max(height / ref_height)
+            }
+        };
+        if factor.is_finite() && factor > 0.0 {
+            factor
+        } else {
+            1.0
+        }
+    }
+}
+
+/// Resource configuring the scaling of the whole ui, to author it at a reference resolution and
+/// have it look the same on other resolutions and DPIs.
+///
+/// Positions, sizes, stretch margins and font sizes of ui elements are given in ui units, which
+/// are converted to pixels by the `UiTransformSystem`. Mouse events keep using pixels.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct UiCanvas {
+    /// How the ui is scaled.
+    pub scaling: CanvasScaling,
+    #[serde(skip)]
+    scale: Option<f32>,
+}
+
+impl UiCanvas {
+    /// Creates a canvas with the given scaling.
+    pub fn new(scaling: CanvasScaling) -> Self {
+        UiCanvas {
+            scaling,
+            scale: None,
+        }
+    }
+
+    /// The amount of pixels per ui unit, as last computed by the `UiTransformSystem`.
+    pub fn scale(&self) -> f32 {
+        self.scale.unwrap_or(1.0)
+    }
+
+    pub(crate) fn set_scale(&mut self, scale: f32) {
+        self.scale = Some(scale);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fit_and_fill_reference_resolution() {
+        let screen = (1280.0, 1024.0);
+        let fit = CanvasScaling::Fit(1920.0, 1080.0).factor(screen, 1.0);
+        assert!((fit - 1280.0 / 1920.0).abs() < 1e-6);
+        let fill = CanvasScaling::Fill(1920.0, 1080.0).factor(screen, 1.0);
+        assert!((fill - 1024.0 / 1080.0).abs() < 1e-6);
+        let invalid = CanvasScaling::ScaleWithHeight(0.0).factor(screen, 1.0);
+        assert!((invalid - 1.0).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,105 @@
+use amethyst_core::ecs::{prelude::Component, storage::NullStorage};
+
+use serde::{Deserialize, Serialize};
+
+/// A component that clips the children of an entity with a `UiTransform` to its rectangle.
+///
+/// Parts of children (and of their own children) outside of the rectangle are not rendered and do
+/// not receive mouse events. Images are cut at the edge of the rectangle, while texts which do not
+/// fully fit inside of it are hidden. The element itself is not clipped.
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+pub struct UiClip;
+
+impl Component for UiClip {
+    type Storage = NullStorage<UiClip>;
+}
+
+/// A rectangle in pixels, given as `[left, bottom, right, top]`.
+pub(crate) type ClipRect = [f32; 4];
+
+/// Returns the intersection of two clip rectangles, where `None` means unclipped.
+pub(crate) fn intersect(a: Option<ClipRect>, b: Option<ClipRect>) -> Option<ClipRect> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some([
+            a[0].max(b[0]),
+            a[1].max(b[1]),
+            a[2].min(b[2]),
+            a[3].min(b[3]),
+        ]),
+        (a, None) => a,
+        (None, b) => b,
+    }
+}
+
+/// Cuts a quad given by its center and size to the clip rectangle, shrinking its texture
+/// coordinate bounds to match. Returns `None` if nothing of the quad is left.
+pub(crate) fn clip_quad(
+    center: [f32; 2],
+    size: [f32; 2],
+    tex_coord_bounds: [f32; 4],
+    clip: ClipRect,
+) -> Option<([f32; 2], [f32; 2], [f32; 4])> {
+    let quad = [
+        center[0] - size[0] / 2.0,
+        center[1] - size[1] / 2.0,
+        center[0] + size[0] / 2.0,
+        center[1] + size[1] / 2.0,
+    ];
+    let cut = [
+        quad[0].max(clip[0]),
+        quad[1].max(clip[1]),
+        quad[2].min(clip[2]),
+        quad[3].min(clip[3]),
+    ];
+    if cut[0] >= cut[2] || cut[1] >= cut[3] {
+        return None;
+    }
+    let lerp_u = |x: f32| {
+        tex_coord_bounds[0]
+            + (tex_coord_bounds[2] - tex_coord_bounds[0]) * (x - quad[0]) / (quad[2] - quad[0])
+    };
+    let lerp_v = |y: f32| {
+        tex_coord_bounds[1]
+            + (tex_coord_bounds[3] - tex_coord_bounds[1]) * (y - quad[1]) / (quad[3] - quad[1])
+    };
+    Some((
+        [(cut[0] + cut[2]) / 2.0, (cut[1] + cut[3]) / 2.0],
+        [cut[2] - cut[0], cut[3] - cut[1]],
+        [
+            lerp_u(cut[0]),
+            lerp_v(cut[1]),
+            lerp_u(cut[2]),
+            lerp_v(cut[3]),
+        ],
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clip_quad_cuts_texture() {
+        let clipped = clip_quad(
+            [10.0, 10.0],
+            [20.0, 20.0],
+            [0.0, 0.0, 1.0, 1.0],
+            [10.0, -5.0, 50.0, 15.0],
+        );
+        assert_eq!(
+            clipped,
+            Some(([15.0, 7.5], [10.0, 15.0], [0.5, 0.0, 1.0, 0.75]))
+        );
+    }
+
+    #[test]
+    fn clip_quad_outside() {
+        let clipped = clip_quad(
+            [10.0, 10.0],
+            [20.0, 20.0],
+            [0.0, 0.0, 1.0, 1.0],
+            [30.0, 0.0, 50.0, 20.0],
+        );
+        assert_eq!(clipped, None);
+    }
+}
@@ -0,0 +1,850 @@
+//! A drop-down developer console.
+
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use log::{Log, Metadata, Record};
+use shred_derive::SystemData;
+use unicode_segmentation::UnicodeSegmentation;
+use winit::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent};
+
+use amethyst_assets::{AssetStorage, Loader};
+use amethyst_core::{
+    ecs::prelude::{
+        Entities, Entity, Join, Read, ReadExpect, Resources, System, SystemData, Write,
+        WriteStorage,
+    },
+    shrev::{EventChannel, ReaderId},
+    Parent,
+};
+use amethyst_renderer::{HiddenPropagate, Texture, TextureHandle};
+
+use crate::{
+    get_default_font, Anchor, FontAsset, LineMode, Selected, Stretch, TextEditing, UiEvent,
+    UiEventType, UiText, UiTransform,
+};
+
+const CONSOLE_Z: f32 = 1200.0;
+const MARGIN: f32 = 6.0;
+const BUILTIN_COMMANDS: [&str; 2] = ["clear", "help"];
+
+/// The type of an argument of a `ConsoleCommand`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConsoleArgType {
+    /// A signed integer.
+    Int,
+    /// A floating point number.
+    Float,
+    /// `true`, `false`, `on`, `off`, `1` or `0`.
+    Bool,
+    /// Any word, or several words in double quotes.
+    Text,
+}
+
+impl ConsoleArgType {
+    fn parse(self, token: &str) -> Option<ConsoleValue> {
+        match self {
+            ConsoleArgType::Int => token.parse().ok().map(ConsoleValue::Int),
+            ConsoleArgType::Float => token.parse().ok().map(ConsoleValue::Float),
+            ConsoleArgType::Bool => match token.to_lowercase().as_str() {
+                "true" | "on" | "1" => Some(ConsoleValue::Bool(true)),
+                "false" | "off" | "0" => Some(ConsoleValue::Bool(false)),
+                _ => None,
+            },
+            ConsoleArgType::Text => Some(ConsoleValue::Text(token.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for ConsoleArgType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match *self {
+            ConsoleArgType::Int => "int",
+            ConsoleArgType::Float => "float",
+            ConsoleArgType::Bool => "bool",
+            ConsoleArgType::Text => "text",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A parsed argument of a `ConsoleEvent`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConsoleValue {
+    /// A signed integer.
+    Int(i64),
+    /// A floating point number.
+    Float(f64),
+    /// A boolean.
+    Bool(bool),
+    /// A text.
+    Text(String),
+}
+
+/// A command that can be typed in the `UiConsole`.
+#[derive(Clone, Debug)]
+pub struct ConsoleCommand {
+    /// Name typed to run the command.
+    pub name: String,
+    /// Description shown by `help`.
+    pub description: String,
+    /// Names and types of the arguments.
+    pub args: Vec<(String, ConsoleArgType)>,
+}
+
+impl ConsoleCommand {
+    /// Creates a command without arguments.
+    pub fn new<N: Into<String>, D: Into<String>>(name: N, description: D) -> Self {
+        ConsoleCommand {
+            name: name.into(),
+            description: description.into(),
+            args: Vec::new(),
+        }
+    }
+
+    /// Adds an argument to the command.
+    pub fn with_arg<N: Into<String>>(mut self, name: N, arg_type: ConsoleArgType) -> Self {
+        self.args.push((name.into(), arg_type));
+        self
+    }
+
+    /// The usage of the command, as shown by `help`.
+    pub fn usage(&self) -> String {
+        self.args
+            .iter()
+            .fold(self.name.clone(), |usage, (name, arg_type)| {
+                format!("{} <{}: {}>", usage, name, arg_type)
+            })
+    }
+
+    fn parse(&self, tokens: &[String]) -> Result<Vec<ConsoleValue>, String> {
+        if tokens.len() != self.args.len() {
+            return Err(format!("Usage: {}", self.usage()));
+        }
+        tokens
+            .iter()
+            .zip(&self.args)
+            .map(|(token, (name, arg_type))| {
+                arg_type.parse(token).ok_or_else(|| {
+                    format!(
+                        "Expected {} for argument '{}', got '{}'",
+                        arg_type, name, token
+                    )
+                })
+            })
+            .collect()
+    }
+}
+
+/// Event written to the `EventChannel<ConsoleEvent>` when a registered command is entered in the
+/// `UiConsole`, with its arguments already parsed to the types of the `ConsoleCommand`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConsoleEvent {
+    /// Name of the command.
+    pub command: String,
+    /// Arguments of the command.
+    pub args: Vec<ConsoleValue>,
+}
+
+/// A `Log` implementation echoing log records to the `UiConsole` it was created from.
+///
+/// It can be chained to the amethyst `Logger` with `Logger::chain`. Until the console shows
+/// them, it keeps as many records as the console had `max_lines` when the logger was created,
+/// dropping the oldest ones.
+#[derive(Clone)]
+pub struct ConsoleLogger {
+    pending: Arc<Mutex<VecDeque<String>>>,
+    capacity: usize,
+}
+
+impl Log for ConsoleLogger {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        let line = format!("[{}] {}", record.level(), record.args());
+        if let Ok(mut pending) = self.pending.lock() {
+            if pending.len() >= self.capacity {
+                pending.pop_front();
+            }
+            if self.capacity > 0 {
+                pending.push_back(line);
+            }
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Resource holding the state of the developer console shown by the `UiConsoleSystem`.
+///
+/// Commands are registered with `register`, entering one writes a `ConsoleEvent` that game
+/// systems read to act on it. The console also offers the `help` and `clear` commands, a history
+/// browsed with the up and down keys and completion of command names with tab.
+pub struct UiConsole {
+    /// Key opening and closing the console.
+    pub toggle_key: VirtualKeyCode,
+    /// Height of the console, in ui units.
+    pub height: f32,
+    /// Font size of the console text.
+    pub font_size: f32,
+    /// Maximum number of output lines kept.
+    pub max_lines: usize,
+    commands: BTreeMap<String, ConsoleCommand>,
+    lines: Vec<String>,
+    history: Vec<String>,
+    history_index: Option<usize>,
+    open: bool,
+    dirty: bool,
+    pending: Arc<Mutex<VecDeque<String>>>,
+}
+
+impl Default for UiConsole {
+    fn default() -> Self {
+        UiConsole {
+            toggle_key: VirtualKeyCode::Grave,
+            height: 300.0,
+            font_size: 16.0,
+            max_lines: 200,
+            commands: BTreeMap::new(),
+            lines: Vec::new(),
+            history: Vec::new(),
+            history_index: None,
+            open: false,
+            dirty: true,
+            pending: Arc::new(Mutex::new(VecDeque::new())),
+        }
+    }
+}
+
+impl UiConsole {
+    /// Registers a command, replacing any command with the same name.
+    pub fn register(&mut self, command: ConsoleCommand) {
+        self.commands.insert(command.name.clone(), command);
+    }
+
+    /// Prints a line to the console output.
+    pub fn print<S: Into<String>>(&mut self, line: S) {
+        self.lines.push(line.into());
+        if self.lines.len() > self.max_lines {
+            let excess = self.lines.len() - self.max_lines;
+            self.lines.drain(..excess);
+        }
+        self.dirty = true;
+    }
+
+    /// Removes all lines of the console output.
+    pub fn clear(&mut self) {
+        self.lines.clear();
+        self.dirty = true;
+    }
+
+    /// The lines of the console output, oldest first.
+    pub fn lines(&self) -> &[String] {
+        &self.lines
+    }
+
+    /// The entered commands, oldest first.
+    pub fn history(&self) -> &[String] {
+        &self.history
+    }
+
+    /// Returns a logger echoing log records to this console.
+    pub fn logger(&self) -> ConsoleLogger {
+        ConsoleLogger {
+            pending: self.pending.clone(),
+            capacity: self.max_lines,
+        }
+    }
+
+    /// Returns whether the console is shown.
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// Shows or hides the console.
+    pub fn set_open(&mut self, open: bool) {
+        self.open = open;
+        self.dirty = true;
+    }
+
+    /// Shows the console if hidden, hides it otherwise.
+    pub fn toggle(&mut self) {
+        let open = !self.open;
+        self.set_open(open);
+    }
+
+    /// Runs a command line as if it was entered in the console, returning the event of the
+    /// command if it was a valid registered command.
+    pub fn execute(&mut self, input: &str) -> Option<ConsoleEvent> {
+        let input = input.trim();
+        if input.is_empty() {
+            return None;
+        }
+        if self.history.last().map(String::as_str) != Some(input) {
+            self.history.push(input.to_string());
+        }
+        self.history_index = None;
+        self.print(format!("> {}", input));
+
+        let mut tokens = tokenize(input).into_iter();
+        let name = match tokens.next() {
+            Some(name) => name,
+            None => return None,
+        };
+        let tokens = tokens.collect::<Vec<_>>();
+        match name.as_str() {
+            "clear" => {
+                self.clear();
+                None
+            }
+            "help" => {
+                self.help(tokens.first().map(String::as_str));
+                None
+            }
+            _ => {
+                let parsed = match self.commands.get(&name) {
+                    Some(command) => command.parse(&tokens),
+                    None => Err(format!(
+                        "Unknown command '{}', type 'help' for a list of commands",
+                        name
+                    )),
+                };
+                match parsed {
+                    Ok(args) => Some(ConsoleEvent {
+                        command: name,
+                        args,
+                    }),
+                    Err(err) => {
+                        self.print(err);
+                        None
+                    }
+                }
+            }
+        }
+    }
+
+    /// The command names starting with `input`, including the built-in ones.
+    pub fn complete(&self, input: &str) -> Vec<String> {
+        if input.contains(char::is_whitespace) {
+            return Vec::new();
+        }
+        let mut names = BUILTIN_COMMANDS
+            .iter()
+            .map(|name| name.to_string())
+            .chain(self.commands.keys().cloned())
+            .filter(|name| name.starts_with(input))
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    fn help(&mut self, command: Option<&str>) {
+        let lines = match command {
+            Some(name) => match self.commands.get(name) {
+                Some(command) => vec![command.usage(), command.description.clone()],
+                None => vec![format!("Unknown command '{}'", name)],
+            },
+            None => self
+                .commands
+                .values()
+                .map(|command| format!("{} - {}", command.usage(), command.description))
+                .chain(vec![
+                    "clear - Clears the console".to_string(),
+                    "help <command> - Shows the usage of a command".to_string(),
+                ])
+                .collect(),
+        };
+        for line in lines {
+            self.print(line);
+        }
+    }
+
+    /// Moves through the history, `older` going back in time. Returns the line to show in the
+    /// input, `None` if the input should be left untouched.
+    fn browse_history(&mut self, older: bool) -> Option<String> {
+        if self.history.is_empty() {
+            return None;
+        }
+        let index = match (self.history_index, older) {
+            (None, true) => Some(self.history.len() - 1),
+            (None, false) => return None,
+            (Some(i), true) => Some(i.saturating_sub(1)),
+            (Some(i), false) if i + 1 < self.history.len() => Some(i + 1),
+            (Some(_), false) => None,
+        };
+        self.history_index = index;
+        Some(index.map_or_else(String::new, |i| self.history[i].clone()))
+    }
+}
+
+/// Splits a command line in words, keeping words in double quotes together.
+fn tokenize(input: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    for c in input.chars() {
+        match c {
+            '"' => {
+                if quoted {
+                    tokens.push(current.split_off(0));
+                }
+                quoted = !quoted;
+            }
+            c if c.is_whitespace() && !quoted => {
+                if !current.is_empty() {
+                    tokens.push(current.split_off(0));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    tokens
+}
+
+#[derive(Clone, Copy)]
+struct ConsoleEntities {
+    background: Entity,
+    output: Entity,
+    input: Entity,
+}
+
+/// Shows the `UiConsole` and handles its input.
+///
+/// While open, the console keeps the keyboard focus on its input line.
+/// It's registered by the `UiConsoleBundle`.
+#[derive(Default)]
+pub struct UiConsoleSystem {
+    commands: Vec<ConsoleCommand>,
+    window_reader: Option<ReaderId<Event>>,
+    ui_reader: Option<ReaderId<UiEvent>>,
+    entities: Option<ConsoleEntities>,
+}
+
+impl UiConsoleSystem {
+    /// Creates a new `UiConsoleSystem`.
+    pub fn new() -> Self {
+        UiConsoleSystem::default()
+    }
+
+    /// Registers the commands in the `UiConsole` when the system is set up.
+    pub fn with_commands(mut self, commands: Vec<ConsoleCommand>) -> Self {
+        self.commands = commands;
+        self
+    }
+}
+
+fn create_entities(
+    console: &UiConsole,
+    entities: &Entities<'_>,
+    loader: &Loader,
+    font_storage: &AssetStorage<FontAsset>,
+    texture_storage: &AssetStorage<Texture>,
+    storages: &mut ConsoleStorages<'_>,
+) -> ConsoleEntities {
+    let console_entities = ConsoleEntities {
+        background: entities.create(),
+        output: entities.create(),
+        input: entities.create(),
+    };
+    let ConsoleEntities {
+        background,
+        output,
+        input,
+    } = console_entities;
+    let font = get_default_font(loader, font_storage);
+    let input_height = console.font_size + MARGIN;
+
+    storages
+        .transforms
+        .insert(
+            background,
+            UiTransform::new(
+                "console".to_string(),
+                Anchor::TopMiddle,
+                0.,
+                -console.height / 2.,
+                CONSOLE_Z,
+                0.,
+                console.height,
+            )
+            .with_stretch(Stretch::X { x_margin: 0. }),
+        )
+        .expect("Unreachable: Inserting newly created entity");
+    storages
+        .images
+        .insert(
+            background,
+            loader.load_from_data([0.05, 0.05, 0.05, 0.85].into(), (), texture_storage),
+        )
+        .expect("Unreachable: Inserting newly created entity");
+    storages
+        .hidden
+        .insert(background, HiddenPropagate)
+        .expect("Unreachable: Inserting newly created entity");
+
+    storages
+        .transforms
+        .insert(
+            output,
+            UiTransform::new(
+                "console_output".to_string(),
+                Anchor::TopMiddle,
+                0.,
+                -(console.height - input_height) / 2.,
+                0.01,
+                0.,
+                console.height - input_height - MARGIN * 2.,
+            )
+            .as_transparent()
+            .with_stretch(Stretch::X { x_margin: MARGIN }),
+        )
+        .expect("Unreachable: Inserting newly created entity");
+    let mut output_text = UiText::new(
+        font.clone(),
+        String::new(),
+        [0.85, 0.85, 0.85, 1.0],
+        console.font_size,
+    );
+    output_text.line_mode = LineMode::Wrap;
+    output_text.align = Anchor::BottomLeft;
+    storages
+        .texts
+        .insert(output, output_text)
+        .expect("Unreachable: Inserting newly created entity");
+
+    storages
+        .transforms
+        .insert(
+            input,
+            UiTransform::new(
+                "console_input".to_string(),
+                Anchor::BottomMiddle,
+                0.,
+                input_height / 2.,
+                0.01,
+                0.,
+                input_height,
+            )
+            .with_stretch(Stretch::X { x_margin: MARGIN }),
+        )
+        .expect("Unreachable: Inserting newly created entity");
+    let mut input_text = UiText::new(font, String::new(), [1.0, 1.0, 1.0, 1.0], console.font_size);
+    input_text.align = Anchor::MiddleLeft;
+    storages
+        .texts
+        .insert(input, input_text)
+        .expect("Unreachable: Inserting newly created entity");
+    storages
+        .editing
+        .insert(
+            input,
+            TextEditing::new(256, [0., 0., 0., 1.], [1., 1., 1., 1.], false),
+        )
+        .expect("Unreachable: Inserting newly created entity");
+
+    for &child in &[output, input] {
+        storages
+            .parents
+            .insert(child, Parent { entity: background })
+            .expect("Unreachable: Inserting newly created entity");
+    }
+    console_entities
+}
+
+/// The storages of the console widgets.
+#[derive(SystemData)]
+pub struct ConsoleStorages<'a> {
+    transforms: WriteStorage<'a, UiTransform>,
+    texts: WriteStorage<'a, UiText>,
+    editing: WriteStorage<'a, TextEditing>,
+    images: WriteStorage<'a, TextureHandle>,
+    parents: WriteStorage<'a, Parent>,
+    hidden: WriteStorage<'a, HiddenPropagate>,
+    selected: WriteStorage<'a, Selected>,
+}
+
+impl<'a> System<'a> for UiConsoleSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, EventChannel<Event>>,
+        Read<'a, EventChannel<UiEvent>>,
+        Write<'a, EventChannel<ConsoleEvent>>,
+        Write<'a, UiConsole>,
+        ReadExpect<'a, Loader>,
+        Read<'a, AssetStorage<FontAsset>>,
+        Read<'a, AssetStorage<Texture>>,
+        ConsoleStorages<'a>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            window_events,
+            ui_events,
+            mut console_events,
+            mut console,
+            loader,
+            font_storage,
+            texture_storage,
+            mut storages,
+        ): Self::SystemData,
+    ) {
+        let console_entities = match self.entities {
+            Some(console_entities) => console_entities,
+            None => {
+                let console_entities = create_entities(
+                    &console,
+                    &entities,
+                    &loader,
+                    &font_storage,
+                    &texture_storage,
+                    &mut storages,
+                );
+                self.entities = Some(console_entities);
+                console_entities
+            }
+        };
+        let input = console_entities.input;
+
+        let pending = console
+            .pending
+            .lock()
+            .map(|mut pending| pending.split_off(0))
+            .unwrap_or_default();
+        for line in pending {
+            console.print(line);
+        }
+
+        let mut toggle_char = None;
+        for event in window_events.read(
+            self.window_reader
+                .as_mut()
+                .expect("`UiConsoleSystem::setup` was not called before `UiConsoleSystem::run`"),
+        ) {
+            match *event {
+                Event::WindowEvent {
+                    event: WindowEvent::ReceivedCharacter(c),
+                    ..
+                } => {
+                    if toggle_char == Some(None) {
+                        toggle_char = Some(Some(c));
+                    }
+                }
+                Event::WindowEvent {
+                    event:
+                        WindowEvent::KeyboardInput {
+                            input:
+                                KeyboardInput {
+                                    state: ElementState::Pressed,
+                                    virtual_keycode: Some(key),
+                                    ..
+                                },
+                            ..
+                        },
+                    ..
+                } => {
+                    if key == console.toggle_key {
+                        console.toggle();
+                        toggle_char = Some(None);
+                        continue;
+                    }
+                    if !console.is_open() {
+                        continue;
+                    }
+                    let line = match key {
+                        VirtualKeyCode::Up => console.browse_history(true),
+                        VirtualKeyCode::Down => console.browse_history(false),
+                        VirtualKeyCode::Tab => {
+                            let typed = storages
+                                .texts
+                                .get(input)
+                                .map_or_else(String::new, |text| text.text.clone());
+                            let names = console.complete(typed.trim_start());
+                            match names.len() {
+                                0 => None,
+                                1 => Some(format!("{} ", names[0])),
+                                _ => {
+                                    console.print(names.join("  "));
+                                    None
+                                }
+                            }
+                        }
+                        _ => None,
+                    };
+                    if let Some(line) = line {
+                        set_input(input, line, &mut storages);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        // The text editing system may have typed the character of the toggle key in the input.
+        if let (Some(Some(c)), false) = (toggle_char, console.is_open()) {
+            remove_typed_char(input, c, &mut storages);
+        }
+
+        for event in ui_events.read(
+            self.ui_reader
+                .as_mut()
+                .expect("`UiConsoleSystem::setup` was not called before `UiConsoleSystem::run`"),
+        ) {
+            if event.event_type != UiEventType::ValueCommit || event.target != input {
+                continue;
+            }
+            let line = storages
+                .texts
+                .get(input)
+                .map_or_else(String::new, |text| text.text.clone());
+            set_input(input, String::new(), &mut storages);
+            if let Some(console_event) = console.execute(&line) {
+                console_events.single_write(console_event);
+            }
+        }
+
+        if console.is_open() {
+            let others = (&*entities, &storages.selected)
+                .join()
+                .map(|(entity, _)| entity)
+                .filter(|entity| *entity != input)
+                .collect::<Vec<_>>();
+            for entity in others {
+                storages.selected.remove(entity);
+            }
+            if !storages.selected.contains(input) {
+                storages
+                    .selected
+                    .insert(input, Selected)
+                    .expect("Unreachable: Entity is alive");
+            }
+        }
+
+        if !console.dirty {
+            return;
+        }
+        console.dirty = false;
+        if console.is_open() {
+            storages.hidden.remove(console_entities.background);
+        } else {
+            storages.selected.remove(input);
+            if !storages.hidden.contains(console_entities.background) {
+                storages
+                    .hidden
+                    .insert(console_entities.background, HiddenPropagate)
+                    .expect("Unreachable: Entity is alive");
+            }
+        }
+        let visible_lines = ((console.height - console.font_size - MARGIN * 3.) / console.font_size)
+            .max(1.0) as usize;
+        let first = console.lines.len().saturating_sub(visible_lines);
+        if let Some(text) = storages.texts.get_mut(console_entities.output) {
+            text.text = console.lines[first..].join("\n");
+        }
+    }
+
+    fn setup(&mut self, res: &mut Resources) {
+        Self::SystemData::setup(res);
+        let mut console = res.fetch_mut::<UiConsole>();
+        for command in self.commands.drain(..) {
+            console.register(command);
+        }
+        drop(console);
+        self.window_reader = Some(res.fetch_mut::<EventChannel<Event>>().register_reader());
+        self.ui_reader = Some(res.fetch_mut::<EventChannel<UiEvent>>().register_reader());
+    }
+}
+
+fn set_input(input: Entity, line: String, storages: &mut ConsoleStorages<'_>) {
+    let len = line.graphemes(true).count() as isize;
+    if let Some(text) = storages.texts.get_mut(input) {
+        text.text = line;
+    }
+    if let Some(editing) = storages.editing.get_mut(input) {
+        editing.cursor_position = len;
+        editing.highlight_vector = 0;
+    }
+}
+
+fn remove_typed_char(input: Entity, c: char, storages: &mut ConsoleStorages<'_>) {
+    let (text, editing) = match (
+        storages.texts.get_mut(input),
+        storages.editing.get_mut(input),
+    ) {
+        (Some(text), Some(editing)) => (text, editing),
+        _ => return,
+    };
+    if editing.cursor_position <= 0 {
+        return;
+    }
+    let typed = text
+        .text
+        .grapheme_indices(true)
+        .nth(editing.cursor_position as usize - 1)
+        .filter(|(_, grapheme)| grapheme.chars().eq(Some(c)))
+        .map(|(i, grapheme)| i..i + grapheme.len());
+    if let Some(range) = typed {
+        text.text.replace_range(range, "");
+        editing.cursor_position -= 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_typed_arguments() {
+        let mut console = UiConsole::default();
+        console.register(
+            ConsoleCommand::new("spawn", "Spawns a monster")
+                .with_arg("name", ConsoleArgType::Text)
+                .with_arg("count", ConsoleArgType::Int),
+        );
+        assert_eq!(
+            console.execute("spawn \"big slime\" 3"),
+            Some(ConsoleEvent {
+                command: "spawn".to_string(),
+                args: vec![
+                    ConsoleValue::Text("big slime".to_string()),
+                    ConsoleValue::Int(3)
+                ],
+            })
+        );
+        assert_eq!(console.execute("spawn slime many"), None);
+        assert_eq!(
+            console.lines().last().map(String::as_str),
+            Some("Expected int for argument 'count', got 'many'")
+        );
+        assert_eq!(console.complete("sp"), vec!["spawn".to_string()]);
+        assert_eq!(console.history().len(), 2);
+    }
+
+    #[test]
+    fn logged_records_are_capped() {
+        let mut console = UiConsole::default();
+        console.max_lines = 2;
+        let logger = console.logger();
+        for i in 0..3 {
+            logger.log(
+                &Record::builder()
+                    .args(format_args!("line {}", i))
+                    .level(log::Level::Warn)
+                    .build(),
+            );
+        }
+        let pending = console.pending.lock().unwrap().clone();
+        assert_eq!(
+            pending.into_iter().collect::<Vec<_>>(),
+            vec!["[WARN] line 1".to_string(), "[WARN] line 2".to_string()]
+        );
+    }
+}
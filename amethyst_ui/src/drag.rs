@@ -0,0 +1,578 @@
+//! Drag and drop between ui elements.
+
+use std::{hash::Hash, marker::PhantomData};
+
+use amethyst_core::{
+    ecs::prelude::{
+        Component, DenseVecStorage, Entities, Entity, Join, Read, ReadExpect, ReadStorage, System,
+        Write, WriteStorage,
+    },
+    math::Vector2,
+    shrev::EventChannel,
+};
+use amethyst_input::InputHandler;
+use amethyst_renderer::{HiddenPropagate, MouseButton, Rgba, ScreenDimensions, TextureHandle};
+
+use log::error;
+
+use crate::{
+    targeted, transform::cmp_draw_order, Anchor, Interactable, UiCanvas, UiEvent, UiEventType,
+    UiText, UiTransform,
+};
+
+const DEFAULT_THRESHOLD: f32 = 4.0;
+const GHOST_Z: f32 = 1100.0;
+const GHOST_ALPHA: f32 = 0.6;
+
+/// What follows the cursor while a `Draggable` element is dragged.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DragGhost {
+    /// Nothing follows the cursor.
+    None,
+    /// A translucent copy of the image and text of the dragged element, removed when the drag
+    /// stops.
+    Copy,
+    /// An existing ui entity without `Parent`. It is shown while dragging and hidden with a
+    /// `HiddenPropagate` component otherwise.
+    Entity(Entity),
+}
+
+/// Allows dragging the entity with the left mouse button.
+///
+/// The entity needs to be `Interactable`. The drag starts once the cursor moved `threshold`
+/// pixels away from where the click started, so simple clicks keep working.
+#[derive(Debug, Clone)]
+pub struct Draggable {
+    /// The entity carried by the drag and passed to drop targets. Defaults to the dragged entity
+    /// itself.
+    pub payload: Option<Entity>,
+    /// What follows the cursor while dragging.
+    pub ghost: DragGhost,
+    /// Only `DropTarget`s accepting this tag receive the drag. An untagged element can only be
+    /// dropped on targets accepting everything.
+    pub tag: Option<String>,
+    /// Distance the cursor has to move before the drag starts, in pixels.
+    pub threshold: f32,
+}
+
+impl Default for Draggable {
+    fn default() -> Self {
+        Draggable {
+            payload: None,
+            ghost: DragGhost::Copy,
+            tag: None,
+            threshold: DEFAULT_THRESHOLD,
+        }
+    }
+}
+
+impl Draggable {
+    /// Creates a draggable element with a `DragGhost::Copy` ghost.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the entity carried by the drag.
+    pub fn with_payload(mut self, payload: Entity) -> Self {
+        self.payload = Some(payload);
+        self
+    }
+
+    /// Sets what follows the cursor while dragging.
+    pub fn with_ghost(mut self, ghost: DragGhost) -> Self {
+        self.ghost = ghost;
+        self
+    }
+
+    /// Sets the tag matched against `DropTarget::accepts`.
+    pub fn with_tag<S: ToString>(mut self, tag: S) -> Self {
+        self.tag = Some(tag.to_string());
+        self
+    }
+}
+
+impl Component for Draggable {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// Marks an entity with a `UiTransform` as a place `Draggable` elements can be dropped on.
+#[derive(Debug, Clone, Default)]
+pub struct DropTarget {
+    /// Tags of the `Draggable` elements accepted by this target. Everything is accepted when
+    /// empty.
+    pub accepts: Vec<String>,
+}
+
+impl DropTarget {
+    /// Creates a drop target accepting every `Draggable`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only accept `Draggable` elements with the given tag. Can be called multiple times.
+    pub fn accepting<S: ToString>(mut self, tag: S) -> Self {
+        self.accepts.push(tag.to_string());
+        self
+    }
+
+    /// Returns whether this target accepts a `Draggable` with the given tag.
+    pub fn accepts(&self, tag: Option<&str>) -> bool {
+        self.accepts.is_empty() || tag.map_or(false, |tag| self.accepts.iter().any(|a| a == tag))
+    }
+}
+
+impl Component for DropTarget {
+    type Storage = DenseVecStorage<Self>;
+}
+
+struct Ghost {
+    entity: Entity,
+    /// Whether the ghost was created for this drag and has to be deleted afterwards.
+    owned: bool,
+    /// The `opaque` flag of the ghost before the drag started.
+    opaque: bool,
+}
+
+struct Drag {
+    source: Entity,
+    payload: Entity,
+    tag: Option<String>,
+    element_offset: Vector2<f32>,
+    ghost: Option<Ghost>,
+    over: Option<Entity>,
+}
+
+/// Generates the drag and drop events of `Draggable` and `DropTarget` entities and moves the
+/// drag ghost.
+/// The generic types A and B represent the A and B generic parameter of the InputHandler<A,B>.
+///
+/// It's automatically registered with the `UiBundle`.
+pub struct UiDragSystem<A, B> {
+    was_down: bool,
+    pressed: Option<(Entity, (f32, f32))>,
+    drag: Option<Drag>,
+    last_position: (f32, f32),
+    _marker: PhantomData<(A, B)>,
+}
+
+impl<A, B> UiDragSystem<A, B> {
+    /// Creates a new UiDragSystem.
+    pub fn new() -> Self {
+        UiDragSystem {
+            was_down: false,
+            pressed: None,
+            drag: None,
+            last_position: (0.0, 0.0),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, A, B> System<'a> for UiDragSystem<A, B>
+where
+    A: Send + Sync + Eq + Hash + Clone + 'static,
+    B: Send + Sync + Eq + Hash + Clone + 'static,
+{
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, InputHandler<A, B>>,
+        ReadExpect<'a, ScreenDimensions>,
+        Read<'a, UiCanvas>,
+        Write<'a, EventChannel<UiEvent>>,
+        ReadStorage<'a, Interactable>,
+        ReadStorage<'a, Draggable>,
+        ReadStorage<'a, DropTarget>,
+        WriteStorage<'a, UiTransform>,
+        WriteStorage<'a, TextureHandle>,
+        WriteStorage<'a, UiText>,
+        WriteStorage<'a, Rgba>,
+        WriteStorage<'a, HiddenPropagate>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            input,
+            screen_dimensions,
+            canvas,
+            mut events,
+            interactables,
+            draggables,
+            drop_targets,
+            mut transforms,
+            mut images,
+            mut texts,
+            mut tints,
+            mut hidden,
+        ): Self::SystemData,
+    ) {
+        let down = input.mouse_button_is_down(MouseButton::Left);
+        let click_started = down && !self.was_down;
+        self.was_down = down;
+
+        let pos = match input.mouse_position() {
+            Some((x, y)) => (x as f32, screen_dimensions.height() - y as f32),
+            None => self.last_position,
+        };
+        let moved = pos != self.last_position;
+        self.last_position = pos;
+
+        if self
+            .drag
+            .as_ref()
+            .map_or(false, |drag| !entities.is_alive(drag.source))
+        {
+            let drag = self.drag.take().expect("Unreachable: Drag is in progress");
+            end_drag(drag, &entities, &mut transforms, &mut hidden);
+        }
+
+        if self.drag.is_none() {
+            if click_started {
+                self.pressed =
+                    targeted(pos, (&*entities, &transforms, interactables.maybe()).join())
+                        .filter(|target| draggables.contains(*target))
+                        .map(|target| (target, pos));
+            }
+            if !down {
+                self.pressed = None;
+            }
+
+            let (source, start) = match self.pressed {
+                Some(pressed) => pressed,
+                None => return,
+            };
+            let (draggable, transform) = match (draggables.get(source), transforms.get(source)) {
+                (Some(draggable), Some(transform)) => (draggable, transform),
+                _ => {
+                    self.pressed = None;
+                    return;
+                }
+            };
+            let distance = ((pos.0 - start.0).powi(2) + (pos.1 - start.1).powi(2)).sqrt();
+            if distance < draggable.threshold {
+                return;
+            }
+            self.pressed = None;
+
+            let element_offset =
+                Vector2::new(start.0 - transform.pixel_x, start.1 - transform.pixel_y);
+            let size = (
+                transform.pixel_width / canvas.scale(),
+                transform.pixel_height / canvas.scale(),
+            );
+            let payload = draggable.payload.unwrap_or(source);
+            let ghost = match draggable.ghost {
+                DragGhost::None => None,
+                DragGhost::Entity(ghost) => transforms.get_mut(ghost).map(|transform| {
+                    // The ghost must not block the search for drop targets below the cursor.
+                    let opaque = transform.opaque;
+                    transform.opaque = false;
+                    if hidden.contains(ghost) {
+                        hidden.remove(ghost);
+                    }
+                    Ghost {
+                        entity: ghost,
+                        owned: false,
+                        opaque,
+                    }
+                }),
+                DragGhost::Copy => {
+                    let ghost = entities.create();
+                    let transform = UiTransform::new(
+                        "drag_ghost".to_string(),
+                        Anchor::BottomLeft,
+                        0.0,
+                        0.0,
+                        GHOST_Z,
+                        size.0,
+                        size.1,
+                    )
+                    .as_transparent();
+                    transforms
+                        .insert(ghost, transform)
+                        .expect("Unreachable: Entity was just created");
+                    if let Some(image) = images.get(source).cloned() {
+                        images
+                            .insert(ghost, image)
+                            .expect("Unreachable: Entity was just created");
+                    }
+                    if let Some(text) = texts.get(source).cloned() {
+                        texts
+                            .insert(ghost, text)
+                            .expect("Unreachable: Entity was just created");
+                    }
+                    let mut tint = tints.get(source).cloned().unwrap_or_else(Rgba::white);
+                    tint.3 *= GHOST_ALPHA;
+                    tints
+                        .insert(ghost, tint)
+                        .expect("Unreachable: Entity was just created");
+                    Some(Ghost {
+                        entity: ghost,
+                        owned: true,
+                        opaque: false,
+                    })
+                }
+            };
+            events.single_write(UiEvent::new(UiEventType::DragStart { payload }, source));
+            self.drag = Some(Drag {
+                source,
+                payload,
+                tag: draggable.tag.clone(),
+                element_offset,
+                ghost,
+                over: None,
+            });
+        }
+
+        let mut drag = self.drag.take().expect("Unreachable: Drag is in progress");
+
+        if let Some(ref ghost) = drag.ghost {
+            if let Some(transform) = transforms.get_mut(ghost.entity) {
+                transform.set_root_center(
+                    pos.0 - drag.element_offset.x,
+                    pos.1 - drag.element_offset.y,
+                    (screen_dimensions.width(), screen_dimensions.height()),
+                    canvas.scale(),
+                );
+            }
+        }
+        if moved {
+            events.single_write(UiEvent::new(
+                UiEventType::Dragging {
+                    element_offset: drag.element_offset,
+                },
+                drag.source,
+            ));
+        }
+
+        let over = (&*entities, &transforms, &drop_targets)
+            .join()
+            .filter(|(entity, transform, target)| {
+                *entity != drag.source
+                    && transform.position_inside(pos.0, pos.1)
+                    && target.accepts(drag.tag.as_ref().map(String::as_str))
+            })
+            .max_by(|(e1, t1, _), (e2, t2, _)| cmp_draw_order(t1.global_z, *e1, t2.global_z, *e2))
+            .map(|(entity, _, _)| entity);
+        if over != drag.over {
+            if let Some(over) = over {
+                events.single_write(UiEvent::new(
+                    UiEventType::DragOver {
+                        dragged: drag.source,
+                        payload: drag.payload,
+                    },
+                    over,
+                ));
+            }
+            drag.over = over;
+        }
+
+        if down {
+            self.drag = Some(drag);
+            return;
+        }
+
+        if let Some(over) = drag.over {
+            events.single_write(UiEvent::new(
+                UiEventType::Drop {
+                    dragged: drag.source,
+                    payload: drag.payload,
+                },
+                over,
+            ));
+            events.single_write(UiEvent::new(
+                UiEventType::Dropped { dropped_on: over },
+                drag.source,
+            ));
+        }
+        events.single_write(UiEvent::new(UiEventType::DragStop, drag.source));
+        end_drag(drag, &entities, &mut transforms, &mut hidden);
+    }
+}
+
+fn end_drag(
+    drag: Drag,
+    entities: &Entities<'_>,
+    transforms: &mut WriteStorage<'_, UiTransform>,
+    hidden: &mut WriteStorage<'_, HiddenPropagate>,
+) {
+    let ghost = match drag.ghost {
+        Some(ref ghost) if entities.is_alive(ghost.entity) => ghost,
+        _ => return,
+    };
+    if ghost.owned {
+        if let Err(err) = entities.delete(ghost.entity) {
+            error!("Failed to delete the drag ghost: {}", err);
+        }
+    } else {
+        if let Some(transform) = transforms.get_mut(ghost.entity) {
+            transform.opaque = ghost.opaque;
+        }
+        hidden
+            .insert(ghost.entity, HiddenPropagate)
+            .expect("Unreachable: Entity is alive");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::ecs::prelude::{Builder, RunNow, World};
+    use amethyst_input::InputEvent;
+    use winit::{
+        dpi::LogicalPosition, DeviceId, ElementState, Event, ModifiersState, WindowEvent, WindowId,
+    };
+
+    use super::*;
+
+    type Input = InputHandler<String, String>;
+
+    fn element(x: f32, y: f32) -> UiTransform {
+        let mut transform = UiTransform::new(
+            "element".to_string(),
+            Anchor::BottomLeft,
+            x,
+            y,
+            0.,
+            50.,
+            50.,
+        );
+        transform.pixel_x = x;
+        transform.pixel_y = y;
+        transform.pixel_width = 50.;
+        transform.pixel_height = 50.;
+        transform
+    }
+
+    // Sends a window event to the input handler, like the window would.
+    fn send(world: &mut World, event: WindowEvent) {
+        let event = Event::WindowEvent {
+            window_id: unsafe { WindowId::dummy() },
+            event,
+        };
+        world.write_resource::<Input>().send_event(
+            &event,
+            &mut EventChannel::<InputEvent<String>>::new(),
+            1.0,
+        );
+    }
+
+    fn modifiers() -> ModifiersState {
+        ModifiersState {
+            shift: false,
+            ctrl: false,
+            alt: false,
+            logo: false,
+        }
+    }
+
+    // Moves the cursor to a ui position, y going up from the bottom of the screen.
+    fn move_to(world: &mut World, x: f32, y: f32) {
+        let position = LogicalPosition::new(f64::from(x), f64::from(600. - y));
+        send(
+            world,
+            WindowEvent::CursorMoved {
+                device_id: unsafe { DeviceId::dummy() },
+                position,
+                modifiers: modifiers(),
+            },
+        );
+    }
+
+    fn left_button(world: &mut World, state: ElementState) {
+        send(
+            world,
+            WindowEvent::MouseInput {
+                device_id: unsafe { DeviceId::dummy() },
+                state,
+                button: MouseButton::Left,
+                modifiers: modifiers(),
+            },
+        );
+    }
+
+    #[test]
+    fn dragged_elements_are_dropped_on_the_targets_accepting_them() {
+        let mut world = World::new();
+        let mut system = UiDragSystem::<String, String>::new();
+        System::setup(&mut system, &mut world.res);
+        world.add_resource(ScreenDimensions::new(800, 600, 1.0));
+        let mut reader = world
+            .write_resource::<EventChannel<UiEvent>>()
+            .register_reader();
+        let source = world
+            .create_entity()
+            .with(element(100., 100.))
+            .with(Interactable)
+            .with(Draggable::new().with_tag("item"))
+            .build();
+        let target = world
+            .create_entity()
+            .with(element(300., 100.))
+            .with(DropTarget::new().accepting("item"))
+            .build();
+        world
+            .create_entity()
+            .with(element(300., 100.))
+            .with(DropTarget::new().accepting("spell"))
+            .build();
+        let mut step = |world: &mut World| {
+            system.run_now(&world.res);
+            world.maintain();
+            world
+                .read_resource::<EventChannel<UiEvent>>()
+                .read(&mut reader)
+                .map(|event| (event.event_type.clone(), event.target))
+                .collect::<Vec<_>>()
+        };
+        let ghosts = |world: &World| {
+            world
+                .read_storage::<UiTransform>()
+                .join()
+                .filter(|transform| transform.id == "drag_ghost")
+                .count()
+        };
+
+        move_to(&mut world, 100., 100.);
+        left_button(&mut world, ElementState::Pressed);
+        assert_eq!(step(&mut world), vec![]);
+        // Under the threshold, the press could still be a click.
+        move_to(&mut world, 102., 100.);
+        assert_eq!(step(&mut world), vec![]);
+
+        move_to(&mut world, 300., 100.);
+        let element_offset = Vector2::new(0., 0.);
+        assert_eq!(
+            step(&mut world),
+            vec![
+                (UiEventType::DragStart { payload: source }, source),
+                (UiEventType::Dragging { element_offset }, source),
+                (
+                    UiEventType::DragOver {
+                        dragged: source,
+                        payload: source,
+                    },
+                    target
+                ),
+            ]
+        );
+        assert_eq!(ghosts(&world), 1);
+
+        left_button(&mut world, ElementState::Released);
+        assert_eq!(
+            step(&mut world),
+            vec![
+                (
+                    UiEventType::Drop {
+                        dragged: source,
+                        payload: source,
+                    },
+                    target
+                ),
+                (UiEventType::Dropped { dropped_on: target }, source),
+                (UiEventType::DragStop, source),
+            ]
+        );
+        assert_eq!(ghosts(&world), 0);
+    }
+}
@@ -15,7 +15,7 @@ use amethyst_renderer::{MouseButton, ScreenDimensions};
 
 use serde::{Deserialize, Serialize};
 
-use crate::transform::UiTransform;
+use crate::transform::{cmp_draw_order, UiTransform};
 
 pub trait TargetedEvent {
     fn get_target(&self) -> Entity;
@@ -38,6 +38,12 @@ pub enum UiEventType {
     HoverStart,
     /// When the cursor stops being over an element.
     HoverStop,
+    /// When the cursor moved far enough while clicking a `Draggable` Ui element to start dragging
+    /// it.
+    DragStart {
+        /// The entity carried by the drag, see `Draggable::payload`.
+        payload: Entity,
+    },
     /// When dragging a `Draggable` Ui element.
     Dragging {
         /// The position of the mouse relative to the center of the transform when the drag started.
@@ -48,6 +54,25 @@ pub enum UiEventType {
         /// The entity on which the dragged object was dropped.
         dropped_on: Entity,
     },
+    /// When the dragging of a `Draggable` Ui element stops, whether or not it was dropped on a
+    /// `DropTarget`. Emitted after `Dropped`.
+    DragStop,
+    /// When a dragged element enters a `DropTarget` accepting it. The target of the event is the
+    /// drop target.
+    DragOver {
+        /// The entity being dragged.
+        dragged: Entity,
+        /// The entity carried by the drag.
+        payload: Entity,
+    },
+    /// When a dragged element is released over a `DropTarget` accepting it. The target of the
+    /// event is the drop target.
+    Drop {
+        /// The entity which was dragged.
+        dragged: Entity,
+        /// The entity carried by the drag.
+        payload: Entity,
+    },
     /// When the value of a UiText element has been changed by user input.
     ValueChange,
     /// When the value of a UiText element has been committed by user action.
@@ -186,10 +211,6 @@ where
 {
     transforms
         .filter(|(_e, t, _m)| t.opaque && t.position_inside(pos.0, pos.1))
-        .max_by(|(_e1, t1, _m1), (_e2, t2, _m2)| {
-            t1.global_z
-                .partial_cmp(&t2.global_z)
-                .expect("Unexpected NaN")
-        })
+        .max_by(|(e1, t1, _m1), (e2, t2, _m2)| cmp_draw_order(t1.global_z, *e1, t2.global_z, *e2))
         .and_then(|(e, _, m)| m.map(|_m| e))
 }
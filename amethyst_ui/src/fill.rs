@@ -0,0 +1,120 @@
+use amethyst_core::ecs::prelude::{Component, DenseVecStorage};
+
+use serde::{Deserialize, Serialize};
+
+use crate::clip::ClipRect;
+
+/// How a `UiFill` image is revealed as its value grows.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum UiFillMode {
+    /// Fills from the left edge to the right edge.
+    LeftToRight,
+    /// Fills from the right edge to the left edge.
+    RightToLeft,
+    /// Fills from the bottom edge to the top edge.
+    BottomToTop,
+    /// Fills from the top edge to the bottom edge.
+    TopToBottom,
+    /// Fills around the center of the image, like a clock hand. Useful for cooldown indicators.
+    ///
+    /// The angle is computed from the texture coordinates, so the image should not be
+    /// a `UiNinePatch`.
+    Radial {
+        /// Angle at which the fill starts, in radians, measured clockwise from the top.
+        start_angle: f32,
+        /// Whether the fill grows clockwise.
+        clockwise: bool,
+    },
+}
+
+impl Default for UiFillMode {
+    fn default() -> Self {
+        UiFillMode::LeftToRight
+    }
+}
+
+/// When attached to an entity with a `UiTransform` and a `TextureHandle`, only the part of the
+/// image given by `value` is drawn.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct UiFill {
+    /// How the image is revealed.
+    pub mode: UiFillMode,
+    /// Part of the image shown, from 0 (nothing) to 1 (the full image).
+    pub value: f32,
+}
+
+impl UiFill {
+    /// Creates a new fill.
+    pub fn new(mode: UiFillMode, value: f32) -> Self {
+        UiFill { mode, value }
+    }
+
+    /// Creates a radial fill starting at the top and growing clockwise.
+    pub fn radial(value: f32) -> Self {
+        UiFill::new(
+            UiFillMode::Radial {
+                start_angle: 0.0,
+                clockwise: true,
+            },
+            value,
+        )
+    }
+
+    /// The part of `rect` revealed by a linear fill, `None` for radial fills.
+    pub(crate) fn linear_rect(&self, rect: ClipRect) -> Option<ClipRect> {
+        let value = self.clamped_value();
+        let [left, bottom, right, top] = rect;
+        let width = (right - left) * value;
+        let height = (top - bottom) * value;
+        match self.mode {
+            UiFillMode::LeftToRight => Some([left, bottom, left + width, top]),
+            UiFillMode::RightToLeft => Some([right - width, bottom, right, top]),
+            UiFillMode::BottomToTop => Some([left, bottom, right, bottom + height]),
+            UiFillMode::TopToBottom => Some([left, top - height, right, top]),
+            UiFillMode::Radial { .. } => None,
+        }
+    }
+
+    /// The radial fill parameters passed to the shaders as `(value, start_angle, direction,
+    /// enabled)`.
+    pub(crate) fn radial_args(&self) -> [f32; 4] {
+        match self.mode {
+            UiFillMode::Radial {
+                start_angle,
+                clockwise,
+            } => [
+                self.clamped_value(),
+                start_angle,
+                if clockwise { 1.0 } else { -1.0 },
+                1.0,
+            ],
+            _ => NO_RADIAL_FILL,
+        }
+    }
+
+    fn clamped_value(&self) -> f32 {
+        self.value.max(0.0).min(1.0)
+    }
+}
+
+/// Radial fill parameters drawing the full image.
+pub(crate) const NO_RADIAL_FILL: [f32; 4] = [1.0, 0.0, 1.0, 0.0];
+
+impl Component for UiFill {
+    type Storage = DenseVecStorage<Self>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn linear_fill_rect() {
+        let rect = [0.0, 0.0, 100.0, 20.0];
+        let fill = UiFill::new(UiFillMode::RightToLeft, 0.25);
+        assert_eq!(fill.linear_rect(rect), Some([75.0, 0.0, 100.0, 20.0]));
+        let fill = UiFill::new(UiFillMode::BottomToTop, 2.0);
+        assert_eq!(fill.linear_rect(rect), Some(rect));
+        assert_eq!(UiFill::radial(0.5).linear_rect(rect), None);
+    }
+}
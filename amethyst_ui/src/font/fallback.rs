@@ -0,0 +1,119 @@
+use std::ops::Range;
+
+use gfx_glyph::{Font, FontId};
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Splits `text` into runs of graphemes rendered with the same font, picking for every grapheme
+/// the first of `fonts` that has glyphs for all of its characters.
+///
+/// Graphemes no font fully supports fall back to the first font with a glyph for their base
+/// character, and to the first font otherwise, rendering as its missing glyph.
+pub(crate) fn font_runs(text: &str, fonts: &[&Font<'_>]) -> Vec<(Range<usize>, FontId)> {
+    runs_by(text, fonts.len(), |font, c| {
+        c.is_whitespace() || fonts[font].glyph(c).id().0 != 0
+    })
+}
+
+/// The font runs of a text, kept by its `UiText` until the text or its fonts change.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct CachedRuns {
+    text: String,
+    brush_id: Option<u64>,
+    runs: Vec<(Range<usize>, FontId)>,
+}
+
+impl CachedRuns {
+    /// The runs of `text` rendered by the glyph brush `brush_id`, computed with its `fonts` if
+    /// they weren't already.
+    pub(crate) fn runs<'f, F>(
+        &mut self,
+        text: &str,
+        brush_id: u64,
+        fonts: F,
+    ) -> &[(Range<usize>, FontId)]
+    where
+        F: FnOnce() -> Vec<&'f Font<'f>>,
+    {
+        if self.brush_id != Some(brush_id) || self.text != text {
+            self.runs = font_runs(text, &fonts());
+            self.text.clear();
+            self.text.push_str(text);
+            self.brush_id = Some(brush_id);
+        }
+        &self.runs
+    }
+}
+
+// The runs of `text`, with `has_glyph` telling whether the font at an index has a glyph for a
+// character.
+fn runs_by<F>(text: &str, fonts: usize, has_glyph: F) -> Vec<(Range<usize>, FontId)>
+where
+    F: Fn(usize, char) -> bool,
+{
+    let mut runs: Vec<(Range<usize>, FontId)> = Vec::new();
+    for (start, grapheme) in text.grapheme_indices(true) {
+        let font_id = grapheme_font(grapheme, fonts, &has_glyph);
+        let end = start + grapheme.len();
+        match runs.last_mut() {
+            Some((range, id)) if *id == font_id => range.end = end,
+            _ => runs.push((start..end, font_id)),
+        }
+    }
+    runs
+}
+
+fn grapheme_font<F>(grapheme: &str, fonts: usize, has_glyph: &F) -> FontId
+where
+    F: Fn(usize, char) -> bool,
+{
+    if fonts <= 1 {
+        return FontId(0);
+    }
+    (0..fonts)
+        .find(|&font| grapheme.chars().all(|c| has_glyph(font, c)))
+        .or_else(|| {
+            let base = grapheme.chars().next()?;
+            (0..fonts).find(|&font| has_glyph(font, base))
+        })
+        .map_or(FontId(0), FontId)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The first font has the ASCII characters, the second one the letters.
+    fn has_glyph(font: usize, c: char) -> bool {
+        match font {
+            0 => c.is_ascii(),
+            _ => c.is_alphabetic(),
+        }
+    }
+
+    #[test]
+    fn graphemes_of_the_same_font_are_merged() {
+        assert_eq!(
+            runs_by("ab\u{6f22}\u{5b57}cd", 2, has_glyph),
+            vec![(0..2, FontId(0)), (2..8, FontId(1)), (8..10, FontId(0))]
+        );
+        assert_eq!(runs_by("ab\u{6f22}", 1, has_glyph), vec![(0..5, FontId(0))]);
+    }
+
+    #[test]
+    fn graphemes_fall_back_to_their_base_character() {
+        // No font has both the letter and the combining accent, the second one has the letter.
+        assert_eq!(grapheme_font("\u{e9}\u{301}", 2, &has_glyph), FontId(1));
+        assert_eq!(grapheme_font("e\u{301}", 2, &has_glyph), FontId(0));
+    }
+
+    #[test]
+    fn graphemes_no_font_has_use_the_first_font() {
+        assert_eq!(grapheme_font("\u{1f600}", 2, &has_glyph), FontId(0));
+
+        let font = Font::from_bytes(&include_bytes!("square.ttf")[..]).unwrap();
+        assert_eq!(
+            font_runs("a\u{3042}", &[&font, &font]),
+            vec![(0..4, FontId(0))]
+        );
+    }
+}
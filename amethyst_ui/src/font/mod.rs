@@ -1,2 +1,3 @@
 pub mod default;
+pub(crate) mod fallback;
 pub mod systemfont;
@@ -1,3 +1,5 @@
+use std::sync::Arc;
+
 use gfx_glyph::Font;
 use serde::{Deserialize, Serialize};
 
@@ -5,15 +7,15 @@ use amethyst_assets::{Asset, Handle, ProcessingState, SimpleFormat};
 use amethyst_core::ecs::prelude::VecStorage;
 use amethyst_error::{format_err, Error, ResultExt};
 
-/// A loaded set of fonts from a file.
+/// A loaded set of fonts from a file, with the file, read by the text shaper.
 #[derive(Clone)]
-pub struct FontAsset(pub Font<'static>);
+pub struct FontAsset(pub Font<'static>, pub(crate) Arc<[u8]>);
 
 /// A handle to font data stored with `amethyst_assets`.
 pub type FontHandle = Handle<FontAsset>;
 
 #[derive(Clone)]
-pub struct FontData(Font<'static>);
+pub struct FontData(Font<'static>, Arc<[u8]>);
 
 impl Asset for FontAsset {
     const NAME: &'static str = "ui::Font";
@@ -23,7 +25,7 @@ impl Asset for FontAsset {
 
 impl Into<Result<ProcessingState<FontAsset>, Error>> for FontData {
     fn into(self) -> Result<ProcessingState<FontAsset>, Error> {
-        Ok(ProcessingState::Loaded(FontAsset(self.0)))
+        Ok(ProcessingState::Loaded(FontAsset(self.0, self.1)))
     }
 }
 
@@ -49,8 +51,9 @@ impl SimpleFormat<FontAsset> for TtfFormat {
     type Options = ();
 
     fn import(&self, bytes: Vec<u8>, _: ()) -> Result<FontData, Error> {
-        Font::from_bytes(bytes)
-            .map(FontData)
+        let bytes: Arc<[u8]> = bytes.into();
+        Font::from_bytes(bytes.clone())
+            .map(|font| FontData(font, bytes))
             .with_context(|_| format_err!("Font parsing error"))
     }
 }
@@ -0,0 +1,775 @@
+//! An overlay inspecting the entities, their components and the resources.
+
+use std::{collections::BTreeMap, marker::PhantomData};
+
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use shred_derive::SystemData;
+use winit::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent};
+
+use amethyst_assets::{AssetStorage, Loader};
+use amethyst_core::{
+    ecs::prelude::{
+        Entities, Entity, Join, Read, ReadExpect, Resources, RunNow, SystemData, Write,
+        WriteStorage,
+    },
+    reflect::ComponentRegistry,
+    shred::Resource,
+    shrev::{EventChannel, ReaderId},
+    Parent,
+};
+use amethyst_error::Error;
+use amethyst_renderer::{HiddenPropagate, Texture, TextureHandle};
+
+use crate::{
+    get_default_font, Anchor, FontAsset, LineMode, Selected, Stretch, TextEditing, UiEvent,
+    UiEventType, UiText, UiTransform,
+};
+
+const INSPECTOR_Z: f32 = 1100.0;
+const MARGIN: f32 = 6.0;
+const LISTED_ENTITIES: usize = 9;
+
+// The access of the inspector to a resource, through its serialization.
+trait ResourceAccess: Send + Sync {
+    fn get(&self, res: &Resources) -> Result<Option<Value>, Error>;
+    fn set(&self, res: &Resources, value: Value) -> Result<(), Error>;
+}
+
+struct Access<T>(PhantomData<T>);
+
+impl<T> ResourceAccess for Access<T>
+where
+    T: Resource + Serialize + DeserializeOwned,
+{
+    fn get(&self, res: &Resources) -> Result<Option<Value>, Error> {
+        match res.try_fetch::<T>() {
+            Some(resource) => Ok(Some(serde_json::to_value(&*resource)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn set(&self, res: &Resources, value: Value) -> Result<(), Error> {
+        let mut resource = res
+            .try_fetch_mut::<T>()
+            .ok_or_else(|| Error::from_string("The resource is missing"))?;
+        *resource = serde_json::from_value(value)?;
+        Ok(())
+    }
+}
+
+/// Resource holding the state of the inspector shown by the `UiInspectorSystem`.
+///
+/// The inspector lists the entities, with the components of the selected one and the resources,
+/// as they are registered in the `UiInspectorBundle`. The page up and page down keys select the
+/// previous and the next entity, and a line of its input edits a number, a bool or a text:
+/// `Health.value = 10` edits the `Health` of the selected entity, `Gravity.y = -9.8` a resource,
+/// and a number alone selects the entity of that id.
+pub struct UiInspector {
+    /// Key opening and closing the inspector.
+    pub toggle_key: VirtualKeyCode,
+    /// Width of the inspector, in ui units.
+    pub width: f32,
+    /// Font size of the inspector text.
+    pub font_size: f32,
+    selected: Option<Entity>,
+    message: String,
+    open: bool,
+    dirty: bool,
+}
+
+impl Default for UiInspector {
+    fn default() -> Self {
+        UiInspector {
+            toggle_key: VirtualKeyCode::F12,
+            width: 420.0,
+            font_size: 14.0,
+            selected: None,
+            message: String::new(),
+            open: false,
+            dirty: true,
+        }
+    }
+}
+
+impl UiInspector {
+    /// The inspected entity.
+    pub fn selected(&self) -> Option<Entity> {
+        self.selected
+    }
+
+    /// Inspects an entity.
+    pub fn select(&mut self, entity: Option<Entity>) {
+        self.selected = entity;
+    }
+
+    /// Returns whether the inspector is shown.
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// Shows or hides the inspector.
+    pub fn set_open(&mut self, open: bool) {
+        self.open = open;
+        self.dirty = true;
+    }
+
+    /// Shows the inspector if hidden, hides it otherwise.
+    pub fn toggle(&mut self) {
+        let open = !self.open;
+        self.set_open(open);
+    }
+}
+
+/// Replaces the number, the bool or the text at a path of fields in a value, parsing the input
+/// the same way the field is.
+fn edit(value: &mut Value, path: &[&str], input: &str) -> Result<(), String> {
+    let field = path
+        .iter()
+        .try_fold(value, |value, key| match value {
+            Value::Object(fields) => fields.get_mut(*key),
+            Value::Array(values) => key
+                .parse::<usize>()
+                .ok()
+                .and_then(move |i| values.get_mut(i)),
+            _ => None,
+        })
+        .ok_or_else(|| format!("No field {}", path.join(".")))?;
+    let input = input.trim();
+    let edited = match *field {
+        Value::Bool(_) => match input.to_lowercase().as_str() {
+            "true" | "on" | "1" => Value::Bool(true),
+            "false" | "off" | "0" => Value::Bool(false),
+            _ => return Err(format!("Expected a bool, got '{}'", input)),
+        },
+        Value::Number(_) => serde_json::from_str::<Value>(input)
+            .ok()
+            .filter(Value::is_number)
+            .ok_or_else(|| format!("Expected a number, got '{}'", input))?,
+        Value::String(_) => Value::String(input.trim_matches('"').to_string()),
+        _ => {
+            return Err(format!(
+                "{} is not a number, a bool or a text",
+                path.join(".")
+            ))
+        }
+    };
+    *field = edited;
+    Ok(())
+}
+
+/// Adds the lines describing a value, its fields on their own lines.
+fn describe(name: &str, value: &Value, depth: usize, lines: &mut Vec<String>) {
+    let indent = "  ".repeat(depth);
+    match value {
+        Value::Object(fields) => {
+            lines.push(format!("{}{}", indent, name));
+            for (field, value) in fields {
+                describe(field, value, depth + 1, lines);
+            }
+        }
+        value => lines.push(format!("{}{}: {}", indent, name, value)),
+    }
+}
+
+#[derive(Clone, Copy)]
+struct InspectorEntities {
+    background: Entity,
+    output: Entity,
+    input: Entity,
+}
+
+#[derive(SystemData)]
+struct InspectorStorages<'a> {
+    transforms: WriteStorage<'a, UiTransform>,
+    texts: WriteStorage<'a, UiText>,
+    editing: WriteStorage<'a, TextEditing>,
+    images: WriteStorage<'a, TextureHandle>,
+    parents: WriteStorage<'a, Parent>,
+    hidden: WriteStorage<'a, HiddenPropagate>,
+    selected: WriteStorage<'a, Selected>,
+}
+
+fn create_entities(
+    inspector: &UiInspector,
+    entities: &Entities<'_>,
+    loader: &Loader,
+    font_storage: &AssetStorage<FontAsset>,
+    texture_storage: &AssetStorage<Texture>,
+    storages: &mut InspectorStorages<'_>,
+) -> InspectorEntities {
+    let inspector_entities = InspectorEntities {
+        background: entities.create(),
+        output: entities.create(),
+        input: entities.create(),
+    };
+    let InspectorEntities {
+        background,
+        output,
+        input,
+    } = inspector_entities;
+    let font = get_default_font(loader, font_storage);
+    let input_height = inspector.font_size + MARGIN;
+
+    storages
+        .transforms
+        .insert(
+            background,
+            UiTransform::new(
+                "inspector".to_string(),
+                Anchor::MiddleRight,
+                -inspector.width / 2.,
+                0.,
+                INSPECTOR_Z,
+                inspector.width,
+                0.,
+            )
+            .with_stretch(Stretch::Y { y_margin: 0. }),
+        )
+        .expect("Unreachable: Inserting newly created entity");
+    storages
+        .images
+        .insert(
+            background,
+            loader.load_from_data([0.05, 0.05, 0.1, 0.85].into(), (), texture_storage),
+        )
+        .expect("Unreachable: Inserting newly created entity");
+    storages
+        .hidden
+        .insert(background, HiddenPropagate)
+        .expect("Unreachable: Inserting newly created entity");
+
+    storages
+        .transforms
+        .insert(
+            output,
+            UiTransform::new(
+                "inspector_output".to_string(),
+                Anchor::Middle,
+                0.,
+                input_height / 2.,
+                0.01,
+                inspector.width - MARGIN * 2.,
+                0.,
+            )
+            .as_transparent()
+            .with_stretch(Stretch::Y {
+                y_margin: input_height / 2. + MARGIN,
+            }),
+        )
+        .expect("Unreachable: Inserting newly created entity");
+    let mut output_text = UiText::new(
+        font.clone(),
+        String::new(),
+        [0.85, 0.85, 0.85, 1.0],
+        inspector.font_size,
+    );
+    output_text.line_mode = LineMode::Wrap;
+    output_text.align = Anchor::TopLeft;
+    storages
+        .texts
+        .insert(output, output_text)
+        .expect("Unreachable: Inserting newly created entity");
+
+    storages
+        .transforms
+        .insert(
+            input,
+            UiTransform::new(
+                "inspector_input".to_string(),
+                Anchor::BottomMiddle,
+                0.,
+                input_height / 2.,
+                0.01,
+                0.,
+                input_height,
+            )
+            .with_stretch(Stretch::X { x_margin: MARGIN }),
+        )
+        .expect("Unreachable: Inserting newly created entity");
+    let mut input_text = UiText::new(
+        font,
+        String::new(),
+        [1.0, 1.0, 1.0, 1.0],
+        inspector.font_size,
+    );
+    input_text.align = Anchor::MiddleLeft;
+    storages
+        .texts
+        .insert(input, input_text)
+        .expect("Unreachable: Inserting newly created entity");
+    storages
+        .editing
+        .insert(
+            input,
+            TextEditing::new(256, [0., 0., 0., 1.], [1., 1., 1., 1.], false),
+        )
+        .expect("Unreachable: Inserting newly created entity");
+
+    for &child in &[output, input] {
+        storages
+            .parents
+            .insert(child, Parent { entity: background })
+            .expect("Unreachable: Inserting newly created entity");
+    }
+    inspector_entities
+}
+
+/// Shows the `UiInspector`, reading and editing the components of its `ComponentRegistry` and
+/// its resources.
+///
+/// The inspector reaches into the whole world, so the system is a thread local one, registered by
+/// the `UiInspectorBundle`.
+#[derive(Default)]
+pub struct UiInspectorSystem {
+    pub(crate) registry: ComponentRegistry,
+    resources: BTreeMap<String, Box<dyn ResourceAccess>>,
+    window_reader: Option<ReaderId<Event>>,
+    ui_reader: Option<ReaderId<UiEvent>>,
+    entities: Option<InspectorEntities>,
+}
+
+impl UiInspectorSystem {
+    /// Creates the system inspecting the components of a registry.
+    pub fn new(registry: ComponentRegistry) -> Self {
+        UiInspectorSystem {
+            registry,
+            ..Default::default()
+        }
+    }
+
+    /// Inspects a resource under a name.
+    pub fn with_resource<T, S>(mut self, name: S) -> Self
+    where
+        T: Resource + Serialize + DeserializeOwned,
+        S: Into<String>,
+    {
+        self.resources
+            .insert(name.into(), Box::new(Access::<T>(PhantomData)));
+        self
+    }
+
+    // Reads the keys and the input line, returning the committed line.
+    fn read_input(&mut self, res: &Resources) -> Option<String> {
+        let (
+            entities,
+            window_events,
+            ui_events,
+            mut inspector,
+            loader,
+            fonts,
+            textures,
+            mut storages,
+        ) = <(
+            Entities<'_>,
+            Read<'_, EventChannel<Event>>,
+            Read<'_, EventChannel<UiEvent>>,
+            Write<'_, UiInspector>,
+            ReadExpect<'_, Loader>,
+            Read<'_, AssetStorage<FontAsset>>,
+            Read<'_, AssetStorage<Texture>>,
+            InspectorStorages<'_>,
+        )>::fetch(res);
+        let inspector_entities = match self.entities {
+            Some(inspector_entities) => inspector_entities,
+            None => {
+                let inspector_entities = create_entities(
+                    &inspector,
+                    &entities,
+                    &loader,
+                    &fonts,
+                    &textures,
+                    &mut storages,
+                );
+                self.entities = Some(inspector_entities);
+                inspector_entities
+            }
+        };
+        let input = inspector_entities.input;
+        let own = [
+            inspector_entities.background,
+            inspector_entities.output,
+            input,
+        ];
+
+        for event in window_events.read(self.window_reader.as_mut().expect(
+            "`UiInspectorSystem::setup` was not called before `UiInspectorSystem::run_now`",
+        )) {
+            if let Event::WindowEvent {
+                event:
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(key),
+                                ..
+                            },
+                        ..
+                    },
+                ..
+            } = *event
+            {
+                if key == inspector.toggle_key {
+                    inspector.toggle();
+                    if inspector.is_open() {
+                        let others = (&*entities, &storages.selected)
+                            .join()
+                            .map(|(entity, _)| entity)
+                            .collect::<Vec<_>>();
+                        for entity in others {
+                            storages.selected.remove(entity);
+                        }
+                        storages
+                            .selected
+                            .insert(input, Selected)
+                            .expect("Unreachable: Entity is alive");
+                    }
+                } else if inspector.is_open()
+                    && (key == VirtualKeyCode::PageUp || key == VirtualKeyCode::PageDown)
+                {
+                    let listed = (&*entities)
+                        .join()
+                        .filter(|entity| !own.contains(entity))
+                        .collect::<Vec<_>>();
+                    let current = inspector
+                        .selected
+                        .and_then(|selected| listed.iter().position(|&entity| entity == selected));
+                    let next = match (current, key == VirtualKeyCode::PageDown) {
+                        (Some(i), true) => (i + 1).min(listed.len().saturating_sub(1)),
+                        (Some(i), false) => i.saturating_sub(1),
+                        (None, _) => 0,
+                    };
+                    inspector.select(listed.get(next).cloned());
+                }
+            }
+        }
+
+        let mut line = None;
+        for event in ui_events.read(self.ui_reader.as_mut().expect(
+            "`UiInspectorSystem::setup` was not called before `UiInspectorSystem::run_now`",
+        )) {
+            if event.event_type != UiEventType::ValueCommit || event.target != input {
+                continue;
+            }
+            if let Some(text) = storages.texts.get_mut(input) {
+                line = Some(text.text.split_off(0));
+            }
+            if let Some(editing) = storages.editing.get_mut(input) {
+                editing.cursor_position = 0;
+                editing.highlight_vector = 0;
+            }
+        }
+
+        if inspector.dirty {
+            inspector.dirty = false;
+            if inspector.is_open() {
+                storages.hidden.remove(inspector_entities.background);
+            } else {
+                storages.selected.remove(input);
+                if !storages.hidden.contains(inspector_entities.background) {
+                    storages
+                        .hidden
+                        .insert(inspector_entities.background, HiddenPropagate)
+                        .expect("Unreachable: Entity is alive");
+                }
+            }
+        }
+        line
+    }
+
+    // Runs a line of the input, returning the message to show.
+    fn execute(
+        &self,
+        res: &Resources,
+        selected: Option<Entity>,
+        line: &str,
+    ) -> Result<String, String> {
+        let line = line.trim();
+        if let Ok(id) = line.parse::<u32>() {
+            let entities = Entities::fetch(res);
+            let entity = entities.entity(id);
+            if !entities.is_alive(entity) {
+                return Err(format!("The entity {} is dead", id));
+            }
+            res.fetch_mut::<UiInspector>().select(Some(entity));
+            return Ok(format!("Inspecting {:?}", entity));
+        }
+
+        let mut parts = line.splitn(2, '=');
+        let (path, input) = match (parts.next(), parts.next()) {
+            (Some(path), Some(input)) => (path.trim(), input),
+            _ => return Err("Expected `Component.field = value`".to_string()),
+        };
+        let path = path.split('.').map(str::trim).collect::<Vec<_>>();
+        let name = path[0];
+        let error = |error: Error| error.to_string();
+        if self.registry.contains(name) {
+            let entity = selected.ok_or_else(|| "No entity is inspected".to_string())?;
+            let mut value = self
+                .registry
+                .get(res, name, entity)
+                .map_err(error)?
+                .ok_or_else(|| format!("{:?} has no {}", entity, name))?;
+            edit(&mut value, &path[1..], input)?;
+            self.registry.set(res, name, entity, value).map_err(error)?;
+        } else if let Some(access) = self.resources.get(name) {
+            let mut value = access
+                .get(res)
+                .map_err(error)?
+                .ok_or_else(|| format!("The resource {} is missing", name))?;
+            edit(&mut value, &path[1..], input)?;
+            access.set(res, value).map_err(error)?;
+        } else {
+            return Err(format!("Nothing is inspected as {}", name));
+        }
+        Ok(line.to_string())
+    }
+
+    // The text of the inspector.
+    fn describe(&self, res: &Resources, inspector: &UiInspector) -> Result<String, Error> {
+        let own = self
+            .entities
+            .map_or_else(Vec::new, |own| vec![own.background, own.output, own.input]);
+        let entities = Entities::fetch(res);
+        let listed = (&*entities)
+            .join()
+            .filter(|entity| !own.contains(entity))
+            .collect::<Vec<_>>();
+        let current = inspector
+            .selected
+            .and_then(|selected| listed.iter().position(|&entity| entity == selected))
+            .unwrap_or(0);
+        let first = current
+            .saturating_sub(LISTED_ENTITIES / 2)
+            .min(listed.len().saturating_sub(LISTED_ENTITIES));
+
+        let mut lines = vec![format!(
+            "Entities ({}), page up and page down to select",
+            listed.len()
+        )];
+        for &entity in listed.iter().skip(first).take(LISTED_ENTITIES) {
+            let components = self.registry.components_of(res, entity)?;
+            let marker = if Some(entity) == inspector.selected {
+                '>'
+            } else {
+                ' '
+            };
+            lines.push(format!(
+                "{} {}v{} {}",
+                marker,
+                entity.id(),
+                entity.gen().id(),
+                components.keys().cloned().collect::<Vec<_>>().join(", ")
+            ));
+        }
+        if let Some(selected) = inspector
+            .selected
+            .filter(|&entity| entities.is_alive(entity))
+        {
+            lines.push(String::new());
+            lines.push(format!("Entity {}v{}", selected.id(), selected.gen().id()));
+            for (name, value) in self.registry.components_of(res, selected)? {
+                describe(&name, &value, 1, &mut lines);
+            }
+        }
+        if !self.resources.is_empty() {
+            lines.push(String::new());
+            lines.push("Resources".to_string());
+            for (name, access) in &self.resources {
+                if let Some(value) = access.get(res)? {
+                    describe(name, &value, 1, &mut lines);
+                }
+            }
+        }
+        if !inspector.message.is_empty() {
+            lines.push(String::new());
+            lines.push(inspector.message.clone());
+        }
+        Ok(lines.join("\n"))
+    }
+}
+
+impl<'a> RunNow<'a> for UiInspectorSystem {
+    fn run_now(&mut self, res: &'a Resources) {
+        let line = self.read_input(res);
+        if !res.fetch::<UiInspector>().is_open() {
+            return;
+        }
+
+        if let Some(line) = line {
+            let selected = res.fetch::<UiInspector>().selected;
+            let message = match self.execute(res, selected, &line) {
+                Ok(message) => message,
+                Err(message) => message,
+            };
+            res.fetch_mut::<UiInspector>().message = message;
+        }
+
+        let text = {
+            let inspector = res.fetch::<UiInspector>();
+            self.describe(res, &inspector)
+                .unwrap_or_else(|e| format!("The inspector failed: {}", e))
+        };
+        if let Some(own) = self.entities {
+            if let Some(output) = WriteStorage::<UiText>::fetch(res).get_mut(own.output) {
+                output.text = text;
+            }
+        }
+    }
+
+    fn setup(&mut self, res: &mut Resources) {
+        <(
+            Entities<'_>,
+            Read<'_, EventChannel<Event>>,
+            Read<'_, EventChannel<UiEvent>>,
+            Write<'_, UiInspector>,
+            ReadExpect<'_, Loader>,
+            Read<'_, AssetStorage<FontAsset>>,
+            Read<'_, AssetStorage<Texture>>,
+            InspectorStorages<'_>,
+        )>::setup(res);
+        self.registry.setup(res);
+        self.window_reader = Some(res.fetch_mut::<EventChannel<Event>>().register_reader());
+        self.ui_reader = Some(res.fetch_mut::<EventChannel<UiEvent>>().register_reader());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use rayon::ThreadPoolBuilder;
+    use serde::Deserialize;
+    use serde_json::json;
+    use winit::{DeviceId, ModifiersState, WindowId};
+
+    use amethyst_core::ecs::prelude::{Builder, Component, DenseVecStorage, World};
+
+    use super::*;
+
+    #[derive(Serialize, Deserialize)]
+    struct Health {
+        value: u32,
+    }
+
+    impl Component for Health {
+        type Storage = DenseVecStorage<Self>;
+    }
+
+    #[derive(Default, Serialize, Deserialize)]
+    struct Gravity {
+        y: f32,
+    }
+
+    fn press(world: &mut World, key: VirtualKeyCode) {
+        world
+            .write_resource::<EventChannel<Event>>()
+            .single_write(Event::WindowEvent {
+                window_id: unsafe { WindowId::dummy() },
+                event: WindowEvent::KeyboardInput {
+                    device_id: unsafe { DeviceId::dummy() },
+                    input: KeyboardInput {
+                        scancode: 0,
+                        state: ElementState::Pressed,
+                        virtual_keycode: Some(key),
+                        modifiers: ModifiersState::default(),
+                    },
+                },
+            });
+    }
+
+    // Types a line in the input of the inspector and commits it.
+    fn commit(world: &mut World, input: Entity, line: &str) {
+        world.write_storage::<UiText>().get_mut(input).unwrap().text = line.to_string();
+        world
+            .write_resource::<EventChannel<UiEvent>>()
+            .single_write(UiEvent::new(UiEventType::ValueCommit, input));
+    }
+
+    fn run(world: &mut World, system: &mut UiInspectorSystem) -> String {
+        system.run_now(&world.res);
+        world.maintain();
+        world.read_resource::<UiInspector>().message.clone()
+    }
+
+    #[test]
+    fn edits_the_fields_as_they_are() {
+        let mut value = json!({ "value": 10, "alive": true, "name": "slime", "at": [1.0, 2.0] });
+        edit(&mut value, &["value"], " 12").unwrap();
+        edit(&mut value, &["alive"], "off").unwrap();
+        edit(&mut value, &["name"], "\"big slime\"").unwrap();
+        edit(&mut value, &["at", "1"], "-0.5").unwrap();
+        assert_eq!(
+            value,
+            json!({ "value": 12, "alive": false, "name": "big slime", "at": [1.0, -0.5] })
+        );
+        assert!(edit(&mut value, &["value"], "many").is_err());
+        assert!(edit(&mut value, &["at"], "3").is_err());
+        assert!(edit(&mut value, &["speed"], "3").is_err());
+
+        let mut lines = Vec::new();
+        describe("Health", &json!({ "value": 12 }), 1, &mut lines);
+        assert_eq!(lines, vec!["  Health", "    value: 12"]);
+    }
+
+    #[test]
+    fn the_selected_entity_and_the_resources_are_edited() {
+        let mut world = World::new();
+        let mut registry = ComponentRegistry::new();
+        registry.register_with_fields::<Health, _>("Health", Vec::new());
+        let mut system = UiInspectorSystem::new(registry).with_resource::<Gravity, _>("Gravity");
+        let pool = Arc::new(ThreadPoolBuilder::new().build().unwrap());
+        world.add_resource(Loader::new(".", pool));
+        RunNow::setup(&mut system, &mut world.res);
+        world.add_resource(Gravity::default());
+        let slime = world.create_entity().with(Health { value: 10 }).build();
+        let other = world.create_entity().build();
+
+        press(&mut world, VirtualKeyCode::F12);
+        press(&mut world, VirtualKeyCode::PageDown);
+        run(&mut world, &mut system);
+        let own = system.entities.unwrap();
+        assert!(world.read_resource::<UiInspector>().is_open());
+        assert!(world.read_storage::<Selected>().contains(own.input));
+        assert!(!world
+            .read_storage::<HiddenPropagate>()
+            .contains(own.background));
+        assert_eq!(world.read_resource::<UiInspector>().selected(), Some(slime));
+
+        commit(&mut world, own.input, "Health.value = 12");
+        assert_eq!(run(&mut world, &mut system), "Health.value = 12");
+        assert_eq!(world.read_storage::<Health>().get(slime).unwrap().value, 12);
+        assert!(world
+            .read_storage::<UiText>()
+            .get(own.input)
+            .unwrap()
+            .text
+            .is_empty());
+        assert!(world
+            .read_storage::<UiText>()
+            .get(own.output)
+            .unwrap()
+            .text
+            .contains("    value: 12"));
+
+        commit(&mut world, own.input, "Gravity.y = -9.8");
+        assert_eq!(run(&mut world, &mut system), "Gravity.y = -9.8");
+        assert!((world.read_resource::<Gravity>().y + 9.8).abs() < 1.0e-6);
+
+        commit(&mut world, own.input, &other.id().to_string());
+        run(&mut world, &mut system);
+        assert_eq!(world.read_resource::<UiInspector>().selected(), Some(other));
+        commit(&mut world, own.input, "Health.value = 3");
+        assert_eq!(
+            run(&mut world, &mut system),
+            format!("{:?} has no Health", other)
+        );
+
+        press(&mut world, VirtualKeyCode::F12);
+        run(&mut world, &mut system);
+        assert!(!world.read_resource::<UiInspector>().is_open());
+        assert!(world
+            .read_storage::<HiddenPropagate>()
+            .contains(own.background));
+    }
+}
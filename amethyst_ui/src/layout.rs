@@ -6,14 +6,17 @@ use thread_profiler::profile_scope;
 
 use amethyst_core::{
     ecs::prelude::{
-        BitSet, ComponentEvent, Join, ReadExpect, ReadStorage, ReaderId, Resources, System,
-        WriteStorage,
+        BitSet, ComponentEvent, Entities, Join, ReadExpect, ReadStorage, ReaderId, Resources,
+        System, Write, WriteStorage,
     },
     HierarchyEvent, Parent, ParentHierarchy,
 };
 use amethyst_renderer::ScreenDimensions;
 
-use super::UiTransform;
+use super::{
+    clip::{intersect, UiClip},
+    UiCanvas, UiTransform,
+};
 
 /// Indicates if the position and margins should be calculated in pixel or
 /// relative to their parent size.
@@ -136,17 +139,22 @@ pub struct UiTransformSystem {
     parent_events_id: Option<ReaderId<HierarchyEvent>>,
 
     screen_size: (f32, f32),
+
+    scale: f32,
 }
 
 impl<'a> System<'a> for UiTransformSystem {
     type SystemData = (
+        Entities<'a>,
         WriteStorage<'a, UiTransform>,
         ReadStorage<'a, Parent>,
+        ReadStorage<'a, UiClip>,
         ReadExpect<'a, ScreenDimensions>,
         ReadExpect<'a, ParentHierarchy>,
+        Write<'a, UiCanvas>,
     );
     fn run(&mut self, data: Self::SystemData) {
-        let (mut transforms, parents, screen_dim, hierarchy) = data;
+        let (entities, mut transforms, parents, clips, screen_dim, hierarchy, mut canvas) = data;
         #[cfg(feature = "profiler")]
         profile_scope!("ui_parent_system");
 
@@ -182,12 +190,19 @@ impl<'a> System<'a> for UiTransformSystem {
         }
 
         let current_screen_size = (screen_dim.width(), screen_dim.height());
-        let screen_resized = current_screen_size != self.screen_size;
+        let scale = canvas
+            .scaling
+            .factor(current_screen_size, screen_dim.hidpi_factor() as f32);
+        canvas.set_scale(scale);
+        #[allow(clippy::float_cmp)] // cmp just used to recognize change
+        let screen_resized = current_screen_size != self.screen_size || scale != self.scale;
         self.screen_size = current_screen_size;
+        self.scale = scale;
         if screen_resized {
             process_root_iter(
                 (&mut transforms, !&parents).join().map(|i| i.0),
                 &*screen_dim,
+                scale,
             );
         } else {
             // Immutable borrow
@@ -197,6 +212,7 @@ impl<'a> System<'a> for UiTransformSystem {
                     .join()
                     .map(|i| i.0),
                 &*screen_dim,
+                scale,
             );
         }
 
@@ -241,44 +257,44 @@ impl<'a> System<'a> for UiTransformSystem {
                     let new_size = match transform.stretch {
                         Stretch::NoStretch => (transform.width, transform.height),
                         Stretch::X { x_margin } => (
-                            parent_transform_copy.pixel_width - x_margin * 2.0,
+                            parent_transform_copy.pixel_width / scale - x_margin * 2.0,
                             transform.height,
                         ),
                         Stretch::Y { y_margin } => (
                             transform.width,
-                            parent_transform_copy.pixel_height - y_margin * 2.0,
+                            parent_transform_copy.pixel_height / scale - y_margin * 2.0,
                         ),
                         Stretch::XY {
                             keep_aspect_ratio: false,
                             x_margin,
                             y_margin,
                         } => (
-                            parent_transform_copy.pixel_width - x_margin * 2.0,
-                            parent_transform_copy.pixel_height - y_margin * 2.0,
+                            parent_transform_copy.pixel_width / scale - x_margin * 2.0,
+                            parent_transform_copy.pixel_height / scale - y_margin * 2.0,
                         ),
                         Stretch::XY {
                             keep_aspect_ratio: true,
                             x_margin,
                             y_margin,
                         } => {
-                            let scale = f32::min(
-                                (parent_transform_copy.pixel_width - x_margin * 2.0)
+                            let ratio = f32::min(
+                                (parent_transform_copy.pixel_width / scale - x_margin * 2.0)
                                     / transform.width,
-                                (parent_transform_copy.pixel_height - y_margin * 2.0)
+                                (parent_transform_copy.pixel_height / scale - y_margin * 2.0)
                                     / transform.height,
                             );
 
-                            (transform.width * scale, transform.height * scale)
+                            (transform.width * ratio, transform.height * ratio)
                         }
                     };
                     transform.width = new_size.0;
                     transform.height = new_size.1;
                     match transform.scale_mode {
                         ScaleMode::Pixel => {
-                            transform.pixel_x += transform.local_x;
-                            transform.pixel_y += transform.local_y;
-                            transform.pixel_width = transform.width;
-                            transform.pixel_height = transform.height;
+                            transform.pixel_x += transform.local_x * scale;
+                            transform.pixel_y += transform.local_y * scale;
+                            transform.pixel_width = transform.width * scale;
+                            transform.pixel_height = transform.height * scale;
                         }
                         ScaleMode::Percent => {
                             transform.pixel_x +=
@@ -303,6 +319,39 @@ impl<'a> System<'a> for UiTransformSystem {
                     }
                 });
         }
+
+        // Compute the clip rectangles. They are cheap to compute, so they are always recomputed
+        // and only written when they changed.
+        let unparented_clipped = (&*entities, &transforms, !&parents)
+            .join()
+            .filter(|(_, transform, _)| transform.clip.is_some())
+            .map(|(entity, _, _)| entity)
+            .collect::<Vec<_>>();
+        for entity in unparented_clipped {
+            if let Some(transform) = transforms.get_mut(entity) {
+                transform.clip = None;
+            }
+        }
+        for entity in hierarchy.all() {
+            let parent_entity = parents
+                .get(*entity)
+                .expect("Unreachable: All entities in `ParentHierarchy` should also be in `Parent`")
+                .entity;
+            let clip = match transforms.get(parent_entity) {
+                Some(parent) if clips.contains(parent_entity) => {
+                    intersect(parent.clip, Some(parent.pixel_rect()))
+                }
+                Some(parent) => parent.clip,
+                None => None,
+            };
+            if transforms.get(*entity).map_or(false, |t| t.clip != clip) {
+                transforms
+                    .get_mut(*entity)
+                    .expect("Unreachable: Entity has a `UiTransform`")
+                    .clip = clip;
+            }
+        }
+
         // We need to treat any changes done inside the system as non-modifications, so we read out
         // any events that were generated during the system run
         transforms
@@ -325,7 +374,7 @@ impl<'a> System<'a> for UiTransformSystem {
     }
 }
 
-fn process_root_iter<'a, I>(iter: I, screen_dim: &ScreenDimensions)
+fn process_root_iter<'a, I>(iter: I, screen_dim: &ScreenDimensions, scale: f32)
 where
     I: Iterator<Item = &'a mut UiTransform>,
 {
@@ -337,37 +386,43 @@ where
 
         let new_size = match transform.stretch {
             Stretch::NoStretch => (transform.width, transform.height),
-            Stretch::X { x_margin } => (screen_dim.width() - x_margin * 2.0, transform.height),
-            Stretch::Y { y_margin } => (transform.width, screen_dim.height() - y_margin * 2.0),
+            Stretch::X { x_margin } => (
+                screen_dim.width() / scale - x_margin * 2.0,
+                transform.height,
+            ),
+            Stretch::Y { y_margin } => (
+                transform.width,
+                screen_dim.height() / scale - y_margin * 2.0,
+            ),
             Stretch::XY {
                 keep_aspect_ratio: false,
                 x_margin,
                 y_margin,
             } => (
-                screen_dim.width() - x_margin * 2.0,
-                screen_dim.height() - y_margin * 2.0,
+                screen_dim.width() / scale - x_margin * 2.0,
+                screen_dim.height() / scale - y_margin * 2.0,
             ),
             Stretch::XY {
                 keep_aspect_ratio: true,
                 x_margin,
                 y_margin,
             } => {
-                let scale = f32::min(
-                    (screen_dim.width() - x_margin * 2.0) / transform.width,
-                    (screen_dim.height() - y_margin * 2.0) / transform.height,
+                let ratio = f32::min(
+                    (screen_dim.width() / scale - x_margin * 2.0) / transform.width,
+                    (screen_dim.height() / scale - y_margin * 2.0) / transform.height,
                 );
 
-                (transform.width * scale, transform.height * scale)
+                (transform.width * ratio, transform.height * ratio)
             }
         };
         transform.width = new_size.0;
         transform.height = new_size.1;
         match transform.scale_mode {
             ScaleMode::Pixel => {
-                transform.pixel_x += transform.local_x;
-                transform.pixel_y += transform.local_y;
-                transform.pixel_width = transform.width;
-                transform.pixel_height = transform.height;
+                transform.pixel_x += transform.local_x * scale;
+                transform.pixel_y += transform.local_y * scale;
+                transform.pixel_width = transform.width * scale;
+                transform.pixel_height = transform.height * scale;
             }
             ScaleMode::Percent => {
                 transform.pixel_x += transform.local_x * screen_dim.width();
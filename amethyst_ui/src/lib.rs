@@ -3,53 +3,96 @@
 #![warn(missing_docs, rust_2018_idioms, rust_2018_compatibility)]
 
 pub use self::{
-    bundle::UiBundle,
+    binding::{
+        UiBindingValue, UiComponentBinding, UiComponentBindingSystem, UiResourceBinding,
+        UiResourceBindingSystem,
+    },
+    bundle::{UiBundle, UiConsoleBundle, UiInspectorBundle, UiLogPanelBundle},
     button::{
         UiButton, UiButtonAction, UiButtonActionRetrigger, UiButtonActionRetriggerSystem,
         UiButtonActionType, UiButtonBuilder, UiButtonBuilderResources, UiButtonSystem,
     },
+    canvas::{CanvasScaling, UiCanvas},
+    clip::UiClip,
+    console::{
+        ConsoleArgType, ConsoleCommand, ConsoleEvent, ConsoleLogger, ConsoleValue, UiConsole,
+        UiConsoleSystem,
+    },
+    drag::{DragGhost, Draggable, DropTarget, UiDragSystem},
     event::{targeted, Interactable, UiEvent, UiEventType, UiMouseSystem},
     event_retrigger::{EventReceiver, EventRetriggerSystem},
+    fill::{UiFill, UiFillMode},
     font::{
         default::get_default_font,
         systemfont::{default_system_font, get_all_font_handles, list_system_font_families},
     },
     format::{FontAsset, FontFormat, FontHandle, OtfFormat, TtfFormat},
+    inspector::{UiInspector, UiInspectorSystem},
     label::{UiLabel, UiLabelBuilder, UiLabelBuilderResources},
     layout::{Anchor, ScaleMode, Stretch, UiTransformSystem},
+    list_view::{UiListRows, UiListSource, UiListView, UiListViewScrollSystem, UiListViewSystem},
+    log_panel::{UiLogPanel, UiLogPanelSystem},
+    nine_patch::UiNinePatch,
     pass::DrawUi,
     prefab::{
         NoCustomUi, ToNativeWidget, UiCreator, UiFormat, UiImagePrefab, UiLoader, UiLoaderSystem,
         UiPrefab, UiTextBuilder, UiTransformBuilder, UiWidget,
     },
+    progress_bar::{
+        UiProgress, UiProgressBar, UiProgressBarBuilder, UiProgressBarBuilderResources,
+        UiProgressBarSystem,
+    },
     resize::{ResizeSystem, UiResize},
     selection::{Selectable, Selected, SelectionKeyboardSystem, SelectionMouseSystem},
     selection_order_cache::{CacheSelectionOrderSystem, CachedSelectionOrder},
     sound::{UiPlaySoundAction, UiSoundRetrigger, UiSoundRetriggerSystem, UiSoundSystem},
+    style::{UiStyle, UiStyleSheet, UiStyleSheetHandle, UiStyleSystem, UiStyled, UiTheme},
     text::{LineMode, TextEditing, TextEditingMouseSystem, UiText},
     text_editing::TextEditingInputSystem,
+    tooltip::{UiTooltip, UiTooltipContent, UiTooltipSystem, TOOLTIP_STYLE},
     transform::{UiFinder, UiTransform},
+    transition::{UiEdge, UiTransition, UiTransitionKind, UiTransitionSystem},
     widgets::{Widget, WidgetId, Widgets},
+    window::{UiWindow, UiWindowSystem},
+    world_anchor::{OffScreen, UiWorldAnchor, UiWorldAnchorSystem},
 };
 
 pub(crate) use amethyst_core::ecs::prelude::Entity;
 pub(crate) use paste;
 
+mod bidi;
+mod binding;
 mod bundle;
 mod button;
+mod canvas;
+mod clip;
+mod console;
+mod drag;
 mod event;
 mod event_retrigger;
+mod fill;
 mod font;
 mod format;
+mod inspector;
 mod label;
 mod layout;
+mod list_view;
+mod log_panel;
+mod nine_patch;
 mod pass;
 mod prefab;
+mod progress_bar;
 mod resize;
 mod selection;
 mod selection_order_cache;
+mod shaping;
 mod sound;
+mod style;
 mod text;
 mod text_editing;
+mod tooltip;
 mod transform;
+mod transition;
 mod widgets;
+mod window;
+mod world_anchor;
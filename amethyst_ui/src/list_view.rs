@@ -0,0 +1,492 @@
+//! Virtualized list and grid views.
+
+use std::{marker::PhantomData, ops::Range};
+
+use derivative::Derivative;
+use shred_derive::SystemData;
+use winit::{Event, MouseScrollDelta, WindowEvent};
+
+use amethyst_core::{
+    ecs::prelude::{
+        Component, DenseVecStorage, Entities, Entity, Join, Read, ReadExpect, ReadStorage,
+        Resources, System, SystemData, WriteStorage,
+    },
+    shrev::{EventChannel, ReaderId},
+    Parent,
+};
+use amethyst_renderer::{HiddenPropagate, ScreenDimensions};
+
+use crate::{transform::cmp_draw_order, Anchor, UiCanvas, UiTransform};
+
+/// Default distance scrolled for one line of mouse wheel movement, in ui units.
+const DEFAULT_SCROLL_SPEED: f32 = 32.0;
+
+/// A scrollable list or grid of items laid out inside the `UiTransform` of its entity.
+///
+/// Only the rows that are visible get an entity: when scrolling, rows leaving the view are
+/// recycled for the items coming into view. The content of the rows is provided by a
+/// `UiListSource` component attached to the same entity, see `UiListViewSystem`.
+///
+/// Rows are laid out from the top left corner of the list, `columns` items per line.
+#[derive(Debug, Clone)]
+pub struct UiListView {
+    /// Height of an item, in ui units.
+    pub item_height: f32,
+    /// Number of items per line. Use more than one to get a grid.
+    pub columns: usize,
+    /// Space between two items, in ui units.
+    pub spacing: f32,
+    /// How far the list is scrolled down, in ui units.
+    pub scroll_offset: f32,
+    /// Distance scrolled for one line of mouse wheel movement, in ui units.
+    pub scroll_speed: f32,
+    pub(crate) rows: Vec<UiListRow>,
+    pub(crate) len: usize,
+    pub(crate) needs_rebind: bool,
+}
+
+/// A recycled row entity and the index of the item it currently displays.
+#[derive(Debug, Clone)]
+pub(crate) struct UiListRow {
+    pub(crate) entity: Entity,
+    pub(crate) index: Option<usize>,
+}
+
+impl UiListView {
+    /// Creates a list view with one item per line.
+    pub fn new(item_height: f32) -> Self {
+        UiListView {
+            item_height,
+            columns: 1,
+            spacing: 0.0,
+            scroll_offset: 0.0,
+            scroll_speed: DEFAULT_SCROLL_SPEED,
+            rows: Vec::new(),
+            len: 0,
+            needs_rebind: true,
+        }
+    }
+
+    /// Lays out `columns` items per line, turning the list into a grid.
+    pub fn with_columns(mut self, columns: usize) -> Self {
+        self.columns = columns.max(1);
+        self
+    }
+
+    /// Sets the space between two items.
+    pub fn with_spacing(mut self, spacing: f32) -> Self {
+        self.spacing = spacing;
+        self
+    }
+
+    /// Sets the distance scrolled for one line of mouse wheel movement.
+    pub fn with_scroll_speed(mut self, scroll_speed: f32) -> Self {
+        self.scroll_speed = scroll_speed;
+        self
+    }
+
+    /// Requests every visible row to be bound again, for when the data of the items changed.
+    ///
+    /// Changes to the number of items are detected automatically.
+    pub fn refresh(&mut self) {
+        self.needs_rebind = true;
+    }
+
+    /// Scrolls the list so the item at `index` is on the first visible line.
+    pub fn scroll_to(&mut self, index: usize) {
+        self.scroll_offset = (index / self.columns.max(1)) as f32 * self.stride();
+    }
+
+    /// Returns the number of items, as of the last run of the `UiListViewSystem`.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if the list had no items on the last run of the `UiListViewSystem`.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the height of all the items of the list, in ui units.
+    pub fn content_height(&self) -> f32 {
+        let lines = (self.len + self.columns.max(1) - 1) / self.columns.max(1);
+        (lines as f32 * self.stride() - self.spacing).max(0.0)
+    }
+
+    fn stride(&self) -> f32 {
+        self.item_height + self.spacing
+    }
+
+    fn clamp_scroll(&mut self, view_height: f32) {
+        let max = (self.content_height() - view_height).max(0.0);
+        self.scroll_offset = self.scroll_offset.max(0.0).min(max);
+    }
+
+    // The number of rows covering a view of `view_height`, with a line partially visible at the
+    // top and the bottom, and the items visible in the view.
+    fn visible_items(&self, view_height: f32) -> (usize, Range<usize>) {
+        let columns = self.columns.max(1);
+        let stride = self.stride();
+        let visible_lines = if stride > 0.0 {
+            (view_height / stride).ceil() as usize + 1
+        } else {
+            1
+        };
+        let pool_size = visible_lines * columns;
+        let first = (self.scroll_offset / stride.max(1e-3)).floor() as usize * columns;
+        (
+            pool_size,
+            first.min(self.len)..(first + pool_size).min(self.len),
+        )
+    }
+}
+
+impl Component for UiListView {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// Storages used by the `UiListViewSystem` to maintain row entities.
+///
+/// They are handed to `UiListSource` so rows can be given child entities.
+#[derive(SystemData)]
+pub struct UiListRows<'a> {
+    /// Entities
+    pub entities: Entities<'a>,
+    /// `UiTransform` storage
+    pub transforms: WriteStorage<'a, UiTransform>,
+    /// `Parent` storage
+    pub parents: WriteStorage<'a, Parent>,
+    /// `HiddenPropagate` storage, used to hide rows which are not in use
+    pub hidden: WriteStorage<'a, HiddenPropagate>,
+}
+
+/// Provides the items displayed by a `UiListView`.
+///
+/// Attach the implementing component to the entity holding the `UiListView`, and register a
+/// `UiListViewSystem` for it. `SystemData` must not fetch the storages in `UiListRows`, use the
+/// given `UiListRows` instead.
+pub trait UiListSource<'a>: Component {
+    /// The data needed to create and bind rows.
+    type SystemData: SystemData<'a>;
+
+    /// Returns the number of items in the list.
+    fn len(&self, data: &Self::SystemData) -> usize;
+
+    /// Called once when a row entity is created, before it is bound for the first time.
+    ///
+    /// The row already has a `UiTransform` and a `Parent`. Use this to attach components and
+    /// child entities which don't depend on the displayed item.
+    fn create_row(&self, _row: Entity, _rows: &mut UiListRows<'a>, _data: &mut Self::SystemData) {}
+
+    /// Fills the row entity with the item at `index`.
+    fn bind_row(
+        &self,
+        row: Entity,
+        index: usize,
+        rows: &mut UiListRows<'a>,
+        data: &mut Self::SystemData,
+    );
+}
+
+/// Creates, lays out and recycles the rows of the `UiListView`s having a `S` component.
+///
+/// Should run after the `UiTransformSystem`, so that the size of the lists is known.
+#[derive(Derivative)]
+#[derivative(Default(bound = ""))]
+pub struct UiListViewSystem<S> {
+    _marker: PhantomData<S>,
+}
+
+impl<S> UiListViewSystem<S> {
+    /// Creates a new `UiListViewSystem`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<'a, S> System<'a> for UiListViewSystem<S>
+where
+    S: UiListSource<'a>,
+{
+    type SystemData = (
+        UiListRows<'a>,
+        Read<'a, UiCanvas>,
+        WriteStorage<'a, UiListView>,
+        ReadStorage<'a, S>,
+        S::SystemData,
+    );
+
+    #[allow(clippy::float_cmp)] // cmp just used to recognize change
+    fn run(&mut self, (mut rows, canvas, mut lists, sources, mut data): Self::SystemData) {
+        let list_entities = (&*rows.entities, &lists, &sources)
+            .join()
+            .map(|(entity, _, _)| entity)
+            .collect::<Vec<_>>();
+
+        for list_entity in list_entities {
+            let (width, height) = match rows.transforms.get(list_entity) {
+                Some(transform) => (
+                    transform.pixel_width / canvas.scale(),
+                    transform.pixel_height / canvas.scale(),
+                ),
+                None => continue,
+            };
+            let list = lists
+                .get_mut(list_entity)
+                .expect("Unreachable: Entity was joined with `UiListView`");
+            let source = sources
+                .get(list_entity)
+                .expect("Unreachable: Entity was joined with the list source");
+
+            let len = source.len(&data);
+            if len != list.len {
+                list.len = len;
+                list.needs_rebind = true;
+            }
+            if list.needs_rebind {
+                list.rows.iter_mut().for_each(|row| row.index = None);
+                list.needs_rebind = false;
+            }
+            list.clamp_scroll(height);
+
+            let columns = list.columns.max(1);
+            let stride = list.stride();
+            let (pool_size, visible) = list.visible_items(height);
+
+            while list.rows.len() < pool_size {
+                let row = rows.entities.create();
+                let transform = UiTransform::new(
+                    format!("{}_row_{}", list_entity.id(), list.rows.len()),
+                    Anchor::TopLeft,
+                    0.0,
+                    0.0,
+                    1.0,
+                    0.0,
+                    0.0,
+                );
+                rows.transforms
+                    .insert(row, transform)
+                    .expect("Unreachable: Entity was just created");
+                rows.parents
+                    .insert(row, Parent::new(list_entity))
+                    .expect("Unreachable: Entity was just created");
+                source.create_row(row, &mut rows, &mut data);
+                list.rows.push(UiListRow {
+                    entity: row,
+                    index: None,
+                });
+            }
+
+            let item_width = (width + list.spacing) / columns as f32 - list.spacing;
+            let mut used = vec![false; list.rows.len()];
+            // The items take the rows in turn, so the rows scrolled out of view are bound to the
+            // items scrolled into view while the others keep theirs.
+            for index in visible {
+                let slot = index % list.rows.len();
+                used[slot] = true;
+                let row = &mut list.rows[slot];
+                if row.index != Some(index) {
+                    source.bind_row(row.entity, index, &mut rows, &mut data);
+                    row.index = Some(index);
+                }
+
+                let line = (index / columns) as f32;
+                let column = (index % columns) as f32;
+                let x = column * (item_width + list.spacing) + item_width / 2.0;
+                let y = -(line * stride - list.scroll_offset) - list.item_height / 2.0;
+                let needs_layout = rows.transforms.get(row.entity).map_or(false, |t| {
+                    t.local_x != x
+                        || t.local_y != y
+                        || t.width != item_width
+                        || t.height != list.item_height
+                });
+                if needs_layout {
+                    let transform = rows
+                        .transforms
+                        .get_mut(row.entity)
+                        .expect("Unreachable: Just checked the row has a `UiTransform`");
+                    transform.local_x = x;
+                    transform.local_y = y;
+                    transform.width = item_width;
+                    transform.height = list.item_height;
+                }
+                if rows.hidden.contains(row.entity) {
+                    rows.hidden.remove(row.entity);
+                }
+            }
+
+            for (row, _) in list.rows.iter_mut().zip(used).filter(|(_, used)| !used) {
+                row.index = None;
+                if !rows.hidden.contains(row.entity) {
+                    rows.hidden
+                        .insert(row.entity, HiddenPropagate)
+                        .expect("Unreachable: Row entities are alive");
+                }
+            }
+        }
+    }
+}
+
+/// Scrolls the `UiListView` under the mouse cursor when the mouse wheel is used.
+///
+/// It's automatically registered with the `UiBundle`.
+#[derive(Default)]
+pub struct UiListViewScrollSystem {
+    event_reader: Option<ReaderId<Event>>,
+    mouse_position: (f32, f32),
+}
+
+impl UiListViewScrollSystem {
+    /// Creates a new `UiListViewScrollSystem`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<'a> System<'a> for UiListViewScrollSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, EventChannel<Event>>,
+        ReadExpect<'a, ScreenDimensions>,
+        ReadStorage<'a, UiTransform>,
+        WriteStorage<'a, UiListView>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, events, screen_dimensions, transforms, mut lists): Self::SystemData,
+    ) {
+        let hidpi = screen_dimensions.hidpi_factor() as f32;
+        let event_reader = self.event_reader.as_mut().expect(
+            "`UiListViewScrollSystem::setup` was not called before `UiListViewScrollSystem::run`",
+        );
+
+        for event in events.read(event_reader) {
+            let lines = match *event {
+                Event::WindowEvent {
+                    event: WindowEvent::CursorMoved { position, .. },
+                    ..
+                } => {
+                    self.mouse_position = (
+                        position.x as f32 * hidpi,
+                        screen_dimensions.height() - position.y as f32 * hidpi,
+                    );
+                    continue;
+                }
+                Event::WindowEvent {
+                    event: WindowEvent::MouseWheel { delta, .. },
+                    ..
+                } => match delta {
+                    MouseScrollDelta::LineDelta(_, y) => y,
+                    MouseScrollDelta::PixelDelta(position) => {
+                        position.y as f32 * hidpi / DEFAULT_SCROLL_SPEED
+                    }
+                },
+                _ => continue,
+            };
+
+            let (x, y) = self.mouse_position;
+            let hovered = (&*entities, &transforms, &mut lists)
+                .join()
+                .filter(|(_, transform, _)| transform.position_inside(x, y))
+                .max_by(|(e1, t1, _), (e2, t2, _)| {
+                    cmp_draw_order(t1.global_z, *e1, t2.global_z, *e2)
+                });
+            if let Some((_, _, list)) = hovered {
+                // Moving the wheel up scrolls towards the start of the list.
+                list.scroll_offset -= lines * list.scroll_speed;
+            }
+        }
+    }
+
+    fn setup(&mut self, res: &mut Resources) {
+        Self::SystemData::setup(res);
+        self.event_reader = Some(res.fetch_mut::<EventChannel<Event>>().register_reader());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::ecs::prelude::{Builder, RunNow, World, Write};
+
+    use super::*;
+
+    #[test]
+    fn visible_items_cover_the_view() {
+        let mut list = UiListView::new(10.0).with_spacing(2.0);
+        list.len = 100;
+        // Two lines and a half in view, and one more for the line partially visible at the top.
+        assert_eq!(list.visible_items(30.0), (4, 0..4));
+        list.scroll_offset = 25.0;
+        assert_eq!(list.visible_items(30.0), (4, 2..6));
+
+        let mut grid = UiListView::new(10.0).with_columns(3);
+        grid.len = 10;
+        grid.scroll_offset = 20.0;
+        assert_eq!(grid.visible_items(20.0), (9, 6..10));
+        grid.len = 0;
+        assert_eq!(grid.visible_items(20.0), (9, 0..0));
+    }
+
+    struct Items(usize);
+
+    impl Component for Items {
+        type Storage = DenseVecStorage<Self>;
+    }
+
+    impl<'a> UiListSource<'a> for Items {
+        // The rows bound, with their items.
+        type SystemData = Write<'a, Vec<(Entity, usize)>>;
+
+        fn len(&self, _: &Self::SystemData) -> usize {
+            self.0
+        }
+
+        fn bind_row(
+            &self,
+            row: Entity,
+            index: usize,
+            _: &mut UiListRows<'a>,
+            binds: &mut Self::SystemData,
+        ) {
+            binds.push((row, index));
+        }
+    }
+
+    #[test]
+    fn rows_scrolled_out_are_recycled() {
+        let mut world = World::new();
+        let mut system = UiListViewSystem::<Items>::new();
+        System::setup(&mut system, &mut world.res);
+        let mut transform =
+            UiTransform::new("list".to_string(), Anchor::Middle, 0., 0., 0., 100., 30.);
+        transform.pixel_width = 100.0;
+        transform.pixel_height = 30.0;
+        let list = world
+            .create_entity()
+            .with(transform)
+            .with(UiListView::new(10.0))
+            .with(Items(100))
+            .build();
+
+        system.run_now(&world.res);
+        let binds = world.read_resource::<Vec<(Entity, usize)>>().clone();
+        assert_eq!(
+            binds.iter().map(|bind| bind.1).collect::<Vec<_>>(),
+            vec![0, 1, 2, 3]
+        );
+
+        // Scrolled by a line: the row of the first item shows the one coming into view.
+        world
+            .write_storage::<UiListView>()
+            .get_mut(list)
+            .unwrap()
+            .scroll_offset = 10.0;
+        world.write_resource::<Vec<(Entity, usize)>>().clear();
+        system.run_now(&world.res);
+        assert_eq!(
+            *world.read_resource::<Vec<(Entity, usize)>>(),
+            vec![(binds[0].0, 4)]
+        );
+    }
+}
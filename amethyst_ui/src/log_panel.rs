@@ -0,0 +1,252 @@
+//! An on-screen panel of the recent log records.
+
+use log::Level;
+use winit::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent};
+
+use amethyst_assets::{AssetStorage, Loader};
+use amethyst_core::{
+    ecs::prelude::{
+        Entities, Entity, Read, ReadExpect, Resources, System, SystemData, Write, WriteStorage,
+    },
+    shrev::{EventChannel, ReaderId},
+    LogBuffer, LogEntry,
+};
+use amethyst_renderer::HiddenPropagate;
+
+use crate::{get_default_font, Anchor, FontAsset, LineMode, Stretch, UiText, UiTransform};
+
+const PANEL_Z: f32 = 1100.0;
+const MARGIN: f32 = 6.0;
+
+/// Resource holding the settings of the log panel shown by the `UiLogPanelSystem`, at the bottom
+/// of the screen, with the recent records of the `LogBuffer` resource.
+///
+/// The panel is open from the start, so that the warnings are seen while playing.
+pub struct UiLogPanel {
+    /// Key opening and closing the panel.
+    pub toggle_key: VirtualKeyCode,
+    /// The least severe level of the records shown.
+    pub level: Level,
+    /// The number of records shown.
+    pub lines: usize,
+    /// Font size of the panel text.
+    pub font_size: f32,
+    /// Color of the panel text.
+    pub color: [f32; 4],
+    open: bool,
+    dirty: bool,
+}
+
+impl Default for UiLogPanel {
+    fn default() -> Self {
+        UiLogPanel {
+            toggle_key: VirtualKeyCode::F2,
+            level: Level::Warn,
+            lines: 8,
+            font_size: 14.0,
+            color: [1.0, 0.8, 0.3, 1.0],
+            open: true,
+            dirty: true,
+        }
+    }
+}
+
+impl UiLogPanel {
+    /// Returns whether the panel is shown.
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// Shows or hides the panel.
+    pub fn set_open(&mut self, open: bool) {
+        self.open = open;
+        self.dirty = true;
+    }
+
+    /// Shows the panel if it's hidden, hides it otherwise.
+    pub fn toggle(&mut self) {
+        let open = !self.open;
+        self.set_open(open);
+    }
+
+    /// Shows the records at `level` or more severe, from the next frame.
+    pub fn set_level(&mut self, level: Level) {
+        self.level = level;
+        self.dirty = true;
+    }
+
+    /// The text of the panel, for the records.
+    fn text(entries: &[LogEntry]) -> String {
+        entries
+            .iter()
+            .map(|entry| format!("[{}] {}: {}", entry.level, entry.target, entry.message))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// Shows the `UiLogPanel`, updated when records are added to the `LogBuffer`.
+///
+/// Nothing is shown without a `LogBuffer` resource, which is added from the handle returned by
+/// `start_logger`. It's registered by the `UiLogPanelBundle`.
+#[derive(Default)]
+pub struct UiLogPanelSystem {
+    reader: Option<ReaderId<Event>>,
+    entity: Option<Entity>,
+    total: u64,
+}
+
+impl UiLogPanelSystem {
+    /// Creates a new `UiLogPanelSystem`.
+    pub fn new() -> Self {
+        UiLogPanelSystem::default()
+    }
+}
+
+impl<'a> System<'a> for UiLogPanelSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, EventChannel<Event>>,
+        Option<Read<'a, LogBuffer>>,
+        Write<'a, UiLogPanel>,
+        ReadExpect<'a, Loader>,
+        Read<'a, AssetStorage<FontAsset>>,
+        WriteStorage<'a, UiTransform>,
+        WriteStorage<'a, UiText>,
+        WriteStorage<'a, HiddenPropagate>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            events,
+            buffer,
+            mut panel,
+            loader,
+            font_storage,
+            mut transforms,
+            mut texts,
+            mut hidden,
+        ): Self::SystemData,
+    ) {
+        for event in events.read(
+            self.reader
+                .as_mut()
+                .expect("`UiLogPanelSystem::setup` was not called before `UiLogPanelSystem::run`"),
+        ) {
+            if let Event::WindowEvent {
+                event:
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(key),
+                                ..
+                            },
+                        ..
+                    },
+                ..
+            } = *event
+            {
+                if key == panel.toggle_key {
+                    panel.toggle();
+                }
+            }
+        }
+
+        let buffer = match buffer {
+            Some(buffer) => buffer,
+            None => return,
+        };
+        let total = buffer.total();
+        if !panel.dirty && total == self.total {
+            return;
+        }
+        panel.dirty = false;
+        self.total = total;
+
+        let entity = match self.entity {
+            Some(entity) => entity,
+            None => {
+                let entity = entities.create();
+                let height = panel.lines as f32 * panel.font_size + MARGIN * 2.;
+                transforms
+                    .insert(
+                        entity,
+                        UiTransform::new(
+                            "log_panel".to_string(),
+                            Anchor::BottomMiddle,
+                            0.,
+                            height / 2.,
+                            PANEL_Z,
+                            0.,
+                            height,
+                        )
+                        .as_transparent()
+                        .with_stretch(Stretch::X { x_margin: MARGIN }),
+                    )
+                    .expect("Unreachable: Inserting newly created entity");
+                let font = get_default_font(&loader, &font_storage);
+                let mut text = UiText::new(font, String::new(), panel.color, panel.font_size);
+                text.line_mode = LineMode::Wrap;
+                text.align = Anchor::BottomLeft;
+                texts
+                    .insert(entity, text)
+                    .expect("Unreachable: Inserting newly created entity");
+                self.entity = Some(entity);
+                entity
+            }
+        };
+
+        let entries = buffer.recent(panel.lines, panel.level);
+        if panel.is_open() && !entries.is_empty() {
+            hidden.remove(entity);
+            if let Some(text) = texts.get_mut(entity) {
+                text.text = UiLogPanel::text(&entries);
+            }
+        } else if !hidden.contains(entity) {
+            hidden
+                .insert(entity, HiddenPropagate)
+                .expect("Unreachable: Entity is alive");
+        }
+    }
+
+    fn setup(&mut self, res: &mut Resources) {
+        Self::SystemData::setup(res);
+        self.reader = Some(res.fetch_mut::<EventChannel<Event>>().register_reader());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_are_shown_one_per_line() {
+        let entries = vec![
+            LogEntry {
+                level: Level::Warn,
+                target: "game::ai".to_string(),
+                message: "No path".to_string(),
+                file: None,
+                line: None,
+            },
+            LogEntry {
+                level: Level::Error,
+                target: "amethyst_assets".to_string(),
+                message: "Failed to load".to_string(),
+                file: None,
+                line: None,
+            },
+        ];
+        assert_eq!(
+            UiLogPanel::text(&entries),
+            "[WARN] game::ai: No path\n[ERROR] amethyst_assets: Failed to load"
+        );
+
+        let mut panel = UiLogPanel::default();
+        panel.toggle();
+        assert!(!panel.is_open());
+    }
+}
@@ -0,0 +1,128 @@
+use amethyst_core::ecs::prelude::{Component, DenseVecStorage};
+
+use serde::{Deserialize, Serialize};
+
+/// When attached to an entity with a `UiTransform` and a `TextureHandle`, the image is drawn as a
+/// nine-patch: the corners keep their size, the edges are stretched along one axis and the
+/// center is stretched along both.
+///
+/// Border sizes are given in texels of the source texture and are drawn with the same size in
+/// ui units on screen, see `UiCanvas`. When the element is smaller than the borders, they are
+/// shrunk proportionally.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct UiNinePatch {
+    /// Width of the left border.
+    pub left: f32,
+    /// Width of the right border.
+    pub right: f32,
+    /// Height of the top border.
+    pub top: f32,
+    /// Height of the bottom border.
+    pub bottom: f32,
+}
+
+impl UiNinePatch {
+    /// Creates a nine-patch with the same border size on every side.
+    pub fn uniform(border: f32) -> Self {
+        UiNinePatch {
+            left: border,
+            right: border,
+            top: border,
+            bottom: border,
+        }
+    }
+
+    /// Computes the nine quads making up the patch.
+    ///
+    /// `center` and `size` describe the element on screen, `texture_size` is the size of the
+    /// texture in texels and `scale` the amount of pixels per texel of the borders. Each returned
+    /// entry contains the center and size of the quad in pixels,
+    /// followed by its texture coordinate bounds as `[u_min, v_min, u_max, v_max]`.
+    pub(crate) fn slices(
+        &self,
+        center: (f32, f32),
+        size: (f32, f32),
+        texture_size: (f32, f32),
+        scale: f32,
+    ) -> Vec<([f32; 2], [f32; 2], [f32; 4])> {
+        let shrink = |a: f32, b: f32, available: f32| {
+            if a + b > available && a + b > 0.0 {
+                let ratio = available / (a + b);
+                (a * ratio, b * ratio)
+            } else {
+                (a, b)
+            }
+        };
+        let (left, right) = shrink(self.left * scale, self.right * scale, size.0);
+        let (bottom, top) = shrink(self.bottom * scale, self.top * scale, size.1);
+
+        let x0 = center.0 - size.0 / 2.0;
+        let y0 = center.1 - size.1 / 2.0;
+        let xs = [x0, x0 + left, x0 + size.0 - right, x0 + size.0];
+        let ys = [y0, y0 + bottom, y0 + size.1 - top, y0 + size.1];
+        let us = [
+            0.0,
+            self.left / texture_size.0,
+            1.0 - self.right / texture_size.0,
+            1.0,
+        ];
+        // Texture coordinates have their origin at the bottom left, like the ui.
+        let vs = [
+            0.0,
+            self.bottom / texture_size.1,
+            1.0 - self.top / texture_size.1,
+            1.0,
+        ];
+
+        let mut slices = Vec::with_capacity(9);
+        for row in 0..3 {
+            for column in 0..3 {
+                let width = xs[column + 1] - xs[column];
+                let height = ys[row + 1] - ys[row];
+                if width <= 0.0 || height <= 0.0 {
+                    continue;
+                }
+                slices.push((
+                    [xs[column] + width / 2.0, ys[row] + height / 2.0],
+                    [width, height],
+                    [us[column], vs[row], us[column + 1], vs[row + 1]],
+                ));
+            }
+        }
+        slices
+    }
+}
+
+impl Component for UiNinePatch {
+    type Storage = DenseVecStorage<Self>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slices_cover_element() {
+        let patch = UiNinePatch::uniform(4.0);
+        let slices = patch.slices((50.0, 20.0), (100.0, 40.0), (16.0, 16.0), 1.0);
+        assert_eq!(slices.len(), 9);
+
+        let area: f32 = slices.iter().map(|(_, size, _)| size[0] * size[1]).sum();
+        assert!((area - 100.0 * 40.0).abs() < 1e-3);
+
+        // Bottom left corner keeps its size and samples the corner of the texture.
+        let (center, size, bounds) = slices[0];
+        assert_eq!(center, [2.0, 2.0]);
+        assert_eq!(size, [4.0, 4.0]);
+        assert_eq!(bounds, [0.0, 0.0, 0.25, 0.25]);
+    }
+
+    #[test]
+    fn borders_shrink_when_too_small() {
+        let patch = UiNinePatch::uniform(10.0);
+        let slices = patch.slices((5.0, 5.0), (10.0, 10.0), (32.0, 32.0), 1.0);
+        // The stretched middle row and column collapse.
+        assert_eq!(slices.len(), 4);
+        assert!(slices.iter().all(|(_, size, _)| size == &[5.0, 5.0]));
+    }
+}
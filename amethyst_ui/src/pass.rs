@@ -3,6 +3,7 @@
 use std::{
     cmp::{Ordering, PartialOrd},
     hash::{Hash, Hasher},
+    ops::Range,
 };
 
 use derive_new::new;
@@ -31,10 +32,15 @@ use amethyst_renderer::{
         Effect, NewEffect,
     },
     Encoder, Factory, Hidden, HiddenPropagate, Mesh, PosTex, Resources, Rgba, ScreenDimensions,
-    Shape, Texture, TextureData, TextureHandle, TextureMetadata, VertexFormat,
+    PngFormat, Shape, Texture, TextureData, TextureHandle, TextureMetadata, VertexFormat,
 };
 
-use super::*;
+use super::{
+    clip::{clip_quad, intersect, ClipRect},
+    fill::NO_RADIAL_FILL,
+    shaping::{bitmap_png, ShapedLayout, ShapingFont, ShapingParams},
+    *,
+};
 
 const VERT_SRC: &[u8] = include_bytes!("shaders/vertex.glsl");
 const FRAG_SRC: &[u8] = include_bytes!("shaders/frag.glsl");
@@ -47,8 +53,13 @@ struct VertexArgs {
     coord: vec2,
     dimension: vec2,
     color: vec4,
+    tex_coord_bounds: vec4,
+    radial_fill: vec4,
 }
 
+/// Texture coordinate bounds sampling the whole texture.
+const FULL_TEX_COORD_BOUNDS: [f32; 4] = [0.0, 0.0, 1.0, 1.0];
+
 #[derive(Clone, Debug, Default)]
 struct CachedDrawOrder {
     pub cached: BitSet,
@@ -88,8 +99,13 @@ pub struct DrawUi {
     glyph_brushes: GlyphBrushCache,
     #[new(default)]
     next_brush_cache_id: u64,
+    #[new(default)]
+    glyph_bitmaps: HashMap<BitmapKey, Option<TextureHandle>>,
 }
 
+/// A color bitmap glyph: its brush, font, glyph and strike.
+type BitmapKey = (u64, usize, u32, u16);
+
 type GlyphBrushCache = HashMap<u64, GlyphBrush<'static, Resources, Factory>>;
 
 impl<'a> PassData<'a> for DrawUi {
@@ -107,6 +123,9 @@ impl<'a> PassData<'a> for DrawUi {
         ReadStorage<'a, HiddenPropagate>,
         ReadStorage<'a, Selected>,
         ReadStorage<'a, Rgba>,
+        ReadStorage<'a, UiNinePatch>,
+        ReadStorage<'a, UiFill>,
+        Read<'a, UiCanvas>,
     );
 }
 
@@ -154,6 +173,9 @@ impl Pass for DrawUi {
             hidden_prop,
             selecteds,
             rgba,
+            nine_patches,
+            fills,
+            canvas,
         ): <Self as PassData<'_>>::Data,
     ) {
         #[cfg(feature = "profiler")]
@@ -216,8 +238,10 @@ impl Pass for DrawUi {
             profile_scope!("ui_pass_sortz");
             self.cached_draw_order
                 .cache
-                .sort_unstable_by(|&(z1, _), &(z2, _)| {
-                    z1.partial_cmp(&z2).unwrap_or(Ordering::Equal)
+                .sort_unstable_by(|&(z1, e1), &(z2, e2)| {
+                    z1.partial_cmp(&z2)
+                        .unwrap_or(Ordering::Equal)
+                        .then_with(|| e1.id().cmp(&e2.id()))
                 });
         }
 
@@ -275,18 +299,52 @@ impl Pass for DrawUi {
             {
                 #[cfg(feature = "profiler")]
                 profile_scope!("ui_pass_draw_uiimage");
-                let vertex_args = VertexArgs {
-                    invert_window_size: invert_window_size.into(),
-                    // Coordinates are middle centered. It makes it easier to do layouting in most cases.
-                    coord: [ui_transform.pixel_x, ui_transform.pixel_y].into(),
-                    dimension: [ui_transform.pixel_width, ui_transform.pixel_height].into(),
-                    color: rgba.into(),
+                // Coordinates are middle centered. It makes it easier to do layouting in most cases.
+                let center = (ui_transform.pixel_x, ui_transform.pixel_y);
+                let size = (ui_transform.pixel_width, ui_transform.pixel_height);
+                let quads = match nine_patches.get(entity) {
+                    Some(nine_patch) => {
+                        let (width, height) = image.size();
+                        nine_patch.slices(
+                            center,
+                            size,
+                            (width as f32, height as f32),
+                            canvas.scale(),
+                        )
+                    }
+                    None => vec![(
+                        [center.0, center.1],
+                        [size.0, size.1],
+                        FULL_TEX_COORD_BOUNDS,
+                    )],
                 };
 
-                effect.update_constant_buffer("VertexArgs", &vertex_args.std140(), encoder);
+                let fill = fills.get(entity);
+                let clip_rect =
+                    match fill.and_then(|fill| fill.linear_rect(ui_transform.pixel_rect())) {
+                        Some(fill_rect) => intersect(ui_transform.clip, Some(fill_rect)),
+                        None => ui_transform.clip,
+                    };
+                let radial_fill = fill.map_or(NO_RADIAL_FILL, UiFill::radial_args);
+                let quads = quads
+                    .into_iter()
+                    .filter_map(|quad| clip(quad, clip_rect))
+                    .collect::<Vec<_>>();
+
                 effect.data.textures.push(image.view().clone());
                 effect.data.samplers.push(image.sampler().clone());
-                effect.draw(mesh.slice(), encoder);
+                for (coord, dimension, tex_coord_bounds) in quads {
+                    let vertex_args = VertexArgs {
+                        invert_window_size: invert_window_size.into(),
+                        coord: coord.into(),
+                        dimension: dimension.into(),
+                        color: rgba.into(),
+                        tex_coord_bounds: tex_coord_bounds.into(),
+                        radial_fill: radial_fill.into(),
+                    };
+                    effect.update_constant_buffer("VertexArgs", &vertex_args.std140(), encoder);
+                    effect.draw(mesh.slice(), encoder);
+                }
                 effect.data.textures.clear();
                 effect.data.samplers.clear();
             }
@@ -295,19 +353,28 @@ impl Pass for DrawUi {
                 #[cfg(feature = "profiler")]
                 profile_scope!("ui_pass_draw_uitext");
                 // Maintain glyph brushes.
-                if ui_text.brush_id.is_none() || ui_text.font != ui_text.cached_font {
-                    let font = match font_storage.get(&ui_text.font) {
-                        Some(font) => font,
+                if ui_text.brush_id.is_none()
+                    || ui_text.font != ui_text.cached_font
+                    || ui_text.fallback_fonts != ui_text.cached_fallback_fonts
+                {
+                    let fonts = match Some(&ui_text.font)
+                        .into_iter()
+                        .chain(&ui_text.fallback_fonts)
+                        .map(|handle| font_storage.get(handle).map(|font| font.0.clone()))
+                        .collect::<Option<Vec<_>>>()
+                    {
+                        Some(fonts) => fonts,
                         None => continue,
                     };
 
                     self.glyph_brushes.insert(
                         self.next_brush_cache_id,
-                        GlyphBrushBuilder::using_font(font.0.clone()).build(factory.clone()),
+                        GlyphBrushBuilder::using_fonts(fonts).build(factory.clone()),
                     );
 
                     ui_text.brush_id = Some(self.next_brush_cache_id);
                     ui_text.cached_font = ui_text.font.clone();
+                    ui_text.cached_fallback_fonts = ui_text.fallback_fonts.clone();
                     self.next_brush_cache_id += 1;
                 } else if let Some(brush_id) = ui_text.brush_id {
                     unused_glyph_brushes.remove(&brush_id);
@@ -325,10 +392,22 @@ impl Pass for DrawUi {
                 } else {
                     None
                 };
-                let rendered_string = password_string.as_ref().unwrap_or(&ui_text.text);
+                let rendered_string = password_string
+                    .as_ref()
+                    .map_or(ui_text.text.as_str(), String::as_str);
                 let hidpi = screen_dimensions.hidpi_factor() as f32;
-                let size = ui_text.font_size;
-                let scale = Scale::uniform(size);
+                let font_size = ui_text.font_size * canvas.scale();
+                let scale = Scale::uniform(font_size);
+                let brush_id = ui_text.brush_id.expect("Unreachable: `ui_text.brush_id` is guarenteed to be set earlier in this function");
+                let runs = {
+                    let brush = self
+                        .glyph_brushes
+                        .get(&brush_id)
+                        .expect("Unable to get brush from `glyph_brushes`-map");
+                    ui_text
+                        .cached_runs
+                        .runs(rendered_string, brush_id, || brush.fonts().iter().collect())
+                };
                 let text = editing
                     .and_then(|editing| {
                         if editing.highlight_vector == 0 {
@@ -355,34 +434,33 @@ impl Pass for DrawUi {
                     })
                     .map(|(editing, (start_byte, end_byte))| {
                         let base_color = multiply_colors(ui_text.color, rgba);
-                        vec![
-                            SectionText {
-                                text: &((rendered_string)[0..start_byte]),
-                                scale: scale,
-                                color: base_color,
-                                font_id: FontId(0),
-                            },
-                            SectionText {
-                                text: &((rendered_string)[start_byte..end_byte]),
-                                scale: scale,
-                                color: multiply_colors(editing.selected_text_color, rgba),
-                                font_id: FontId(0),
-                            },
-                            SectionText {
-                                text: &((rendered_string)[end_byte..]),
-                                scale: scale,
-                                color: base_color,
-                                font_id: FontId(0),
-                            },
-                        ]
+                        let selected_color = multiply_colors(editing.selected_text_color, rgba);
+                        let mut text =
+                            section_texts(rendered_string, 0..start_byte, runs, scale, base_color);
+                        text.extend(section_texts(
+                            rendered_string,
+                            start_byte..end_byte,
+                            runs,
+                            scale,
+                            selected_color,
+                        ));
+                        text.extend(section_texts(
+                            rendered_string,
+                            end_byte..rendered_string.len(),
+                            runs,
+                            scale,
+                            base_color,
+                        ));
+                        text
                     })
                     .unwrap_or_else(|| {
-                        vec![SectionText {
-                            text: rendered_string,
-                            scale: scale,
-                            color: multiply_colors(ui_text.color, rgba),
-                            font_id: FontId(0),
-                        }]
+                        section_texts(
+                            rendered_string,
+                            0..rendered_string.len(),
+                            runs,
+                            scale,
+                            multiply_colors(ui_text.color, rgba),
+                        )
                     });
 
                 let layout = match ui_text.line_mode {
@@ -398,6 +476,41 @@ impl Pass for DrawUi {
                     },
                 };
 
+                // The texts are shaped, broken into lines and then reordered line by line for
+                // the right-to-left scripts. Edited texts and passwords are laid out by the
+                // brush in logical order, so that the cursor and the selection follow the
+                // graphemes.
+                let (font, fallback_fonts) = (&ui_text.font, &ui_text.fallback_fonts);
+                let shaped = if editing.is_none() && password_string.is_none() {
+                    let params = ShapingParams {
+                        brush_id,
+                        scale,
+                        width: match ui_text.line_mode {
+                            LineMode::Single => None,
+                            LineMode::Wrap => Some(ui_transform.pixel_width),
+                        },
+                        h_align: ui_text.align.horizontal_align(),
+                        v_align: ui_text.align.vertical_align(),
+                    };
+                    let fonts = || {
+                        Some(font)
+                            .into_iter()
+                            .chain(fallback_fonts)
+                            .filter_map(|handle| font_storage.get(handle))
+                            .map(|font| ShapingFont {
+                                font: &font.0,
+                                data: &font.1,
+                            })
+                            .collect()
+                    };
+                    let text = ui_text
+                        .cached_shaping
+                        .shaped(rendered_string, params, runs, fonts);
+                    Some(ShapedLayout { text, layout })
+                } else {
+                    None
+                };
+
                 let section = VariedSection {
                     // Needs a recenter because we are using [-0.5,0.5] for the mesh
                     // instead of the expected [0,1]
@@ -422,14 +535,32 @@ impl Pass for DrawUi {
                     profile_scope!("ui_pass_draw_uitext_backgroundhighlight");
                     &mut self
                         .glyph_brushes
-                        .get_mut(&ui_text.brush_id.expect("Unreachable: `ui_text.brush_id` is guarenteed to be set earlier in this function"))
+                        .get_mut(&brush_id)
                         .expect("Unable to get brush from `glyph_brushes`-map")
                 };
                 // Maintain the glyph cache (used by the input code).
                 ui_text.cached_glyphs.clear();
-                ui_text
-                    .cached_glyphs
-                    .extend(brush.glyphs(&section).cloned());
+                match shaped {
+                    Some(ref shaped) => ui_text
+                        .cached_glyphs
+                        .extend(brush.glyphs_custom_layout(&section, shaped).cloned()),
+                    None => ui_text
+                        .cached_glyphs
+                        .extend(brush.glyphs(&section).cloned()),
+                }
+
+                // Texts can't be cut, so they are hidden unless they fit completely in their
+                // clip area.
+                if let Some(clip) = ui_transform.clip {
+                    let rect = ui_transform.pixel_rect();
+                    if rect[0] < clip[0]
+                        || rect[1] < clip[1]
+                        || rect[2] > clip[2]
+                        || rect[3] > clip[3]
+                    {
+                        continue;
+                    }
+                }
                 let cache = &mut self.cached_color_textures;
 
                 // Render text selection
@@ -464,7 +595,7 @@ impl Pass for DrawUi {
                         .fonts()
                         .get(0)
                         .expect("Unable to get first font of brush")
-                        .v_metrics(Scale::uniform(ui_text.font_size))
+                        .v_metrics(Scale::uniform(font_size))
                         .ascent;
                     for glyph in brush
                         .glyphs(&section)
@@ -487,6 +618,8 @@ impl Pass for DrawUi {
                             .into(),
                             dimension: [width, height].into(),
                             color: rgba.into(),
+                            tex_coord_bounds: FULL_TEX_COORD_BOUNDS.into(),
+                            radial_fill: NO_RADIAL_FILL.into(),
                         };
                         effect.update_constant_buffer("VertexArgs", &vertex_args.std140(), encoder);
                         effect.draw(mesh.slice(), encoder);
@@ -498,7 +631,10 @@ impl Pass for DrawUi {
                 {
                     #[cfg(feature = "profiler")]
                     profile_scope!("ui_pass_draw_uitext_rendertext");
-                    brush.queue(section.clone());
+                    match shaped {
+                        Some(ref shaped) => brush.queue_custom_layout(section.clone(), shaped),
+                        None => brush.queue(section.clone()),
+                    }
                     if let Err(err) = brush.draw_queued(
                         encoder,
                         &effect.data.out_blends[0],
@@ -512,6 +648,63 @@ impl Pass for DrawUi {
                         error!("Unable to draw text! Error: {:?}", err);
                     }
                 }
+                // Render the color bitmap glyphs, such as emoji
+                if let Some(ref shaped) = shaped {
+                    #[cfg(feature = "profiler")]
+                    profile_scope!("ui_pass_draw_uitext_renderbitmaps");
+                    let (x, y) = section.screen_position;
+                    for glyph in &shaped.text.glyphs {
+                        let bitmap = match glyph.bitmap {
+                            Some(ref bitmap) => bitmap,
+                            None => continue,
+                        };
+                        let key = (brush_id, glyph.font_id.0, glyph.id, bitmap.strike);
+                        let texture = self
+                            .glyph_bitmaps
+                            .entry(key)
+                            .or_insert_with(|| {
+                                let handle = Some(font)
+                                    .into_iter()
+                                    .chain(fallback_fonts)
+                                    .nth(glyph.font_id.0)?;
+                                let asset = font_storage.get(handle)?;
+                                let png = bitmap_png(&asset.1, glyph.id, bitmap.strike)?;
+                                match PngFormat::from_data(&png, TextureMetadata::srgb_scale()) {
+                                    Ok(data) => Some(loader.load_from_data(data, (), &*tex_storage)),
+                                    Err(err) => {
+                                        error!("Unable to load the bitmap of a glyph: {}", err);
+                                        None
+                                    }
+                                }
+                            })
+                            .as_ref()
+                            .and_then(|handle| tex_storage.get(handle));
+                        let texture = match texture {
+                            Some(texture) => texture,
+                            None => continue,
+                        };
+                        let [left, top, width, height] = bitmap.rect;
+                        effect.data.textures.push(texture.view().clone());
+                        effect.data.samplers.push(texture.sampler().clone());
+                        let vertex_args = VertexArgs {
+                            invert_window_size: invert_window_size.into(),
+                            // gfx-glyph uses y down so we need to convert to y up
+                            coord: [
+                                x + glyph.x + left + width / 2.0,
+                                screen_dimensions.height() - (y + glyph.y + top + height / 2.0),
+                            ]
+                            .into(),
+                            dimension: [width, height].into(),
+                            color: rgba.into(),
+                            tex_coord_bounds: FULL_TEX_COORD_BOUNDS.into(),
+                            radial_fill: NO_RADIAL_FILL.into(),
+                        };
+                        effect.update_constant_buffer("VertexArgs", &vertex_args.std140(), encoder);
+                        effect.draw(mesh.slice(), encoder);
+                        effect.data.textures.clear();
+                        effect.data.samplers.clear();
+                    }
+                }
                 // Render cursor
                 if selecteds.contains(entity) {
                     if let Some((texture, editing)) = editing.as_ref().and_then(|ed| {
@@ -537,7 +730,7 @@ impl Pass for DrawUi {
                                     .get(0)
                                     .expect("Unable to get first font of brush")
                                     .glyph(' ')
-                                    .scaled(Scale::uniform(ui_text.font_size))
+                                    .scaled(Scale::uniform(font_size))
                                     .h_metrics()
                                     .advance_width
                             } else {
@@ -548,7 +741,7 @@ impl Pass for DrawUi {
                                 .fonts()
                                 .get(0)
                                 .expect("Unable to get first font of brush")
-                                .v_metrics(Scale::uniform(ui_text.font_size))
+                                .v_metrics(Scale::uniform(font_size))
                                 .ascent;
                             let glyph_len = brush.glyphs(&section).count();
                             let (glyph, at_end) = if editing.cursor_position as usize >= glyph_len {
@@ -561,14 +754,14 @@ impl Pass for DrawUi {
                             };
                             let (height, width) = if editing.use_block_cursor {
                                 let height = if blink_on {
-                                    ui_text.font_size
+                                    font_size
                                 } else {
-                                    ui_text.font_size / 10.0
+                                    font_size / 10.0
                                 };
 
                                 (height, space_width)
                             } else {
-                                (ui_text.font_size, 2.0)
+                                (font_size, 2.0)
                             };
 
                             let mut pos = glyph.map(|g| g.position()).unwrap_or(Point {
@@ -588,13 +781,15 @@ impl Pass for DrawUi {
                             }
                             let mut y = pos.y;
                             if editing.use_block_cursor && !blink_on {
-                                y -= ui_text.font_size * 0.9;
+                                y -= font_size * 0.9;
                             }
                             let vertex_args = VertexArgs {
                                 invert_window_size: invert_window_size.into(),
                                 coord: [x, screen_dimensions.height() - y + ascent / 2.0].into(),
                                 dimension: [width, height].into(),
                                 color: rgba.into(),
+                                tex_coord_bounds: FULL_TEX_COORD_BOUNDS.into(),
+                                radial_fill: NO_RADIAL_FILL.into(),
                             };
                             effect.update_constant_buffer(
                                 "VertexArgs",
@@ -613,6 +808,20 @@ impl Pass for DrawUi {
         for id in unused_glyph_brushes.drain() {
             self.glyph_brushes.remove(&id);
         }
+        let glyph_brushes = &self.glyph_brushes;
+        self.glyph_bitmaps
+            .retain(|&(brush_id, ..), _| glyph_brushes.contains_key(&brush_id));
+    }
+}
+
+/// Cuts a quad to the clip area of its element, if any.
+fn clip(
+    (center, size, tex_coord_bounds): ([f32; 2], [f32; 2], [f32; 4]),
+    clip: Option<ClipRect>,
+) -> Option<([f32; 2], [f32; 2], [f32; 4])> {
+    match clip {
+        Some(clip) => clip_quad(center, size, tex_coord_bounds, clip),
+        None => Some((center, size, tex_coord_bounds)),
     }
 }
 
@@ -620,6 +829,44 @@ fn multiply_colors(a: [f32; 4], b: [f32; 4]) -> [f32; 4] {
     [a[0] * b[0], a[1] * b[1], a[2] * b[2], a[3] * b[3]]
 }
 
+/// Builds the section texts for the byte `range` of `text`, split at the font runs.
+fn section_texts<'a>(
+    text: &'a str,
+    range: Range<usize>,
+    runs: &[(Range<usize>, FontId)],
+    scale: Scale,
+    color: [f32; 4],
+) -> Vec<SectionText<'a>> {
+    let texts = runs
+        .iter()
+        .filter_map(|(run, font_id)| {
+            let start = run.start.max(range.start);
+            let end = run.end.min(range.end);
+            if start < end {
+                Some(SectionText {
+                    text: &text[start..end],
+                    scale,
+                    color,
+                    font_id: *font_id,
+                })
+            } else {
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+    if texts.is_empty() {
+        // Keep an empty section so that the layout still has a font for the cursor.
+        vec![SectionText {
+            text: &text[range.start..range.start],
+            scale,
+            color,
+            font_id: FontId(0),
+        }]
+    } else {
+        texts
+    }
+}
+
 fn cached_color_texture(
     cache: &mut HashMap<KeyColor, TextureHandle>,
     color: [f32; 4],
@@ -1,11 +1,11 @@
 use derivative::Derivative;
 use serde::de::DeserializeOwned;
 use shred_derive::SystemData;
-use std::marker::PhantomData;
+use std::{collections::BTreeMap, marker::PhantomData, sync::Arc};
 
 use amethyst_assets::{
-    AssetPrefab, AssetStorage, Format, Handle, Loader, Prefab, PrefabData, PrefabLoaderSystem,
-    Progress, ProgressCounter, SimpleFormat,
+    AssetPrefab, AssetStorage, Format, FormatValue, Handle, Loader, Prefab, PrefabData,
+    PrefabLoaderSystem, Progress, ProgressCounter, Reload, SingleFile, Source,
 };
 use amethyst_audio::{AudioFormat, Source as Audio};
 use amethyst_core::ecs::prelude::{Entities, Entity, Read, ReadExpect, Write, WriteStorage};
@@ -18,8 +18,8 @@ use serde::{Deserialize, Serialize};
 
 use crate::{
     get_default_font, Anchor, FontAsset, FontFormat, Interactable, LineMode, Selectable, Stretch,
-    TextEditing, UiButton, UiButtonAction, UiButtonActionRetrigger, UiButtonActionType,
-    UiPlaySoundAction, UiSoundRetrigger, UiText, UiTransform, WidgetId, Widgets,
+    TextEditing, UiButton, UiButtonAction, UiButtonActionRetrigger, UiButtonActionType, UiClip,
+    UiPlaySoundAction, UiSoundRetrigger, UiStyled, UiText, UiTransform, WidgetId, Widgets,
 };
 
 /// Loadable `UiTransform` data.
@@ -66,6 +66,10 @@ pub struct UiTransformBuilder<G> {
     /// this ordering backwards.
     // TODO: Make full prefab for Selectable.
     pub selectable: Option<u32>,
+    /// Name of a style in the active `UiTheme` applied to this element, see `UiStyled`.
+    pub style: Option<String>,
+    /// Clips the children of this element to its rectangle by adding a `UiClip` component.
+    pub clip: bool,
     #[serde(skip)]
     _phantom: PhantomData<G>,
 }
@@ -124,6 +128,21 @@ impl<G> UiTransformBuilder<G> {
         self.stretch = Some(stretch);
         self
     }
+
+    /// Clip children to this element
+    pub fn clip_children(mut self) -> Self {
+        self.clip = true;
+        self
+    }
+
+    /// Set style
+    pub fn with_style<S>(mut self, style: S) -> Self
+    where
+        S: ToString,
+    {
+        self.style = Some(style.to_string());
+        self
+    }
 }
 
 impl<'a, G> PrefabData<'a> for UiTransformBuilder<G>
@@ -135,6 +154,8 @@ where
         WriteStorage<'a, Interactable>,
         WriteStorage<'a, HiddenPropagate>,
         WriteStorage<'a, Selectable<G>>,
+        WriteStorage<'a, UiStyled>,
+        WriteStorage<'a, UiClip>,
     );
     type Result = ();
 
@@ -176,6 +197,14 @@ where
             system_data.3.insert(entity, Selectable::<G>::new(u))?;
         }
 
+        if let Some(ref style) = self.style {
+            system_data.4.insert(entity, UiStyled::new(style))?;
+        }
+
+        if self.clip {
+            system_data.5.insert(entity, UiClip)?;
+        }
+
         Ok(())
     }
 }
@@ -199,6 +228,9 @@ where
     pub color: [f32; 4],
     /// Font
     pub font: Option<AssetPrefab<FontAsset, F>>,
+    /// Fonts used for the characters `font` has no glyphs for, in order
+    #[serde(default)]
+    pub fallback_fonts: Vec<AssetPrefab<FontAsset, F>>,
     /// Should the text be shown as dots instead of the proper characters?
     #[serde(default)]
     pub password: bool,
@@ -262,6 +294,10 @@ where
             .add_to_entity(entity, fonts, &[], &[])?;
         let mut ui_text = UiText::new(font_handle, self.text.clone(), self.color, self.font_size);
         ui_text.password = self.password;
+        for fallback_font in &self.fallback_fonts {
+            let handle = fallback_font.add_to_entity(entity, fonts, &[], &[])?;
+            ui_text.fallback_fonts.push(handle);
+        }
 
         if let Some(ref align) = self.align {
             ui_text.align = align.clone();
@@ -293,12 +329,17 @@ where
     ) -> Result<bool, Error> {
         let (_, _, ref mut fonts) = system_data;
 
-        self.font
+        let mut loading = self
+            .font
             .get_or_insert_with(|| {
                 let (ref loader, _, ref storage) = fonts;
                 AssetPrefab::Handle(get_default_font(loader, storage))
             })
-            .load_sub_assets(progress, fonts)
+            .load_sub_assets(progress, fonts)?;
+        for fallback_font in &mut self.fallback_fonts {
+            loading |= fallback_font.load_sub_assets(progress, fonts)?;
+        }
+        Ok(loading)
     }
 }
 
@@ -605,6 +646,19 @@ pub enum UiWidget<
         /// Button
         button: UiButtonBuilder<A, I, F, W>,
     },
+    /// Widget tree of another `UiFormat` file, used to define a widget once and reuse it.
+    ///
+    /// Every `${name}` in the included file is replaced by the value of the `name` parameter
+    /// before the file is parsed, so a template can take for example the text and id of a button.
+    /// The values are escaped as the contents of a string, so the placeholders go between quotes,
+    /// as in `text: "${label}"`. The included file can itself contain includes.
+    Include {
+        /// Asset path of the included file
+        path: String,
+        /// Values substituted for the `${name}` placeholders of the included file
+        #[serde(default)]
+        params: BTreeMap<String, String>,
+    },
     /// Custom UI widget
     Custom(Box<C>),
 }
@@ -624,7 +678,7 @@ where
             UiWidget::Image { ref transform, .. } => Some(transform),
             UiWidget::Label { ref transform, .. } => Some(transform),
             UiWidget::Button { ref transform, .. } => Some(transform),
-            UiWidget::Custom(_) => None,
+            UiWidget::Include { .. } | UiWidget::Custom(_) => None,
         }
     }
 
@@ -643,7 +697,7 @@ where
             UiWidget::Button {
                 ref mut transform, ..
             } => Some(transform),
-            UiWidget::Custom(_) => None,
+            UiWidget::Include { .. } | UiWidget::Custom(_) => None,
         }
     }
 
@@ -756,49 +810,152 @@ pub type UiPrefab<
 #[derivative(Clone(bound = ""), Debug(bound = ""), Default(bound = ""))]
 pub struct UiFormat<C>(PhantomData<C>);
 
-impl<A, I, F, C, W> SimpleFormat<UiPrefab<A, I, F, C::PrefabData, W>> for UiFormat<C>
+/// Maximum nesting of `UiWidget::Include`s, reaching it most likely means includes are cyclic.
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+impl<A, I, F, C, W> Format<UiPrefab<A, I, F, C::PrefabData, W>> for UiFormat<C>
 where
     A: Format<Audio, Options = ()> + Sync + DeserializeOwned,
     I: Format<Texture, Options = TextureMetadata> + Sync + DeserializeOwned + Clone,
     F: Format<FontAsset, Options = ()> + Sync + DeserializeOwned + Clone,
-    C: ToNativeWidget<A, I, F, W> + for<'de> serde::Deserialize<'de>,
+    C: ToNativeWidget<A, I, F, W> + for<'de> serde::Deserialize<'de> + Send + Sync + 'static,
     W: WidgetId + DeserializeOwned,
 {
     const NAME: &'static str = "Ui";
     type Options = ();
 
-    fn import(&self, bytes: Vec<u8>, _: ()) -> Result<UiPrefab<A, I, F, C::PrefabData, W>, Error> {
-        use ron::de::Deserializer;
-        let mut d = Deserializer::from_bytes(&bytes)
-            .with_context(|_| format_err!("Failed deserializing Ron file"))?;
-        let root: UiWidget<A, I, F, C, W> = UiWidget::deserialize(&mut d)
-            .with_context(|_| format_err!("Failed parsing Ron file"))?;
-        d.end()
-            .with_context(|_| format_err!("Failed parsing Ron file"))?;
+    fn import(
+        &self,
+        name: String,
+        source: Arc<dyn Source>,
+        _: (),
+        create_reload: bool,
+    ) -> Result<FormatValue<UiPrefab<A, I, F, C::PrefabData, W>>, Error> {
+        let (bytes, modified) = source
+            .load_with_metadata(&name)
+            .with_context(|_| format_err!("Failed loading ui file '{}'", name))?;
+        let root = parse_widget::<A, I, F, C, W>(&bytes)?;
 
         let mut prefab = Prefab::new();
-        walk_ui_tree(root, 0, &mut prefab, Default::default());
+        walk_ui_tree(root, 0, &mut prefab, Default::default(), &*source, 0)?;
 
-        Ok(prefab)
+        // Only changes to the root file trigger a reload, not changes to included files.
+        let reload = if create_reload {
+            let reload = SingleFile::new(self.clone(), modified, (), name, source);
+            Some(Box::new(reload) as Box<dyn Reload<UiPrefab<A, I, F, C::PrefabData, W>>>)
+        } else {
+            None
+        };
+        Ok(FormatValue {
+            data: prefab,
+            reload,
+        })
     }
 }
 
+fn parse_widget<A, I, F, C, W>(bytes: &[u8]) -> Result<UiWidget<A, I, F, C, W>, Error>
+where
+    A: Format<Audio, Options = ()> + DeserializeOwned,
+    I: Format<Texture, Options = TextureMetadata> + DeserializeOwned,
+    F: Format<FontAsset, Options = ()> + DeserializeOwned,
+    C: ToNativeWidget<A, I, F, W> + for<'de> serde::Deserialize<'de>,
+    W: WidgetId + DeserializeOwned,
+{
+    use ron::de::Deserializer;
+    let mut d = Deserializer::from_bytes(bytes)
+        .with_context(|_| format_err!("Failed deserializing Ron file"))?;
+    let root: UiWidget<A, I, F, C, W> =
+        UiWidget::deserialize(&mut d).with_context(|_| format_err!("Failed parsing Ron file"))?;
+    d.end()
+        .with_context(|_| format_err!("Failed parsing Ron file"))?;
+    Ok(root)
+}
+
+/// Replaces every `${name}` in `text` by the value of the `name` parameter, escaped for a RON
+/// string, placeholders without a parameter are left untouched.
+fn substitute_params(text: &str, params: &BTreeMap<String, String>) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("${") {
+        result.push_str(&rest[..start]);
+        let placeholder = &rest[start..];
+        match placeholder
+            .find('}')
+            .and_then(|end| params.get(&placeholder[2..end]).map(|value| (end, value)))
+        {
+            Some((end, value)) => {
+                for c in value.chars() {
+                    match c {
+                        '\\' => result.push_str("\\\\"),
+                        '"' => result.push_str("\\\""),
+                        '\n' => result.push_str("\\n"),
+                        '\r' => result.push_str("\\r"),
+                        '\t' => result.push_str("\\t"),
+                        c => result.push(c),
+                    }
+                }
+                rest = &placeholder[end + 1..];
+            }
+            None => {
+                result.push_str("${");
+                rest = &placeholder[2..];
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
 fn walk_ui_tree<A, I, F, C, W>(
     widget: UiWidget<A, I, F, C, W>,
     current_index: usize,
     prefab: &mut Prefab<UiPrefabData<A, I, F, C::PrefabData, W>>,
     custom_data: C::PrefabData,
-) where
-    A: Format<Audio, Options = ()>,
-    I: Format<Texture, Options = TextureMetadata> + Clone,
-    F: Format<FontAsset, Options = ()> + Clone,
-    C: ToNativeWidget<A, I, F, W>,
-    W: WidgetId,
+    source: &dyn Source,
+    include_depth: usize,
+) -> Result<(), Error>
+where
+    A: Format<Audio, Options = ()> + DeserializeOwned,
+    I: Format<Texture, Options = TextureMetadata> + DeserializeOwned + Clone,
+    F: Format<FontAsset, Options = ()> + DeserializeOwned + Clone,
+    C: ToNativeWidget<A, I, F, W> + for<'de> serde::Deserialize<'de>,
+    W: WidgetId + DeserializeOwned,
 {
     match widget {
         UiWidget::Custom(custom) => {
             let (widget, custom_data) = custom.to_native_widget(custom_data);
-            walk_ui_tree(widget, current_index, prefab, custom_data);
+            walk_ui_tree(
+                widget,
+                current_index,
+                prefab,
+                custom_data,
+                source,
+                include_depth,
+            )?;
+        }
+
+        UiWidget::Include { path, params } => {
+            if include_depth >= MAX_INCLUDE_DEPTH {
+                return Err(format_err!(
+                    "Too many nested includes when including ui file '{}', includes may be cyclic",
+                    path
+                ));
+            }
+            let bytes = source
+                .load(&path)
+                .with_context(|_| format_err!("Failed loading included ui file '{}'", path))?;
+            let text = String::from_utf8(bytes)
+                .with_context(|_| format_err!("Included ui file '{}' is not utf-8", path))?;
+            let widget = parse_widget(substitute_params(&text, &params).as_bytes())
+                .with_context(|_| format_err!("Failed parsing included ui file '{}'", path))?;
+            walk_ui_tree(
+                widget,
+                current_index,
+                prefab,
+                custom_data,
+                source,
+                include_depth + 1,
+            )?;
         }
 
         UiWidget::Image { transform, image } => {
@@ -827,7 +984,14 @@ fn walk_ui_tree<A, I, F, C, W>(
 
             for child_widget in children {
                 let child_index = prefab.add(Some(current_index), None);
-                walk_ui_tree(child_widget, child_index, prefab, Default::default());
+                walk_ui_tree(
+                    child_widget,
+                    child_index,
+                    prefab,
+                    Default::default(),
+                    source,
+                    include_depth,
+                )?;
             }
         }
 
@@ -837,6 +1001,7 @@ fn walk_ui_tree<A, I, F, C, W>(
                 color: button.normal_text_color,
                 editable: None,
                 font: button.font.clone(),
+                fallback_fonts: Vec::new(),
                 password: false,
                 align: None,
                 line_mode: None,
@@ -869,6 +1034,7 @@ fn walk_ui_tree<A, I, F, C, W>(
             );
         }
     }
+    Ok(())
 }
 
 /// Specialised UI loader
@@ -1018,3 +1184,65 @@ fn button_text_transform<G>(mut id: String) -> UiTransformBuilder<G> {
         })
         .transparent()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn substitutes_include_params() {
+        let mut params = BTreeMap::new();
+        params.insert("label".to_string(), "Start".to_string());
+        params.insert("id".to_string(), "start_button".to_string());
+        assert_eq!(
+            substitute_params(
+                r#"(id: "${id}", text: "${label}", other: "${missing}")"#,
+                &params
+            ),
+            r#"(id: "start_button", text: "Start", other: "${missing}")"#
+        );
+
+        params.insert("label".to_string(), "Say \"hi\"\\\n".to_string());
+        assert_eq!(
+            substitute_params(r#"text: "${label}""#, &params),
+            r#"text: "Say \"hi\"\\\n""#
+        );
+    }
+
+    struct Files(Vec<(&'static str, &'static str)>);
+
+    impl Source for Files {
+        fn modified(&self, _: &str) -> Result<u64, Error> {
+            Ok(0)
+        }
+
+        fn load(&self, path: &str) -> Result<Vec<u8>, Error> {
+            self.0
+                .iter()
+                .find(|(name, _)| *name == path)
+                .map(|(_, text)| text.as_bytes().to_vec())
+                .ok_or_else(|| format_err!("No file '{}'", path))
+        }
+    }
+
+    #[test]
+    fn cyclic_includes_are_refused() {
+        let files = Files(vec![
+            ("root.ron", r#"Include(path: "a.ron")"#),
+            ("a.ron", r#"Include(path: "b.ron")"#),
+            ("b.ron", r#"Include(path: "a.ron")"#),
+        ]);
+        let result = Format::<UiPrefab>::import(
+            &UiFormat::<NoCustomUi>::default(),
+            "root.ron".to_string(),
+            Arc::new(files),
+            (),
+            false,
+        );
+        let error = result
+            .err()
+            .expect("cyclic includes should fail")
+            .to_string();
+        assert!(error.contains("Too many nested includes"), "{}", error);
+    }
+}
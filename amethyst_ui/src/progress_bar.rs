@@ -0,0 +1,403 @@
+use shred::SystemData;
+use shred_derive::SystemData;
+
+use amethyst_assets::{AssetStorage, Loader};
+use amethyst_core::{
+    ecs::prelude::{
+        Component, DenseVecStorage, Entities, Entity, Join, Read, ReadExpect, ReadStorage, System,
+        World, WriteExpect, WriteStorage,
+    },
+    Parent,
+};
+use amethyst_renderer::{Texture, TextureHandle};
+
+use crate::{
+    define_widget, font::default::get_default_font, Anchor, FontAsset, FontHandle, Stretch, UiFill,
+    UiFillMode, UiText, UiTransform, WidgetId, Widgets,
+};
+
+const DEFAULT_Z: f32 = 1.0;
+const DEFAULT_WIDTH: f32 = 128.0;
+const DEFAULT_HEIGHT: f32 = 24.0;
+const DEFAULT_BKGD_COLOR: [f32; 4] = [0.25, 0.25, 0.25, 1.0];
+const DEFAULT_FILL_COLOR: [f32; 4] = [0.2, 0.7, 0.3, 1.0];
+const DEFAULT_TXT_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+
+define_widget!(UiProgressBar =>
+    entities: [background_entity, fill_entity, text_entity]
+    components: [
+        (has UiTransform as position on background_entity),
+        (has UiProgress as progress on background_entity),
+        (has TextureHandle as background on background_entity),
+        (has UiFill as fill on fill_entity),
+        (has TextureHandle as fill_image on fill_entity),
+        (has UiText as text on text_entity)
+    ]
+);
+
+/// The value of a progress bar, copied to the `UiFill` of its fill entity by the
+/// `UiProgressBarSystem`.
+///
+/// Can be animated with the animation crate.
+#[derive(Clone, Debug)]
+pub struct UiProgress {
+    /// Progress from 0 to 1.
+    pub value: f32,
+    /// Entity with the `UiFill` showing the progress.
+    pub fill: Entity,
+    /// Entity with the `UiText` showing the progress as a percentage, if any.
+    pub text: Option<Entity>,
+}
+
+impl UiProgress {
+    /// Creates a new progress value.
+    pub fn new(value: f32, fill: Entity, text: Option<Entity>) -> Self {
+        UiProgress { value, fill, text }
+    }
+}
+
+impl Component for UiProgress {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// Keeps the fill and text of `UiProgress` entities in sync with their value.
+///
+/// It's automatically registered with the `UiBundle`.
+#[derive(Default)]
+pub struct UiProgressBarSystem;
+
+impl UiProgressBarSystem {
+    /// Creates a new `UiProgressBarSystem`.
+    pub fn new() -> Self {
+        UiProgressBarSystem
+    }
+}
+
+impl<'a> System<'a> for UiProgressBarSystem {
+    type SystemData = (
+        ReadStorage<'a, UiProgress>,
+        WriteStorage<'a, UiFill>,
+        WriteStorage<'a, UiText>,
+    );
+
+    #[allow(clippy::float_cmp)] // cmp just used to recognize change
+    fn run(&mut self, (progresses, mut fills, mut texts): Self::SystemData) {
+        for progress in (&progresses).join() {
+            let value = progress.value.max(0.0).min(1.0);
+            if let Some(fill) = fills.get_mut(progress.fill) {
+                if fill.value != value {
+                    fill.value = value;
+                }
+            }
+            if let Some(text) = progress.text.and_then(|text| texts.get_mut(text)) {
+                let percentage = format!("{:.0}%", value * 100.0);
+                if text.text != percentage {
+                    text.text = percentage;
+                }
+            }
+        }
+    }
+}
+
+/// Container for all the resources the builder needs to make a new UiProgressBar.
+#[derive(SystemData)]
+pub struct UiProgressBarBuilderResources<'a, I: WidgetId = u32> {
+    font_asset: Read<'a, AssetStorage<FontAsset>>,
+    texture_asset: Read<'a, AssetStorage<Texture>>,
+    loader: ReadExpect<'a, Loader>,
+    entities: Entities<'a>,
+    image: WriteStorage<'a, TextureHandle>,
+    parent: WriteStorage<'a, Parent>,
+    text: WriteStorage<'a, UiText>,
+    transform: WriteStorage<'a, UiTransform>,
+    fill: WriteStorage<'a, UiFill>,
+    progress: WriteStorage<'a, UiProgress>,
+    progress_bar_widgets: WriteExpect<'a, Widgets<UiProgressBar, I>>,
+}
+
+/// Convenience structure for building a progress bar
+#[derive(Debug, Clone)]
+pub struct UiProgressBarBuilder<I: WidgetId = u32> {
+    id: Option<I>,
+    x: f32,
+    y: f32,
+    z: f32,
+    width: f32,
+    height: f32,
+    anchor: Anchor,
+    stretch: Stretch,
+    value: f32,
+    mode: UiFillMode,
+    background: Option<TextureHandle>,
+    fill: Option<TextureHandle>,
+    show_percentage: bool,
+    text_color: [f32; 4],
+    font: Option<FontHandle>,
+    font_size: f32,
+    parent: Option<Entity>,
+}
+
+impl<I: WidgetId> Default for UiProgressBarBuilder<I> {
+    fn default() -> Self {
+        UiProgressBarBuilder {
+            id: None,
+            x: 0.,
+            y: 0.,
+            z: DEFAULT_Z,
+            width: DEFAULT_WIDTH,
+            height: DEFAULT_HEIGHT,
+            anchor: Anchor::TopLeft,
+            stretch: Stretch::NoStretch,
+            value: 0.,
+            mode: UiFillMode::LeftToRight,
+            background: None,
+            fill: None,
+            show_percentage: false,
+            text_color: DEFAULT_TXT_COLOR,
+            font: None,
+            font_size: 16.,
+            parent: None,
+        }
+    }
+}
+
+impl<'a, I: WidgetId + 'static> UiProgressBarBuilder<I> {
+    /// Construct a new UiProgressBarBuilder with the given initial value.
+    /// The value can later be changed through the `UiProgress` component of the background
+    /// entity, see [`Widgets`](../struct.Widgets.html).
+    pub fn new(value: f32) -> UiProgressBarBuilder<I> {
+        let mut builder = UiProgressBarBuilder::default();
+        builder.value = value;
+        builder
+    }
+
+    /// Sets an ID for this widget. The type of this ID will determine which `Widgets`
+    /// resource this widget will be added to, see [`Widgets`](../struct.Widgets.html).
+    pub fn with_id(mut self, id: I) -> Self {
+        self.id = Some(id);
+        self
+    }
+
+    /// Set progress bar size
+    pub fn with_size(mut self, width: f32, height: f32) -> Self {
+        self.width = width;
+        self.height = height;
+        self
+    }
+
+    /// Set progress bar position
+    pub fn with_position(mut self, x: f32, y: f32) -> Self {
+        self.x = x;
+        self.y = y;
+        self
+    }
+
+    /// Set progress bar z order
+    pub fn with_layer(mut self, z: f32) -> Self {
+        self.z = z;
+        self
+    }
+
+    /// Add an anchor to the progress bar.
+    pub fn with_anchor(mut self, anchor: Anchor) -> Self {
+        self.anchor = anchor;
+        self
+    }
+
+    /// Stretch the progress bar.
+    pub fn with_stretch(mut self, stretch: Stretch) -> Self {
+        self.stretch = stretch;
+        self
+    }
+
+    /// Set the direction in which the bar fills, defaults to `UiFillMode::LeftToRight`.
+    pub fn with_fill_mode(mut self, mode: UiFillMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Use a texture for the background of the bar.
+    pub fn with_background(mut self, image: TextureHandle) -> Self {
+        self.background = Some(image);
+        self
+    }
+
+    /// Use a texture for the filled part of the bar.
+    pub fn with_fill_image(mut self, image: TextureHandle) -> Self {
+        self.fill = Some(image);
+        self
+    }
+
+    /// Show the progress as a percentage on top of the bar.
+    pub fn with_percentage(mut self) -> Self {
+        self.show_percentage = true;
+        self
+    }
+
+    /// Set text color
+    pub fn with_text_color(mut self, text_color: [f32; 4]) -> Self {
+        self.text_color = text_color;
+        self
+    }
+
+    /// Use a different font for the percentage text.
+    pub fn with_font(mut self, font: FontHandle) -> Self {
+        self.font = Some(font);
+        self
+    }
+
+    /// Set font size
+    pub fn with_font_size(mut self, size: f32) -> Self {
+        self.font_size = size;
+        self
+    }
+
+    /// Add a parent to the progress bar.
+    pub fn with_parent(mut self, parent: Entity) -> Self {
+        self.parent = Some(parent);
+        self
+    }
+
+    /// Build this with the `UiProgressBarBuilderResources`.
+    pub fn build(self, mut res: UiProgressBarBuilderResources<'a, I>) -> (I, UiProgressBar) {
+        let background_entity = res.entities.create();
+        let fill_entity = res.entities.create();
+        let text_entity = res.entities.create();
+        let widget = UiProgressBar::new(background_entity, fill_entity, text_entity);
+
+        let id = {
+            let widget = widget.clone();
+
+            if let Some(id) = self.id {
+                let added_id = id.clone();
+                res.progress_bar_widgets.add_with_id(id, widget);
+                added_id
+            } else {
+                res.progress_bar_widgets.add(widget)
+            }
+        };
+
+        res.transform
+            .insert(
+                background_entity,
+                UiTransform::new(
+                    format!("{}_progress", id),
+                    self.anchor,
+                    self.x,
+                    self.y,
+                    self.z,
+                    self.width,
+                    self.height,
+                )
+                .with_stretch(self.stretch),
+            )
+            .expect("Unreachable: Inserting newly created entity");
+        let background = self.background.unwrap_or_else(|| {
+            res.loader
+                .load_from_data(DEFAULT_BKGD_COLOR.into(), (), &res.texture_asset)
+        });
+        res.image
+            .insert(background_entity, background)
+            .expect("Unreachable: Inserting newly created entity");
+        res.progress
+            .insert(
+                background_entity,
+                UiProgress::new(
+                    self.value,
+                    fill_entity,
+                    if self.show_percentage {
+                        Some(text_entity)
+                    } else {
+                        None
+                    },
+                ),
+            )
+            .expect("Unreachable: Inserting newly created entity");
+        if let Some(parent) = self.parent {
+            res.parent
+                .insert(background_entity, Parent { entity: parent })
+                .expect("Unreachable: Inserting newly created entity");
+        }
+
+        let fill_stretch = Stretch::XY {
+            x_margin: 0.,
+            y_margin: 0.,
+            keep_aspect_ratio: false,
+        };
+        res.transform
+            .insert(
+                fill_entity,
+                UiTransform::new(
+                    format!("{}_progress_fill", id),
+                    Anchor::Middle,
+                    0.,
+                    0.,
+                    0.01,
+                    0.,
+                    0.,
+                )
+                .as_transparent()
+                .with_stretch(fill_stretch.clone()),
+            )
+            .expect("Unreachable: Inserting newly created entity");
+        let fill = self.fill.unwrap_or_else(|| {
+            res.loader
+                .load_from_data(DEFAULT_FILL_COLOR.into(), (), &res.texture_asset)
+        });
+        res.image
+            .insert(fill_entity, fill)
+            .expect("Unreachable: Inserting newly created entity");
+        res.fill
+            .insert(fill_entity, UiFill::new(self.mode, self.value))
+            .expect("Unreachable: Inserting newly created entity");
+        res.parent
+            .insert(
+                fill_entity,
+                Parent {
+                    entity: background_entity,
+                },
+            )
+            .expect("Unreachable: Inserting newly created entity");
+
+        res.transform
+            .insert(
+                text_entity,
+                UiTransform::new(
+                    format!("{}_progress_text", id),
+                    Anchor::Middle,
+                    0.,
+                    0.,
+                    0.02,
+                    0.,
+                    0.,
+                )
+                .as_transparent()
+                .with_stretch(fill_stretch),
+            )
+            .expect("Unreachable: Inserting newly created entity");
+        let font_handle = self
+            .font
+            .unwrap_or_else(|| get_default_font(&res.loader, &res.font_asset));
+        res.text
+            .insert(
+                text_entity,
+                UiText::new(font_handle, String::new(), self.text_color, self.font_size),
+            )
+            .expect("Unreachable: Inserting newly created entity");
+        res.parent
+            .insert(
+                text_entity,
+                Parent {
+                    entity: background_entity,
+                },
+            )
+            .expect("Unreachable: Inserting newly created entity");
+
+        (id, widget)
+    }
+
+    /// Create the UiProgressBar based on provided configuration parameters.
+    pub fn build_from_world(self, world: &World) -> (I, UiProgressBar) {
+        self.build(UiProgressBarBuilderResources::<I>::fetch(&world.res))
+    }
+}
@@ -8,10 +8,21 @@ in VertexData {
   vec4 position;
   vec2 tex_coord;
   vec4 color;
+  vec4 radial_fill;
 } vertex;
 
 out vec4 color;
 
+const float TAU = 6.28318530718;
+
 void main() {
+    if (vertex.radial_fill.w > 0.5) {
+        // Angle around the center of the texture, clockwise from the top.
+        vec2 dir = vertex.tex_coord - vec2(0.5);
+        float angle = (atan(dir.x, dir.y) - vertex.radial_fill.y) * vertex.radial_fill.z;
+        if (fract(angle / TAU) >= vertex.radial_fill.x) {
+            discard;
+        }
+    }
     color = texture(albedo, vertex.tex_coord) * vertex.color;
 }
@@ -8,6 +8,10 @@ layout (std140) uniform VertexArgs {
     uniform vec2 coord;
     uniform vec2 dimension;
     uniform vec4 color;
+    // Region of the texture to sample, as (u_min, v_min, u_max, v_max).
+    uniform vec4 tex_coord_bounds;
+    // Radial fill, as (value, start angle, direction, enabled).
+    uniform vec4 radial_fill;
 };
 
 // Square [-1.0,1.0]
@@ -18,6 +22,7 @@ out VertexData {
   vec4 position;
   vec2 tex_coord;
   vec4 color;
+  vec4 radial_fill;
 } vertex;
 
 void main() {
@@ -38,7 +43,8 @@ void main() {
     // Recenter the whole viewport.
     vertex.position += vec4(-1, -1, 0, 0);
 
-    vertex.tex_coord = tex_coord;
+    vertex.tex_coord = mix(tex_coord_bounds.xy, tex_coord_bounds.zw, tex_coord);
     vertex.color = color;
+    vertex.radial_fill = radial_fill;
     gl_Position = vertex.position;
 }
@@ -0,0 +1,499 @@
+//! Shaping and laying out the texts, for the scripts whose glyphs depend on their neighbours,
+//! like the joining forms of Arabic or the conjuncts of the Indic scripts.
+
+use std::{
+    hash::{Hash, Hasher},
+    ops::Range,
+};
+
+use gfx_glyph::{
+    rusttype::{point, GlyphId, Rect},
+    BuiltInLineBreaker, Font, FontId, FontMap, GlyphPositioner, HorizontalAlign, Layout,
+    PositionedGlyph, Scale, SectionGeometry, SectionText, VerticalAlign,
+};
+use rustybuzz::{Direction, Face, UnicodeBuffer};
+use ttf_parser::RasterImageFormat;
+use unicode_bidi::BidiInfo;
+use xi_unicode::LineBreakIterator;
+
+use crate::bidi::visual_runs;
+
+/// A font of a text, with its file for the shaper.
+pub(crate) struct ShapingFont<'a> {
+    pub font: &'a Font<'static>,
+    pub data: &'a [u8],
+}
+
+/// How a text is laid out.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct ShapingParams {
+    /// The glyph brush of the fonts of the text.
+    pub brush_id: u64,
+    pub scale: Scale,
+    /// The width the lines are wrapped at, `None` to keep the text on a single line.
+    pub width: Option<f32>,
+    pub h_align: HorizontalAlign,
+    pub v_align: VerticalAlign,
+}
+
+/// A glyph of a shaped text, positioned from the screen position of its section.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct ShapedGlyph {
+    pub font_id: FontId,
+    pub id: u32,
+    /// The position of the glyph on its baseline, with y down.
+    pub x: f32,
+    pub y: f32,
+    /// The byte offset of the characters the glyph was shaped from.
+    pub cluster: usize,
+    /// The color bitmap drawn instead of the outline of the glyph, if it has one.
+    pub bitmap: Option<BitmapGlyph>,
+}
+
+/// The color bitmap of a glyph, such as the emoji of the color fonts, drawn as an image since
+/// the glyph brush only draws outlines.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct BitmapGlyph {
+    /// The size of the bitmaps the image is from, in pixels per em.
+    pub strike: u16,
+    /// The left, top, width and height of the image from the position of the glyph, with y down.
+    pub rect: [f32; 4],
+}
+
+/// The glyphs of a text, shaped, broken into lines and laid out.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct ShapedText {
+    pub glyphs: Vec<ShapedGlyph>,
+    pub lines: usize,
+}
+
+impl Hash for ShapedText {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.lines.hash(state);
+        for glyph in &self.glyphs {
+            glyph.font_id.hash(state);
+            glyph.id.hash(state);
+            glyph.x.to_bits().hash(state);
+            glyph.y.to_bits().hash(state);
+            glyph.bitmap.is_some().hash(state);
+        }
+    }
+}
+
+/// The shaped text of a `UiText`, kept until the text or its layout change.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct CachedShaping {
+    text: String,
+    params: Option<ShapingParams>,
+    shaped: ShapedText,
+}
+
+impl CachedShaping {
+    /// The shaped `text`, shaped with the `fonts` if it wasn't already.
+    pub(crate) fn shaped<'a, F>(
+        &mut self,
+        text: &str,
+        params: ShapingParams,
+        runs: &[(Range<usize>, FontId)],
+        fonts: F,
+    ) -> &ShapedText
+    where
+        F: FnOnce() -> Vec<ShapingFont<'a>>,
+    {
+        if self.params != Some(params) || self.text != text {
+            self.shaped = shape(text, runs, &fonts(), &params);
+            self.text.clear();
+            self.text.push_str(text);
+            self.params = Some(params);
+        }
+        &self.shaped
+    }
+}
+
+/// Places the glyphs of a `ShapedText` for the glyph brush, instead of its own layout, which
+/// lays out the characters one by one.
+#[derive(Hash)]
+pub(crate) struct ShapedLayout<'a> {
+    pub text: &'a ShapedText,
+    /// The layout of the brush with the same alignment, for the bounds of the section.
+    pub layout: Layout<BuiltInLineBreaker>,
+}
+
+impl<'a> GlyphPositioner for ShapedLayout<'a> {
+    fn calculate_glyphs<'font, F: FontMap<'font>>(
+        &self,
+        fonts: &F,
+        geometry: &SectionGeometry,
+        sections: &[SectionText<'_>],
+    ) -> Vec<(PositionedGlyph<'font>, [f32; 4], FontId)> {
+        let (scale, color) = match sections.first() {
+            Some(section) => (section.scale, section.color),
+            None => return Vec::new(),
+        };
+        let (x, y) = geometry.screen_position;
+        self.text
+            .glyphs
+            .iter()
+            .filter(|glyph| glyph.bitmap.is_none())
+            .map(|glyph| {
+                let positioned = fonts
+                    .font(glyph.font_id)
+                    .glyph(GlyphId(glyph.id))
+                    .scaled(scale)
+                    .positioned(point(x + glyph.x, y + glyph.y));
+                (positioned, color, glyph.font_id)
+            })
+            .collect()
+    }
+
+    fn bounds_rect(&self, geometry: &SectionGeometry) -> Rect<f32> {
+        self.layout.bounds_rect(geometry)
+    }
+}
+
+// A run of a text shaped with one font in one direction.
+struct Run {
+    range: Range<usize>,
+    // In display order.
+    glyphs: Vec<RunGlyph>,
+}
+
+struct RunGlyph {
+    font_id: FontId,
+    id: u32,
+    cluster: usize,
+    advance: f32,
+    x_offset: f32,
+    y_offset: f32,
+    bitmap: Option<BitmapGlyph>,
+}
+
+/// Shapes `text` with its font `runs`, then breaks it into lines, reorders every line for the
+/// right-to-left scripts and aligns them.
+pub(crate) fn shape(
+    text: &str,
+    runs: &[(Range<usize>, FontId)],
+    fonts: &[ShapingFont<'_>],
+    params: &ShapingParams,
+) -> ShapedText {
+    let base = match fonts.first() {
+        Some(base) => base.font,
+        None => return ShapedText::default(),
+    };
+    let faces = fonts
+        .iter()
+        .map(|font| Face::from_slice(font.data, 0))
+        .collect::<Vec<_>>();
+    let info = BidiInfo::new(text, None);
+
+    let mut shaped_runs = Vec::new();
+    for (range, font_id) in runs {
+        for (range, rtl) in split_run(text, &info, range.clone()) {
+            let glyphs = match (fonts.get(font_id.0), faces.get(font_id.0)) {
+                (Some(font), Some(Some(face))) => shape_run(
+                    &text[range.clone()],
+                    range.start,
+                    *font_id,
+                    rtl,
+                    font,
+                    face,
+                    params.scale,
+                ),
+                (Some(font), _) => unshaped_run(
+                    &text[range.clone()],
+                    range.start,
+                    *font_id,
+                    rtl,
+                    font,
+                    params.scale,
+                ),
+                (None, _) => continue,
+            };
+            shaped_runs.push(Run { range, glyphs });
+        }
+    }
+
+    let lines = break_lines(text, &shaped_runs, params.width);
+    let metrics = base.v_metrics(params.scale);
+    let line_height = metrics.ascent - metrics.descent + metrics.line_gap;
+    let height = line_height * lines.len() as f32;
+    let top = match params.v_align {
+        VerticalAlign::Top => 0.0,
+        VerticalAlign::Center => -height / 2.0,
+        VerticalAlign::Bottom => -height,
+    };
+
+    let mut glyphs = Vec::new();
+    for (number, line) in lines.iter().enumerate() {
+        let baseline = top + number as f32 * line_height + metrics.ascent;
+        let first = glyphs.len();
+        let mut x = 0.0;
+        for (visual, rtl) in visual_runs(&info, line.clone()) {
+            let mut parts = shaped_runs
+                .iter()
+                .filter(|run| run.range.start < visual.end && visual.start < run.range.end)
+                .collect::<Vec<_>>();
+            if rtl {
+                parts.reverse();
+            }
+            for run in parts {
+                for glyph in &run.glyphs {
+                    if glyph.cluster < visual.start || glyph.cluster >= visual.end {
+                        continue;
+                    }
+                    glyphs.push(ShapedGlyph {
+                        font_id: glyph.font_id,
+                        id: glyph.id,
+                        x: x + glyph.x_offset,
+                        y: baseline - glyph.y_offset,
+                        cluster: glyph.cluster,
+                        bitmap: glyph.bitmap.clone(),
+                    });
+                    x += glyph.advance;
+                }
+            }
+        }
+        let shift = match params.h_align {
+            HorizontalAlign::Left => 0.0,
+            HorizontalAlign::Center => -x / 2.0,
+            HorizontalAlign::Right => -x,
+        };
+        for glyph in &mut glyphs[first..] {
+            glyph.x += shift;
+        }
+    }
+
+    ShapedText {
+        glyphs,
+        lines: lines.len(),
+    }
+}
+
+// Splits a font run at the changes of direction and at the line breaks, which aren't shaped.
+fn split_run(text: &str, info: &BidiInfo<'_>, range: Range<usize>) -> Vec<(Range<usize>, bool)> {
+    let mut parts: Vec<(Range<usize>, bool)> = Vec::new();
+    let mut start = None;
+    for (offset, c) in text[range.clone()].char_indices() {
+        let index = range.start + offset;
+        let rtl = info.levels[index].is_rtl();
+        if c == '\n' || c == '\r' {
+            if let Some((begin, rtl)) = start.take() {
+                parts.push((begin..index, rtl));
+            }
+            continue;
+        }
+        match start {
+            Some((_, current)) if current == rtl => {}
+            Some((begin, current)) => {
+                parts.push((begin..index, current));
+                start = Some((index, rtl));
+            }
+            None => start = Some((index, rtl)),
+        }
+    }
+    if let Some((begin, rtl)) = start {
+        parts.push((begin..range.end, rtl));
+    }
+    parts
+}
+
+// The factor from the units of the font to pixels, as the glyphs are scaled by rusttype.
+fn units_to_pixels(font: &Font<'_>, scale: Scale) -> (f32, f32) {
+    let metrics = font.v_metrics_unscaled();
+    let height = metrics.ascent - metrics.descent;
+    (scale.x / height, scale.y / height)
+}
+
+fn shape_run(
+    text: &str,
+    offset: usize,
+    font_id: FontId,
+    rtl: bool,
+    font: &ShapingFont<'_>,
+    face: &Face<'_>,
+    scale: Scale,
+) -> Vec<RunGlyph> {
+    let mut buffer = UnicodeBuffer::new();
+    buffer.push_str(text);
+    buffer.set_direction(if rtl {
+        Direction::RightToLeft
+    } else {
+        Direction::LeftToRight
+    });
+    buffer.guess_segment_properties();
+    let output = rustybuzz::shape(face, &[], buffer);
+
+    let (x_scale, y_scale) = units_to_pixels(font.font, scale);
+    let em = y_scale * f32::from(font.font.units_per_em());
+    output
+        .glyph_infos()
+        .iter()
+        .zip(output.glyph_positions())
+        .map(|(info, position)| RunGlyph {
+            font_id,
+            id: info.glyph_id,
+            cluster: offset + info.cluster as usize,
+            advance: position.x_advance as f32 * x_scale,
+            x_offset: position.x_offset as f32 * x_scale,
+            y_offset: position.y_offset as f32 * y_scale,
+            bitmap: bitmap_glyph(face, info.glyph_id, em),
+        })
+        .collect()
+}
+
+// The PNG bitmap of a glyph from the strike the closest to `em` pixels per em, scaled to it.
+fn bitmap_glyph(face: &Face<'_>, id: u32, em: f32) -> Option<BitmapGlyph> {
+    let image =
+        face.glyph_raster_image(ttf_parser::GlyphId(id as u16), em.round().max(1.0) as u16)?;
+    if image.format != RasterImageFormat::PNG || image.pixels_per_em == 0 {
+        return None;
+    }
+    // The offsets are those of the bottom left corner, with y up.
+    let factor = em / f32::from(image.pixels_per_em);
+    Some(BitmapGlyph {
+        strike: image.pixels_per_em,
+        rect: [
+            f32::from(image.x) * factor,
+            -(f32::from(image.y) + f32::from(image.height)) * factor,
+            f32::from(image.width) * factor,
+            f32::from(image.height) * factor,
+        ],
+    })
+}
+
+/// The PNG image of the bitmap of the glyph `id` in the font file `data`, from its `strike`.
+pub(crate) fn bitmap_png(data: &[u8], id: u32, strike: u16) -> Option<Vec<u8>> {
+    let face = Face::from_slice(data, 0)?;
+    let image = face.glyph_raster_image(ttf_parser::GlyphId(id as u16), strike)?;
+    Some(image.data.to_vec())
+}
+
+// The glyphs of the characters, for the fonts the shaper can't read.
+fn unshaped_run(
+    text: &str,
+    offset: usize,
+    font_id: FontId,
+    rtl: bool,
+    font: &ShapingFont<'_>,
+    scale: Scale,
+) -> Vec<RunGlyph> {
+    let mut glyphs = text
+        .char_indices()
+        .map(|(index, c)| {
+            let glyph = font.font.glyph(c).scaled(scale);
+            RunGlyph {
+                font_id,
+                id: glyph.id().0,
+                cluster: offset + index,
+                advance: glyph.h_metrics().advance_width,
+                x_offset: 0.0,
+                y_offset: 0.0,
+                bitmap: None,
+            }
+        })
+        .collect::<Vec<_>>();
+    if rtl {
+        glyphs.reverse();
+    }
+    glyphs
+}
+
+// Breaks the text into lines at the hard breaks, and where it's wider than `width` at the last
+// break opportunity before. The lines don't have their line breaks.
+fn break_lines(text: &str, runs: &[Run], width: Option<f32>) -> Vec<Range<usize>> {
+    let width = match width {
+        Some(width) => width,
+        None => return vec![0..text.len()],
+    };
+
+    // The width of the text before every byte, from the advances of the clusters.
+    let mut before = vec![0.0; text.len() + 1];
+    for glyph in runs.iter().flat_map(|run| &run.glyphs) {
+        before[glyph.cluster + 1] += glyph.advance;
+    }
+    for index in 1..before.len() {
+        before[index] += before[index - 1];
+    }
+    // The trailing whitespace of a line doesn't count in its width.
+    let measure = |range: Range<usize>| {
+        let end = range.start + text[range.clone()].trim_end().len();
+        before[end] - before[range.start]
+    };
+
+    let mut lines = Vec::new();
+    let mut start = 0;
+    let mut last_break = None;
+    for (index, hard) in LineBreakIterator::new(text) {
+        if measure(start..index) > width {
+            if let Some(end) = last_break {
+                lines.push(start..end);
+                start = end;
+            }
+        }
+        if hard && index < text.len() {
+            let line = text[start..index].trim_end_matches(|c| c == '\n' || c == '\r');
+            lines.push(start..start + line.len());
+            start = index;
+            last_break = None;
+        } else {
+            last_break = Some(index);
+        }
+    }
+    if start < text.len() || lines.is_empty() {
+        lines.push(start..text.len());
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SQUARE: &[u8] = include_bytes!("font/square.ttf");
+
+    fn shape_with(text: &str, width: Option<f32>) -> ShapedText {
+        let font = Font::from_bytes(SQUARE).unwrap();
+        let fonts = [ShapingFont {
+            font: &font,
+            data: SQUARE,
+        }];
+        let params = ShapingParams {
+            brush_id: 0,
+            scale: Scale::uniform(16.0),
+            width,
+            h_align: HorizontalAlign::Left,
+            v_align: VerticalAlign::Top,
+        };
+        shape(text, &[(0..text.len(), FontId(0))], &fonts, &params)
+    }
+
+    // The clusters of the glyphs of every line, from left to right.
+    fn lines(shaped: &ShapedText) -> Vec<Vec<usize>> {
+        let mut lines: Vec<(f32, Vec<usize>)> = Vec::new();
+        for glyph in &shaped.glyphs {
+            match lines.last_mut() {
+                Some((y, clusters)) if *y == glyph.y => clusters.push(glyph.cluster),
+                _ => lines.push((glyph.y, vec![glyph.cluster])),
+            }
+        }
+        lines.into_iter().map(|(_, clusters)| clusters).collect()
+    }
+
+    #[test]
+    fn text_is_wrapped_at_break_opportunities() {
+        let shaped = shape_with("ab cd\nef", Some(1.0));
+        assert_eq!(shaped.lines, 3);
+        assert_eq!(lines(&shaped), vec![vec![0, 1, 2], vec![3, 4], vec![6, 7]]);
+
+        let shaped = shape_with("ab cd\nef", None);
+        assert_eq!(shaped.lines, 1);
+    }
+
+    #[test]
+    fn wrapped_right_to_left_lines_keep_their_order() {
+        // Two Hebrew words wrapped on two lines: the first word is on the first line, each
+        // drawn from right to left.
+        let shaped = shape_with("\u{5d0}\u{5d1} \u{5d2}\u{5d3}", Some(1.0));
+        assert_eq!(lines(&shaped), vec![vec![4, 2, 0], vec![7, 5]]);
+    }
+}
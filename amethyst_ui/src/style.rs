@@ -0,0 +1,577 @@
+//! Style sheets shared by the widgets of a user interface.
+
+use std::{
+    collections::HashMap,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use log::{error, warn};
+use serde::{Deserialize, Serialize};
+
+use amethyst_assets::{Asset, AssetStorage, Handle, Loader, ProcessingState};
+use amethyst_core::{
+    ecs::prelude::{
+        BitSet, Component, ComponentEvent, DenseVecStorage, Entities, Entity, FlaggedStorage, Join,
+        Read, ReadExpect, ReadStorage, ReaderId, Resources, System, VecStorage, WriteStorage,
+    },
+    ParentHierarchy,
+};
+use amethyst_error::Error;
+use amethyst_renderer::{Rgba, Texture, TextureFormat, TextureHandle, TextureMetadata};
+
+use crate::{
+    FontAsset, FontFormat, FontHandle, Stretch, UiButtonAction, UiButtonActionRetrigger,
+    UiButtonActionType, UiNinePatch, UiText, UiTransform,
+};
+
+/// The visual properties of a widget, as defined in a `UiStyleSheet`.
+///
+/// Every property is optional: properties that are not set are left untouched on the styled
+/// entity, so a style can override only what it cares about.
+///
+/// Texture and font paths are loaded through the `Loader`, the format is deduced from the file
+/// extension.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UiStyle {
+    /// Path of the font used by the text of the widget.
+    pub font: Option<String>,
+    /// Paths of the fonts used, in order, for the characters the font has no glyphs for.
+    pub fallback_fonts: Option<Vec<String>>,
+    /// Font size of the text of the widget.
+    pub font_size: Option<f32>,
+    /// Text color, using a range of 0.0 to 1.0 per channel.
+    pub text_color: Option<[f32; 4]>,
+    /// Path of the image of the widget.
+    pub image: Option<String>,
+    /// Draws the image of the widget as a nine-patch.
+    pub nine_patch: Option<UiNinePatch>,
+    /// Color multiplied with everything rendered for the widget.
+    pub tint: Option<[f32; 4]>,
+    /// Horizontal and vertical margins applied to a stretched widget.
+    pub padding: Option<(f32, f32)>,
+    /// Path of the image used when the widget is hovered over.
+    pub hover_image: Option<String>,
+    /// Text color used when the widget is hovered over.
+    pub hover_text_color: Option<[f32; 4]>,
+    /// Path of the image used when the widget is pressed.
+    pub press_image: Option<String>,
+    /// Text color used when the widget is pressed.
+    pub press_text_color: Option<[f32; 4]>,
+}
+
+impl UiStyle {
+    fn has_button_states(&self) -> bool {
+        self.hover_image.is_some()
+            || self.hover_text_color.is_some()
+            || self.press_image.is_some()
+            || self.press_text_color.is_some()
+    }
+}
+
+/// A set of named `UiStyle`s, usually loaded from a `ron` file with the `RonFormat`.
+///
+/// ### Example:
+///
+/// ```ron
+/// (
+///     styles: {
+///         "button": (
+///             image: Some("texture/button.png"),
+///             nine_patch: Some((left: 8, right: 8, top: 8, bottom: 8)),
+///             text_color: Some((0.1, 0.1, 0.1, 1.0)),
+///             hover_text_color: Some((0.9, 0.2, 0.2, 1.0)),
+///         ),
+///     },
+/// )
+/// ```
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UiStyleSheet {
+    /// Styles, by name.
+    pub styles: HashMap<String, UiStyle>,
+    // Set when the sheet is loaded, so that the `UiStyleSystem` tells a reloaded sheet from the
+    // one it applied without comparing them.
+    #[serde(skip)]
+    version: usize,
+}
+
+// The versions given to the style sheets as they are loaded.
+static SHEET_VERSIONS: AtomicUsize = AtomicUsize::new(1);
+
+impl PartialEq for UiStyleSheet {
+    fn eq(&self, other: &Self) -> bool {
+        self.styles == other.styles
+    }
+}
+
+impl UiStyleSheet {
+    /// Returns the style with the given name.
+    pub fn get(&self, name: &str) -> Option<&UiStyle> {
+        self.styles.get(name)
+    }
+}
+
+/// A handle to a `UiStyleSheet` asset.
+pub type UiStyleSheetHandle = Handle<UiStyleSheet>;
+
+impl Asset for UiStyleSheet {
+    const NAME: &'static str = "ui::StyleSheet";
+    type Data = Self;
+    type HandleStorage = VecStorage<Handle<Self>>;
+}
+
+impl Into<Result<ProcessingState<UiStyleSheet>, Error>> for UiStyleSheet {
+    fn into(mut self) -> Result<ProcessingState<UiStyleSheet>, Error> {
+        self.version = SHEET_VERSIONS.fetch_add(1, Ordering::Relaxed);
+        Ok(ProcessingState::Loaded(self))
+    }
+}
+
+/// Resource selecting the style sheet used to resolve `UiStyled` components.
+///
+/// Replacing the handle switches the theme of the whole user interface.
+#[derive(Clone, Debug, Default)]
+pub struct UiTheme {
+    /// The active style sheet.
+    pub sheet: Option<UiStyleSheetHandle>,
+}
+
+impl UiTheme {
+    /// Creates a theme using the given style sheet.
+    pub fn new(sheet: UiStyleSheetHandle) -> Self {
+        UiTheme { sheet: Some(sheet) }
+    }
+}
+
+/// Applies the style with the given name from the active `UiTheme` to this entity.
+///
+/// If the entity has no `UiText` itself, text properties are applied to the `UiText` of its
+/// children instead, which is how buttons are built.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct UiStyled {
+    /// Name of the style in the style sheet.
+    pub style: String,
+}
+
+impl UiStyled {
+    /// Creates a new `UiStyled` component referencing the given style.
+    pub fn new<S: ToString>(style: S) -> Self {
+        UiStyled {
+            style: style.to_string(),
+        }
+    }
+}
+
+impl Component for UiStyled {
+    type Storage = FlaggedStorage<Self, DenseVecStorage<Self>>;
+}
+
+/// Applies styles from the active `UiTheme` to entities with a `UiStyled` component.
+///
+/// Styles are applied when the component is attached or changed, and to every styled entity when
+/// the style sheet changes, for instance when it is hot-reloaded or the theme is switched. A
+/// sheet changed in place through `AssetStorage::get_mut` isn't applied again.
+#[derive(Default)]
+pub struct UiStyleSystem {
+    styled_events_id: Option<ReaderId<ComponentEvent>>,
+    dirty: BitSet,
+    applied_sheet: Option<(u32, usize)>,
+    textures: HashMap<String, TextureHandle>,
+    fonts: HashMap<String, FontHandle>,
+}
+
+impl UiStyleSystem {
+    /// Creates a new `UiStyleSystem`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn texture(
+        &mut self,
+        path: &str,
+        loader: &Loader,
+        storage: &AssetStorage<Texture>,
+    ) -> Option<TextureHandle> {
+        if let Some(handle) = self.textures.get(path) {
+            return Some(handle.clone());
+        }
+        let extension = path.rsplit('.').next().unwrap_or("").to_lowercase();
+        let format = match extension.as_str() {
+            "png" => TextureFormat::Png,
+            "jpg" | "jpeg" => TextureFormat::Jpg,
+            "bmp" => TextureFormat::Bmp,
+            "tga" => TextureFormat::Tga,
+            _ => {
+                error!("Style sheet texture '{}' has an unknown format", path);
+                return None;
+            }
+        };
+        let handle = loader.load(path, format, TextureMetadata::srgb_scale(), (), storage);
+        self.textures.insert(path.to_string(), handle.clone());
+        Some(handle)
+    }
+
+    fn font(
+        &mut self,
+        path: &str,
+        loader: &Loader,
+        storage: &AssetStorage<FontAsset>,
+    ) -> Option<FontHandle> {
+        if let Some(handle) = self.fonts.get(path) {
+            return Some(handle.clone());
+        }
+        let extension = path.rsplit('.').next().unwrap_or("").to_lowercase();
+        let format = match extension.as_str() {
+            "ttf" => FontFormat::Ttf,
+            "otf" => FontFormat::Otf,
+            _ => {
+                error!("Style sheet font '{}' has an unknown format", path);
+                return None;
+            }
+        };
+        let handle = loader.load(path, format, (), (), storage);
+        self.fonts.insert(path.to_string(), handle.clone());
+        Some(handle)
+    }
+}
+
+impl<'a> System<'a> for UiStyleSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, UiTheme>,
+        Read<'a, AssetStorage<UiStyleSheet>>,
+        ReadExpect<'a, Loader>,
+        Read<'a, AssetStorage<Texture>>,
+        Read<'a, AssetStorage<FontAsset>>,
+        ReadExpect<'a, ParentHierarchy>,
+        ReadStorage<'a, UiStyled>,
+        WriteStorage<'a, UiTransform>,
+        WriteStorage<'a, UiText>,
+        WriteStorage<'a, TextureHandle>,
+        WriteStorage<'a, UiNinePatch>,
+        WriteStorage<'a, Rgba>,
+        WriteStorage<'a, UiButtonActionRetrigger>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            theme,
+            sheets,
+            loader,
+            texture_storage,
+            font_storage,
+            hierarchy,
+            styled,
+            mut transforms,
+            mut texts,
+            mut images,
+            mut nine_patches,
+            mut tints,
+            mut retriggers,
+        ): Self::SystemData,
+    ) {
+        self.dirty.clear();
+        let dirty = &mut self.dirty;
+        styled
+            .channel()
+            .read(
+                self.styled_events_id
+                    .as_mut()
+                    .expect("`UiStyleSystem::setup` was not called before `UiStyleSystem::run`"),
+            )
+            .for_each(|event| match event {
+                ComponentEvent::Inserted(id) | ComponentEvent::Modified(id) => {
+                    dirty.add(*id);
+                }
+                ComponentEvent::Removed(_id) => {}
+            });
+
+        let (sheet, applied) = match theme.sheet.as_ref().and_then(|handle| {
+            sheets
+                .get(handle)
+                .map(|sheet| (sheet, (handle.id(), sheet.version)))
+        }) {
+            Some(sheet) => sheet,
+            None => return,
+        };
+        let sheet_changed = self.applied_sheet != Some(applied);
+        self.applied_sheet = Some(applied);
+
+        for (entity, styled) in (&*entities, &styled).join() {
+            if !sheet_changed && !self.dirty.contains(entity.id()) {
+                continue;
+            }
+            let style = match sheet.get(&styled.style) {
+                Some(style) => style,
+                None => {
+                    warn!(
+                        "Style '{}' not found in the active style sheet",
+                        styled.style
+                    );
+                    continue;
+                }
+            };
+
+            let font = style
+                .font
+                .as_ref()
+                .and_then(|path| self.font(path, &loader, &font_storage));
+            let fallback_fonts = style.fallback_fonts.as_ref().map(|paths| {
+                paths
+                    .iter()
+                    .filter_map(|path| self.font(path, &loader, &font_storage))
+                    .collect::<Vec<_>>()
+            });
+            let text_entities = if texts.contains(entity) {
+                vec![entity]
+            } else {
+                hierarchy
+                    .children(entity)
+                    .iter()
+                    .cloned()
+                    .filter(|child| texts.contains(*child))
+                    .collect::<Vec<Entity>>()
+            };
+            for text_entity in text_entities {
+                let text = texts
+                    .get_mut(text_entity)
+                    .expect("Unreachable: Entities were filtered by having a `UiText`");
+                if let Some(ref font) = font {
+                    text.font = font.clone();
+                }
+                if let Some(ref fallback_fonts) = fallback_fonts {
+                    text.fallback_fonts = fallback_fonts.clone();
+                }
+                if let Some(font_size) = style.font_size {
+                    text.font_size = font_size;
+                }
+                if let Some(text_color) = style.text_color {
+                    text.color = text_color;
+                }
+            }
+
+            if let Some(image) = style
+                .image
+                .as_ref()
+                .and_then(|path| self.texture(path, &loader, &texture_storage))
+            {
+                images
+                    .insert(entity, image)
+                    .expect("Unreachable: Entity is alive");
+            }
+            if let Some(nine_patch) = style.nine_patch {
+                nine_patches
+                    .insert(entity, nine_patch)
+                    .expect("Unreachable: Entity is alive");
+            }
+            if let Some(tint) = style.tint {
+                tints
+                    .insert(entity, Rgba::from(tint))
+                    .expect("Unreachable: Entity is alive");
+            }
+
+            if let (Some((x_padding, y_padding)), Some(transform)) =
+                (style.padding, transforms.get_mut(entity))
+            {
+                match transform.stretch {
+                    Stretch::NoStretch => {}
+                    Stretch::X { ref mut x_margin } => *x_margin = x_padding,
+                    Stretch::Y { ref mut y_margin } => *y_margin = y_padding,
+                    Stretch::XY {
+                        ref mut x_margin,
+                        ref mut y_margin,
+                        ..
+                    } => {
+                        *x_margin = x_padding;
+                        *y_margin = y_padding;
+                    }
+                }
+            }
+
+            if style.has_button_states() {
+                let hover_image = style
+                    .hover_image
+                    .as_ref()
+                    .and_then(|path| self.texture(path, &loader, &texture_storage));
+                let press_image = style
+                    .press_image
+                    .as_ref()
+                    .and_then(|path| self.texture(path, &loader, &texture_storage));
+                let retrigger = button_state_retrigger(
+                    entity,
+                    hover_image,
+                    style.hover_text_color,
+                    press_image,
+                    style.press_text_color,
+                );
+                retriggers
+                    .insert(entity, retrigger)
+                    .expect("Unreachable: Entity is alive");
+            }
+        }
+    }
+
+    fn setup(&mut self, res: &mut Resources) {
+        use amethyst_core::ecs::prelude::SystemData;
+        Self::SystemData::setup(res);
+        let mut styled = WriteStorage::<UiStyled>::fetch(res);
+        self.styled_events_id = Some(styled.register_reader());
+    }
+}
+
+fn button_state_retrigger(
+    entity: Entity,
+    hover_image: Option<TextureHandle>,
+    hover_text_color: Option<[f32; 4]>,
+    press_image: Option<TextureHandle>,
+    press_text_color: Option<[f32; 4]>,
+) -> UiButtonActionRetrigger {
+    let action = |event_type| UiButtonAction {
+        target: entity,
+        event_type,
+    };
+    let mut retrigger = UiButtonActionRetrigger {
+        on_click_start: Vec::new(),
+        on_click_stop: Vec::new(),
+        on_hover_start: Vec::new(),
+        on_hover_stop: Vec::new(),
+    };
+
+    if let Some(press_image) = press_image {
+        retrigger
+            .on_click_start
+            .push(action(UiButtonActionType::SetTexture(press_image.clone())));
+        retrigger
+            .on_click_stop
+            .push(action(UiButtonActionType::UnsetTexture(press_image)));
+    }
+    if let Some(hover_image) = hover_image {
+        retrigger
+            .on_hover_start
+            .push(action(UiButtonActionType::SetTexture(hover_image.clone())));
+        retrigger
+            .on_hover_stop
+            .push(action(UiButtonActionType::UnsetTexture(hover_image)));
+    }
+    if let Some(color) = press_text_color {
+        retrigger
+            .on_click_start
+            .push(action(UiButtonActionType::SetTextColor(color)));
+        retrigger
+            .on_click_stop
+            .push(action(UiButtonActionType::UnsetTextColor(color)));
+    }
+    if let Some(color) = hover_text_color {
+        retrigger
+            .on_hover_start
+            .push(action(UiButtonActionType::SetTextColor(color)));
+        retrigger
+            .on_hover_stop
+            .push(action(UiButtonActionType::UnsetTextColor(color)));
+    }
+
+    retrigger
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use amethyst_core::{
+        ecs::prelude::{Builder, RunNow, World},
+        Parent,
+    };
+    use rayon::{ThreadPool, ThreadPoolBuilder};
+
+    use super::*;
+    use crate::Anchor;
+
+    fn sheet(tint: [f32; 4]) -> UiStyleSheet {
+        let mut sheet = UiStyleSheet::default();
+        let style = UiStyle {
+            tint: Some(tint),
+            padding: Some((4., 2.)),
+            ..Default::default()
+        };
+        sheet.styles.insert("panel".to_string(), style);
+        sheet
+    }
+
+    fn use_sheet(world: &mut World, sheet: UiStyleSheet) {
+        let handle = world.read_resource::<Loader>().load_from_data(
+            sheet,
+            (),
+            &world.read_resource::<AssetStorage<UiStyleSheet>>(),
+        );
+        let pool = world.read_resource::<Arc<ThreadPool>>().clone();
+        world
+            .write_resource::<AssetStorage<UiStyleSheet>>()
+            .process(Into::into, 0, &pool, None);
+        world.write_resource::<UiTheme>().sheet = Some(handle);
+    }
+
+    fn tint(world: &World, entity: Entity) -> Option<Rgba> {
+        world.read_storage::<Rgba>().get(entity).cloned()
+    }
+
+    #[test]
+    fn styles_are_applied_when_the_sheet_or_the_style_changes() {
+        let mut world = World::new();
+        let mut system = UiStyleSystem::new();
+        System::setup(&mut system, &mut world.res);
+        let reader = world.write_storage::<Parent>().register_reader();
+        world.add_resource(ParentHierarchy::new(reader));
+        let pool = Arc::new(ThreadPoolBuilder::new().build().unwrap());
+        world.add_resource(Loader::new(".", pool.clone()));
+        world.add_resource(pool);
+        let stretch = Stretch::XY {
+            x_margin: 0.,
+            y_margin: 0.,
+            keep_aspect_ratio: false,
+        };
+        let transform = UiTransform::new("panel".into(), Anchor::Middle, 0., 0., 0., 50., 50.)
+            .with_stretch(stretch);
+        let entity = world
+            .create_entity()
+            .with(transform)
+            .with(UiStyled::new("panel"))
+            .build();
+
+        use_sheet(&mut world, sheet([1., 0., 0., 1.]));
+        system.run_now(&world.res);
+        assert_eq!(tint(&world, entity), Some(Rgba(1., 0., 0., 1.)));
+        match world
+            .read_storage::<UiTransform>()
+            .get(entity)
+            .unwrap()
+            .stretch
+        {
+            Stretch::XY {
+                x_margin, y_margin, ..
+            } => assert_eq!((x_margin, y_margin), (4., 2.)),
+            _ => panic!("The stretch was replaced"),
+        }
+
+        // Left alone while neither the sheet nor the style changes.
+        world
+            .write_storage::<Rgba>()
+            .insert(entity, Rgba::white())
+            .unwrap();
+        system.run_now(&world.res);
+        assert_eq!(tint(&world, entity), Some(Rgba::white()));
+
+        world
+            .write_storage::<UiStyled>()
+            .insert(entity, UiStyled::new("panel"))
+            .unwrap();
+        system.run_now(&world.res);
+        assert_eq!(tint(&world, entity), Some(Rgba(1., 0., 0., 1.)));
+
+        use_sheet(&mut world, sheet([0., 0., 1., 1.]));
+        system.run_now(&world.res);
+        assert_eq!(tint(&world, entity), Some(Rgba(0., 0., 1., 1.)));
+    }
+}
@@ -17,6 +17,7 @@ use amethyst_core::{
 use amethyst_renderer::ScreenDimensions;
 
 use super::*;
+use crate::{font::fallback::CachedRuns, shaping::CachedShaping};
 
 /// How lines should behave when they are longer than the maximum line length.
 #[derive(Debug, Clone, Eq, PartialEq, Deserialize, Serialize)]
@@ -40,6 +41,14 @@ pub struct UiText {
     /// The font used for rendering.
     #[serde(skip)]
     pub font: FontHandle,
+    /// Fonts used, in order, for the characters `font` has no glyphs for, like CJK characters,
+    /// symbols or emoji.
+    ///
+    /// Glyphs are rendered as outlines in the text color, and the PNG color bitmaps of emoji
+    /// fonts as images tinted by the `Rgba` of the entity only. While the text is edited, the
+    /// bitmaps aren't drawn.
+    #[serde(skip)]
+    pub fallback_fonts: Vec<FontHandle>,
     /// If true this will be rendered as dots instead of the text.
     pub password: bool,
     /// How the text should handle new lines.
@@ -49,6 +58,17 @@ pub struct UiText {
     /// Cached FontHandle, used to detect changes to the font.
     #[serde(skip)]
     pub(crate) cached_font: FontHandle,
+    /// Cached fallback fonts, used to detect changes to the fallback fonts.
+    #[serde(skip)]
+    pub(crate) cached_fallback_fonts: Vec<FontHandle>,
+    /// Cached font runs of the rendered text, recomputed when it or its fonts change.
+    #[derivative(Debug = "ignore")]
+    #[serde(skip)]
+    pub(crate) cached_runs: CachedRuns,
+    /// Cached shaped glyphs of the text, reshaped when it or its layout change.
+    #[derivative(Debug = "ignore")]
+    #[serde(skip)]
+    pub(crate) cached_shaping: CachedShaping,
     /// Cached glyph positions, used to process mouse highlighting
     #[derivative(Debug = "ignore")]
     #[serde(skip)]
@@ -73,10 +93,14 @@ impl UiText {
             color,
             font_size,
             font: font.clone(),
+            fallback_fonts: Vec::new(),
             password: false,
             line_mode: LineMode::Single,
             align: Anchor::Middle,
             cached_font: font,
+            cached_fallback_fonts: Vec::new(),
+            cached_runs: CachedRuns::default(),
+            cached_shaping: CachedShaping::default(),
             cached_glyphs: Vec::new(),
             brush_id: None,
         }
@@ -0,0 +1,349 @@
+//! Tooltips shown when hovering over ui elements.
+
+use winit::{Event, WindowEvent};
+
+use amethyst_assets::{AssetStorage, Loader};
+use amethyst_core::{
+    ecs::prelude::{
+        Component, DenseVecStorage, Entities, Entity, Join, Read, ReadExpect, ReadStorage,
+        Resources, System, SystemData, WriteStorage,
+    },
+    shrev::{EventChannel, ReaderId},
+    timing::Time,
+};
+use amethyst_renderer::{
+    HiddenPropagate, ScreenDimensions, Texture, TextureData, TextureHandle, TextureMetadata,
+};
+
+use crate::{
+    get_default_font, Anchor, FontAsset, LineMode, UiCanvas, UiEvent, UiEventType, UiStyled,
+    UiText, UiTransform,
+};
+
+const DEFAULT_DELAY: f32 = 0.5;
+const DEFAULT_SIZE: (f32, f32) = (200.0, 32.0);
+const DEFAULT_OFFSET: (f32, f32) = (16.0, 16.0);
+const TOOLTIP_Z: f32 = 1000.0;
+const TEXT_FONT_SIZE: f32 = 16.0;
+const TEXT_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
+const TEXT_BACKGROUND_COLOR: [f32; 4] = [1.0, 1.0, 0.88, 1.0];
+
+/// Name of the style applied to text tooltips, see `UiTheme`.
+pub const TOOLTIP_STYLE: &str = "tooltip";
+
+/// What a `UiTooltip` displays.
+#[derive(Debug, Clone)]
+pub enum UiTooltipContent {
+    /// Displays the text in a box shared by all text tooltips. The box is styled with the
+    /// `"tooltip"` style of the active `UiTheme`, if any.
+    Text(String),
+    /// Displays an existing ui entity, for instance one created from a `UiPrefab`.
+    ///
+    /// The entity must have a `UiTransform` and no `Parent`. It is hidden with a
+    /// `HiddenPropagate` component while the tooltip is not shown.
+    Entity(Entity),
+}
+
+/// Shows a tooltip when the cursor stays over this entity for `delay` seconds.
+///
+/// The entity needs to be `Interactable` to receive hover events. The tooltip is placed next to
+/// the cursor and moved to the other side of it when it would leave the screen.
+#[derive(Debug, Clone)]
+pub struct UiTooltip {
+    /// What the tooltip displays.
+    pub content: UiTooltipContent,
+    /// Time the cursor has to stay over the entity before the tooltip is shown, in seconds.
+    pub delay: f32,
+    /// Size of text tooltips, in ui units. Entity tooltips use the size of their `UiTransform`.
+    pub size: (f32, f32),
+    /// Distance between the cursor and the closest corner of the tooltip, in ui units.
+    pub offset: (f32, f32),
+}
+
+impl UiTooltip {
+    /// Creates a tooltip displaying a text.
+    pub fn text<S: ToString>(text: S) -> Self {
+        UiTooltip::new(UiTooltipContent::Text(text.to_string()))
+    }
+
+    /// Creates a tooltip displaying an existing ui entity.
+    pub fn entity(entity: Entity) -> Self {
+        UiTooltip::new(UiTooltipContent::Entity(entity))
+    }
+
+    fn new(content: UiTooltipContent) -> Self {
+        UiTooltip {
+            content,
+            delay: DEFAULT_DELAY,
+            size: DEFAULT_SIZE,
+            offset: DEFAULT_OFFSET,
+        }
+    }
+
+    /// Sets the hover delay, in seconds.
+    pub fn with_delay(mut self, delay: f32) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// Sets the size of a text tooltip.
+    pub fn with_size(mut self, width: f32, height: f32) -> Self {
+        self.size = (width, height);
+        self
+    }
+
+    /// Sets the distance between the cursor and the tooltip.
+    pub fn with_offset(mut self, x: f32, y: f32) -> Self {
+        self.offset = (x, y);
+        self
+    }
+}
+
+impl Component for UiTooltip {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// Shows and places the tooltips of hovered `UiTooltip` entities.
+///
+/// It's automatically registered with the `UiBundle`.
+#[derive(Default)]
+pub struct UiTooltipSystem {
+    ui_reader: Option<ReaderId<UiEvent>>,
+    window_reader: Option<ReaderId<Event>>,
+    mouse_position: (f32, f32),
+    hovered: Option<(Entity, f32)>,
+    text_entity: Option<Entity>,
+}
+
+impl UiTooltipSystem {
+    /// Creates a new `UiTooltipSystem`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<'a> System<'a> for UiTooltipSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, EventChannel<UiEvent>>,
+        Read<'a, EventChannel<Event>>,
+        Read<'a, Time>,
+        ReadExpect<'a, ScreenDimensions>,
+        Read<'a, UiCanvas>,
+        ReadExpect<'a, Loader>,
+        Read<'a, AssetStorage<FontAsset>>,
+        Read<'a, AssetStorage<Texture>>,
+        ReadStorage<'a, UiTooltip>,
+        WriteStorage<'a, UiTransform>,
+        WriteStorage<'a, UiText>,
+        WriteStorage<'a, TextureHandle>,
+        WriteStorage<'a, UiStyled>,
+        WriteStorage<'a, HiddenPropagate>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            ui_events,
+            window_events,
+            time,
+            screen_dimensions,
+            canvas,
+            loader,
+            font_storage,
+            texture_storage,
+            tooltips,
+            mut transforms,
+            mut texts,
+            mut images,
+            mut styled,
+            mut hidden,
+        ): Self::SystemData,
+    ) {
+        let hidpi = screen_dimensions.hidpi_factor() as f32;
+        for event in window_events.read(
+            self.window_reader
+                .as_mut()
+                .expect("`UiTooltipSystem::setup` was not called before `UiTooltipSystem::run`"),
+        ) {
+            if let Event::WindowEvent {
+                event: WindowEvent::CursorMoved { position, .. },
+                ..
+            } = *event
+            {
+                self.mouse_position = (
+                    position.x as f32 * hidpi,
+                    screen_dimensions.height() - position.y as f32 * hidpi,
+                );
+            }
+        }
+
+        for event in ui_events.read(
+            self.ui_reader
+                .as_mut()
+                .expect("`UiTooltipSystem::setup` was not called before `UiTooltipSystem::run`"),
+        ) {
+            match event.event_type {
+                UiEventType::HoverStart if tooltips.contains(event.target) => {
+                    self.hovered = Some((event.target, 0.0));
+                }
+                UiEventType::HoverStop | UiEventType::ClickStart
+                    if self.hovered.map(|(e, _)| e) == Some(event.target) =>
+                {
+                    self.hovered = None;
+                }
+                _ => {}
+            }
+        }
+
+        if let Some((target, _)) = self.hovered {
+            if !entities.is_alive(target) {
+                self.hovered = None;
+            }
+        }
+        let tooltip = match self.hovered {
+            Some((target, ref mut elapsed)) => {
+                *elapsed += time.delta_real_seconds();
+                tooltips
+                    .get(target)
+                    .filter(|tooltip| *elapsed >= tooltip.delay)
+            }
+            None => None,
+        };
+
+        let to_show = tooltip.map(|tooltip| match tooltip.content {
+            UiTooltipContent::Entity(entity) => entity,
+            UiTooltipContent::Text(ref text) => {
+                let text_entity = *self.text_entity.get_or_insert_with(|| {
+                    let entity = entities.create();
+                    let font = get_default_font(&loader, &font_storage);
+                    let mut ui_text = UiText::new(font, String::new(), TEXT_COLOR, TEXT_FONT_SIZE);
+                    ui_text.line_mode = LineMode::Wrap;
+                    let background = loader.load_from_data(
+                        TextureData::Rgba(TEXT_BACKGROUND_COLOR, TextureMetadata::srgb()),
+                        (),
+                        &texture_storage,
+                    );
+                    let transform = UiTransform::new(
+                        "tooltip".to_string(),
+                        Anchor::BottomLeft,
+                        0.0,
+                        0.0,
+                        TOOLTIP_Z,
+                        0.0,
+                        0.0,
+                    )
+                    .as_transparent();
+                    transforms
+                        .insert(entity, transform)
+                        .expect("Unreachable: Entity was just created");
+                    texts
+                        .insert(entity, ui_text)
+                        .expect("Unreachable: Entity was just created");
+                    images
+                        .insert(entity, background)
+                        .expect("Unreachable: Entity was just created");
+                    styled
+                        .insert(entity, UiStyled::new(TOOLTIP_STYLE))
+                        .expect("Unreachable: Entity was just created");
+                    entity
+                });
+                if let Some(ui_text) = texts.get_mut(text_entity) {
+                    if ui_text.text != *text {
+                        ui_text.text = text.clone();
+                    }
+                }
+                if let Some(transform) = transforms.get_mut(text_entity) {
+                    transform.width = tooltip.size.0;
+                    transform.height = tooltip.size.1;
+                }
+                text_entity
+            }
+        });
+
+        // Hide every tooltip which is not shown.
+        let unused = (&tooltips)
+            .join()
+            .filter_map(|tooltip| match tooltip.content {
+                UiTooltipContent::Entity(entity) => Some(entity),
+                UiTooltipContent::Text(_) => None,
+            })
+            .chain(self.text_entity)
+            .filter(|entity| Some(*entity) != to_show)
+            .collect::<Vec<_>>();
+        for entity in unused {
+            if entities.is_alive(entity) && !hidden.contains(entity) {
+                hidden
+                    .insert(entity, HiddenPropagate)
+                    .expect("Unreachable: Entity is alive");
+            }
+        }
+        if let Some(entity) = to_show {
+            if hidden.contains(entity) {
+                hidden.remove(entity);
+            }
+        }
+
+        if let (Some(tooltip), Some(entity)) = (tooltip, to_show) {
+            if let Some(transform) = transforms.get_mut(entity) {
+                let screen = (screen_dimensions.width(), screen_dimensions.height());
+                let scale = canvas.scale();
+                let (x, y) = tooltip_position(
+                    self.mouse_position,
+                    (transform.width * scale, transform.height * scale),
+                    (tooltip.offset.0 * scale, tooltip.offset.1 * scale),
+                    screen,
+                );
+                transform.set_root_center(x, y, screen, scale);
+            }
+        }
+    }
+
+    fn setup(&mut self, res: &mut Resources) {
+        Self::SystemData::setup(res);
+        self.ui_reader = Some(res.fetch_mut::<EventChannel<UiEvent>>().register_reader());
+        self.window_reader = Some(res.fetch_mut::<EventChannel<Event>>().register_reader());
+    }
+}
+
+/// Computes the center of a tooltip of the given `size` shown for the cursor at `cursor`.
+///
+/// The tooltip is placed below and to the right of the cursor, and flipped to the other side of
+/// the cursor on each axis where it would leave the screen.
+fn tooltip_position(
+    cursor: (f32, f32),
+    size: (f32, f32),
+    offset: (f32, f32),
+    screen: (f32, f32),
+) -> (f32, f32) {
+    let mut left = cursor.0 + offset.0;
+    if left + size.0 > screen.0 {
+        left = cursor.0 - offset.0 - size.0;
+    }
+    // y goes up, so "below" the cursor means a lower y.
+    let mut bottom = cursor.1 - offset.1 - size.1;
+    if bottom < 0.0 {
+        bottom = cursor.1 + offset.1;
+    }
+    let left = left.max(0.0).min((screen.0 - size.0).max(0.0));
+    let bottom = bottom.max(0.0).min((screen.1 - size.1).max(0.0));
+    (left + size.0 / 2.0, bottom + size.1 / 2.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::tooltip_position;
+
+    #[test]
+    fn tooltip_below_right_of_cursor() {
+        let pos = tooltip_position((100.0, 500.0), (200.0, 30.0), (10.0, 10.0), (800.0, 600.0));
+        assert_eq!(pos, (210.0, 475.0));
+    }
+
+    #[test]
+    fn tooltip_flips_at_screen_edges() {
+        let pos = tooltip_position((750.0, 20.0), (200.0, 30.0), (10.0, 10.0), (800.0, 600.0));
+        assert_eq!(pos, (640.0, 45.0));
+    }
+}
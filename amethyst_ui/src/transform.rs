@@ -1,13 +1,16 @@
-use std::marker::PhantomData;
+use std::{cmp::Ordering, marker::PhantomData};
 
-use amethyst_core::ecs::prelude::{
-    Component, DenseVecStorage, Entities, Entity, FlaggedStorage, Join, ReadStorage,
+use amethyst_core::{
+    ecs::prelude::{
+        Component, DenseVecStorage, Entities, Entity, FlaggedStorage, Join, ReadStorage,
+    },
+    ParentHierarchy,
 };
 
 use serde::{Deserialize, Serialize};
 use shred_derive::SystemData;
 
-use super::{Anchor, ScaleMode, Stretch};
+use super::{clip::ClipRect, Anchor, ScaleMode, Stretch};
 
 /// Utility `SystemData` for finding UI entities based on `UiTransform` id
 #[derive(SystemData)]
@@ -64,6 +67,9 @@ pub struct UiTransform {
     pub(crate) pixel_width: f32,
     /// Height in pixels, used for rendering.  Duplicate of `height` if `scale_mode == ScaleMode::Pixel`.
     pub(crate) pixel_height: f32,
+    /// Area outside of which this element is clipped, set by the `UiTransformSystem` from the
+    /// `UiClip` ancestors of the element.
+    pub(crate) clip: Option<ClipRect>,
     /// The scale mode indicates if the position is in pixel or is relative (%) (WIP!) to the parent's size.
     pub scale_mode: ScaleMode,
     /// Indicates if actions on the ui can go through this element.
@@ -100,6 +106,7 @@ impl UiTransform {
             global_z: z,
             pixel_width: width,
             pixel_height: height,
+            clip: None,
             scale_mode: ScaleMode::Pixel,
             opaque: true,
             pd: PhantomData,
@@ -115,7 +122,13 @@ impl UiTransform {
     }
 
     /// Checks if the input position is in the UiTransform rectangle.
+    /// Positions clipped away by a `UiClip` ancestor are never inside.
     pub fn position_inside(&self, x: f32, y: f32) -> bool {
+        if let Some(clip) = self.clip {
+            if x <= clip[0] || y <= clip[1] || x >= clip[2] || y >= clip[3] {
+                return false;
+            }
+        }
         x > self.pixel_x - self.pixel_width / 2.0
             && y > self.pixel_y - self.pixel_height / 2.0
             && x < self.pixel_x + self.pixel_width / 2.0
@@ -155,12 +168,52 @@ impl UiTransform {
     pub fn global_z(&self) -> f32 {
         self.global_z
     }
+
+    /// The rectangle covered by this element in pixels, as `[left, bottom, right, top]`.
+    pub(crate) fn pixel_rect(&self) -> ClipRect {
+        [
+            self.pixel_x - self.pixel_width / 2.0,
+            self.pixel_y - self.pixel_height / 2.0,
+            self.pixel_x + self.pixel_width / 2.0,
+            self.pixel_y + self.pixel_height / 2.0,
+        ]
+    }
+
+    /// Moves an element without `Parent` so that its center ends up at the given position in
+    /// pixels, taking the anchor of the element and the scale of the `UiCanvas` into account.
+    pub(crate) fn set_root_center(&mut self, x: f32, y: f32, screen_size: (f32, f32), scale: f32) {
+        let norm = self.anchor.norm_offset();
+        self.local_x = (x - screen_size.0 * (0.5 + norm.0)) / scale;
+        self.local_y = (y - screen_size.1 * (0.5 + norm.1)) / scale;
+    }
 }
 
 impl Component for UiTransform {
     type Storage = FlaggedStorage<Self, DenseVecStorage<Self>>;
 }
 
+/// Compares the draw order of two ui elements, elements drawn later are on top and receive the
+/// mouse events first.
+///
+/// Elements are ordered by their global z, elements at the same z by their entity id so that
+/// the order is stable across frames.
+pub(crate) fn cmp_draw_order(z1: f32, e1: Entity, z2: f32, e2: Entity) -> Ordering {
+    z1.partial_cmp(&z2)
+        .expect("Unexpected NaN")
+        .then_with(|| e1.id().cmp(&e2.id()))
+}
+
+/// Returns the entity followed by all of its descendants.
+pub(crate) fn with_descendants(entity: Entity, hierarchy: &ParentHierarchy) -> Vec<Entity> {
+    let mut entities = vec![entity];
+    let mut i = 0;
+    while i < entities.len() {
+        entities.extend_from_slice(hierarchy.children(entities[i]));
+        i += 1;
+    }
+    entities
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,429 @@
+//! Show and hide transitions of ui elements.
+
+use amethyst_core::{
+    ecs::prelude::{
+        Component, DenseVecStorage, Entities, Entity, Join, Read, ReadExpect, ReadStorage, System,
+        WriteStorage,
+    },
+    timing::Time,
+    Parent, ParentHierarchy,
+};
+use amethyst_renderer::{HiddenPropagate, Rgba, ScreenDimensions};
+
+use crate::{transform::with_descendants, ScaleMode, UiCanvas, UiTransform};
+
+/// An edge of the screen.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UiEdge {
+    /// The left edge.
+    Left,
+    /// The right edge.
+    Right,
+    /// The bottom edge.
+    Bottom,
+    /// The top edge.
+    Top,
+}
+
+/// An effect played when a ui element is shown or hidden.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UiTransitionKind {
+    /// Fades the element and its children in or out, by changing the alpha of their `Rgba`.
+    Fade,
+    /// Slides the element in from, or out to, an edge of the screen.
+    Slide(UiEdge),
+}
+
+/// The values of a shown element, from which it is faded and slid, while a transition plays.
+#[derive(Clone, Debug)]
+struct TransitionBase {
+    local_x: f32,
+    local_y: f32,
+    /// Offset in pixels moving the element out of the screen for slides.
+    offset: (f32, f32),
+    /// Alpha of the element and its children for fades.
+    alphas: Vec<(Entity, f32)>,
+    /// How faded in the element is, 0 when faded out and 1 when shown.
+    fade: f32,
+    /// How slid in the element is, 0 when out of the screen and 1 when shown.
+    slide: f32,
+}
+
+/// Plays transitions when the entity is shown or hidden with `show` and `hide`.
+///
+/// Hiding the entity once its transition finished is done by adding a `HiddenPropagate`
+/// component, showing it removes the component. Without a transition for a direction the change
+/// is immediate. Reversing a transition that is playing goes back from where it got to, and
+/// an effect of the other direction that was playing is undone at the same pace.
+#[derive(Clone, Debug)]
+pub struct UiTransition {
+    /// Transition played by `show`.
+    pub show: Option<UiTransitionKind>,
+    /// Transition played by `hide`.
+    pub hide: Option<UiTransitionKind>,
+    /// Length of the transitions, in seconds.
+    pub duration: f32,
+    target: Option<bool>,
+    /// Whether the entity is shown or being shown, `None` until the entity was first seen by the
+    /// `UiTransitionSystem`.
+    visible: Option<bool>,
+    base: Option<TransitionBase>,
+}
+
+impl UiTransition {
+    /// Creates a transition without effects, lasting `duration` seconds.
+    pub fn new(duration: f32) -> Self {
+        UiTransition {
+            show: None,
+            hide: None,
+            duration,
+            target: None,
+            visible: None,
+            base: None,
+        }
+    }
+
+    /// Fades the element in and out.
+    pub fn fade(duration: f32) -> Self {
+        UiTransition::new(duration)
+            .with_show(UiTransitionKind::Fade)
+            .with_hide(UiTransitionKind::Fade)
+    }
+
+    /// Slides the element in from the edge and out to the same edge.
+    pub fn slide(edge: UiEdge, duration: f32) -> Self {
+        UiTransition::new(duration)
+            .with_show(UiTransitionKind::Slide(edge))
+            .with_hide(UiTransitionKind::Slide(edge))
+    }
+
+    /// Sets the transition played by `show`.
+    pub fn with_show(mut self, kind: UiTransitionKind) -> Self {
+        self.show = Some(kind);
+        self
+    }
+
+    /// Sets the transition played by `hide`.
+    pub fn with_hide(mut self, kind: UiTransitionKind) -> Self {
+        self.hide = Some(kind);
+        self
+    }
+
+    /// Shows the entity, playing the `show` transition.
+    pub fn show(&mut self) {
+        self.target = Some(true);
+    }
+
+    /// Hides the entity, playing the `hide` transition.
+    pub fn hide(&mut self) {
+        self.target = Some(false);
+    }
+
+    /// Returns whether the entity is shown or being shown.
+    pub fn is_visible(&self) -> bool {
+        self.target.or(self.visible).unwrap_or(true)
+    }
+
+    /// Returns whether a transition is playing.
+    pub fn is_playing(&self) -> bool {
+        self.base.is_some()
+    }
+
+    fn kind(&self) -> Option<UiTransitionKind> {
+        if self.visible.unwrap_or(true) {
+            self.show
+        } else {
+            self.hide
+        }
+    }
+}
+
+impl Component for UiTransition {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// Plays the transitions of `UiTransition` entities.
+///
+/// It's automatically registered with the `UiBundle`.
+#[derive(Default)]
+pub struct UiTransitionSystem;
+
+impl UiTransitionSystem {
+    /// Creates a new `UiTransitionSystem`.
+    pub fn new() -> Self {
+        UiTransitionSystem
+    }
+}
+
+impl<'a> System<'a> for UiTransitionSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, Time>,
+        ReadExpect<'a, ScreenDimensions>,
+        Read<'a, UiCanvas>,
+        ReadExpect<'a, ParentHierarchy>,
+        ReadStorage<'a, Parent>,
+        WriteStorage<'a, UiTransition>,
+        WriteStorage<'a, UiTransform>,
+        WriteStorage<'a, Rgba>,
+        WriteStorage<'a, HiddenPropagate>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            time,
+            screen_dimensions,
+            canvas,
+            hierarchy,
+            parents,
+            mut transitions,
+            mut transforms,
+            mut tints,
+            mut hidden,
+        ): Self::SystemData,
+    ) {
+        let screen = (screen_dimensions.width(), screen_dimensions.height());
+        for (entity, transition) in (&*entities, &mut transitions).join() {
+            if transition.visible.is_none() {
+                transition.visible = Some(!hidden.contains(entity));
+            }
+            if let Some(visible) = transition.target.take() {
+                if Some(visible) != transition.visible {
+                    transition.visible = Some(visible);
+                    if visible && hidden.contains(entity) {
+                        hidden.remove(entity);
+                    }
+                    if let (Some(kind), Some(transform)) =
+                        (transition.kind(), transforms.get(entity))
+                    {
+                        // A transition playing is reversed from the values it got to, otherwise
+                        // the element is at rest, and shown unless it is being shown.
+                        let base = transition.base.get_or_insert_with(|| TransitionBase {
+                            local_x: transform.local_x,
+                            local_y: transform.local_y,
+                            offset: (0.0, 0.0),
+                            alphas: with_descendants(entity, &hierarchy)
+                                .into_iter()
+                                .map(|e| (e, tints.get(e).map_or(1.0, |tint| tint.3)))
+                                .collect(),
+                            fade: if visible && kind == UiTransitionKind::Fade {
+                                0.0
+                            } else {
+                                1.0
+                            },
+                            slide: if visible && kind != UiTransitionKind::Fade {
+                                0.0
+                            } else {
+                                1.0
+                            },
+                        });
+                        // Unless it is sliding, the element is where it rests.
+                        if kind != UiTransitionKind::Fade && base.slide >= 1.0 {
+                            base.offset = slide_offset(kind, transform, screen);
+                        }
+                    }
+                }
+            }
+
+            let visible = transition.visible.unwrap_or(true);
+            let kind = transition.kind();
+            let duration = transition.duration;
+            let base = match transition.base {
+                Some(ref mut base) => base,
+                None => {
+                    if !visible && !hidden.contains(entity) {
+                        hidden
+                            .insert(entity, HiddenPropagate)
+                            .expect("Unreachable: Entity is alive");
+                    }
+                    continue;
+                }
+            };
+
+            // Without a transition for the direction, the one that was playing is undone at once.
+            let step = if duration > 0.0 && kind.is_some() {
+                time.delta_real_seconds() / duration
+            } else {
+                std::f32::INFINITY
+            };
+            // The effect of the transition goes to its end, the other one back to the shown values.
+            let shown = if visible { 1.0 } else { 0.0 };
+            let fades = kind == Some(UiTransitionKind::Fade);
+            let slides = kind.map_or(false, |kind| kind != UiTransitionKind::Fade);
+            let fade_target = if fades { shown } else { 1.0 };
+            let slide_target = if slides { shown } else { 1.0 };
+            let fading = fades || base.fade < 1.0;
+            let sliding = slides || base.slide < 1.0;
+            base.fade = towards(base.fade, fade_target, step);
+            base.slide = towards(base.slide, slide_target, step);
+            let finished = base.fade == fade_target && base.slide == slide_target;
+            // Finished transitions restore the original values, so that the next one starts
+            // from them.
+            let (faded, slid) = if finished {
+                (1.0, 1.0)
+            } else {
+                (smoothstep(base.fade), smoothstep(base.slide))
+            };
+
+            if fading {
+                for &(e, alpha) in &base.alphas {
+                    if let Some(tint) = tints.get_mut(e) {
+                        tint.3 = alpha * faded;
+                    } else if entities.is_alive(e) {
+                        let mut tint = Rgba::WHITE;
+                        tint.3 = alpha * faded;
+                        tints.insert(e, tint).expect("Unreachable: Entity is alive");
+                    }
+                }
+            }
+            if sliding {
+                let scale = local_scale(entity, &parents, &transforms, screen, canvas.scale());
+                if let Some(transform) = transforms.get_mut(entity) {
+                    transform.local_x = base.local_x + base.offset.0 * (1.0 - slid) * scale.0;
+                    transform.local_y = base.local_y + base.offset.1 * (1.0 - slid) * scale.1;
+                }
+            }
+
+            if finished {
+                transition.base = None;
+                if !visible {
+                    hidden
+                        .insert(entity, HiddenPropagate)
+                        .expect("Unreachable: Entity is alive");
+                }
+            }
+        }
+    }
+}
+
+/// Moves `value` by `step` towards `target`, without going past it.
+fn towards(value: f32, target: f32, step: f32) -> f32 {
+    if value < target {
+        (value + step).min(target)
+    } else {
+        (value - step).max(target)
+    }
+}
+
+/// The offset in pixels moving an element from its position to just outside of the screen edge.
+fn slide_offset(kind: UiTransitionKind, transform: &UiTransform, screen: (f32, f32)) -> (f32, f32) {
+    let [left, bottom, right, top] = transform.pixel_rect();
+    match kind {
+        UiTransitionKind::Fade => (0.0, 0.0),
+        UiTransitionKind::Slide(UiEdge::Left) => (-right, 0.0),
+        UiTransitionKind::Slide(UiEdge::Right) => (screen.0 - left, 0.0),
+        UiTransitionKind::Slide(UiEdge::Bottom) => (0.0, -top),
+        UiTransitionKind::Slide(UiEdge::Top) => (0.0, screen.1 - bottom),
+    }
+}
+
+/// Factor converting pixels to the local coordinates of the element.
+fn local_scale(
+    entity: Entity,
+    parents: &ReadStorage<'_, Parent>,
+    transforms: &WriteStorage<'_, UiTransform>,
+    screen: (f32, f32),
+    canvas_scale: f32,
+) -> (f32, f32) {
+    let transform = match transforms.get(entity) {
+        Some(transform) => transform,
+        None => return (1.0, 1.0),
+    };
+    match transform.scale_mode {
+        ScaleMode::Pixel => (1.0 / canvas_scale, 1.0 / canvas_scale),
+        ScaleMode::Percent => {
+            let size = parents
+                .get(entity)
+                .and_then(|parent| transforms.get(parent.entity))
+                .map_or(screen, |parent| (parent.pixel_width, parent.pixel_height));
+            (1.0 / size.0.max(1.0), 1.0 / size.1.max(1.0))
+        }
+    }
+}
+
+fn smoothstep(t: f32) -> f32 {
+    t * t * (3.0 - 2.0 * t)
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::ecs::prelude::{Builder, RunNow, World};
+
+    use super::*;
+    use crate::Anchor;
+
+    fn setup(transition: UiTransition) -> (World, Entity) {
+        let mut world = World::new();
+        System::setup(&mut UiTransitionSystem::new(), &mut world.res);
+        let reader = world.write_storage::<Parent>().register_reader();
+        world.add_resource(ParentHierarchy::new(reader));
+        world.add_resource(ScreenDimensions::new(800, 600, 1.0));
+        let transform = UiTransform::new("panel".into(), Anchor::Middle, 100., 0., 0., 50., 50.);
+        let entity = world
+            .create_entity()
+            .with(transform)
+            .with(transition)
+            .build();
+        (world, entity)
+    }
+
+    fn run(world: &mut World, entity: Entity, seconds: f32) -> (f32, Option<f32>, bool) {
+        world.write_resource::<Time>().set_delta_seconds(seconds);
+        UiTransitionSystem::new().run_now(&world.res);
+        (
+            world
+                .read_storage::<UiTransform>()
+                .get(entity)
+                .unwrap()
+                .local_x,
+            world.read_storage::<Rgba>().get(entity).map(|tint| tint.3),
+            world.read_storage::<HiddenPropagate>().contains(entity),
+        )
+    }
+
+    fn transition<F: FnOnce(&mut UiTransition)>(world: &World, entity: Entity, f: F) {
+        f(world
+            .write_storage::<UiTransition>()
+            .get_mut(entity)
+            .unwrap());
+    }
+
+    #[test]
+    fn transitions_hide_once_finished() {
+        let (mut world, entity) = setup(UiTransition::fade(0.5));
+        run(&mut world, entity, 0.25);
+        transition(&world, entity, UiTransition::hide);
+        assert_eq!(run(&mut world, entity, 0.25), (100., Some(0.5), false));
+        assert_eq!(run(&mut world, entity, 0.25), (100., Some(1.), true));
+
+        transition(&world, entity, UiTransition::show);
+        assert_eq!(run(&mut world, entity, 0.25), (100., Some(0.5), false));
+    }
+
+    #[test]
+    fn reversed_transitions_go_back_from_where_they_got_to() {
+        let transition = UiTransition::new(1.)
+            .with_show(UiTransitionKind::Fade)
+            .with_hide(UiTransitionKind::Slide(UiEdge::Left));
+        let (mut world, entity) = setup(transition);
+        run(&mut world, entity, 0.25);
+        transition(&world, entity, UiTransition::hide);
+        run(&mut world, entity, 0.25);
+        let (hiding, _, _) = run(&mut world, entity, 0.25);
+        assert!(hiding < 100.);
+
+        // The element slides back in, instead of fading in from where it slid to.
+        transition(&world, entity, UiTransition::show);
+        let (showing, alpha, hidden) = run(&mut world, entity, 0.25);
+        assert!(hiding < showing && showing < 100.);
+        assert_eq!((alpha, hidden), (Some(1.), false));
+        assert_eq!(run(&mut world, entity, 0.25), (100., Some(1.), false));
+        assert!(!world
+            .read_storage::<UiTransition>()
+            .get(entity)
+            .unwrap()
+            .is_playing());
+    }
+}
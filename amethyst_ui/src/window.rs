@@ -0,0 +1,240 @@
+//! Stacking of overlapping ui windows.
+
+use std::cmp::Ordering;
+
+use fnv::FnvHashMap as HashMap;
+
+use amethyst_core::{
+    ecs::prelude::{
+        Component, DenseVecStorage, Entities, Entity, Join, Read, ReadExpect, ReadStorage,
+        Resources, System, SystemData, WriteStorage,
+    },
+    shrev::{EventChannel, ReaderId},
+    Parent, ParentHierarchy,
+};
+
+use crate::{transform::with_descendants, UiEvent, UiEventType, UiTransform};
+
+/// Gap in z between the top of a window and the next window of the stack.
+const WINDOW_Z_GAP: f32 = 1.0;
+
+/// Makes an entity with a `UiTransform` a window, stacked with the other windows sharing its
+/// parent.
+///
+/// The `UiWindowSystem` moves the windows in z so that every window, including all of its
+/// children, is drawn above the windows below it in the stack and receives the mouse events
+/// first. Windows are stacked by the `local_z` they had when first seen by the system, windows
+/// with the same z in the order they were brought to the front, newer windows on top.
+#[derive(Clone, Debug)]
+pub struct UiWindow {
+    /// Brings the window to the front when it or one of its children is clicked.
+    pub raise_on_click: bool,
+    raise: bool,
+    stack: u64,
+    base_z: Option<f32>,
+}
+
+impl UiWindow {
+    /// Creates a window that is brought to the front when clicked.
+    pub fn new() -> Self {
+        UiWindow {
+            raise_on_click: true,
+            raise: false,
+            stack: 0,
+            base_z: None,
+        }
+    }
+
+    /// Makes the window keep its place in the stack when clicked.
+    pub fn without_raise_on_click(mut self) -> Self {
+        self.raise_on_click = false;
+        self
+    }
+
+    /// Brings the window to the front of the windows with the same z.
+    pub fn bring_to_front(&mut self) {
+        self.raise = true;
+    }
+}
+
+impl Default for UiWindow {
+    fn default() -> Self {
+        UiWindow::new()
+    }
+}
+
+impl Component for UiWindow {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// Stacks the `UiWindow` entities and brings clicked windows to the front.
+///
+/// It's automatically registered with the `UiBundle`.
+#[derive(Default)]
+pub struct UiWindowSystem {
+    event_reader: Option<ReaderId<UiEvent>>,
+    counter: u64,
+}
+
+impl UiWindowSystem {
+    /// Creates a new `UiWindowSystem`.
+    pub fn new() -> Self {
+        UiWindowSystem::default()
+    }
+}
+
+impl<'a> System<'a> for UiWindowSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, EventChannel<UiEvent>>,
+        ReadExpect<'a, ParentHierarchy>,
+        ReadStorage<'a, Parent>,
+        WriteStorage<'a, UiWindow>,
+        WriteStorage<'a, UiTransform>,
+    );
+
+    #[allow(clippy::float_cmp)] // cmp just used to recognize change
+    fn run(
+        &mut self,
+        (entities, events, hierarchy, parents, mut windows, mut transforms): Self::SystemData,
+    ) {
+        let event_reader = self
+            .event_reader
+            .as_mut()
+            .expect("`UiWindowSystem::setup` was not called before `UiWindowSystem::run`");
+        for event in events.read(event_reader) {
+            if event.event_type != UiEventType::ClickStart {
+                continue;
+            }
+            let mut current = Some(event.target);
+            while let Some(entity) = current {
+                if let Some(window) = windows.get_mut(entity) {
+                    if window.raise_on_click {
+                        window.raise = true;
+                    }
+                    break;
+                }
+                current = parents.get(entity).map(|parent| parent.entity);
+            }
+        }
+
+        let mut stacks = HashMap::<Option<Entity>, Vec<(Entity, f32, u64)>>::default();
+        for (entity, window, transform) in (&*entities, &mut windows, &transforms).join() {
+            if window.base_z.is_none() {
+                window.base_z = Some(transform.local_z);
+                window.raise = true;
+            }
+            if window.raise {
+                window.raise = false;
+                self.counter += 1;
+                window.stack = self.counter;
+            }
+            stacks
+                .entry(parents.get(entity).map(|parent| parent.entity))
+                .or_insert_with(Vec::new)
+                .push((
+                    entity,
+                    window.base_z.expect("Unreachable: `base_z` was set above"),
+                    window.stack,
+                ));
+        }
+
+        for stack in stacks.values_mut() {
+            stack.sort_by(|(_, z1, stack1), (_, z2, stack2)| {
+                z1.partial_cmp(z2)
+                    .unwrap_or(Ordering::Equal)
+                    .then(stack1.cmp(stack2))
+            });
+            let mut top = None;
+            for &(entity, base_z, _) in stack.iter() {
+                let transform = match transforms.get(entity) {
+                    Some(transform) => transform,
+                    None => continue,
+                };
+                // Height of the window's subtree above the window, as of the last layout.
+                let depth = with_descendants(entity, &hierarchy)
+                    .into_iter()
+                    .filter_map(|e| transforms.get(e))
+                    .map(|t| t.global_z - transform.global_z)
+                    .fold(0.0, f32::max);
+                let z = top.map_or(base_z, |top: f32| base_z.max(top + WINDOW_Z_GAP));
+                top = Some(z + depth);
+                let transform = transforms
+                    .get_mut(entity)
+                    .expect("Unreachable: Checked above");
+                if transform.local_z != z {
+                    transform.local_z = z;
+                }
+            }
+        }
+    }
+
+    fn setup(&mut self, res: &mut Resources) {
+        Self::SystemData::setup(res);
+        self.event_reader = Some(res.fetch_mut::<EventChannel<UiEvent>>().register_reader());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::ecs::prelude::{Builder, RunNow, World};
+
+    use super::*;
+    use crate::Anchor;
+
+    fn window(world: &mut World, z: f32, window: UiWindow) -> Entity {
+        let transform = UiTransform::new("window".to_string(), Anchor::Middle, 0., 0., z, 50., 50.);
+        world.create_entity().with(transform).with(window).build()
+    }
+
+    fn z(world: &World, entity: Entity) -> f32 {
+        world
+            .read_storage::<UiTransform>()
+            .get(entity)
+            .unwrap()
+            .local_z
+    }
+
+    #[test]
+    fn clicked_windows_are_brought_to_the_front() {
+        let mut world = World::new();
+        let mut system = UiWindowSystem::new();
+        System::setup(&mut system, &mut world.res);
+        let reader = world.write_storage::<Parent>().register_reader();
+        world.add_resource(ParentHierarchy::new(reader));
+        let first = window(&mut world, 0., UiWindow::new());
+        let second = window(&mut world, 0., UiWindow::new());
+        let pinned = window(&mut world, 0., UiWindow::new().without_raise_on_click());
+        let top = window(&mut world, 10., UiWindow::new());
+        let button = world.create_entity().with(Parent { entity: first }).build();
+
+        // Stacked in the order they were seen, the window above them keeping its z.
+        system.run_now(&world.res);
+        let stack = |world: &World| {
+            [first, second, pinned, top]
+                .iter()
+                .map(|window| z(world, *window))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(stack(&world), vec![0., 1., 2., 10.]);
+
+        let mut click = |world: &mut World, target| {
+            world
+                .write_resource::<EventChannel<UiEvent>>()
+                .single_write(UiEvent::new(UiEventType::ClickStart, target));
+            system.run_now(&world.res);
+        };
+        click(&mut world, button);
+        assert_eq!(stack(&world), vec![2., 0., 1., 10.]);
+        click(&mut world, pinned);
+        assert_eq!(stack(&world), vec![2., 0., 1., 10.]);
+
+        world
+            .write_storage::<UiWindow>()
+            .get_mut(pinned)
+            .unwrap()
+            .bring_to_front();
+        system.run_now(&world.res);
+        assert_eq!(stack(&world), vec![1., 0., 2., 10.]);
+    }
+}
@@ -0,0 +1,277 @@
+//! Ui elements following entities of the world.
+
+use amethyst_core::{
+    ecs::prelude::{
+        Component, DenseVecStorage, Entities, Entity, Join, Read, ReadExpect, ReadStorage, System,
+        WriteStorage,
+    },
+    math::{Point3, Vector2, Vector3},
+    GlobalTransform,
+};
+use amethyst_renderer::{ActiveCamera, Camera, HiddenPropagate, ScreenDimensions};
+
+use crate::{UiCanvas, UiTransform};
+
+/// What a `UiWorldAnchor` does when its target is outside of the screen or behind the camera.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OffScreen {
+    /// Hides the element until the target is back on screen.
+    Hide,
+    /// Keeps the element on the screen edge closest to the target, `margin` pixels away from the
+    /// edge. Useful for quest markers.
+    Clamp {
+        /// Distance between the center of the element and the screen edge, in pixels.
+        margin: f32,
+    },
+}
+
+/// Makes an ui element without `Parent` follow an entity with a `GlobalTransform`, by projecting
+/// the position of the entity through the active camera every frame.
+///
+/// Useful for health bars and names above characters.
+#[derive(Clone, Debug)]
+pub struct UiWorldAnchor {
+    /// The followed entity.
+    pub target: Entity,
+    /// Offset from the target position, in world units. For example to place a health bar above
+    /// the head of the target.
+    pub offset: Vector3<f32>,
+    /// Offset from the projected position, in pixels.
+    pub screen_offset: Vector2<f32>,
+    /// What to do when the target is off screen.
+    pub off_screen: OffScreen,
+    /// An entity shown only while the element is clamped to the screen edge, for example an
+    /// arrow image pointing towards the target.
+    pub arrow: Option<Entity>,
+    direction: Option<Vector2<f32>>,
+    hidden: bool,
+}
+
+impl UiWorldAnchor {
+    /// Creates an anchor following `target`, hiding the element when the target is off screen.
+    pub fn new(target: Entity) -> Self {
+        UiWorldAnchor {
+            target,
+            offset: Vector3::zeros(),
+            screen_offset: Vector2::zeros(),
+            off_screen: OffScreen::Hide,
+            arrow: None,
+            direction: None,
+            hidden: false,
+        }
+    }
+
+    /// Sets the offset from the target position, in world units.
+    pub fn with_offset(mut self, offset: Vector3<f32>) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Sets the offset from the projected position, in pixels.
+    pub fn with_screen_offset(mut self, screen_offset: Vector2<f32>) -> Self {
+        self.screen_offset = screen_offset;
+        self
+    }
+
+    /// Keeps the element on the screen edge when the target is off screen.
+    pub fn clamped(mut self, margin: f32) -> Self {
+        self.off_screen = OffScreen::Clamp { margin };
+        self
+    }
+
+    /// Shows `arrow` only while the element is clamped to the screen edge.
+    pub fn with_arrow(mut self, arrow: Entity) -> Self {
+        self.arrow = Some(arrow);
+        self
+    }
+
+    /// The normalized direction from the screen center towards the target while the element is
+    /// clamped to the screen edge, `None` while the target is on screen.
+    pub fn off_screen_direction(&self) -> Option<Vector2<f32>> {
+        self.direction
+    }
+}
+
+impl Component for UiWorldAnchor {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// Moves the `UiWorldAnchor` entities to the screen positions of their targets.
+///
+/// Uses the `ActiveCamera`, or the first camera if there is no active camera.
+/// It's automatically registered with the `UiBundle`.
+#[derive(Default)]
+pub struct UiWorldAnchorSystem;
+
+impl UiWorldAnchorSystem {
+    /// Creates a new `UiWorldAnchorSystem`.
+    pub fn new() -> Self {
+        UiWorldAnchorSystem
+    }
+}
+
+impl<'a> System<'a> for UiWorldAnchorSystem {
+    type SystemData = (
+        Entities<'a>,
+        Option<Read<'a, ActiveCamera>>,
+        ReadStorage<'a, Camera>,
+        ReadStorage<'a, GlobalTransform>,
+        ReadExpect<'a, ScreenDimensions>,
+        Read<'a, UiCanvas>,
+        WriteStorage<'a, UiWorldAnchor>,
+        WriteStorage<'a, UiTransform>,
+        WriteStorage<'a, HiddenPropagate>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            active_camera,
+            cameras,
+            globals,
+            screen_dimensions,
+            canvas,
+            mut anchors,
+            mut transforms,
+            mut hidden,
+        ): Self::SystemData,
+    ) {
+        let camera = active_camera
+            .and_then(|active| active.entity)
+            .and_then(|entity| Some((cameras.get(entity)?, globals.get(entity)?)))
+            .or_else(|| (&cameras, &globals).join().next());
+        let screen = (screen_dimensions.width(), screen_dimensions.height());
+
+        for (entity, anchor, transform) in (&*entities, &mut anchors, &mut transforms).join() {
+            let projected = match (camera, globals.get(anchor.target)) {
+                (Some((camera, camera_transform)), Some(target)) => {
+                    let position = target.0.transform_point(&Point3::from(anchor.offset));
+                    project(camera, camera_transform, position, screen)
+                }
+                _ => None,
+            };
+
+            let placement = projected.and_then(|(x, y, in_front)| {
+                let x = x + anchor.screen_offset.x;
+                let y = y + anchor.screen_offset.y;
+                let on_screen = in_front && x >= 0. && x <= screen.0 && y >= 0. && y <= screen.1;
+                match anchor.off_screen {
+                    _ if on_screen => Some(((x, y), None)),
+                    OffScreen::Hide => None,
+                    OffScreen::Clamp { margin } => {
+                        Some(clamp_to_edge((x, y), in_front, screen, margin))
+                    }
+                }
+            });
+
+            let hide = placement.is_none();
+            anchor.direction = placement.and_then(|(_, direction)| direction);
+            if let Some(((x, y), _)) = placement {
+                transform.set_root_center(x, y, screen, canvas.scale());
+            }
+            if hide != anchor.hidden {
+                anchor.hidden = hide;
+                if hide {
+                    hidden
+                        .insert(entity, HiddenPropagate)
+                        .expect("Unreachable: Entity is alive");
+                } else {
+                    hidden.remove(entity);
+                }
+            }
+            if let Some(arrow) = anchor.arrow.filter(|arrow| entities.is_alive(*arrow)) {
+                let show_arrow = anchor.direction.is_some();
+                if show_arrow == hidden.contains(arrow) {
+                    if show_arrow {
+                        hidden.remove(arrow);
+                    } else {
+                        hidden
+                            .insert(arrow, HiddenPropagate)
+                            .expect("Unreachable: Entity is alive");
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Projects a world position to pixels from the bottom left of the screen, with whether the
+/// position is in front of the camera. Positions behind the camera are mirrored, so that their
+/// direction from the screen center stays meaningful.
+fn project(
+    camera: &Camera,
+    camera_transform: &GlobalTransform,
+    position: Point3<f32>,
+    screen: (f32, f32),
+) -> Option<(f32, f32, bool)> {
+    let view = camera_transform.0.try_inverse()?;
+    let clip = camera.proj * view * position.to_homogeneous();
+    // Clip `w` is zero for positions in the plane of the camera.
+    let w = if clip.w.abs() > std::f32::EPSILON {
+        clip.w.abs()
+    } else {
+        std::f32::EPSILON
+    };
+    let sign = if clip.w < 0. { -1. } else { 1. };
+    Some((
+        (sign * clip.x / w + 1.) * screen.0 / 2.,
+        (sign * clip.y / w + 1.) * screen.1 / 2.,
+        clip.w > 0.,
+    ))
+}
+
+/// Moves an off screen position to the screen edge, along the line from the screen center.
+/// Returns the position and the direction of the target.
+fn clamp_to_edge(
+    (x, y): (f32, f32),
+    in_front: bool,
+    screen: (f32, f32),
+    margin: f32,
+) -> ((f32, f32), Option<Vector2<f32>>) {
+    let center = (screen.0 / 2., screen.1 / 2.);
+    let mut direction = Vector2::new(x - center.0, y - center.1);
+    if !in_front {
+        // Behind the camera the mirrored position points the right way, but may lie on screen.
+        direction = -direction;
+    }
+    if direction.norm() <= std::f32::EPSILON {
+        direction = Vector2::new(0., -1.);
+    }
+    let half = ((center.0 - margin).max(0.), (center.1 - margin).max(0.));
+    let scale_x = if direction.x.abs() > std::f32::EPSILON {
+        half.0 / direction.x.abs()
+    } else {
+        std::f32::MAX
+    };
+    let scale_y = if direction.y.abs() > std::f32::EPSILON {
+        half.1 / direction.y.abs()
+    } else {
+        std::f32::MAX
+    };
+    let scale = scale_x.min(scale_y);
+    (
+        (
+            center.0 + direction.x * scale,
+            center.1 + direction.y * scale,
+        ),
+        Some(direction.normalize()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clamps_to_screen_edge() {
+        let ((x, y), direction) = clamp_to_edge((1000., 300.), true, (800., 600.), 20.);
+        assert!((x - 780.).abs() < 1e-4);
+        assert!((y - 300.).abs() < 1e-4);
+        assert_eq!(direction, Some(Vector2::new(1., 0.)));
+
+        let ((x, y), _) = clamp_to_edge((600., 300.), false, (800., 600.), 20.);
+        assert!((x - 20.).abs() < 1e-4);
+        assert!((y - 300.).abs() < 1e-4);
+    }
+}
@@ -1,11 +1,17 @@
 //! Util Resources
 
+use std::fmt::Write as FmtWrite;
+
 use amethyst_core::{
-    ecs::prelude::{DispatcherBuilder, Read, System, Write},
+    ecs::prelude::{Entities, Join, Read, Resources, System, SystemData, Write},
+    shrev::{EventChannel, ReaderId},
     timing::{duration_to_nanos, Time},
-    SystemBundle,
+    BundleBuilder, DispatcherProfile, SystemBundle, SystemTiming,
 };
 use amethyst_error::Error;
+use amethyst_renderer::{
+    ElementState, Event, KeyboardInput, RenderStats, VirtualKeyCode, WindowEvent,
+};
 
 use crate::circular_buffer::CircularBuffer;
 
@@ -79,8 +85,247 @@ impl<'a> System<'a> for FPSCounterSystem {
 pub struct FPSCounterBundle;
 
 impl<'a, 'b> SystemBundle<'a, 'b> for FPSCounterBundle {
-    fn build(self, builder: &mut DispatcherBuilder<'a, 'b>) -> Result<(), Error> {
+    fn build(self, builder: &mut BundleBuilder<'a, 'b>) -> Result<(), Error> {
         builder.add(FPSCounterSystem, "fps_counter_system", &[]);
         Ok(())
     }
 }
+
+/// The performance HUD: the frame times and their graph, the slowest systems of the
+/// `DispatcherProfile`, the draw calls of the `RenderStats` and the number of entities, updated
+/// by the `PerformanceHudSystem`.
+///
+/// The HUD is shown as the text of a widget, bound to the resource:
+///
+/// ```rust,ignore
+/// world
+///     .create_entity()
+///     .with(hud_transform)
+///     .with(UiText::new(font, String::new(), [1.0; 4], 14.0))
+///     .with(UiResourceBinding::<PerformanceHud>::text(|hud| hud.text()))
+///     .build();
+/// ```
+///
+/// with a `UiResourceBindingSystem::<PerformanceHud>` added to the game data. The text is empty
+/// while the HUD is hidden; the `toggle_key` shows and hides it. The systems are only listed
+/// when profiling is enabled with `GameDataBuilder::with_profiling`.
+pub struct PerformanceHud {
+    /// The key showing and hiding the HUD.
+    pub toggle_key: VirtualKeyCode,
+    /// The number of systems shown, slowest first.
+    pub systems: usize,
+    /// The number of rows of the frame time graph.
+    pub graph_rows: usize,
+    visible: bool,
+    frame_times: CircularBuffer<f32>,
+    timings: Vec<SystemTiming>,
+    draw_calls: Option<usize>,
+    entities: usize,
+}
+
+impl Default for PerformanceHud {
+    fn default() -> Self {
+        PerformanceHud::new(60)
+    }
+}
+
+impl PerformanceHud {
+    /// Creates a hidden HUD graphing the times of the last `samplesize` frames, toggled with F3.
+    pub fn new(samplesize: usize) -> Self {
+        PerformanceHud {
+            toggle_key: VirtualKeyCode::F3,
+            systems: 5,
+            graph_rows: 4,
+            visible: false,
+            frame_times: CircularBuffer::new(samplesize),
+            timings: Vec::new(),
+            draw_calls: None,
+            entities: 0,
+        }
+    }
+
+    /// Whether the HUD is shown.
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    /// Shows or hides the HUD.
+    pub fn set_visible(&mut self, visible: bool) {
+        self.visible = visible;
+    }
+
+    /// Shows the HUD if it is hidden, hides it otherwise.
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+    }
+
+    /// The times of the recent frames in milliseconds, oldest first.
+    pub fn frame_times(&self) -> impl Iterator<Item = f32> + '_ {
+        self.frame_times.queue().iter().cloned()
+    }
+
+    /// The timings of the slowest systems, slowest first.
+    pub fn timings(&self) -> &[SystemTiming] {
+        &self.timings
+    }
+
+    /// The draw calls of the last frame, if the frame was drawn by the `RenderSystem`.
+    pub fn draw_calls(&self) -> Option<usize> {
+        self.draw_calls
+    }
+
+    /// The number of living entities.
+    pub fn entities(&self) -> usize {
+        self.entities
+    }
+
+    /// The text of the HUD, empty while it is hidden.
+    pub fn text(&self) -> String {
+        if !self.visible {
+            return String::new();
+        }
+        let times = self.frame_times.queue();
+        let last = times.back().cloned().unwrap_or(0.0);
+        let max = times.iter().cloned().fold(0.0, f32::max);
+        let average = if times.is_empty() {
+            0.0
+        } else {
+            times.iter().sum::<f32>() / times.len() as f32
+        };
+
+        let mut text = String::new();
+        let fps = if average > 0.0 { 1000.0 / average } else { 0.0 };
+        // Writing to a `String` can't fail.
+        let _ = writeln!(text, "{:.0} fps, {:.2} ms (max {:.2} ms)", fps, last, max);
+        text.push_str(&self.graph(max));
+        let _ = write!(text, "entities: {}", self.entities);
+        if let Some(draw_calls) = self.draw_calls {
+            let _ = write!(text, ", draw calls: {}", draw_calls);
+        }
+        for timing in &self.timings {
+            let millis = timing.average.as_secs() as f64 * 1000.0
+                + f64::from(timing.average.subsec_nanos()) / 1.0e6;
+            let _ = write!(text, "\n{}: {:.2} ms", timing.name, millis);
+        }
+        text
+    }
+
+    /// The graph of the frame times, a frame per column scaled to the slowest, a row per line.
+    fn graph(&self, max: f32) -> String {
+        let mut graph = String::new();
+        if max <= 0.0 {
+            return graph;
+        }
+        for row in (0..self.graph_rows).rev() {
+            let threshold = row as f32 / self.graph_rows as f32;
+            for time in self.frame_times.queue() {
+                graph.push(if time / max > threshold { '#' } else { '.' });
+            }
+            graph.push('\n');
+        }
+        graph
+    }
+}
+
+/// Updates the `PerformanceHud`, and shows or hides it when its key is pressed.
+///
+/// The HUD is updated while it is hidden too, so that its graph is full when it is shown.
+#[derive(Default)]
+pub struct PerformanceHudSystem {
+    reader: Option<ReaderId<Event>>,
+}
+
+impl PerformanceHudSystem {
+    /// Creates a new `PerformanceHudSystem`.
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl<'a> System<'a> for PerformanceHudSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, Time>,
+        Read<'a, EventChannel<Event>>,
+        Option<Read<'a, DispatcherProfile>>,
+        Option<Read<'a, RenderStats>>,
+        Write<'a, PerformanceHud>,
+    );
+
+    fn run(&mut self, (entities, time, events, profile, stats, mut hud): Self::SystemData) {
+        for event in events.read(self.reader.as_mut().expect(
+            "`PerformanceHudSystem::setup` was not called before `PerformanceHudSystem::run`",
+        )) {
+            if let Event::WindowEvent {
+                event:
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(key),
+                                ..
+                            },
+                        ..
+                    },
+                ..
+            } = *event
+            {
+                if key == hud.toggle_key {
+                    hud.toggle();
+                }
+            }
+        }
+
+        hud.frame_times
+            .push(duration_to_nanos(time.delta_real_time()) as f32 / 1.0e6);
+        let systems = hud.systems;
+        hud.timings = profile.map_or_else(Vec::new, |profile| profile.slowest(systems));
+        hud.draw_calls = stats.map(|stats| stats.draw_calls);
+        hud.entities = (&*entities).join().count();
+    }
+
+    fn setup(&mut self, res: &mut Resources) {
+        Self::SystemData::setup(res);
+        self.reader = Some(res.fetch_mut::<EventChannel<Event>>().register_reader());
+    }
+}
+
+/// Adds the `PerformanceHudSystem`, with the `PerformanceHud`.
+#[derive(Default)]
+pub struct PerformanceHudBundle;
+
+impl PerformanceHudBundle {
+    /// Creates the bundle.
+    pub fn new() -> Self {
+        PerformanceHudBundle
+    }
+}
+
+impl<'a, 'b> SystemBundle<'a, 'b> for PerformanceHudBundle {
+    fn build(self, builder: &mut BundleBuilder<'a, 'b>) -> Result<(), Error> {
+        builder.add(PerformanceHudSystem::new(), "performance_hud_system", &[]);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hud_is_shown_when_toggled() {
+        let mut hud = PerformanceHud::new(4);
+        hud.graph_rows = 2;
+        for &time in &[10.0, 20.0, 10.0, 20.0] {
+            hud.frame_times.push(time);
+        }
+        hud.entities = 3;
+        assert_eq!(hud.text(), "");
+
+        hud.toggle();
+        assert_eq!(
+            hud.text(),
+            "67 fps, 20.00 ms (max 20.00 ms)\n.#.#\n####\nentities: 3"
+        );
+    }
+}
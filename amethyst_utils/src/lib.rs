@@ -12,5 +12,6 @@ pub mod ortho_camera;
 pub mod removal;
 pub mod render;
 pub mod scene;
+pub mod spatial;
 pub mod tag;
 pub mod time_destroy;
@@ -0,0 +1,548 @@
+//! A spatial index of the entities, to find the ones in a region, along a ray or nearest to a
+//! point without going through all of them.
+
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+};
+
+use hibitset::{BitSetLike, BitSetOr};
+
+use amethyst_core::{
+    changes::ComponentChanges,
+    ecs::prelude::{
+        Component, Entities, Entity, FlaggedStorage, Join, ReadStorage, Resources, System,
+        SystemData, VecStorage, Write,
+    },
+    math::{Vector3, Vector4},
+    BundleBuilder, BundleRequirement, GlobalTransform, SystemBundle,
+};
+use amethyst_error::Error;
+use serde::{Deserialize, Serialize};
+
+/// The default size of the cells of the `SpatialIndex`.
+pub const DEFAULT_CELL_SIZE: f32 = 10.0;
+
+/// A sphere around an entity, which the `SpatialIndex` uses to find it.
+///
+/// The sphere moves, rotates and scales with the `GlobalTransform` of the entity, with the radius
+/// scaled by the largest scale of the transform.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BoundingSphere {
+    /// The center, relative to the entity.
+    pub center: Vector3<f32>,
+    /// The radius.
+    pub radius: f32,
+}
+
+impl BoundingSphere {
+    /// Creates a sphere of the given radius centered on the entity.
+    pub fn new(radius: f32) -> Self {
+        BoundingSphere {
+            center: Vector3::zeros(),
+            radius,
+        }
+    }
+
+    /// Moves the center of the sphere, relative to the entity.
+    pub fn with_center(mut self, center: Vector3<f32>) -> Self {
+        self.center = center;
+        self
+    }
+}
+
+impl Component for BoundingSphere {
+    type Storage = FlaggedStorage<Self, VecStorage<Self>>;
+}
+
+type Cell = (i32, i32, i32);
+
+struct Entry {
+    entity: Entity,
+    center: Vector3<f32>,
+    radius: f32,
+    min: Cell,
+    max: Cell,
+}
+
+/// A uniform grid of cells holding the entities whose `BoundingSphere` overlaps them, kept up to
+/// date by the `SpatialIndexSystem`.
+///
+/// The cells should be about the size of the queries, or of the largest entities: with smaller
+/// cells entities are in many cells, with larger ones each cell holds many entities.
+///
+/// ```rust,ignore
+/// fn run(&mut self, (index, mut enemies): (Read<'_, SpatialIndex>, WriteStorage<'_, Enemy>)) {
+///     for entity in index.query_sphere(self.player_position, 20.0) {
+///         if let Some(enemy) = enemies.get_mut(entity) {
+///             enemy.alert();
+///         }
+///     }
+/// }
+/// ```
+pub struct SpatialIndex {
+    cell_size: f32,
+    cells: HashMap<Cell, Vec<Entity>>,
+    entries: HashMap<u32, Entry>,
+    // The box around all the entities ever indexed, to end the rays leaving it.
+    bounds: Option<(Vector3<f32>, Vector3<f32>)>,
+}
+
+impl Default for SpatialIndex {
+    fn default() -> Self {
+        SpatialIndex::new(DEFAULT_CELL_SIZE)
+    }
+}
+
+impl SpatialIndex {
+    /// Creates an empty index with cells of the given size.
+    pub fn new(cell_size: f32) -> Self {
+        assert!(cell_size > 0.0, "The cells must have a positive size");
+        SpatialIndex {
+            cell_size,
+            cells: HashMap::new(),
+            entries: HashMap::new(),
+            bounds: None,
+        }
+    }
+
+    /// The size of the cells.
+    pub fn cell_size(&self) -> f32 {
+        self.cell_size
+    }
+
+    /// The number of entities in the index.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Checks whether the index holds no entities.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The center and radius of the sphere of an entity in the world, as last indexed.
+    pub fn sphere(&self, entity: Entity) -> Option<(Vector3<f32>, f32)> {
+        self.entries
+            .get(&entity.id())
+            .filter(|entry| entry.entity == entity)
+            .map(|entry| (entry.center, entry.radius))
+    }
+
+    /// Adds an entity, or moves it if it is already indexed.
+    pub fn insert(&mut self, entity: Entity, center: Vector3<f32>, radius: f32) {
+        self.remove(entity.id());
+        let min = self.cell(&center.add_scalar(-radius));
+        let max = self.cell(&center.add_scalar(radius));
+        for cell in cells(min, max) {
+            self.cells.entry(cell).or_insert_with(Vec::new).push(entity);
+        }
+        let extent = Vector3::repeat(radius);
+        self.bounds = Some(match self.bounds {
+            Some((low, high)) => (low.inf(&(center - extent)), high.sup(&(center + extent))),
+            None => (center - extent, center + extent),
+        });
+        self.entries.insert(
+            entity.id(),
+            Entry {
+                entity,
+                center,
+                radius,
+                min,
+                max,
+            },
+        );
+    }
+
+    /// Removes the entity with the given id, if it is indexed.
+    pub fn remove(&mut self, id: u32) {
+        if let Some(entry) = self.entries.remove(&id) {
+            for cell in cells(entry.min, entry.max) {
+                if let Some(entities) = self.cells.get_mut(&cell) {
+                    entities.retain(|entity| entity.id() != id);
+                    if entities.is_empty() {
+                        self.cells.remove(&cell);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Removes all the entities.
+    pub fn clear(&mut self) {
+        self.cells.clear();
+        self.entries.clear();
+        self.bounds = None;
+    }
+
+    /// The entities whose sphere overlaps the given sphere.
+    pub fn query_sphere(&self, center: Vector3<f32>, radius: f32) -> Vec<Entity> {
+        self.candidates(center.add_scalar(-radius), center.add_scalar(radius))
+            .filter(|entry| (entry.center - center).norm() <= entry.radius + radius)
+            .map(|entry| entry.entity)
+            .collect()
+    }
+
+    /// The entities whose sphere overlaps the box between `min` and `max`.
+    pub fn query_aabb(&self, min: Vector3<f32>, max: Vector3<f32>) -> Vec<Entity> {
+        self.candidates(min, max)
+            .filter(|entry| {
+                let closest = entry.center.sup(&min).inf(&max);
+                (closest - entry.center).norm() <= entry.radius
+            })
+            .map(|entry| entry.entity)
+            .collect()
+    }
+
+    /// The entity whose sphere is the closest to `point`, within `max_distance`, with the
+    /// distance to its sphere. The distance is zero if the point is inside the sphere.
+    pub fn nearest(&self, point: Vector3<f32>, max_distance: f32) -> Option<(Entity, f32)> {
+        self.candidates(
+            point.add_scalar(-max_distance),
+            point.add_scalar(max_distance),
+        )
+        .map(|entry| {
+            let distance = ((entry.center - point).norm() - entry.radius).max(0.0);
+            (entry.entity, distance)
+        })
+        .filter(|&(_, distance)| distance <= max_distance)
+        .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal))
+    }
+
+    /// The first entity whose sphere the ray from `origin` along `direction` hits within
+    /// `max_distance`, with the distance to the hit. The distance is zero if the origin is
+    /// inside the sphere.
+    pub fn raycast(
+        &self,
+        origin: Vector3<f32>,
+        direction: Vector3<f32>,
+        max_distance: f32,
+    ) -> Option<(Entity, f32)> {
+        let length = direction.norm();
+        if length == 0.0 || !length.is_finite() || origin.iter().any(|x| !x.is_finite()) {
+            return None;
+        }
+        let direction = direction / length;
+        let (low, high) = self.bounds?;
+        let (enter, exit) = crossing(&origin, &direction, &low, &high)?;
+        // The walk starts where the ray enters the indexed entities, so that a far origin
+        // neither walks through the empty cells nor loses the precision of the cell boundaries.
+        let origin = origin + direction * enter;
+        let max_distance = max_distance.min(exit) - enter;
+        if max_distance.is_nan() || max_distance < 0.0 {
+            return None;
+        }
+
+        // Walks through the cells along the ray, until a hit closer than the next cell is found.
+        let mut cell = self.cell(&origin);
+        let mut step = [0; 3];
+        let mut next = [std::f32::INFINITY; 3];
+        let mut delta = [std::f32::INFINITY; 3];
+        let current = [cell.0, cell.1, cell.2];
+        for axis in 0..3 {
+            if direction[axis] > 0.0 {
+                step[axis] = 1;
+                next[axis] =
+                    ((current[axis] + 1) as f32 * self.cell_size - origin[axis]) / direction[axis];
+            } else if direction[axis] < 0.0 {
+                step[axis] = -1;
+                next[axis] =
+                    (current[axis] as f32 * self.cell_size - origin[axis]) / direction[axis];
+            }
+            if step[axis] != 0 {
+                delta[axis] = self.cell_size / direction[axis].abs();
+            }
+        }
+
+        let mut best: Option<(Entity, f32)> = None;
+        loop {
+            for entity in self.cells.get(&cell).into_iter().flatten() {
+                let entry = &self.entries[&entity.id()];
+                if let Some(distance) = hit_sphere(&origin, &direction, entry) {
+                    if distance <= max_distance
+                        && best.map_or(true, |(_, closest)| distance < closest)
+                    {
+                        best = Some((*entity, distance));
+                    }
+                }
+            }
+            let axis = (0..3)
+                .min_by(|&a, &b| next[a].partial_cmp(&next[b]).unwrap_or(Ordering::Equal))
+                .expect("there are three axes");
+            let entered = next[axis];
+            if entered.is_nan()
+                || entered > max_distance
+                || best.map_or(false, |(_, closest)| closest <= entered)
+            {
+                return best.map(|(entity, distance)| (entity, enter + distance));
+            }
+            match axis {
+                0 => cell.0 += step[0],
+                1 => cell.1 += step[1],
+                _ => cell.2 += step[2],
+            }
+            next[axis] += delta[axis];
+        }
+    }
+
+    fn cell(&self, point: &Vector3<f32>) -> Cell {
+        (
+            (point.x / self.cell_size).floor() as i32,
+            (point.y / self.cell_size).floor() as i32,
+            (point.z / self.cell_size).floor() as i32,
+        )
+    }
+
+    // The entries in the cells overlapping the box, each only once.
+    fn candidates<'a>(
+        &'a self,
+        min: Vector3<f32>,
+        max: Vector3<f32>,
+    ) -> impl Iterator<Item = &'a Entry> + 'a {
+        let mut seen = HashSet::new();
+        cells(self.cell(&min), self.cell(&max))
+            .filter_map(move |cell| self.cells.get(&cell))
+            .flatten()
+            .filter(move |entity| seen.insert(entity.id()))
+            .map(move |entity| &self.entries[&entity.id()])
+    }
+}
+
+fn cells(min: Cell, max: Cell) -> impl Iterator<Item = Cell> {
+    (min.0..=max.0).flat_map(move |x| {
+        (min.1..=max.1).flat_map(move |y| (min.2..=max.2).map(move |z| (x, y, z)))
+    })
+}
+
+// The distance along the ray to where it hits the sphere.
+fn hit_sphere(origin: &Vector3<f32>, direction: &Vector3<f32>, entry: &Entry) -> Option<f32> {
+    let offset = origin - entry.center;
+    let c = offset.norm_squared() - entry.radius * entry.radius;
+    if c <= 0.0 {
+        return Some(0.0);
+    }
+    let b = offset.dot(direction);
+    let discriminant = b * b - c;
+    if b > 0.0 || discriminant < 0.0 {
+        return None;
+    }
+    Some(-b - discriminant.sqrt())
+}
+
+// The distances along the ray to where it enters and leaves the box, if it goes through it.
+fn crossing(
+    origin: &Vector3<f32>,
+    direction: &Vector3<f32>,
+    low: &Vector3<f32>,
+    high: &Vector3<f32>,
+) -> Option<f32> {
+    let mut enter = 0.0f32;
+    let mut exit = std::f32::INFINITY;
+    for axis in 0..3 {
+        if direction[axis] == 0.0 {
+            if origin[axis] < low[axis] || origin[axis] > high[axis] {
+                return None;
+            }
+        } else {
+            let a = (low[axis] - origin[axis]) / direction[axis];
+            let b = (high[axis] - origin[axis]) / direction[axis];
+            enter = enter.max(a.min(b));
+            exit = exit.min(a.max(b));
+        }
+    }
+    if enter <= exit {
+        Some((enter, exit))
+    } else {
+        None
+    }
+}
+
+/// Indexes the entities with a `GlobalTransform` and a `BoundingSphere` in the `SpatialIndex`,
+/// updating the ones whose transform or sphere changed.
+///
+/// Since `GlobalTransform`s are updated by the `TransformSystem`, this system should run after
+/// it, which the `SpatialIndexBundle` takes care of.
+pub struct SpatialIndexSystem {
+    cell_size: f32,
+    transforms: Option<ComponentChanges<GlobalTransform>>,
+    spheres: Option<ComponentChanges<BoundingSphere>>,
+}
+
+impl SpatialIndexSystem {
+    /// Creates the system, indexing in cells of the given size unless the `SpatialIndex` was
+    /// already added.
+    pub fn new(cell_size: f32) -> Self {
+        SpatialIndexSystem {
+            cell_size,
+            transforms: None,
+            spheres: None,
+        }
+    }
+}
+
+impl Default for SpatialIndexSystem {
+    fn default() -> Self {
+        SpatialIndexSystem::new(DEFAULT_CELL_SIZE)
+    }
+}
+
+impl<'a> System<'a> for SpatialIndexSystem {
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, GlobalTransform>,
+        ReadStorage<'a, BoundingSphere>,
+        Write<'a, SpatialIndex>,
+    );
+
+    fn run(&mut self, (entities, globals, spheres, mut index): Self::SystemData) {
+        let transform_changes = self.transforms.as_mut().expect("setup was not called");
+        let sphere_changes = self.spheres.as_mut().expect("setup was not called");
+        transform_changes.update(&globals);
+        sphere_changes.update(&spheres);
+
+        for id in transform_changes
+            .removed()
+            .iter()
+            .chain(sphere_changes.removed().iter())
+        {
+            index.remove(id);
+        }
+        let changed = BitSetOr(transform_changes.changed(), sphere_changes.changed());
+        for (entity, global, sphere, _) in (&entities, &globals, &spheres, changed).join() {
+            let matrix = &global.0;
+            let center = sphere.center;
+            let center = (matrix * Vector4::new(center.x, center.y, center.z, 1.0)).xyz();
+            let scale = (0..3)
+                .map(|column| {
+                    Vector3::new(
+                        matrix[(0, column)],
+                        matrix[(1, column)],
+                        matrix[(2, column)],
+                    )
+                    .norm()
+                })
+                .fold(0.0, f32::max);
+            index.insert(entity, center, sphere.radius * scale);
+        }
+    }
+
+    fn setup(&mut self, res: &mut Resources) {
+        let cell_size = self.cell_size;
+        res.entry::<SpatialIndex>()
+            .or_insert_with(|| SpatialIndex::new(cell_size));
+        Self::SystemData::setup(res);
+        self.transforms = Some(ComponentChanges::new(res));
+        self.spheres = Some(ComponentChanges::new(res));
+    }
+}
+
+/// Adds the `SpatialIndexSystem` with the name "spatial_index_system", after the
+/// `TransformSystem`.
+///
+/// ## Errors
+///
+/// Fails if the `TransformBundle` wasn't added before it.
+#[derive(Debug)]
+pub struct SpatialIndexBundle {
+    cell_size: f32,
+}
+
+impl SpatialIndexBundle {
+    /// Creates the bundle, indexing in cells of the given size.
+    pub fn new(cell_size: f32) -> Self {
+        SpatialIndexBundle { cell_size }
+    }
+}
+
+impl Default for SpatialIndexBundle {
+    fn default() -> Self {
+        SpatialIndexBundle::new(DEFAULT_CELL_SIZE)
+    }
+}
+
+impl<'a, 'b> SystemBundle<'a, 'b> for SpatialIndexBundle {
+    fn build(self, builder: &mut BundleBuilder<'a, 'b>) -> Result<(), Error> {
+        builder.add(
+            SpatialIndexSystem::new(self.cell_size),
+            "spatial_index_system",
+            &["transform_system"],
+        );
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        "SpatialIndexBundle"
+    }
+
+    fn requirements(&self) -> Vec<BundleRequirement> {
+        vec![BundleRequirement::new(
+            "transform_system",
+            "TransformBundle",
+        )]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use amethyst_core::{
+        ecs::prelude::{Builder, RunNow, World},
+        math::{Matrix4, Translation3},
+    };
+
+    #[test]
+    fn queries_follow_the_transforms() {
+        let mut world = World::new();
+        let mut system = SpatialIndexSystem::new(2.0);
+        System::setup(&mut system, &mut world.res);
+        let near = world
+            .create_entity()
+            .with(GlobalTransform::default())
+            .with(BoundingSphere::new(1.0))
+            .build();
+        let far = world
+            .create_entity()
+            .with(GlobalTransform(
+                Translation3::new(10.0, 0.0, 0.0).to_homogeneous(),
+            ))
+            .with(BoundingSphere::new(1.0))
+            .build();
+        system.run_now(&world.res);
+        {
+            let index = world.read_resource::<SpatialIndex>();
+            assert_eq!(
+                index.query_sphere(Vector3::new(1.5, 0.0, 0.0), 1.0),
+                vec![near]
+            );
+            assert_eq!(
+                index.nearest(Vector3::new(7.0, 0.0, 0.0), 5.0),
+                Some((far, 2.0))
+            );
+            let hit = index.raycast(Vector3::new(-5.0, 0.0, 0.0), Vector3::x(), 100.0);
+            assert_eq!(hit, Some((near, 4.0)));
+            let hit = index.raycast(Vector3::new(-1.0e9, 0.0, 0.0), Vector3::x(), 2.0e9);
+            assert_eq!(hit.map(|(entity, _)| entity), Some(near));
+            let nan = Vector3::new(std::f32::NAN, 0.0, 0.0);
+            assert_eq!(index.raycast(nan, Vector3::x(), 100.0), None);
+            assert_eq!(index.nearest(nan, 5.0), None);
+            assert_eq!(
+                index.raycast(Vector3::new(5.0, 0.0, 0.0), Vector3::x(), 3.0),
+                None
+            );
+        }
+
+        world
+            .write_storage::<GlobalTransform>()
+            .get_mut(far)
+            .unwrap()
+            .0 = Matrix4::new_translation(&Vector3::new(1.0, 3.0, 0.0));
+        world.delete_entity(near).unwrap();
+        system.run_now(&world.res);
+        let index = world.read_resource::<SpatialIndex>();
+        assert_eq!(index.len(), 1);
+        assert_eq!(
+            index.query_aabb(Vector3::new(0.0, 1.5, -1.0), Vector3::new(2.0, 2.5, 1.0)),
+            vec![far]
+        );
+    }
+}
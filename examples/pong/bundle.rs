@@ -1,12 +1,15 @@
 use crate::systems::{BounceSystem, MoveBallsSystem, PaddleSystem, WinnerSystem};
-use amethyst::{core::bundle::SystemBundle, ecs::prelude::DispatcherBuilder, error::Error};
+use amethyst::{
+    core::bundle::{BundleBuilder, SystemBundle},
+    error::Error,
+};
 
 /// A bundle is a convenient way to initialise related resources, components and systems in a
 /// world. This bundle prepares the world for a game of pong.
 pub struct PongBundle;
 
 impl<'a, 'b> SystemBundle<'a, 'b> for PongBundle {
-    fn build(self, builder: &mut DispatcherBuilder<'a, 'b>) -> Result<(), Error> {
+    fn build(self, builder: &mut BundleBuilder<'a, 'b>) -> Result<(), Error> {
         builder.add(PaddleSystem, "paddle_system", &["input_system"]);
         builder.add(MoveBallsSystem, "ball_system", &[]);
         builder.add(
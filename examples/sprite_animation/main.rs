@@ -7,7 +7,7 @@ use amethyst::{
         get_animation_set, AnimationBundle, AnimationCommand, AnimationControlSet, AnimationSet,
         AnimationSetPrefab, EndControl,
     },
-    assets::{PrefabData, PrefabLoader, PrefabLoaderSystem, ProgressCounter, RonFormat},
+    assets::{PrefabData, PrefabLoaderSystem},
     config::Config,
     core::transform::{Transform, TransformBundle},
     derive::PrefabData,
@@ -19,7 +19,8 @@ use amethyst::{
         SpriteRender, SpriteScenePrefab, Stage,
     },
     utils::application_root_dir,
-    Application, GameData, GameDataBuilder, SimpleState, SimpleTrans, StateData, Trans,
+    Application, GameData, GameDataBuilder, LoadedAssets, LoadingState, SimpleState, SimpleTrans,
+    StateData, StatePayload, Trans,
 };
 use serde::{Deserialize, Serialize};
 
@@ -39,26 +40,17 @@ struct MyPrefabData {
 }
 
 /// The main state
-#[derive(Default)]
-struct Example {
-    /// A progress tracker to check that assets are loaded
-    pub progress_counter: Option<ProgressCounter>,
-}
+struct Example;
 
 impl SimpleState for Example {
-    fn on_start(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+    fn on_start_with(&mut self, data: StateData<'_, GameData<'_, '_>>, payload: StatePayload) {
         let StateData { world, .. } = data;
-        // Crates new progress counter
-        self.progress_counter = Some(Default::default());
-        // Starts asset loading
-        let prefab_handle = world.exec(|loader: PrefabLoader<'_, MyPrefabData>| {
-            loader.load(
-                "prefab/sprite_animation.ron",
-                RonFormat,
-                (),
-                self.progress_counter.as_mut().unwrap(),
-            )
-        });
+        let assets = payload
+            .downcast::<LoadedAssets>()
+            .expect("Expected the assets of the loading state");
+        let prefab_handle = assets
+            .prefab::<MyPrefabData>("prefab/sprite_animation.ron")
+            .unwrap();
         // Creates new entities with components from MyPrefabData
         world.create_entity().with(prefab_handle).build();
         // Creates a new camera
@@ -66,37 +58,37 @@ impl SimpleState for Example {
     }
 
     fn update(&mut self, data: &mut StateData<'_, GameData<'_, '_>>) -> SimpleTrans {
-        // Checks if we are still loading data
-        if let Some(ref progress_counter) = self.progress_counter {
-            // Checks progress
-            if progress_counter.is_complete() {
-                let StateData { world, .. } = data;
-                // Execute a pass similar to a system
-                world.exec(
-                    |(entities, animation_sets, mut control_sets): (
-                        Entities,
-                        ReadStorage<AnimationSet<AnimationId, SpriteRender>>,
-                        WriteStorage<AnimationControlSet<AnimationId, SpriteRender>>,
-                    )| {
-                        // For each entity that has AnimationSet
-                        for (entity, animation_set) in (&entities, &animation_sets).join() {
-                            // Creates a new AnimationControlSet for the entity
-                            let control_set = get_animation_set(&mut control_sets, entity).unwrap();
-                            // Adds the `Fly` animation to AnimationControlSet and loops infinitely
-                            control_set.add_animation(
-                                AnimationId::Fly,
-                                &animation_set.get(&AnimationId::Fly).unwrap(),
-                                EndControl::Loop(None),
-                                1.0,
-                                AnimationCommand::Start,
-                            );
-                        }
-                    },
-                );
-                // All data loaded
-                self.progress_counter = None;
-            }
-        }
+        let StateData { world, .. } = data;
+        // Execute a pass similar to a system
+        world.exec(
+            |(entities, animation_sets, mut control_sets): (
+                Entities,
+                ReadStorage<AnimationSet<AnimationId, SpriteRender>>,
+                WriteStorage<AnimationControlSet<AnimationId, SpriteRender>>,
+            )| {
+                // For each entity that has an AnimationSet but isn't animated yet
+                let new = (&entities, &animation_sets, !&control_sets)
+                    .join()
+                    .filter_map(|(entity, animation_set, _)| {
+                        animation_set
+                            .get(&AnimationId::Fly)
+                            .map(|animation| (entity, animation.clone()))
+                    })
+                    .collect::<Vec<_>>();
+                for (entity, animation) in new {
+                    // Creates a new AnimationControlSet for the entity
+                    let control_set = get_animation_set(&mut control_sets, entity).unwrap();
+                    // Adds the `Fly` animation to AnimationControlSet and loops infinitely
+                    control_set.add_animation(
+                        AnimationId::Fly,
+                        &animation,
+                        EndControl::Loop(None),
+                        1.0,
+                        AnimationCommand::Start,
+                    );
+                }
+            },
+        );
         Trans::None
     }
 }
@@ -142,7 +134,9 @@ fn main() -> amethyst::Result<()> {
         ))?
         .with_bundle(RenderBundle::new(pipe, Some(display_config)).with_sprite_sheet_processor())?;
 
-    let mut game = Application::new(assets_directory, Example::default(), game_data)?;
+    let loading =
+        LoadingState::new(Example).with_prefab::<MyPrefabData>("prefab/sprite_animation.ron");
+    let mut game = Application::new(assets_directory, loading, game_data)?;
     game.run();
 
     Ok(())
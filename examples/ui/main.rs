@@ -29,6 +29,10 @@ struct Example {
 }
 
 impl SimpleState for Example {
+    fn name(&self) -> &'static str {
+        "example"
+    }
+
     fn on_start(&mut self, data: StateData<'_, GameData<'_, '_>>) {
         let StateData { world, .. } = data;
         // Initialise the scene with an object, a light and a camera.
@@ -66,6 +70,10 @@ impl SimpleState for Example {
                 info!("Input Event detected: {:?}.", input);
                 Trans::None
             }
+            StateEvent::Transition(transition) => {
+                info!("State transition: {:?}.", transition);
+                Trans::None
+            }
         }
     }
 
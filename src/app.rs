@@ -1,11 +1,11 @@
 //! The core engine framework.
 
-use std::{env, marker::PhantomData, path::Path, sync::Arc, time::Duration};
+use std::{env, hash::Hash, marker::PhantomData, path::Path, time::Duration};
 
 use crate::shred::Resource;
 use derivative::Derivative;
-use log::{debug, info, log_enabled, trace, Level};
-use rayon::ThreadPoolBuilder;
+use log::{debug, info, log_enabled, trace, warn, Level};
+use serde::{de::DeserializeOwned, Serialize};
 use winit::Event;
 
 #[cfg(feature = "profiler")]
@@ -14,23 +14,31 @@ use thread_profiler::{profile_scope, register_thread_with_profiler, write_profil
 use crate::{
     assets::{Loader, Source},
     callback_queue::CallbackQueue,
+    command_buffer::CommandBuffer,
     core::{
+        deterministic::FrameHasher,
         frame_limiter::{FrameLimiter, FrameRateLimitConfig, FrameRateLimitStrategy},
         shrev::{EventChannel, ReaderId},
         timing::{Stopwatch, Time},
-        ArcThreadPool, EventReader, Named,
+        ArcThreadPool, EventBus, EventReader, GameRng, Named,
     },
+    crash::CrashReporter,
     ecs::{
         common::Errors,
         prelude::{Component, Read, World, Write},
     },
     error::Error,
     game_data::DataInit,
-    state::{State, StateData, StateMachine, TransEvent},
+    replay,
+    state::{State, StateData, StateMachine, TransEvent, TransQueue},
     state_event::{StateEvent, StateEventReader},
+    thread_pool::ThreadPoolConfig,
     ui::UiEvent,
 };
 
+#[cfg(feature = "hot_code")]
+use crate::hot_code::GameLibrary;
+
 /// `CoreApplication` is the application implementation for the game engine. This is fully generic
 /// over the state type and event type.
 ///
@@ -61,9 +69,35 @@ where
     trans_reader_id: ReaderId<TransEvent<T, E>>,
     states: StateMachine<'a, T, E>,
     ignore_window_close: bool,
+    deterministic: bool,
+    initialized: bool,
+    #[derivative(Debug = "ignore")]
+    hooks: Hooks<E>,
     data: T,
 }
 
+type FrameHook = Box<dyn FnMut(&mut World)>;
+
+type EventHook<E> = Box<dyn FnMut(&mut World, &E)>;
+
+// The callbacks added with `ApplicationBuilder::with_pre_frame`, `with_post_frame` and
+// `with_event_hook`.
+struct Hooks<E> {
+    pre_frame: Vec<FrameHook>,
+    post_frame: Vec<FrameHook>,
+    event: Vec<EventHook<E>>,
+}
+
+impl<E> Default for Hooks<E> {
+    fn default() -> Self {
+        Hooks {
+            pre_frame: Vec::new(),
+            post_frame: Vec::new(),
+            event: Vec::new(),
+        }
+    }
+}
+
 /// An Application is the root object of the game engine. It binds the OS
 /// event loop, state machines, timers and other core components in a central place.
 ///
@@ -220,24 +254,82 @@ where
     where
         for<'b> R: EventReader<'b, Event = E>,
     {
-        self.initialize();
-        self.world.write_resource::<Stopwatch>().start();
-        while self.states.is_running() {
-            self.advance_frame();
+        while self.step() {}
+    }
 
-            self.world.write_resource::<FrameLimiter>().wait();
-            {
-                let elapsed = self.world.read_resource::<Stopwatch>().elapsed();
-                let mut time = self.world.write_resource::<Time>();
-                time.increment_frame_number();
+    /// Runs a single frame of the game loop, starting the states on the first call, and returns
+    /// whether the game is still running.
+    ///
+    /// This drives the engine from an external loop, like the one of an editor or a test,
+    /// instead of `run`. The frame ends by waiting for the `FrameLimiter`, like with `run`, so
+    /// the external loop may want to use `FrameRateLimitStrategy::Unlimited`.
+    ///
+    /// ~~~no_run
+    /// use amethyst::prelude::*;
+    ///
+    /// struct NullState;
+    /// impl EmptyState for NullState {}
+    ///
+    /// let mut game = Application::new("assets/", NullState, ()).expect("Failed to initialize");
+    /// while game.step() {
+    ///     // Do the work of the external loop between the frames.
+    /// }
+    /// ~~~
+    pub fn step(&mut self) -> bool
+    where
+        for<'b> R: EventReader<'b, Event = E>,
+    {
+        if !self.initialized {
+            self.initialized = true;
+            self.initialize();
+            self.world.write_resource::<Stopwatch>().start();
+        }
+        if !self.states.is_running() {
+            return false;
+        }
+
+        for hook in &mut self.hooks.pre_frame {
+            hook(&mut self.world);
+        }
+        self.advance_frame();
+        for hook in &mut self.hooks.post_frame {
+            hook(&mut self.world);
+        }
+
+        self.world.write_resource::<FrameLimiter>().wait();
+        {
+            let elapsed = self.world.read_resource::<Stopwatch>().elapsed();
+            let mut time = self.world.write_resource::<Time>();
+            time.increment_frame_number();
+            if self.deterministic {
+                let fixed = time.fixed_time();
+                time.set_delta_time(fixed);
+            } else {
                 time.set_delta_time(elapsed);
             }
+        }
+        {
             let mut stopwatch = self.world.write_resource::<Stopwatch>();
             stopwatch.stop();
             stopwatch.restart();
         }
 
-        self.shutdown();
+        if self.states.is_running() {
+            true
+        } else {
+            self.shutdown();
+            false
+        }
+    }
+
+    /// The world of the application, to inspect it between the frames run with `step`.
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+
+    /// The world of the application, to change it between the frames run with `step`.
+    pub fn world_mut(&mut self) -> &mut World {
+        &mut self.world
     }
 
     /// Sets up the application.
@@ -334,6 +426,9 @@ where
                 let world = &mut self.world;
                 let states = &mut self.states;
                 for e in self.events.drain(..) {
+                    for hook in &mut self.hooks.event {
+                        hook(world, &e);
+                    }
                     states.handle_event(StateData::new(world, &mut self.data), e);
                 }
             }
@@ -348,6 +443,11 @@ where
             while { self.world.write_resource::<Time>().step_fixed_update() } {
                 self.states
                     .fixed_update(StateData::new(&mut self.world, &mut self.data));
+                CommandBuffer::apply(&mut self.world);
+                self.states
+                    .apply_queued(StateData::new(&mut self.world, &mut self.data));
+                // The next step sees the entities created and deleted by this one.
+                self.world.maintain();
             }
             {
                 self.world.write_resource::<Time>().finish_fixed_update();
@@ -357,6 +457,9 @@ where
             profile_scope!("update");
             self.states
                 .update(StateData::new(&mut self.world, &mut self.data));
+            CommandBuffer::apply(&mut self.world);
+            self.states
+                .apply_queued(StateData::new(&mut self.world, &mut self.data));
         }
 
         #[cfg(feature = "profiler")]
@@ -399,6 +502,8 @@ pub struct ApplicationBuilder<S, T, E, R> {
     /// Used by bundles to access the world directly
     pub world: World,
     ignore_window_close: bool,
+    deterministic: bool,
+    hooks: Hooks<E>,
     phantom: PhantomData<(T, E, R)>,
 }
 
@@ -497,31 +602,27 @@ where
 
         let mut world = World::new();
 
-        let thread_pool_builder = ThreadPoolBuilder::new();
-        #[cfg(feature = "profiler")]
-        let thread_pool_builder = thread_pool_builder.start_handler(|_index| {
-            register_thread_with_profiler();
-        });
-        let pool: ArcThreadPool;
+        let mut pool_config = ThreadPoolConfig::default();
         if let Some(thread_count) = thread_count {
             debug!("Running Amethyst with fixed thread pool: {}", thread_count);
-            pool = thread_pool_builder
-                .num_threads(thread_count)
-                .build()
-                .map(Arc::new)?;
-        } else {
-            pool = thread_pool_builder.build().map(Arc::new)?;
+            pool_config = pool_config.with_threads(thread_count);
         }
-        world.add_resource(Loader::new(path.as_ref().to_owned(), pool.clone()));
+        let pool = pool_config.build()?;
+        let io_pool = ThreadPoolConfig::io().build()?;
+        world.add_resource(Loader::new(path.as_ref().to_owned(), io_pool));
         world.add_resource(pool);
         world.add_resource(EventChannel::<Event>::with_capacity(2000));
         world.add_resource(EventChannel::<UiEvent>::with_capacity(40));
         world.add_resource(EventChannel::<TransEvent<T, StateEvent>>::with_capacity(2));
+        world.add_resource(TransQueue::<T, StateEvent>::default());
         world.add_resource(Errors::default());
         world.add_resource(FrameLimiter::default());
         world.add_resource(Stopwatch::default());
         world.add_resource(Time::default());
         world.add_resource(CallbackQueue::default());
+        world.add_resource(CommandBuffer::default());
+        world.add_resource(GameRng::default());
+        world.add_resource(EventBus::new());
 
         world.register::<Named>();
 
@@ -529,6 +630,8 @@ where
             initial_state,
             world,
             ignore_window_close: false,
+            deterministic: false,
+            hooks: Hooks::default(),
             phantom: PhantomData,
         })
     }
@@ -817,6 +920,254 @@ where
         self
     }
 
+    /// Adds a callback run at the start of every frame, before the events are handled and the
+    /// states are updated.
+    ///
+    /// # Parameters
+    ///
+    /// `hook`: The callback, given the world.
+    ///
+    /// # Returns
+    ///
+    /// This function returns the ApplicationBuilder after modifying it.
+    pub fn with_pre_frame<F>(mut self, hook: F) -> Self
+    where
+        F: FnMut(&mut World) + 'static,
+    {
+        self.hooks.pre_frame.push(Box::new(hook));
+        self
+    }
+
+    /// Adds a callback run at the end of every frame, after the world is maintained and before
+    /// waiting for the frame limiter.
+    ///
+    /// # Parameters
+    ///
+    /// `hook`: The callback, given the world.
+    ///
+    /// # Returns
+    ///
+    /// This function returns the ApplicationBuilder after modifying it.
+    pub fn with_post_frame<F>(mut self, hook: F) -> Self
+    where
+        F: FnMut(&mut World) + 'static,
+    {
+        self.hooks.post_frame.push(Box::new(hook));
+        self
+    }
+
+    /// Adds a callback run for every event, before it is handled by the states.
+    ///
+    /// # Parameters
+    ///
+    /// `hook`: The callback, given the world and the event.
+    ///
+    /// # Returns
+    ///
+    /// This function returns the ApplicationBuilder after modifying it.
+    pub fn with_event_hook<F>(mut self, hook: F) -> Self
+    where
+        F: FnMut(&mut World, &E) + 'static,
+    {
+        self.hooks.event.push(Box::new(hook));
+        self
+    }
+
+    /// Installs the panic hook of a `CrashReporter`, writing a crash report when the game panics.
+    /// The state stack and the summary of the world given to the reporter are updated at the end
+    /// of every frame.
+    ///
+    /// # Parameters
+    ///
+    /// `reporter`: The reporter, whose logger is chained to the logger to report the recent
+    /// records.
+    ///
+    /// # Returns
+    ///
+    /// This function returns the ApplicationBuilder after modifying it.
+    pub fn with_crash_reporter(self, reporter: CrashReporter) -> Self {
+        reporter.install();
+        self.with_post_frame(move |world| reporter.update(world))
+    }
+
+    /// Loads the game logic of a `GameLibrary`, updated at the start of every frame and reloaded
+    /// when the library is rebuilt. The world is kept across the reloads.
+    ///
+    /// # Parameters
+    ///
+    /// `library`: The library, exporting its logic with the `game_library!` macro.
+    ///
+    /// # Returns
+    ///
+    /// This function returns the ApplicationBuilder after modifying it.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the library can't be loaded. The failures of the reloads are logged, and the
+    /// previous logic kept.
+    #[cfg(feature = "hot_code")]
+    pub fn with_game_library(mut self, mut library: GameLibrary) -> Result<Self, Error> {
+        library.load(&mut self.world)?;
+        Ok(self.with_pre_frame(move |world| {
+            library.reload_if_changed(world);
+            library.update(world);
+        }))
+    }
+
+    /// Sets up the thread pool the systems are dispatched on.
+    ///
+    /// The `AMETHYST_NUM_THREADS` environment variable sets the number of threads of the default
+    /// pool. This must be called before `build`, since the dispatcher is created with the pool.
+    ///
+    /// # Parameters
+    ///
+    /// `config`: The configuration of the pool.
+    ///
+    /// # Returns
+    ///
+    /// This function returns the ApplicationBuilder after modifying it.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the pool can't be created.
+    ///
+    /// In deterministic mode, the pool keeps a single thread, whichever of `with_thread_pool` and
+    /// `with_deterministic` is called first.
+    pub fn with_thread_pool(mut self, mut config: ThreadPoolConfig) -> Result<Self, Error> {
+        if self.deterministic {
+            if config.threads != Some(1) {
+                warn!("The thread pool of a deterministic game runs a single thread");
+            }
+            config = config.with_threads(1);
+        }
+        let pool = config.build()?;
+        self.world.add_resource::<ArcThreadPool>(pool);
+        Ok(self)
+    }
+
+    /// Sets up the thread pool the assets are loaded on, separate from the pool of the systems
+    /// so that loading doesn't slow down the game. Defaults to `ThreadPoolConfig::io`.
+    ///
+    /// # Parameters
+    ///
+    /// `config`: The configuration of the pool.
+    ///
+    /// # Returns
+    ///
+    /// This function returns the ApplicationBuilder after modifying it.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the pool can't be created.
+    pub fn with_io_pool(mut self, config: ThreadPoolConfig) -> Result<Self, Error> {
+        let pool = config.build()?;
+        self.world.write_resource::<Loader>().set_pool(pool);
+        Ok(self)
+    }
+
+    /// Runs the game deterministically, so that the same inputs give the same frames, as needed
+    /// for lockstep networking and replays. See the [`deterministic`] module for the requirements
+    /// on gameplay systems.
+    ///
+    /// The systems run one at a time on a single thread, the ones sharing resources in the order
+    /// they were added, every frame advances the `Time` by one fixed step, and the `GameRng`
+    /// resource is seeded with `seed`. This must be called before `build`, since the dispatcher
+    /// is created with the thread pool.
+    ///
+    /// # Parameters
+    ///
+    /// `seed`: The seed of the `GameRng`.
+    ///
+    /// # Returns
+    ///
+    /// This function returns the ApplicationBuilder after modifying it.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the single threaded pool can't be created.
+    ///
+    /// [`deterministic`]: ../amethyst_core/deterministic/index.html
+    pub fn with_deterministic(mut self, seed: u64) -> Result<Self, Error> {
+        let pool = ThreadPoolConfig::default().with_threads(1).build()?;
+        self.world.add_resource::<ArcThreadPool>(pool);
+        self.world.add_resource(GameRng::new(seed));
+        self.deterministic = true;
+        Ok(self)
+    }
+
+    /// Records the session to a file, to be replayed with `with_replay`. The game runs
+    /// deterministically with `seed`, as with `with_deterministic`, and every frame the state of
+    /// the `InputHandler<AX, AC>`, the `InputEvent<AC>`s and the hash of the world by `hasher` are
+    /// written to the recording.
+    ///
+    /// The `ReplayStatus` resource has the number of ticks recorded.
+    ///
+    /// # Parameters
+    ///
+    /// - `path`: The file of the recording, replaced if it exists.
+    /// - `seed`: The seed of the `GameRng`.
+    /// - `hasher`: The hasher of the world, with the components and resources of the simulation.
+    ///
+    /// # Returns
+    ///
+    /// This function returns the ApplicationBuilder after modifying it.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the single threaded pool can't be created. Failing to write the recording is
+    /// logged, and stops the recording.
+    pub fn with_recording<AX, AC, P>(
+        self,
+        path: P,
+        seed: u64,
+        hasher: FrameHasher,
+    ) -> Result<Self, Error>
+    where
+        AX: Hash + Eq + Clone + Send + Sync + 'static,
+        AC: Hash + Eq + Clone + Send + Sync + Serialize + 'static,
+        P: AsRef<Path>,
+    {
+        let mut builder = self.with_deterministic(seed)?;
+        let hook = replay::recorder::<AX, AC>(&mut builder.world, path.as_ref(), seed, hasher);
+        Ok(builder.with_post_frame(hook))
+    }
+
+    /// Replays a recording made with `with_recording`, with the seed and the fixed step of the
+    /// session. Before every frame the recorded input is given to the `InputHandler<AX, AC>`,
+    /// which ignores the input of the window, and after it the hash of the world by `hasher` is
+    /// compared to the recorded one. The game quits at the end of the recording.
+    ///
+    /// The replay is headless when no window is created, as without the `RenderBundle`. The
+    /// ticks where the hashes differ are in the `ReplayStatus` resource, and the first one is
+    /// logged, as where the game stopped being deterministic.
+    ///
+    /// # Parameters
+    ///
+    /// - `path`: The file of the recording.
+    /// - `hasher`: The hasher of the world, hashing what the hasher of the recording did.
+    ///
+    /// # Returns
+    ///
+    /// This function returns the ApplicationBuilder after modifying it.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the recording can't be read, or if the single threaded pool can't be created.
+    pub fn with_replay<AX, AC, P>(self, path: P, hasher: FrameHasher) -> Result<Self, Error>
+    where
+        AX: Hash + Eq + Clone + Send + Sync + 'static,
+        AC: Hash + Eq + Clone + Send + Sync + DeserializeOwned + 'static,
+        E: Send + Sync + 'static,
+        P: AsRef<Path>,
+    {
+        let (header, ticks) = replay::read_recording_file::<AC>(path.as_ref())?;
+        let mut builder = self
+            .with_deterministic(header.seed)?
+            .with_fixed_step_length(replay::fixed_step(&header));
+        let (before, after) = replay::replayer::<AX, AC, T, E>(&mut builder.world, ticks, hasher);
+        Ok(builder.with_pre_frame(before).with_post_frame(after))
+    }
+
     /// Build an `Application` object using the `ApplicationBuilder` as configured.
     ///
     /// # Returns
@@ -854,6 +1205,7 @@ where
 
         let mut reader = X::default();
         reader.setup(&mut self.world.res);
+        init.validate()?;
         let data = init.build(&mut self.world);
         let event_reader_id = self
             .world
@@ -869,9 +1221,126 @@ where
             reader,
             events: Vec::new(),
             ignore_window_close: self.ignore_window_close,
+            deterministic: self.deterministic,
+            initialized: false,
+            hooks: self.hooks,
             data,
             event_reader_id,
             trans_reader_id,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ecs::prelude::{
+            Component, Entities, Join, LazyUpdate, NullStorage, Read, ReadStorage, System,
+        },
+        prelude::*,
+        renderer::WindowEvent,
+    };
+    use winit::WindowId;
+
+    #[derive(Default)]
+    struct Spawned;
+
+    impl Component for Spawned {
+        type Storage = NullStorage<Self>;
+    }
+
+    // Records how many entities the previous steps spawned, then spawns one.
+    struct Spawner;
+
+    impl<'a> System<'a> for Spawner {
+        type SystemData = (
+            Entities<'a>,
+            ReadStorage<'a, Spawned>,
+            Read<'a, LazyUpdate>,
+            Write<'a, Vec<usize>>,
+        );
+
+        fn run(&mut self, (entities, spawned, lazy, mut counts): Self::SystemData) {
+            counts.push(spawned.join().count());
+            lazy.create_entity(&entities).with(Spawned).build();
+        }
+    }
+
+    struct Idle;
+
+    impl SimpleState for Idle {}
+
+    // Quits once the window gains the focus.
+    struct QuitOnFocus;
+
+    impl SimpleState for QuitOnFocus {
+        fn handle_event(
+            &mut self,
+            _data: StateData<'_, GameData<'_, '_>>,
+            event: StateEvent,
+        ) -> SimpleTrans {
+            match event {
+                StateEvent::Window(Event::WindowEvent {
+                    event: WindowEvent::Focused(true),
+                    ..
+                }) => Trans::Quit,
+                _ => Trans::None,
+            }
+        }
+    }
+
+    #[test]
+    fn the_world_is_maintained_between_fixed_steps() {
+        let game_data = GameDataBuilder::default().with_fixed(Spawner, "spawner", &[]);
+        let mut app = Application::build("assets/", Idle)
+            .unwrap()
+            .build(game_data)
+            .unwrap();
+        {
+            let mut time = app.world_mut().write_resource::<Time>();
+            time.set_fixed_seconds(0.25);
+            time.set_delta_seconds(0.75);
+        }
+        assert!(app.step());
+        assert_eq!(*app.world().read_resource::<Vec<usize>>(), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn steps_run_the_hooks_until_the_states_quit() {
+        let mut frame = 0;
+        let mut app = Application::build("assets/", QuitOnFocus)
+            .unwrap()
+            .with_pre_frame(move |world| {
+                world.write_resource::<Vec<&'static str>>().push("pre");
+                frame += 1;
+                if frame == 2 {
+                    world
+                        .write_resource::<EventChannel<Event>>()
+                        .single_write(Event::WindowEvent {
+                            window_id: unsafe { WindowId::dummy() },
+                            event: WindowEvent::Focused(true),
+                        });
+                }
+            })
+            .with_event_hook(|world, event| {
+                if let StateEvent::Window(_) = event {
+                    world.write_resource::<Vec<&'static str>>().push("event");
+                }
+            })
+            .with_post_frame(|world| {
+                world.write_resource::<Vec<&'static str>>().push("post");
+            })
+            .build(GameDataBuilder::default())
+            .unwrap();
+        app.world_mut().add_resource(Vec::<&'static str>::new());
+
+        assert!(app.step());
+        assert!(!app.step());
+        assert!(!app.step());
+        assert_eq!(
+            *app.world().read_resource::<Vec<&'static str>>(),
+            vec!["pre", "post", "pre", "event", "post"]
+        );
+    }
+}
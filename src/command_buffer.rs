@@ -0,0 +1,240 @@
+use std::sync::Mutex;
+
+use log::error;
+
+use crate::core::ecs::{
+    prelude::{Component, Entity, World},
+    world::EntitiesRes,
+};
+
+type Command = Box<dyn FnOnce(&mut World) + Send>;
+
+/// A buffer of structural changes to the world, like creating and deleting entities or inserting
+/// and removing components, recorded by systems and applied later.
+///
+/// Systems only need to read the buffer to record commands, so they can still run in parallel.
+/// The `Application` applies the commands after every fixed update and after the update of the
+/// states, before the world is maintained. The commands of a system are applied in the order it
+/// recorded them, with the commands of systems running in parallel interleaved.
+///
+/// Unlike with `LazyUpdate`, the changes are applied at the same point every frame, and no
+/// `WriteStorage` is needed, so the systems using it don't conflict with the ones reading the
+/// components.
+///
+/// ```rust
+/// use amethyst::{
+///     ecs::prelude::*,
+///     CommandBuffer,
+/// };
+///
+/// struct Bullet;
+///
+/// impl Component for Bullet {
+///     type Storage = DenseVecStorage<Self>;
+/// }
+///
+/// struct FireSystem;
+///
+/// impl<'a> System<'a> for FireSystem {
+///     type SystemData = (Entities<'a>, Read<'a, CommandBuffer>);
+///
+///     fn run(&mut self, (entities, commands): Self::SystemData) {
+///         commands.create_entity(&entities).with(Bullet).build();
+///     }
+/// }
+///
+/// let mut world = World::new();
+/// world.register::<Bullet>();
+/// let mut system = FireSystem;
+/// System::setup(&mut system, &mut world.res);
+/// system.run_now(&world.res);
+///
+/// CommandBuffer::apply(&mut world);
+/// assert_eq!(world.read_storage::<Bullet>().join().count(), 1);
+/// ```
+#[derive(Default)]
+pub struct CommandBuffer {
+    commands: Mutex<Vec<Command>>,
+}
+
+impl CommandBuffer {
+    /// Creates an empty buffer.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Creates an entity right away, and returns a builder to add its components once the
+    /// commands are applied.
+    pub fn create_entity<'a>(&'a self, entities: &EntitiesRes) -> EntityCommands<'a> {
+        EntityCommands {
+            entity: entities.create(),
+            buffer: self,
+        }
+    }
+
+    /// Inserts a component once the commands are applied.
+    pub fn insert<C>(&self, entity: Entity, component: C)
+    where
+        C: Component + Send,
+    {
+        self.exec(move |world| {
+            if let Err(err) = world.write_storage::<C>().insert(entity, component) {
+                error!("Failed to insert a component into {:?}: {}", entity, err);
+            }
+        });
+    }
+
+    /// Removes a component once the commands are applied.
+    pub fn remove<C>(&self, entity: Entity)
+    where
+        C: Component,
+    {
+        self.exec(move |world| {
+            world.write_storage::<C>().remove(entity);
+        });
+    }
+
+    /// Deletes an entity once the commands are applied.
+    pub fn delete(&self, entity: Entity) {
+        self.exec(move |world| {
+            // The entity may have been deleted in the meantime, which is fine.
+            let _ = world.delete_entity(entity);
+        });
+    }
+
+    /// Runs a function on the world once the commands are applied.
+    pub fn exec<F>(&self, command: F)
+    where
+        F: FnOnce(&mut World) + Send + 'static,
+    {
+        match self.commands.lock() {
+            Ok(mut commands) => commands.push(Box::new(command)),
+            Err(_) => error!("A system panicked while recording commands"),
+        }
+    }
+
+    /// The number of commands waiting to be applied.
+    pub fn len(&self) -> usize {
+        self.commands
+            .lock()
+            .map(|commands| commands.len())
+            .unwrap_or(0)
+    }
+
+    /// Checks whether no commands are waiting to be applied.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Applies the commands recorded in the `CommandBuffer` resource of the world, if it has one.
+    ///
+    /// Commands recorded while applying are applied as well.
+    pub fn apply(world: &mut World) {
+        loop {
+            let commands = match world.res.try_fetch::<CommandBuffer>() {
+                Some(buffer) => match buffer.commands.lock() {
+                    Ok(mut commands) => std::mem::replace(&mut *commands, Vec::new()),
+                    Err(_) => return,
+                },
+                None => return,
+            };
+            if commands.is_empty() {
+                return;
+            }
+            for command in commands {
+                command(world);
+            }
+        }
+    }
+}
+
+/// Builds an entity created with `CommandBuffer::create_entity`.
+pub struct EntityCommands<'a> {
+    entity: Entity,
+    buffer: &'a CommandBuffer,
+}
+
+impl<'a> EntityCommands<'a> {
+    /// Adds a component to the entity once the commands are applied.
+    pub fn with<C>(self, component: C) -> Self
+    where
+        C: Component + Send,
+    {
+        self.buffer.insert(self.entity, component);
+        self
+    }
+
+    /// Finishes building, returning the entity.
+    pub fn build(self) -> Entity {
+        self.entity
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::ecs::prelude::{Builder, DenseVecStorage, Join};
+
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct Health(u32);
+
+    impl Component for Health {
+        type Storage = DenseVecStorage<Self>;
+    }
+
+    fn world() -> World {
+        let mut world = World::new();
+        world.register::<Health>();
+        world.add_resource(CommandBuffer::new());
+        world
+    }
+
+    #[test]
+    fn commands_are_applied_at_the_sync_point() {
+        let mut world = world();
+        let kept = world.create_entity().build();
+        let deleted = world.create_entity().with(Health(1)).build();
+        let created = {
+            let commands = world.read_resource::<CommandBuffer>();
+            let created = commands
+                .create_entity(&world.entities())
+                .with(Health(2))
+                .build();
+            commands.insert(kept, Health(3));
+            commands.delete(deleted);
+            created
+        };
+        assert_eq!(world.read_resource::<CommandBuffer>().len(), 3);
+        assert_eq!(world.read_storage::<Health>().join().count(), 1);
+
+        CommandBuffer::apply(&mut world);
+        world.maintain();
+        assert!(world.read_resource::<CommandBuffer>().is_empty());
+        let health = world.read_storage::<Health>();
+        assert_eq!(health.get(created), Some(&Health(2)));
+        assert_eq!(health.get(kept), Some(&Health(3)));
+        assert!(!world.is_alive(deleted));
+    }
+
+    #[test]
+    fn commands_on_entities_deleted_earlier_are_skipped() {
+        let mut world = world();
+        let entity = world.create_entity().build();
+        {
+            let commands = world.read_resource::<CommandBuffer>();
+            commands.delete(entity);
+            commands.insert(entity, Health(1));
+            // Recorded while applying, and applied as well.
+            commands.exec(move |world| {
+                world.read_resource::<CommandBuffer>().delete(entity);
+            });
+        }
+
+        CommandBuffer::apply(&mut world);
+        world.maintain();
+        assert!(!world.is_alive(entity));
+        assert_eq!(world.read_storage::<Health>().join().count(), 0);
+        assert!(world.read_resource::<CommandBuffer>().is_empty());
+    }
+}
@@ -0,0 +1,361 @@
+//! The crash reports written when the game panics.
+
+use std::{
+    fmt::Write as FmtWrite,
+    fs,
+    io::{self, Read as IoRead, Write as IoWrite},
+    net::{TcpStream, ToSocketAddrs},
+    panic::{self, PanicInfo},
+    path::PathBuf,
+    sync::{Arc, Mutex, MutexGuard},
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use backtrace::Backtrace;
+use log::{Log, Metadata, Record};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    core::{timing::Time, LogBuffer},
+    ecs::prelude::{Join, World},
+    state::StateStack,
+};
+
+/// The configuration of the `CrashReporter`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CrashConfig {
+    /// The directory the reports are written to, created if it doesn't exist.
+    pub directory: PathBuf,
+    /// An `http://` endpoint the reports are posted to, as plain text.
+    pub endpoint: Option<String>,
+    /// The number of recent log records in the reports.
+    pub log_lines: usize,
+    /// Whether the reports describe the world: its entities and its frame.
+    pub world_summary: bool,
+}
+
+impl Default for CrashConfig {
+    fn default() -> Self {
+        CrashConfig {
+            directory: PathBuf::from("crashes"),
+            endpoint: None,
+            log_lines: 100,
+            world_summary: true,
+        }
+    }
+}
+
+// What the reporter knows of the game, kept up to date while it runs.
+#[derive(Default)]
+struct Context {
+    states: Vec<&'static str>,
+    world: Option<String>,
+}
+
+/// Writes a crash report when the game panics, with the backtrace, the version of the engine,
+/// the recent log records, the state stack and a summary of the world.
+///
+/// The reporter is installed with `ApplicationBuilder::with_crash_reporter`, and its logger is
+/// chained to the logger so that the reports have the recent records:
+///
+/// ~~~no_run
+/// use amethyst::{prelude::*, CrashConfig, CrashReporter, Logger};
+///
+/// struct NullState;
+/// impl EmptyState for NullState {}
+///
+/// let reporter = CrashReporter::new(CrashConfig::default());
+/// Logger::from_config(Default::default())
+///     .chain(Box::new(reporter.logger()))
+///     .start();
+/// let mut game = Application::build("assets/", NullState)
+///     .expect("Failed to initialize")
+///     .with_crash_reporter(reporter)
+///     .build(())
+///     .expect("Failed to create Application");
+/// game.run();
+/// ~~~
+///
+/// The state stack and the world are those of the end of the last frame.
+#[derive(Clone)]
+pub struct CrashReporter {
+    config: Arc<CrashConfig>,
+    log: LogBuffer,
+    context: Arc<Mutex<Context>>,
+}
+
+impl CrashReporter {
+    /// Creates a reporter, which does nothing until it is installed.
+    pub fn new(config: CrashConfig) -> Self {
+        CrashReporter {
+            log: LogBuffer::new(config.log_lines),
+            config: Arc::new(config),
+            context: Default::default(),
+        }
+    }
+
+    /// The configuration of the reporter.
+    pub fn config(&self) -> &CrashConfig {
+        &self.config
+    }
+
+    /// A logger keeping the recent log records for the reports.
+    pub fn logger(&self) -> CrashLog {
+        CrashLog {
+            buffer: self.log.clone(),
+        }
+    }
+
+    /// Installs the panic hook writing the reports, after the hook installed before it.
+    pub fn install(&self) {
+        let reporter = self.clone();
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            previous(info);
+            reporter.crash(info);
+        }));
+    }
+
+    /// Records the state stack and the summary of the world, for the next report.
+    pub fn update(&self, world: &World) {
+        let states = world
+            .res
+            .try_fetch::<StateStack>()
+            .map(|stack| stack.states().to_vec())
+            .unwrap_or_default();
+        let summary = if self.config.world_summary {
+            let entities = (&*world.entities()).join().count();
+            let frame = world
+                .res
+                .try_fetch::<Time>()
+                .map_or(0, |time| time.frame_number());
+            Some(format!("{} entities, frame {}", entities, frame))
+        } else {
+            None
+        };
+        let mut context = self.context();
+        context.states = states;
+        context.world = summary;
+    }
+
+    /// The report of a panic, with the backtrace of the current thread.
+    pub fn report(&self, message: &str, location: Option<&str>) -> String {
+        let backtrace = Backtrace::new();
+        let context = self.context();
+        let rustc = rustc_version_runtime::version_meta();
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+
+        let mut report = String::new();
+        // Writing to a `String` can't fail.
+        let _ = writeln!(report, "Amethyst crash report");
+        let _ = writeln!(report, "Time: {}", time);
+        let _ = writeln!(
+            report,
+            "Version: {} ({})",
+            env!("CARGO_PKG_VERSION"),
+            env!("VERGEN_SHA")
+        );
+        let _ = writeln!(report, "Platform: {}", env!("VERGEN_TARGET_TRIPLE"));
+        let _ = writeln!(report, "Rustc: {} {:?}", rustc.semver, rustc.channel);
+        let _ = writeln!(
+            report,
+            "Thread: {}",
+            thread::current().name().unwrap_or("<unnamed>")
+        );
+        let _ = writeln!(report, "Panic: {}", message);
+        if let Some(location) = location {
+            let _ = writeln!(report, "Location: {}", location);
+        }
+        let _ = writeln!(report, "\nStates: {}", context.states.join(" > "));
+        if let Some(ref world) = context.world {
+            let _ = writeln!(report, "World: {}", world);
+        }
+        let _ = writeln!(report, "\nRecent log:");
+        for entry in self.log.entries() {
+            let _ = writeln!(
+                report,
+                "[{}][{}] {}",
+                entry.level, entry.target, entry.message
+            );
+        }
+        let _ = write!(report, "\nBacktrace:\n{:?}", backtrace);
+        report
+    }
+
+    /// Writes a report to the directory of the reports, returning its path.
+    pub fn write(&self, report: &str) -> io::Result<PathBuf> {
+        fs::create_dir_all(&self.config.directory)?;
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let path = self.config.directory.join(format!(
+            "crash-{}-{:03}.txt",
+            time.as_secs(),
+            time.subsec_millis()
+        ));
+        fs::write(&path, report)?;
+        Ok(path)
+    }
+
+    /// Posts a report to the endpoint, if there is one.
+    pub fn post(&self, report: &str) -> io::Result<()> {
+        match self.config.endpoint {
+            Some(ref endpoint) => post(endpoint, report),
+            None => Ok(()),
+        }
+    }
+
+    fn crash(&self, info: &PanicInfo<'_>) {
+        let message = match info.payload().downcast_ref::<&str>() {
+            Some(message) => (*message).to_string(),
+            None => match info.payload().downcast_ref::<String>() {
+                Some(message) => message.clone(),
+                None => "Box<Any>".to_string(),
+            },
+        };
+        let location = info.location().map(|location| {
+            format!(
+                "{}:{}:{}",
+                location.file(),
+                location.line(),
+                location.column()
+            )
+        });
+        let report = self.report(&message, location.as_ref().map(String::as_str));
+
+        // The logger may be what panicked, so the outcome goes to the standard error.
+        match self.write(&report) {
+            Ok(path) => eprintln!("The crash report was written to {}", path.display()),
+            Err(err) => eprintln!("Failed to write the crash report: {}", err),
+        }
+        if let Err(err) = self.post(&report) {
+            eprintln!("Failed to post the crash report: {}", err);
+        }
+    }
+
+    // A panic while the context was locked mustn't keep the report from being written.
+    fn context(&self) -> MutexGuard<'_, Context> {
+        self.context
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// The logger of a `CrashReporter`, keeping its recent log records in a `LogBuffer`.
+pub struct CrashLog {
+    buffer: LogBuffer,
+}
+
+impl Log for CrashLog {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        self.buffer.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        self.buffer.log(record);
+    }
+
+    fn flush(&self) {}
+}
+
+// Posts the report with HTTP/1.1, only over plain `http://`.
+fn post(endpoint: &str, report: &str) -> io::Result<()> {
+    const SCHEME: &str = "http://";
+    if !endpoint.starts_with(SCHEME) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Only http:// endpoints are supported, not {}", endpoint),
+        ));
+    }
+    let rest = &endpoint[SCHEME.len()..];
+    let (host, path) = match rest.find('/') {
+        Some(index) => (&rest[..index], &rest[index..]),
+        None => (rest, "/"),
+    };
+    let address = if host.contains(':') {
+        host.to_string()
+    } else {
+        format!("{}:80", host)
+    };
+
+    // A crashing game mustn't hang on an unreachable endpoint, so every step is bounded.
+    let timeout = Duration::from_secs(5);
+    let mut stream = connect(&address, timeout)?;
+    stream.set_write_timeout(Some(timeout))?;
+    stream.set_read_timeout(Some(timeout))?;
+    write!(
+        stream,
+        "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: text/plain; charset=utf-8\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+        path,
+        host,
+        report.len(),
+        report
+    )?;
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response)?;
+    let status = response.split_whitespace().nth(1).unwrap_or("");
+    if status.starts_with('2') {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!("The endpoint answered {}", status),
+        ))
+    }
+}
+
+// Connects to the first address `address` resolves to that answers within `timeout`.
+fn connect(address: &str, timeout: Duration) -> io::Result<TcpStream> {
+    let mut last_error = None;
+    for address in address.to_socket_addrs()? {
+        match TcpStream::connect_timeout(&address, timeout) {
+            Ok(stream) => return Ok(stream),
+            Err(err) => last_error = Some(err),
+        }
+    }
+    Err(last_error.unwrap_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{} resolves to no address", address),
+        )
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use log::Level;
+
+    use super::*;
+
+    #[test]
+    fn reports_have_the_recent_records() {
+        let reporter = CrashReporter::new(CrashConfig {
+            log_lines: 2,
+            ..Default::default()
+        });
+        let logger = reporter.logger();
+        for message in &["first", "second", "third"] {
+            logger.log(
+                &Record::builder()
+                    .args(format_args!("{}", message))
+                    .level(Level::Warn)
+                    .target("game")
+                    .build(),
+            );
+        }
+        reporter.update(&World::new());
+
+        let report = reporter.report("boom", Some("src/main.rs:1:1"));
+        assert!(report.contains("Panic: boom\nLocation: src/main.rs:1:1\n"));
+        assert!(report.contains("World: 0 entities, frame 0\n"));
+        assert!(report.contains("Recent log:\n[WARN][game] second\n[WARN][game] third\n"));
+        assert!(post("https://example.com", &report).is_err());
+    }
+}
@@ -0,0 +1,279 @@
+//! The graph of the systems added to a `GameDataBuilder`.
+
+use std::{collections::HashSet, fmt::Write};
+
+use crate::core::{
+    ecs::prelude::System,
+    shred::{Accessor, ResourceId},
+};
+
+/// How a system is dispatched.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum SystemKind {
+    Parallel,
+    ThreadLocal,
+    Fixed,
+}
+
+#[derive(Debug)]
+struct SystemNode {
+    name: String,
+    dependencies: Vec<String>,
+    reads: Vec<ResourceId>,
+    writes: Vec<ResourceId>,
+    kind: SystemKind,
+    // Systems in different stages are separated by a barrier.
+    stage: usize,
+}
+
+/// Records the systems, their dependencies and the resources they access, to check the
+/// dependencies and to draw the graph.
+#[derive(Debug, Default)]
+pub(crate) struct SystemGraph {
+    nodes: Vec<SystemNode>,
+    stage: usize,
+    // The systems added by bundles, with the name of their bundle.
+    bundle_systems: Vec<(String, &'static str, SystemKind)>,
+    errors: Vec<String>,
+}
+
+impl SystemGraph {
+    /// Records a system, returning the dependencies that exist.
+    pub(crate) fn add<'d, S>(
+        &mut self,
+        system: &S,
+        name: &str,
+        dependencies: &[&'d str],
+        kind: SystemKind,
+    ) -> Vec<&'d str>
+    where
+        for<'c> S: System<'c>,
+    {
+        if !name.is_empty() && self.has_system(name, kind) {
+            self.errors
+                .push(format!("System `{}` was added twice", name));
+        }
+        let mut existing = Vec::with_capacity(dependencies.len());
+        for &dependency in dependencies {
+            if self.has_system(dependency, kind) {
+                existing.push(dependency);
+            } else {
+                self.errors.push(format!(
+                    "System `{}` depends on `{}`, which wasn't added before it",
+                    name, dependency
+                ));
+            }
+        }
+        let accessor = System::accessor(system);
+        self.nodes.push(SystemNode {
+            name: name.to_owned(),
+            dependencies: existing
+                .iter()
+                .map(|dependency| dependency.to_string())
+                .collect(),
+            reads: accessor.reads(),
+            writes: accessor.writes(),
+            kind,
+            stage: self.stage,
+        });
+        existing
+    }
+
+    pub(crate) fn add_barrier(&mut self) {
+        self.stage += 1;
+    }
+
+    /// Records a bundle with the names of the systems it added.
+    pub(crate) fn add_bundle(
+        &mut self,
+        bundle: &'static str,
+        systems: &[String],
+        kind: SystemKind,
+    ) {
+        self.bundle_systems
+            .extend(systems.iter().map(|system| (system.clone(), bundle, kind)));
+    }
+
+    /// Checks whether a system with the given name was added, either directly or by a bundle.
+    pub(crate) fn has_system(&self, name: &str, kind: SystemKind) -> bool {
+        self.nodes
+            .iter()
+            .any(|node| node.name == name && node.kind == kind)
+            || self.bundle_of(name, kind).is_some()
+    }
+
+    /// The name of the bundle that added the system.
+    pub(crate) fn bundle_of(&self, system: &str, kind: SystemKind) -> Option<&'static str> {
+        self.bundle_systems
+            .iter()
+            .find(|(name, _, added)| name == system && *added == kind)
+            .map(|(_, bundle, _)| *bundle)
+    }
+
+    /// The names of the systems added, either directly or by a bundle.
+    pub(crate) fn names(&self, kind: SystemKind) -> Vec<String> {
+        self.nodes
+            .iter()
+            .filter(|node| node.kind == kind && !node.name.is_empty())
+            .map(|node| node.name.clone())
+            .chain(
+                self.bundle_systems
+                    .iter()
+                    .filter(|(_, _, added)| *added == kind)
+                    .map(|(name, _, _)| name.clone()),
+            )
+            .collect()
+    }
+
+    /// The problems found while adding the systems.
+    pub(crate) fn errors(&self) -> &[String] {
+        &self.errors
+    }
+
+    /// Draws the graph in the DOT format of graphviz. Dependencies are solid arrows, and systems
+    /// accessing the same resource without an order between them are linked with a dashed red
+    /// line, as they can't run in parallel.
+    pub(crate) fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph systems {\n    node [shape=box];\n");
+        for (index, node) in self.nodes.iter().enumerate() {
+            let style = match node.kind {
+                SystemKind::Parallel => "solid",
+                SystemKind::ThreadLocal => "dashed",
+                SystemKind::Fixed => "rounded",
+            };
+            let _ = writeln!(
+                dot,
+                "    s{} [label=\"{}\\nreads {}, writes {}\", style={}];",
+                index,
+                escape(&node.name),
+                node.reads.len(),
+                node.writes.len(),
+                style
+            );
+        }
+        for (index, node) in self.nodes.iter().enumerate() {
+            for dependency in &node.dependencies {
+                if let Some(from) = self.index_of(dependency, node.kind) {
+                    let _ = writeln!(dot, "    s{} -> s{};", from, index);
+                }
+            }
+        }
+        for (a, b, shared) in self.conflicts() {
+            let _ = writeln!(
+                dot,
+                "    s{} -> s{} [dir=none, style=dashed, color=red, label=\"{} shared\"];",
+                a, b, shared
+            );
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    fn index_of(&self, name: &str, kind: SystemKind) -> Option<usize> {
+        self.nodes
+            .iter()
+            .position(|node| node.name == name && node.kind == kind)
+    }
+
+    /// The pairs of parallel or fixed systems of the same stage, without an order between them,
+    /// that access the same resource with at least one of them writing it.
+    fn conflicts(&self) -> Vec<(usize, usize, usize)> {
+        let mut conflicts = Vec::new();
+        for (a, first) in self.nodes.iter().enumerate() {
+            for (b, second) in self.nodes.iter().enumerate().skip(a + 1) {
+                if first.kind != second.kind
+                    || first.kind == SystemKind::ThreadLocal
+                    || first.stage != second.stage
+                    || self.depends_on(b, a)
+                {
+                    continue;
+                }
+                let shared = first
+                    .writes
+                    .iter()
+                    .filter(|id| second.reads.contains(id) || second.writes.contains(id))
+                    .chain(second.writes.iter().filter(|id| first.reads.contains(id)))
+                    .collect::<HashSet<_>>()
+                    .len();
+                if shared > 0 {
+                    conflicts.push((a, b, shared));
+                }
+            }
+        }
+        conflicts
+    }
+
+    /// Checks whether the system `later` runs after the system `earlier`, through its
+    /// dependencies.
+    fn depends_on(&self, later: usize, earlier: usize) -> bool {
+        let mut open = vec![later];
+        let mut seen = HashSet::new();
+        while let Some(index) = open.pop() {
+            if index == earlier {
+                return true;
+            }
+            if !seen.insert(index) {
+                continue;
+            }
+            let node = &self.nodes[index];
+            open.extend(
+                node.dependencies
+                    .iter()
+                    .filter_map(|dependency| self.index_of(dependency, node.kind)),
+            );
+        }
+        false
+    }
+}
+
+fn escape(name: &str) -> String {
+    name.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecs::prelude::{Read, Write};
+
+    struct Writer;
+
+    impl<'a> System<'a> for Writer {
+        type SystemData = Write<'a, u32>;
+
+        fn run(&mut self, _: Self::SystemData) {}
+    }
+
+    struct Reader;
+
+    impl<'a> System<'a> for Reader {
+        type SystemData = Read<'a, u32>;
+
+        fn run(&mut self, _: Self::SystemData) {}
+    }
+
+    #[test]
+    fn missing_dependencies_and_conflicts() {
+        let mut graph = SystemGraph::default();
+        graph.add(&Writer, "writer", &[], SystemKind::Parallel);
+        let existing = graph.add(&Reader, "reader", &["writer", "typo"], SystemKind::Parallel);
+        assert_eq!(existing, vec!["writer"]);
+        assert_eq!(graph.errors().len(), 1);
+        assert!(graph.conflicts().is_empty());
+
+        graph.add(&Reader, "unordered", &[], SystemKind::Parallel);
+        assert_eq!(graph.conflicts(), vec![(0, 2, 1)]);
+        assert!(graph.to_dot().contains("s0 -> s1;"));
+
+        graph.add_bundle("TestBundle", &["bundled".to_owned()], SystemKind::Parallel);
+        assert_eq!(
+            graph.bundle_of("bundled", SystemKind::Parallel),
+            Some("TestBundle")
+        );
+        assert_eq!(graph.bundle_of("bundled", SystemKind::Fixed), None);
+        assert!(graph
+            .names(SystemKind::Parallel)
+            .contains(&"bundled".to_owned()));
+        graph.add(&Reader, "reader", &["bundled"], SystemKind::Parallel);
+        assert_eq!(graph.errors().len(), 2);
+    }
+}
@@ -1,11 +1,12 @@
-use std::path::Path;
+use std::{fs, path::Path};
 
 use crate::{
     core::{
         ecs::prelude::{Dispatcher, DispatcherBuilder, System, World},
-        ArcThreadPool, SystemBundle,
+        ArcThreadPool, BundleBuilder, DispatcherProfile, SystemBundle,
     },
-    error::Error,
+    dependency_graph::{SystemGraph, SystemKind},
+    error::{format_err, Error, ResultExt},
     renderer::pipe::pass::Pass,
 };
 
@@ -13,6 +14,11 @@ use crate::{
 pub trait DataInit<T> {
     /// Build game data
     fn build(self, world: &mut World) -> T;
+
+    /// Checks the game data can be built, before `build` is called.
+    fn validate(&self) -> Result<(), Error> {
+        Ok(())
+    }
 }
 
 /// Default game data.
@@ -21,23 +27,52 @@ pub trait DataInit<T> {
 /// field.
 pub struct GameData<'a, 'b> {
     dispatcher: Dispatcher<'a, 'b>,
+    fixed_dispatcher: Option<Dispatcher<'a, 'b>>,
 }
 
 impl<'a, 'b> GameData<'a, 'b> {
     /// Create new game data
     pub fn new(dispatcher: Dispatcher<'a, 'b>) -> Self {
-        GameData { dispatcher }
+        GameData {
+            dispatcher,
+            fixed_dispatcher: None,
+        }
+    }
+
+    /// Create new game data with systems run at the fixed time step, see
+    /// `GameDataBuilder::with_fixed`.
+    pub fn with_fixed(
+        dispatcher: Dispatcher<'a, 'b>,
+        fixed_dispatcher: Dispatcher<'a, 'b>,
+    ) -> Self {
+        GameData {
+            dispatcher,
+            fixed_dispatcher: Some(fixed_dispatcher),
+        }
     }
 
     /// Update game data
     pub fn update(&mut self, world: &World) {
         self.dispatcher.dispatch(&world.res);
     }
+
+    /// Run the systems of the fixed time step, once per fixed update.
+    ///
+    /// `SimpleState`s call this in their `fixed_update`. States implementing `State` directly
+    /// call it like they call `update`.
+    pub fn fixed_update(&mut self, world: &World) {
+        if let Some(ref mut dispatcher) = self.fixed_dispatcher {
+            dispatcher.dispatch(&world.res);
+        }
+    }
 }
 
 /// Builder for default game data
 pub struct GameDataBuilder<'a, 'b> {
     disp_builder: DispatcherBuilder<'a, 'b>,
+    fixed_builder: Option<DispatcherBuilder<'a, 'b>>,
+    profile: Option<DispatcherProfile>,
+    graph: SystemGraph,
 }
 
 impl<'a, 'b> Default for GameDataBuilder<'a, 'b> {
@@ -51,9 +86,29 @@ impl<'a, 'b> GameDataBuilder<'a, 'b> {
     pub fn new() -> Self {
         GameDataBuilder {
             disp_builder: DispatcherBuilder::new(),
+            fixed_builder: None,
+            profile: None,
+            graph: SystemGraph::default(),
         }
     }
 
+    /// Times the systems added from now on, including those of the bundles, in the
+    /// `DispatcherProfile` resource.
+    ///
+    /// The systems added before are not timed. Without profiling, the resource isn't added.
+    ///
+    /// # Examples
+    ///
+    /// ~~~no_run
+    /// use amethyst::prelude::*;
+    ///
+    /// let game_data = GameDataBuilder::default().with_profiling();
+    /// ~~~
+    pub fn with_profiling(mut self) -> Self {
+        self.profile.get_or_insert_with(DispatcherProfile::default);
+        self
+    }
+
     /// Inserts a barrier which assures that all systems added before the
     /// barrier are executed before the ones after this barrier.
     ///
@@ -88,6 +143,7 @@ impl<'a, 'b> GameDataBuilder<'a, 'b> {
     /// ~~~
     pub fn with_barrier(mut self) -> Self {
         self.disp_builder.add_barrier();
+        self.graph.add_barrier();
         self
     }
 
@@ -95,6 +151,10 @@ impl<'a, 'b> GameDataBuilder<'a, 'b> {
     ///
     /// __Note:__ all dependencies must be added before you add the system.
     ///
+    /// To run a system only some of the time, wrap it with the `SystemExt` methods `pausable`,
+    /// `run_if`, `run_when` or `every` before adding it. A `RunCondition` given to `run_when`
+    /// can read any system data, like several resources or the components of the world.
+    ///
     /// # Parameters
     ///
     /// - `system`: The system that is to be added to the game loop.
@@ -115,11 +175,11 @@ impl<'a, 'b> GameDataBuilder<'a, 'b> {
     ///
     /// # Panics
     ///
-    /// If two system are added that share an identical name, this function will panic.
-    /// Empty names are permitted, and this function will not panic if more then two are added.
+    /// If two system are added that share an identical name, building the application fails
+    /// with an error. Empty names are permitted, and may be used by any number of systems.
     ///
-    /// If a dependency is referenced (by name), but has not previously been added this
-    /// function will panic.
+    /// If a dependency is referenced (by name), but has not previously been added, building
+    /// the application fails with an error listing the missing dependencies.
     ///
     /// # Examples
     ///
@@ -140,13 +200,27 @@ impl<'a, 'b> GameDataBuilder<'a, 'b> {
     ///     // The "bar" system will only run after the "foo" system has completed
     ///     .with(NopSystem, "bar", &["foo"])
     ///     // It is legal to register a system with an empty name
-    ///     .with(NopSystem, "", &[]);
+    ///     .with(NopSystem, "", &[])
+    ///     // The "slow" system only runs every tenth frame
+    ///     .with(NopSystem.every(10), "slow", &[]);
     /// ~~~
     pub fn with<S>(mut self, system: S, name: &str, dependencies: &[&str]) -> Self
     where
         for<'c> S: System<'c> + Send + 'a,
     {
-        self.disp_builder.add(system, name, dependencies);
+        let duplicate = self.graph.has_system(name, SystemKind::Parallel);
+        let dependencies = self
+            .graph
+            .add(&system, name, dependencies, SystemKind::Parallel);
+        // Building fails on duplicates, without the name the dispatcher doesn't panic first.
+        let added = if duplicate { "" } else { name };
+        match self.profile {
+            Some(ref profile) => {
+                let system = profile.wrap(system, name);
+                self.disp_builder.add(system, added, &dependencies)
+            }
+            None => self.disp_builder.add(system, added, &dependencies),
+        }
         self
     }
 
@@ -192,7 +266,77 @@ impl<'a, 'b> GameDataBuilder<'a, 'b> {
     where
         for<'c> S: System<'c> + 'b,
     {
-        self.disp_builder.add_thread_local(system);
+        // Thread-local systems have no name, they are told apart by the order they were added.
+        let name = format!(
+            "thread_local_{}",
+            self.graph.names(SystemKind::ThreadLocal).len()
+        );
+        self.graph.add(&system, &name, &[], SystemKind::ThreadLocal);
+        match self.profile {
+            Some(ref profile) => self
+                .disp_builder
+                .add_thread_local(profile.wrap(system, &name)),
+            None => self.disp_builder.add_thread_local(system),
+        }
+        self
+    }
+
+    /// Adds a given system to the fixed time step.
+    ///
+    /// Fixed systems run on every fixed update, at the stable rate set by
+    /// `Time::set_fixed_seconds`, independently of the frame rate. This is meant for
+    /// physics and deterministic gameplay. They run before the active state's `fixed_update`,
+    /// and the world is maintained after every fixed update.
+    ///
+    /// The fixed systems are run by the `fixed_update` of `SimpleState`s. States implementing
+    /// `State` directly run them with `GameData::fixed_update`, like they run the other systems
+    /// with `GameData::update`.
+    ///
+    /// # Parameters
+    ///
+    /// - `system`: The system that is to be added to the fixed time step.
+    /// - `name`: A string name that is used to identify the system, only among fixed systems.
+    /// - `dependencies`: A list of the names of fixed systems that must run before this one.
+    ///
+    /// # Returns
+    ///
+    /// This function returns GameDataBuilder after it has modified it.
+    ///
+    /// # Examples
+    ///
+    /// ~~~no_run
+    /// use amethyst::prelude::*;
+    /// use amethyst::ecs::prelude::System;
+    ///
+    /// struct PhysicsSystem;
+    /// impl<'a> System<'a> for PhysicsSystem {
+    ///     type SystemData = ();
+    ///     fn run(&mut self, _: Self::SystemData) {}
+    /// }
+    ///
+    /// GameDataBuilder::default()
+    ///     // the physics system runs at the fixed time step
+    ///     .with_fixed(PhysicsSystem, "physics", &[]);
+    /// ~~~
+    pub fn with_fixed<S>(mut self, system: S, name: &str, dependencies: &[&str]) -> Self
+    where
+        for<'c> S: System<'c> + Send + 'a,
+    {
+        let duplicate = self.graph.has_system(name, SystemKind::Fixed);
+        let dependencies = self
+            .graph
+            .add(&system, name, dependencies, SystemKind::Fixed);
+        let added = if duplicate { "" } else { name };
+        let fixed_builder = self
+            .fixed_builder
+            .get_or_insert_with(DispatcherBuilder::new);
+        match self.profile {
+            Some(ref profile) => {
+                let system = profile.wrap(system, name);
+                fixed_builder.add(system, added, &dependencies)
+            }
+            None => fixed_builder.add(system, added, &dependencies),
+        }
         self
     }
 
@@ -214,15 +358,98 @@ impl<'a, 'b> GameDataBuilder<'a, 'b> {
     /// This function creates systems, which use any number of dependent crates or APIs, which
     /// could result in any number of errors.
     /// See each individual bundle for a description of the errors it could produce.
+    /// It also fails if the bundle requires a system that wasn't added yet, or adds a system
+    /// with the name of one that was.
     ///
     pub fn with_bundle<B>(mut self, bundle: B) -> Result<Self, Error>
     where
         B: SystemBundle<'a, 'b>,
     {
-        bundle.build(&mut self.disp_builder)?;
+        let name = bundle.name();
+        for requirement in bundle.requirements() {
+            if !self
+                .graph
+                .has_system(requirement.system, SystemKind::Parallel)
+            {
+                return Err(format_err!(
+                    "`{}` requires the system `{}`, add the `{}` before it",
+                    name,
+                    requirement.system,
+                    requirement.bundle
+                ));
+            }
+        }
+        let dispatcher = std::mem::replace(&mut self.disp_builder, DispatcherBuilder::new());
+        self.disp_builder = self.build_bundle(dispatcher, bundle, SystemKind::Parallel)?;
         Ok(self)
     }
 
+    /// Adds a given ECS bundle to the fixed time step, see `with_fixed`.
+    ///
+    /// The systems of the bundle run on every fixed update, and their dependencies are among the
+    /// fixed systems. Unlike `with_bundle`, the requirements of the bundle aren't checked, as
+    /// they are systems of the frame.
+    ///
+    /// # Errors
+    ///
+    /// See each individual bundle for a description of the errors it could produce. It also
+    /// fails if the bundle adds a system with the name of a fixed system that was added, or
+    /// depending on a fixed system that wasn't.
+    pub fn with_fixed_bundle<B>(mut self, bundle: B) -> Result<Self, Error>
+    where
+        B: SystemBundle<'a, 'b>,
+    {
+        let dispatcher = self
+            .fixed_builder
+            .take()
+            .unwrap_or_else(DispatcherBuilder::new);
+        self.fixed_builder = Some(self.build_bundle(dispatcher, bundle, SystemKind::Fixed)?);
+        Ok(self)
+    }
+
+    /// Builds the bundle into the dispatcher, recording the systems it adds in the graph.
+    fn build_bundle<B>(
+        &mut self,
+        dispatcher: DispatcherBuilder<'a, 'b>,
+        bundle: B,
+        kind: SystemKind,
+    ) -> Result<DispatcherBuilder<'a, 'b>, Error>
+    where
+        B: SystemBundle<'a, 'b>,
+    {
+        let name = bundle.name();
+        let mut builder = BundleBuilder::with_systems(dispatcher, self.graph.names(kind));
+        if let Some(ref profile) = self.profile {
+            builder = builder.with_profile(profile.clone());
+        }
+        bundle.build(&mut builder)?;
+        if let Some(system) = builder.duplicates().first() {
+            return Err(match self.graph.bundle_of(system, kind) {
+                Some(other) => format_err!(
+                    "`{}` adds the system `{}`, which `{}` already added",
+                    name,
+                    system,
+                    other
+                ),
+                None => format_err!(
+                    "`{}` adds the system `{}`, which was already added",
+                    name,
+                    system
+                ),
+            });
+        }
+        if let Some((system, dependency)) = builder.missing().first() {
+            return Err(format_err!(
+                "`{}` adds the system `{}` depending on `{}`, which wasn't added before it",
+                name,
+                system,
+                dependency
+            ));
+        }
+        self.graph.add_bundle(name, builder.systems(), kind);
+        Ok(builder.into_dispatcher())
+    }
+
     /// Create a basic renderer with a single given `Pass`, and optional support for the `DrawUi` pass.
     ///
     /// Will set the clear color to black.
@@ -260,22 +487,212 @@ impl<'a, 'b> GameDataBuilder<'a, 'b> {
             self.with_bundle(RenderBundle::new(pipe, Some(config)))
         }
     }
+
+    /// Writes the graph of the systems to `path` in the DOT format, to be drawn with graphviz.
+    ///
+    /// The graph has the systems added with `with`, `with_thread_local` and `with_fixed`, with
+    /// their dependencies and the number of resources they read and write. Systems that access
+    /// the same resource without a dependency between them, and so can't run in parallel, are
+    /// linked with a dashed red line. The systems added by bundles are not in the graph.
+    ///
+    /// # Examples
+    ///
+    /// ~~~no_run
+    /// use amethyst::prelude::*;
+    ///
+    /// # fn main() -> amethyst::Result<()> {
+    /// let game_data = GameDataBuilder::default();
+    /// game_data.write_dependency_graph("systems.dot")?;
+    /// # Ok(())
+    /// # }
+    /// ~~~
+    pub fn write_dependency_graph<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        fs::write(path.as_ref(), self.graph.to_dot()).with_context(|_| {
+            format_err!(
+                "Failed to write the dependency graph to {}",
+                path.as_ref().display()
+            )
+        })
+    }
+
+    /// Checks that the dependencies of the systems were added before them.
+    ///
+    /// Unknown dependencies are left out when the system is added, so that the mistake is
+    /// reported by this check when the application is built.
+    pub fn validate(&self) -> Result<(), Error> {
+        match self.graph.errors() {
+            [] => Ok(()),
+            errors => Err(format_err!(
+                "Invalid system dependencies:\n{}",
+                errors.join("\n")
+            )),
+        }
+    }
 }
 
 impl<'a, 'b> DataInit<GameData<'a, 'b>> for GameDataBuilder<'a, 'b> {
+    fn validate(&self) -> Result<(), Error> {
+        GameDataBuilder::validate(self)
+    }
+
     fn build(self, world: &mut World) -> GameData<'a, 'b> {
+        if let Some(profile) = self.profile {
+            world.add_resource(profile);
+        }
+
         #[cfg(not(no_threading))]
         let pool = world.read_resource::<ArcThreadPool>().clone();
 
         #[cfg(not(no_threading))]
-        let mut dispatcher = self.disp_builder.with_pool(pool).build();
+        let mut dispatcher = self.disp_builder.with_pool(pool.clone()).build();
         #[cfg(no_threading)]
         let mut dispatcher = self.disp_builder.build();
         dispatcher.setup(&mut world.res);
-        GameData::new(dispatcher)
+
+        match self.fixed_builder {
+            Some(fixed_builder) => {
+                #[cfg(not(no_threading))]
+                let mut fixed_dispatcher = fixed_builder.with_pool(pool).build();
+                #[cfg(no_threading)]
+                let mut fixed_dispatcher = fixed_builder.build();
+                fixed_dispatcher.setup(&mut world.res);
+                GameData::with_fixed(dispatcher, fixed_dispatcher)
+            }
+            None => GameData::new(dispatcher),
+        }
     }
 }
 
 impl DataInit<()> for () {
     fn build(self, _: &mut World) {}
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::BundleRequirement;
+
+    struct NopSystem;
+
+    impl<'a> System<'a> for NopSystem {
+        type SystemData = ();
+
+        fn run(&mut self, _: Self::SystemData) {}
+    }
+
+    struct TestBundle {
+        system: &'static str,
+        dependencies: &'static [&'static str],
+        requirement: Option<BundleRequirement>,
+    }
+
+    impl TestBundle {
+        fn new(system: &'static str, dependencies: &'static [&'static str]) -> Self {
+            TestBundle {
+                system,
+                dependencies,
+                requirement: None,
+            }
+        }
+    }
+
+    impl<'a, 'b> SystemBundle<'a, 'b> for TestBundle {
+        fn build(self, builder: &mut BundleBuilder<'a, 'b>) -> Result<(), Error> {
+            builder.add(NopSystem, self.system, self.dependencies);
+            Ok(())
+        }
+
+        fn name(&self) -> &'static str {
+            "TestBundle"
+        }
+
+        fn requirements(&self) -> Vec<BundleRequirement> {
+            self.requirement.into_iter().collect()
+        }
+    }
+
+    fn error(result: Result<GameDataBuilder<'_, '_>, Error>) -> String {
+        result
+            .err()
+            .expect("adding the bundle should fail")
+            .to_string()
+    }
+
+    #[test]
+    fn systems_depend_on_the_systems_of_bundles() {
+        let builder = GameDataBuilder::default()
+            .with_bundle(TestBundle::new("bundled", &[]))
+            .unwrap()
+            .with(NopSystem, "after", &["bundled"])
+            .with_bundle(TestBundle::new("last", &["after"]))
+            .unwrap()
+            .with_fixed_bundle(TestBundle::new("fixed", &[]))
+            .unwrap()
+            .with_fixed(NopSystem, "after_fixed", &["fixed"]);
+        assert!(builder.validate().is_ok());
+
+        let builder = GameDataBuilder::default()
+            .with_fixed_bundle(TestBundle::new("fixed", &[]))
+            .unwrap()
+            .with(NopSystem, "after", &["fixed"]);
+        assert!(builder.validate().is_err());
+    }
+
+    #[test]
+    fn bundles_adding_existing_systems_fail() {
+        let added = GameDataBuilder::default()
+            .with(NopSystem, "nop", &[])
+            .with_bundle(TestBundle::new("nop", &[]));
+        assert!(error(added).contains("which was already added"));
+
+        let bundled = GameDataBuilder::default()
+            .with_bundle(TestBundle::new("nop", &[]))
+            .unwrap()
+            .with_bundle(TestBundle::new("nop", &[]));
+        assert!(error(bundled).contains("which `TestBundle` already added"));
+
+        let fixed = GameDataBuilder::default()
+            .with_fixed(NopSystem, "nop", &[])
+            .with_fixed_bundle(TestBundle::new("nop", &[]));
+        assert!(error(fixed).contains("which was already added"));
+    }
+
+    #[test]
+    fn bundles_missing_systems_fail() {
+        let missing = GameDataBuilder::default().with_bundle(TestBundle::new("nop", &["missing"]));
+        assert!(error(missing).contains("depending on `missing`"));
+
+        let required = GameDataBuilder::default().with_bundle(TestBundle {
+            requirement: Some(BundleRequirement::new("missing", "OtherBundle")),
+            ..TestBundle::new("nop", &[])
+        });
+        assert!(error(required).contains("add the `OtherBundle` before it"));
+
+        let frame = GameDataBuilder::default()
+            .with(NopSystem, "frame", &[])
+            .with_fixed_bundle(TestBundle::new("nop", &["frame"]));
+        assert!(error(frame).contains("depending on `frame`"));
+    }
+
+    #[test]
+    fn profiling_times_the_systems_added_after_it() {
+        assert!(GameDataBuilder::default().profile.is_none());
+
+        let builder = GameDataBuilder::default()
+            .with(NopSystem, "before", &[])
+            .with_profiling()
+            .with(NopSystem, "added", &[])
+            .with_bundle(TestBundle::new("bundled", &[]))
+            .unwrap()
+            .with_fixed_bundle(TestBundle::new("fixed", &[]))
+            .unwrap();
+        let names = builder
+            .profile
+            .expect("profiling should be enabled")
+            .timings()
+            .into_iter()
+            .map(|timing| timing.name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["added", "bundled", "fixed"]);
+    }
+}
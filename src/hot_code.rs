@@ -0,0 +1,385 @@
+//! Game logic compiled into a dynamic library, reloaded while the game runs.
+
+use std::{
+    collections::hash_map::DefaultHasher,
+    env, fs,
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+    process,
+    time::{Duration, Instant, SystemTime},
+};
+
+use libloading::{Library, Symbol};
+use log::{error, info, LevelFilter, Log};
+
+use crate::{
+    ecs::prelude::World,
+    error::{format_err, Error, ResultExt},
+};
+
+const ABI_SYMBOL: &[u8] = b"amethyst_game_library_abi\0";
+const LOGIC_SYMBOL: &[u8] = b"amethyst_game_logic\0";
+
+type AbiFn = fn() -> u64;
+type LogicFn = fn(&'static dyn Log, LevelFilter) -> Box<dyn GameLogic>;
+
+/// The game logic of a `GameLibrary`, exported by the library with the [`game_library`] macro.
+///
+/// The logic is dropped when the library is reloaded, so the state of the game must be kept in
+/// the world, with components and resources defined in a crate the game links, not in the
+/// library. The systems of the library are run by the logic, with `RunNow::run_now`.
+///
+/// [`game_library`]: macro.game_library.html
+pub trait GameLogic {
+    /// Called when the library is loaded, and after every reload.
+    fn load(&mut self, _world: &mut World) {}
+
+    /// Called at the start of every frame, before the states are updated.
+    fn update(&mut self, world: &mut World);
+
+    /// Called before the library is unloaded, to prepare the world for the next version.
+    fn unload(&mut self, _world: &mut World) {}
+}
+
+/// Exports the `GameLogic` of a game library, built as a `cdylib` with the same compiler and the
+/// same versions of the crates as the game.
+///
+/// ~~~ignore
+/// use amethyst::{ecs::prelude::*, game_library, GameLogic};
+///
+/// #[derive(Default)]
+/// struct Gameplay;
+///
+/// impl GameLogic for Gameplay {
+///     fn update(&mut self, world: &mut World) {
+///         MovementSystem.run_now(&world.res);
+///     }
+/// }
+///
+/// game_library!(Gameplay::default());
+/// ~~~
+#[macro_export]
+macro_rules! game_library {
+    ($logic:expr) => {
+        #[doc(hidden)]
+        #[no_mangle]
+        pub fn amethyst_game_library_abi() -> u64 {
+            $crate::__game_library_abi()
+        }
+
+        #[doc(hidden)]
+        #[no_mangle]
+        pub fn amethyst_game_logic(
+            logger: &'static dyn $crate::__Log,
+            level: $crate::LogLevelFilter,
+        ) -> Box<dyn $crate::GameLogic> {
+            $crate::__init_game_library(logger, level);
+            Box::new($logic)
+        }
+    };
+}
+
+#[doc(hidden)]
+pub use log::Log as __Log;
+
+/// The version of the engine and of the compiler, which the game and the library must share.
+#[doc(hidden)]
+pub fn __game_library_abi() -> u64 {
+    let rustc = rustc_version_runtime::version_meta();
+    let mut hasher = DefaultHasher::new();
+    env!("CARGO_PKG_VERSION").hash(&mut hasher);
+    rustc.semver.to_string().hash(&mut hasher);
+    format!("{:?}", rustc.channel).hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Sends the records of the library to the logger of the game.
+#[doc(hidden)]
+pub fn __init_game_library(logger: &'static dyn Log, level: LevelFilter) {
+    // The library has its own copy of the `log` crate, set once per load.
+    if log::set_logger(logger).is_ok() {
+        log::set_max_level(level);
+    }
+}
+
+struct Loaded {
+    logic: Box<dyn GameLogic>,
+    library: Library,
+    copy: PathBuf,
+}
+
+impl Loaded {
+    fn close(self) {
+        let Loaded {
+            logic,
+            library,
+            copy,
+        } = self;
+        drop(logic);
+        // The world may still hold code of the library, like the vtables of the trait objects
+        // or the functions of the callbacks it registered, so it's never unloaded.
+        std::mem::forget(library);
+        // Fails on the platforms keeping the loaded libraries from being removed.
+        let _ = fs::remove_file(copy);
+    }
+}
+
+/// A dynamic library with the `GameLogic`, reloaded when it is rebuilt. It's added with
+/// `ApplicationBuilder::with_game_library`, which loads it and updates it every frame.
+///
+/// The library is copied before it's loaded, so that it can be rebuilt while the game runs. A
+/// reload waits for the file to stop changing, and a library that fails to load is logged and
+/// the previous one kept, so that a failed build doesn't stop the game.
+///
+/// The replaced libraries stay loaded until the process exits, as the world may still refer to
+/// their code, so every reload adds a library to the memory of the process.
+pub struct GameLibrary {
+    path: PathBuf,
+    settle_time: Duration,
+    modified: Option<SystemTime>,
+    changed: Option<(SystemTime, Instant)>,
+    generation: u64,
+    loaded: Option<Loaded>,
+}
+
+impl GameLibrary {
+    /// Creates the library built at `path`, which is loaded by `load`.
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        GameLibrary {
+            path: path.as_ref().to_path_buf(),
+            settle_time: Duration::from_millis(300),
+            modified: None,
+            changed: None,
+            generation: 0,
+            loaded: None,
+        }
+    }
+
+    /// Creates the library of the crate `name` built in `directory`, with the name of the
+    /// libraries of the platform, as `target/debug/libgame.so` for `game` on Linux.
+    pub fn from_crate<P: AsRef<Path>>(directory: P, name: &str) -> Self {
+        GameLibrary::new(directory.as_ref().join(library_file_name(name)))
+    }
+
+    /// Sets for how long the file must stay unchanged before it's reloaded, 300 milliseconds by
+    /// default, so that a library being written isn't loaded.
+    pub fn with_settle_time(mut self, settle_time: Duration) -> Self {
+        self.settle_time = settle_time;
+        self
+    }
+
+    /// The path of the library.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The number of times the library was loaded.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Whether a library is loaded.
+    pub fn is_loaded(&self) -> bool {
+        self.loaded.is_some()
+    }
+
+    /// Loads the library, replacing the loaded one.
+    ///
+    /// The new library is opened before the logic of the loaded one is unloaded, which is kept
+    /// if the new one can't be loaded.
+    pub fn load(&mut self, world: &mut World) -> Result<(), Error> {
+        let modified = fs::metadata(&self.path)
+            .and_then(|metadata| metadata.modified())
+            .with_context(|_| format_err!("Failed to read {}", self.path.display()))?;
+        let mut loaded = self.open(self.generation + 1)?;
+
+        if let Some(mut previous) = self.loaded.take() {
+            previous.logic.unload(world);
+            previous.close();
+        }
+        loaded.logic.load(world);
+        self.loaded = Some(loaded);
+        self.modified = Some(modified);
+        self.changed = None;
+        self.generation += 1;
+        Ok(())
+    }
+
+    /// Reloads the library if it was rebuilt, returning whether it was.
+    pub fn reload_if_changed(&mut self, world: &mut World) -> bool {
+        let modified = match fs::metadata(&self.path).and_then(|metadata| metadata.modified()) {
+            Ok(modified) => modified,
+            // Removed while it's rebuilt.
+            Err(_) => return false,
+        };
+        if Some(modified) == self.modified {
+            self.changed = None;
+            return false;
+        }
+        match self.changed {
+            Some((changed, since)) if changed == modified => {
+                if since.elapsed() < self.settle_time {
+                    return false;
+                }
+            }
+            _ => {
+                self.changed = Some((modified, Instant::now()));
+                return false;
+            }
+        }
+
+        match self.load(world) {
+            Ok(()) => {
+                info!(
+                    "Reloaded the game library {} ({})",
+                    self.path.display(),
+                    self.generation
+                );
+                true
+            }
+            Err(err) => {
+                error!("Failed to reload the game library: {}", err);
+                // Not retried until it's rebuilt again.
+                self.modified = Some(modified);
+                self.changed = None;
+                false
+            }
+        }
+    }
+
+    /// Runs the logic of the loaded library for a frame.
+    pub fn update(&mut self, world: &mut World) {
+        if let Some(ref mut loaded) = self.loaded {
+            loaded.logic.update(world);
+        }
+    }
+
+    fn open(&self, generation: u64) -> Result<Loaded, Error> {
+        // Loaded from a copy with a new name, as the library can't be replaced while it's
+        // loaded on some platforms, and the same path isn't opened again on others.
+        let file_name = self
+            .path
+            .file_name()
+            .ok_or_else(|| format_err!("{} is not a file", self.path.display()))?;
+        let copy = env::temp_dir().join(format!(
+            "amethyst-{}-{}-{}",
+            process::id(),
+            generation,
+            file_name.to_string_lossy()
+        ));
+        fs::copy(&self.path, &copy)
+            .with_context(|_| format_err!("Failed to copy {}", self.path.display()))?;
+
+        let opened = Library::new(&copy)
+            .with_context(|_| format_err!("Failed to open {}", self.path.display()))
+            .and_then(|library| {
+                let logic = unsafe { create_logic(&library) }?;
+                Ok(Loaded {
+                    logic,
+                    library,
+                    copy: copy.clone(),
+                })
+            });
+        if opened.is_err() {
+            let _ = fs::remove_file(&copy);
+        }
+        opened
+    }
+}
+
+impl Drop for GameLibrary {
+    fn drop(&mut self) {
+        if let Some(loaded) = self.loaded.take() {
+            loaded.close();
+        }
+    }
+}
+
+// The symbols are only called with the signatures of `game_library!`, once the library is known
+// to be built with the same engine and compiler.
+unsafe fn create_logic(library: &Library) -> Result<Box<dyn GameLogic>, Error> {
+    let abi: Symbol<'_, AbiFn> = library.get(ABI_SYMBOL).with_context(|_| {
+        format_err!("The library doesn't export its game logic with `game_library!`")
+    })?;
+    if abi() != __game_library_abi() {
+        return Err(format_err!(
+            "The library was built with another version of the engine or of the compiler"
+        ));
+    }
+    let logic: Symbol<'_, LogicFn> = library.get(LOGIC_SYMBOL).with_context(|_| {
+        format_err!("The library doesn't export its game logic with `game_library!`")
+    })?;
+    Ok(logic(log::logger(), log::max_level()))
+}
+
+/// The file name of the dynamic library of a crate on this platform.
+pub fn library_file_name(name: &str) -> String {
+    format!(
+        "{}{}{}",
+        env::consts::DLL_PREFIX,
+        name,
+        env::consts::DLL_SUFFIX
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_libraries_fail_to_load() {
+        let name = library_file_name("game");
+        assert!(name.contains("game"));
+        assert!(name.ends_with(env::consts::DLL_SUFFIX));
+
+        let mut library = GameLibrary::from_crate("target/missing", "game");
+        let mut world = World::new();
+        assert!(library.load(&mut world).is_err());
+        assert!(!library.is_loaded());
+        assert!(!library.reload_if_changed(&mut world));
+        assert_eq!(library.generation(), 0);
+    }
+
+    #[test]
+    fn rebuilt_libraries_are_reloaded_once_they_settle() {
+        let path = env::temp_dir().join(format!("amethyst-{}-plain", process::id()));
+        fs::write(&path, b"not a library").unwrap();
+        let modified = fs::metadata(&path).unwrap().modified().unwrap();
+        let settle_time = Duration::from_millis(50);
+        let mut library = GameLibrary::new(&path).with_settle_time(settle_time);
+        let mut world = World::new();
+
+        // The change is seen, then waits for the file to settle.
+        assert!(!library.reload_if_changed(&mut world));
+        assert_eq!(library.changed.map(|(changed, _)| changed), Some(modified));
+        assert!(!library.reload_if_changed(&mut world));
+        assert_eq!(library.modified, None);
+
+        // A file changing while it settles waits again.
+        library.changed = Some((SystemTime::UNIX_EPOCH, Instant::now() - settle_time * 2));
+        assert!(!library.reload_if_changed(&mut world));
+        assert_eq!(library.modified, None);
+
+        // Once settled it's loaded, and as it isn't a library, not retried until rebuilt.
+        std::thread::sleep(settle_time * 2);
+        assert!(!library.reload_if_changed(&mut world));
+        assert_eq!(library.modified, Some(modified));
+        assert!(library.changed.is_none());
+        std::thread::sleep(settle_time * 2);
+        assert!(!library.reload_if_changed(&mut world));
+        assert!(library.changed.is_none());
+
+        // Rebuilt, it's loaded again after settling.
+        library.modified = Some(SystemTime::UNIX_EPOCH);
+        assert!(!library.reload_if_changed(&mut world));
+        assert!(library.changed.is_some());
+        std::thread::sleep(settle_time * 2);
+        assert!(!library.reload_if_changed(&mut world));
+        assert_eq!(library.modified, Some(modified));
+
+        fs::remove_file(&path).unwrap();
+        assert!(!library.reload_if_changed(&mut world));
+        assert!(!library.is_loaded());
+        assert_eq!(library.generation(), 0);
+    }
+}
@@ -55,6 +55,8 @@
 #![doc(html_logo_url = "https://www.amethyst.rs/brand/logo-standard.svg")]
 #![warn(missing_docs, rust_2018_idioms, rust_2018_compatibility)]
 
+#[cfg(feature = "ai")]
+pub use amethyst_ai as ai;
 #[cfg(feature = "animation")]
 pub use amethyst_animation as animation;
 pub use amethyst_assets as assets;
@@ -67,12 +69,20 @@ pub use amethyst_derive as derive;
 pub use amethyst_error as error;
 #[cfg(feature = "gltf")]
 pub use amethyst_gltf as gltf;
+#[cfg(feature = "imgui")]
+pub use amethyst_imgui as imgui;
 pub use amethyst_input as input;
 #[cfg(feature = "locale")]
 pub use amethyst_locale as locale;
 #[cfg(feature = "network")]
 pub use amethyst_network as network;
+#[cfg(feature = "physics")]
+pub use amethyst_physics as physics;
 pub use amethyst_renderer as renderer;
+#[cfg(feature = "scripting")]
+pub use amethyst_scripting as scripting;
+#[cfg(feature = "tiles")]
+pub use amethyst_tiles as tiles;
 pub use amethyst_ui as ui;
 pub use amethyst_utils as utils;
 pub use winit;
@@ -81,17 +91,33 @@ pub use crate::core::{ecs, shred, shrev};
 #[doc(hidden)]
 pub use crate::derive::*;
 
+#[cfg(feature = "hot_code")]
+#[doc(hidden)]
+pub use self::hot_code::{__Log, __game_library_abi, __init_game_library};
+#[cfg(feature = "hot_code")]
+pub use self::hot_code::{library_file_name, GameLibrary, GameLogic};
+
 pub use self::{
     app::{Application, ApplicationBuilder, CoreApplication},
     callback_queue::{Callback, CallbackQueue},
+    command_buffer::{CommandBuffer, EntityCommands},
+    crash::{CrashConfig, CrashLog, CrashReporter},
     error::Error,
     game_data::{DataInit, GameData, GameDataBuilder},
-    logger::{start_logger, LevelFilter as LogLevelFilter, Logger, LoggerConfig, StdoutLog},
+    loading::{LoadedAssets, LoadingState},
+    logger::{
+        start_logger, LevelFilter as LogLevelFilter, LogFilters, Logger, LoggerConfig,
+        LoggerHandle, StdoutLog,
+    },
+    replay::{
+        read_recording, Desync, ReplayHeader, ReplayMode, ReplayStatus, ReplayTick, ReplayWriter,
+    },
     state::{
-        EmptyState, EmptyTrans, SimpleState, SimpleTrans, State, StateData, StateMachine, Trans,
-        TransEvent,
+        EmptyState, EmptyTrans, SimpleState, SimpleTrans, SimpleTransQueue, State, StateData,
+        StateMachine, StatePayload, StateStack, Trans, TransEvent, TransQueue,
     },
-    state_event::{StateEvent, StateEventReader},
+    state_event::{StateEvent, StateEventReader, StateTransition},
+    thread_pool::{ThreadPoolConfig, ThreadPriority},
 };
 
 /// Convenience alias for use in main functions that uses Amethyst.
@@ -101,7 +127,15 @@ pub mod prelude;
 
 mod app;
 mod callback_queue;
+mod command_buffer;
+mod crash;
+mod dependency_graph;
 mod game_data;
+#[cfg(feature = "hot_code")]
+mod hot_code;
+mod loading;
 mod logger;
+mod replay;
 mod state;
 mod state_event;
+mod thread_pool;
@@ -0,0 +1,334 @@
+//! A reusable state loading assets before the game starts.
+
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    marker::PhantomData,
+};
+
+use log::error;
+use serde::Deserialize;
+
+use crate::{
+    assets::{
+        Asset, AssetStorage, Completion, Format, Handle, Loader, Prefab, ProgressCounter, RonFormat,
+    },
+    core::TransformHierarchy,
+    ecs::prelude::{Entity, World, WriteStorage},
+    ui::{UiCreator, UiFinder, UiText},
+    GameData, SimpleState, SimpleTrans, State, StateData, StateEvent, StatePayload, Trans,
+};
+
+/// The handles of the assets loaded by a `LoadingState`, by name.
+#[derive(Default)]
+pub struct LoadedAssets {
+    handles: HashMap<(TypeId, String), Box<dyn Any + Send + Sync>>,
+}
+
+impl LoadedAssets {
+    /// The handle of the asset of type `A` loaded from `name`.
+    pub fn get<A: Asset>(&self, name: &str) -> Option<Handle<A>> {
+        self.handles
+            .get(&(TypeId::of::<A>(), name.to_owned()))
+            .and_then(|handle| handle.downcast_ref::<Handle<A>>())
+            .cloned()
+    }
+
+    /// The handle of the prefab loaded from `name`.
+    pub fn prefab<T: Send + Sync + 'static>(&self, name: &str) -> Option<Handle<Prefab<T>>> {
+        self.get::<Prefab<T>>(name)
+    }
+
+    fn insert<A: Asset>(&mut self, name: String, handle: Handle<A>) {
+        self.handles
+            .insert((TypeId::of::<A>(), name), Box::new(handle));
+    }
+}
+
+/// An asset to load, type erased so that the assets of a `LoadingState` can have any type.
+trait Load {
+    fn load(&mut self, world: &World, progress: &mut ProgressCounter, loaded: &mut LoadedAssets);
+}
+
+struct AssetLoad<A, F: Format<A>>
+where
+    A: Asset,
+{
+    name: String,
+    format: Option<(F, F::Options)>,
+    marker: PhantomData<A>,
+}
+
+impl<A, F> Load for AssetLoad<A, F>
+where
+    A: Asset,
+    F: Format<A>,
+{
+    fn load(&mut self, world: &World, progress: &mut ProgressCounter, loaded: &mut LoadedAssets) {
+        if let Some((format, options)) = self.format.take() {
+            let handle = world.read_resource::<Loader>().load(
+                self.name.clone(),
+                format,
+                options,
+                progress,
+                &world.read_resource::<AssetStorage<A>>(),
+            );
+            loaded.insert(self.name.clone(), handle);
+        }
+    }
+}
+
+/// A state loading a list of assets and prefabs, then switching to the next state.
+///
+/// The next state gets the `LoadedAssets` as the payload of `on_start_with`. An optional UI shows
+/// the progress in percent in one of its texts, and is removed once loading is done. If an asset
+/// fails to load, the errors are logged and the application quits.
+///
+/// ```rust,ignore
+/// let loading = LoadingState::new(Game)
+///     .with_prefab::<MyPrefabData>("prefab/level.ron")
+///     .with_asset::<Texture, _>("texture/logo.png", PngFormat, TextureMetadata::srgb())
+///     .with_progress_ui("ui/loading.ron", "progress");
+///
+/// impl SimpleState for Game {
+///     fn on_start_with(&mut self, data: StateData<'_, GameData<'_, '_>>, payload: StatePayload) {
+///         let assets = payload.downcast::<LoadedAssets>().unwrap();
+///         let level = assets.prefab::<MyPrefabData>("prefab/level.ron").unwrap();
+///         data.world.create_entity().with(level).build();
+///     }
+/// }
+/// ```
+pub struct LoadingState<T> {
+    next: Option<T>,
+    loads: Vec<Box<dyn Load>>,
+    loaded: LoadedAssets,
+    progress: ProgressCounter,
+    ui: Option<(String, String)>,
+    ui_root: Option<Entity>,
+}
+
+impl<T> LoadingState<T>
+where
+    T: State<GameData<'static, 'static>, StateEvent> + 'static,
+{
+    /// Creates a state switching to `next` once its assets are loaded.
+    pub fn new(next: T) -> Self {
+        LoadingState {
+            next: Some(next),
+            loads: Vec::new(),
+            loaded: LoadedAssets::default(),
+            progress: ProgressCounter::new(),
+            ui: None,
+            ui_root: None,
+        }
+    }
+
+    /// Loads the asset `name` with the given format.
+    pub fn with_asset<A, F>(
+        mut self,
+        name: impl Into<String>,
+        format: F,
+        options: F::Options,
+    ) -> Self
+    where
+        A: Asset,
+        F: Format<A>,
+    {
+        self.loads.push(Box::new(AssetLoad::<A, F> {
+            name: name.into(),
+            format: Some((format, options)),
+            marker: PhantomData,
+        }));
+        self
+    }
+
+    /// Loads the prefab `name` from a `ron` file.
+    pub fn with_prefab<P>(self, name: impl Into<String>) -> Self
+    where
+        P: for<'de> Deserialize<'de> + Send + Sync + 'static,
+    {
+        self.with_asset::<Prefab<P>, _>(name, RonFormat, ())
+    }
+
+    /// Shows the UI loaded from `ui` while loading, writing the progress to the text of the
+    /// `UiTransform` with the id `text_id`.
+    pub fn with_progress_ui(mut self, ui: impl Into<String>, text_id: impl Into<String>) -> Self {
+        self.ui = Some((ui.into(), text_id.into()));
+        self
+    }
+
+    /// The progress of the loading.
+    pub fn progress(&self) -> &ProgressCounter {
+        &self.progress
+    }
+
+    fn show_progress(&self, world: &mut World) {
+        let text_id = match self.ui {
+            Some((_, ref text_id)) => text_id,
+            None => return,
+        };
+        let percent = self.progress.num_finished() * 100 / self.progress.num_assets().max(1);
+        let progress = format!("{}%", percent);
+        world.exec(
+            |(finder, mut texts): (UiFinder<'_>, WriteStorage<'_, UiText>)| {
+                if let Some(text) = finder
+                    .find(text_id)
+                    .and_then(|entity| texts.get_mut(entity))
+                {
+                    if text.text != progress {
+                        text.text = progress;
+                    }
+                }
+            },
+        );
+    }
+}
+
+impl<T> SimpleState for LoadingState<T>
+where
+    T: State<GameData<'static, 'static>, StateEvent> + 'static,
+{
+    fn on_start(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        let world = data.world;
+        if let Some((ref ui, _)) = self.ui {
+            self.ui_root =
+                Some(world.exec(|mut creator: UiCreator<'_>| creator.create(ui.as_str(), ())));
+        }
+        for load in &mut self.loads {
+            load.load(world, &mut self.progress, &mut self.loaded);
+        }
+    }
+
+    fn on_stop(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        if let Some(root) = self.ui_root.take() {
+            let despawned = data
+                .world
+                .exec(|hierarchy: TransformHierarchy<'_>| hierarchy.despawn_recursive(root));
+            if let Err(err) = despawned {
+                error!("Failed to remove the loading UI: {}", err);
+            }
+        }
+    }
+
+    fn update(&mut self, data: &mut StateData<'_, GameData<'_, '_>>) -> SimpleTrans {
+        self.show_progress(data.world);
+        match self.progress.complete() {
+            Completion::Loading => Trans::None,
+            Completion::Complete => match self.next.take() {
+                Some(next) => {
+                    let loaded = std::mem::replace(&mut self.loaded, LoadedAssets::default());
+                    Trans::SwitchWith(Box::new(next), StatePayload::new(loaded))
+                }
+                None => Trans::None,
+            },
+            Completion::Failed => {
+                for err in self.progress.errors() {
+                    error!(
+                        "Failed to load asset {} of type {}: {}",
+                        err.asset_name, err.asset_type_name, err.error
+                    );
+                }
+                Trans::Quit
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, thread, time::Duration};
+
+    use rayon::ThreadPoolBuilder;
+
+    use super::*;
+    use crate::{
+        assets::{ProcessingState, SimpleFormat, Source},
+        ecs::prelude::{DispatcherBuilder, VecStorage},
+        error::{format_err, Error},
+    };
+
+    struct Text(String);
+
+    impl Asset for Text {
+        const NAME: &'static str = "Text";
+        type Data = String;
+        type HandleStorage = VecStorage<Handle<Self>>;
+    }
+
+    #[derive(Clone)]
+    struct TextFormat;
+
+    impl SimpleFormat<Text> for TextFormat {
+        const NAME: &'static str = "TEXT";
+        type Options = ();
+
+        fn import(&self, bytes: Vec<u8>, _: ()) -> Result<String, Error> {
+            Ok(String::from_utf8_lossy(&bytes).into_owned())
+        }
+    }
+
+    // A source with only the file `greeting.txt`.
+    struct Greeting;
+
+    impl Source for Greeting {
+        fn modified(&self, _: &str) -> Result<u64, Error> {
+            Ok(0)
+        }
+
+        fn load(&self, path: &str) -> Result<Vec<u8>, Error> {
+            match path {
+                "greeting.txt" => Ok(b"hello".to_vec()),
+                _ => Err(format_err!("{} doesn't exist", path)),
+            }
+        }
+    }
+
+    struct Next;
+
+    impl SimpleState for Next {}
+
+    // Runs the state until it loaded its assets, returning the transition it made.
+    fn load(mut state: LoadingState<Next>, world: &mut World) -> SimpleTrans {
+        let pool = Arc::new(ThreadPoolBuilder::new().num_threads(1).build().unwrap());
+        world.add_resource(Loader::with_default_source(Greeting, pool.clone()));
+        world.add_resource(AssetStorage::<Text>::new());
+        let mut data = GameData::new(DispatcherBuilder::new().build());
+        state.on_start(StateData::new(world, &mut data));
+        for _ in 0..500 {
+            world.write_resource::<AssetStorage<Text>>().process(
+                |text| Ok(ProcessingState::Loaded(Text(text))),
+                0,
+                &pool,
+                None,
+            );
+            match state.update(&mut StateData::new(world, &mut data)) {
+                Trans::None => thread::sleep(Duration::from_millis(10)),
+                trans => return trans,
+            }
+        }
+        panic!("The assets weren't loaded");
+    }
+
+    #[test]
+    fn the_loaded_assets_are_passed_to_the_next_state() {
+        let mut world = World::new();
+        let state = LoadingState::new(Next).with_asset::<Text, _>("greeting.txt", TextFormat, ());
+        let loaded = match load(state, &mut world) {
+            Trans::SwitchWith(_, payload) => payload.downcast::<LoadedAssets>().ok().unwrap(),
+            _ => panic!("The state didn't switch to the next one"),
+        };
+        let handle = loaded.get::<Text>("greeting.txt").unwrap();
+        let storage = world.read_resource::<AssetStorage<Text>>();
+        assert_eq!(storage.get(&handle).map(|text| &text.0[..]), Some("hello"));
+    }
+
+    #[test]
+    fn failing_to_load_quits() {
+        let mut world = World::new();
+        let state = LoadingState::new(Next).with_asset::<Text, _>("missing.txt", TextFormat, ());
+        match load(state, &mut world) {
+            Trans::Quit => {}
+            _ => panic!("The state didn't quit"),
+        }
+    }
+}
@@ -1,9 +1,17 @@
 pub use log::LevelFilter;
 
-use log::debug;
+use log::{debug, Metadata};
 use serde::{Deserialize, Serialize};
 
-use std::{env, io, path::PathBuf, str::FromStr};
+use std::{
+    collections::BTreeMap,
+    env, io,
+    path::PathBuf,
+    str::FromStr,
+    sync::{Arc, RwLock},
+};
+
+use crate::core::LogBuffer;
 
 /// An enum that contains options for logging to the terminal.
 #[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
@@ -29,6 +37,16 @@ pub struct LoggerConfig {
     pub allow_env_override: bool,
     /// Sets a different level for gfx_device_gl if Some
     pub log_gfx_device_level: Option<LevelFilter>,
+    /// Sets the levels of modules, and of their submodules, over the `level_filter`.
+    #[serde(default)]
+    pub module_levels: BTreeMap<String, LevelFilter>,
+    /// The number of recent records kept by the `LogBuffer` of the logger, none if it's 0.
+    #[serde(default = "default_buffer_size")]
+    pub buffer_size: usize,
+}
+
+fn default_buffer_size() -> usize {
+    500
 }
 
 impl Default for LoggerConfig {
@@ -39,10 +57,144 @@ impl Default for LoggerConfig {
             log_file: None,
             allow_env_override: true,
             log_gfx_device_level: Some(LevelFilter::Warn),
+            module_levels: BTreeMap::new(),
+            buffer_size: default_buffer_size(),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Levels {
+    default: LevelFilter,
+    modules: BTreeMap<String, LevelFilter>,
+    started: bool,
+}
+
+/// The levels of the logger, changed while the game runs, like from a console command:
+///
+/// ```rust,ignore
+/// world
+///     .read_resource::<LogFilters>()
+///     .set_module_level("amethyst_assets", LogLevelFilter::Debug);
+/// ```
+///
+/// A module takes the level of its closest parent with a level, or the default level. The clones
+/// of the filters share their levels.
+#[derive(Clone, Debug)]
+pub struct LogFilters {
+    levels: Arc<RwLock<Levels>>,
+}
+
+impl LogFilters {
+    /// Creates filters at the default `level`.
+    pub fn new(level: LevelFilter) -> Self {
+        LogFilters {
+            levels: Arc::new(RwLock::new(Levels {
+                default: level,
+                modules: BTreeMap::new(),
+                started: false,
+            })),
+        }
+    }
+
+    /// The default level, of the modules without a level.
+    pub fn level(&self) -> LevelFilter {
+        self.levels
+            .read()
+            .map_or(LevelFilter::Off, |levels| levels.default)
+    }
+
+    /// Sets the default level.
+    pub fn set_level(&self, level: LevelFilter) {
+        if let Ok(mut levels) = self.levels.write() {
+            levels.default = level;
+            update_max_level(&levels);
+        }
+    }
+
+    /// Sets the level of a module and of its submodules without a level.
+    pub fn set_module_level<S: Into<String>>(&self, module: S, level: LevelFilter) {
+        if let Ok(mut levels) = self.levels.write() {
+            levels.modules.insert(module.into(), level);
+            update_max_level(&levels);
+        }
+    }
+
+    /// Removes the level of a module, which takes the level of its parent again.
+    pub fn remove_module_level(&self, module: &str) {
+        if let Ok(mut levels) = self.levels.write() {
+            levels.modules.remove(module);
+            update_max_level(&levels);
+        }
+    }
+
+    /// The modules with a level, and their levels.
+    pub fn module_levels(&self) -> Vec<(String, LevelFilter)> {
+        self.levels
+            .read()
+            .map(|levels| {
+                levels
+                    .modules
+                    .iter()
+                    .map(|(module, &level)| (module.clone(), level))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// The level of the records of a target, a module path.
+    pub fn level_for(&self, target: &str) -> LevelFilter {
+        let levels = match self.levels.read() {
+            Ok(levels) => levels,
+            Err(_) => return LevelFilter::Off,
+        };
+        let mut module = target;
+        loop {
+            if let Some(&level) = levels.modules.get(module) {
+                return level;
+            }
+            match module.rfind("::") {
+                Some(index) => module = &module[..index],
+                None => return levels.default,
+            }
+        }
+    }
+
+    /// Whether the records of a target at a level are logged.
+    pub fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.level() <= self.level_for(metadata.target())
+    }
+
+    fn start(&self) {
+        if let Ok(mut levels) = self.levels.write() {
+            levels.started = true;
+            update_max_level(&levels);
         }
     }
 }
 
+// The `log` macros skip the records over the max level, so it follows the most verbose level once
+// the logger is started.
+fn update_max_level(levels: &Levels) {
+    if levels.started {
+        let max = levels
+            .modules
+            .values()
+            .cloned()
+            .fold(levels.default, Ord::max);
+        log::set_max_level(max);
+    }
+}
+
+/// The parts of a started logger the game changes and reads while it runs, to add as resources.
+#[derive(Clone, Debug)]
+pub struct LoggerHandle {
+    /// The levels of the logger.
+    pub filters: LogFilters,
+    /// The recent records, for the in-game log views like the `UiLogPanel`.
+    pub buffer: LogBuffer,
+}
+
 /// Allows the creation of a logger with a set of custom configurations. If no custom configuration
 /// is required [`start_logger`] can be used instead.
 ///
@@ -55,6 +207,8 @@ impl Default for LoggerConfig {
 /// ```
 pub struct Logger {
     dispatch: fern::Dispatch,
+    filters: LogFilters,
+    buffer: Option<LogBuffer>,
 }
 
 impl Logger {
@@ -67,7 +221,11 @@ impl Logger {
                 message = message,
             ))
         });
-        Logger { dispatch }
+        Logger {
+            dispatch,
+            filters: LogFilters::new(LevelFilter::Info),
+            buffer: None,
+        }
     }
 
     /// Create a new Logger from [`LoggerConfig`]
@@ -77,7 +235,13 @@ impl Logger {
         }
 
         let mut logger = Logger::new();
-        logger.dispatch = logger.dispatch.level(config.level_filter);
+        logger.filters.set_level(config.level_filter);
+        for (module, level) in config.module_levels {
+            logger.filters.set_module_level(module, level);
+        }
+        if config.buffer_size > 0 {
+            logger.buffer = Some(LogBuffer::new(config.buffer_size));
+        }
 
         match config.stdout {
             StdoutLog::Plain => logger.dispatch = logger.dispatch.chain(io::stdout()),
@@ -90,9 +254,9 @@ impl Logger {
         }
 
         if let Some(log_gfx_device_level) = config.log_gfx_device_level {
-            logger.dispatch = logger
-                .dispatch
-                .level_for("gfx_device_gl", log_gfx_device_level);
+            logger
+                .filters
+                .set_module_level("gfx_device_gl", log_gfx_device_level);
         }
 
         if let Some(path) = config.log_file {
@@ -107,19 +271,45 @@ impl Logger {
 
     /// Set individual log levels for modules.
     pub fn level_for<T: Into<std::borrow::Cow<'static, str>>>(
-        mut self,
+        self,
         module: T,
         level: LevelFilter,
     ) -> Self {
-        self.dispatch = self.dispatch.level_for(module, level);
+        self.filters
+            .set_module_level(module.into().into_owned(), level);
         self
     }
 
-    /// Starts [`Logger`] by consuming it.
-    pub fn start(self) {
-        self.dispatch.apply().unwrap_or_else(|_| {
-            debug!("Global logger already set, default Amethyst logger will not be used")
-        });
+    /// The levels of the logger, which can be changed after it's started.
+    pub fn filters(&self) -> LogFilters {
+        self.filters.clone()
+    }
+
+    /// Sends the log records to an additional output, like the `ConsoleLogger` of the ui console.
+    pub fn chain(mut self, output: Box<dyn log::Log>) -> Self {
+        self.dispatch = self.dispatch.chain(output);
+        self
+    }
+
+    /// Starts [`Logger`] by consuming it, returning the resources changing its levels and keeping
+    /// its recent records.
+    pub fn start(self) -> LoggerHandle {
+        let filters = self.filters.clone();
+        let mut dispatch = fern::Dispatch::new()
+            .filter(move |metadata| filters.enabled(metadata))
+            .chain(self.dispatch);
+        // The buffer keeps the records as they were logged, without the format of the outputs.
+        if let Some(ref buffer) = self.buffer {
+            dispatch = dispatch.chain(Box::new(buffer.clone()) as Box<dyn log::Log>);
+        }
+        match dispatch.apply() {
+            Ok(()) => self.filters.start(),
+            Err(_) => debug!("Global logger already set, default Amethyst logger will not be used"),
+        }
+        LoggerHandle {
+            filters: self.filters,
+            buffer: self.buffer.unwrap_or_else(|| LogBuffer::new(0)),
+        }
     }
 }
 
@@ -142,8 +332,19 @@ impl Logger {
 ///     * "trace" everything
 /// * AMETHYST_LOG_FILE_PATH - if set, enables logging to the file at the path
 ///     * the value is expected to be a path to the logging file
-pub fn start_logger(config: LoggerConfig) {
-    Logger::from_config(config).start();
+///
+/// The returned handle changes the levels of the logger while the game runs, and keeps its recent
+/// records for the in-game log views; both are added as resources:
+///
+/// ```rust,ignore
+/// let log = amethyst::start_logger(Default::default());
+/// let game = Application::build("assets/", GameState)?
+///     .with_resource(log.filters)
+///     .with_resource(log.buffer)
+///     .build(game_data)?;
+/// ```
+pub fn start_logger(config: LoggerConfig) -> LoggerHandle {
+    Logger::from_config(config).start()
 }
 
 fn env_var_override(config: &mut LoggerConfig) {
@@ -195,4 +396,27 @@ mod tests {
 
         assert_eq!(config.stdout, StdoutLog::Plain);
     }
+
+    #[test]
+    fn modules_take_the_level_of_their_parent() {
+        let filters = LogFilters::new(LevelFilter::Warn);
+        filters.set_module_level("amethyst_assets", LevelFilter::Debug);
+        filters.set_module_level("amethyst_assets::storage", LevelFilter::Error);
+
+        assert_eq!(
+            filters.level_for("amethyst_assets::loader"),
+            LevelFilter::Debug
+        );
+        assert_eq!(
+            filters.level_for("amethyst_assets::storage::x"),
+            LevelFilter::Error
+        );
+        assert_eq!(filters.level_for("amethyst_assetsx"), LevelFilter::Warn);
+
+        filters.remove_module_level("amethyst_assets");
+        assert_eq!(
+            filters.level_for("amethyst_assets::loader"),
+            LevelFilter::Warn
+        );
+    }
 }
@@ -3,12 +3,14 @@
 pub use crate::{
     app::{Application, ApplicationBuilder, CoreApplication},
     callback_queue::{Callback, CallbackQueue},
+    command_buffer::CommandBuffer,
     config::Config,
     core::{SystemExt, WithNamed},
     ecs::prelude::{Builder, World},
     game_data::{DataInit, GameData, GameDataBuilder},
     state::{
-        EmptyState, EmptyTrans, SimpleState, SimpleTrans, State, StateData, Trans, TransEvent,
+        EmptyState, EmptyTrans, SimpleState, SimpleTrans, SimpleTransQueue, State, StateData,
+        StatePayload, Trans, TransEvent, TransQueue,
     },
     state_event::StateEvent,
 };
@@ -0,0 +1,457 @@
+//! Recording the sessions of the game, and replaying them deterministically.
+
+use std::{
+    cell::RefCell,
+    fs::File,
+    hash::Hash,
+    io::{BufRead, BufReader, BufWriter, Write as IoWrite},
+    path::{Path, PathBuf},
+    rc::Rc,
+    time::Duration,
+};
+
+use log::error;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    core::{
+        deterministic::FrameHasher,
+        shrev::{EventChannel, ReaderId},
+        timing::{duration_to_nanos, nanos_to_duration, Time},
+    },
+    ecs::prelude::World,
+    error::{format_err, Error},
+    input::{InputEvent, InputHandler, InputState},
+    renderer::ScreenDimensions,
+    state::{Trans, TransEvent},
+};
+
+// The version of the format of the recordings, in their first line.
+const VERSION: u32 = 1;
+
+/// The first line of a recording, with what the replay needs to run like the session.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReplayHeader {
+    /// The version of the format of the recording.
+    pub version: u32,
+    /// The seed of the `GameRng`.
+    pub seed: u64,
+    /// The fixed step of the `Time`, in nanoseconds.
+    pub fixed_step: u64,
+}
+
+/// A tick of a recording: the input the game saw during the frame and the hash of the world at
+/// its end.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ReplayTick<AC> {
+    /// The state of the `InputHandler`.
+    pub input: InputState,
+    /// The `InputEvent`s written during the frame.
+    pub events: Vec<InputEvent<AC>>,
+    /// The hash of the world, by the `FrameHasher`.
+    pub hash: u64,
+}
+
+/// A tick where the world of a replay differs from the world of its recording.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Desync {
+    /// The tick, from 0.
+    pub tick: u64,
+    /// The hash of the world in the recording.
+    pub recorded: u64,
+    /// The hash of the world in the replay.
+    pub replayed: u64,
+}
+
+/// Whether the game is recorded or replayed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReplayMode {
+    /// The session is recorded.
+    Recording,
+    /// A recording is replayed.
+    Replaying,
+}
+
+/// The resource following a recording or a replay, added by `ApplicationBuilder::with_recording`
+/// and `with_replay`.
+#[derive(Clone, Debug)]
+pub struct ReplayStatus {
+    mode: ReplayMode,
+    tick: u64,
+    length: Option<u64>,
+    desyncs: Vec<Desync>,
+}
+
+impl ReplayStatus {
+    /// Whether the game is recorded or replayed.
+    pub fn mode(&self) -> ReplayMode {
+        self.mode
+    }
+
+    /// The number of ticks recorded or replayed.
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// The number of ticks of the replayed recording.
+    pub fn length(&self) -> Option<u64> {
+        self.length
+    }
+
+    /// Whether the replay reached the end of the recording.
+    pub fn is_finished(&self) -> bool {
+        self.length.map_or(false, |length| self.tick >= length)
+    }
+
+    /// The ticks where the replay differed from the recording, the first one being where the
+    /// simulation stopped being deterministic.
+    pub fn desyncs(&self) -> &[Desync] {
+        &self.desyncs
+    }
+}
+
+/// Writes the header and the ticks of a recording, one JSON line each.
+pub struct ReplayWriter<W: IoWrite> {
+    out: W,
+}
+
+impl ReplayWriter<BufWriter<File>> {
+    /// Creates the recording at `path`, replacing it.
+    pub fn create<P: AsRef<Path>>(path: P, header: &ReplayHeader) -> Result<Self, Error> {
+        ReplayWriter::new(BufWriter::new(File::create(path)?), header)
+    }
+}
+
+impl<W: IoWrite> ReplayWriter<W> {
+    /// Starts a recording on `out`, with its header.
+    pub fn new(out: W, header: &ReplayHeader) -> Result<Self, Error> {
+        let mut writer = ReplayWriter { out };
+        writer.write_line(header)?;
+        Ok(writer)
+    }
+
+    /// Adds a tick to the recording.
+    pub fn write<AC: Serialize>(&mut self, tick: &ReplayTick<AC>) -> Result<(), Error> {
+        self.write_line(tick)?;
+        // Flushed every tick, so that the recording of a crashed session can be replayed.
+        self.out.flush()?;
+        Ok(())
+    }
+
+    fn write_line<T: Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        serde_json::to_writer(&mut self.out, value)?;
+        self.out.write_all(b"\n")?;
+        Ok(())
+    }
+}
+
+/// Reads a recording written by a `ReplayWriter`.
+pub fn read_recording<R, AC>(input: R) -> Result<(ReplayHeader, Vec<ReplayTick<AC>>), Error>
+where
+    R: BufRead,
+    AC: DeserializeOwned,
+{
+    let mut lines = input.lines();
+    let header = match lines.next() {
+        Some(line) => serde_json::from_str::<ReplayHeader>(&line?)?,
+        None => return Err(format_err!("The recording is empty")),
+    };
+    if header.version != VERSION {
+        return Err(format_err!(
+            "The recording has the version {} of the format, not {}",
+            header.version,
+            VERSION
+        ));
+    }
+    let mut ticks = Vec::new();
+    for line in lines {
+        let line = line?;
+        if !line.is_empty() {
+            ticks.push(serde_json::from_str(&line)?);
+        }
+    }
+    Ok((header, ticks))
+}
+
+// The recorder, shared by the hook of the frames.
+struct Recorder<AC> {
+    path: PathBuf,
+    seed: u64,
+    writer: Option<ReplayWriter<BufWriter<File>>>,
+    reader: ReaderId<InputEvent<AC>>,
+    failed: bool,
+}
+
+/// Records the end of every frame, returning the hook of `ApplicationBuilder::with_recording`.
+pub(crate) fn recorder<AX, AC>(
+    world: &mut World,
+    path: &Path,
+    seed: u64,
+    hasher: FrameHasher,
+) -> impl FnMut(&mut World)
+where
+    AX: Hash + Eq + Clone + Send + Sync + 'static,
+    AC: Hash + Eq + Clone + Send + Sync + Serialize + 'static,
+{
+    world
+        .res
+        .entry()
+        .or_insert_with(EventChannel::<InputEvent<AC>>::new);
+    let reader = world
+        .write_resource::<EventChannel<InputEvent<AC>>>()
+        .register_reader();
+    world.add_resource(ReplayStatus {
+        mode: ReplayMode::Recording,
+        tick: 0,
+        length: None,
+        desyncs: Vec::new(),
+    });
+    let mut recorder = Recorder::<AC> {
+        path: path.into(),
+        seed,
+        writer: None,
+        reader,
+        failed: false,
+    };
+
+    move |world: &mut World| {
+        if recorder.failed {
+            return;
+        }
+        let events = {
+            let channel = world.read_resource::<EventChannel<InputEvent<AC>>>();
+            channel.read(&mut recorder.reader).cloned().collect()
+        };
+        let input = world
+            .res
+            .try_fetch::<InputHandler<AX, AC>>()
+            .map(|handler| handler.state())
+            .unwrap_or_default();
+        let tick = ReplayTick {
+            input,
+            events,
+            hash: hasher.hash(&world.res),
+        };
+
+        if let Err(err) = recorder.write(world, &tick) {
+            error!(
+                "Failed to record the session, it won't be recorded further: {}",
+                err
+            );
+            recorder.failed = true;
+            return;
+        }
+        world.write_resource::<ReplayStatus>().tick += 1;
+    }
+}
+
+impl<AC: Serialize> Recorder<AC> {
+    fn write(&mut self, world: &World, tick: &ReplayTick<AC>) -> Result<(), Error> {
+        // The header is written with the first tick, once the fixed step is set.
+        if self.writer.is_none() {
+            let header = ReplayHeader {
+                version: VERSION,
+                seed: self.seed,
+                fixed_step: duration_to_nanos(world.read_resource::<Time>().fixed_time()),
+            };
+            self.writer = Some(ReplayWriter::create(&self.path, &header)?);
+        }
+        self.writer
+            .as_mut()
+            .expect("Unreachable: The writer was just created")
+            .write(tick)
+    }
+}
+
+/// Reads the recording at `path`, returning its header.
+pub(crate) fn read_recording_file<AC>(
+    path: &Path,
+) -> Result<(ReplayHeader, Vec<ReplayTick<AC>>), Error>
+where
+    AC: DeserializeOwned,
+{
+    read_recording(BufReader::new(File::open(path)?))
+}
+
+/// Gives the recorded input to the game before every frame, and compares the hashes of the
+/// worlds after, returning the hooks of `ApplicationBuilder::with_replay`.
+pub(crate) fn replayer<AX, AC, T, E>(
+    world: &mut World,
+    ticks: Vec<ReplayTick<AC>>,
+    hasher: FrameHasher,
+) -> (impl FnMut(&mut World), impl FnMut(&mut World))
+where
+    AX: Hash + Eq + Clone + Send + Sync + 'static,
+    AC: Hash + Eq + Clone + Send + Sync + 'static,
+    T: 'static,
+    E: Send + Sync + 'static,
+{
+    // Replayed without a window, the input system still needs the dimensions of the screen.
+    if !world.res.has_value::<ScreenDimensions>() {
+        world.add_resource(ScreenDimensions::new(1, 1, 1.0));
+    }
+    world
+        .res
+        .entry()
+        .or_insert_with(EventChannel::<InputEvent<AC>>::new);
+    world.add_resource(ReplayStatus {
+        mode: ReplayMode::Replaying,
+        tick: 0,
+        length: Some(ticks.len() as u64),
+        desyncs: Vec::new(),
+    });
+    let ticks = Rc::new(RefCell::new(ticks));
+    let recorded = ticks.clone();
+
+    let before = move |world: &mut World| {
+        let tick = world.read_resource::<ReplayStatus>().tick as usize;
+        let mut ticks = ticks.borrow_mut();
+        let replayed = match ticks.get_mut(tick) {
+            Some(replayed) => replayed,
+            None => {
+                world
+                    .write_resource::<EventChannel<TransEvent<T, E>>>()
+                    .single_write(Box::new(|| Trans::Quit));
+                return;
+            }
+        };
+        if let Some(mut handler) = world.res.try_fetch_mut::<InputHandler<AX, AC>>() {
+            handler.set_replaying(true);
+            handler.set_state(replayed.input.clone());
+        }
+        // The events are only replayed once, so they are taken from the recording.
+        world
+            .write_resource::<EventChannel<InputEvent<AC>>>()
+            .drain_vec_write(&mut replayed.events);
+    };
+
+    let after = move |world: &mut World| {
+        let mut status = world.write_resource::<ReplayStatus>();
+        let tick = status.tick;
+        if let Some(replayed) = recorded.borrow().get(tick as usize) {
+            let hash = hasher.hash(&world.res);
+            if hash != replayed.hash {
+                if status.desyncs.is_empty() {
+                    error!(
+                        "The replay differs from its recording from the tick {}: the game isn't \
+                         deterministic",
+                        tick
+                    );
+                }
+                status.desyncs.push(Desync {
+                    tick,
+                    recorded: replayed.hash,
+                    replayed: hash,
+                });
+            }
+            status.tick += 1;
+        }
+    };
+
+    (before, after)
+}
+
+/// The fixed step of a recording.
+pub(crate) fn fixed_step(header: &ReplayHeader) -> Duration {
+    nanos_to_duration(header.fixed_step)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use super::*;
+
+    // Only the counter is hashed, so the replays differ from their recording where it does.
+    fn hasher() -> FrameHasher {
+        FrameHasher::new().with_resource::<u64>()
+    }
+
+    #[test]
+    fn recordings_are_read_back() {
+        let header = ReplayHeader {
+            version: VERSION,
+            seed: 7,
+            fixed_step: 16_666_667,
+        };
+        let tick = ReplayTick {
+            input: InputState::default(),
+            events: vec![InputEvent::ActionPressed("jump".to_string())],
+            hash: 42,
+        };
+
+        let mut out = Vec::new();
+        {
+            let mut writer = ReplayWriter::new(&mut out, &header).unwrap();
+            writer.write(&tick).unwrap();
+            writer.write(&tick).unwrap();
+        }
+        let (read_header, ticks) = read_recording::<_, String>(&out[..]).unwrap();
+        assert_eq!(read_header, header);
+        assert_eq!(ticks, vec![tick.clone(), tick]);
+        assert!(read_recording::<_, String>(&b""[..]).is_err());
+    }
+
+    #[test]
+    fn replays_flag_the_ticks_differing_from_the_recording() {
+        let path = env::temp_dir().join(format!("amethyst-{}-session.replay", process::id()));
+        let jump = InputEvent::ActionPressed("jump".to_string());
+        let mut world = World::new();
+        world.add_resource(Time::default());
+        world.add_resource(0u64);
+        {
+            let mut record = recorder::<String, String>(&mut world, &path, 7, hasher());
+            for frame in 0..3 {
+                *world.write_resource::<u64>() = frame;
+                if frame == 0 {
+                    world
+                        .write_resource::<EventChannel<InputEvent<String>>>()
+                        .single_write(jump.clone());
+                }
+                record(&mut world);
+            }
+        }
+        assert_eq!(world.read_resource::<ReplayStatus>().tick(), 3);
+        let (header, ticks) = read_recording_file::<String>(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(header.seed, 7);
+        assert_eq!(ticks.len(), 3);
+
+        let mut world = World::new();
+        world.add_resource(EventChannel::<TransEvent<(), ()>>::new());
+        world.add_resource(0u64);
+        let (mut before, mut after) =
+            replayer::<String, String, (), ()>(&mut world, ticks, hasher());
+        let mut events = world
+            .write_resource::<EventChannel<InputEvent<String>>>()
+            .register_reader();
+        let mut quit = world
+            .write_resource::<EventChannel<TransEvent<(), ()>>>()
+            .register_reader();
+        for frame in 0..3 {
+            before(&mut world);
+            // The second frame isn't deterministic, the replay giving another counter.
+            *world.write_resource::<u64>() = if frame == 1 { 5 } else { frame };
+            after(&mut world);
+        }
+
+        let replayed = world
+            .read_resource::<EventChannel<InputEvent<String>>>()
+            .read(&mut events)
+            .cloned()
+            .collect::<Vec<_>>();
+        assert_eq!(replayed, vec![jump]);
+        {
+            let status = world.read_resource::<ReplayStatus>();
+            assert!(status.is_finished());
+            let desyncs = status.desyncs().iter().map(|desync| desync.tick);
+            assert_eq!(desyncs.collect::<Vec<_>>(), vec![1]);
+        }
+        before(&mut world);
+        let transitions = world
+            .read_resource::<EventChannel<TransEvent<(), ()>>>()
+            .read(&mut quit)
+            .count();
+        assert_eq!(transitions, 1);
+    }
+}
@@ -4,9 +4,17 @@ use amethyst_input::is_close_requested;
 
 use derivative::Derivative;
 
-use crate::{ecs::prelude::World, GameData, StateEvent};
-
-use std::fmt::{Display, Formatter, Result as FmtResult};
+use crate::{
+    ecs::prelude::{Dispatcher, DispatcherBuilder, World},
+    shrev::EventChannel,
+    state_event::StateTransition,
+    GameData, StateEvent,
+};
+
+use std::{
+    any::Any,
+    fmt::{Debug, Display, Formatter, Result as FmtResult},
+};
 
 /// Error type for errors occurring in StateMachine
 #[derive(Debug)]
@@ -52,14 +60,71 @@ pub enum Trans<T, E> {
     /// Remove the active state and resume the next state on the stack or stop
     /// if there are none.
     Pop,
+    /// Remove the active state and resume the next state on the stack, passing it a result in
+    /// `on_resume_with`, or stop if there are none.
+    PopWith(StatePayload),
     /// Pause the active state and push a new state onto the stack.
     Push(Box<dyn State<T, E>>),
+    /// Pause the active state and push a new state onto the stack, passing it a payload in
+    /// `on_start_with`.
+    PushWith(Box<dyn State<T, E>>, StatePayload),
     /// Remove the current state on the stack and insert a different one.
     Switch(Box<dyn State<T, E>>),
+    /// Remove the current state on the stack and insert a different one, passing it a payload in
+    /// `on_start_with`.
+    SwitchWith(Box<dyn State<T, E>>, StatePayload),
     /// Stop and remove all states and shut down the engine.
     Quit,
 }
 
+/// A value of any type passed between states by `Trans::PushWith`, `Trans::SwitchWith` and
+/// `Trans::PopWith`, like the level picked in a level selection state.
+///
+/// # Example:
+/// ```rust, ignore
+/// // In the level selection state:
+/// Trans::PopWith(StatePayload::new(LevelSelected(3)))
+///
+/// // In the resumed state:
+/// fn on_resume_with(&mut self, data: StateData<'_, GameData<'_, '_>>, result: StatePayload) {
+///     if let Some(LevelSelected(level)) = result.downcast_ref() {
+///         // ...
+///     }
+/// }
+/// ```
+pub struct StatePayload(Box<dyn Any + Send>);
+
+impl StatePayload {
+    /// Wraps a value to pass it to another state.
+    pub fn new<P: Any + Send>(payload: P) -> Self {
+        StatePayload(Box::new(payload))
+    }
+
+    /// Checks whether the payload is of type `P`.
+    pub fn is<P: Any>(&self) -> bool {
+        self.0.is::<P>()
+    }
+
+    /// Gets a reference to the payload if it is of type `P`.
+    pub fn downcast_ref<P: Any>(&self) -> Option<&P> {
+        self.0.downcast_ref()
+    }
+
+    /// Takes the payload if it is of type `P`, or gives it back.
+    pub fn downcast<P: Any>(self) -> Result<P, StatePayload> {
+        self.0
+            .downcast()
+            .map(|payload| *payload)
+            .map_err(StatePayload)
+    }
+}
+
+impl Debug for StatePayload {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> FmtResult {
+        fmt.write_str("StatePayload { .. }")
+    }
+}
+
 /// Event queue to trigger state `Trans` from other places than a `State`'s methods.
 /// # Example:
 /// ```rust, ignore
@@ -69,6 +134,56 @@ pub enum Trans<T, E> {
 /// Transitions will be executed sequentially by Amethyst's `CoreApplication` update loop.
 pub type TransEvent<T, E> = Box<dyn Fn() -> Trans<T, E> + Send + Sync + 'static>;
 
+/// A queue of state transitions requested by systems, so that gameplay code can change the state
+/// without routing a custom event through `handle_event`. The transitions are applied in order
+/// after the systems ran.
+///
+/// # Example:
+/// ```rust, ignore
+/// fn run(&mut self, (health, mut queue): (ReadStorage<'a, Health>, Write<'a, SimpleTransQueue>)) {
+///     if player_died(&health) {
+///         queue.push(|| Trans::Switch(Box::new(GameOver)));
+///     }
+/// }
+/// ```
+pub struct TransQueue<T, E> {
+    queue: Vec<TransEvent<T, E>>,
+}
+
+impl<T, E> Default for TransQueue<T, E> {
+    fn default() -> Self {
+        TransQueue { queue: Vec::new() }
+    }
+}
+
+impl<T, E> TransQueue<T, E> {
+    /// Requests the transition created by `trans`.
+    pub fn push<F>(&mut self, trans: F)
+    where
+        F: Fn() -> Trans<T, E> + Send + Sync + 'static,
+    {
+        self.queue.push(Box::new(trans));
+    }
+
+    /// The number of requested transitions.
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Checks whether no transition was requested.
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// Forgets the requested transitions, e.g. when loading a save.
+    pub fn clear(&mut self) {
+        self.queue.clear();
+    }
+}
+
+/// A `TransQueue` made to be used with `SimpleState`.
+pub type SimpleTransQueue = TransQueue<GameData<'static, 'static>, StateEvent>;
+
 /// An empty `Trans`. Made to be used with `EmptyState`.
 pub type EmptyTrans = Trans<(), StateEvent>;
 
@@ -78,9 +193,34 @@ pub type SimpleTrans = Trans<GameData<'static, 'static>, StateEvent>;
 
 /// A trait which defines game states that can be used by the state machine.
 pub trait State<T, E: Send + Sync + 'static> {
+    /// The name of the state, reported in `StateTransition` events and by the `StateStack`
+    /// resource. Defaults to `"unnamed"`, the states override it to be told apart.
+    fn name(&self) -> &'static str {
+        "unnamed"
+    }
+
+    /// Builds the systems that only run while this state is the active state, after the state's
+    /// `update`. Called once before `on_start`, the systems are set up with the `World` and
+    /// dropped with the state.
+    ///
+    /// This is meant for systems of pause menus or minigames, which shouldn't run in other
+    /// states.
+    fn build_dispatcher(
+        &mut self,
+        _world: &mut World,
+    ) -> Option<DispatcherBuilder<'static, 'static>> {
+        None
+    }
+
     /// Executed when the game state begins.
     fn on_start(&mut self, _data: StateData<'_, T>) {}
 
+    /// Executed instead of `on_start` when the game state begins with a payload from
+    /// `Trans::PushWith` or `Trans::SwitchWith`. Calls `on_start` by default.
+    fn on_start_with(&mut self, data: StateData<'_, T>, _payload: StatePayload) {
+        self.on_start(data)
+    }
+
     /// Executed when the game state exits.
     fn on_stop(&mut self, _data: StateData<'_, T>) {}
 
@@ -90,6 +230,12 @@ pub trait State<T, E: Send + Sync + 'static> {
     /// Executed when the application returns to this game state once again.
     fn on_resume(&mut self, _data: StateData<'_, T>) {}
 
+    /// Executed instead of `on_resume` when the application returns to this game state with a
+    /// result from `Trans::PopWith`. Calls `on_resume` by default.
+    fn on_resume_with(&mut self, data: StateData<'_, T>, _result: StatePayload) {
+        self.on_resume(data)
+    }
+
     /// Executed on every frame before updating, for use in reacting to events.
     fn handle_event(&mut self, _data: StateData<'_, T>, _event: E) -> Trans<T, E> {
         Trans::None
@@ -122,9 +268,30 @@ pub trait State<T, E: Send + Sync + 'static> {
 
 /// An empty `State` trait. It contains no `StateData` or custom `StateEvent`.
 pub trait EmptyState {
+    /// The name of the state, reported in `StateTransition` events and by the `StateStack`
+    /// resource. Defaults to `"unnamed"`, the states override it to be told apart.
+    fn name(&self) -> &'static str {
+        "unnamed"
+    }
+
+    /// Builds the systems that only run while this state is the active state, after the state's
+    /// `update`. Called once before `on_start`.
+    fn build_dispatcher(
+        &mut self,
+        _world: &mut World,
+    ) -> Option<DispatcherBuilder<'static, 'static>> {
+        None
+    }
+
     /// Executed when the game state begins.
     fn on_start(&mut self, _data: StateData<'_, ()>) {}
 
+    /// Executed instead of `on_start` when the game state begins with a payload from
+    /// `Trans::PushWith` or `Trans::SwitchWith`. Calls `on_start` by default.
+    fn on_start_with(&mut self, data: StateData<'_, ()>, _payload: StatePayload) {
+        self.on_start(data)
+    }
+
     /// Executed when the game state exits.
     fn on_stop(&mut self, _data: StateData<'_, ()>) {}
 
@@ -134,6 +301,12 @@ pub trait EmptyState {
     /// Executed when the application returns to this game state once again.
     fn on_resume(&mut self, _data: StateData<'_, ()>) {}
 
+    /// Executed instead of `on_resume` when the application returns to this game state with a
+    /// result from `Trans::PopWith`. Calls `on_resume` by default.
+    fn on_resume_with(&mut self, data: StateData<'_, ()>, _result: StatePayload) {
+        self.on_resume(data)
+    }
+
     /// Executed on every frame before updating, for use in reacting to events.
     fn handle_event(&mut self, _data: StateData<'_, ()>, event: StateEvent) -> EmptyTrans {
         if let StateEvent::Window(event) = &event {
@@ -171,11 +344,29 @@ pub trait EmptyState {
 }
 
 impl<T: EmptyState> State<(), StateEvent> for T {
+    /// The name of the state.
+    fn name(&self) -> &'static str {
+        self.name()
+    }
+
+    /// Builds the systems that only run while this state is the active state.
+    fn build_dispatcher(
+        &mut self,
+        world: &mut World,
+    ) -> Option<DispatcherBuilder<'static, 'static>> {
+        self.build_dispatcher(world)
+    }
+
     /// Executed when the game state begins.
     fn on_start(&mut self, data: StateData<'_, ()>) {
         self.on_start(data)
     }
 
+    /// Executed instead of `on_start` when the game state begins with a payload.
+    fn on_start_with(&mut self, data: StateData<'_, ()>, payload: StatePayload) {
+        self.on_start_with(data, payload)
+    }
+
     /// Executed when the game state exits.
     fn on_stop(&mut self, data: StateData<'_, ()>) {
         self.on_stop(data)
@@ -191,6 +382,12 @@ impl<T: EmptyState> State<(), StateEvent> for T {
         self.on_resume(data)
     }
 
+    /// Executed instead of `on_resume` when the application returns to this game state with a
+    /// result.
+    fn on_resume_with(&mut self, data: StateData<'_, ()>, result: StatePayload) {
+        self.on_resume_with(data, result)
+    }
+
     /// Executed on every frame before updating, for use in reacting to events.
     fn handle_event(&mut self, data: StateData<'_, ()>, event: StateEvent) -> EmptyTrans {
         self.handle_event(data, event)
@@ -225,9 +422,30 @@ impl<T: EmptyState> State<(), StateEvent> for T {
 
 /// A simple `State` trait. It contains `GameData` as its `StateData` and no custom `StateEvent`.
 pub trait SimpleState {
+    /// The name of the state, reported in `StateTransition` events and by the `StateStack`
+    /// resource. Defaults to `"unnamed"`, the states override it to be told apart.
+    fn name(&self) -> &'static str {
+        "unnamed"
+    }
+
+    /// Builds the systems that only run while this state is the active state, after the state's
+    /// `update`. Called once before `on_start`.
+    fn build_dispatcher(
+        &mut self,
+        _world: &mut World,
+    ) -> Option<DispatcherBuilder<'static, 'static>> {
+        None
+    }
+
     /// Executed when the game state begins.
     fn on_start(&mut self, _data: StateData<'_, GameData<'_, '_>>) {}
 
+    /// Executed instead of `on_start` when the game state begins with a payload from
+    /// `Trans::PushWith` or `Trans::SwitchWith`. Calls `on_start` by default.
+    fn on_start_with(&mut self, data: StateData<'_, GameData<'_, '_>>, _payload: StatePayload) {
+        self.on_start(data)
+    }
+
     /// Executed when the game state exits.
     fn on_stop(&mut self, _data: StateData<'_, GameData<'_, '_>>) {}
 
@@ -237,6 +455,12 @@ pub trait SimpleState {
     /// Executed when the application returns to this game state once again.
     fn on_resume(&mut self, _data: StateData<'_, GameData<'_, '_>>) {}
 
+    /// Executed instead of `on_resume` when the application returns to this game state with a
+    /// result from `Trans::PopWith`. Calls `on_resume` by default.
+    fn on_resume_with(&mut self, data: StateData<'_, GameData<'_, '_>>, _result: StatePayload) {
+        self.on_resume(data)
+    }
+
     /// Executed on every frame before updating, for use in reacting to events.
     fn handle_event(
         &mut self,
@@ -280,11 +504,29 @@ pub trait SimpleState {
 impl<T: SimpleState> State<GameData<'static, 'static>, StateEvent> for T {
     //pub trait SimpleState<'a,'b>: State<GameData<'a,'b>,()> {
 
+    /// The name of the state.
+    fn name(&self) -> &'static str {
+        self.name()
+    }
+
+    /// Builds the systems that only run while this state is the active state.
+    fn build_dispatcher(
+        &mut self,
+        world: &mut World,
+    ) -> Option<DispatcherBuilder<'static, 'static>> {
+        self.build_dispatcher(world)
+    }
+
     /// Executed when the game state begins.
     fn on_start(&mut self, data: StateData<'_, GameData<'_, '_>>) {
         self.on_start(data)
     }
 
+    /// Executed instead of `on_start` when the game state begins with a payload.
+    fn on_start_with(&mut self, data: StateData<'_, GameData<'_, '_>>, payload: StatePayload) {
+        self.on_start_with(data, payload)
+    }
+
     /// Executed when the game state exits.
     fn on_stop(&mut self, data: StateData<'_, GameData<'_, '_>>) {
         self.on_stop(data)
@@ -300,6 +542,12 @@ impl<T: SimpleState> State<GameData<'static, 'static>, StateEvent> for T {
         self.on_resume(data)
     }
 
+    /// Executed instead of `on_resume` when the application returns to this game state with a
+    /// result.
+    fn on_resume_with(&mut self, data: StateData<'_, GameData<'_, '_>>, result: StatePayload) {
+        self.on_resume_with(data, result)
+    }
+
     /// Executed on every frame before updating, for use in reacting to events.
     fn handle_event(
         &mut self,
@@ -312,7 +560,9 @@ impl<T: SimpleState> State<GameData<'static, 'static>, StateEvent> for T {
     /// Executed repeatedly at stable, predictable intervals (1/60th of a second
     /// by default).
     fn fixed_update(&mut self, data: StateData<'_, GameData<'_, '_>>) -> SimpleTrans {
-        self.fixed_update(data)
+        let StateData { world, data } = data;
+        data.fixed_update(&world);
+        self.fixed_update(StateData::new(world, data))
     }
 
     /// Executed on every frame immediately, as fast as the engine will allow (taking into account the frame rate limit).
@@ -338,6 +588,29 @@ impl<T: SimpleState> State<GameData<'static, 'static>, StateEvent> for T {
     }
 }
 
+/// A read-only view of the state stack, kept up to date by the `StateMachine`.
+#[derive(Clone, Debug, Default)]
+pub struct StateStack {
+    states: Vec<&'static str>,
+}
+
+impl StateStack {
+    /// The names of the states on the stack, from the bottom to the active state.
+    pub fn states(&self) -> &[&'static str] {
+        &self.states
+    }
+
+    /// The name of the active state, `None` if the state machine isn't running.
+    pub fn active(&self) -> Option<&'static str> {
+        self.states.last().cloned()
+    }
+
+    /// The number of states on the stack.
+    pub fn depth(&self) -> usize {
+        self.states.len()
+    }
+}
+
 /// A simple stack-based state machine (pushdown automaton).
 #[derive(Derivative)]
 #[derivative(Debug)]
@@ -345,6 +618,9 @@ pub struct StateMachine<'a, T, E> {
     running: bool,
     #[derivative(Debug = "ignore")]
     state_stack: Vec<Box<dyn State<T, E> + 'a>>,
+    // The systems of the states on the stack.
+    #[derivative(Debug = "ignore")]
+    dispatchers: Vec<Option<Dispatcher<'static, 'static>>>,
 }
 
 impl<'a, T, E: Send + Sync + 'static> StateMachine<'a, T, E> {
@@ -353,6 +629,7 @@ impl<'a, T, E: Send + Sync + 'static> StateMachine<'a, T, E> {
         StateMachine {
             running: false,
             state_stack: vec![Box::new(initial_state)],
+            dispatchers: vec![None],
         }
     }
 
@@ -364,12 +641,19 @@ impl<'a, T, E: Send + Sync + 'static> StateMachine<'a, T, E> {
     /// Initializes the state machine.
     pub fn start(&mut self, data: StateData<'_, T>) -> Result<(), StateError> {
         if !self.running {
+            let StateData { world, data } = data;
             let state = self
                 .state_stack
                 .last_mut()
                 .ok_or(StateError::NoStatesPresent)?;
-            state.on_start(data);
+            let dispatcher = build_dispatcher(&mut **state, world);
+            if let Some(last) = self.dispatchers.last_mut() {
+                *last = dispatcher;
+            }
+            state.on_start(StateData { world, data });
+            let name = state.name();
             self.running = true;
+            self.announce(world, StateTransition::Pushed(name));
         }
         Ok(())
     }
@@ -414,6 +698,10 @@ impl<'a, T, E: Send + Sync + 'static> StateMachine<'a, T, E> {
             for state in self.state_stack.iter_mut() {
                 state.shadow_update(StateData { world, data });
             }
+            if let Some(Some(dispatcher)) = self.dispatchers.last_mut() {
+                dispatcher.dispatch(&world.res);
+                world.maintain();
+            }
 
             self.transition(trans, StateData { world, data });
         }
@@ -428,57 +716,98 @@ impl<'a, T, E: Send + Sync + 'static> StateMachine<'a, T, E> {
         if self.running {
             match request {
                 Trans::None => (),
-                Trans::Pop => self.pop(data),
-                Trans::Push(state) => self.push(state, data),
-                Trans::Switch(state) => self.switch(state, data),
+                Trans::Pop => self.pop(None, data),
+                Trans::PopWith(result) => self.pop(Some(result), data),
+                Trans::Push(state) => self.push(state, None, data),
+                Trans::PushWith(state, payload) => self.push(state, Some(payload), data),
+                Trans::Switch(state) => self.switch(state, None, data),
+                Trans::SwitchWith(state, payload) => self.switch(state, Some(payload), data),
                 Trans::Quit => self.stop(data),
             }
         }
     }
 
     /// Removes the current state on the stack and inserts a different one.
-    fn switch(&mut self, state: Box<dyn State<T, E>>, data: StateData<'_, T>) {
+    fn switch(
+        &mut self,
+        state: Box<dyn State<T, E>>,
+        payload: Option<StatePayload>,
+        data: StateData<'_, T>,
+    ) {
         if self.running {
             let StateData { world, data } = data;
-            if let Some(mut state) = self.state_stack.pop() {
+            let from = self.state_stack.pop().map(|mut state| {
                 state.on_stop(StateData { world, data });
-            }
-
-            self.state_stack.push(state);
-
-            //State was just pushed, thus pop will always succeed
-            let state = self.state_stack.last_mut().unwrap();
-            state.on_start(StateData { world, data });
+                self.dispatchers.pop();
+                state.name()
+            });
+            let to = state.name();
+
+            self.enter(state, payload, StateData { world, data });
+            self.announce(
+                world,
+                match from {
+                    Some(from) => StateTransition::Switched { from, to },
+                    None => StateTransition::Pushed(to),
+                },
+            );
         }
     }
 
     /// Pauses the active state and pushes a new state onto the state stack.
-    fn push(&mut self, state: Box<dyn State<T, E>>, data: StateData<'_, T>) {
+    fn push(
+        &mut self,
+        state: Box<dyn State<T, E>>,
+        payload: Option<StatePayload>,
+        data: StateData<'_, T>,
+    ) {
         if self.running {
             let StateData { world, data } = data;
             if let Some(state) = self.state_stack.last_mut() {
                 state.on_pause(StateData { world, data });
             }
+            let name = state.name();
 
-            self.state_stack.push(state);
+            self.enter(state, payload, StateData { world, data });
+            self.announce(world, StateTransition::Pushed(name));
+        }
+    }
 
-            //State was just pushed, thus pop will always succeed
-            let state = self.state_stack.last_mut().unwrap();
-            state.on_start(StateData { world, data });
+    /// Builds the systems of a new state, pushes it onto the stack and starts it.
+    fn enter(
+        &mut self,
+        mut state: Box<dyn State<T, E>>,
+        payload: Option<StatePayload>,
+        data: StateData<'_, T>,
+    ) {
+        let StateData { world, data } = data;
+        self.dispatchers.push(build_dispatcher(&mut *state, world));
+        self.state_stack.push(state);
+
+        //State was just pushed, thus pop will always succeed
+        let state = self.state_stack.last_mut().unwrap();
+        match payload {
+            Some(payload) => state.on_start_with(StateData { world, data }, payload),
+            None => state.on_start(StateData { world, data }),
         }
     }
 
     /// Stops and removes the active state and un-pauses the next state on the
     /// stack (if any).
-    fn pop(&mut self, data: StateData<'_, T>) {
+    fn pop(&mut self, result: Option<StatePayload>, data: StateData<'_, T>) {
         if self.running {
             let StateData { world, data } = data;
             if let Some(mut state) = self.state_stack.pop() {
                 state.on_stop(StateData { world, data });
+                self.dispatchers.pop();
+                self.announce(world, StateTransition::Popped(state.name()));
             }
 
             if let Some(state) = self.state_stack.last_mut() {
-                state.on_resume(StateData { world, data });
+                match result {
+                    Some(result) => state.on_resume_with(StateData { world, data }, result),
+                    None => state.on_resume(StateData { world, data }),
+                }
             } else {
                 self.running = false;
             }
@@ -491,11 +820,53 @@ impl<'a, T, E: Send + Sync + 'static> StateMachine<'a, T, E> {
             let StateData { world, data } = data;
             while let Some(mut state) = self.state_stack.pop() {
                 state.on_stop(StateData { world, data });
+                self.announce(world, StateTransition::Popped(state.name()));
             }
+            self.dispatchers.clear();
 
             self.running = false;
         }
     }
+
+    /// Updates the `StateStack` and sends a transition event.
+    fn announce(&self, world: &mut World, transition: StateTransition) {
+        world
+            .res
+            .entry::<StateStack>()
+            .or_insert_with(StateStack::default)
+            .states = self.state_stack.iter().map(|state| state.name()).collect();
+        world
+            .res
+            .entry::<EventChannel<StateTransition>>()
+            .or_insert_with(EventChannel::new)
+            .single_write(transition);
+    }
+}
+
+impl<'a, T: 'static, E: Send + Sync + 'static> StateMachine<'a, T, E> {
+    /// Applies the transitions requested in the `TransQueue` since the last call, in order.
+    pub fn apply_queued(&mut self, data: StateData<'_, T>) {
+        let StateData { world, data } = data;
+        let queued = match world.res.try_fetch_mut::<TransQueue<T, E>>() {
+            Some(mut queue) => std::mem::replace(&mut queue.queue, Vec::new()),
+            None => return,
+        };
+        for trans in queued {
+            self.transition(trans(), StateData { world, data });
+        }
+    }
+}
+
+/// Builds and sets up the systems of a state.
+fn build_dispatcher<T, E: Send + Sync + 'static>(
+    state: &mut (dyn State<T, E> + '_),
+    world: &mut World,
+) -> Option<Dispatcher<'static, 'static>> {
+    state.build_dispatcher(world).map(|builder| {
+        let mut dispatcher = builder.build();
+        dispatcher.setup(&mut world.res);
+        dispatcher
+    })
 }
 
 #[cfg(test)]
@@ -540,4 +911,135 @@ mod tests {
         sm.update(StateData::new(&mut world, &mut ()));
         assert!(!sm.is_running());
     }
+
+    struct Counter;
+
+    impl<'a> crate::ecs::prelude::System<'a> for Counter {
+        type SystemData = crate::ecs::prelude::Write<'a, u32>;
+
+        fn run(&mut self, mut count: Self::SystemData) {
+            *count += 1;
+        }
+    }
+
+    struct Minigame;
+
+    impl State<(), ()> for Minigame {
+        fn build_dispatcher(
+            &mut self,
+            _: &mut World,
+        ) -> Option<DispatcherBuilder<'static, 'static>> {
+            Some(DispatcherBuilder::new().with(Counter, "counter", &[]))
+        }
+
+        fn update(&mut self, _: StateData<'_, ()>) -> Trans<(), ()> {
+            Trans::Pop
+        }
+    }
+
+    #[test]
+    fn state_dispatcher_runs_while_active() {
+        let mut world = World::new();
+
+        let mut sm = StateMachine::new(State1(7));
+        sm.start(StateData::new(&mut world, &mut ())).unwrap();
+        sm.transition(
+            Trans::Push(Box::new(Minigame)),
+            StateData::new(&mut world, &mut ()),
+        );
+        // The minigame runs its systems once, then pops itself.
+        sm.update(StateData::new(&mut world, &mut ()));
+        sm.update(StateData::new(&mut world, &mut ()));
+        assert_eq!(*world.read_resource::<u32>(), 1);
+    }
+
+    struct LevelSelection;
+    struct Menu;
+
+    impl State<(), ()> for LevelSelection {
+        fn name(&self) -> &'static str {
+            "level_selection"
+        }
+
+        fn update(&mut self, _: StateData<'_, ()>) -> Trans<(), ()> {
+            Trans::PopWith(StatePayload::new(3u8))
+        }
+    }
+
+    impl State<(), ()> for Menu {
+        fn name(&self) -> &'static str {
+            "menu"
+        }
+
+        fn on_resume_with(&mut self, data: StateData<'_, ()>, result: StatePayload) {
+            data.world.add_resource(result.downcast::<u8>().unwrap());
+        }
+    }
+
+    #[test]
+    fn pop_with_result() {
+        let mut world = World::new();
+
+        let mut sm = StateMachine::new(Menu);
+        sm.start(StateData::new(&mut world, &mut ())).unwrap();
+        sm.transition(
+            Trans::Push(Box::new(LevelSelection)),
+            StateData::new(&mut world, &mut ()),
+        );
+        sm.update(StateData::new(&mut world, &mut ()));
+        assert_eq!(*world.read_resource::<u8>(), 3);
+    }
+
+    #[test]
+    fn queued_transitions() {
+        let mut world = World::new();
+        world.add_resource(TransQueue::<(), ()>::default());
+
+        let mut sm = StateMachine::new(Menu);
+        sm.start(StateData::new(&mut world, &mut ())).unwrap();
+        {
+            let mut queue = world.write_resource::<TransQueue<(), ()>>();
+            queue.push(|| Trans::Push(Box::new(State2)));
+            queue.push(|| Trans::Pop);
+        }
+        sm.apply_queued(StateData::new(&mut world, &mut ()));
+        assert!(world.read_resource::<TransQueue<(), ()>>().is_empty());
+        assert!(sm.is_running());
+        assert_eq!(world.read_resource::<StateStack>().states(), &["menu"]);
+    }
+
+    #[test]
+    fn transitions_are_announced() {
+        let mut world = World::new();
+        let mut reader = world
+            .res
+            .entry::<EventChannel<StateTransition>>()
+            .or_insert_with(EventChannel::new)
+            .register_reader();
+
+        let mut sm = StateMachine::new(Menu);
+        sm.start(StateData::new(&mut world, &mut ())).unwrap();
+        sm.transition(
+            Trans::Push(Box::new(LevelSelection)),
+            StateData::new(&mut world, &mut ()),
+        );
+        assert_eq!(world.read_resource::<StateStack>().depth(), 2);
+        sm.update(StateData::new(&mut world, &mut ()));
+
+        let (menu, level_selection) = ("menu", "level_selection");
+        assert_eq!(world.read_resource::<StateStack>().states(), &[menu]);
+        let transitions = world
+            .read_resource::<EventChannel<StateTransition>>()
+            .read(&mut reader)
+            .cloned()
+            .collect::<Vec<_>>();
+        assert_eq!(
+            transitions,
+            vec![
+                StateTransition::Pushed(menu),
+                StateTransition::Pushed(level_selection),
+                StateTransition::Popped(level_selection),
+            ]
+        );
+    }
 }
@@ -23,4 +23,23 @@ where
     Ui(UiEvent),
     /// Events sent by the input system.
     Input(InputEvent<T>),
+    /// Events sent by the state machine when the state stack changes.
+    Transition(StateTransition),
+}
+
+/// A change of the state stack, sent by the `StateMachine` to `EventChannel<StateTransition>`
+/// with the names of the states involved.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StateTransition {
+    /// A state was pushed onto the stack, or started as the initial state.
+    Pushed(&'static str),
+    /// A state was popped off the stack.
+    Popped(&'static str),
+    /// The active state was replaced by another one.
+    Switched {
+        /// The state that was stopped.
+        from: &'static str,
+        /// The state that was started.
+        to: &'static str,
+    },
 }
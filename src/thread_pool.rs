@@ -0,0 +1,193 @@
+use std::sync::Arc;
+
+use log::{debug, warn};
+use rayon::ThreadPoolBuilder;
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "profiler")]
+use thread_profiler::register_thread_with_profiler;
+
+use crate::{core::ArcThreadPool, error::Error};
+
+/// The scheduling priority of the threads of a pool.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum ThreadPriority {
+    /// The same priority as the main thread.
+    Normal,
+    /// A lower priority, so that background work like asset loading doesn't take time from the
+    /// game. Only supported on Linux, other platforms use the normal priority.
+    Low,
+}
+
+/// The configuration of a thread pool, see `ApplicationBuilder::with_thread_pool` and
+/// `ApplicationBuilder::with_io_pool`.
+///
+/// ```rust
+/// use amethyst::{ThreadPoolConfig, ThreadPriority};
+///
+/// let dispatcher = ThreadPoolConfig::default().with_threads(4).pinned(true);
+/// let io = ThreadPoolConfig::io().with_priority(ThreadPriority::Low);
+/// ```
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ThreadPoolConfig {
+    /// The number of threads, one per logical core if `None`.
+    pub threads: Option<usize>,
+    /// The threads are named after it, followed by their index.
+    pub name: String,
+    /// Pins each thread to a core, in order. Only supported with the `thread_affinity` feature,
+    /// without which the threads aren't pinned.
+    pub pin_to_cores: bool,
+    /// The priority of the threads.
+    pub priority: ThreadPriority,
+}
+
+impl Default for ThreadPoolConfig {
+    fn default() -> Self {
+        ThreadPoolConfig {
+            threads: None,
+            name: "amethyst".to_owned(),
+            pin_to_cores: false,
+            priority: ThreadPriority::Normal,
+        }
+    }
+}
+
+impl ThreadPoolConfig {
+    /// The default configuration of the pool loading assets: two threads with a low priority.
+    pub fn io() -> Self {
+        ThreadPoolConfig {
+            threads: Some(2),
+            name: "amethyst io".to_owned(),
+            pin_to_cores: false,
+            priority: ThreadPriority::Low,
+        }
+    }
+
+    /// Sets the number of threads.
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = Some(threads);
+        self
+    }
+
+    /// Sets the name of the threads.
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    /// Sets whether the threads are pinned to cores.
+    pub fn pinned(mut self, pin_to_cores: bool) -> Self {
+        self.pin_to_cores = pin_to_cores;
+        self
+    }
+
+    /// Sets the priority of the threads.
+    pub fn with_priority(mut self, priority: ThreadPriority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Creates the pool.
+    pub fn build(&self) -> Result<ArcThreadPool, Error> {
+        let name = self.name.clone();
+        let cores = if self.pin_to_cores {
+            core_ids()
+        } else {
+            Vec::new()
+        };
+        if self.pin_to_cores && cores.is_empty() {
+            warn!("Can't pin the threads of `{}` to cores", self.name);
+        }
+        let priority = self.priority;
+
+        let mut builder = ThreadPoolBuilder::new()
+            .thread_name(move |index| format!("{} {}", name, index))
+            .start_handler(move |index| {
+                #[cfg(feature = "profiler")]
+                register_thread_with_profiler();
+                if !cores.is_empty() {
+                    pin_to_core(cores[index % cores.len()]);
+                }
+                if priority == ThreadPriority::Low {
+                    lower_priority();
+                }
+            });
+        if let Some(threads) = self.threads {
+            debug!(
+                "Creating thread pool `{}` with {} threads",
+                self.name, threads
+            );
+            builder = builder.num_threads(threads);
+        }
+        Ok(builder.build().map(Arc::new)?)
+    }
+}
+
+#[cfg(feature = "thread_affinity")]
+type CoreId = core_affinity::CoreId;
+
+// Never made, as there are no cores to pin to.
+#[cfg(not(feature = "thread_affinity"))]
+type CoreId = usize;
+
+#[cfg(feature = "thread_affinity")]
+fn core_ids() -> Vec<CoreId> {
+    core_affinity::get_core_ids().unwrap_or_default()
+}
+
+#[cfg(not(feature = "thread_affinity"))]
+fn core_ids() -> Vec<CoreId> {
+    warn!("Pinning threads to cores needs the `thread_affinity` feature");
+    Vec::new()
+}
+
+#[cfg(feature = "thread_affinity")]
+fn pin_to_core(core: CoreId) {
+    core_affinity::set_for_current(core);
+}
+
+#[cfg(not(feature = "thread_affinity"))]
+fn pin_to_core(_: CoreId) {}
+
+#[cfg(target_os = "linux")]
+fn lower_priority() {
+    // On Linux, the nice value is per thread, and `0` is the calling thread.
+    if unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, 10) } != 0 {
+        debug!("Failed to lower the priority of a thread");
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn lower_priority() {}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn pools_have_the_threads_and_names_of_their_config() {
+        let pool = ThreadPoolConfig::default()
+            .with_threads(3)
+            .with_name("test")
+            .build()
+            .unwrap();
+        assert_eq!(pool.current_num_threads(), 3);
+        let name = pool.install(|| thread::current().name().map(str::to_owned));
+        let index = name.as_ref().and_then(|name| {
+            let mut words = name.split(' ');
+            match (words.next(), words.next(), words.next()) {
+                (Some("test"), Some(index), None) => index.parse::<usize>().ok(),
+                _ => None,
+            }
+        });
+        assert!(index.map_or(false, |index| index < 3), "{:?}", name);
+
+        let pool = ThreadPoolConfig::io().build().unwrap();
+        assert_eq!(pool.current_num_threads(), 2);
+        let name = pool.install(|| thread::current().name().map(str::to_owned));
+        assert!(name.map_or(false, |name| name.starts_with("amethyst io ")));
+    }
+}